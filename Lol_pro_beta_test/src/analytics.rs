@@ -0,0 +1,53 @@
+use log::info;
+use tokio::sync::RwLock;
+use crate::compiler::{ProCompilationResult, ProCompilationStatus};
+
+/// What one run of the compiler did.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionStats {
+    pub files_compiled: usize,
+    pub files_failed: usize,
+    pub warnings: usize,
+}
+
+impl SessionStats {
+    pub fn new(results: &[ProCompilationResult]) -> Self {
+        let mut stats = Self { files_compiled: 0, files_failed: 0, warnings: 0 };
+        for result in results {
+            match &result.status {
+                ProCompilationStatus::Success { warnings, .. } => {
+                    stats.files_compiled += result.files.len();
+                    stats.warnings += warnings;
+                }
+                ProCompilationStatus::Failure { .. } => stats.files_failed += result.files.len(),
+            }
+        }
+        stats
+    }
+}
+
+/// The sessions of this process, kept in memory only; nothing is sent or
+/// written anywhere.
+#[derive(Debug, Default)]
+pub struct Analytics {
+    sessions: RwLock<Vec<SessionStats>>,
+}
+
+impl Analytics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub async fn record_compilation_session(&self, results: &[ProCompilationResult]) {
+        let stats = SessionStats::new(results);
+        let mut sessions = self.sessions.write().await;
+        info!(
+            "Session {}: {} files compiled, {} failed, {} warnings",
+            sessions.len() + 1,
+            stats.files_compiled,
+            stats.files_failed,
+            stats.warnings
+        );
+        sessions.push(stats);
+    }
+}
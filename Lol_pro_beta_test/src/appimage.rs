@@ -3,12 +3,12 @@ use std::fs;
 use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::os::unix::fs::PermissionsExt;
+use std::sync::Arc;
 use anyhow::{Context, Result};
 use tokio::sync::RwLock;
 use crate::language_support::Language;
-use crate::enterprise::EnterpriseManager;
+use crate::enterprise::{EnterpriseManager, Feature};
 use image::{ImageBuffer, Rgb};
-use base64::{Engine as _, engine::general_purpose};
 use sha2::{Sha256, Digest};
 use uuid::Uuid;
 use chrono::{DateTime, Utc};
@@ -135,7 +135,7 @@ impl ProAppImageBuilder {
                     consolidated_content.push_str("    }\n\n");
                 }
             }
-            consolidated_content.push_str("\n");
+            consolidated_content.push('\n');
         }
         
         // Add professional execution logic
@@ -197,7 +197,7 @@ impl ProAppImageBuilder {
             "[Desktop Entry]\n\
             Name={} Pro\n\
             Comment=Professional multi-language project generated by LOL PRO\n\
-            Exec={}\n\
+            Exec={}.py\n\
             Icon={}\n\
             Terminal=true\n\
             Type=Application\n\
@@ -206,7 +206,7 @@ impl ProAppImageBuilder {
             Version=1.0\n\
             GenericName=Professional Code Project\n",
             self.project_name,
-            format!("{}.py", self.project_name),
+            self.project_name,
             self.project_name
         );
         
@@ -391,13 +391,15 @@ impl ProAppImageBuilder {
 
     async fn generate_build_report(&self, appimage_path: &Path) -> Result<()> {
         let report_path = self.output_dir.join("build_report.json");
+        let enterprise_manager = self.enterprise_manager.read().await;
+        let licensed_features: Vec<Feature> = Feature::ALL.into_iter().filter(|feature| enterprise_manager.is_enabled(*feature)).collect();
         
         let report = serde_json::json!({
             "project_name": self.project_name,
             "build_id": self.build_id,
             "timestamp": self.timestamp.to_rfc3339(),
-            "source_files": {
-                language.name(): {
+            "source_files": self.source_files.iter().map(|(language, files)| {
+                (language.name().to_string(), serde_json::json!({
                     "count": files.len(),
                     "files": files.iter().map(|f| {
                         let content = fs::read_to_string(f).unwrap_or_default();
@@ -408,10 +410,11 @@ impl ProAppImageBuilder {
                             "hash": self.calculate_file_hash(&content)
                         })
                     }).collect::<Vec<_>>()
-                }
-            },
+                }))
+            }).collect::<serde_json::Map<_, _>>(),
             "output_path": appimage_path.to_string_lossy(),
-            "enterprise_features": true,
+            "licensed_to": enterprise_manager.license().map(|license| license.customer.clone()),
+            "enterprise_features": licensed_features,
             "build_tool": "LOL PRO - Enterprise Edition",
             "version": "1.0.0"
         });
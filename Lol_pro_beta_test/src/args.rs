@@ -0,0 +1,43 @@
+use clap::Parser;
+use std::path::PathBuf;
+
+#[derive(Parser, Debug, Clone)]
+#[command(
+    name = "lol-pro",
+    about = "Professional Multi-language Code Compiler & AppImage Creator - Enterprise Edition",
+    version,
+    author
+)]
+pub struct ProArgs {
+    /// Project directory to compile or create a Professional AppImage from
+    #[arg(value_name = "PROJECT_PATH", default_value = ".")]
+    pub project_path: PathBuf,
+
+    /// Create a Professional AppImage with this name instead of compiling
+    #[arg(long, value_name = "APP_NAME")]
+    pub name: Option<String>,
+
+    /// Verify the installed license and turn on the features it grants
+    #[arg(long)]
+    pub enterprise: bool,
+
+    /// Number of files to compile at once
+    #[arg(short, long, default_value_t = num_cpus::get())]
+    pub jobs: usize,
+
+    /// Recompile whenever a source file changes
+    #[arg(short, long)]
+    pub watch: bool,
+
+    /// Show every file and the compilers' output
+    #[arg(short, long)]
+    pub verbose: bool,
+
+    /// Custom compiler flags for C
+    #[arg(long, value_name = "FLAGS", allow_hyphen_values = true)]
+    pub cflags: Option<String>,
+
+    /// Custom compiler flags for C++
+    #[arg(long, value_name = "FLAGS", allow_hyphen_values = true)]
+    pub cxxflags: Option<String>,
+}
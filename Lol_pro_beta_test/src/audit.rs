@@ -0,0 +1,195 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Sha256, Digest};
+use crate::compiler::{ProCompilationResult, ProCompilationStatus};
+
+/// `previous` of the first entry.
+const GENESIS: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Where audit entries go: `LOL_PRO_AUDIT_LOG` set to a path, or `syslog`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuditDestination {
+    File(PathBuf),
+    Syslog,
+}
+
+impl AuditDestination {
+    pub fn from_env() -> Option<Self> {
+        match std::env::var("LOL_PRO_AUDIT_LOG").ok()?.as_str() {
+            "" => None,
+            "syslog" => Some(AuditDestination::Syslog),
+            path => Some(AuditDestination::File(PathBuf::from(path))),
+        }
+    }
+}
+
+/// Who ran which build and how it went.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BuildRecord {
+    pub timestamp: DateTime<Utc>,
+    pub user: String,
+    pub host: String,
+    /// The command line, flags included
+    pub command: Vec<String>,
+    pub project: PathBuf,
+    pub commit: Option<String>,
+    pub success: bool,
+    pub files_compiled: usize,
+    pub files_failed: usize,
+    pub failed_languages: Vec<String>,
+}
+
+impl BuildRecord {
+    pub fn new(project_path: &Path, results: &[ProCompilationResult]) -> Self {
+        let mut record = Self {
+            timestamp: Utc::now(),
+            user: std::env::var("USER").or_else(|_| std::env::var("USERNAME")).unwrap_or_else(|_| "unknown".to_string()),
+            host: command_output(&mut Command::new("hostname")).unwrap_or_else(|| "unknown".to_string()),
+            command: std::env::args().collect(),
+            project: project_path.canonicalize().unwrap_or_else(|_| project_path.to_path_buf()),
+            commit: command_output(Command::new("git").args(["rev-parse", "HEAD"]).current_dir(project_path)),
+            success: true,
+            files_compiled: 0,
+            files_failed: 0,
+            failed_languages: Vec::new(),
+        };
+        for result in results {
+            match &result.status {
+                ProCompilationStatus::Success { .. } => record.files_compiled += result.files.len(),
+                ProCompilationStatus::Failure { .. } => {
+                    record.success = false;
+                    record.files_failed += result.files.len();
+                    record.failed_languages.push(result.language.name().to_string());
+                }
+            }
+        }
+        record
+    }
+}
+
+/// A line of the log: the record, chained to the line before by hashing
+/// that line's hash together with this record.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub sequence: u64,
+    #[serde(flatten)]
+    pub record: BuildRecord,
+    pub previous: String,
+    pub hash: String,
+}
+
+impl AuditEntry {
+    fn chain(sequence: u64, record: BuildRecord, previous: String) -> Result<Self> {
+        let hash = entry_hash(sequence, &record, &previous)?;
+        Ok(Self { sequence, record, previous, hash })
+    }
+}
+
+fn entry_hash(sequence: u64, record: &BuildRecord, previous: &str) -> Result<String> {
+    let mut hasher = Sha256::new();
+    hasher.update(previous.as_bytes());
+    hasher.update(sequence.to_be_bytes());
+    hasher.update(serde_json::to_vec(record)?);
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+fn command_output(command: &mut Command) -> Option<String> {
+    let output = command.output().ok().filter(|output| output.status.success())?;
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string()).filter(|text| !text.is_empty())
+}
+
+/// Checks every link of the chain in `contents`, returning the last entry.
+/// An edited, removed or reordered line breaks the chain where it happened.
+pub fn verify_chain(contents: &str) -> Result<Option<AuditEntry>> {
+    let mut last: Option<AuditEntry> = None;
+    for (index, line) in contents.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+        let entry: AuditEntry = serde_json::from_str(line).with_context(|| format!("line {} is not an audit entry", index + 1))?;
+        let (sequence, previous) = match &last {
+            Some(last) => (last.sequence + 1, last.hash.as_str()),
+            None => (1, GENESIS),
+        };
+        if entry.sequence != sequence || entry.previous != previous || entry.hash != entry_hash(entry.sequence, &entry.record, &entry.previous)? {
+            anyhow::bail!("the chain breaks at line {} (entry {}); the log has been altered", index + 1, entry.sequence);
+        }
+        last = Some(entry);
+    }
+    Ok(last)
+}
+
+/// The tamper-evident record of builds, kept when the license includes it.
+#[derive(Debug, Clone)]
+pub struct AuditLog {
+    destination: AuditDestination,
+}
+
+impl AuditLog {
+    pub fn new(destination: AuditDestination) -> Self {
+        Self { destination }
+    }
+
+    /// Syslog cannot be read back, so the chain's head is kept locally.
+    fn head_path() -> Result<PathBuf> {
+        let dir = dirs::state_dir().or_else(dirs::data_local_dir).context("No directory for the audit chain's state")?;
+        Ok(dir.join("lol-pro").join("audit-head.json"))
+    }
+
+    /// Appends `record`, after checking that the log is intact.
+    pub fn append(&self, record: BuildRecord) -> Result<AuditEntry> {
+        match &self.destination {
+            AuditDestination::File(path) => {
+                let contents = match fs::read_to_string(path) {
+                    Ok(contents) => contents,
+                    Err(error) if error.kind() == std::io::ErrorKind::NotFound => String::new(),
+                    Err(error) => return Err(error).with_context(|| format!("Failed to read audit log {}", path.display())),
+                };
+                let last = verify_chain(&contents).with_context(|| format!("Audit log {} failed verification", path.display()))?;
+                let entry = match last {
+                    Some(last) => AuditEntry::chain(last.sequence + 1, record, last.hash)?,
+                    None => AuditEntry::chain(1, record, GENESIS.to_string())?,
+                };
+                if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+                    fs::create_dir_all(parent)?;
+                }
+                let mut file = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .with_context(|| format!("Failed to open audit log {}", path.display()))?;
+                writeln!(file, "{}", serde_json::to_string(&entry)?)?;
+                file.sync_data()?;
+                Ok(entry)
+            }
+            AuditDestination::Syslog => {
+                let head_path = Self::head_path()?;
+                let last: Option<AuditEntry> = fs::read_to_string(&head_path).ok().and_then(|head| serde_json::from_str(&head).ok());
+                let entry = match last {
+                    Some(last) => AuditEntry::chain(last.sequence + 1, record, last.hash)?,
+                    None => AuditEntry::chain(1, record, GENESIS.to_string())?,
+                };
+                let status = Command::new("logger")
+                    .args(["--tag", "lol-pro", "--priority", "auth.info", "--"])
+                    .arg(serde_json::to_string(&entry)?)
+                    .status()
+                    .context("Failed to run logger for the audit log")?;
+                if !status.success() {
+                    anyhow::bail!("logger failed to write the audit entry to syslog");
+                }
+                fs::create_dir_all(head_path.parent().unwrap())?;
+                fs::write(&head_path, serde_json::to_string(&entry)?)?;
+                Ok(entry)
+            }
+        }
+    }
+
+    /// Records the build that produced `results`.
+    pub fn record(&self, project_path: &Path, results: &[ProCompilationResult]) -> Result<()> {
+        let entry = self.append(BuildRecord::new(project_path, results))?;
+        println!("📝 Audit entry {} recorded", entry.sequence);
+        Ok(())
+    }
+}
@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use anyhow::{Context, Result};
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+use crate::args::ProArgs;
+use crate::config::ProConfig;
+use crate::language_support::Language;
+
+/// How one language's files went.
+#[derive(Debug, Clone)]
+pub struct ProCompilationResult {
+    pub language: Language,
+    pub files: Vec<PathBuf>,
+    pub status: ProCompilationStatus,
+}
+
+#[derive(Debug, Clone)]
+pub enum ProCompilationStatus {
+    Success { output: String, warnings: usize },
+    Failure { error: String, suggestions: Vec<String> },
+}
+
+pub struct ProCompiler {
+    config: ProConfig,
+    jobs: usize,
+}
+
+fn split_flags(flags: Option<&str>) -> Vec<String> {
+    flags.into_iter().flat_map(str::split_whitespace).map(str::to_string).collect()
+}
+
+/// The compiler `language` runs and every flag it is given: those in its
+/// `language_settings`, then `--cflags` or `--cxxflags`.
+pub fn compiler_and_flags(config: &ProConfig, language: Language, args: &ProArgs) -> (String, Vec<String>) {
+    let (compiler, mut flags) = config.compiler_for(language);
+    match language {
+        Language::C => flags.extend(split_flags(args.cflags.as_deref())),
+        Language::Cpp => flags.extend(split_flags(args.cxxflags.as_deref())),
+        _ => {}
+    }
+    (compiler, flags)
+}
+
+fn count_warnings(output: &str) -> usize {
+    output.lines().filter(|line| line.to_lowercase().contains("warning:")).count()
+}
+
+impl ProCompiler {
+    pub fn new(config: ProConfig, jobs: usize) -> Self {
+        Self { config, jobs: jobs.max(1) }
+    }
+
+    /// Compiles every language's files, at most `jobs` files at a time, and
+    /// reports each language in name order.
+    pub async fn compile_all(
+        &self,
+        source_files: HashMap<Language, Vec<PathBuf>>,
+        multi_progress: &MultiProgress,
+        progress_style: &ProgressStyle,
+        args: &ProArgs,
+    ) -> Result<Vec<ProCompilationResult>> {
+        let semaphore = Arc::new(Semaphore::new(self.jobs));
+        let mut languages: Vec<(Language, Vec<PathBuf>)> = source_files.into_iter().collect();
+        languages.sort_by_key(|(language, _)| *language);
+
+        let mut results = Vec::new();
+        for (language, files) in languages {
+            let (compiler, flags) = compiler_and_flags(&self.config, language, args);
+            let output_dir = args.project_path.join(&self.config.output_directory).join(language.config_key());
+            std::fs::create_dir_all(&output_dir).with_context(|| format!("Failed to create {}", output_dir.display()))?;
+
+            let progress_bar = multi_progress.add(ProgressBar::new(files.len() as u64));
+            progress_bar.set_style(progress_style.clone());
+            progress_bar.set_message(format!("Compiling {}", language.name()));
+
+            let mut tasks = JoinSet::new();
+            for file in &files {
+                let command = language.compile_command(&compiler, file, &output_dir, &flags);
+                let semaphore = Arc::clone(&semaphore);
+                let progress_bar = progress_bar.clone();
+                let file = file.clone();
+                tasks.spawn(async move {
+                    let _permit = semaphore.acquire_owned().await;
+                    let output = tokio::process::Command::from(command).output().await;
+                    progress_bar.inc(1);
+                    (file, output)
+                });
+            }
+
+            let mut output = String::new();
+            let mut errors = Vec::new();
+            let mut missing = false;
+            while let Some(task) = tasks.join_next().await {
+                let (file, result) = task.context("A compilation task panicked")?;
+                match result {
+                    Ok(result) => {
+                        let text = String::from_utf8_lossy(&result.stdout).into_owned() + &String::from_utf8_lossy(&result.stderr);
+                        if result.status.success() {
+                            output.push_str(&text);
+                        } else {
+                            errors.push(format!("{}:\n{}", file.display(), text.trim_end()));
+                        }
+                    }
+                    Err(error) if error.kind() == std::io::ErrorKind::NotFound => missing = true,
+                    Err(error) => errors.push(format!("{}: failed to run {}: {}", file.display(), compiler, error)),
+                }
+            }
+
+            let status = if missing {
+                progress_bar.finish_with_message(format!("{}: {} is not installed", language.name(), compiler));
+                ProCompilationStatus::Failure {
+                    error: format!("Compiler '{}' not found", compiler),
+                    suggestions: vec![language.install_hint().to_string()],
+                }
+            } else if errors.is_empty() {
+                progress_bar.finish_with_message(format!("Compiled {} files", language.name()));
+                ProCompilationStatus::Success { warnings: count_warnings(&output), output: output.trim_end().to_string() }
+            } else {
+                progress_bar.finish_with_message(format!("{} failed", language.name()));
+                ProCompilationStatus::Failure { error: errors.join("\n"), suggestions: Vec::new() }
+            };
+            results.push(ProCompilationResult { language, files, status });
+        }
+        Ok(results)
+    }
+}
@@ -0,0 +1,71 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use crate::language_support::Language;
+
+/// Settings for one language.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LanguageSettings {
+    /// The compiler to run instead of the language's default
+    #[serde(default)]
+    pub compiler_path: Option<String>,
+    #[serde(default)]
+    pub flags: Vec<String>,
+}
+
+/// lol-pro's settings, from `~/.config/lol-pro/config.json`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProConfig {
+    /// Where compiled outputs go, relative to the project
+    #[serde(default = "default_output_directory")]
+    pub output_directory: String,
+
+    /// Directories never searched for sources
+    #[serde(default = "default_exclude_directories")]
+    pub exclude_directories: Vec<String>,
+
+    #[serde(default)]
+    pub language_settings: HashMap<String, LanguageSettings>,
+}
+
+fn default_output_directory() -> String {
+    "build".to_string()
+}
+
+fn default_exclude_directories() -> Vec<String> {
+    ["target", "build", "node_modules", "__pycache__", "dist"].iter().map(|dir| dir.to_string()).collect()
+}
+
+impl Default for ProConfig {
+    fn default() -> Self {
+        Self {
+            output_directory: default_output_directory(),
+            exclude_directories: default_exclude_directories(),
+            language_settings: HashMap::new(),
+        }
+    }
+}
+
+impl ProConfig {
+    pub fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("lol-pro").join("config.json"))
+    }
+
+    /// The saved configuration, or the defaults when there is none.
+    pub fn load() -> Result<Self> {
+        let Some(path) = Self::path().filter(|path| path.is_file()) else {
+            return Ok(Self::default());
+        };
+        let content = fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+        serde_json::from_str(&content).with_context(|| format!("Failed to parse {}", path.display()))
+    }
+
+    /// The compiler for `language` and the flags to pass it.
+    pub fn compiler_for(&self, language: Language) -> (String, Vec<String>) {
+        let settings = self.language_settings.get(language.config_key()).cloned().unwrap_or_default();
+        let compiler = settings.compiler_path.unwrap_or_else(|| language.default_compiler().to_string());
+        (compiler, settings.flags)
+    }
+}
@@ -0,0 +1,272 @@
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+use anyhow::{Context, Result};
+use base64::{Engine as _, engine::general_purpose};
+use chrono::{NaiveDate, Utc};
+use colored::*;
+use ed25519_dalek::{Signature, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use crate::args::ProArgs;
+use crate::audit::{AuditDestination, AuditLog};
+use crate::config::ProConfig;
+use crate::language_support::Language;
+use crate::policy::{BuildRequest, Policy};
+
+/// The key licenses are signed with, base64-encoded, baked in at release
+/// build time. Builds without it accept no license.
+const LICENSE_PUBLIC_KEY: Option<&str> = option_env!("LOL_PRO_LICENSE_PUBLIC_KEY");
+
+/// License tiers, lowest first; each includes the features of those below.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Tier {
+    Professional,
+    Business,
+    Enterprise,
+}
+
+impl fmt::Display for Tier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Tier::Professional => "Professional",
+            Tier::Business => "Business",
+            Tier::Enterprise => "Enterprise",
+        })
+    }
+}
+
+/// Features a license unlocks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Feature {
+    RemoteCache,
+    AuditLog,
+    Policies,
+    DistributedBuilds,
+}
+
+impl Feature {
+    pub const ALL: [Feature; 4] = [Feature::RemoteCache, Feature::AuditLog, Feature::Policies, Feature::DistributedBuilds];
+
+    /// The lowest tier that includes the feature.
+    pub fn tier(self) -> Tier {
+        match self {
+            Feature::RemoteCache | Feature::AuditLog => Tier::Business,
+            Feature::Policies | Feature::DistributedBuilds => Tier::Enterprise,
+        }
+    }
+}
+
+impl fmt::Display for Feature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Feature::RemoteCache => "Remote cache",
+            Feature::AuditLog => "Audit log",
+            Feature::Policies => "Policy enforcement",
+            Feature::DistributedBuilds => "Distributed builds",
+        })
+    }
+}
+
+/// What a license grants, as signed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct License {
+    pub customer: String,
+    pub tier: Tier,
+    /// Last day the license is valid
+    pub expires: NaiveDate,
+    /// Features granted on top of the tier's
+    #[serde(default)]
+    pub features: Vec<Feature>,
+}
+
+/// A license file: the license as base64 JSON, and the Ed25519 signature of
+/// exactly those bytes, so no JSON canonicalization is needed to verify it.
+#[derive(Debug, Deserialize)]
+struct SignedLicense {
+    payload: String,
+    signature: String,
+}
+
+impl License {
+    pub fn has(&self, feature: Feature) -> bool {
+        self.tier >= feature.tier() || self.features.contains(&feature)
+    }
+
+    /// Checks `document`'s signature against `key` and its expiry against
+    /// `today`. Nothing is contacted; verification works offline.
+    pub fn verify(document: &str, key: &VerifyingKey, today: NaiveDate) -> Result<License> {
+        let signed: SignedLicense = serde_json::from_str(document).context("not a lol-pro license file")?;
+        let payload = general_purpose::STANDARD.decode(signed.payload.trim()).context("the payload is not base64")?;
+        let signature = general_purpose::STANDARD.decode(signed.signature.trim()).context("the signature is not base64")?;
+        let signature = Signature::from_slice(&signature).context("the signature is not an Ed25519 signature")?;
+        key.verify_strict(&payload, &signature)
+            .map_err(|_| anyhow::anyhow!("the signature does not match; the file was edited or is not a lol-pro license"))?;
+        let license: License = serde_json::from_slice(&payload).context("the signed license is malformed")?;
+        if license.expires < today {
+            anyhow::bail!("the license for {} expired on {}; renew it to keep using enterprise features", license.customer, license.expires);
+        }
+        Ok(license)
+    }
+
+    /// Where the license is read from: `LOL_PRO_LICENSE`, else
+    /// `~/.config/lol-pro/license.json`.
+    pub fn path() -> Option<PathBuf> {
+        std::env::var_os("LOL_PRO_LICENSE")
+            .map(PathBuf::from)
+            .or_else(|| dirs::config_dir().map(|dir| dir.join("lol-pro").join("license.json")))
+    }
+
+    /// The installed license, verified. `LOL_PRO_LICENSE_KEY` may hold the
+    /// license file's contents instead, for CI secrets.
+    pub fn load() -> Result<License> {
+        let encoded_key = LICENSE_PUBLIC_KEY.context("This build of lol-pro cannot verify licenses; use an official release")?;
+        let key_bytes: [u8; 32] = general_purpose::STANDARD
+            .decode(encoded_key)
+            .ok()
+            .and_then(|bytes| bytes.try_into().ok())
+            .context("The built-in license key is malformed")?;
+        let key = VerifyingKey::from_bytes(&key_bytes).context("The built-in license key is malformed")?;
+
+        let (document, source) = match std::env::var("LOL_PRO_LICENSE_KEY") {
+            Ok(document) => (document, "LOL_PRO_LICENSE_KEY".to_string()),
+            Err(_) => {
+                let path = Self::path().context("No license found: set LOL_PRO_LICENSE to the license file")?;
+                let document = fs::read_to_string(&path).with_context(|| {
+                    format!("No license found at {}; set LOL_PRO_LICENSE to the license file", path.display())
+                })?;
+                (document, path.display().to_string())
+            }
+        };
+        Self::verify(&document, &key, Utc::now().date_naive()).with_context(|| format!("Invalid license in {}", source))
+    }
+}
+
+/// Holds the verified license and decides which enterprise features run.
+#[derive(Debug, Default)]
+pub struct EnterpriseManager {
+    license: Option<License>,
+    audit_log: Option<AuditLog>,
+    /// The policy builds are checked against, and the file it came from
+    policy: Option<(PathBuf, Policy)>,
+}
+
+impl EnterpriseManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads and verifies the license for `--enterprise`; an expired or
+    /// invalid one stops the build with the reason.
+    pub fn activate_enterprise_features(&mut self, args: &ProArgs) -> Result<()> {
+        let license = License::load()?;
+        println!("🔑 Licensed to {} ({}, until {})", license.customer.bold(), license.tier, license.expires);
+        if args.verbose {
+            for feature in Feature::ALL {
+                let state = if license.has(feature) { "enabled".green() } else { format!("needs {}", feature.tier()).dimmed() };
+                println!("   {}: {}", feature, state);
+            }
+        }
+        if let Some(destination) = AuditDestination::from_env() {
+            self.require_licensed(&license, Feature::AuditLog)?;
+            match &destination {
+                AuditDestination::File(path) => println!("📝 Audit log: {}", path.display()),
+                AuditDestination::Syslog => println!("📝 Audit log: syslog"),
+            }
+            self.audit_log = Some(AuditLog::new(destination));
+        }
+        if let Some(path) = Policy::path() {
+            self.require_licensed(&license, Feature::Policies)?;
+            println!("📜 Policy: {}", path.display());
+            self.policy = Some((path.clone(), Policy::load(&path)?));
+        }
+        self.license = Some(license);
+        Ok(())
+    }
+
+    /// Where builds are recorded, when `LOL_PRO_AUDIT_LOG` is set and the
+    /// license includes the audit log.
+    pub fn audit_log(&self) -> Option<&AuditLog> {
+        self.audit_log.as_ref()
+    }
+
+    /// Checks the build about to compile `languages` against the policy,
+    /// when there is one, before anything is compiled.
+    pub fn enforce_policy(&self, languages: Vec<Language>, config: &ProConfig, args: &ProArgs) -> Result<()> {
+        match &self.policy {
+            Some((path, policy)) => policy.enforce(&BuildRequest::new(languages, config, args), path),
+            None => Ok(()),
+        }
+    }
+
+    pub fn license(&self) -> Option<&License> {
+        self.license.as_ref()
+    }
+
+    pub fn is_enabled(&self, feature: Feature) -> bool {
+        self.license.as_ref().is_some_and(|license| license.has(feature))
+    }
+
+    fn require_licensed(&self, license: &License, feature: Feature) -> Result<()> {
+        if !license.has(feature) {
+            anyhow::bail!("{} needs the {} tier; the license for {} is {}", feature, feature.tier(), license.customer, license.tier);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::{Signer, SigningKey};
+
+    /// A fixed key, so the signatures are the same on every run.
+    fn test_key() -> SigningKey {
+        SigningKey::from_bytes(&[7; 32])
+    }
+
+    fn license_file(key: &SigningKey, payload: &str) -> String {
+        let signature = key.sign(payload.as_bytes());
+        serde_json::json!({
+            "payload": general_purpose::STANDARD.encode(payload),
+            "signature": general_purpose::STANDARD.encode(signature.to_bytes()),
+        })
+        .to_string()
+    }
+
+    #[test]
+    fn test_verify_license() {
+        let key = test_key();
+        let verifying_key = key.verifying_key();
+        let today = NaiveDate::from_ymd_opt(2026, 6, 1).unwrap();
+        let payload = r#"{"customer": "Acme", "tier": "business", "expires": "2026-12-31", "features": ["policies"]}"#;
+
+        let license = License::verify(&license_file(&key, payload), &verifying_key, today).unwrap();
+        assert_eq!((license.customer.as_str(), license.tier), ("Acme", Tier::Business));
+        assert!(license.has(Feature::AuditLog) && license.has(Feature::Policies));
+        assert!(!license.has(Feature::DistributedBuilds));
+
+        let expired = License::verify(&license_file(&key, payload), &verifying_key, NaiveDate::from_ymd_opt(2027, 1, 1).unwrap());
+        assert!(expired.unwrap_err().to_string().contains("expired on 2026-12-31"));
+
+        // The signature covers the payload's bytes, so raising the tier breaks it
+        let signed: serde_json::Value = serde_json::from_str(&license_file(&key, payload)).unwrap();
+        let tampered = serde_json::json!({
+            "payload": general_purpose::STANDARD.encode(payload.replace("business", "enterprise")),
+            "signature": signed["signature"],
+        });
+        let error = License::verify(&tampered.to_string(), &verifying_key, today).unwrap_err();
+        assert!(error.to_string().contains("signature does not match"));
+
+        let other_key = SigningKey::from_bytes(&[8; 32]);
+        assert!(License::verify(&license_file(&other_key, payload), &verifying_key, today).is_err());
+
+        let unsigned = serde_json::json!({ "payload": general_purpose::STANDARD.encode(payload) });
+        let error = License::verify(&unsigned.to_string(), &verifying_key, today).unwrap_err();
+        assert!(error.to_string().contains("not a lol-pro license file"));
+        let empty_signature = serde_json::json!({ "payload": general_purpose::STANDARD.encode(payload), "signature": "" });
+        let error = License::verify(&empty_signature.to_string(), &verifying_key, today).unwrap_err();
+        assert!(error.to_string().contains("not an Ed25519 signature"));
+    }
+}
@@ -0,0 +1,39 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use anyhow::Result;
+use walkdir::WalkDir;
+use crate::config::ProConfig;
+use crate::language_support::Language;
+
+pub struct ProFileDetector;
+
+impl ProFileDetector {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// The project's source files by language, each list sorted. Hidden
+    /// directories and `exclude_directories` are skipped.
+    pub fn detect_files(&self, project_path: &Path, config: &ProConfig) -> Result<HashMap<Language, Vec<PathBuf>>> {
+        let mut files: HashMap<Language, Vec<PathBuf>> = HashMap::new();
+        let walker = WalkDir::new(project_path).into_iter().filter_entry(|entry| {
+            let name = entry.file_name().to_string_lossy();
+            entry.depth() == 0
+                || !entry.file_type().is_dir()
+                || !(name.starts_with('.') || config.exclude_directories.iter().any(|excluded| *excluded == name))
+        });
+        for entry in walker {
+            let entry = entry?;
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            if let Some(language) = Language::from_path(entry.path()) {
+                files.entry(language).or_default().push(entry.into_path());
+            }
+        }
+        for list in files.values_mut() {
+            list.sort();
+        }
+        Ok(files)
+    }
+}
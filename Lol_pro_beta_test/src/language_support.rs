@@ -0,0 +1,169 @@
+use std::path::Path;
+use std::process::Command;
+
+/// The languages lol-pro compiles or syntax-checks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Language {
+    C,
+    Cpp,
+    Rust,
+    Go,
+    Java,
+    Python,
+    JavaScript,
+    TypeScript,
+    Ruby,
+    Perl,
+    Lua,
+    Shell,
+}
+
+impl Language {
+    pub const ALL: [Language; 12] = [
+        Language::C,
+        Language::Cpp,
+        Language::Rust,
+        Language::Go,
+        Language::Java,
+        Language::Python,
+        Language::JavaScript,
+        Language::TypeScript,
+        Language::Ruby,
+        Language::Perl,
+        Language::Lua,
+        Language::Shell,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Language::C => "C",
+            Language::Cpp => "C++",
+            Language::Rust => "Rust",
+            Language::Go => "Go",
+            Language::Java => "Java",
+            Language::Python => "Python",
+            Language::JavaScript => "JavaScript",
+            Language::TypeScript => "TypeScript",
+            Language::Ruby => "Ruby",
+            Language::Perl => "Perl",
+            Language::Lua => "Lua",
+            Language::Shell => "Shell",
+        }
+    }
+
+    /// The key of the language's settings in the configuration.
+    pub fn config_key(&self) -> &'static str {
+        match self {
+            Language::C => "c",
+            Language::Cpp => "cpp",
+            Language::Rust => "rust",
+            Language::Go => "go",
+            Language::Java => "java",
+            Language::Python => "python",
+            Language::JavaScript => "javascript",
+            Language::TypeScript => "typescript",
+            Language::Ruby => "ruby",
+            Language::Perl => "perl",
+            Language::Lua => "lua",
+            Language::Shell => "shell",
+        }
+    }
+
+    pub fn extensions(&self) -> &'static [&'static str] {
+        match self {
+            Language::C => &["c"],
+            Language::Cpp => &["cpp", "cc", "cxx"],
+            Language::Rust => &["rs"],
+            Language::Go => &["go"],
+            Language::Java => &["java"],
+            Language::Python => &["py"],
+            Language::JavaScript => &["js", "mjs"],
+            Language::TypeScript => &["ts"],
+            Language::Ruby => &["rb"],
+            Language::Perl => &["pl"],
+            Language::Lua => &["lua"],
+            Language::Shell => &["sh"],
+        }
+    }
+
+    pub fn from_path(path: &Path) -> Option<Language> {
+        let extension = path.extension()?.to_str()?;
+        Language::ALL.into_iter().find(|language| language.extensions().contains(&extension))
+    }
+
+    /// The program that compiles or checks the language.
+    pub fn default_compiler(&self) -> &'static str {
+        match self {
+            Language::C => "gcc",
+            Language::Cpp => "g++",
+            Language::Rust => "rustc",
+            Language::Go => "go",
+            Language::Java => "javac",
+            Language::Python => "python3",
+            Language::JavaScript => "node",
+            Language::TypeScript => "tsc",
+            Language::Ruby => "ruby",
+            Language::Perl => "perl",
+            Language::Lua => "luac",
+            Language::Shell => "bash",
+        }
+    }
+
+    /// How to install the compiler, for the failure's suggestions.
+    pub fn install_hint(&self) -> &'static str {
+        match self {
+            Language::C | Language::Cpp => "Install build-essential (or gcc/g++)",
+            Language::Rust => "Install Rust from https://rustup.rs",
+            Language::Go => "Install Go from https://go.dev/dl",
+            Language::Java => "Install a JDK, such as openjdk-17-jdk",
+            Language::Python => "Install python3",
+            Language::JavaScript => "Install Node.js",
+            Language::TypeScript => "Install TypeScript with `npm install -g typescript`",
+            Language::Ruby => "Install ruby",
+            Language::Perl => "Install perl",
+            Language::Lua => "Install lua",
+            Language::Shell => "Install bash",
+        }
+    }
+
+    /// The command compiling `file` with `compiler`, writing anything it
+    /// produces under `output_dir`. Interpreted languages are only checked.
+    pub fn compile_command(&self, compiler: &str, file: &Path, output_dir: &Path, flags: &[String]) -> Command {
+        let stem = file.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+        let mut command = Command::new(compiler);
+        match self {
+            Language::C | Language::Cpp => {
+                command.arg("-c").arg(file).arg("-o").arg(output_dir.join(format!("{}.o", stem)));
+            }
+            Language::Rust => {
+                command.arg("--crate-type=lib").arg("--emit=metadata").arg("--out-dir").arg(output_dir).arg(file);
+            }
+            Language::Go => {
+                command.arg("vet").arg(file);
+            }
+            Language::Java => {
+                command.arg("-d").arg(output_dir).arg(file);
+            }
+            Language::Python => {
+                command.arg("-m").arg("py_compile").arg(file);
+            }
+            Language::JavaScript => {
+                command.arg("--check").arg(file);
+            }
+            Language::TypeScript => {
+                command.arg("--noEmit").arg(file);
+            }
+            Language::Ruby | Language::Perl => {
+                command.arg("-c").arg(file);
+            }
+            Language::Lua => {
+                command.arg("-p").arg(file);
+            }
+            Language::Shell => {
+                command.arg("-n").arg(file);
+            }
+        }
+        command.args(flags);
+        command
+    }
+}
@@ -2,7 +2,9 @@ use anyhow::{Context, Result};
 use clap::Parser;
 use colored::*;
 use indicatif::{MultiProgress, ProgressStyle};
-use log::{info, warn, error};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
@@ -15,6 +17,8 @@ mod appimage;
 mod enterprise;
 mod watcher;
 mod analytics;
+mod audit;
+mod policy;
 
 use compiler::ProCompiler;
 use config::ProConfig;
@@ -24,6 +28,7 @@ use appimage::ProAppImageBuilder;
 use enterprise::EnterpriseManager;
 use watcher::FileWatcher;
 use analytics::Analytics;
+use language_support::Language;
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -74,7 +79,7 @@ async fn main() -> Result<()> {
     
     // Detect source files with professional detection
     let file_detector = ProFileDetector::new();
-    let source_files = file_detector.detect_files(&args.project_path, &args, &config)?;
+    let source_files = file_detector.detect_files(&args.project_path, &config)?;
 
     if source_files.is_empty() {
         println!("{} No source files found to compile.", "⚠️".yellow());
@@ -84,6 +89,10 @@ async fn main() -> Result<()> {
     // Display detected files with enhanced information
     display_enhanced_file_info(&source_files, &args);
 
+    // Refuse builds the organization's policy forbids
+    let languages = source_files.keys().copied().collect();
+    enterprise_manager.read().await.enforce_policy(languages, &config, &args)?;
+
     // Initialize progress bars with professional styling
     let multi_progress = MultiProgress::new();
     let progress_style = ProgressStyle::default_bar()
@@ -92,13 +101,16 @@ async fn main() -> Result<()> {
         .progress_chars("█▉▊▋▌▍▎▏");
 
     // Compile files with professional compiler
-    let compiler = ProCompiler::new(config, args.jobs, enterprise_manager.clone());
+    let compiler = ProCompiler::new(config, args.jobs);
     let results = compiler
         .compile_all(source_files, &multi_progress, &progress_style, &args)
         .await?;
 
     // Record analytics
     analytics.record_compilation_session(&results).await;
+    if let Some(audit_log) = enterprise_manager.read().await.audit_log() {
+        audit_log.record(&args.project_path, &results)?;
+    }
 
     // Display professional results
     display_professional_results(&results, args.verbose);
@@ -129,7 +141,7 @@ async fn create_professional_appimage(
     
     // Detect source files with enhanced detection
     let file_detector = ProFileDetector::new();
-    let source_files = file_detector.detect_files(&args.project_path, args, config)?;
+    let source_files = file_detector.detect_files(&args.project_path, config)?;
 
     if source_files.is_empty() {
         println!("{} No source files found to include in AppImage.", "⚠️".yellow());
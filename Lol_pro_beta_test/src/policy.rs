@@ -0,0 +1,241 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use anyhow::{Context, Result};
+use regex::Regex;
+use serde::Deserialize;
+use crate::args::ProArgs;
+use crate::compiler::compiler_and_flags;
+use crate::config::ProConfig;
+use crate::language_support::Language;
+
+/// Where the policy is read from when `LOL_PRO_POLICY` is not set. It lives
+/// outside the project so that a project cannot loosen its own rules.
+const SYSTEM_POLICY: &str = "/etc/lol-pro/policy.json";
+
+/// Languages `required_sanitizers` applies to.
+const SANITIZED_LANGUAGES: &[Language] = &[Language::C, Language::Cpp];
+
+/// The organization's rules for builds. Flag patterns match a flag exactly,
+/// or by prefix when they end in `*`, as in `-std=*`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Policy {
+    /// When not empty, the only flags allowed
+    pub allowed_flags: Vec<String>,
+    pub banned_flags: Vec<String>,
+    /// Sanitizers C and C++ builds must enable with `-fsanitize=` in CI
+    pub required_sanitizers: Vec<String>,
+    /// Oldest version allowed per compiler, by executable name, checked
+    /// against the compiler that is configured to run
+    pub minimum_versions: BTreeMap<String, String>,
+    /// Languages that may not be compiled, by name
+    pub banned_languages: Vec<String>,
+}
+
+/// A rule the build breaks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    pub rule: &'static str,
+    pub detail: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.rule, self.detail)
+    }
+}
+
+/// What one language's compiles will run.
+#[derive(Debug, Clone)]
+pub struct LanguageBuild {
+    pub language: Language,
+    /// The compiler as configured, a name on `PATH` or a path
+    pub compiler: String,
+    /// Each option, with the argument it takes separately, as in `-I include`
+    pub flags: Vec<String>,
+}
+
+/// What the build is about to do, as far as the policy cares.
+#[derive(Debug, Clone, Default)]
+pub struct BuildRequest {
+    pub languages: Vec<LanguageBuild>,
+    /// Running in CI, where `required_sanitizers` applies
+    pub ci: bool,
+}
+
+/// `flags` as they are passed, with a token that does not start with `-`
+/// kept together with the option before it, whose argument it is.
+fn group_flags(flags: &[String]) -> Vec<String> {
+    let mut grouped: Vec<String> = Vec::new();
+    for flag in flags {
+        match grouped.last_mut() {
+            Some(option) if !flag.starts_with('-') => {
+                option.push(' ');
+                option.push_str(flag);
+            }
+            _ => grouped.push(flag.clone()),
+        }
+    }
+    grouped
+}
+
+impl BuildRequest {
+    /// The request for compiling `languages` with exactly the compilers and
+    /// flags `ProCompiler` will use.
+    pub fn new(languages: Vec<Language>, config: &ProConfig, args: &ProArgs) -> Self {
+        let languages = languages
+            .into_iter()
+            .map(|language| {
+                let (compiler, flags) = compiler_and_flags(config, language, args);
+                LanguageBuild { language, compiler, flags: group_flags(&flags) }
+            })
+            .collect();
+        let ci = std::env::var("CI").is_ok_and(|ci| !ci.is_empty() && ci != "false" && ci != "0");
+        Self { languages, ci }
+    }
+}
+
+fn flag_matches(pattern: &str, flag: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => flag.starts_with(prefix),
+        None => flag == pattern,
+    }
+}
+
+/// The numeric components of `version`, so `1.75.0` sorts above `1.9`.
+fn version_parts(version: &str) -> Vec<u64> {
+    version.split('.').map_while(|part| part.parse().ok()).collect()
+}
+
+/// The first version number in `program --version`, or `None` when the
+/// program is not installed. `program` may be a path.
+fn installed_version(program: &str) -> Option<String> {
+    let output = Command::new(program).arg("--version").output().ok()?;
+    let text = String::from_utf8_lossy(&output.stdout).into_owned() + &String::from_utf8_lossy(&output.stderr);
+    let version = Regex::new(r"\b\d+(?:\.\d+)+\b").expect("version pattern");
+    version.find(&text).map(|found| found.as_str().to_string())
+}
+
+impl Policy {
+    /// The file the policy is read from: `LOL_PRO_POLICY`, else the system
+    /// policy when one is installed.
+    pub fn path() -> Option<PathBuf> {
+        match std::env::var_os("LOL_PRO_POLICY") {
+            Some(path) if !path.is_empty() => Some(PathBuf::from(path)),
+            _ => Some(PathBuf::from(SYSTEM_POLICY)).filter(|path| path.exists()),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Policy> {
+        let contents = fs::read_to_string(path).with_context(|| format!("Failed to read policy {}", path.display()))?;
+        serde_json::from_str(&contents).with_context(|| format!("Failed to parse policy {}", path.display()))
+    }
+
+    /// Every rule `request` breaks. `version_of` reports a compiler's
+    /// installed version; compilers it does not find are left to the build.
+    pub fn violations(&self, request: &BuildRequest, version_of: impl Fn(&str) -> Option<String>) -> Vec<Violation> {
+        let mut violations = Vec::new();
+        for build in &request.languages {
+            let language = build.language.name();
+            if self.banned_languages.iter().any(|banned| banned.eq_ignore_ascii_case(language)) {
+                violations.push(Violation { rule: "banned language", detail: format!("{} may not be compiled", language) });
+            }
+            for flag in &build.flags {
+                if let Some(pattern) = self.banned_flags.iter().find(|pattern| flag_matches(pattern, flag)) {
+                    violations.push(Violation { rule: "banned flag", detail: format!("{}: `{}` matches `{}`", language, flag, pattern) });
+                } else if !self.allowed_flags.is_empty() && !self.allowed_flags.iter().any(|pattern| flag_matches(pattern, flag)) {
+                    violations.push(Violation { rule: "flag not allowed", detail: format!("{}: `{}` is not in allowed_flags", language, flag) });
+                }
+            }
+            if request.ci && SANITIZED_LANGUAGES.contains(&build.language) {
+                let enabled: Vec<&str> = build
+                    .flags
+                    .iter()
+                    .filter_map(|flag| flag.strip_prefix("-fsanitize="))
+                    .flat_map(|sanitizers| sanitizers.split(','))
+                    .collect();
+                for sanitizer in &self.required_sanitizers {
+                    if !enabled.contains(&sanitizer.as_str()) {
+                        violations.push(Violation {
+                            rule: "missing sanitizer",
+                            detail: format!("CI builds of {} need `-fsanitize={}`", language, sanitizer),
+                        });
+                    }
+                }
+            }
+        }
+        let mut compilers: Vec<&str> = request.languages.iter().map(|build| build.compiler.as_str()).collect();
+        compilers.sort();
+        compilers.dedup();
+        for compiler in compilers {
+            let executable = Path::new(compiler).file_name().map(|name| name.to_string_lossy());
+            let Some(minimum) = executable.and_then(|executable| self.minimum_versions.get(executable.as_ref())) else {
+                continue;
+            };
+            if let Some(installed) = version_of(compiler) {
+                if version_parts(&installed) < version_parts(minimum) {
+                    violations.push(Violation {
+                        rule: "toolchain too old",
+                        detail: format!("{} {} is older than the required {}", compiler, installed, minimum),
+                    });
+                }
+            }
+        }
+        violations
+    }
+
+    /// Fails with a report of every violation, so nothing is compiled
+    /// against the policy.
+    pub fn enforce(&self, request: &BuildRequest, source: &Path) -> Result<()> {
+        let violations = self.violations(request, installed_version);
+        if violations.is_empty() {
+            println!("📜 Policy {}: no violations", source.display());
+            return Ok(());
+        }
+        println!("🚫 Policy violations ({}):", violations.len());
+        for violation in &violations {
+            println!("   • {}", violation);
+        }
+        anyhow::bail!("The build violates {} rule(s) of policy {}; nothing was compiled", violations.len(), source.display())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use crate::config::LanguageSettings;
+
+    fn request(settings: LanguageSettings, cflags: &str) -> BuildRequest {
+        let mut config = ProConfig::default();
+        config.language_settings.insert("c".to_string(), settings);
+        let args = ProArgs::parse_from(["lol-pro", "--cflags", cflags]);
+        let mut request = BuildRequest::new(vec![Language::C], &config, &args);
+        request.ci = true;
+        request
+    }
+
+    #[test]
+    fn test_policy_sees_the_flags_and_compiler_that_will_run() {
+        let policy = Policy {
+            allowed_flags: vec!["-I*".to_string(), "-D*".to_string(), "-fsanitize=*".to_string()],
+            banned_flags: vec!["-fno-stack-protector".to_string()],
+            required_sanitizers: vec!["address".to_string()],
+            minimum_versions: BTreeMap::from([("gcc".to_string(), "11".to_string())]),
+            ..Policy::default()
+        };
+        let settings = LanguageSettings {
+            compiler_path: Some("/opt/gcc-9/bin/gcc".to_string()),
+            flags: vec!["-fsanitize=address".to_string(), "-fno-stack-protector".to_string()],
+        };
+        let request = request(settings, "-I include -D FOO");
+        assert_eq!(request.languages[0].flags, ["-fsanitize=address", "-fno-stack-protector", "-I include", "-D FOO"]);
+
+        let version_of = |program: &str| (program == "/opt/gcc-9/bin/gcc").then(|| "9.4.0".to_string());
+        let rules: Vec<&str> = policy.violations(&request, version_of).iter().map(|violation| violation.rule).collect();
+        assert_eq!(rules, ["banned flag", "toolchain too old"]);
+    }
+}
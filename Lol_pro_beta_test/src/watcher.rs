@@ -0,0 +1,73 @@
+use std::path::PathBuf;
+use std::time::Duration;
+use anyhow::{Context, Result};
+use indicatif::{MultiProgress, ProgressStyle};
+use notify::{RecursiveMode, Watcher};
+use tokio::sync::mpsc;
+use crate::args::ProArgs;
+use crate::compiler::{ProCompilationStatus, ProCompiler};
+use crate::config::ProConfig;
+use crate::file_detector::ProFileDetector;
+use crate::language_support::Language;
+
+/// How long to wait for a burst of saves to settle before recompiling.
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+pub struct FileWatcher {
+    project_path: PathBuf,
+    args: ProArgs,
+}
+
+impl FileWatcher {
+    pub fn new(project_path: PathBuf, args: ProArgs) -> Self {
+        Self { project_path, args }
+    }
+
+    /// Recompiles whenever a source file changes, until interrupted.
+    pub async fn start_watching(&self) -> Result<()> {
+        let (sender, mut changes) = mpsc::unbounded_channel();
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            if let Ok(event) = event {
+                if event.paths.iter().any(|path| Language::from_path(path).is_some()) {
+                    let _ = sender.send(event.paths);
+                }
+            }
+        })
+        .context("Failed to start the file watcher")?;
+        watcher
+            .watch(&self.project_path, RecursiveMode::Recursive)
+            .with_context(|| format!("Failed to watch {}", self.project_path.display()))?;
+        println!("👀 Watching {} (Ctrl+C to stop)", self.project_path.display());
+
+        loop {
+            let paths = tokio::select! {
+                paths = changes.recv() => paths.context("The file watcher stopped")?,
+                _ = tokio::signal::ctrl_c() => return Ok(()),
+            };
+            tokio::time::sleep(DEBOUNCE).await;
+            while changes.try_recv().is_ok() {}
+            if let Some(path) = paths.first() {
+                println!("\n🔄 {} changed, recompiling...", path.display());
+            }
+            if let Err(error) = self.recompile().await {
+                println!("❌ {:#}", error);
+            }
+        }
+    }
+
+    async fn recompile(&self) -> Result<()> {
+        let config = ProConfig::load().context("Failed to load professional configuration")?;
+        let source_files = ProFileDetector::new().detect_files(&self.project_path, &config)?;
+        let progress_style = ProgressStyle::default_bar().template("{msg}").context("Bad progress template")?;
+        let results = ProCompiler::new(config, self.args.jobs)
+            .compile_all(source_files, &MultiProgress::new(), &progress_style, &self.args)
+            .await?;
+        for result in &results {
+            match &result.status {
+                ProCompilationStatus::Success { .. } => println!("✅ {}: {} files", result.language.name(), result.files.len()),
+                ProCompilationStatus::Failure { error, .. } => println!("❌ {}: {}", result.language.name(), error),
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,15 +1,147 @@
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::process::Command;
-use std::os::unix::fs::PermissionsExt;
 use anyhow::{Context, Result};
-use crate::language_support::Language;
+use crate::artifacts;
+use crate::build_info::BuildInfo;
+use crate::entry_point::EntryPoint;
+use crate::flatpak;
+use crate::language_support::{Language, Toolchain};
+use crate::node_env::NodeEnvironment;
+use crate::package;
+use crate::reproducible;
+use crate::secrets::{self, SecretScan};
+use crate::signing;
+
+/// How a Python app gets an interpreter that travels with the AppImage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PythonBundler {
+    /// A PyInstaller one-folder build: interpreter, site-packages and app
+    PyInstaller,
+    /// A relocatable CPython from python-appimage, with the requirements
+    /// installed into it and the sources run as they are
+    PythonAppImage,
+}
+
+impl PythonBundler {
+    /// The `bundler` setting, or when unset whichever tool `python` can
+    /// import. `None` means the app runs on the system's `python3`.
+    pub fn select(setting: Option<&str>, python: &Toolchain) -> Result<Option<Self>> {
+        match setting {
+            Some("pyinstaller") => return Ok(Some(PythonBundler::PyInstaller)),
+            Some("python-appimage") => return Ok(Some(PythonBundler::PythonAppImage)),
+            Some("system") => return Ok(None),
+            Some(other) => anyhow::bail!("Unknown Python bundler '{}' (expected pyinstaller, python-appimage or system)", other),
+            None => {}
+        }
+        let importable = |module: &str| {
+            python
+                .command()
+                .args(["-c", &format!("import {}", module)])
+                .output()
+                .is_ok_and(|output| output.status.success())
+        };
+        Ok(if importable("PyInstaller") {
+            Some(PythonBundler::PyInstaller)
+        } else if importable("python_appimage") {
+            Some(PythonBundler::PythonAppImage)
+        } else {
+            None
+        })
+    }
+}
+
+/// How a JavaScript or TypeScript app becomes one executable that needs
+/// no system Node.js.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeBundler {
+    /// An esbuild bundle injected into a copy of `node` as a Node.js
+    /// single executable application
+    Sea,
+    /// An esbuild bundle compiled with `pkg`
+    Pkg,
+}
+
+impl NodeBundler {
+    /// The `bundler` setting, or when unset SEA if `node` supports it and
+    /// esbuild and postject are installed, then pkg. `None` means the app
+    /// runs on the system's `node`.
+    pub fn select(setting: Option<&str>, environment: &NodeEnvironment) -> Result<Option<Self>> {
+        match setting {
+            Some("sea") => return Ok(Some(NodeBundler::Sea)),
+            Some("pkg") => return Ok(Some(NodeBundler::Pkg)),
+            Some("system") => return Ok(None),
+            Some(other) => anyhow::bail!("Unknown Node.js bundler '{}' (expected sea, pkg or system)", other),
+            None => {}
+        }
+        let available = |tool: &str| node_tool(environment, tool).command().arg("--version").output().is_ok_and(|output| output.status.success());
+        let has_sea = Command::new("node")
+            .args(["-e", "require('node:sea')"])
+            .output()
+            .is_ok_and(|output| output.status.success());
+        Ok(if has_sea && available("esbuild") && available("postject") {
+            Some(NodeBundler::Sea)
+        } else if available("esbuild") && available("pkg") {
+            Some(NodeBundler::Pkg)
+        } else {
+            None
+        })
+    }
+}
+
+/// `tool` from the project's `node_modules/.bin`, or from `PATH`.
+fn node_tool(environment: &NodeEnvironment, tool: &str) -> Toolchain {
+    environment
+        .bin_dir()
+        .map(|dir| dir.join(tool))
+        .filter(|path| path.is_file())
+        .map(|path| Toolchain::new(path.to_string_lossy()))
+        .unwrap_or_else(|| Toolchain::new(tool))
+}
+
+/// The fuse Node.js checks to find an injected SEA blob.
+const SEA_FUSE: &str = "NODE_SEA_FUSE_fce680ab2cc467b6e072b8b5df1996b2";
+
+fn run_node_step(command: &mut Command, step: &str) -> Result<()> {
+    let output = command.output().with_context(|| format!("Failed to run {}", step))?;
+    if !output.status.success() {
+        anyhow::bail!("{} failed: {}", step, String::from_utf8_lossy(&output.stderr));
+    }
+    Ok(())
+}
+
+/// Whether `languages` together have more source files than any other
+/// language.
+pub fn is_dominant(source_files: &HashMap<Language, Vec<PathBuf>>, languages: &[Language]) -> bool {
+    let count: usize = languages.iter().filter_map(|language| source_files.get(language)).map(Vec::len).sum();
+    count > 0
+        && source_files
+            .iter()
+            .all(|(other, files)| languages.contains(other) || files.len() < count)
+}
 
 pub struct AppImageBuilder {
     project_name: String,
     source_files: HashMap<Language, Vec<PathBuf>>,
     output_dir: PathBuf,
+    julia_project: Option<PathBuf>,
+    ruby_project: Option<PathBuf>,
+    python_app: Option<(PathBuf, Toolchain, PythonBundler)>,
+    node_app: Option<(PathBuf, NodeEnvironment, NodeBundler)>,
+    entry: Option<(PathBuf, EntryPoint)>,
+    build_info: Option<BuildInfo>,
+    source_date_epoch: Option<u64>,
+    binaries: Vec<PathBuf>,
+    icon: Option<PathBuf>,
+    categories: Vec<String>,
+    version: Option<String>,
+    license: Option<String>,
+    update_info: Option<String>,
+    sign: bool,
+    signing_key: Option<String>,
+    secret_scan: Option<(SecretScan, PathBuf)>,
+    app_id: Option<String>,
 }
 
 impl AppImageBuilder {
@@ -19,9 +151,156 @@ impl AppImageBuilder {
             project_name,
             source_files,
             output_dir,
+            julia_project: None,
+            ruby_project: None,
+            python_app: None,
+            node_app: None,
+            entry: None,
+            build_info: None,
+            source_date_epoch: None,
+            binaries: Vec::new(),
+            icon: None,
+            categories: vec!["Development".to_string()],
+            version: None,
+            license: None,
+            update_info: None,
+            sign: false,
+            signing_key: None,
+            secret_scan: None,
+            app_id: None,
         }
     }
 
+    /// Compiles the Julia package at `project_dir` into a standalone app with
+    /// PackageCompiler and makes it the AppImage entry point.
+    pub fn with_julia_app(mut self, project_dir: PathBuf) -> Self {
+        self.julia_project = Some(project_dir);
+        self
+    }
+
+    /// Ships the project's Ruby sources (and its Gemfile) inside the AppDir
+    /// and runs them with the system `ruby`.
+    pub fn with_ruby_app(mut self, project_dir: PathBuf) -> Self {
+        self.ruby_project = Some(project_dir);
+        self
+    }
+
+    /// Bundles the project's Python app at `project_dir` together with an
+    /// interpreter, so the AppImage runs without a system Python. `python`
+    /// is the project's interpreter, which runs the bundler and whose
+    /// installed packages PyInstaller collects.
+    pub fn with_python_app(mut self, project_dir: PathBuf, python: Toolchain, bundler: PythonBundler) -> Self {
+        self.python_app = Some((project_dir, python, bundler));
+        self
+    }
+
+    /// Bundles the project's JavaScript or TypeScript app at `project_dir`
+    /// with esbuild and turns it into one executable in `usr/bin`.
+    pub fn with_node_app(mut self, project_dir: PathBuf, environment: NodeEnvironment, bundler: NodeBundler) -> Self {
+        self.node_app = Some((project_dir, environment, bundler));
+        self
+    }
+
+    /// Runs `entry` of the project at `project_dir` when the AppImage
+    /// starts: the executable built from it, the bundled app it belongs
+    /// to, or its interpreter on it.
+    pub fn with_entry(mut self, project_dir: PathBuf, entry: EntryPoint) -> Self {
+        self.entry = Some((project_dir, entry));
+        self
+    }
+
+    /// The entry point's file, if it is in one of `languages`.
+    fn entry_file(&self, languages: &[Language]) -> Option<&PathBuf> {
+        self.entry
+            .as_ref()
+            .filter(|(_, entry)| languages.contains(&entry.language))
+            .map(|(_, entry)| &entry.file)
+    }
+
+    /// Ships compiled executables in `usr/bin`, with the shared libraries
+    /// they load in `usr/lib`, and runs the first one when nothing else is
+    /// the entry point.
+    pub fn with_binaries(mut self, binaries: Vec<PathBuf>) -> Self {
+        self.binaries = binaries;
+        self
+    }
+
+    /// Uses the PNG, SVG or XPM at `path` instead of the generated icon.
+    pub fn with_icon(mut self, path: PathBuf) -> Self {
+        self.icon = Some(path);
+        self
+    }
+
+    /// Freedesktop menu categories for the desktop entry.
+    pub fn with_categories(mut self, categories: Vec<String>) -> Self {
+        self.categories = categories;
+        self
+    }
+
+    pub fn with_version(mut self, version: String) -> Self {
+        self.version = Some(version);
+        self
+    }
+
+    /// Records a `--version-stamp` build's commit in the desktop entry and
+    /// its version and date as the AppStream release. An explicit
+    /// `with_version` wins over the stamped version.
+    pub fn with_build_info(mut self, build_info: BuildInfo) -> Self {
+        self.build_info = Some(build_info);
+        self
+    }
+
+    /// SPDX expression recorded as the AppStream `project_license`.
+    pub fn with_license(mut self, license: String) -> Self {
+        self.license = Some(license);
+        self
+    }
+
+    /// Embeds AppImageUpdate information, `zsync|<url>` or
+    /// `gh-releases-zsync|<user>|<repo>|<tag>|<file pattern>`, and writes
+    /// the `.zsync` file users' updaters download deltas with.
+    pub fn with_update_info(mut self, update_info: String) -> Result<Self> {
+        validate_update_info(&update_info)?;
+        self.update_info = Some(update_info);
+        Ok(self)
+    }
+
+    /// Signs the AppImage with GPG, embedding the signature and writing a
+    /// detached `.sig`, with `key` or the first secret key.
+    pub fn with_signing(mut self, key: Option<String>) -> Self {
+        self.sign = true;
+        self.signing_key = key;
+        self
+    }
+
+    /// The reverse-DNS ID of the AppStream metadata, such as
+    /// `io.github.<user>.<name>`; `io.lol.<name>` when not set.
+    pub fn with_app_id(mut self, app_id: String) -> Self {
+        self.app_id = Some(app_id);
+        self
+    }
+
+    /// Scans everything in the AppDir for credentials before it is packed,
+    /// with the allowlist of the project at `project_path`.
+    pub fn with_secret_scan(mut self, mode: SecretScan, project_path: PathBuf) -> Self {
+        self.secret_scan = Some((mode, project_path));
+        self
+    }
+
+    /// Stamps every file in the AppDir with `epoch` and packs it
+    /// deterministically, so the same sources give the same image.
+    pub fn with_source_date_epoch(mut self, epoch: u64) -> Self {
+        self.source_date_epoch = Some(epoch);
+        self
+    }
+
+    /// Languages in name order, so generated files do not depend on hash order.
+    fn sorted_sources(&self) -> Vec<(&Language, &Vec<PathBuf>)> {
+        let mut sources: Vec<_> = self.source_files.iter().collect();
+        sources.sort_by_key(|(language, _)| language.name());
+        sources
+    }
+
     pub fn build(&self) -> Result<PathBuf> {
         // Create output directory structure
         self.create_directory_structure()?;
@@ -29,8 +308,34 @@ impl AppImageBuilder {
         // Consolidate source files
         let consolidated_file = self.consolidate_source_files()?;
         
+        // Build the native Julia app or bundle Ruby sources, if requested
+        let julia_entry = self.build_julia_app()?;
+        let python_entry = self.bundle_python_app()?;
+        let node_entry = self.bundle_node_app()?;
+        let ruby_entry = self.bundle_ruby_sources()?;
+        let binary_entry = self.bundle_binaries()?;
+        // The entry point's own language decides, then the bundled apps in
+        // order, then running the entry script with its interpreter
+        let language = self.entry.as_ref().map(|(_, entry)| &entry.language);
+        let entry_command = match language {
+            Some(Language::Julia) => julia_entry.clone(),
+            Some(Language::Python) => python_entry.clone(),
+            Some(Language::JavaScript | Language::TypeScript) => node_entry.clone(),
+            Some(Language::Ruby) => ruby_entry.clone(),
+            Some(language) if language.is_compiled() => binary_entry.clone(),
+            _ => None,
+        };
+        let entry_command = match entry_command {
+            Some(command) => Some(command),
+            None => self.bundle_script()?,
+        };
+        let entry_command = entry_command.or(julia_entry).or(python_entry).or(node_entry).or(ruby_entry).or(binary_entry);
+
         // Create AppImage structure
-        self.create_appimage_structure(&consolidated_file)?;
+        self.create_appimage_structure(&consolidated_file, entry_command.as_deref())?;
+        if let Some((mode, project_path)) = &self.secret_scan {
+            secrets::guard_packaging(*mode, &self.output_dir.join("AppDir"), project_path)?;
+        }
         
         // Create the AppImage
         let appimage_path = self.create_appimage()?;
@@ -49,7 +354,7 @@ impl AppImageBuilder {
         // Create standard AppImage directories
         fs::create_dir_all(appdir.join("usr").join("bin"))?;
         fs::create_dir_all(appdir.join("usr").join("share").join("applications"))?;
-        fs::create_dir_all(appdir.join("usr").join("share").join("icons").join("hicolor").join("256x256").join("apps"))?;
+        fs::create_dir_all(appdir.join("usr").join("share").join("icons").join("hicolor").join("scalable").join("apps"))?;
         
         Ok(())
     }
@@ -71,7 +376,7 @@ impl AppImageBuilder {
         consolidated_content.push_str("    print('📁 Source files consolidated into this AppImage\\n')\n\n");
         
         // Add source code from each language
-        for (language, files) in &self.source_files {
+        for (language, files) in self.sorted_sources() {
             consolidated_content.push_str(&format!("    # {} Source Files\n", language.name()));
             consolidated_content.push_str(&format!("    print('📋 {} files:')\n", language.name()));
             
@@ -85,7 +390,7 @@ impl AppImageBuilder {
                         filename.replace(".", "_").replace("-", "_"), content));
                 }
             }
-            consolidated_content.push_str("\n");
+            consolidated_content.push('\n');
         }
         
         // Add execution logic
@@ -108,115 +413,654 @@ impl AppImageBuilder {
         
         fs::write(&consolidated_path, consolidated_content)?;
         
-        // Make it executable
-        let mut perms = fs::metadata(&consolidated_path)?.permissions();
-        perms.set_mode(0o755);
-        fs::set_permissions(&consolidated_path, perms)?;
+        set_executable(&consolidated_path)?;
         
         Ok(consolidated_path)
     }
 
-    fn create_appimage_structure(&self, _consolidated_file: &Path) -> Result<()> {
+    /// Runs PackageCompiler's `create_app` into `AppDir/usr/lib/julia` and
+    /// returns the executable's path relative to the AppDir.
+    fn build_julia_app(&self) -> Result<Option<String>> {
+        let Some(project_dir) = &self.julia_project else {
+            return Ok(None);
+        };
+
+        let manifest = fs::read_to_string(project_dir.join("Project.toml"))
+            .context("PackageCompiler needs a Project.toml in the project directory")?;
+        let package_name = manifest
+            .lines()
+            .find_map(|line| {
+                let value = line.trim().strip_prefix("name")?.trim_start().strip_prefix('=')?;
+                Some(value.trim().trim_matches('"').to_string())
+            })
+            .context("Project.toml has no package name")?;
+
+        let app_dir = self.output_dir.join("AppDir").join("usr").join("lib").join("julia");
+        let output = Command::new("julia")
+            .arg("--startup-file=no")
+            .arg("-e")
+            .arg("using PackageCompiler; create_app(ARGS[1], ARGS[2]; force = true)")
+            .arg(project_dir)
+            .arg(&app_dir)
+            .output()
+            .context("Failed to run julia for PackageCompiler")?;
+
+        if !output.status.success() {
+            return Err(anyhow::anyhow!(
+                "PackageCompiler failed: {}",
+                String::from_utf8_lossy(&output.stderr)
+            ));
+        }
+
+        Ok(Some(format!("\"${{APPDIR}}/usr/lib/julia/bin/{}\"", package_name)))
+    }
+
+    /// Copies `files` into `destination`, keeping their layout relative to
+    /// `project_dir`, and returns the copied paths relative to it.
+    fn copy_sources(files: &[PathBuf], project_dir: &Path, destination: &Path) -> Result<Vec<PathBuf>> {
+        let mut copied = Vec::new();
+        for file in files {
+            let relative = file
+                .strip_prefix(project_dir)
+                .map(Path::to_path_buf)
+                .unwrap_or_else(|_| PathBuf::from(file.file_name().unwrap_or_default()));
+            let target = destination.join(&relative);
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::copy(file, &target)
+                .with_context(|| format!("Failed to copy {}", file.display()))?;
+            copied.push(relative);
+        }
+        Ok(copied)
+    }
+
+    /// Bundles the Python app with its own interpreter and returns the
+    /// command that runs it: the PyInstaller executable, or the bundled
+    /// CPython running the entry script.
+    fn bundle_python_app(&self) -> Result<Option<String>> {
+        let (Some((project_dir, python, bundler)), Some(files)) = (&self.python_app, self.source_files.get(&Language::Python)) else {
+            return Ok(None);
+        };
+        let project_dir = project_dir.canonicalize()?;
+        let files: Vec<PathBuf> = files.iter().map(|file| file.canonicalize().unwrap_or_else(|_| file.clone())).collect();
+        let relative: Vec<PathBuf> = files.iter().map(|file| file.strip_prefix(&project_dir).unwrap_or(file).to_path_buf()).collect();
+        let entry = self
+            .entry_file(&[Language::Python])
+            .or_else(|| package::entry_point(&relative))
+            .context("No Python entry point: add a main.py, app.py or __main__.py, or pass --entry")?
+            .clone();
+        let usr_lib = std::path::absolute(self.output_dir.join("AppDir").join("usr").join("lib"))?;
+        let work_dir = std::path::absolute(self.output_dir.join("python-build"))?;
+
+        let entry_command = match bundler {
+            PythonBundler::PyInstaller => {
+                let output = python
+                    .command()
+                    .args(["-m", "PyInstaller", "--noconfirm", "--onedir", "--name", &self.project_name])
+                    .arg("--distpath")
+                    .arg(&usr_lib)
+                    .arg("--workpath")
+                    .arg(&work_dir)
+                    .arg("--specpath")
+                    .arg(&work_dir)
+                    .arg("--paths")
+                    .arg(&project_dir)
+                    .arg(project_dir.join(&entry))
+                    .current_dir(&project_dir)
+                    .output()
+                    .context("Failed to run PyInstaller")?;
+                if !output.status.success() {
+                    anyhow::bail!("PyInstaller failed: {}", String::from_utf8_lossy(&output.stderr));
+                }
+                format!("\"${{APPDIR}}/usr/lib/{name}/{name}\"", name = self.project_name)
+            }
+            PythonBundler::PythonAppImage => {
+                Self::copy_sources(&files, &project_dir, &usr_lib.join("python"))?;
+                let interpreter = self.extract_python_appimage(python, &work_dir)?;
+                let requirements = project_dir.join("requirements.txt");
+                if requirements.is_file() {
+                    let output = Command::new(self.output_dir.join("AppDir").join(&interpreter))
+                        .args(["-m", "pip", "install", "--no-warn-script-location", "-r"])
+                        .arg(&requirements)
+                        .output()
+                        .context("Failed to run the bundled pip")?;
+                    if !output.status.success() {
+                        anyhow::bail!("Installing requirements.txt failed: {}", String::from_utf8_lossy(&output.stderr));
+                    }
+                }
+                format!("\"${{APPDIR}}/{}\" \"${{APPDIR}}/usr/lib/python/{}\"", interpreter.display(), entry.display())
+            }
+        };
+        let _ = fs::remove_dir_all(&work_dir);
+        Ok(Some(entry_command))
+    }
+
+    /// Builds a Python AppImage from `python` with python-appimage and
+    /// moves its relocatable CPython into `AppDir/opt`. Returns the
+    /// interpreter's path relative to the AppDir.
+    fn extract_python_appimage(&self, python: &Toolchain, work_dir: &Path) -> Result<PathBuf> {
+        fs::create_dir_all(work_dir)?;
+        let image = work_dir.join("python.AppImage");
+        let output = python
+            .command()
+            .args(["-m", "python_appimage", "build", "local", "-d"])
+            .arg(&image)
+            .output()
+            .context("Failed to run python-appimage")?;
+        if !output.status.success() {
+            anyhow::bail!("python-appimage failed: {}", String::from_utf8_lossy(&output.stderr));
+        }
+        let output = Command::new(&image)
+            .arg("--appimage-extract")
+            .current_dir(work_dir)
+            .output()
+            .with_context(|| format!("Failed to extract {}", image.display()))?;
+        if !output.status.success() {
+            anyhow::bail!("Extracting {} failed: {}", image.display(), String::from_utf8_lossy(&output.stderr));
+        }
+
+        let opt = self.output_dir.join("AppDir").join("opt");
+        if opt.exists() {
+            fs::remove_dir_all(&opt)?;
+        }
+        fs::rename(work_dir.join("squashfs-root").join("opt"), &opt)?;
+        // The interpreter is `opt/python3.X/bin/python3.X`
+        for entry in fs::read_dir(&opt)? {
+            let name = entry?.file_name().to_string_lossy().into_owned();
+            let interpreter = Path::new("opt").join(&name).join("bin").join(&name);
+            if name.starts_with("python3") && self.output_dir.join("AppDir").join(&interpreter).is_file() {
+                return Ok(interpreter);
+            }
+        }
+        anyhow::bail!("The python-appimage build holds no opt/python3.X interpreter")
+    }
+
+    /// Bundles the JavaScript or TypeScript entry point with esbuild and
+    /// builds `usr/bin/<name>` from it with Node.js SEA or pkg. The entry
+    /// point is `package.json`'s `main`, or an `index`, `main` or `app` file.
+    fn bundle_node_app(&self) -> Result<Option<String>> {
+        let Some((project_dir, environment, bundler)) = &self.node_app else {
+            return Ok(None);
+        };
+        let project_dir = project_dir.canonicalize()?;
+        let relative: Vec<PathBuf> = [Language::JavaScript, Language::TypeScript]
+            .iter()
+            .filter_map(|language| self.source_files.get(language))
+            .flatten()
+            .map(|file| {
+                let file = file.canonicalize().unwrap_or_else(|_| file.clone());
+                file.strip_prefix(&project_dir).unwrap_or(&file).to_path_buf()
+            })
+            .collect();
+        let declared_main = fs::read_to_string(project_dir.join("package.json"))
+            .ok()
+            .and_then(|content| serde_json::from_str::<serde_json::Value>(&content).ok())
+            .and_then(|manifest| manifest.get("main").and_then(|main| main.as_str()).map(PathBuf::from))
+            .filter(|main| project_dir.join(main).is_file());
+        let entry = self
+            .entry_file(&[Language::JavaScript, Language::TypeScript])
+            .cloned()
+            .or(declared_main)
+            .or_else(|| package::entry_point(&relative).cloned())
+            .context("No JavaScript entry point: set `main` in package.json or add an index, main or app file")?;
+
+        let work_dir = std::path::absolute(self.output_dir.join("node-build"))?;
+        fs::create_dir_all(&work_dir)?;
+        let bundle = work_dir.join("bundle.js");
+        let output = node_tool(environment, "esbuild")
+            .command()
+            .arg(project_dir.join(&entry))
+            .args(["--bundle", "--platform=node", "--format=cjs", "--log-level=warning"])
+            .arg(format!("--outfile={}", bundle.display()))
+            .current_dir(&project_dir)
+            .output()
+            .context("Failed to run esbuild")?;
+        if !output.status.success() {
+            anyhow::bail!("esbuild failed: {}", String::from_utf8_lossy(&output.stderr));
+        }
+
+        let executable = std::path::absolute(self.output_dir.join("AppDir").join("usr").join("bin").join(&self.project_name))?;
+        match bundler {
+            NodeBundler::Sea => {
+                let blob = work_dir.join("sea-prep.blob");
+                let sea_config = serde_json::json!({
+                    "main": bundle,
+                    "output": blob,
+                    "disableExperimentalSEAWarning": true,
+                });
+                let config_path = work_dir.join("sea-config.json");
+                fs::write(&config_path, serde_json::to_string_pretty(&sea_config)?)?;
+                run_node_step(Command::new("node").arg("--experimental-sea-config").arg(&config_path), "node --experimental-sea-config")?;
+
+                let node_path = Command::new("node")
+                    .args(["-p", "process.execPath"])
+                    .output()
+                    .context("Failed to locate node")?;
+                let node_path = PathBuf::from(String::from_utf8_lossy(&node_path.stdout).trim());
+                fs::copy(&node_path, &executable).with_context(|| format!("Failed to copy {}", node_path.display()))?;
+                set_executable(&executable)?;
+                run_node_step(
+                    node_tool(environment, "postject")
+                        .command()
+                        .arg(&executable)
+                        .arg("NODE_SEA_BLOB")
+                        .arg(&blob)
+                        .args(["--sentinel-fuse", SEA_FUSE]),
+                    "postject",
+                )?;
+            }
+            NodeBundler::Pkg => {
+                run_node_step(
+                    node_tool(environment, "pkg")
+                        .command()
+                        .arg(&bundle)
+                        .args(["--targets", "host", "--output"])
+                        .arg(&executable),
+                    "pkg",
+                )?;
+            }
+        }
+        let _ = fs::remove_dir_all(&work_dir);
+        Ok(Some(format!("\"${{APPDIR}}/usr/bin/{}\"", self.project_name)))
+    }
+
+    /// Copies the entry point's language's sources into
+    /// `AppDir/usr/lib/<language>` and returns the command running the
+    /// entry point with the system interpreter, for languages no bundler
+    /// handled.
+    fn bundle_script(&self) -> Result<Option<String>> {
+        let Some((project_dir, entry)) = &self.entry else {
+            return Ok(None);
+        };
+        let (Some(interpreter), Some(files)) = (package::interpreter(&entry.language), self.source_files.get(&entry.language)) else {
+            return Ok(None);
+        };
+        let project_dir = project_dir.canonicalize()?;
+        let files: Vec<PathBuf> = files.iter().map(|file| file.canonicalize().unwrap_or_else(|_| file.clone())).collect();
+        let script_dir = self.output_dir.join("AppDir").join("usr").join("lib").join(entry.language.config_key());
+        Self::copy_sources(&files, &project_dir, &script_dir)?;
+        Ok(Some(format!(
+            "{} \"${{APPDIR}}/usr/lib/{}/{}\"",
+            interpreter,
+            entry.language.config_key(),
+            entry.file.display()
+        )))
+    }
+
+    /// Copies Ruby files into `AppDir/usr/lib/ruby`, keeping their layout
+    /// relative to the project so `require_relative` keeps working, and
+    /// returns the command that runs the entry script.
+    fn bundle_ruby_sources(&self) -> Result<Option<String>> {
+        let (Some(project_dir), Some(files)) = (&self.ruby_project, self.source_files.get(&Language::Ruby)) else {
+            return Ok(None);
+        };
+
+        let ruby_dir = self.output_dir.join("AppDir").join("usr").join("lib").join("ruby");
+        let bundled = Self::copy_sources(files, project_dir, &ruby_dir)?;
+
+        let mut environment = String::new();
+        if project_dir.join("Gemfile").is_file() {
+            for name in ["Gemfile", "Gemfile.lock"] {
+                if project_dir.join(name).is_file() {
+                    fs::copy(project_dir.join(name), ruby_dir.join(name))?;
+                }
+            }
+            environment.push_str("env BUNDLE_GEMFILE=\"${APPDIR}/usr/lib/ruby/Gemfile\" ");
+        }
+
+        let Some(entry) = self
+            .entry_file(&[Language::Ruby])
+            .or_else(|| bundled.iter().find(|path| path.file_stem().is_some_and(|stem| stem == "main")))
+            .or_else(|| bundled.iter().find(|path| path.extension().is_some_and(|ext| ext == "rb")))
+        else {
+            return Ok(None);
+        };
+
+        Ok(Some(format!(
+            "{}ruby \"${{APPDIR}}/usr/lib/ruby/{}\"",
+            environment,
+            entry.display()
+        )))
+    }
+
+    /// Copies the executables and every shared library `ldd` resolves for
+    /// them, apart from the ones every system provides, and returns the
+    /// command running the first executable.
+    fn bundle_binaries(&self) -> Result<Option<String>> {
+        if self.binaries.is_empty() {
+            return Ok(None);
+        }
+
+        let usr = self.output_dir.join("AppDir").join("usr");
+        let lib_dir = usr.join("lib");
+        fs::create_dir_all(&lib_dir)?;
+        let mut libraries = BTreeSet::new();
+        let mut names: HashMap<&std::ffi::OsStr, &PathBuf> = HashMap::new();
+        for binary in &self.binaries {
+            let name = binary.file_name().context("Executable without a file name")?;
+            // One would silently replace the other in usr/bin
+            if let Some(other) = names.insert(name, binary) {
+                anyhow::bail!(
+                    "{} and {} would both be usr/bin/{} in the AppImage; rename one of them",
+                    other.display(),
+                    binary.display(),
+                    name.to_string_lossy()
+                );
+            }
+            fs::copy(binary, usr.join("bin").join(name))
+                .with_context(|| format!("Failed to copy {}", binary.display()))?;
+            libraries.extend(shared_libraries(binary));
+        }
+        for library in &libraries {
+            let Some(name) = library.file_name() else {
+                continue;
+            };
+            // Follow symlinks so the AppDir holds the library itself under its soname
+            fs::copy(library, lib_dir.join(name))
+                .with_context(|| format!("Failed to copy {}", library.display()))?;
+        }
+
+        let entry = self.binaries[0].file_name().unwrap_or_default().to_string_lossy();
+        Ok(Some(format!("\"${{APPDIR}}/usr/bin/{}\"", entry)))
+    }
+
+    fn create_appimage_structure(&self, _consolidated_file: &Path, entry_command: Option<&str>) -> Result<()> {
         let appdir = self.output_dir.join("AppDir");
+        let entry_command = entry_command
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("\"${{APPDIR}}/usr/bin/{}.py\"", self.project_name));
+
+        // Create desktop entry; appimagetool and the runtime read the copy
+        // at the top of the AppDir
+        let desktop_content = self.desktop_entry(&exec_name(&entry_command));
+        let desktop_file = format!("{}.desktop", self.project_name);
+        fs::write(appdir.join("usr").join("share").join("applications").join(&desktop_file), &desktop_content)?;
+        fs::write(appdir.join(&desktop_file), desktop_content)?;
+        validate_desktop_file(&appdir.join(&desktop_file))?;
+
+        let metainfo_dir = appdir.join("usr").join("share").join("metainfo");
+        fs::create_dir_all(&metainfo_dir)?;
+        fs::write(metainfo_dir.join(format!("{}.appdata.xml", self.project_name)), self.metainfo())?;
         
-        // Create desktop entry
-        let desktop_content = format!(
-            "[Desktop Entry]\n\
-            Name={}\n\
-            Comment=Multi-language project generated by lol\n\
-            Exec={}\n\
-            Icon={}\n\
-            Terminal=true\n\
-            Type=Application\n\
-            Categories=Development;\n",
-            self.project_name,
-            format!("{}.py", self.project_name),
-            self.project_name
-        );
-        
-        fs::write(
-            appdir.join("usr").join("share").join("applications").join(format!("{}.desktop", self.project_name)),
-            desktop_content
-        )?;
-        
-        // Create a simple icon (placeholder)
-        self.create_placeholder_icon()?;
+        match &self.icon {
+            Some(icon) => self.install_icon(icon)?,
+            None => self.create_placeholder_icon()?,
+        }
         
         // Create AppRun script
         let apprun_content = format!(
             "#!/bin/bash\n\
+            APPDIR=\"${{APPDIR:-$(dirname \"$(readlink -f \"$0\")\")}}\"\n\
+            export APPDIR\n\
+            export LD_LIBRARY_PATH=\"${{APPDIR}}/usr/lib${{LD_LIBRARY_PATH:+:$LD_LIBRARY_PATH}}\"\n\
             cd \"${{APPDIR}}/usr/bin\"\n\
-            exec \"${{APPDIR}}/usr/bin/{}.py\" \"$@\"\n",
-            self.project_name
+            exec {} \"$@\"\n",
+            entry_command
         );
         
         let apprun_path = appdir.join("AppRun");
         fs::write(&apprun_path, apprun_content)?;
         
-        // Make AppRun executable
-        let mut perms = fs::metadata(&apprun_path)?.permissions();
-        perms.set_mode(0o755);
-        fs::set_permissions(&apprun_path, perms)?;
+        set_executable(&apprun_path)?;
         
         Ok(())
     }
 
+    /// The desktop entry, whose `Exec` is `exec`, the program AppRun runs.
+    fn desktop_entry(&self, exec: &str) -> String {
+        let mut entry = format!(
+            "[Desktop Entry]\n\
+            Name={}\n\
+            Comment=Multi-language project generated by lol\n\
+            Exec={}\n\
+            Icon={}\n\
+            Terminal=true\n\
+            Type=Application\n\
+            Categories={};\n",
+            self.project_name,
+            exec,
+            self.project_name,
+            self.categories.join(";")
+        );
+        if let Some(version) = self.release_version() {
+            entry.push_str(&format!("X-AppImage-Version={}\n", version));
+        }
+        if let Some(commit) = self.build_info.as_ref().and_then(|info| info.commit.as_ref()) {
+            entry.push_str(&format!("X-Lol-Git-Commit={}\n", commit));
+        }
+        entry
+    }
+
+    fn release_version(&self) -> Option<&String> {
+        self.version.as_ref().or(self.build_info.as_ref().map(|info| &info.version))
+    }
+
+    /// AppStream metadata, which software centers show for the AppImage.
+    fn metainfo(&self) -> String {
+        let mut metainfo = format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+            <component type=\"desktop-application\">\n  \
+            <id>{id}</id>\n  \
+            <metadata_license>CC0-1.0</metadata_license>\n  \
+            <name>{name}</name>\n  \
+            <summary>Multi-language project generated by lol</summary>\n  \
+            <launchable type=\"desktop-id\">{name}.desktop</launchable>\n",
+            id = xml_escape(&self.app_id.clone().unwrap_or_else(|| format!("io.lol.{}", flatpak::id_component(&self.project_name)))),
+            name = xml_escape(&self.project_name)
+        );
+        if let Some(license) = &self.license {
+            metainfo.push_str(&format!("  <project_license>{}</project_license>\n", xml_escape(license)));
+        }
+        metainfo.push_str("  <categories>\n");
+        for category in &self.categories {
+            metainfo.push_str(&format!("    <category>{}</category>\n", xml_escape(category)));
+        }
+        metainfo.push_str("  </categories>\n");
+        if let Some(version) = self.release_version() {
+            // AppStream dates are ISO 8601 days
+            let date = self
+                .build_info
+                .as_ref()
+                .map(|info| format!(" date=\"{}\"", &crate::sbom::format_rfc3339(info.timestamp)[..10]))
+                .unwrap_or_default();
+            metainfo.push_str(&format!(
+                "  <releases>\n    <release version=\"{}\"{}/>\n  </releases>\n",
+                xml_escape(version),
+                date
+            ));
+        }
+        metainfo.push_str("</component>\n");
+        metainfo
+    }
+
+    /// Copies a user-supplied icon into the hicolor theme, at the size a
+    /// PNG declares, and to the top of the AppDir.
+    fn install_icon(&self, icon: &Path) -> Result<()> {
+        let extension = icon.extension().unwrap_or_default().to_string_lossy().to_lowercase();
+        let theme_dir = self.output_dir.join("AppDir").join("usr").join("share").join("icons").join("hicolor");
+        let size_dir = match extension.as_str() {
+            "svg" => "scalable".to_string(),
+            "png" => {
+                let content = fs::read(icon).with_context(|| format!("Failed to read icon {}", icon.display()))?;
+                let (width, height) = png_size(&content)
+                    .with_context(|| format!("{} is not a PNG image", icon.display()))?;
+                format!("{}x{}", width, height)
+            }
+            "xpm" => "48x48".to_string(),
+            _ => anyhow::bail!("Unsupported icon format '{}': use a PNG, SVG or XPM file", icon.display()),
+        };
+
+        let icon_file = format!("{}.{}", self.project_name, extension);
+        let apps_dir = theme_dir.join(size_dir).join("apps");
+        fs::create_dir_all(&apps_dir)?;
+        fs::copy(icon, apps_dir.join(&icon_file)).with_context(|| format!("Failed to copy icon {}", icon.display()))?;
+        fs::copy(icon, self.output_dir.join("AppDir").join(&icon_file))?;
+        self.link_dir_icon(&icon_file)
+    }
+
+    fn link_dir_icon(&self, icon_file: &str) -> Result<()> {
+        let dir_icon = self.output_dir.join("AppDir").join(".DirIcon");
+        if dir_icon.symlink_metadata().is_ok() {
+            fs::remove_file(&dir_icon)?;
+        }
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(icon_file, &dir_icon)?;
+        #[cfg(not(unix))]
+        fs::copy(self.output_dir.join("AppDir").join(icon_file), &dir_icon)?;
+        Ok(())
+    }
+
+    /// A scalable icon showing the project's initial, installed into the
+    /// hicolor theme and at the top of the AppDir, with `.DirIcon` pointing
+    /// at it.
     fn create_placeholder_icon(&self) -> Result<()> {
-        // Create a simple text-based icon as placeholder
+        let initial = self.project_name.chars().next().unwrap_or('L').to_uppercase();
         let icon_content = format!(
-            "{}",
-            self.project_name.chars().next().unwrap_or('L')
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"256\" height=\"256\" viewBox=\"0 0 256 256\">\n\
+            <rect width=\"256\" height=\"256\" rx=\"32\" fill=\"#2b6cb0\"/>\n\
+            <text x=\"128\" y=\"176\" font-family=\"sans-serif\" font-size=\"160\" text-anchor=\"middle\" fill=\"#ffffff\">{}</text>\n\
+            </svg>\n",
+            initial
         );
-        
-        let icon_path = self.output_dir
-            .join("AppDir")
-            .join("usr")
-            .join("share")
-            .join("icons")
-            .join("hicolor")
-            .join("256x256")
-            .join("apps")
-            .join(format!("{}.png", self.project_name));
-        
-        // For now, just create a text file as placeholder
-        // In a real implementation, you'd generate a proper PNG icon
-        fs::write(icon_path.with_extension("txt"), icon_content)?;
-        
-        Ok(())
+
+        let appdir = self.output_dir.join("AppDir");
+        let icon_file = format!("{}.svg", self.project_name);
+        fs::write(
+            appdir.join("usr").join("share").join("icons").join("hicolor").join("scalable").join("apps").join(&icon_file),
+            &icon_content,
+        )?;
+        fs::write(appdir.join(&icon_file), icon_content)?;
+        self.link_dir_icon(&icon_file)
     }
 
     fn create_appimage(&self) -> Result<PathBuf> {
         let appdir = self.output_dir.join("AppDir");
         let appimage_path = self.output_dir.join(format!("{}.AppImage", self.project_name));
         
-        // Check if appimagetool is available
-        if Command::new("appimagetool").arg("--version").output().is_ok() {
-            // Use appimagetool if available
-            let output = Command::new("appimagetool")
-                .arg("--no-appstream")
-                .arg(&appdir)
-                .arg(&appimage_path)
-                .output()
-                .context("Failed to create AppImage with appimagetool")?;
-            
-            if !output.status.success() {
-                return Err(anyhow::anyhow!(
-                    "appimagetool failed: {}",
-                    String::from_utf8_lossy(&output.stderr)
-                ));
-            }
+        if let Some(epoch) = self.source_date_epoch {
+            reproducible::normalize_mtimes(&appdir, epoch)?;
+        }
+
+        let has_appimagetool = tool_available("appimagetool", "--version");
+        // mksquashfs is no use without a runtime to append the image to
+        let runtime = if !has_appimagetool && tool_available("mksquashfs", "-version") {
+            runtime_file()
+                .inspect_err(|error| tracing::warn!("{:#}", error))
+                .ok()
+        } else {
+            None
+        };
+
+        let package_path = if has_appimagetool {
+            self.run_appimagetool(&appdir, &appimage_path)?;
+            appimage_path.clone()
+        } else if let Some(runtime) = runtime {
+            tracing::warn!("appimagetool not found, assembling the AppImage with mksquashfs");
+            self.assemble_with_mksquashfs(&appdir, &appimage_path, &runtime)?;
+            appimage_path.clone()
         } else {
             // Fallback: create a simple tar.gz archive
-            println!("⚠️  appimagetool not found, creating archive instead");
+            tracing::warn!("Neither appimagetool nor mksquashfs with a runtime found, creating archive instead");
+            if self.update_info.is_some() {
+                tracing::warn!("Update information needs a real AppImage and was not embedded");
+            }
             self.create_fallback_archive(&appdir, &appimage_path)?;
+            appimage_path.with_extension("tar.gz")
+        };
+
+        // The .zsync and detached signature cover the finished file
+        if self.update_info.is_some() && package_path == appimage_path {
+            make_zsync(&appimage_path)?;
+        }
+        if self.sign {
+            let signature = signing::sign_detached(&package_path, self.signing_key.as_deref())?;
+            say!("🔏 Signature: {}", signature.display());
         }
         
-        Ok(appimage_path)
+        Ok(package_path)
+    }
+
+    fn run_appimagetool(&self, appdir: &Path, appimage_path: &Path) -> Result<()> {
+        let mut command = Command::new("appimagetool");
+        // Script-only AppDirs have no binaries to guess the architecture from
+        if let Some(arch) = runtime_arch() {
+            command.env("ARCH", arch);
+        }
+        // mksquashfs picks up SOURCE_DATE_EPOCH
+        if let Some(epoch) = self.source_date_epoch {
+            command.env("SOURCE_DATE_EPOCH", epoch.to_string());
+        }
+        if let Some(update_info) = &self.update_info {
+            command.arg("--updateinformation").arg(update_info);
+        }
+        if self.sign {
+            command.arg("--sign");
+            if let Some(key) = &self.signing_key {
+                command.arg("--sign-key").arg(key);
+            }
+        }
+        let output = command
+            .arg("--no-appstream")
+            .arg(appdir)
+            .arg(appimage_path)
+            .output()
+            .context("Failed to create AppImage with appimagetool")?;
+        
+        if !output.status.success() {
+            return Err(anyhow::anyhow!(
+                "appimagetool failed: {}",
+                String::from_utf8_lossy(&output.stderr)
+            ));
+        }
+        Ok(())
+    }
+
+    /// Does what appimagetool does: packs the AppDir into a squashfs image
+    /// and appends it to the AppImage runtime.
+    fn assemble_with_mksquashfs(&self, appdir: &Path, appimage_path: &Path, runtime: &Path) -> Result<()> {
+        let squashfs_path = self.output_dir.join(format!("{}.squashfs", self.project_name));
+        let _ = fs::remove_file(&squashfs_path);
+
+        let mut command = Command::new("mksquashfs");
+        if let Some(epoch) = self.source_date_epoch {
+            command.env("SOURCE_DATE_EPOCH", epoch.to_string());
+        }
+        let output = command
+            .arg(appdir)
+            .arg(&squashfs_path)
+            .args(["-root-owned", "-noappend", "-comp", "gzip"])
+            .output()
+            .context("Failed to run mksquashfs")?;
+        if !output.status.success() {
+            anyhow::bail!("mksquashfs failed: {}", String::from_utf8_lossy(&output.stderr));
+        }
+
+        let mut image = fs::read(runtime).with_context(|| format!("Failed to read {}", runtime.display()))?;
+        if let Some(update_info) = &self.update_info {
+            embed_in_section(&mut image, ".upd_info", update_info.as_bytes())?;
+        }
+        if self.sign {
+            signing::embed_signature(&mut image, self.signing_key.as_deref())?;
+        }
+        image.extend(fs::read(&squashfs_path)?);
+        fs::write(appimage_path, image)
+            .with_context(|| format!("Failed to write {}", appimage_path.display()))?;
+        fs::remove_file(&squashfs_path)?;
+
+        set_executable(appimage_path)?;
+        Ok(())
     }
 
     fn create_fallback_archive(&self, appdir: &Path, output_path: &Path) -> Result<()> {
         let archive_path = output_path.with_extension("tar.gz");
         
+        if let Some(epoch) = self.source_date_epoch {
+            reproducible::tar_gz(appdir, &archive_path, epoch)?;
+            say!("📦 Created archive: {}", archive_path.display());
+            return Ok(());
+        }
+
         let output = Command::new("tar")
             .arg("-czf")
             .arg(&archive_path)
@@ -233,7 +1077,7 @@ impl AppImageBuilder {
             ));
         }
         
-        println!("📦 Created archive: {}", archive_path.display());
+        say!("📦 Created archive: {}", archive_path.display());
         Ok(())
     }
 
@@ -242,7 +1086,7 @@ impl AppImageBuilder {
         summary.push_str(&"=".repeat(50));
         summary.push('\n');
         
-        for (language, files) in &self.source_files {
+        for (language, files) in self.sorted_sources() {
             summary.push_str(&format!("\n🔧 {}: {} files\n", language.name(), files.len()));
             for file in files {
                 if let Ok(content) = fs::read_to_string(file) {
@@ -255,4 +1099,457 @@ impl AppImageBuilder {
         
         summary
     }
-} 
\ No newline at end of file
+}
+
+/// Libraries that come with glibc or the graphics driver. Bundling them
+/// breaks the AppImage on systems with a different version.
+const EXCLUDED_LIBRARIES: &[&str] = &[
+    "linux-vdso", "linux-gate", "ld-linux", "libc.so", "libm.so", "libdl.so", "libpthread.so",
+    "librt.so", "libresolv.so", "libutil.so", "libnsl.so", "libanl.so", "libmvec.so",
+    "libBrokenLocale.so", "libGL.so", "libGLX.so", "libEGL.so", "libGLdispatch.so", "libdrm.so",
+];
+
+/// Makes `path` executable. Windows goes by the file extension instead,
+/// so there is nothing to do there.
+pub fn set_executable(path: &Path) -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o755))
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = path;
+        Ok(())
+    }
+}
+
+/// Whether `path` is an ELF executable (not a shared library) lol should
+/// bundle, or on Windows a PE `.exe`.
+pub fn is_executable(path: &Path) -> bool {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    if name.contains(".so") || name.ends_with(".o") {
+        return false;
+    }
+    let mut magic = [0u8; 4];
+    let read = fs::metadata(path).is_ok_and(|metadata| metadata.is_file())
+        && fs::File::open(path)
+            .and_then(|mut file| std::io::Read::read_exact(&mut file, &mut magic))
+            .is_ok();
+    if cfg!(windows) {
+        return read && path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("exe")) && magic[..2] == *b"MZ";
+    }
+    #[cfg(unix)]
+    let read = read && {
+        use std::os::unix::fs::PermissionsExt;
+        fs::metadata(path).is_ok_and(|metadata| metadata.permissions().mode() & 0o111 != 0)
+    };
+    read && magic == *b"\x7fELF"
+}
+
+/// The shared libraries `binary` loads, resolved by `ldd`.
+fn shared_libraries(binary: &Path) -> Vec<PathBuf> {
+    match Command::new("ldd").arg(binary).output() {
+        Ok(output) if output.status.success() => parse_ldd(&String::from_utf8_lossy(&output.stdout)),
+        _ => Vec::new(),
+    }
+}
+
+/// Resolved paths from `ldd` output (`libfoo.so.1 => /usr/lib/libfoo.so.1 (0x...)`),
+/// without the excluded system libraries.
+fn parse_ldd(output: &str) -> Vec<PathBuf> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (name, resolved) = line.trim().split_once(" => ")?;
+            if EXCLUDED_LIBRARIES.iter().any(|excluded| name.starts_with(excluded)) {
+                return None;
+            }
+            let path = resolved.split(" (").next()?.trim();
+            path.starts_with('/').then(|| PathBuf::from(path))
+        })
+        .collect()
+}
+
+/// The file an AppRun command runs, from its last `${APPDIR}` path: the
+/// executable, or the script handed to a bundled interpreter.
+fn exec_name(entry_command: &str) -> String {
+    let program = match entry_command.rsplit_once("${APPDIR}/") {
+        Some((_, path)) => path.split('"').next().unwrap_or(path),
+        None => entry_command.split_whitespace().next().unwrap_or(entry_command).trim_matches('"'),
+    };
+    Path::new(program).file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_else(|| program.to_string())
+}
+
+/// Runs `desktop-file-validate` on `path` when it is installed.
+fn validate_desktop_file(path: &Path) -> Result<()> {
+    let Ok(output) = Command::new("desktop-file-validate").arg(path).output() else {
+        return Ok(());
+    };
+    if !output.status.success() {
+        anyhow::bail!(
+            "Invalid desktop entry {}:\n{}",
+            path.display(),
+            String::from_utf8_lossy(&output.stdout).trim()
+        );
+    }
+    Ok(())
+}
+
+/// Width and height from a PNG's IHDR chunk.
+fn png_size(content: &[u8]) -> Option<(u32, u32)> {
+    if content.len() < 24 || &content[..8] != b"\x89PNG\r\n\x1a\n" || &content[12..16] != b"IHDR" {
+        return None;
+    }
+    let width = u32::from_be_bytes(content[16..20].try_into().ok()?);
+    let height = u32::from_be_bytes(content[20..24].try_into().ok()?);
+    Some((width, height))
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// Checks update information against the two transports AppImageUpdate
+/// understands.
+fn validate_update_info(update_info: &str) -> Result<()> {
+    let fields: Vec<&str> = update_info.split('|').collect();
+    let valid = match fields[0] {
+        "zsync" => fields.len() == 2 && fields[1].starts_with("http"),
+        "gh-releases-zsync" => fields.len() == 5 && fields[1..].iter().all(|field| !field.is_empty()),
+        _ => false,
+    };
+    if !valid {
+        anyhow::bail!(
+            "Invalid update information '{}': expected zsync|<url> or gh-releases-zsync|<user>|<repo>|<tag>|<file pattern>",
+            update_info
+        );
+    }
+    Ok(())
+}
+
+/// Writes `<appimage>.zsync` next to the AppImage, unless appimagetool
+/// already did.
+fn make_zsync(appimage_path: &Path) -> Result<()> {
+    let mut zsync_path = appimage_path.as_os_str().to_owned();
+    zsync_path.push(".zsync");
+    let zsync_path = PathBuf::from(zsync_path);
+    if zsync_path.is_file() {
+        say!("🔄 Update file: {}", zsync_path.display());
+        return Ok(());
+    }
+
+    let file_name = appimage_path.file_name().unwrap_or_default();
+    let output = match Command::new("zsyncmake")
+        .arg("-u")
+        .arg(file_name)
+        .arg("-o")
+        .arg(&zsync_path)
+        .arg(appimage_path)
+        .output()
+    {
+        Ok(output) => output,
+        Err(_) => {
+            tracing::warn!("zsyncmake not found, no .zsync file was written");
+            return Ok(());
+        }
+    };
+    if !output.status.success() {
+        anyhow::bail!("zsyncmake failed: {}", String::from_utf8_lossy(&output.stderr));
+    }
+    say!("🔄 Update file: {}", zsync_path.display());
+    Ok(())
+}
+
+/// Overwrites the ELF section `name` of `image` with `data`, zero-padded,
+/// the way appimagetool fills the runtime's reserved `.upd_info` section.
+pub fn embed_in_section(image: &mut [u8], name: &str, data: &[u8]) -> Result<()> {
+    let (offset, size) = find_section(image, name)
+        .with_context(|| format!("The AppImage runtime has no {} section", name))?;
+    if data.len() >= size {
+        anyhow::bail!("{} does not fit into the {}-byte {} section", String::from_utf8_lossy(data), size, name);
+    }
+    let section = &mut image[offset..offset + size];
+    section.fill(0);
+    section[..data.len()].copy_from_slice(data);
+    Ok(())
+}
+
+/// File offset and size of a section in a little-endian ELF32/ELF64 file.
+pub fn find_section(elf: &[u8], name: &str) -> Option<(usize, usize)> {
+    if elf.get(..4)? != b"\x7fELF" || *elf.get(5)? != 1 {
+        return None;
+    }
+    let is_64 = *elf.get(4)? == 2;
+    let read = |offset: usize, width: usize| -> Option<usize> {
+        let bytes = elf.get(offset..offset + width)?;
+        Some(bytes.iter().rev().fold(0usize, |value, byte| (value << 8) | *byte as usize))
+    };
+    let (section_headers, entry_size, count, names_index) = if is_64 {
+        (read(0x28, 8)?, read(0x3A, 2)?, read(0x3C, 2)?, read(0x3E, 2)?)
+    } else {
+        (read(0x20, 4)?, read(0x2E, 2)?, read(0x30, 2)?, read(0x32, 2)?)
+    };
+    let (offset_field, size_field, width) = if is_64 { (0x18, 0x20, 8) } else { (0x10, 0x14, 4) };
+    let header = |index: usize| section_headers + index * entry_size;
+    let names_offset = read(header(names_index) + offset_field, width)?;
+
+    (0..count).find_map(|index| {
+        let name_start = names_offset + read(header(index), 4)?;
+        let name_end = name_start + elf.get(name_start..)?.iter().position(|byte| *byte == 0)?;
+        if &elf[name_start..name_end] != name.as_bytes() {
+            return None;
+        }
+        Some((read(header(index) + offset_field, width)?, read(header(index) + size_field, width)?))
+    })
+}
+
+/// The AppImage runtime for each architecture: a file from a tagged
+/// type2-runtime release, never `continuous`, and its SHA-256. A runtime
+/// whose checksum does not match is refused, whether downloaded or cached.
+/// On an architecture missing here, AppImages need appimagetool; with only
+/// mksquashfs installed, the AppDir is archived instead.
+const RUNTIMES: &[(&str, &str, &str)] = &[];
+
+fn tool_available(tool: &str, version_flag: &str) -> bool {
+    Command::new(tool).arg(version_flag).output().is_ok()
+}
+
+/// The AppImage name for the host architecture.
+fn runtime_arch() -> Option<&'static str> {
+    match std::env::consts::ARCH {
+        "x86_64" => Some("x86_64"),
+        "aarch64" => Some("aarch64"),
+        "x86" => Some("i686"),
+        "arm" => Some("armhf"),
+        _ => None,
+    }
+}
+
+/// The pinned runtime URL and SHA-256 for `arch`.
+fn pinned_runtime(arch: &str) -> Option<(&'static str, &'static str)> {
+    RUNTIMES
+        .iter()
+        .find(|(runtime_arch, _, _)| *runtime_arch == arch)
+        .map(|&(_, url, sha256)| (url, sha256))
+}
+
+/// The AppImage runtime for the host, downloaded into the cache directory
+/// the first time it is needed.
+fn runtime_file() -> Result<PathBuf> {
+    let arch = runtime_arch()
+        .with_context(|| format!("No AppImage runtime is available for {}", std::env::consts::ARCH))?;
+    let (url, sha256) = pinned_runtime(arch)
+        .with_context(|| format!("lol has no pinned AppImage runtime for {}; install appimagetool to build AppImages", arch))?;
+    let cache_dir = dirs::cache_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("lol");
+    // Named by checksum, so a runtime cached from another release is not reused
+    let runtime = cache_dir.join(format!("runtime-{}-{}", arch, &sha256[..12]));
+    if runtime.is_file() {
+        verify_runtime(&runtime, sha256)?;
+        return Ok(runtime);
+    }
+
+    fs::create_dir_all(&cache_dir)?;
+    say!("⬇️  Downloading the AppImage runtime from {}", url);
+    let partial = runtime.with_extension("part");
+    let downloaded = Command::new("curl")
+        .args(["-fsSL", "-o"])
+        .arg(&partial)
+        .arg(url)
+        .status()
+        .or_else(|_| Command::new("wget").args(["-q", "-O"]).arg(&partial).arg(url).status())
+        .context("Downloading the AppImage runtime needs curl or wget")?;
+    if !downloaded.success() {
+        let _ = fs::remove_file(&partial);
+        anyhow::bail!("Failed to download the AppImage runtime from {}", url);
+    }
+    if let Err(error) = verify_runtime(&partial, sha256) {
+        let _ = fs::remove_file(&partial);
+        return Err(error);
+    }
+    fs::rename(&partial, &runtime)?;
+    Ok(runtime)
+}
+
+/// Fails unless the runtime at `path` has the pinned checksum.
+fn verify_runtime(path: &Path, expected: &str) -> Result<()> {
+    let actual = artifacts::sha256(path)?;
+    if actual != expected {
+        anyhow::bail!(
+            "Refusing to use the AppImage runtime {}: its SHA-256 is {}, but lol expects {}",
+            path.display(),
+            actual,
+            expected
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_appdir_has_desktop_entry_icon_and_apprun_at_top_level() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut builder = AppImageBuilder::new("demo".to_string(), HashMap::new());
+        builder.output_dir = temp_dir.path().to_path_buf();
+
+        builder.create_directory_structure().unwrap();
+        builder.create_appimage_structure(Path::new("demo.py"), None).unwrap();
+
+        let appdir = temp_dir.path().join("AppDir");
+        let desktop = fs::read_to_string(appdir.join("demo.desktop")).unwrap();
+        assert!(desktop.contains("Icon=demo\n"));
+        assert!(desktop.contains("Exec=demo.py\n"));
+        assert!(fs::read_to_string(appdir.join("demo.svg")).unwrap().contains(">D</text>"));
+        assert_eq!(fs::read_link(appdir.join(".DirIcon")).unwrap(), Path::new("demo.svg"));
+        assert!(appdir.join("AppRun").is_file());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let apprun = fs::metadata(appdir.join("AppRun")).unwrap();
+            assert_eq!(apprun.permissions().mode() & 0o111, 0o111);
+        }
+    }
+
+    #[test]
+    fn test_custom_icon_categories_and_version() {
+        let temp_dir = TempDir::new().unwrap();
+        let icon = temp_dir.path().join("logo.png");
+        let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR".to_vec();
+        png.extend(128u32.to_be_bytes());
+        png.extend(128u32.to_be_bytes());
+        fs::write(&icon, png).unwrap();
+
+        let mut builder = AppImageBuilder::new("demo".to_string(), HashMap::new())
+            .with_icon(icon)
+            .with_categories(vec!["Utility".to_string(), "Office".to_string()])
+            .with_version("1.2.0".to_string())
+            .with_license("MIT".to_string());
+        builder.output_dir = temp_dir.path().join("out");
+        builder.create_directory_structure().unwrap();
+        builder.create_appimage_structure(Path::new("demo.py"), Some("\"${APPDIR}/usr/bin/demo-cli\"")).unwrap();
+
+        let appdir = builder.output_dir.join("AppDir");
+        let desktop = fs::read_to_string(appdir.join("demo.desktop")).unwrap();
+        assert!(desktop.contains("Categories=Utility;Office;\n"));
+        assert!(desktop.contains("X-AppImage-Version=1.2.0\n"));
+        assert!(desktop.contains("Exec=demo-cli\n"));
+        assert!(appdir.join("usr/share/icons/hicolor/128x128/apps/demo.png").is_file());
+        assert_eq!(fs::read_link(appdir.join(".DirIcon")).unwrap(), Path::new("demo.png"));
+        let metainfo = fs::read_to_string(appdir.join("usr/share/metainfo/demo.appdata.xml")).unwrap();
+        assert!(metainfo.contains("<id>io.lol.demo</id>"));
+        assert!(metainfo.contains("<project_license>MIT</project_license>"));
+        assert!(metainfo.contains("<release version=\"1.2.0\"/>"));
+    }
+
+    #[test]
+    fn test_binaries_sharing_a_name_are_refused() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut builder = AppImageBuilder::new("demo".to_string(), HashMap::new());
+        builder.output_dir = temp_dir.path().join("out");
+        builder.create_directory_structure().unwrap();
+        for dir in ["a", "b"] {
+            fs::create_dir_all(temp_dir.path().join(dir)).unwrap();
+            fs::write(temp_dir.path().join(dir).join("tool"), "#!/bin/sh\n").unwrap();
+        }
+
+        builder.binaries = vec![temp_dir.path().join("a").join("tool")];
+        assert!(builder.bundle_binaries().is_ok());
+        builder.binaries.push(temp_dir.path().join("b").join("tool"));
+        let error = builder.bundle_binaries().unwrap_err().to_string();
+        assert!(error.contains("would both be usr/bin/tool"), "{}", error);
+    }
+
+    #[test]
+    fn test_runtime_must_match_its_pinned_checksum() {
+        for (arch, url, sha256) in RUNTIMES {
+            assert!(["x86_64", "aarch64", "i686", "armhf"].contains(arch), "{} is not an AppImage architecture", arch);
+            assert!(!url.contains("/continuous/"), "{} is not a tagged release", url);
+            assert_eq!(sha256.len(), 64);
+        }
+        // create_appimage archives the AppDir when this fails
+        if runtime_arch().and_then(pinned_runtime).is_none() {
+            let error = runtime_file().unwrap_err().to_string();
+            assert!(error.contains("install appimagetool"), "{}", error);
+        }
+
+        let temp_dir = TempDir::new().unwrap();
+        let runtime = temp_dir.path().join("runtime-x86_64");
+        fs::write(&runtime, "runtime").unwrap();
+        let expected = artifacts::sha256(&runtime).unwrap();
+        assert!(verify_runtime(&runtime, &expected).is_ok());
+        fs::write(&runtime, "tampered").unwrap();
+        let error = verify_runtime(&runtime, &expected).unwrap_err().to_string();
+        assert!(error.starts_with("Refusing to use the AppImage runtime"), "{}", error);
+    }
+
+    #[test]
+    fn test_update_info_is_embedded_in_runtime_section() {
+        assert!(validate_update_info("gh-releases-zsync|acme|demo|latest|demo-*x86_64.AppImage.zsync").is_ok());
+        assert!(validate_update_info("zsync|https://example.com/demo.AppImage.zsync").is_ok());
+        assert!(validate_update_info("zsync").is_err());
+        assert!(validate_update_info("bintray-zsync|acme|demo").is_err());
+
+        // Minimal ELF64: a null section, .shstrtab and a 16-byte .upd_info
+        let names = b"\0.shstrtab\0.upd_info\0";
+        let mut elf = vec![0u8; 64];
+        elf[..6].copy_from_slice(b"\x7fELF\x02\x01");
+        let names_offset = elf.len();
+        elf.extend(names);
+        let section_offset = elf.len();
+        elf.extend([0xAAu8; 16]);
+        let headers_offset = elf.len();
+        elf[0x28..0x30].copy_from_slice(&(headers_offset as u64).to_le_bytes());
+        elf[0x3A..0x3C].copy_from_slice(&64u16.to_le_bytes());
+        elf[0x3C..0x3E].copy_from_slice(&3u16.to_le_bytes());
+        elf[0x3E..0x40].copy_from_slice(&1u16.to_le_bytes());
+        for (name, offset, size) in [(0u32, 0u64, 0u64), (1, names_offset as u64, names.len() as u64), (11, section_offset as u64, 16)] {
+            let mut header = vec![0u8; 64];
+            header[..4].copy_from_slice(&name.to_le_bytes());
+            header[0x18..0x20].copy_from_slice(&offset.to_le_bytes());
+            header[0x20..0x28].copy_from_slice(&size.to_le_bytes());
+            elf.extend(header);
+        }
+
+        embed_in_section(&mut elf, ".upd_info", b"zsync|http://x").unwrap();
+        assert_eq!(&elf[section_offset..section_offset + 16], b"zsync|http://x\0\0");
+        assert!(embed_in_section(&mut elf, ".upd_info", b"zsync|https://example.com/x").is_err());
+    }
+
+    #[test]
+    fn test_interpreter_bundler_selection() {
+        let mut sources = HashMap::new();
+        sources.insert(Language::Python, vec![PathBuf::from("main.py"), PathBuf::from("util.py")]);
+        sources.insert(Language::C, vec![PathBuf::from("fast.c")]);
+        assert!(is_dominant(&sources, &[Language::Python]));
+        sources.insert(Language::C, vec![PathBuf::from("fast.c"), PathBuf::from("faster.c")]);
+        assert!(!is_dominant(&sources, &[Language::Python]));
+        assert!(!is_dominant(&sources, &[Language::JavaScript, Language::TypeScript]));
+        sources.insert(Language::JavaScript, vec![PathBuf::from("index.js")]);
+        sources.insert(Language::TypeScript, vec![PathBuf::from("a.ts"), PathBuf::from("b.ts")]);
+        assert!(is_dominant(&sources, &[Language::JavaScript, Language::TypeScript]));
+
+        let python = Toolchain::new("python3");
+        assert_eq!(PythonBundler::select(Some("pyinstaller"), &python).unwrap(), Some(PythonBundler::PyInstaller));
+        assert_eq!(PythonBundler::select(Some("system"), &python).unwrap(), None);
+        assert!(PythonBundler::select(Some("nuitka"), &python).is_err());
+        assert_eq!(NodeBundler::select(Some("sea"), &NodeEnvironment::default()).unwrap(), Some(NodeBundler::Sea));
+        assert!(NodeBundler::select(Some("nexe"), &NodeEnvironment::default()).is_err());
+    }
+
+    #[test]
+    fn test_parse_ldd_skips_system_libraries() {
+        let output = "\tlinux-vdso.so.1 (0x00007ffd)\n\
+            \tlibssl.so.3 => /usr/lib/x86_64-linux-gnu/libssl.so.3 (0x00007f01)\n\
+            \tlibc.so.6 => /lib/x86_64-linux-gnu/libc.so.6 (0x00007f02)\n\
+            \tlibm.so.6 => /lib/x86_64-linux-gnu/libm.so.6 (0x00007f03)\n\
+            \tlibmissing.so.1 => not found\n\
+            \t/lib64/ld-linux-x86-64.so.2 (0x00007f04)\n";
+        assert_eq!(parse_ldd(output), [PathBuf::from("/usr/lib/x86_64-linux-gnu/libssl.so.3")]);
+    }
+}
@@ -1,7 +1,13 @@
-use clap::Parser;
+use clap::{Parser, Subcommand, ValueEnum};
+use clap_complete::engine::ArgValueCandidates;
+use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
+use crate::dev_env::DevEnvironment;
+use crate::logging::{LogFormat, LogLevel};
+use crate::package::PackageFormat;
+use crate::sbom::SbomFormat;
 
-#[derive(Parser, Debug)]
+#[derive(Parser, Debug, Clone)]
 #[command(
     name = "lol",
     about = "The Fast Multi-language Code Compiler CLI App",
@@ -10,8 +16,11 @@ use std::path::PathBuf;
     author
 )]
 pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
     /// Project directory to compile or create AppImage from
-    #[arg(value_name = "PROJECT_PATH")]
+    #[arg(value_name = "PROJECT_PATH", default_value = ".")]
     pub project_path: PathBuf,
 
     /// Compile C files
@@ -50,14 +59,78 @@ pub struct Args {
     #[arg(long)]
     pub all: bool,
 
+    /// Compile these languages, by their `language_settings` key, e.g. `kotlin,zig` or a custom language
+    #[arg(long = "lang", value_name = "LANGUAGE", value_delimiter = ',', add = ArgValueCandidates::new(crate::completions::languages))]
+    pub languages: Vec<String>,
+
     /// Show verbose output
     #[arg(short, long)]
     pub verbose: bool,
 
+    /// Only print errors
+    #[arg(short, long, global = true)]
+    pub quiet: bool,
+
+    /// Leave emoji out of the output
+    #[arg(long, global = true)]
+    pub no_emoji: bool,
+
+    /// Record nothing about this run, whatever the configuration says
+    #[arg(long, global = true)]
+    pub no_telemetry: bool,
+
+    /// Most detailed log events to show
+    #[arg(long, value_enum, default_value_t = LogLevel::Warn, global = true)]
+    pub log_level: LogLevel,
+
+    /// Write log events to this file instead of stderr
+    #[arg(long, value_name = "PATH", global = true)]
+    pub log_file: Option<PathBuf>,
+
+    /// How log events are written
+    #[arg(long, value_enum, default_value_t = LogFormat::Pretty, global = true)]
+    pub log_format: LogFormat,
+
+    /// Print compiler output as it happens, each line prefixed with `[language:file]`
+    #[arg(long)]
+    pub stream_output: bool,
+
+    /// List every diagnostic in the summary, instead of each distinct message once with a count
+    #[arg(long)]
+    pub show_all_duplicates: bool,
+
+    /// List at most this many errors in the summary; the full output goes to lol-errors.log in the output directory
+    #[arg(long, value_name = "N")]
+    pub max_errors: Option<usize>,
+
+    /// Fail the build when a language's compiler is not installed, instead of skipping that language
+    #[arg(long)]
+    pub strict_toolchains: bool,
+
+    /// Compile again, up to N times, when a failure looks transient (see `retry_patterns`)
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    pub retries: u32,
+
+    /// Seconds to wait before the first retry, doubling for each one after it
+    #[arg(long, value_name = "SECONDS", requires = "retries")]
+    pub retry_backoff: Option<u64>,
+
     /// Number of parallel compilation jobs
     #[arg(short, long, default_value_t = num_cpus::get())]
     pub jobs: usize,
 
+    /// Number of parallel syntax checks (Python, JavaScript, Ruby, ...), which wait apart from compiles
+    #[arg(long, value_name = "N", default_value_t = num_cpus::get() * 4)]
+    pub check_jobs: usize,
+
+    /// Run compilers at this nice level, from -20 to 19 (see `priority`)
+    #[arg(long, value_name = "N", allow_hyphen_values = true, value_parser = clap::value_parser!(i32).range(-20..=19))]
+    pub nice: Option<i32>,
+
+    /// Pin compilers to these CPUs, e.g. `0-3,6`; on Linux only
+    #[arg(long, value_name = "LIST")]
+    pub cpus: Option<String>,
+
     /// Custom compiler flags for C/C++
     #[arg(long, value_name = "FLAGS", allow_hyphen_values = true)]
     pub cflags: Option<String>,
@@ -66,7 +139,378 @@ pub struct Args {
     #[arg(long, value_name = "FLAGS", allow_hyphen_values = true)]
     pub cxxflags: Option<String>,
 
-    /// Create an AppImage with consolidated source code (instead of compiling)
+    /// Build profile, mapped onto each language's optimization switches
+    #[arg(long, value_enum, default_value_t = Profile::Debug)]
+    pub profile: Profile,
+
+    /// Cross-compile Rust, C and Zig for another target instead of the host
+    #[arg(long, value_enum, value_name = "TARGET")]
+    pub target: Option<Target>,
+
+    /// Build every combination of the given axes, e.g. `--matrix profile=debug,release --matrix target=host,wasm32`
+    #[arg(long, value_name = "AXIS=VALUES")]
+    pub matrix: Vec<String>,
+
+    /// Produce bit-identical artifacts: fixed timestamps, remapped source paths, sorted inputs
+    #[arg(long)]
+    pub reproducible: bool,
+
+    /// Fail when a toolchain's version differs from the one recorded in lol.lock
+    #[arg(long)]
+    pub locked: bool,
+
+    /// Write every build output under this directory and fail if anything is written into the source tree
+    #[arg(long, value_name = "PATH")]
+    pub build_dir: Option<PathBuf>,
+
+    /// Run compilers in the project's Nix shell or devcontainer [default: the devcontainer, if there is one]
+    #[arg(long, value_enum, value_name = "ENV")]
+    pub env: Option<DevEnvironment>,
+
+    /// Inject version, git commit and build time into the build and record them in its manifests
+    #[arg(long)]
+    pub version_stamp: bool,
+
+    /// Build the project and package it as an AppImage
     #[arg(long, value_name = "NAME")]
     pub name: Option<String>,
-} 
\ No newline at end of file
+
+    /// Source file the AppImage runs, instead of the detected entry point
+    #[arg(long, value_name = "FILE", requires = "name")]
+    pub entry: Option<PathBuf>,
+
+    /// Icon for the AppImage (PNG, SVG or XPM)
+    #[arg(long, value_name = "PATH", requires = "name")]
+    pub icon: Option<PathBuf>,
+
+    /// Desktop entry categories for the AppImage, e.g. `Development,Utility`
+    #[arg(long, value_name = "CATEGORIES", value_delimiter = ',', requires = "name")]
+    pub desktop_categories: Vec<String>,
+
+    /// Version recorded in the AppImage's desktop entry and AppStream metadata
+    #[arg(long, value_name = "VERSION", requires = "name")]
+    pub app_version: Option<String>,
+
+    /// Embed AppImageUpdate information and write a .zsync file, e.g. `gh-releases-zsync|user|repo|latest|App-*.AppImage.zsync`
+    #[arg(long, value_name = "INFO", requires = "name")]
+    pub update_info: Option<String>,
+
+    /// Sign the AppImage with GPG, using KEYID or the first secret key
+    #[arg(long, value_name = "KEYID", num_args = 0..=1, default_missing_value = "", requires = "name")]
+    pub sign: Option<String>,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum Command {
+    /// Remove the output directory, including generated code
+    Clean {
+        /// Project directory to clean
+        #[arg(value_name = "PROJECT_PATH", default_value = ".")]
+        project_path: PathBuf,
+    },
+    /// Maintain the global and project config files
+    Config {
+        #[command(subcommand)]
+        action: ConfigCommand,
+    },
+    /// Check source files against the license header policy
+    License {
+        #[command(subcommand)]
+        action: LicenseCommand,
+    },
+    /// Build the project and bundle its outputs into a versioned archive in dist/
+    Package {
+        /// Project directory to package
+        #[arg(value_name = "PROJECT_PATH", default_value = ".")]
+        project_path: PathBuf,
+
+        /// Archive format
+        #[arg(long, value_enum, default_value_t = PackageFormat::TarGz)]
+        format: PackageFormat,
+    },
+    /// Rebuild whenever a source file changes
+    Watch {
+        /// Project directory to watch
+        #[arg(value_name = "PROJECT_PATH", default_value = ".")]
+        project_path: PathBuf,
+
+        /// Keep this script running, restarting it after each passing rebuild
+        #[arg(long, value_name = "ENTRY")]
+        serve: Option<PathBuf>,
+
+        /// Show an interactive UI with per-language status and diagnostics
+        #[arg(long, conflicts_with = "serve")]
+        tui: bool,
+
+        /// Serve Prometheus metrics at http://127.0.0.1:PORT/metrics
+        #[arg(long, value_name = "PORT", conflicts_with = "tui")]
+        metrics_port: Option<u16>,
+    },
+    /// Serve build diagnostics to editors over the Language Server Protocol on stdio
+    Lsp {
+        /// Project directory, unless the editor sends its own root
+        #[arg(value_name = "PROJECT_PATH", default_value = ".")]
+        project_path: PathBuf,
+    },
+    /// Run a build server with an HTTP API for IDE plugins and other tools
+    Daemon {
+        /// Address to listen on; keep it local unless the network is trusted
+        #[arg(long, default_value = "127.0.0.1")]
+        host: String,
+
+        /// Port to listen on
+        #[arg(long, default_value_t = crate::daemon::DEFAULT_PORT)]
+        port: u16,
+
+        /// Serve a web dashboard of live and past builds at /
+        #[arg(long)]
+        dashboard: bool,
+    },
+    /// Inspect or cancel the builds waiting in a running `lol daemon`
+    Queue {
+        #[command(subcommand)]
+        action: QueueCommand,
+    },
+    /// Upload the packages, AppImages and their checksums to the configured destinations
+    Publish {
+        /// Project directory whose dist/ to publish
+        #[arg(value_name = "PROJECT_PATH", default_value = ".")]
+        project_path: PathBuf,
+
+        /// Release tag [default: v<version>]
+        #[arg(long)]
+        tag: Option<String>,
+
+        /// List what would be uploaded without uploading
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Check an AppImage's or artifact's signatures and its artifact manifest checksum
+    Verify {
+        /// AppImage or other artifact to check
+        #[arg(value_name = "FILE")]
+        path: PathBuf,
+
+        /// Fingerprint of a key to accept signatures from, on top of `trusted_keys`
+        #[arg(long = "trusted-key", value_name = "FINGERPRINT")]
+        trusted_keys: Vec<String>,
+    },
+    /// Detect the project's languages and toolchains and write its lol.json
+    Init {
+        /// Project directory to set up
+        #[arg(value_name = "PROJECT_PATH", default_value = ".")]
+        project_path: PathBuf,
+
+        /// Accept every detected language and proposed ignore pattern without asking
+        #[arg(short, long)]
+        yes: bool,
+
+        /// Replace an existing configuration
+        #[arg(long)]
+        force: bool,
+
+        /// Write the default global configuration instead, for editing
+        #[arg(long, conflicts_with = "yes")]
+        global: bool,
+    },
+    /// List the supported languages with their toolchains and flags
+    Languages {
+        /// Project whose configuration supplies the flags
+        #[arg(value_name = "PROJECT_PATH", default_value = ".")]
+        project_path: PathBuf,
+
+        /// Print JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+    /// Check that the toolchains the project needs are installed
+    Doctor {
+        /// Project directory to check
+        #[arg(value_name = "PROJECT_PATH", default_value = ".")]
+        project_path: PathBuf,
+
+        /// Install missing Rust, Zig and Nim toolchains for the current user
+        #[arg(long)]
+        fix: bool,
+    },
+    /// Create a hello-world project to try lol with
+    New {
+        /// Language of the project, e.g. rust or python
+        #[arg(add = ArgValueCandidates::new(crate::completions::languages))]
+        language: String,
+
+        /// Name of the directory to create
+        name: String,
+    },
+    /// Print a shell completion script, e.g. `source <(lol completions bash)`
+    Completions {
+        #[arg(value_enum)]
+        shell: crate::completions::Shell,
+    },
+    /// List past builds recorded with `history` on, or show one of them
+    History {
+        #[command(subcommand)]
+        action: Option<HistoryCommand>,
+
+        /// Project whose builds to list
+        #[arg(value_name = "PROJECT_PATH", default_value = ".")]
+        project_path: PathBuf,
+
+        /// How many of the latest builds to list
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+    },
+    /// Export the recorded build history, or turn recording on or off
+    Analytics {
+        #[command(subcommand)]
+        action: AnalyticsCommand,
+    },
+    /// Compare the latest recorded build with an earlier one, for time regressions and new failures
+    Compare {
+        /// Project whose builds to compare
+        #[arg(value_name = "PROJECT_PATH", default_value = ".")]
+        project_path: PathBuf,
+
+        /// Build to compare with: a `lol history` ID or a git ref [default: the build before]
+        #[arg(long, value_name = "ID|REF")]
+        baseline: Option<String>,
+
+        /// Fail when anything newly fails or gets slower by more than PCT percent
+        #[arg(long, value_name = "PCT")]
+        fail_on_regression: Option<f64>,
+    },
+    /// Write a software bill of materials for the project
+    Sbom {
+        /// Project directory to describe
+        #[arg(value_name = "PROJECT_PATH", default_value = ".")]
+        project_path: PathBuf,
+
+        /// Document format
+        #[arg(long, value_enum, default_value_t = SbomFormat::Cyclonedx)]
+        format: SbomFormat,
+
+        /// Where to write the document [default: <output_directory>/sbom.cdx.json or sbom.spdx.json]
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum LicenseCommand {
+    /// Report source files without the configured license header
+    Check {
+        /// Project directory to check
+        #[arg(value_name = "PROJECT_PATH", default_value = ".")]
+        project_path: PathBuf,
+
+        /// Insert missing headers instead of failing
+        #[arg(long)]
+        fix: bool,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum ConfigCommand {
+    /// Rewrite ignore and include patterns written for the old matcher as globs,
+    /// and optionally convert the config files to another format
+    Migrate {
+        /// Project whose lol.json to migrate along with the global config
+        #[arg(value_name = "PROJECT_PATH", default_value = ".")]
+        project_path: PathBuf,
+
+        /// Convert the config files to this format, replacing the old ones
+        #[arg(long, value_name = "FORMAT")]
+        to: Option<crate::config::ConfigFormat>,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum QueueCommand {
+    /// List the running build and the waiting ones, in the order they will run
+    Status {
+        /// Address the daemon listens on
+        #[arg(long, default_value = "127.0.0.1")]
+        host: String,
+
+        #[arg(long, default_value_t = crate::daemon::DEFAULT_PORT)]
+        port: u16,
+    },
+    /// Take a build out of the queue before it starts
+    Cancel {
+        /// The build's ID, as `lol queue status` lists it
+        id: u64,
+
+        /// Address the daemon listens on
+        #[arg(long, default_value = "127.0.0.1")]
+        host: String,
+
+        #[arg(long, default_value_t = crate::daemon::DEFAULT_PORT)]
+        port: u16,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum AnalyticsCommand {
+    /// Write every recorded build as CSV or JSON
+    Export {
+        /// Project whose history to export
+        #[arg(value_name = "PROJECT_PATH", default_value = ".")]
+        project_path: PathBuf,
+
+        /// Output format
+        #[arg(long, value_enum, default_value_t = crate::history::ExportFormat::Csv)]
+        format: crate::history::ExportFormat,
+
+        /// File to write instead of stdout
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+    /// Record the project's builds from now on, by setting `history` in its lol.json
+    Enable {
+        #[arg(value_name = "PROJECT_PATH", default_value = ".")]
+        project_path: PathBuf,
+    },
+    /// Stop recording the project's builds; the history recorded so far is kept
+    Disable {
+        #[arg(value_name = "PROJECT_PATH", default_value = ".")]
+        project_path: PathBuf,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum HistoryCommand {
+    /// Show one recorded build, language by language
+    Show {
+        /// The build's ID, as `lol history` lists it
+        id: u64,
+
+        /// Project the build belongs to
+        #[arg(value_name = "PROJECT_PATH", default_value = ".")]
+        project_path: PathBuf,
+    },
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Profile {
+    #[default]
+    Debug,
+    Release,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    /// WebAssembly modules, collected in `<output_directory>/wasm`
+    Wasm32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn test_cli_definition() {
+        Args::command().debug_assert();
+    }
+}
@@ -0,0 +1,275 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use walkdir::WalkDir;
+use crate::build_info::BuildInfo;
+use crate::compiler::{BuiltFile, CompilationResult};
+
+/// Written to the output directory after every build.
+pub const MANIFEST_FILE: &str = "artifacts.json";
+
+/// A file produced by a build.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Artifact {
+    /// Relative to the project root
+    pub path: PathBuf,
+    pub language: Option<String>,
+    /// The sources the artifact was built from, relative to the project root
+    pub inputs: Vec<PathBuf>,
+    pub size: u64,
+    pub sha256: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ArtifactManifest {
+    pub artifacts: Vec<Artifact>,
+    /// The compiler each language was built with, by language name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub toolchains: BTreeMap<String, String>,
+    /// What `--version-stamp` injected into the build
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub build_info: Option<BuildInfo>,
+}
+
+impl ArtifactManifest {
+    pub fn load(output_dir: &Path) -> Result<Option<Self>> {
+        let path = output_dir.join(MANIFEST_FILE);
+        if !path.is_file() {
+            return Ok(None);
+        }
+        let content = fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let manifest = serde_json::from_str(&content).with_context(|| format!("Failed to parse {}", path.display()))?;
+        Ok(Some(manifest))
+    }
+
+    pub fn save(&self, output_dir: &Path) -> Result<PathBuf> {
+        fs::create_dir_all(output_dir)
+            .with_context(|| format!("Failed to create {}", output_dir.display()))?;
+        let path = output_dir.join(MANIFEST_FILE);
+        fs::write(&path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(path)
+    }
+}
+
+/// Lists the files directly inside a package output directory, such as an
+/// AppImage with its `.zsync` and `.sig`, with paths relative to it.
+pub fn package_manifest(output_dir: &Path) -> Result<ArtifactManifest> {
+    let mut artifacts = Vec::new();
+    for entry in fs::read_dir(output_dir).with_context(|| format!("Failed to read {}", output_dir.display()))? {
+        let path = entry?.path();
+        let is_manifest = path.file_name().is_some_and(|name| name == MANIFEST_FILE || *name == *format!("{}.sig", MANIFEST_FILE));
+        if !path.is_file() || is_manifest {
+            continue;
+        }
+        artifacts.push(Artifact {
+            path: PathBuf::from(path.file_name().unwrap_or_default()),
+            language: None,
+            inputs: Vec::new(),
+            size: fs::metadata(&path)?.len(),
+            sha256: sha256(&path)?,
+        });
+    }
+    artifacts.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(ArtifactManifest { artifacts, ..ArtifactManifest::default() })
+}
+
+/// Modification times of the files in a project, taken before a build so
+/// `--build-dir` can tell which files it wrote into the sources.
+pub struct Snapshot {
+    files: HashMap<PathBuf, SystemTime>,
+}
+
+impl Snapshot {
+    pub fn take(project_path: &Path) -> Self {
+        let files = project_files(project_path)
+            .filter_map(|path| {
+                let modified = fs::metadata(&path).and_then(|metadata| metadata.modified()).ok()?;
+                Some((path, modified))
+            })
+            .collect();
+        Self { files }
+    }
+
+    /// Files in the project, outside `output_dirs` (canonical paths), that
+    /// are new or changed since the snapshot.
+    pub fn written_outside(&self, project_path: &Path, output_dirs: &[PathBuf]) -> Vec<PathBuf> {
+        let root = project_path.canonicalize().unwrap_or_else(|_| project_path.to_path_buf());
+        let mut written: Vec<PathBuf> = project_files(project_path)
+            .filter(|path| {
+                let absolute = root.join(path.strip_prefix(project_path).unwrap_or(path));
+                !output_dirs.iter().any(|dir| absolute.starts_with(dir))
+            })
+            .filter(|path| {
+                let modified = fs::metadata(path).and_then(|metadata| metadata.modified()).ok();
+                modified != self.files.get(path).copied()
+            })
+            .collect();
+        written.sort();
+        written
+    }
+}
+
+/// Builds the manifest from what the build wrote: the files the compilers
+/// reported, attributed to their sources, and everything in the output
+/// directory, which only lol writes to. Files elsewhere in the project are
+/// never listed, however recently they changed.
+pub fn collect(
+    project_path: &Path,
+    output_dir: Option<&Path>,
+    results: &[CompilationResult],
+    outputs: &[BuiltFile],
+) -> Result<ArtifactManifest> {
+    let mut seen = HashSet::new();
+    let mut artifacts = Vec::new();
+    let mut add = |path: &Path, language: Option<String>, inputs: Vec<PathBuf>| -> Result<()> {
+        if !path.is_file() || !seen.insert(path.canonicalize().unwrap_or_else(|_| path.to_path_buf())) {
+            return Ok(());
+        }
+        artifacts.push(Artifact {
+            path: relative(path, project_path),
+            language,
+            inputs: inputs.iter().map(|input| relative(input, project_path)).collect(),
+            size: fs::metadata(path)?.len(),
+            sha256: sha256(path)?,
+        });
+        Ok(())
+    };
+
+    for output in outputs {
+        add(&output.path, Some(output.language.name().to_string()), vec![output.source.clone()])?;
+    }
+    // An output directory that is the project itself would list the sources
+    let root = project_path.canonicalize().unwrap_or_else(|_| project_path.to_path_buf());
+    if let Some(output_dir) = output_dir.filter(|dir| dir.canonicalize().ok().as_ref() != Some(&root)) {
+        for path in project_files(output_dir) {
+            if path != output_dir.join(MANIFEST_FILE) {
+                let (language, inputs) = attribute(&path, output_dir, results);
+                add(&path, language, inputs)?;
+            }
+        }
+    }
+
+    artifacts.sort_by(|a, b| a.path.cmp(&b.path));
+    let toolchains = results
+        .iter()
+        .filter_map(|result| Some((result.language.name().to_string(), result.toolchain.clone()?)))
+        .collect();
+    Ok(ArtifactManifest { artifacts, toolchains, build_info: None })
+}
+
+fn project_files(project_path: &Path) -> impl Iterator<Item = PathBuf> {
+    WalkDir::new(project_path)
+        .into_iter()
+        .filter_entry(|e| e.depth() == 0 || !e.file_name().to_string_lossy().starts_with('.'))
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .map(|e| e.into_path())
+}
+
+fn attribute(path: &Path, output_dir: &Path, results: &[CompilationResult]) -> (Option<String>, Vec<PathBuf>) {
+    let stem = base_stem(path);
+    for result in results {
+        let inputs: Vec<PathBuf> = result
+            .files
+            .iter()
+            .filter(|file| base_stem(file) == stem)
+            .cloned()
+            .collect();
+        if !inputs.is_empty() {
+            return (Some(result.language.name().to_string()), inputs);
+        }
+    }
+
+    // Batch outputs (bytecode trees, linked apps) live under the language's
+    // subdirectory of the output directory and come from all of its sources
+    let subdirectory = path
+        .strip_prefix(output_dir)
+        .ok()
+        .and_then(|relative| relative.components().next())
+        .map(|component| component.as_os_str().to_string_lossy().into_owned());
+    match results.iter().find(|result| subdirectory.as_deref() == Some(result.language.config_key())) {
+        Some(result) => (Some(result.language.name().to_string()), result.files.clone()),
+        None => (None, Vec::new()),
+    }
+}
+
+/// The file name up to its first dot, so `mod.cpython-311.pyc` matches `mod.py`.
+fn base_stem(path: &Path) -> String {
+    let name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+    name.split('.').next().unwrap_or_default().to_string()
+}
+
+fn relative(path: &Path, project_path: &Path) -> PathBuf {
+    let project_path = project_path.canonicalize().unwrap_or_else(|_| project_path.to_path_buf());
+    let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    path.strip_prefix(&project_path).map(Path::to_path_buf).unwrap_or(path)
+}
+
+pub fn sha256(path: &Path) -> Result<String> {
+    let mut file = fs::File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let mut hasher = Sha256::new();
+    let mut buffer = [0u8; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::CompilationStatus;
+    use crate::language_support::Language;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_manifest_lists_compiler_outputs_with_their_sources() {
+        let temp_dir = TempDir::new().unwrap();
+        let project = temp_dir.path();
+        let output_dir = project.join("build");
+        fs::write(project.join("util.c"), "int util(void) { return 1; }").unwrap();
+        fs::write(project.join("README"), "docs").unwrap();
+
+        // The build writes its outputs while the user edits files in the tree
+        fs::write(project.join("util.o"), "obj").unwrap();
+        fs::create_dir_all(output_dir.join("c")).unwrap();
+        fs::write(output_dir.join("c").join("app"), "binary").unwrap();
+        fs::write(project.join("util.c"), "int util(void) { return 2; }").unwrap();
+        fs::write(project.join("README"), "more docs").unwrap();
+
+        let results = vec![CompilationResult {
+            language: Language::C,
+            files: vec![project.join("util.c")],
+            status: CompilationStatus::Success { output: String::new() },
+            diagnostics: Vec::new(),
+            toolchain: None,
+            retries: 0,
+            duration_ms: 0,
+        }];
+        let outputs = vec![BuiltFile { language: Language::C, path: project.join("util.o"), source: project.join("util.c") }];
+        let manifest = collect(project, Some(&output_dir), &results, &outputs).unwrap();
+
+        let paths: Vec<&Path> = manifest.artifacts.iter().map(|artifact| artifact.path.as_path()).collect();
+        assert_eq!(paths, [Path::new("build/c/app"), Path::new("util.o")]);
+        let object = &manifest.artifacts[1];
+        assert_eq!(object.language.as_deref(), Some("C"));
+        assert_eq!(object.inputs, vec![PathBuf::from("util.c")]);
+        assert_eq!(object.size, 3);
+        assert_eq!(object.sha256, "772a5fb04f9bad38681a2f56ddfdbd6a15185753df8dcc029788d02bf3b6825b");
+        assert_eq!(manifest.artifacts[0].inputs, vec![PathBuf::from("util.c")]);
+
+        manifest.save(&output_dir).unwrap();
+        let loaded = ArtifactManifest::load(&output_dir).unwrap().unwrap();
+        assert_eq!(loaded.artifacts, manifest.artifacts);
+    }
+}
@@ -0,0 +1,433 @@
+//! `lol-cache-server`: a shared build cache teams host themselves.
+//!
+//! Entries are opaque blobs under a key, stored on disk and evicted least
+//! recently used first once the cache outgrows `--max-size-mb`:
+//!
+//! - `GET /cache/{key}` returns an entry, `404` when there is none
+//! - `HEAD /cache/{key}` tells whether an entry exists
+//! - `PUT /cache/{key}` stores the request body, `201` once written
+//! - `GET /health` reports the entry count and size, without a token
+//!
+//! Keys are 1 to 128 letters, digits, `-` or `_`. Requests carry
+//! `Authorization: Bearer <token>` with a token from `--tokens`, unless the
+//! server was started with `--allow-anonymous`.
+//!
+//! `lol` itself does not use the server yet; this is the protocol its
+//! remote cache will speak.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use anyhow::{Context, Result};
+use axum::body::Bytes;
+use axum::extract::{DefaultBodyLimit, Path as UrlPath, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use clap::Parser;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+
+/// Port the server listens on unless told otherwise.
+const DEFAULT_PORT: u16 = 7272;
+
+#[derive(Parser, Debug)]
+#[command(name = "lol-cache-server", version, about = "Shared build cache server for lol")]
+struct ServerArgs {
+    /// Directory entries are stored in
+    #[arg(long, default_value = "lol-cache")]
+    storage: PathBuf,
+
+    /// Address to listen on
+    #[arg(long, default_value = "127.0.0.1")]
+    host: IpAddr,
+
+    #[arg(long, default_value_t = DEFAULT_PORT)]
+    port: u16,
+
+    /// Size the cache is kept under by evicting the least recently used entries
+    #[arg(long, default_value_t = 10_240)]
+    max_size_mb: u64,
+
+    /// Largest single entry accepted
+    #[arg(long, default_value_t = 512)]
+    max_entry_mb: u64,
+
+    /// File of access tokens, one per line; a token followed by `read` may
+    /// only fetch
+    #[arg(long)]
+    tokens: Option<PathBuf>,
+
+    /// Run without `--tokens`, letting anyone who can reach the server read
+    /// and write the cache
+    #[arg(long, conflicts_with = "tokens")]
+    allow_anonymous: bool,
+}
+
+/// What a token allows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Access {
+    Read,
+    ReadWrite,
+}
+
+/// Parses a tokens file: `<token>` or `<token> read` per line, with blank
+/// lines and `#` comments skipped.
+fn parse_tokens(contents: &str) -> Result<HashMap<String, Access>> {
+    let mut tokens = HashMap::new();
+    for (index, line) in contents.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        let mut fields = line.split_whitespace();
+        let Some(token) = fields.next() else {
+            continue;
+        };
+        let access = match fields.next() {
+            None | Some("write") => Access::ReadWrite,
+            Some("read") => Access::Read,
+            Some(other) => anyhow::bail!("Line {}: unknown access '{}'; use `read` or `write`", index + 1, other),
+        };
+        tokens.insert(token.to_string(), access);
+    }
+    Ok(tokens)
+}
+
+fn valid_key(key: &str) -> bool {
+    (1..=128).contains(&key.len()) && key.bytes().all(|byte| byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'_')
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Entry {
+    size: u64,
+    last_used: SystemTime,
+}
+
+/// The entries on disk, with an index of their sizes and last use kept in
+/// memory. The last use survives restarts as the file's modification time.
+struct CacheStore {
+    root: PathBuf,
+    max_bytes: u64,
+    entries: Mutex<HashMap<String, Entry>>,
+    /// Numbers temporary files, so concurrent uploads do not collide
+    uploads: AtomicU64,
+}
+
+impl CacheStore {
+    fn open(root: &Path, max_bytes: u64) -> Result<Self> {
+        fs::create_dir_all(root.join("tmp")).with_context(|| format!("Failed to create {}", root.display()))?;
+        let mut entries = HashMap::new();
+        for shard in fs::read_dir(root)? {
+            let shard = shard?;
+            if shard.file_name() == "tmp" || !shard.file_type()?.is_dir() {
+                continue;
+            }
+            for file in fs::read_dir(shard.path())? {
+                let file = file?;
+                let metadata = file.metadata()?;
+                let key = file.file_name().to_string_lossy().into_owned();
+                if metadata.is_file() && valid_key(&key) {
+                    entries.insert(key, Entry { size: metadata.len(), last_used: metadata.modified()? });
+                }
+            }
+        }
+        // Uploads cut short by a restart
+        for file in fs::read_dir(root.join("tmp"))? {
+            let _ = fs::remove_file(file?.path());
+        }
+        Ok(Self { root: root.to_path_buf(), max_bytes, entries: Mutex::new(entries), uploads: AtomicU64::new(0) })
+    }
+
+    /// Entries are spread over directories named after their first two
+    /// characters, to keep directories small.
+    fn path(&self, key: &str) -> PathBuf {
+        self.root.join(&key[..key.len().min(2)]).join(key)
+    }
+
+    fn contains(&self, key: &str) -> bool {
+        self.entries.lock().unwrap().contains_key(key)
+    }
+
+    fn get(&self, key: &str) -> Option<Vec<u8>> {
+        let path = self.path(key);
+        let last_used = {
+            let mut entries = self.entries.lock().unwrap();
+            let entry = entries.get_mut(key)?;
+            entry.last_used = SystemTime::now();
+            entry.last_used
+        };
+        let _ = fs::File::options().write(true).open(&path).and_then(|file| file.set_modified(last_used));
+        // An entry evicted in between is simply a miss
+        fs::read(&path).ok()
+    }
+
+    fn temporary_path(&self) -> PathBuf {
+        let upload = self.uploads.fetch_add(1, Ordering::Relaxed);
+        self.root.join("tmp").join(format!("{}-{}", std::process::id(), upload))
+    }
+
+    fn put(&self, key: &str, data: &[u8]) -> Result<()> {
+        let temporary = self.temporary_path();
+        let mut file = fs::File::create(&temporary)?;
+        file.write_all(data)?;
+        file.sync_data()?;
+
+        let path = self.path(key);
+        fs::create_dir_all(path.parent().expect("entries live in a shard"))?;
+        let evicted = {
+            let mut entries = self.entries.lock().unwrap();
+            // Renamed under the lock, so the index and the disk agree
+            fs::rename(&temporary, &path)?;
+            entries.insert(key.to_string(), Entry { size: data.len() as u64, last_used: SystemTime::now() });
+            self.evict(&mut entries, key)
+        };
+        // Deleting large files can take a while; nothing waits on it
+        for file in evicted {
+            if let Err(error) = fs::remove_file(&file) {
+                eprintln!("Failed to delete evicted entry {}: {}", file.display(), error);
+            }
+        }
+        Ok(())
+    }
+
+    /// Takes the least recently used entries out of the cache until it
+    /// fits, never the one just written. They are moved aside rather than
+    /// deleted, and the returned files are left for the caller to delete
+    /// once the lock is released.
+    fn evict(&self, entries: &mut HashMap<String, Entry>, keep: &str) -> Vec<PathBuf> {
+        let mut evicted = Vec::new();
+        let mut total: u64 = entries.values().map(|entry| entry.size).sum();
+        if total <= self.max_bytes {
+            return evicted;
+        }
+        let mut oldest: Vec<(String, Entry)> = entries.iter().filter(|(key, _)| *key != keep).map(|(key, entry)| (key.clone(), *entry)).collect();
+        oldest.sort_by_key(|(_, entry)| entry.last_used);
+        for (key, entry) in oldest {
+            if total <= self.max_bytes {
+                break;
+            }
+            let aside = self.temporary_path();
+            match fs::rename(self.path(&key), &aside) {
+                Ok(()) => evicted.push(aside),
+                Err(error) if error.kind() == std::io::ErrorKind::NotFound => {}
+                Err(error) => {
+                    eprintln!("Failed to evict {}: {}", key, error);
+                    continue;
+                }
+            }
+            entries.remove(&key);
+            total -= entry.size;
+        }
+        evicted
+    }
+
+    fn usage(&self) -> (usize, u64) {
+        let entries = self.entries.lock().unwrap();
+        (entries.len(), entries.values().map(|entry| entry.size).sum())
+    }
+}
+
+/// A token's SHA-256, so that tokens of any length compare in the same
+/// time.
+type TokenDigest = [u8; 32];
+
+fn token_digest(token: &str) -> TokenDigest {
+    Sha256::digest(token.as_bytes()).into()
+}
+
+/// What `token` allows. Every known token is compared, in constant time, so
+/// the response time tells nothing about how close a guess came.
+fn token_access(tokens: &[(TokenDigest, Access)], token: &str) -> Option<Access> {
+    let digest = token_digest(token);
+    let mut access = None;
+    for (known, known_access) in tokens {
+        if bool::from(known.ct_eq(&digest)) {
+            access = Some(*known_access);
+        }
+    }
+    access
+}
+
+/// The tokens from `--tokens`, or `None` with `--allow-anonymous`. Without
+/// either, the server does not start.
+fn load_tokens(args: &ServerArgs) -> Result<Option<Vec<(TokenDigest, Access)>>> {
+    let Some(path) = &args.tokens else {
+        anyhow::ensure!(
+            args.allow_anonymous,
+            "No --tokens file; pass one, or --allow-anonymous to let anyone who can reach the server read and write the cache"
+        );
+        return Ok(None);
+    };
+    let contents = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let tokens = parse_tokens(&contents).with_context(|| format!("Invalid tokens file {}", path.display()))?;
+    anyhow::ensure!(!tokens.is_empty(), "{} lists no tokens", path.display());
+    Ok(Some(tokens.iter().map(|(token, access)| (token_digest(token), *access)).collect()))
+}
+
+struct Server {
+    store: CacheStore,
+    /// `None` when the server is open to anyone
+    tokens: Option<Vec<(TokenDigest, Access)>>,
+}
+
+impl Server {
+    /// Checks the request's bearer token allows `needed`.
+    fn authorize(&self, headers: &HeaderMap, needed: Access) -> Result<(), ApiError> {
+        let Some(tokens) = &self.tokens else {
+            return Ok(());
+        };
+        let token = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+        match token.and_then(|token| token_access(tokens, token.trim())) {
+            None => Err(error(StatusCode::UNAUTHORIZED, "A valid token is required")),
+            Some(Access::Read) if needed == Access::ReadWrite => Err(error(StatusCode::FORBIDDEN, "This token may only read")),
+            Some(_) => Ok(()),
+        }
+    }
+}
+
+type ApiError = (StatusCode, Json<serde_json::Value>);
+
+fn error(status: StatusCode, message: &str) -> ApiError {
+    (status, Json(json!({ "error": message })))
+}
+
+/// Runs the store's file work on the blocking pool, off the threads that
+/// serve requests.
+async fn blocking<T: Send + 'static>(work: impl FnOnce() -> T + Send + 'static) -> Result<T, ApiError> {
+    tokio::task::spawn_blocking(work)
+        .await
+        .map_err(|failure| error(StatusCode::INTERNAL_SERVER_ERROR, &failure.to_string()))
+}
+
+fn check_key(key: &str) -> Result<(), ApiError> {
+    if valid_key(key) {
+        Ok(())
+    } else {
+        Err(error(StatusCode::BAD_REQUEST, "Keys are 1 to 128 letters, digits, '-' or '_'"))
+    }
+}
+
+async fn health(State(server): State<Arc<Server>>) -> Json<serde_json::Value> {
+    let (entries, bytes) = server.store.usage();
+    Json(json!({ "name": "lol-cache-server", "version": env!("CARGO_PKG_VERSION"), "entries": entries, "bytes": bytes }))
+}
+
+async fn get_entry(State(server): State<Arc<Server>>, UrlPath(key): UrlPath<String>, headers: HeaderMap) -> Result<Response, ApiError> {
+    server.authorize(&headers, Access::Read)?;
+    check_key(&key)?;
+    match blocking(move || server.store.get(&key)).await? {
+        Some(data) => Ok(([(header::CONTENT_TYPE, "application/octet-stream")], data).into_response()),
+        None => Err(error(StatusCode::NOT_FOUND, "No such entry")),
+    }
+}
+
+async fn head_entry(State(server): State<Arc<Server>>, UrlPath(key): UrlPath<String>, headers: HeaderMap) -> Result<StatusCode, ApiError> {
+    server.authorize(&headers, Access::Read)?;
+    check_key(&key)?;
+    Ok(if server.store.contains(&key) { StatusCode::OK } else { StatusCode::NOT_FOUND })
+}
+
+async fn put_entry(State(server): State<Arc<Server>>, UrlPath(key): UrlPath<String>, headers: HeaderMap, body: Bytes) -> Result<StatusCode, ApiError> {
+    server.authorize(&headers, Access::ReadWrite)?;
+    check_key(&key)?;
+    blocking(move || server.store.put(&key, &body))
+        .await?
+        .map_err(|failure| error(StatusCode::INTERNAL_SERVER_ERROR, &format!("{:#}", failure)))?;
+    Ok(StatusCode::CREATED)
+}
+
+fn router(server: Arc<Server>, max_entry_bytes: usize) -> Router {
+    Router::new()
+        .route("/health", get(health))
+        .route("/cache/{key}", get(get_entry).head(head_entry).put(put_entry))
+        .layer(DefaultBodyLimit::max(max_entry_bytes))
+        .with_state(server)
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = ServerArgs::parse();
+    let tokens = load_tokens(&args)?;
+    if tokens.is_none() {
+        eprintln!("⚠️  --allow-anonymous: anyone who can reach the server can read and write the cache");
+    }
+    let store = CacheStore::open(&args.storage, args.max_size_mb * 1024 * 1024)?;
+    let (entries, bytes) = store.usage();
+    let server = Arc::new(Server { store, tokens });
+
+    let listener = tokio::net::TcpListener::bind((args.host, args.port))
+        .await
+        .with_context(|| format!("Failed to listen on {}:{}", args.host, args.port))?;
+    println!(
+        "🗄️  lol-cache-server on http://{} serving {} ({} entries, {:.1} MB)",
+        listener.local_addr()?,
+        args.storage.display(),
+        entries,
+        bytes as f64 / (1024.0 * 1024.0)
+    );
+    axum::serve(listener, router(server, (args.max_entry_mb * 1024 * 1024) as usize)).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_store_evicts_least_recently_used() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = CacheStore::open(temp_dir.path(), 10).unwrap();
+        store.put("aaaa", b"1234").unwrap();
+        store.put("bbbb", b"1234").unwrap();
+        // Reading `aaaa` makes `bbbb` the least recently used
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        assert_eq!(store.get("aaaa").unwrap(), b"1234");
+        store.put("cccc", b"1234").unwrap();
+
+        assert!(store.contains("aaaa") && store.contains("cccc"));
+        assert!(!store.contains("bbbb") && !temp_dir.path().join("bb").join("bbbb").exists());
+        assert_eq!(CacheStore::open(temp_dir.path(), 10).unwrap().usage(), (2, 8));
+
+        assert!(valid_key("3f2a-build_1") && !valid_key("../etc") && !valid_key(""));
+        let tokens = parse_tokens("# CI\nci-token write\n\ndev-token read  # laptops\n").unwrap();
+        assert_eq!(tokens.get("ci-token"), Some(&Access::ReadWrite));
+        assert_eq!(tokens.get("dev-token"), Some(&Access::Read));
+        assert!(parse_tokens("token admin").is_err());
+    }
+
+    #[test]
+    fn test_tokens_are_required_unless_anonymous_is_allowed() {
+        let temp_dir = TempDir::new().unwrap();
+        let args = |extra: &[&str]| ServerArgs::parse_from(["lol-cache-server"].iter().chain(extra));
+        let error = load_tokens(&args(&[])).unwrap_err();
+        assert!(error.to_string().contains("--allow-anonymous"));
+        assert!(load_tokens(&args(&["--allow-anonymous"])).unwrap().is_none());
+
+        let tokens_file = temp_dir.path().join("tokens");
+        fs::write(&tokens_file, "ci-token\ndev-token read\n").unwrap();
+        let tokens = load_tokens(&args(&["--tokens", tokens_file.to_str().unwrap()])).unwrap().unwrap();
+        assert_eq!(token_access(&tokens, "ci-token"), Some(Access::ReadWrite));
+        assert_eq!(token_access(&tokens, "dev-token"), Some(Access::Read));
+        assert_eq!(token_access(&tokens, "ci-toke"), None);
+
+        let server = Server { store: CacheStore::open(temp_dir.path(), 1024).unwrap(), tokens: Some(tokens) };
+        let bearer = |token: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::AUTHORIZATION, format!("Bearer {}", token).parse().unwrap());
+            headers
+        };
+        assert!(server.authorize(&bearer("ci-token"), Access::ReadWrite).is_ok());
+        assert_eq!(server.authorize(&bearer("dev-token"), Access::ReadWrite).unwrap_err().0, StatusCode::FORBIDDEN);
+        assert_eq!(server.authorize(&bearer("guess"), Access::Read).unwrap_err().0, StatusCode::UNAUTHORIZED);
+        assert_eq!(server.authorize(&HeaderMap::new(), Access::Read).unwrap_err().0, StatusCode::UNAUTHORIZED);
+    }
+}
@@ -0,0 +1,96 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use crate::config::Config;
+use crate::package;
+use crate::reproducible;
+
+/// Written to the output directory by `--version-stamp`, for scripts to read.
+pub const BUILD_INFO_FILE: &str = "build_info.json";
+
+/// What `--version-stamp` injects into a build.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BuildInfo {
+    pub version: String,
+    /// Commit hash of `HEAD`, when the project is a git checkout
+    pub commit: Option<String>,
+    /// Unix seconds; `SOURCE_DATE_EPOCH` when set, so stamped builds stay reproducible
+    pub timestamp: u64,
+}
+
+impl BuildInfo {
+    /// The version `lol package` would use, the current commit and the
+    /// build time.
+    pub fn collect(project_path: &Path, config: &Config) -> Self {
+        let commit = git_commit(project_path, "HEAD");
+        let timestamp = reproducible::epoch()
+            .unwrap_or_else(|| SystemTime::now().duration_since(UNIX_EPOCH).map(|age| age.as_secs()).unwrap_or(0));
+        Self {
+            version: package::package_version(config, project_path),
+            commit,
+            timestamp,
+        }
+    }
+
+    /// `LOL_VERSION`, `LOL_GIT_COMMIT` and `LOL_BUILD_TIMESTAMP`, set on
+    /// every compiler so e.g. Rust can read them with `env!`.
+    pub fn variables(&self) -> Vec<(String, String)> {
+        let mut variables = vec![("LOL_VERSION".to_string(), self.version.clone())];
+        if let Some(commit) = &self.commit {
+            variables.push(("LOL_GIT_COMMIT".to_string(), commit.clone()));
+        }
+        variables.push(("LOL_BUILD_TIMESTAMP".to_string(), self.timestamp.to_string()));
+        variables
+    }
+
+    /// The same values as preprocessor definitions, strings quoted so they
+    /// expand to C string literals.
+    pub fn defines(&self) -> Vec<String> {
+        self.variables()
+            .into_iter()
+            .map(|(name, value)| match name.as_str() {
+                "LOL_BUILD_TIMESTAMP" => format!("{}={}", name, value),
+                _ => format!("{}=\"{}\"", name, value.replace('\\', "\\\\").replace('"', "\\\"")),
+            })
+            .collect()
+    }
+
+    pub fn save(&self, output_dir: &Path) -> Result<PathBuf> {
+        fs::create_dir_all(output_dir).with_context(|| format!("Failed to create {}", output_dir.display()))?;
+        let path = output_dir.join(BUILD_INFO_FILE);
+        fs::write(&path, serde_json::to_string_pretty(self)? + "\n")
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(path)
+    }
+}
+
+/// The commit hash `rev` names in the git checkout at `project_path`.
+pub fn git_commit(project_path: &Path, rev: &str) -> Option<String> {
+    Command::new("git")
+        .args(["rev-parse", "--verify", "--quiet"])
+        .arg(format!("{}^{{commit}}", rev))
+        .current_dir(project_path)
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_defines_quote_strings() {
+        let info = BuildInfo { version: "1.2.0".to_string(), commit: Some("abc123".to_string()), timestamp: 1_700_000_000 };
+        assert_eq!(
+            info.defines(),
+            ["LOL_VERSION=\"1.2.0\"", "LOL_GIT_COMMIT=\"abc123\"", "LOL_BUILD_TIMESTAMP=1700000000"]
+        );
+        let info = BuildInfo { commit: None, ..info };
+        assert_eq!(info.variables().len(), 2);
+    }
+}
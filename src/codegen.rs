@@ -0,0 +1,197 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use anyhow::{Context, Result};
+use colored::*;
+use walkdir::WalkDir;
+use crate::config::Config;
+
+/// Written into the generated-code directory once lol has generated into
+/// it, so that `lol clean` never removes a `generated/` directory of the
+/// project's own.
+const MARKER: &str = ".lol-generated";
+
+/// A schema compiler run before source detection, so the code it emits is
+/// compiled in the same build.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Generator {
+    Protobuf,
+    Flatbuffers,
+}
+
+impl Generator {
+    pub fn all() -> [Generator; 2] {
+        [Generator::Protobuf, Generator::Flatbuffers]
+    }
+
+    pub fn tool(&self) -> &'static str {
+        match self {
+            Generator::Protobuf => "protoc",
+            Generator::Flatbuffers => "flatc",
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self {
+            Generator::Protobuf => "proto",
+            Generator::Flatbuffers => "fbs",
+        }
+    }
+
+    fn languages<'a>(&self, config: &'a Config) -> &'a [String] {
+        match self {
+            Generator::Protobuf => &config.codegen.protobuf,
+            Generator::Flatbuffers => &config.codegen.flatbuffers,
+        }
+    }
+
+    /// Builds the command generating `language` sources for `schemas` into
+    /// `out_dir`, with imports resolved against `project_path`.
+    fn command(&self, language: &str, schemas: &[PathBuf], project_path: &Path, out_dir: &Path) -> Command {
+        let mut cmd = Command::new(self.tool());
+        match self {
+            Generator::Protobuf => {
+                cmd.arg(format!("--proto_path={}", project_path.display()));
+                cmd.arg(format!("--{}_out={}", language, out_dir.display()));
+            }
+            Generator::Flatbuffers => {
+                cmd.arg(format!("--{}", language));
+                cmd.arg("-o").arg(out_dir);
+                cmd.arg("-I").arg(project_path);
+            }
+        }
+        cmd.args(schemas);
+        cmd
+    }
+}
+
+/// Schema files for `generator` in the project, skipping hidden directories
+/// and the output directory.
+pub fn find_schemas(generator: Generator, project_path: &Path, config: &Config) -> Vec<PathBuf> {
+    let output_dir = config.output_dir(project_path);
+    let mut schemas: Vec<PathBuf> = WalkDir::new(project_path)
+        .follow_links(true)
+        .into_iter()
+        .filter_entry(|e| {
+            let hidden = e.depth() > 0 && e.file_name().to_string_lossy().starts_with('.');
+            !hidden && output_dir.as_ref().is_none_or(|dir| !e.path().starts_with(dir))
+        })
+        .filter_map(|e| e.ok())
+        .map(|e| e.into_path())
+        .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == generator.extension()))
+        .collect();
+    schemas.sort();
+    schemas
+}
+
+/// Runs every configured generator over the project's schemas, writing to
+/// `<generated_dir>/<language>`. Generators without configured languages, or
+/// without schemas to work on, are skipped.
+pub fn run(config: &Config, project_path: &Path) -> Result<()> {
+    let project_path = project_path.canonicalize().unwrap_or_else(|_| project_path.to_path_buf());
+    let generated_dir = config.generated_dir(&project_path);
+
+    for generator in Generator::all() {
+        let languages = generator.languages(config);
+        if languages.is_empty() {
+            continue;
+        }
+        let schemas = find_schemas(generator, &project_path, config);
+        if schemas.is_empty() {
+            continue;
+        }
+
+        for language in languages {
+            let out_dir = generated_dir.join(language);
+            std::fs::create_dir_all(&out_dir)
+                .with_context(|| format!("Failed to create {}", out_dir.display()))?;
+            let marker = generated_dir.join(MARKER);
+            if !marker.is_file() {
+                std::fs::write(&marker, "Generated by lol; removed by `lol clean`\n")
+                    .with_context(|| format!("Failed to write {}", marker.display()))?;
+            }
+
+            say!("🧬 {}: generating {} code from {} schemas", generator.tool(), language, schemas.len());
+            let output = generator
+                .command(language, &schemas, &project_path, &out_dir)
+                .output()
+                .with_context(|| format!("Failed to run {}; is it installed?", generator.tool()))?;
+
+            if !output.status.success() {
+                anyhow::bail!(
+                    "{} failed to generate {} code:\n{}",
+                    generator.tool(),
+                    language,
+                    String::from_utf8_lossy(&output.stderr).trim().red()
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Whether `lol clean` may remove the generated-code directory: codegen is
+/// configured and lol generated what is in it.
+pub fn owns_generated_dir(config: &Config, project_path: &Path) -> bool {
+    let configured = Generator::all().iter().any(|generator| !generator.languages(config).is_empty());
+    configured && config.generated_dir(project_path).join(MARKER).is_file()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_find_schemas_skips_output_directory() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_path = temp_dir.path();
+        fs::create_dir_all(project_path.join("proto")).unwrap();
+        fs::create_dir_all(project_path.join("build")).unwrap();
+        fs::write(project_path.join("proto").join("user.proto"), "syntax = \"proto3\";").unwrap();
+        fs::write(project_path.join("build").join("stale.proto"), "").unwrap();
+        fs::write(project_path.join("monster.fbs"), "table Monster {}").unwrap();
+
+        let config = Config::default();
+        let protos = find_schemas(Generator::Protobuf, project_path, &config);
+        assert_eq!(protos, vec![project_path.join("proto").join("user.proto")]);
+        let fbs = find_schemas(Generator::Flatbuffers, project_path, &config);
+        assert_eq!(fbs, vec![project_path.join("monster.fbs")]);
+    }
+
+    #[test]
+    fn test_generator_commands() {
+        let project = Path::new("/src/app");
+        let schemas = vec![project.join("user.proto")];
+        let out_dir = Path::new("/src/app/build/generated/cpp");
+
+        let cmd = Generator::Protobuf.command("cpp", &schemas, project, out_dir);
+        assert_eq!(cmd.get_program(), "protoc");
+        let args: Vec<_> = cmd.get_args().map(|arg| arg.to_string_lossy().into_owned()).collect();
+        assert_eq!(args, vec![
+            "--proto_path=/src/app",
+            "--cpp_out=/src/app/build/generated/cpp",
+            "/src/app/user.proto",
+        ]);
+
+        let cmd = Generator::Flatbuffers.command("python", &schemas, project, out_dir);
+        assert_eq!(cmd.get_program(), "flatc");
+        let args: Vec<_> = cmd.get_args().map(|arg| arg.to_string_lossy().into_owned()).collect();
+        assert_eq!(&args[..3], &["--python", "-o", "/src/app/build/generated/cpp"]);
+    }
+
+    #[test]
+    fn test_clean_only_owns_generated_dir_lol_wrote() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = Config { output_directory: None, ..Config::default() };
+        fs::create_dir_all(temp_dir.path().join("generated")).unwrap();
+        assert!(!owns_generated_dir(&config, temp_dir.path()));
+
+        // Configured, but the directory is the project's own
+        config.codegen.protobuf = vec!["cpp".to_string()];
+        assert!(!owns_generated_dir(&config, temp_dir.path()));
+
+        fs::write(temp_dir.path().join("generated").join(MARKER), "").unwrap();
+        assert!(owns_generated_dir(&config, temp_dir.path()));
+    }
+}
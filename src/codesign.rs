@@ -0,0 +1,140 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use anyhow::{Context, Result};
+use walkdir::WalkDir;
+use crate::config::CodesignConfig;
+
+/// Mach-O magic numbers, thin and universal, in either byte order.
+const MACH_O_MAGIC: &[[u8; 4]] = &[
+    [0xfe, 0xed, 0xfa, 0xce],
+    [0xfe, 0xed, 0xfa, 0xcf],
+    [0xce, 0xfa, 0xed, 0xfe],
+    [0xcf, 0xfa, 0xed, 0xfe],
+    [0xca, 0xfe, 0xba, 0xbe],
+];
+
+/// Whether `path` is a Mach-O binary. Universal binaries share their magic
+/// with Java class files, which are told apart by extension.
+fn is_mach_o(path: &Path) -> bool {
+    let mut magic = [0u8; 4];
+    path.extension().is_none_or(|ext| ext != "class")
+        && fs::File::open(path).and_then(|mut file| std::io::Read::read_exact(&mut file, &mut magic)).is_ok()
+        && MACH_O_MAGIC.contains(&magic)
+}
+
+/// What needs a signature under `dir`: `.app` bundles, and Mach-O files
+/// outside them. Bundles are signed as a whole, after the loose binaries.
+fn signables(dir: &Path) -> (Vec<PathBuf>, Vec<PathBuf>) {
+    let mut binaries = Vec::new();
+    let mut bundles = Vec::new();
+    let mut walker = WalkDir::new(dir).sort_by_file_name().into_iter();
+    while let Some(Ok(entry)) = walker.next() {
+        if entry.file_type().is_dir() && entry.path().extension().is_some_and(|ext| ext == "app") {
+            bundles.push(entry.path().to_path_buf());
+            walker.skip_current_dir();
+        } else if entry.file_type().is_file() && is_mach_o(entry.path()) {
+            binaries.push(entry.path().to_path_buf());
+        }
+    }
+    (binaries, bundles)
+}
+
+fn codesign_command(config: &CodesignConfig, project_path: &Path, path: &Path) -> Command {
+    let mut command = Command::new("codesign");
+    command.arg("--force").arg("--sign").arg(&config.identity);
+    // Ad hoc signatures cannot be timestamped or notarized
+    if config.identity == "-" {
+        command.arg("--timestamp=none");
+    } else {
+        command.args(["--timestamp", "--options", "runtime"]);
+    }
+    if let Some(entitlements) = &config.entitlements {
+        command.arg("--entitlements").arg(project_path.join(entitlements));
+    }
+    if path.extension().is_some_and(|ext| ext == "app") {
+        command.arg("--deep");
+    }
+    command.arg(path);
+    command
+}
+
+fn run(mut command: Command, what: &str) -> Result<()> {
+    let program = command.get_program().to_string_lossy().into_owned();
+    let output = command.output().with_context(|| format!("Failed to run {}", program))?;
+    if !output.status.success() {
+        anyhow::bail!("{} failed: {}", what, String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(())
+}
+
+/// Signs the binaries and app bundles staged in `staging_dir` and, with a
+/// `notarize_profile`, submits them to Apple's notary service and staples
+/// the tickets to the bundles. Returns how many items were signed.
+pub fn sign_package(staging_dir: &Path, config: &CodesignConfig, project_path: &Path) -> Result<usize> {
+    if !cfg!(target_os = "macos") {
+        tracing::warn!("package.codesign is set, but signing needs macOS; the package is left unsigned");
+        return Ok(0);
+    }
+    let (binaries, bundles) = signables(staging_dir);
+    for path in binaries.iter().chain(&bundles) {
+        run(codesign_command(config, project_path, path), &format!("Signing {}", path.display()))?;
+    }
+    let Some(profile) = &config.notarize_profile else {
+        return Ok(binaries.len() + bundles.len());
+    };
+    if config.identity == "-" {
+        anyhow::bail!("Notarization needs a Developer ID identity, not an ad hoc signature");
+    }
+
+    // notarytool takes a zip; ditto keeps the bundle structure and signatures intact
+    let submission = staging_dir.with_extension("notarize.zip");
+    let mut ditto = Command::new("ditto");
+    ditto.args(["-c", "-k", "--keepParent"]).arg(staging_dir).arg(&submission);
+    run(ditto, "Zipping for notarization")?;
+    let mut notarytool = Command::new("xcrun");
+    notarytool.args(["notarytool", "submit"]).arg(&submission).args(["--keychain-profile", profile, "--wait"]);
+    let submitted = run(notarytool, "Notarization");
+    let _ = fs::remove_file(&submission);
+    submitted?;
+    // Bare binaries cannot hold a ticket; Gatekeeper looks theirs up online
+    for bundle in &bundles {
+        let mut stapler = Command::new("xcrun");
+        stapler.args(["stapler", "staple"]).arg(bundle);
+        run(stapler, &format!("Stapling {}", bundle.display()))?;
+    }
+    Ok(binaries.len() + bundles.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_signables_and_codesign_arguments() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let macos_dir = root.join("lib/Demo.app/Contents/MacOS");
+        fs::create_dir_all(&macos_dir).unwrap();
+        fs::create_dir_all(root.join("bin")).unwrap();
+        fs::write(macos_dir.join("Demo"), [0xcf, 0xfa, 0xed, 0xfe, 7, 0, 0, 1]).unwrap();
+        fs::write(root.join("bin/tool"), [0xcf, 0xfa, 0xed, 0xfe, 7, 0, 0, 1]).unwrap();
+        fs::write(root.join("bin/Main.class"), [0xca, 0xfe, 0xba, 0xbe, 0, 0, 0, 65]).unwrap();
+        fs::write(root.join("bin/run"), "#!/bin/sh\n").unwrap();
+
+        let (binaries, bundles) = signables(root);
+        assert_eq!(binaries, [root.join("bin/tool")]);
+        assert_eq!(bundles, [root.join("lib/Demo.app")]);
+
+        let config = CodesignConfig {
+            identity: "Developer ID Application: Example (TEAMID)".to_string(),
+            entitlements: Some("app.entitlements".to_string()),
+            notarize_profile: None,
+        };
+        let command = codesign_command(&config, Path::new("/project"), &bundles[0]);
+        let args: Vec<_> = command.get_args().map(|arg| arg.to_string_lossy().into_owned()).collect();
+        assert_eq!(args[..6], ["--force", "--sign", "Developer ID Application: Example (TEAMID)", "--timestamp", "--options", "runtime"]);
+        assert_eq!(args[6..], ["--entitlements", "/project/app.entitlements", "--deep", &bundles[0].display().to_string()]);
+    }
+}
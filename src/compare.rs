@@ -0,0 +1,209 @@
+use std::path::Path;
+use anyhow::{Context, Result};
+use colored::*;
+use crate::build_info;
+use crate::history::{self, Session};
+
+/// Timing differences smaller than this are noise, whatever the percentage.
+const MIN_CHANGE_MS: u64 = 100;
+
+/// One language's timing in the two builds, and the files that started
+/// failing in between.
+#[derive(Debug, PartialEq)]
+pub struct LanguageChange {
+    pub language: String,
+    pub baseline_ms: Option<u64>,
+    pub current_ms: u64,
+    /// Set when the language failed now but not in the baseline
+    pub newly_failed: bool,
+    pub newly_failed_files: Vec<String>,
+}
+
+/// How much slower `current_ms` is than `baseline_ms`, in percent; `None`
+/// when there is nothing to compare or the difference is noise.
+fn slowdown(baseline_ms: Option<u64>, current_ms: u64) -> Option<f64> {
+    let baseline_ms = baseline_ms.filter(|&baseline_ms| baseline_ms > 0)?;
+    (current_ms.abs_diff(baseline_ms) >= MIN_CHANGE_MS).then(|| (current_ms as f64 / baseline_ms as f64 - 1.0) * 100.0)
+}
+
+/// The latest build set against a baseline.
+#[derive(Debug, PartialEq)]
+pub struct Comparison {
+    pub baseline_id: u64,
+    pub current_id: u64,
+    pub baseline_ms: u64,
+    pub current_ms: u64,
+    pub languages: Vec<LanguageChange>,
+}
+
+impl Comparison {
+    pub fn new(baseline: &Session, current: &Session) -> Self {
+        let languages = current
+            .languages
+            .iter()
+            .map(|record| {
+                let before = baseline.languages.iter().find(|before| before.language == record.language);
+                let failed_before = before.is_some_and(|before| before.failed > 0);
+                let newly_failed_files = record
+                    .failed_files
+                    .iter()
+                    .filter(|file| before.is_none_or(|before| !before.failed_files.contains(file)))
+                    .cloned()
+                    .collect();
+                LanguageChange {
+                    language: record.language.clone(),
+                    baseline_ms: before.map(|before| before.duration_ms),
+                    current_ms: record.duration_ms,
+                    newly_failed: record.failed > 0 && !failed_before,
+                    newly_failed_files,
+                }
+            })
+            .collect();
+        Self {
+            baseline_id: baseline.id,
+            current_id: current.id,
+            baseline_ms: baseline.duration_ms,
+            current_ms: current.duration_ms,
+            languages,
+        }
+    }
+
+    /// What got worse: anything newly failing, and the languages, or the
+    /// build as a whole, that got slower by more than `threshold` percent.
+    pub fn regressions(&self, threshold: f64) -> Vec<String> {
+        let mut regressions = Vec::new();
+        for change in &self.languages {
+            if change.newly_failed || !change.newly_failed_files.is_empty() {
+                match change.newly_failed_files.as_slice() {
+                    [] => regressions.push(format!("{} fails", change.language)),
+                    files => regressions.push(format!("{} fails in {}", change.language, files.join(", "))),
+                }
+            }
+            if let Some(slower) = slowdown(change.baseline_ms, change.current_ms).filter(|&slower| slower > threshold) {
+                regressions.push(format!("{} is {:.1}% slower", change.language, slower));
+            }
+        }
+        if let Some(slower) = slowdown(Some(self.baseline_ms), self.current_ms).filter(|&slower| slower > threshold) {
+            regressions.push(format!("The build is {:.1}% slower", slower));
+        }
+        regressions
+    }
+}
+
+/// The build `baseline` names: a history ID, or a git ref whose latest
+/// recorded build is taken. Without one, the build before `current`.
+fn find_baseline<'a>(sessions: &'a [Session], current: &Session, baseline: Option<&str>, project_path: &Path) -> Result<&'a Session> {
+    // Newest first, so a ref's latest build is found
+    let mut earlier = sessions.iter().rev().filter(|session| session.id != current.id);
+    let Some(baseline) = baseline else {
+        return earlier.next().context("Only one build is recorded; there is nothing to compare it with");
+    };
+    if let Some(session) = baseline.parse::<u64>().ok().and_then(|id| sessions.iter().find(|session| session.id == id && session.id != current.id)) {
+        return Ok(session);
+    }
+    let commit = build_info::git_commit(project_path, baseline)
+        .with_context(|| format!("'{}' is neither a recorded build nor a git ref", baseline))?;
+    earlier
+        .find(|session| session.commit.as_ref() == Some(&commit))
+        .with_context(|| format!("No build of {} ({}) is recorded", baseline, &commit[..commit.len().min(12)]))
+}
+
+fn format_change(baseline_ms: Option<u64>, current_ms: u64, threshold: Option<f64>) -> ColoredString {
+    let Some(baseline_ms) = baseline_ms else {
+        return "new".dimmed();
+    };
+    let change = match baseline_ms {
+        0 => 0.0,
+        _ => (current_ms as f64 / baseline_ms as f64 - 1.0) * 100.0,
+    };
+    let text = format!("{:+.1}%", change);
+    match slowdown(Some(baseline_ms), current_ms) {
+        Some(slower) if threshold.is_some_and(|threshold| slower > threshold) => text.red(),
+        Some(slower) if slower > 0.0 => text.yellow(),
+        _ => text.normal(),
+    }
+}
+
+/// `lol compare`: prints the latest build against `baseline` and returns
+/// what regressed beyond `fail_on_regression` percent, when given.
+pub fn run(project_path: &Path, baseline: Option<&str>, fail_on_regression: Option<f64>) -> Result<Vec<String>> {
+    let sessions = history::load(project_path)?;
+    let current = sessions
+        .last()
+        .context("No builds recorded; set \"history\": true in lol.json to record them")?;
+    let baseline = find_baseline(&sessions, current, baseline, project_path)?;
+    let comparison = Comparison::new(baseline, current);
+
+    say!("📏 Build {} against build {}", comparison.current_id, comparison.baseline_id);
+    say!("  {:<14} {:>9} {:>9} {:>9}", "Language", "Baseline", "Current", "Change");
+    let seconds = |ms: u64| format!("{:.1}s", ms as f64 / 1000.0);
+    for change in &comparison.languages {
+        say!(
+            "  {:<14} {:>9} {:>9} {:>9}",
+            change.language,
+            change.baseline_ms.map(seconds).unwrap_or_else(|| "-".to_string()),
+            seconds(change.current_ms),
+            format_change(change.baseline_ms, change.current_ms, fail_on_regression)
+        );
+    }
+    say!(
+        "  {:<14} {:>9} {:>9} {:>9}",
+        "Total".bold(),
+        seconds(comparison.baseline_ms),
+        seconds(comparison.current_ms),
+        format_change(Some(comparison.baseline_ms), comparison.current_ms, fail_on_regression)
+    );
+
+    // Without a gate, only new failures are worth listing
+    let regressions = comparison.regressions(fail_on_regression.unwrap_or(f64::INFINITY));
+    for regression in &regressions {
+        say_error!("❌ {}", regression);
+    }
+    Ok(if fail_on_regression.is_some() { regressions } else { Vec::new() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::LanguageRecord;
+
+    fn session(id: u64, duration_ms: u64, languages: Vec<LanguageRecord>) -> Session {
+        Session { id, timestamp: 0, duration_ms, success: true, commit: None, languages }
+    }
+
+    fn language(language: &str, duration_ms: u64, failed_files: &[&str]) -> LanguageRecord {
+        LanguageRecord {
+            language: language.to_string(),
+            compiled: 2,
+            failed: failed_files.len(),
+            failed_files: failed_files.iter().map(|file| file.to_string()).collect(),
+            errors: failed_files.len(),
+            warnings: 0,
+            duration_ms,
+            retries: 0,
+            missing_toolchain: None,
+        }
+    }
+
+    #[test]
+    fn test_regressions_against_baseline() {
+        let baseline = session(1, 3000, vec![language("C", 1000, &["a.c"]), language("Rust", 2000, &[])]);
+        let current = session(2, 3600, vec![
+            language("C", 1050, &["a.c", "b.c"]),
+            language("Rust", 2500, &[]),
+            language("Go", 400, &[]),
+        ]);
+        let comparison = Comparison::new(&baseline, &current);
+        assert_eq!(comparison.languages[2].baseline_ms, None);
+        assert!(!comparison.languages[0].newly_failed);
+
+        // C's 5% is under 100ms, so noise; Rust's 25% and the total's 20% are not
+        assert_eq!(comparison.regressions(10.0), ["C fails in b.c", "Rust is 25.0% slower", "The build is 20.0% slower"]);
+        assert_eq!(comparison.regressions(22.0), ["C fails in b.c", "Rust is 25.0% slower"]);
+        assert_eq!(Comparison::new(&baseline, &baseline).regressions(0.0), Vec::<String>::new());
+
+        let sessions = [baseline, current];
+        assert_eq!(find_baseline(&sessions, &sessions[1], None, Path::new(".")).unwrap().id, 1);
+        assert_eq!(find_baseline(&sessions, &sessions[1], Some("1"), Path::new(".")).unwrap().id, 1);
+    }
+}
@@ -1,19 +1,49 @@
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
 use std::process::Output;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
 use anyhow::{Context, Result};
+use futures_util::stream::{FuturesOrdered, StreamExt};
+use regex::Regex;
 use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
-use rayon::prelude::*;
 use tokio::sync::Semaphore;
 use crate::config::Config;
-use crate::language_support::Language;
-use crate::args::Args;
+use crate::compiler_pool::CompilerPool;
+use crate::dir_config::DirConfigs;
+use crate::language_support::{Lane, Language};
+use crate::args::{Args, Target};
 use std::process::Command;
-use crate::language_support::LanguageSupport;
+use crate::language_support::{CompileOptions, Toolchain};
+use crate::node_env::NodeEnvironment;
+use crate::build_info::BuildInfo;
+use crate::events::{self, BuildEvent, EventSender};
+use crate::python_env::{PythonEnvironment, PythonEnvironmentKind};
+use crate::diagnostics::{self, Diagnostic, Severity};
+use crate::plugins::PluginHost;
+use crate::dev_env::DevShell;
+use crate::doctor::{self, on_path};
+use crate::msvc;
+use crate::reproducible;
+use crate::stream;
 
 pub struct Compiler {
+    config: Config,
     max_jobs: usize,
+    check_jobs: usize,
+    job_pools: Option<JobPools>,
+    plugins: Arc<PluginHost>,
+    build_info: Option<BuildInfo>,
+    events: Option<EventSender>,
+    dev_shell: Option<DevShell>,
+    stream_output: bool,
+    retries: u32,
+    retry_backoff: Duration,
+    /// Retries so far, across languages
+    retried: AtomicU32,
+    compiler_pool: Option<Arc<CompilerPool>>,
 }
 
 #[derive(Debug)]
@@ -21,30 +51,256 @@ pub struct CompilationResult {
     pub language: Language,
     pub files: Vec<PathBuf>,
     pub status: CompilationStatus,
+    pub diagnostics: Vec<Diagnostic>,
+    pub toolchain: Option<String>,
+    /// Compiles repeated after a transient failure
+    pub retries: u32,
+    pub duration_ms: u64,
 }
 
 #[derive(Debug)]
 pub enum CompilationStatus {
     Success { output: String },
     Failure { error: String },
+    /// Not compiled, because `program` is not installed
+    ToolchainMissing { program: String, hint: String },
 }
 
+/// Everything `compile_all` compiled, with what the exit status needs.
+#[derive(Debug, Default)]
+pub struct BuildSummary {
+    pub results: Vec<CompilationResult>,
+    /// Files that failed to compile, by language; a failed batch counts all
+    /// of its files
+    pub failed_files: HashMap<Language, Vec<PathBuf>>,
+    /// Compilers that were not installed, such as `zig`
+    pub missing_toolchains: Vec<String>,
+    /// Files the compilers wrote outside a language's output subdirectory,
+    /// such as objects next to their sources
+    pub outputs: Vec<BuiltFile>,
+}
+
+/// A file a compile wrote, with the source it was compiled from.
+#[derive(Debug, Clone)]
+pub struct BuiltFile {
+    pub language: Language,
+    pub path: PathBuf,
+    pub source: PathBuf,
+}
+
+impl BuildSummary {
+    fn merge(&mut self, other: BuildSummary) {
+        self.results.extend(other.results);
+        self.outputs.extend(other.outputs);
+        for (language, files) in other.failed_files {
+            self.failed_files.entry(language).or_default().extend(files);
+        }
+        for program in other.missing_toolchains {
+            if !self.missing_toolchains.contains(&program) {
+                self.missing_toolchains.push(program);
+            }
+        }
+    }
+}
+
+/// Job slots, one pool per lane, that several builds can share.
+#[derive(Clone)]
+pub struct JobPools {
+    pub compile: Arc<Semaphore>,
+    pub check: Arc<Semaphore>,
+}
+
+impl JobPools {
+    pub fn new(compile_jobs: usize, check_jobs: usize) -> Self {
+        Self {
+            compile: Arc::new(Semaphore::new(compile_jobs.max(1))),
+            check: Arc::new(Semaphore::new(check_jobs.max(1))),
+        }
+    }
+}
+
+/// Why a build failed, which decides the process exit status.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuildFailure {
+    CompileErrors,
+    ToolchainMissing,
+}
+
+impl BuildSummary {
+    fn note_output(&mut self, language: &Language, file: &Path, options: &CompileOptions) {
+        let artifact = language.artifact_file(file, options);
+        let executable = language.executable_file(file, options).filter(|path| Some(path) != artifact.as_ref());
+        for path in artifact.into_iter().chain(executable) {
+            self.outputs.push(BuiltFile { language: language.clone(), path, source: file.to_path_buf() });
+        }
+    }
+
+    fn note_failure(&mut self, language: &Language, files: &[PathBuf], error: &anyhow::Error) {
+        self.failed_files.entry(language.clone()).or_default().extend_from_slice(files);
+        if let Some(missing) = error.downcast_ref::<ToolchainMissing>() {
+            if !self.missing_toolchains.contains(&missing.program) {
+                self.missing_toolchains.push(missing.program.clone());
+            }
+        }
+    }
+
+    /// `None` when every language compiled. A missing compiler outranks
+    /// compile errors elsewhere, since it needs fixing on the machine.
+    pub fn failure(&self) -> Option<BuildFailure> {
+        if !self.missing_toolchains.is_empty() {
+            Some(BuildFailure::ToolchainMissing)
+        } else if self.results.iter().any(|result| matches!(result.status, CompilationStatus::Failure { .. })) {
+            Some(BuildFailure::CompileErrors)
+        } else {
+            None
+        }
+    }
+}
+
+/// A compiler that is not installed, told apart from compile errors.
+#[derive(Debug)]
+pub struct ToolchainMissing {
+    pub program: String,
+}
+
+impl std::fmt::Display for ToolchainMissing {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Compiler '{}' not found", self.program)
+    }
+}
+
+impl std::error::Error for ToolchainMissing {}
+
 impl Compiler {
-    pub fn new(_config: Config, max_jobs: usize) -> Self {
+    pub fn new(config: Config, max_jobs: usize) -> Self {
         Self {
+            config,
             max_jobs,
+            check_jobs: max_jobs,
+            job_pools: None,
+            plugins: Arc::new(PluginHost::default()),
+            build_info: None,
+            events: None,
+            dev_shell: None,
+            stream_output: false,
+            retries: 0,
+            retry_backoff: Duration::ZERO,
+            retried: AtomicU32::new(0),
+            compiler_pool: None,
+        }
+    }
+
+    /// Lets loaded plugins contribute diagnostics parsers.
+    pub fn with_plugins(mut self, plugins: Arc<PluginHost>) -> Self {
+        self.plugins = plugins;
+        self
+    }
+
+    /// Runs up to `check_jobs` syntax checks at a time, apart from the
+    /// `max_jobs` compiles.
+    pub fn with_check_jobs(mut self, check_jobs: usize) -> Self {
+        self.check_jobs = check_jobs.max(1);
+        self
+    }
+
+    /// Draws job slots from `job_pools` instead of pools of `max_jobs` and
+    /// `check_jobs` owned by this compiler.
+    pub fn with_job_pools(mut self, job_pools: JobPools) -> Self {
+        self.job_pools = Some(job_pools);
+        self
+    }
+
+    /// Injects version, commit and build time into every compile.
+    pub fn with_build_info(mut self, build_info: BuildInfo) -> Self {
+        self.build_info = Some(build_info);
+        self
+    }
+
+    /// Reports per-language and per-file progress to `events`.
+    pub fn with_events(mut self, events: EventSender) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    /// Runs compilers and version probes inside `dev_shell`.
+    pub fn with_dev_shell(mut self, dev_shell: DevShell) -> Self {
+        self.dev_shell = Some(dev_shell);
+        self
+    }
+
+    /// Echoes compiler output line by line while it runs, instead of only
+    /// collecting it for the summary.
+    pub fn with_stream_output(mut self) -> Self {
+        self.stream_output = true;
+        self
+    }
+
+    /// Compiles again, up to `retries` times, when a failure matches one of
+    /// the `retry_patterns`, waiting `backoff`, then twice that, and so on.
+    pub fn with_retries(mut self, retries: u32, backoff: Duration) -> Self {
+        self.retries = retries;
+        self.retry_backoff = backoff;
+        self
+    }
+
+    /// Sends the compiles of languages with `daemon` set to the warm
+    /// processes in `compiler_pool` where it has one for their compiler.
+    pub fn with_compiler_pool(mut self, compiler_pool: Arc<CompilerPool>) -> Self {
+        self.compiler_pool = Some(compiler_pool);
+        self
+    }
+
+    /// Runs `command` on the host, or inside the dev shell when there is one.
+    fn output(&self, command: &mut Command) -> std::io::Result<Output> {
+        match &self.dev_shell {
+            Some(dev_shell) => dev_shell.output(command),
+            None => command.output(),
         }
     }
 
+    /// Runs one compile step like `output`, streaming it under `label`
+    /// when asked to, or in `compiler_pool` when given one that serves the
+    /// command. The step runs on tokio's blocking pool, so that the lanes
+    /// and other files carry on meanwhile; the command is handed back for
+    /// a retry.
+    async fn run_step(&self, mut command: Command, label: &str, compiler_pool: Option<Arc<CompilerPool>>) -> (Command, std::io::Result<Output>) {
+        let dev_shell = self.dev_shell.clone();
+        let stream_output = self.stream_output;
+        let label = label.to_string();
+        let step = tokio::task::spawn_blocking(move || {
+            let pooled = compiler_pool.and_then(|compiler_pool| compiler_pool.run(&command)).map(Ok);
+            let output = pooled.unwrap_or_else(|| match (&dev_shell, stream_output) {
+                (Some(dev_shell), true) => stream::output(&mut dev_shell.wrap(&command), &label),
+                (Some(dev_shell), false) => dev_shell.output(&command),
+                (None, true) => stream::output(&mut command, &label),
+                (None, false) => command.output(),
+            });
+            (command, output)
+        });
+        step.await.unwrap_or_else(|failure| std::panic::resume_unwind(failure.into_panic()))
+    }
+
+    pub fn plugins(&self) -> &PluginHost {
+        &self.plugins
+    }
+
+    fn parse_diagnostics(&self, output: &str) -> Vec<Diagnostic> {
+        let mut parsed = diagnostics::parse(output);
+        parsed.extend(self.plugins.parse_diagnostics(output));
+        parsed
+    }
+
     pub async fn compile_all(
         &self,
         source_files: HashMap<Language, Vec<PathBuf>>,
         multi_progress: &MultiProgress,
         progress_style: &ProgressStyle,
         args: &Args,
-    ) -> Result<Vec<CompilationResult>> {
-        let semaphore = Arc::new(Semaphore::new(self.max_jobs));
-        let mut results = Vec::new();
+    ) -> Result<BuildSummary> {
+        let pools = self
+            .job_pools
+            .clone()
+            .unwrap_or_else(|| JobPools::new(self.max_jobs, self.check_jobs));
 
         // Create progress bars for each language
         let mut progress_bars: HashMap<Language, ProgressBar> = HashMap::new();
@@ -56,75 +312,285 @@ impl Compiler {
             progress_bars.insert(language.clone(), progress_bar);
         }
 
+        // Every group's options are resolved first, so that artifact
+        // collisions are caught before anything is compiled
+        let mut groups: Vec<ResolvedGroup> = source_files
+            .into_iter()
+            .map(|(language, files)| {
+                let resolved = self.get_compile_options(&language, args);
+                (language, files, resolved)
+            })
+            .collect();
+        if let Some(erlang_files) = share_mix_project(&mut groups) {
+            if let Some(progress_bar) = progress_bars.get(&Language::Erlang) {
+                progress_bar.finish_with_message(format!("{} Erlang files compiled by mix with Elixir", erlang_files));
+            }
+            if let Some(progress_bar) = progress_bars.get(&Language::Elixir) {
+                progress_bar.inc_length(erlang_files as u64);
+            }
+        }
+        if let Err(error) = check_artifact_collisions(&groups) {
+            multi_progress.clear().context("Failed to clear the progress bars")?;
+            return Err(error);
+        }
+
+        // Checks and compiles run side by side, so that a few long compiles
+        // do not hold up many quick checks
+        let (checks, compiles): (Vec<_>, Vec<_>) = groups
+            .into_iter()
+            .partition(|(language, _, resolved)| matches!(resolved, Ok((options, _)) if language.lane(options) == Lane::Check));
+        let (mut summary, compiled) = tokio::join!(
+            self.compile_lane(checks, Lane::Check, &pools, &progress_bars, args),
+            self.compile_lane(compiles, Lane::Compile, &pools, &progress_bars, args),
+        );
+        summary.merge(compiled);
+
+        // Wait for all progress bars to finish
+        multi_progress.clear().context("Failed to clear the progress bars")?;
+
+        Ok(summary)
+    }
+
+    /// Compiles `groups`, one language after another, with job slots from
+    /// `lane`'s pool.
+    async fn compile_lane(
+        &self,
+        groups: Vec<ResolvedGroup>,
+        lane: Lane,
+        pools: &JobPools,
+        progress_bars: &HashMap<Language, ProgressBar>,
+        args: &Args,
+    ) -> BuildSummary {
+        let mut summary = BuildSummary::default();
+        // Compiles of one language run one at a time, since several write
+        // into shared outputs such as ghdl's work library; checks write
+        // nothing they share
+        let (semaphore, workers) = match lane {
+            Lane::Check => (&pools.check, self.check_jobs),
+            Lane::Compile => (&pools.compile, 1),
+        };
+
+        // Sources are canonical paths, so their `.lol.toml` files are found from the canonical root
+        let root = args.project_path.canonicalize().unwrap_or_else(|_| args.project_path.clone());
+        let mut dir_configs = DirConfigs::new(&root);
+
         // Compile each language group
-        for (language, files) in source_files {
+        for (language, files, resolved) in groups {
             let progress_bar = progress_bars.get(&language).unwrap().clone();
-            let semaphore = Arc::clone(&semaphore);
-            let custom_flags = self.get_custom_flags(&language, args);
-            
-            let result = self.compile_language_group(
+
+            let (options, toolchain) = match resolved {
+                Ok(resolved) => resolved,
+                Err(error) => {
+                    progress_bar.finish_with_message(format!("Skipped {} files", language.name()));
+                    tracing::error!(language = language.name(), "{:#}", error);
+                    summary.failed_files.insert(language.clone(), files.clone());
+                    summary.results.push(CompilationResult {
+                        language,
+                        files,
+                        status: CompilationStatus::Failure {
+                            error: error.to_string(),
+                        },
+                        diagnostics: Vec::new(),
+                        toolchain: None,
+                        retries: 0,
+                        duration_ms: 0,
+                    });
+                    continue;
+                }
+            };
+
+            if let Some(program) = self.missing_toolchain(&language, &files, &options) {
+                progress_bar.finish_with_message(format!("Skipped {} files", language.name()));
+                let missing = anyhow::Error::new(ToolchainMissing { program: program.clone() });
+                let status = if args.strict_toolchains {
+                    summary.note_failure(&language, &files, &missing);
+                    CompilationStatus::Failure { error: missing.to_string() }
+                } else {
+                    tracing::warn!(language = language.name(), "{}; skipping {} files", missing, files.len());
+                    CompilationStatus::ToolchainMissing { program, hint: doctor::install_hint(&language) }
+                };
+                summary.results.push(CompilationResult { language, files: Vec::new(), status, diagnostics: Vec::new(), toolchain: None, retries: 0, duration_ms: 0 });
+                continue;
+            }
+
+            let started = std::time::Instant::now();
+            tracing::info!(language = language.name(), files = files.len(), "Compiling");
+            events::emit(self.events.as_ref(), BuildEvent::LanguageStarted { language: language.name().to_string(), files: files.len() });
+            let retried = self.retried.load(Ordering::Relaxed);
+            let parts = match dir_configs.partition(files.clone(), language.config_key()) {
+                Ok(parts) => parts.into_iter().map(|(flags, files)| (options.with_extra_flags(&flags), files)).collect(),
+                Err(error) => {
+                    progress_bar.finish_with_message(format!("Skipped {} files", language.name()));
+                    summary.note_failure(&language, &files, &error);
+                    summary.results.push(CompilationResult {
+                        language,
+                        files: Vec::new(),
+                        status: CompilationStatus::Failure { error: format!("{:#}", error) },
+                        diagnostics: Vec::new(),
+                        toolchain: None,
+                        retries: 0,
+                        duration_ms: 0,
+                    });
+                    continue;
+                }
+            };
+            let mut result = self.compile_language_group(
                 language.clone(),
-                files,
-                &semaphore,
+                parts,
+                semaphore,
+                workers,
                 &progress_bar,
-                custom_flags,
+                &mut summary,
             ).await;
+            result.toolchain = toolchain;
+            result.retries = self.retried.load(Ordering::Relaxed) - retried;
+            result.duration_ms = started.elapsed().as_millis() as u64;
+            tracing::info!(
+                language = result.language.name(),
+                success = matches!(result.status, CompilationStatus::Success { .. }),
+                diagnostics = result.diagnostics.len(),
+                duration_ms = result.duration_ms,
+                "Compiled"
+            );
+            events::emit(self.events.as_ref(), BuildEvent::LanguageFinished {
+                language: result.language.name().to_string(),
+                success: matches!(result.status, CompilationStatus::Success { .. }),
+                files: result.files.len(),
+                diagnostics: result.diagnostics.len(),
+                duration_ms: result.duration_ms,
+                toolchain: result.toolchain.clone(),
+                retries: result.retries,
+            });
 
-            results.push(result);
+            summary.results.push(result);
         }
 
-        // Wait for all progress bars to finish
-        multi_progress.clear().unwrap();
-
-        Ok(results)
+        summary
     }
 
+    /// Compiles `parts`, a language's files grouped by the options they
+    /// need, usually just one group, up to `workers` files at a time.
     async fn compile_language_group(
         &self,
         language: Language,
-        files: Vec<PathBuf>,
-        semaphore: &Arc<Semaphore>,
+        parts: Vec<(CompileOptions, Vec<PathBuf>)>,
+        semaphore: &Semaphore,
+        workers: usize,
         progress_bar: &ProgressBar,
-        custom_flags: Option<String>,
+        summary: &mut BuildSummary,
     ) -> CompilationResult {
         let mut successful_files = Vec::new();
         let mut failed_files = Vec::new();
         let mut compilation_output = String::new();
         let mut compilation_errors = String::new();
+        let mut diagnostics = Vec::new();
 
-        // Process files in parallel with semaphore limiting concurrency
-        let file_results: Vec<_> = files
-            .par_iter()
-            .map(|file| {
-                let semaphore = Arc::clone(semaphore);
-                let custom_flags = custom_flags.clone();
-                let language_clone = language.clone();
-                
-                async move {
-                    let _permit = semaphore.acquire().await.unwrap();
-                    self.compile_single_file(&language_clone, file, custom_flags.as_deref()).await
+        // Languages that compile a whole group in one invocation succeed or fail together
+        let batches: Vec<_> = parts
+            .iter()
+            .map_while(|(options, files)| Some((language.get_batch_compilation_command(files, options)?, options, files)))
+            .collect();
+        if !batches.is_empty() && batches.len() == parts.len() {
+            for (command, options, files) in batches {
+                let _permit = semaphore.acquire().await.unwrap();
+                let checked: Vec<&Path> = files.iter().map(PathBuf::as_path).collect();
+                let result = match self.execute(command, options, language.config_key()).await {
+                    Ok(output) => self.type_check(&language, &checked, options, language.config_key(), output).await,
+                    Err(error) => Err(error),
+                };
+                progress_bar.inc(files.len() as u64);
+
+                match result {
+                    Ok(output) => {
+                        diagnostics.extend(self.parse_diagnostics(&output));
+                        for file in files {
+                            summary.note_output(&language, file, options);
+                        }
+                        successful_files.extend(files.iter().cloned());
+                        compilation_output.push_str(&output);
+                    }
+                    Err(error) => {
+                        let error_diagnostics = self.parse_diagnostics(&error.to_string());
+                        let (failed, rest) = attribute_failures(files, &error_diagnostics);
+                        summary.note_failure(&language, &failed, &error);
+                        diagnostics.extend(error_diagnostics);
+                        for file in &rest {
+                            summary.note_output(&language, file, options);
+                        }
+                        successful_files.extend(rest);
+                        failed_files.extend(failed);
+                        compilation_errors.push_str(&error.to_string());
+                    }
                 }
-            })
+            }
+            progress_bar.finish_with_message(format!("Finished compiling {} files", language.name()));
+
+            let status = if failed_files.is_empty() {
+                CompilationStatus::Success { output: compilation_output }
+            } else {
+                CompilationStatus::Failure { error: compilation_errors }
+            };
+            return CompilationResult {
+                language,
+                files: successful_files,
+                status,
+                diagnostics,
+                toolchain: None,
+                retries: 0,
+                duration_ms: 0,
+            };
+        }
+
+        let files: Vec<(&PathBuf, &CompileOptions)> = parts
+            .iter()
+            .flat_map(|(options, files)| files.iter().map(move |file| (file, options)))
             .collect();
+        let files = if language == Language::Fortran {
+            let sources: Vec<&Path> = files.iter().map(|(file, _)| file.as_path()).collect();
+            Language::fortran_build_order(&sources).into_iter().map(|index| files[index]).collect()
+        } else {
+            files
+        };
+        let file_results = if workers > 1 {
+            run_parallel(&files, workers, semaphore, |&(file, options)| async {
+                let result = self.compile_single_file(&language, file, options).await;
+                progress_bar.inc(1);
+                result
+            })
+            .await
+        } else {
+            let mut results = Vec::new();
+            for &(file, options) in &files {
+                let _permit = semaphore.acquire().await.unwrap();
+                results.push(self.compile_single_file(&language, file, options).await);
+                progress_bar.inc(1);
+            }
+            results
+        };
+
+        for (&(file, options), result) in files.iter().zip(file_results) {
+            events::emit(self.events.as_ref(), BuildEvent::FileCompiled {
+                language: language.name().to_string(),
+                file: file.clone(),
+                success: result.is_ok(),
+            });
 
-        // Wait for all compilations to complete
-        for (file, result) in files.iter().zip(file_results) {
-            let result = result.await;
-            
             match result {
                 Ok(output) => {
+                    diagnostics.extend(self.parse_diagnostics(&output));
+                    summary.note_output(&language, file, options);
                     successful_files.push(file.clone());
                     if !output.is_empty() {
                         compilation_output.push_str(&format!("{}: {}\n", file.display(), output));
                     }
                 }
                 Err(error) => {
+                    summary.note_failure(&language, std::slice::from_ref(file), &error);
+                    diagnostics.extend(self.parse_diagnostics(&error.to_string()));
                     failed_files.push(file.clone());
                     compilation_errors.push_str(&format!("{}: {}\n", file.display(), error));
                 }
             }
-            
-            progress_bar.inc(1);
         }
 
         progress_bar.finish_with_message(format!("Finished compiling {} files", language.name()));
@@ -144,39 +610,553 @@ impl Compiler {
             language,
             files: successful_files,
             status,
+            diagnostics,
+            toolchain: None,
+            retries: 0,
+            duration_ms: 0,
         }
     }
 
     async fn compile_single_file(
         &self,
         language: &Language,
-        file: &PathBuf,
-        custom_flags: Option<&str>,
+        file: &Path,
+        options: &CompileOptions,
     ) -> Result<String> {
-        let mut command = language
-            .get_compilation_command(file, custom_flags)
+        if let Some(parent) = language.artifact_file(file, options).as_deref().and_then(Path::parent) {
+            std::fs::create_dir_all(parent).with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        let pipeline = language
+            .get_compilation_pipeline(file, options)
             .context("Failed to create compilation command")?;
 
-        // Execute compilation
-        let output = command
-            .output()
-            .context("Failed to execute compilation command")?;
+        let label = format!("{}:{}", language.config_key(), file.file_name().unwrap_or_default().to_string_lossy());
+        let mut result = String::new();
+        for command in pipeline {
+            let step_output = self.execute(command, options, &label).await?;
+            if !step_output.is_empty() {
+                if !result.is_empty() {
+                    result.push('\n');
+                }
+                result.push_str(&step_output);
+            }
+        }
 
-        if output.status.success() {
-            Ok(self.format_output(&output))
-        } else {
-            Err(anyhow::anyhow!("Compilation failed: {}", self.format_error(&output)))
+        self.type_check(language, &[file], options, &label, result).await
+    }
+
+    /// Runs the language's `type_checker`, if any, over `files` once they
+    /// compiled, adding what it prints to `output`.
+    async fn type_check(&self, language: &Language, files: &[&Path], options: &CompileOptions, label: &str, mut output: String) -> Result<String> {
+        let Some(checker) = self.get_type_checker(language) else {
+            return Ok(output);
+        };
+        let mut command = language.get_type_check_command(checker, files, options)?;
+        command.envs(options.env.iter().cloned());
+        let (_, checked) = self.run_step(command, label, None).await;
+        let checked = checked.with_context(|| format!("Failed to execute type checker '{}'", checker))?;
+
+        if !checked.status.success() {
+            return Err(anyhow::anyhow!("Type check failed:\n{}", self.format_error(&checked)));
+        }
+
+        let checker_output = self.format_output(&checked);
+        if !checker_output.is_empty() {
+            if !output.is_empty() {
+                output.push('\n');
+            }
+            output.push_str(&checker_output);
+        }
+        Ok(output)
+    }
+
+    /// Runs one compilation step with the language's environment applied.
+    /// `label` prefixes its lines when output is streamed.
+    async fn execute(&self, mut command: Command, options: &CompileOptions, label: &str) -> Result<String> {
+        command.envs(options.env.iter().cloned());
+        tracing::debug!(?command, "Running compiler");
+
+        // A dev shell has its own toolchain, which the pool's processes are not
+        let compiler_pool = self.compiler_pool.clone().filter(|_| options.settings.daemon && self.dev_shell.is_none());
+        let mut attempt = 0;
+        loop {
+            let (returned, output) = self.run_step(command, label, compiler_pool.clone()).await;
+            command = returned;
+            let output = match output {
+                Ok(output) => output,
+                Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+                    return Err(ToolchainMissing { program: command.get_program().to_string_lossy().into_owned() }.into());
+                }
+                Err(error) => return Err(error).context("Failed to execute compilation command"),
+            };
+
+            if output.status.success() {
+                return Ok(self.format_output(&output));
+            }
+            let error = self.format_error(&output);
+            if attempt == self.retries || !self.is_transient(&error) {
+                return Err(anyhow::anyhow!("Compilation failed:\n{}", error));
+            }
+            let delay = self.retry_backoff * 2u32.saturating_pow(attempt);
+            attempt += 1;
+            tracing::warn!(label, attempt, "Transient failure; compiling again in {:.1}s", delay.as_secs_f64());
+            self.retried.fetch_add(1, Ordering::Relaxed);
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    /// Whether a failure's output matches one of the `retry_patterns`.
+    fn is_transient(&self, error: &str) -> bool {
+        self.config.retry_patterns.iter().any(|pattern| match Regex::new(pattern) {
+            Ok(regex) => regex.is_match(error),
+            Err(error) => {
+                tracing::warn!("Invalid retry pattern {:?}: {}", pattern, error);
+                false
+            }
+        })
+    }
+
+    /// The language's `type_checker`, where `none` turns checking off.
+    /// TypeScript built by esbuild or swc, which only strip types, is
+    /// checked with tsc unless another checker is set.
+    fn get_type_checker(&self, language: &Language) -> Option<&str> {
+        let configured = self
+            .config
+            .get_language_config(language.config_key())
+            .and_then(|settings| settings.type_checker.as_deref());
+        match configured {
+            Some("none") => None,
+            Some(checker) => Some(checker),
+            None if *language == Language::TypeScript && self.config.ts_backend.as_deref().is_some_and(|backend| backend != "tsc") => Some("tsc"),
+            None => None,
+        }
+    }
+
+    /// Packages the compiled JVM classes into a runnable JAR when `jar` is
+    /// configured and every Java/Kotlin/Scala group compiled successfully.
+    pub fn package_jar(&self, results: &[CompilationResult], project_path: &Path) -> Result<Option<PathBuf>> {
+        let Some(jar_config) = &self.config.jar else {
+            return Ok(None);
+        };
+
+        let mut jvm_results = results
+            .iter()
+            .filter(|result| matches!(result.language, Language::Java | Language::Kotlin | Language::Scala))
+            .peekable();
+        if jvm_results.peek().is_none()
+            || jvm_results.any(|result| matches!(result.status, CompilationStatus::Failure { .. }))
+        {
+            return Ok(None);
         }
+
+        let output_dir = self
+            .config
+            .output_dir(project_path)
+            .context("JAR packaging requires an output_directory")?;
+        let name = jar_config.name.clone().unwrap_or_else(|| {
+            project_path
+                .canonicalize()
+                .ok()
+                .and_then(|path| path.file_name().map(|name| name.to_string_lossy().into_owned()))
+                .unwrap_or_else(|| "app".to_string())
+        });
+        let jar_path = output_dir.join(format!("{}.jar", name));
+
+        let mut command = Command::new("jar");
+        command.arg("--create").arg("--file").arg(&jar_path);
+        if let Some(main_class) = &jar_config.main_class {
+            command.arg("--main-class").arg(main_class);
+        }
+        // Entry times otherwise come from the class files (`--reproducible`)
+        if let Some(epoch) = reproducible::epoch() {
+            command.arg(format!("--date={}", crate::sbom::format_rfc3339(epoch)));
+        }
+        command.arg("-C").arg(output_dir.join("classes")).arg(".");
+
+        let output = command.output().context("Failed to execute jar")?;
+        if !output.status.success() {
+            anyhow::bail!("jar failed: {}", self.format_error(&output));
+        }
+
+        Ok(Some(jar_path))
     }
 
     fn get_custom_flags(&self, language: &Language, args: &Args) -> Option<String> {
         match language {
-            Language::C => args.cflags.clone(),
-            Language::Cpp => args.cxxflags.clone(),
+            Language::C | Language::ObjectiveC => args.cflags.clone(),
+            Language::Cpp | Language::ObjectiveCpp | Language::Cuda => args.cxxflags.clone(),
             _ => None,
         }
     }
 
+    /// Resolves per-language command options and a description of the
+    /// toolchain that will be used, for reporting in the results. Fails when
+    /// the language's toolchain is known to be unusable.
+    fn get_compile_options(&self, language: &Language, args: &Args) -> Result<(CompileOptions, Option<String>)> {
+        let mut options = CompileOptions {
+            custom_flags: self.get_custom_flags(language, args),
+            output_dir: self.config.output_dir(&args.project_path),
+            // Detected sources are canonical paths
+            project_root: Some(args.project_path.canonicalize().unwrap_or_else(|_| args.project_path.clone())),
+            out_of_tree: args.build_dir.is_some(),
+            profile: args.profile,
+            target: args.target,
+            ..CompileOptions::default()
+        };
+        let settings = self.config.get_language_config(language.config_key());
+        let mut toolchain = None;
+
+        if let Some(settings) = settings {
+            options.settings = settings.clone();
+        }
+
+        // The language's variables win over the global ones
+        options.env.extend(self.config.env.iter().map(|(name, value)| (name.clone(), value.clone())));
+        options.env.extend(options.settings.env.iter().map(|(name, value)| (name.clone(), value.clone())));
+        if let Some(path) = self.search_path(language) {
+            options.env.push(("PATH".to_string(), path.to_string_lossy().into_owned()));
+        }
+
+        if args.reproducible {
+            let root = args.project_path.canonicalize().unwrap_or_else(|_| args.project_path.clone());
+            options.remap_prefix = Some(root);
+        }
+        if let Some(epoch) = reproducible::epoch() {
+            options.env.push(("SOURCE_DATE_EPOCH".to_string(), epoch.to_string()));
+        }
+
+        if let Some(build_info) = &self.build_info {
+            options.env.extend(build_info.variables());
+            options.defines = build_info.defines();
+        }
+
+        if matches!(language, Language::JavaScript | Language::TypeScript) {
+            options.backend = self.config.ts_backend.clone().filter(|backend| backend != "tsc");
+
+            let environment = NodeEnvironment::detect(&args.project_path);
+            let mut tools: Vec<&str> = self.get_type_checker(language).into_iter().collect();
+            match (&options.backend, language) {
+                (Some(backend), _) => tools.push(backend),
+                (None, Language::TypeScript) => tools.push("tsc"),
+                _ => {}
+            }
+            for tool in tools {
+                environment.require_local_tool(tool)?;
+            }
+
+            options.tool_dirs.extend(environment.bin_dir());
+            let search_path = self.search_path(language);
+            toolchain = Some(match &options.backend {
+                Some(backend) => format!("{} ({})", backend, environment.describe(search_path.as_deref())),
+                None => environment.describe(search_path.as_deref()),
+            });
+        }
+
+        if matches!(language, Language::Kotlin | Language::Scala) && language.batches(&options) {
+            let compiler = match language {
+                Language::Scala if options.settings.daemon => "fsc (compile server)",
+                Language::Scala => "scalac",
+                _ => "kotlinc",
+            };
+            toolchain = toolchain.or_else(|| Some(format!("{}, batched", compiler)));
+        }
+        if matches!(language, Language::Java | Language::Kotlin | Language::TypeScript) && options.settings.daemon && self.compiler_pool.is_some() {
+            toolchain = toolchain.map(|toolchain| format!("{}, kept warm", toolchain));
+        }
+
+        if matches!(language, Language::Java | Language::Kotlin | Language::Scala) {
+            if let Some(classes_dir) = Language::classes_dir(&options) {
+                std::fs::create_dir_all(&classes_dir)
+                    .with_context(|| format!("Failed to create {}", classes_dir.display()))?;
+            }
+        }
+        if *language == Language::FSharp && language.batches(&options) {
+            if let Some(output_dir) = &options.output_dir {
+                let assembly_dir = output_dir.join(language.config_key());
+                std::fs::create_dir_all(&assembly_dir)
+                    .with_context(|| format!("Failed to create {}", assembly_dir.display()))?;
+            }
+        }
+
+        if language == &Language::Fortran {
+            if let Some(module_dir) = Language::fortran_module_dir(&options) {
+                std::fs::create_dir_all(&module_dir)
+                    .with_context(|| format!("Failed to create {}", module_dir.display()))?;
+            }
+        }
+
+        if language == &Language::Ada {
+            options.project_file = language.find_project_file(&args.project_path);
+            match &options.project_file {
+                Some(project_file) => {
+                    let name = project_file.file_name().unwrap_or_default().to_string_lossy();
+                    toolchain = Some(format!("gprbuild (project {})", name));
+                }
+                None => {
+                    if let Some(object_dir) = Language::ada_object_dir(&options) {
+                        std::fs::create_dir_all(&object_dir)
+                            .with_context(|| format!("Failed to create {}", object_dir.display()))?;
+                    }
+                }
+            }
+        }
+
+        if language == &Language::Lua {
+            // Fall back to LuaJIT's bytecode compiler when there is no luac
+            let available = |tool: &str| Command::new(tool).arg("-v").output().is_ok();
+            if !available("luac") && available("luajit") {
+                options.backend = Some("luajit".to_string());
+            }
+            toolchain = Some(options.backend.clone().unwrap_or_else(|| "luac".to_string()));
+
+            if options.settings.output_format.as_deref() == Some("bytecode") {
+                if let Some(output_dir) = &options.output_dir {
+                    let bytecode_dir = output_dir.join("lua");
+                    std::fs::create_dir_all(&bytecode_dir)
+                        .with_context(|| format!("Failed to create {}", bytecode_dir.display()))?;
+                }
+            }
+        }
+
+        if language == &Language::Ruby {
+            let gemfile = args.project_path.join("Gemfile");
+            let bundler_available = Command::new("bundle").arg("--version").output().is_ok();
+            if gemfile.is_file() && bundler_available {
+                options.toolchain = Some(Toolchain::with_args("bundle", ["exec", "ruby"]));
+                options.env.push(("BUNDLE_GEMFILE".to_string(), gemfile.to_string_lossy().into_owned()));
+                toolchain = Some("ruby (bundle exec)".to_string());
+            }
+        }
+
+        if matches!(language, Language::Elixir | Language::Erlang) {
+            options.project_file = language.find_project_file(&args.project_path);
+            match &options.project_file {
+                Some(project_file) => {
+                    let umbrella = std::fs::read_to_string(project_file)
+                        .is_ok_and(|manifest| manifest.contains("apps_path"));
+                    toolchain = Some(if umbrella { "mix (umbrella project)" } else { "mix" }.to_string());
+                }
+                None => {
+                    if let Some(beam_dir) = Language::beam_dir(&options) {
+                        std::fs::create_dir_all(&beam_dir)
+                            .with_context(|| format!("Failed to create {}", beam_dir.display()))?;
+                    }
+                }
+            }
+        }
+
+        if language == &Language::Crystal {
+            options.project_file = language.find_project_file(&args.project_path);
+            if options.project_file.is_some() {
+                toolchain = Some("shards build".to_string());
+            } else if let Some(output_dir) = &options.output_dir {
+                let binary_dir = output_dir.join("crystal");
+                std::fs::create_dir_all(&binary_dir)
+                    .with_context(|| format!("Failed to create {}", binary_dir.display()))?;
+            }
+        }
+
+        if matches!(language, Language::ObjectiveC | Language::ObjectiveCpp) {
+            // Outside macOS the Foundation headers and runtime come from GNUstep
+            if !cfg!(target_os = "macos") {
+                let gnustep_flags = Command::new("gnustep-config")
+                    .arg("--objc-flags")
+                    .output()
+                    .ok()
+                    .filter(|output| output.status.success())
+                    .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string());
+                if let Some(gnustep_flags) = gnustep_flags {
+                    let flags = options.custom_flags.get_or_insert_with(String::new);
+                    flags.push(' ');
+                    flags.push_str(&gnustep_flags);
+                    toolchain = Some("clang (GNUstep)".to_string());
+                }
+            }
+        }
+
+        if matches!(language, Language::C | Language::Cpp | Language::ObjectiveC | Language::ObjectiveCpp)
+            && !options.settings.frameworks.is_empty()
+        {
+            if let Some(output_dir) = &options.output_dir {
+                let link_dir = output_dir.join(language.config_key());
+                std::fs::create_dir_all(&link_dir)
+                    .with_context(|| format!("Failed to create {}", link_dir.display()))?;
+            }
+        }
+
+        let builds_wasm = language == &Language::WebAssembly
+            || (args.target == Some(Target::Wasm32) && matches!(language, Language::C | Language::Rust | Language::Zig));
+        if builds_wasm {
+            if let Some(output_dir) = &options.output_dir {
+                let wasm_dir = output_dir.join("wasm");
+                std::fs::create_dir_all(&wasm_dir)
+                    .with_context(|| format!("Failed to create {}", wasm_dir.display()))?;
+            }
+        }
+
+        if language == &Language::Pascal {
+            options.project_file = language.find_project_file(&args.project_path);
+            match &options.project_file {
+                Some(project_file) => {
+                    let name = project_file.file_name().unwrap_or_default().to_string_lossy();
+                    toolchain = Some(format!("lazbuild (project {})", name));
+                }
+                None => {
+                    if let Some(unit_dir) = Language::pascal_unit_dir(&options) {
+                        std::fs::create_dir_all(&unit_dir)
+                            .with_context(|| format!("Failed to create {}", unit_dir.display()))?;
+                    }
+                }
+            }
+        }
+
+        if language == &Language::Verilog {
+            // Prefer Verilator's linter, falling back to Icarus Verilog
+            let available = |tool: &str| Command::new(tool).arg("-V").output().is_ok();
+            if !available("verilator") && available("iverilog") {
+                options.backend = Some("iverilog".to_string());
+            }
+            toolchain = Some(match &options.backend {
+                Some(backend) => format!("{} (-t null)", backend),
+                None => "verilator --lint-only".to_string(),
+            });
+        }
+
+        if language == &Language::Vhdl {
+            if let Some(work_dir) = Language::vhdl_work_dir(&options) {
+                std::fs::create_dir_all(&work_dir)
+                    .with_context(|| format!("Failed to create {}", work_dir.display()))?;
+            }
+        }
+
+        if let Language::Custom(name) = language {
+            let definition = self
+                .config
+                .get_custom_language(name)
+                .with_context(|| format!("No definition for custom language '{}'", name))?;
+
+            if let Some(probe) = &definition.probe {
+                let mut words = probe.split_whitespace();
+                let available = words.next().is_some_and(|program| {
+                    Command::new(program)
+                        .args(words)
+                        .output()
+                        .is_ok_and(|output| output.status.success())
+                });
+                if !available {
+                    anyhow::bail!("{}: toolchain not available (`{}` failed)", name, probe);
+                }
+            }
+
+            if let Some(output_dir) = &options.output_dir {
+                let language_dir = output_dir.join(name);
+                std::fs::create_dir_all(&language_dir)
+                    .with_context(|| format!("Failed to create {}", language_dir.display()))?;
+            }
+            options.custom = Some(definition.clone());
+        }
+
+        if language == &Language::Cython && !language.check_compiler_available() {
+            anyhow::bail!("cython is not installed (install it with `pip install cython`)");
+        }
+
+        if language == &Language::Cuda && settings.and_then(|settings| settings.compiler_path.as_ref()).is_none() {
+            // nvcc is often installed outside PATH
+            let toolkit_dirs = ["CUDA_HOME", "CUDA_PATH"]
+                .iter()
+                .filter_map(std::env::var_os)
+                .map(PathBuf::from)
+                .chain(std::iter::once(PathBuf::from("/usr/local/cuda")));
+            options.tool_dirs.extend(toolkit_dirs.map(|dir| dir.join("bin")));
+
+            let nvcc = options.resolve_tool("nvcc");
+            let version = Command::new(&nvcc).arg("--version").output().ok().and_then(|output| {
+                String::from_utf8_lossy(&output.stdout)
+                    .lines()
+                    .find(|line| line.contains("release"))
+                    .map(|line| line.trim().to_string())
+            });
+            let Some(version) = version else {
+                anyhow::bail!(
+                    "nvcc not found: install the CUDA toolkit, set CUDA_HOME, or point compiler_path at nvcc"
+                );
+            };
+            toolchain = Some(format!("{} ({})", nvcc.to_string_lossy(), version));
+        }
+
+        if let Some(compiler_path) = settings.and_then(|settings| settings.compiler_path.as_deref()) {
+            options.toolchain = Some(Toolchain::new(compiler_path));
+            toolchain = Some(compiler_path.to_string());
+        } else if !options.settings.compilers.is_empty() {
+            let chosen = self.available_compiler(language).with_context(|| {
+                format!("None of the compilers for {} is installed: {}", language.name(), options.settings.compilers.join(", "))
+            })?;
+            options.toolchain = Some(Toolchain::new(&chosen));
+            toolchain = Some(chosen);
+        } else if let Some(cl) = self.msvc_fallback(language).filter(|_| options.target != Some(Target::Wasm32)) {
+            options.toolchain = Some(Toolchain::new(&cl));
+            toolchain = Some(format!("{} (MSVC)", cl));
+        }
+        // Only clang has a wasm32 backend; gcc would build for the host
+        if *language == Language::C && options.target == Some(Target::Wasm32) {
+            if let Some(toolchain) = &options.toolchain {
+                let stem = Path::new(&toolchain.program).file_stem().unwrap_or_default().to_string_lossy();
+                if !stem.starts_with("clang") {
+                    anyhow::bail!("C for wasm32 is compiled with clang, but the configured C compiler is {}", toolchain.program);
+                }
+            }
+        }
+        if options.toolchain.as_ref().is_some_and(|toolchain| msvc::is_cl(&toolchain.program)) {
+            with_msvc_environment(&mut options.env);
+        }
+
+        if matches!(language, Language::Python | Language::Cython) {
+            let python_settings = self.config.get_language_config(Language::Python.config_key());
+            let python_path = python_settings.and_then(|settings| settings.python_path.as_deref());
+            let environment = PythonEnvironment::detect(&args.project_path, python_path);
+
+            if language == &Language::Cython {
+                // The generated C is compiled as a Python extension module
+                let (mut c_options, _) = self.get_compile_options(&Language::C, args)?;
+                let mut flags = c_options.custom_flags.take().unwrap_or_default();
+                flags.push_str(" -fPIC");
+                if let Some(include_dir) = environment.include_dir() {
+                    flags.push_str(&format!(" -I{}", include_dir.display()));
+                }
+                c_options.custom_flags = Some(flags);
+                options.c_options = Some(Box::new(c_options));
+                toolchain = Some(format!("cython + {}", environment.describe()));
+            } else {
+                if let PythonEnvironmentKind::Virtualenv(venv_dir) = &environment.kind {
+                    options.tool_dirs.push(venv_dir.join("bin"));
+                    options.tool_dirs.push(venv_dir.join("Scripts"));
+                }
+                toolchain = Some(environment.describe());
+                options.toolchain = Some(environment.interpreter);
+
+                // Keep bytecode out of the source tree unless told otherwise
+                let pycache_prefix = options
+                    .settings
+                    .pycache_prefix
+                    .as_ref()
+                    .map(|prefix| args.project_path.join(prefix))
+                    .or_else(|| options.output_dir.as_ref().map(|dir| dir.join("pycache")));
+                if let Some(prefix) = pycache_prefix {
+                    options.env.push((
+                        "PYTHONPYCACHEPREFIX".to_string(),
+                        prefix.to_string_lossy().into_owned(),
+                    ));
+                }
+            }
+        }
+
+        if let Some(min_version) = options.settings.min_version.clone() {
+            check_min_version(self, language, &options, &min_version)?;
+        }
+
+        Ok((options, toolchain))
+    }
+
     fn format_output(&self, output: &Output) -> String {
         let stdout = String::from_utf8_lossy(&output.stdout);
         let stderr = String::from_utf8_lossy(&output.stderr);
@@ -209,24 +1189,110 @@ impl Compiler {
         result
     }
 
-    pub fn check_compilers_available(&self) -> HashMap<Language, bool> {
-        let mut availability = HashMap::new();
-        
-        for language in LanguageSupport::new().get_available_languages() {
-            availability.insert(language.clone(), language.check_compiler_available());
+    /// The `PATH` the compilers for `language` run with: its
+    /// `toolchain_paths`, then the global ones, then the inherited `PATH`
+    /// unless `isolate_path` is set. `None` when nothing changes it.
+    fn search_path(&self, language: &Language) -> Option<OsString> {
+        let settings = self.config.get_language_config(language.config_key());
+        let language_paths = settings.map(|settings| settings.toolchain_paths.as_slice()).unwrap_or_default();
+        let roots: Vec<PathBuf> = language_paths.iter().chain(&self.config.toolchain_paths).map(PathBuf::from).collect();
+        if roots.is_empty() && !self.config.isolate_path {
+            return None;
         }
-        
-        availability
+
+        // A PATH set under `env` stands in for the inherited one
+        let inherited = settings
+            .and_then(|settings| settings.env.get("PATH"))
+            .or_else(|| self.config.env.get("PATH"))
+            .map(OsString::from)
+            .or_else(|| std::env::var_os("PATH"))
+            .filter(|_| !self.config.isolate_path);
+        let inherited = inherited.iter().flat_map(std::env::split_paths);
+        std::env::join_paths(roots.into_iter().chain(inherited)).ok()
+    }
+
+    /// Runs `program` with `args`, finding it, and the tools it runs, on
+    /// the language's `PATH`.
+    fn probe(&self, language: &Language, program: &str, args: &[&str]) -> std::io::Result<Output> {
+        let mut command = Command::new(program);
+        let mut env = Vec::new();
+        if let Some(path) = self.search_path(language) {
+            env.push(("PATH".to_string(), path.to_string_lossy().into_owned()));
+        }
+        if msvc::is_cl(program) {
+            with_msvc_environment(&mut env);
+        } else {
+            command.args(args);
+        }
+        self.output(command.envs(env))
+    }
+
+    /// The program that would compile `files` when it is not installed.
+    /// Languages with built-in checkers need nothing, and inside a dev shell
+    /// the probe runs in the shell like the build would.
+    fn missing_toolchain(&self, language: &Language, files: &[PathBuf], options: &CompileOptions) -> Option<String> {
+        if !language.needs_compiler_check() {
+            return None;
+        }
+        let command = match language.get_batch_compilation_command(files, options) {
+            Some(command) => command,
+            None => language.get_compilation_pipeline(files.first()?, options).ok()?.into_iter().next()?,
+        };
+        let program = command.get_program().to_string_lossy().into_owned();
+        let (_, version_args) = language.get_compiler_command();
+        match self.probe(language, &program, &version_args) {
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Some(program),
+            _ => None,
+        }
+    }
+
+    /// The first of the language's `compilers` that can be run.
+    fn available_compiler(&self, language: &Language) -> Option<String> {
+        let settings = self.config.get_language_config(language.config_key())?;
+        let (_, version_args) = language.get_compiler_command();
+        settings
+            .compilers
+            .iter()
+            .find(|compiler| self.probe(language, compiler, &version_args).is_ok())
+            .cloned()
+    }
+
+    /// `cl.exe` for C and C++ on Windows machines that have Visual Studio's
+    /// C++ tools but no GCC.
+    fn msvc_fallback(&self, language: &Language) -> Option<String> {
+        let (default, _) = language.get_compiler_command();
+        let missing = matches!(language, Language::C | Language::Cpp) && !on_path(default);
+        (missing && msvc::environment().is_some()).then(|| "cl.exe".to_string())
+    }
+
+    /// The program that compiles `language`: its `compiler_path`, else the
+    /// first installed entry of its `compilers`, else the built-in default
+    /// or, in its absence on Windows, MSVC.
+    pub fn compiler_program(&self, language: &Language) -> String {
+        let settings = self.config.get_language_config(language.config_key());
+        settings
+            .and_then(|settings| settings.compiler_path.clone())
+            .or_else(|| self.available_compiler(language))
+            .or_else(|| self.msvc_fallback(language))
+            .unwrap_or_else(|| language.get_compiler_command().0.to_string())
     }
 
-    pub fn get_compiler_info(&self) -> HashMap<Language, String> {
+    /// Version strings of the toolchains for `languages`: the first line the
+    /// version probe prints, "Built-in" or "Not available".
+    pub fn get_compiler_info_for<'a>(&self, languages: impl IntoIterator<Item = &'a Language>) -> HashMap<Language, String> {
         let mut info = HashMap::new();
         
-        for language in LanguageSupport::new().get_available_languages() {
+        for language in languages {
             if language.needs_compiler_check() {
-                let (compiler, args) = language.get_compiler_command();
-                if let Ok(output) = Command::new(compiler).args(args).output() {
-                    let version = String::from_utf8_lossy(&output.stdout);
+                let (_, args) = language.get_compiler_command();
+                let program = self.compiler_program(language);
+                if let Ok(output) = self.probe(language, &program, &args) {
+                    // Some tools (javac, older gfortran) report their version
+                    // on stderr, and cl.exe prints only its banner there
+                    let stdout = String::from_utf8_lossy(&output.stdout);
+                    let stderr = String::from_utf8_lossy(&output.stderr);
+                    let (first, second) = if msvc::is_cl(&program) { (&stderr, &stdout) } else { (&stdout, &stderr) };
+                    let version = first.lines().chain(second.lines()).find(|line| !line.trim().is_empty()).unwrap_or("");
                     info.insert(language.clone(), version.trim().to_string());
                 } else {
                     info.insert(language.clone(), "Not available".to_string());
@@ -240,9 +1306,166 @@ impl Compiler {
     }
 }
 
+/// Adds the variables `cl.exe` needs to `env`, keeping the directories an
+/// existing `PATH` entry (from `toolchain_paths`) puts first.
+fn with_msvc_environment(env: &mut Vec<(String, String)>) {
+    let Some(msvc_env) = msvc::environment() else {
+        return;
+    };
+    for (name, value) in msvc_env {
+        match env.iter_mut().find(|(existing, _)| existing.eq_ignore_ascii_case(name)) {
+            Some((_, existing)) if name.eq_ignore_ascii_case("PATH") => *existing = format!("{};{}", existing, value),
+            Some(_) => {}
+            None => env.push((name.clone(), value.clone())),
+        }
+    }
+}
+
+/// Fails when the toolchain `options` resolve to reports a version older
+/// than `min_version`, so an outdated compiler is named up front rather
+/// than through the flags it does not understand.
+fn check_min_version(runner: &Compiler, language: &Language, options: &CompileOptions, min_version: &str) -> Result<()> {
+    let (compiler, version_args) = language.get_compiler_command();
+    let mut command = match &options.toolchain {
+        Some(toolchain) => toolchain.command(),
+        None => Command::new(options.resolve_tool(compiler)),
+    };
+    let program = command.get_program().to_string_lossy().into_owned();
+    let output = runner
+        .output(command.args(&version_args).envs(options.env.iter().cloned()))
+        .with_context(|| format!("{} requires {} {} or newer, but {} could not be run", language.name(), program, min_version, program))?;
+    let reported = format!("{}{}", String::from_utf8_lossy(&output.stdout), String::from_utf8_lossy(&output.stderr));
+    let Some(found) = reported_version(&reported) else {
+        anyhow::bail!("{}: could not read a version from `{} {}`", language.name(), program, version_args.join(" "));
+    };
+    if !version_at_least(&found, min_version) {
+        anyhow::bail!(
+            "{} {} is older than {}, the min_version set in language_settings.{}",
+            program,
+            found,
+            min_version,
+            language.config_key()
+        );
+    }
+    Ok(())
+}
+
+/// The first dotted version number in a `--version` banner, e.g. `12.2.0`
+/// in `gcc (Debian 12.2.0-14) 12.2.0` or `1.22.1` in `go version go1.22.1`.
+fn reported_version(banner: &str) -> Option<String> {
+    let pattern = regex::Regex::new(r"\d+(?:\.\d+)+").expect("valid version pattern");
+    pattern.find(banner).map(|found| found.as_str().to_string())
+}
+
+fn version_at_least(found: &str, min_version: &str) -> bool {
+    let mut found = crate::plugins::version_key(found);
+    let mut min_version = crate::plugins::version_key(min_version);
+    let len = found.len().max(min_version.len());
+    found.resize(len, 0);
+    min_version.resize(len, 0);
+    found >= min_version
+}
+
+/// Splits a failed batch into the files its errors were reported in and
+/// the rest. When no error names one of `files`, such as a crashed
+/// compiler, every file is blamed.
+fn attribute_failures(files: &[PathBuf], diagnostics: &[Diagnostic]) -> (Vec<PathBuf>, Vec<PathBuf>) {
+    let (failed, rest): (Vec<PathBuf>, Vec<PathBuf>) = files.iter().cloned().partition(|file| {
+        diagnostics
+            .iter()
+            .any(|diagnostic| diagnostic.severity == Severity::Error && (file.ends_with(&diagnostic.file) || diagnostic.file.ends_with(file)))
+    });
+    if failed.is_empty() {
+        (rest, failed)
+    } else {
+        (failed, rest)
+    }
+}
+
+/// Runs `job` for up to `workers` items at a time, each holding a slot from
+/// `semaphore` while it runs, and returns the results in order.
+async fn run_parallel<T, F, R>(items: &[T], workers: usize, semaphore: &Semaphore, job: impl Fn(&T) -> F) -> Vec<R>
+where
+    F: std::future::Future<Output = R>,
+{
+    let mut items = items.iter();
+    let mut running = FuturesOrdered::new();
+    let mut results = Vec::new();
+    loop {
+        while running.len() < workers.max(1) {
+            let Some(item) = items.next() else { break };
+            let job = job(item);
+            running.push_back(async {
+                let _permit = semaphore.acquire().await.unwrap();
+                job.await
+            });
+        }
+        match running.next().await {
+            Some(result) => results.push(result),
+            None => return results,
+        }
+    }
+}
+
+/// A language group with its options, or why they could not be resolved.
+type ResolvedGroup = (Language, Vec<PathBuf>, Result<(CompileOptions, Option<String>)>);
+
+/// Hands the Erlang sources to the Elixir group when both belong to the
+/// same mix project, so that `mix compile`, which builds both, runs once.
+/// Returns how many files moved.
+fn share_mix_project(groups: &mut Vec<ResolvedGroup>) -> Option<usize> {
+    let project_file = |language: &Language, groups: &[ResolvedGroup]| {
+        groups.iter().position(|(group_language, _, resolved)| {
+            group_language == language && matches!(resolved, Ok((options, _)) if options.project_file.is_some())
+        })
+    };
+    let erlang = project_file(&Language::Erlang, groups)?;
+    let elixir = project_file(&Language::Elixir, groups)?;
+    let same_project = match (&groups[erlang].2, &groups[elixir].2) {
+        (Ok((erlang, _)), Ok((elixir, _))) => erlang.project_file == elixir.project_file,
+        _ => false,
+    };
+    if !same_project {
+        return None;
+    }
+    let (_, erlang_files, _) = groups.remove(erlang);
+    let elixir = if elixir > erlang { elixir - 1 } else { elixir };
+    let moved = erlang_files.len();
+    groups[elixir].1.extend(erlang_files);
+    Some(moved)
+}
+
+/// Fails, naming both sources, when two files compiled one at a time would
+/// write the same artifact. Groups compiled in one invocation are left to
+/// their compiler.
+fn check_artifact_collisions(groups: &[ResolvedGroup]) -> Result<()> {
+    let mut claimed: HashMap<PathBuf, &Path> = HashMap::new();
+    let mut collisions = Vec::new();
+    for (language, files, resolved) in groups {
+        let Ok((options, _)) = resolved else { continue };
+        if language.get_batch_compilation_command(files, options).is_some() {
+            continue;
+        }
+        for file in files {
+            let Some(artifact) = language.artifact_file(file, options) else { continue };
+            if let Some(first) = claimed.get(&artifact) {
+                collisions.push(format!("  {} and {} would both write {}", first.display(), file.display(), artifact.display()));
+            } else {
+                claimed.insert(artifact, file);
+            }
+        }
+    }
+    if !collisions.is_empty() {
+        anyhow::bail!("Output artifacts collide; rename one of each pair:\n{}", collisions.join("\n"));
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use clap::Parser;
+    use std::sync::atomic::AtomicUsize;
 
     #[tokio::test]
     async fn test_compiler_creation() {
@@ -252,21 +1475,783 @@ mod tests {
         assert_eq!(compiler.max_jobs, 4);
     }
 
+    #[tokio::test]
+    async fn test_checks_run_in_their_own_lane() {
+        let options = CompileOptions::default();
+        assert_eq!(Language::Python.lane(&options), Lane::Check);
+        assert_eq!(Language::JavaScript.lane(&options), Lane::Check);
+        assert_eq!(Language::Cpp.lane(&options), Lane::Compile);
+        let bundled = CompileOptions { backend: Some("esbuild".to_string()), ..CompileOptions::default() };
+        assert_eq!(Language::JavaScript.lane(&bundled), Lane::Compile);
+        let php = CompileOptions {
+            custom: Some(crate::config::CustomLanguageConfig {
+                name: "php".to_string(),
+                extensions: vec!["php".to_string()],
+                check: Some("php -l {file}".to_string()),
+                compile: None,
+                probe: None,
+                comment: None,
+            }),
+            ..CompileOptions::default()
+        };
+        assert_eq!(Language::Custom("php".to_string()).lane(&php), Lane::Check);
+
+        let running = AtomicUsize::new(0);
+        let most = AtomicUsize::new(0);
+        let items: Vec<usize> = (0..12).collect();
+        let results = run_parallel(&items, 4, &Semaphore::new(2), |&item| {
+            let (running, most) = (&running, &most);
+            async move {
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                most.fetch_max(now, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(5)).await;
+                running.fetch_sub(1, Ordering::SeqCst);
+                item * 2
+            }
+        })
+        .await;
+        assert_eq!(results, items.iter().map(|item| item * 2).collect::<Vec<_>>());
+        assert_eq!(most.load(Ordering::SeqCst), 2);
+    }
+
     #[test]
-    fn test_compiler_availability_check() {
-        let config = Config::default();
+    fn test_min_version() {
+        assert_eq!(reported_version("gcc (Debian 12.2.0-14+deb12u1) 12.2.0").as_deref(), Some("12.2.0"));
+        assert_eq!(reported_version("go version go1.22.1 linux/amd64").as_deref(), Some("1.22.1"));
+        assert!(version_at_least("12.2.0", "12.0"));
+        assert!(version_at_least("12", "12.0"));
+        assert!(!version_at_least("11.4.0", "12.0"));
+        assert!(!version_at_least("1.9.2", "1.10"));
+
+        let mut config = Config::default();
+        config.set_language_config("c", crate::config::LanguageConfig {
+            compiler_path: Some("sh".to_string()),
+            min_version: Some("999.0".to_string()),
+            ..Default::default()
+        });
         let compiler = Compiler::new(config, 1);
-        let availability = compiler.check_compilers_available();
-        
-        // At least some compilers should be available on most systems
-        assert!(!availability.is_empty());
+        let args = Args::parse_from(["lol", "/project"]);
+        // `sh --version` either prints a version, which is too old, or fails
+        assert!(compiler.get_compile_options(&Language::C, &args).is_err());
+    }
+
+    #[test]
+    fn test_env_from_config() {
+        let mut config = Config::default();
+        config.env.insert("GOFLAGS".to_string(), "-mod=vendor".to_string());
+        config.env.insert("CGO_ENABLED".to_string(), "1".to_string());
+        config.set_language_config("go", crate::config::LanguageConfig {
+            env: HashMap::from([("CGO_ENABLED".to_string(), "0".to_string())]),
+            ..Default::default()
+        });
+        let compiler = Compiler::new(config, 1);
+        let args = Args::parse_from(["lol", "/project"]);
+
+        let (options, _) = compiler.get_compile_options(&Language::Go, &args).unwrap();
+        let mut command = Command::new("go");
+        command.envs(options.env.iter().cloned());
+        let envs: HashMap<_, _> = command.get_envs().collect();
+        assert_eq!(envs[std::ffi::OsStr::new("CGO_ENABLED")], Some(std::ffi::OsStr::new("0")));
+        assert_eq!(envs[std::ffi::OsStr::new("GOFLAGS")], Some(std::ffi::OsStr::new("-mod=vendor")));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_toolchain_paths() {
+        use std::os::unix::fs::PermissionsExt;
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let fake_gcc = temp_dir.path().join("gcc");
+        std::fs::write(&fake_gcc, "#!/bin/sh\necho 'gcc (lol test) 99.1.0'\n").unwrap();
+        std::fs::set_permissions(&fake_gcc, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let mut config = Config {
+            toolchain_paths: vec![temp_dir.path().to_string_lossy().into_owned()],
+            ..Config::default()
+        };
+        let compiler = Compiler::new(config.clone(), 1);
+        assert_eq!(compiler.get_compiler_info_for([&Language::C])[&Language::C], "gcc (lol test) 99.1.0");
+
+        let args = Args::parse_from(["lol", "/project"]);
+        let (options, _) = compiler.get_compile_options(&Language::C, &args).unwrap();
+        let path = &options.env.iter().rev().find(|(name, _)| name == "PATH").unwrap().1;
+        assert!(path.starts_with(&*temp_dir.path().to_string_lossy()));
+        assert!(path.len() > temp_dir.path().as_os_str().len());
+
+        config.isolate_path = true;
+        let path = Compiler::new(config, 1).search_path(&Language::C).unwrap();
+        assert_eq!(path, temp_dir.path().as_os_str());
+    }
+
+    #[test]
+    fn test_compiler_fallback_chain() {
+        let mut config = Config::default();
+        config.set_language_config("c", crate::config::LanguageConfig {
+            compilers: vec!["lol-no-such-cc".to_string(), "sh".to_string()],
+            ..Default::default()
+        });
+        config.set_language_config("cpp", crate::config::LanguageConfig {
+            compilers: vec!["lol-no-such-cc".to_string()],
+            ..Default::default()
+        });
+        let compiler = Compiler::new(config, 1);
+        let args = Args::parse_from(["lol", "/project"]);
+
+        assert_eq!(compiler.compiler_program(&Language::C), "sh");
+        let (_, toolchain) = compiler.get_compile_options(&Language::C, &args).unwrap();
+        assert_eq!(toolchain.as_deref(), Some("sh"));
+        let error = compiler.get_compile_options(&Language::Cpp, &args).unwrap_err();
+        assert!(error.to_string().contains("lol-no-such-cc"));
+        assert_eq!(compiler.compiler_program(&Language::Go), "go");
+    }
+
+    #[test]
+    fn test_type_checker_from_language_settings() {
+        let mut config = Config::default();
+        config.set_language_config("python", crate::config::LanguageConfig {
+            type_checker: Some("mypy".to_string()),
+            ..Default::default()
+        });
+        let compiler = Compiler::new(config, 1);
+
+        assert_eq!(compiler.get_type_checker(&Language::Python), Some("mypy"));
+        assert_eq!(compiler.get_type_checker(&Language::C), None);
+        assert!(Language::C
+            .get_type_check_command("mypy", &[Path::new("main.c")], &CompileOptions::default())
+            .is_err());
+    }
+
+    #[test]
+    fn test_python_bytecode_options() {
+        let mut config = Config::default();
+        config.set_language_config("python", crate::config::LanguageConfig {
+            optimization_level: Some(2),
+            compile_all: true,
+            ..Default::default()
+        });
+        let compiler = Compiler::new(config, 1);
+        let args = Args::parse_from(["lol", "/project"]);
+
+        let (options, _) = compiler.get_compile_options(&Language::Python, &args).unwrap();
+        assert!(options.env.contains(&(
+            "PYTHONPYCACHEPREFIX".to_string(),
+            "/project/build/pycache".to_string()
+        )));
+
+        let files = vec![PathBuf::from("src/a.py"), PathBuf::from("tools/c.py"), PathBuf::from("src/b.py")];
+        let command = Language::Python
+            .get_batch_compilation_command(&files, &options)
+            .unwrap();
+        let command_args: Vec<_> = command.get_args().collect();
+        assert_eq!(command_args, ["-OO", "-m", "compileall", "-q", "-l", "src", "tools"]);
+    }
+
+    #[test]
+    fn test_esbuild_backend_for_typescript() {
+        let config = Config {
+            ts_backend: Some("esbuild".to_string()),
+            ..Config::default()
+        };
+        let compiler = Compiler::new(config, 1);
+        let args = Args::parse_from(["lol", "/project"]);
+
+        let (options, toolchain) = compiler.get_compile_options(&Language::TypeScript, &args).unwrap();
+        assert!(toolchain.unwrap().starts_with("esbuild"));
+        assert_eq!(compiler.get_type_checker(&Language::TypeScript), Some("tsc"));
+        assert_eq!(compiler.get_type_checker(&Language::JavaScript), None);
+
+        let command = Language::TypeScript
+            .get_compilation_command(Path::new("/project/src/app.ts"), &options)
+            .unwrap();
+        assert_eq!(command.get_program(), "esbuild");
+        assert!(command
+            .get_args()
+            .any(|arg| arg == "--outfile=/project/build/js/src/app.js"));
+
+        let options = CompileOptions { output_dir: None, ..options };
+        let command = Language::TypeScript
+            .get_compilation_command(Path::new("/project/src/app.ts"), &options)
+            .unwrap();
+        assert!(command
+            .get_args()
+            .any(|arg| arg == "--outfile=/project/dist/js/src/app.js"));
+    }
+
+    #[test]
+    fn test_scala_batches_through_compile_server() {
+        let mut config = Config::default();
+        config.set_language_config("scala", crate::config::LanguageConfig {
+            daemon: true,
+            ..Default::default()
+        });
+        let compiler = Compiler::new(config, 1);
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let args = Args::parse_from(["lol", temp_dir.path().to_str().unwrap()]);
+
+        let (options, _) = compiler.get_compile_options(&Language::Scala, &args).unwrap();
+        let files = vec![PathBuf::from("A.scala"), PathBuf::from("B.scala")];
+        let command = Language::Scala
+            .get_batch_compilation_command(&files, &options)
+            .unwrap();
+
+        let classes_dir = temp_dir.path().join("build").join("classes");
+        assert!(classes_dir.is_dir());
+        assert_eq!(command.get_program(), "fsc");
+        let command_args: Vec<_> = command.get_args().collect();
+        assert_eq!(command_args, ["-d".as_ref(), classes_dir.as_os_str(), "A.scala".as_ref(), "B.scala".as_ref()]);
+    }
+
+    #[test]
+    fn test_batched_compilers_attribute_errors_per_file() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let args = Args::parse_from(["lol", temp_dir.path().to_str().unwrap()]);
+        let mut config = Config::default();
+        config.set_language_config("typescript", crate::config::LanguageConfig { batch: Some(false), ..Default::default() });
+        let compiler = Compiler::new(config, 1);
+
+        let files = vec![temp_dir.path().join("src/A.java"), temp_dir.path().join("src/B.java")];
+        let (options, _) = compiler.get_compile_options(&Language::Java, &args).unwrap();
+        let command = Language::Java.get_batch_compilation_command(&files, &options).unwrap();
+        assert_eq!(command.get_program(), "javac");
+        assert_eq!(command.get_args().count(), 4);
+        let (options, _) = compiler.get_compile_options(&Language::TypeScript, &args).unwrap();
+        assert!(Language::TypeScript.get_batch_compilation_command(&files, &options).is_none());
+
+        let output = format!("{}:3: error: cannot find symbol\nsrc/A.java(4,1): warning TS1: unused", files[1].display());
+        let (failed, rest) = attribute_failures(&files, &diagnostics::parse(&output));
+        assert_eq!((failed, rest), (vec![files[1].clone()], vec![files[0].clone()]));
+        let (failed, rest) = attribute_failures(&files, &diagnostics::parse("error: out of memory"));
+        assert_eq!((failed.len(), rest.len()), (2, 0));
+        assert_eq!(diagnostics::parse("src/app.ts(12,5): error TS2322: Type 'string' is not assignable")[0].line, Some(12));
+    }
+
+    #[test]
+    fn test_fortran_source_form_and_module_dir() {
+        let compiler = Compiler::new(Config::default(), 1);
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let args = Args::parse_from(["lol", temp_dir.path().to_str().unwrap()]);
+
+        let (options, _) = compiler.get_compile_options(&Language::Fortran, &args).unwrap();
+        let module_dir = temp_dir.path().join("build").join("fortran");
+        assert!(module_dir.is_dir());
+
+        let legacy = Language::Fortran
+            .get_compilation_command(Path::new("solver.f"), &options)
+            .unwrap();
+        let legacy_args: Vec<_> = legacy.get_args().collect();
+        assert_eq!(legacy_args[..4], ["-c".as_ref(), "-ffixed-form".as_ref(), "-J".as_ref(), module_dir.as_os_str()]);
+
+        let mesh = temp_dir.path().join("src").join("mesh.f90");
+        let modern = Language::Fortran
+            .get_compilation_command(&mesh, &options)
+            .unwrap();
+        assert!(modern.get_args().any(|arg| arg == "-ffree-form"));
+        assert!(modern.get_args().any(|arg| arg == module_dir.join("src").join("mesh.o").as_os_str()));
+
+        // A module is compiled before the files that use it, wherever it sorts
+        let geometry = temp_dir.path().join("geometry.f90");
+        let main = temp_dir.path().join("main.f90");
+        std::fs::create_dir_all(mesh.parent().unwrap()).unwrap();
+        std::fs::write(&main, "program main\n  use mesh_mod, only: refine\nend program main\n").unwrap();
+        std::fs::write(&mesh, "module mesh_mod\n  use, intrinsic :: iso_fortran_env\n  use geometry\nend module mesh_mod\n").unwrap();
+        std::fs::write(&geometry, "MODULE Geometry ! shapes\nEND MODULE Geometry\n").unwrap();
+        let order = Language::fortran_build_order(&[&main, &mesh, &geometry]);
+        assert_eq!(order, [2, 1, 0]);
+    }
+
+    #[test]
+    fn test_ada_prefers_gnat_project_file() {
+        let compiler = Compiler::new(Config::default(), 1);
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let args = Args::parse_from(["lol", temp_dir.path().to_str().unwrap()]);
+        let files = vec![temp_dir.path().join("main.adb")];
+
+        let (options, _) = compiler.get_compile_options(&Language::Ada, &args).unwrap();
+        assert!(Language::Ada.get_batch_compilation_command(&files, &options).is_none());
+        assert!(temp_dir.path().join("build").join("ada").is_dir());
+
+        let project_file = temp_dir.path().join("hello.gpr");
+        std::fs::write(&project_file, "project Hello is\nend Hello;\n").unwrap();
+        let (options, toolchain) = compiler.get_compile_options(&Language::Ada, &args).unwrap();
+        let command = Language::Ada
+            .get_batch_compilation_command(&files, &options)
+            .unwrap();
+
+        assert_eq!(toolchain.as_deref(), Some("gprbuild (project hello.gpr)"));
+        assert_eq!(command.get_program(), "gprbuild");
+        let command_args: Vec<_> = command.get_args().collect();
+        assert_eq!(command_args, ["-p".as_ref(), "-P".as_ref(), project_file.as_os_str()]);
+    }
+
+    #[test]
+    fn test_julia_check_modes() {
+        let mut options = CompileOptions::default();
+        let file = Path::new("solver.jl");
+
+        let syntax = Language::Julia.get_compilation_command(file, &options).unwrap();
+        assert!(!syntax.get_args().any(|arg| arg == "--compile=min"));
+
+        options.settings.check_mode = Some("precompile".to_string());
+        let precompile = Language::Julia.get_compilation_command(file, &options).unwrap();
+        let command_args: Vec<_> = precompile.get_args().collect();
+        assert_eq!(command_args, ["--startup-file=no", "--compile=min", "-e", "include(ARGS[1])", "solver.jl"]);
+
+        options.settings.check_mode = Some("run".to_string());
+        assert!(Language::Julia.get_compilation_command(file, &options).is_err());
+    }
+
+    #[test]
+    fn test_lua_bytecode_output() {
+        let mut options = CompileOptions {
+            output_dir: Some(PathBuf::from("build")),
+            ..CompileOptions::default()
+        };
+        let file = Path::new("src/game.lua");
+
+        let check = Language::Lua.get_compilation_command(file, &options).unwrap();
+        let command_args: Vec<_> = check.get_args().collect();
+        assert_eq!(command_args, ["-p", "src/game.lua"]);
+
+        options.settings.output_format = Some("bytecode".to_string());
+        let luac = Language::Lua.get_compilation_command(file, &options).unwrap();
+        let command_args: Vec<_> = luac.get_args().collect();
+        assert_eq!(command_args, ["-o", "build/lua/game.luac", "src/game.lua"]);
+
+        options.backend = Some("luajit".to_string());
+        let luajit = Language::Lua.get_compilation_command(file, &options).unwrap();
+        assert_eq!(luajit.get_program(), "luajit");
+        let command_args: Vec<_> = luajit.get_args().collect();
+        assert_eq!(command_args, ["-b", "src/game.lua", "build/lua/game.luac"]);
+    }
+
+    #[test]
+    fn test_mix_project_compiles_at_project_level() {
+        let compiler = Compiler::new(Config::default(), 1);
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let args = Args::parse_from(["lol", temp_dir.path().to_str().unwrap()]);
+        let files = vec![temp_dir.path().join("lib").join("app.ex"), temp_dir.path().join("mix.exs")];
+
+        let (options, _) = compiler.get_compile_options(&Language::Elixir, &args).unwrap();
+        let command = Language::Elixir
+            .get_batch_compilation_command(&files, &options)
+            .unwrap();
+        let beam_dir = temp_dir.path().join("build").join("beam");
+        assert_eq!(command.get_program(), "elixirc");
+        let command_args: Vec<_> = command.get_args().collect();
+        assert_eq!(command_args, ["-o".as_ref(), beam_dir.as_os_str(), files[0].as_os_str()]);
+
+        std::fs::write(
+            temp_dir.path().join("mix.exs"),
+            "defmodule Umbrella.MixProject do\n  def project, do: [apps_path: \"apps\"]\nend\n",
+        )
+        .unwrap();
+        let (options, toolchain) = compiler.get_compile_options(&Language::Erlang, &args).unwrap();
+        let command = Language::Erlang
+            .get_batch_compilation_command(&files, &options)
+            .unwrap();
+        assert_eq!(toolchain.as_deref(), Some("mix (umbrella project)"));
+        assert_eq!(command.get_program(), "mix");
+        assert_eq!(command.get_current_dir(), Some(temp_dir.path()));
+
+        // Erlang and Elixir sources of one mix project run mix once, together
+        let erlang_file = temp_dir.path().join("src").join("native.erl");
+        let mut groups: Vec<ResolvedGroup> = [(Language::Elixir, files[0].clone()), (Language::Erlang, erlang_file.clone())]
+            .into_iter()
+            .map(|(language, file)| {
+                let resolved = compiler.get_compile_options(&language, &args);
+                (language, vec![file], resolved)
+            })
+            .collect();
+        assert_eq!(share_mix_project(&mut groups), Some(1));
+        assert_eq!(groups.len(), 1);
+        assert_eq!((&groups[0].0, &groups[0].1), (&Language::Elixir, &vec![files[0].clone(), erlang_file]));
+    }
+
+    #[test]
+    fn test_crystal_release_profile() {
+        let compiler = Compiler::new(Config::default(), 1);
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let project = temp_dir.path().to_str().unwrap();
+        let file = temp_dir.path().join("hello.cr");
+
+        let args = Args::parse_from(["lol", project]);
+        let (options, _) = compiler.get_compile_options(&Language::Crystal, &args).unwrap();
+        let command = Language::Crystal.get_compilation_command(&file, &options).unwrap();
+        assert!(!command.get_args().any(|arg| arg == "--release"));
+
+        std::fs::write(temp_dir.path().join("shard.yml"), "name: hello\n").unwrap();
+        let args = Args::parse_from(["lol", project, "--profile", "release"]);
+        let (options, toolchain) = compiler.get_compile_options(&Language::Crystal, &args).unwrap();
+        let command = Language::Crystal
+            .get_batch_compilation_command(&[file], &options)
+            .unwrap();
+        assert_eq!(toolchain.as_deref(), Some("shards build"));
+        assert_eq!(command.get_program(), "shards");
+        let command_args: Vec<_> = command.get_args().collect();
+        assert_eq!(command_args, ["build", "--release"]);
+    }
+
+    #[test]
+    fn test_objective_c_language_and_frameworks() {
+        let mut options = CompileOptions {
+            output_dir: Some(PathBuf::from("build")),
+            ..CompileOptions::default()
+        };
+
+        let command = Language::ObjectiveCpp
+            .get_compilation_command(Path::new("View.mm"), &options)
+            .unwrap();
+        let command_args: Vec<_> = command.get_args().collect();
+        assert_eq!(command_args, ["-x", "objective-c++", "-fobjc-arc", "-c", "-o", "View.o", "View.mm"]);
+        assert!(Language::ObjectiveC.get_batch_compilation_command(&[], &options).is_none());
+
+        options.settings.frameworks = vec!["Foundation".to_string()];
+        let files = vec![PathBuf::from("main.m")];
+        let command = Language::ObjectiveC
+            .get_batch_compilation_command(&files, &options)
+            .unwrap();
+        let command_args: Vec<_> = command.get_args().collect();
+        assert_eq!(
+            command_args,
+            ["-x", "objective-c", "-fobjc-arc", "main.m", "-framework", "Foundation", "-o", "build/objc/app"]
+        );
+
+        // Plain C links its frameworks the same way
+        let files = vec![PathBuf::from("main.c")];
+        let command = Language::C.get_batch_compilation_command(&files, &options).unwrap();
+        let command_args: Vec<_> = command.get_args().collect();
+        assert_eq!(command.get_program(), Language::C.get_compiler_command().0);
+        assert_eq!(command_args, ["main.c", "-framework", "Foundation", "-o", "build/c/app"]);
+    }
+
+    #[test]
+    fn test_cuda_arch_and_host_flags() {
+        let mut options = CompileOptions {
+            custom_flags: Some("-O2 -fopenmp".to_string()),
+            ..CompileOptions::default()
+        };
+        options.settings.arch = Some("sm_80".to_string());
+
+        let command = Language::Cuda
+            .get_compilation_command(Path::new("kernel.cu"), &options)
+            .unwrap();
+        let command_args: Vec<_> = command.get_args().collect();
+        assert_eq!(
+            command_args,
+            ["-c", "-arch=sm_80", "-Xcompiler", "-O2,-fopenmp", "-o", "kernel.o", "kernel.cu"]
+        );
+        assert!(Language::Cuda
+            .get_compilation_pipeline(Path::new("kernel.cuh"), &options)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_msvc_command_line() {
+        let options = CompileOptions {
+            toolchain: Some(Toolchain::new("cl.exe")),
+            custom_flags: Some("/O2".to_string()),
+            defines: vec!["LOL_VERSION=\"1.0\"".to_string()],
+            ..CompileOptions::default()
+        };
+        let command = Language::Cpp.get_compilation_command(Path::new("main.cpp"), &options).unwrap();
+        let command_args: Vec<_> = command.get_args().collect();
+        assert_eq!(command.get_program(), "cl.exe");
+        assert_eq!(command_args, ["/nologo", "/c", "/EHsc", "/DLOL_VERSION=\"1.0\"", "/O2", "/Fomain.obj", "main.cpp"]);
+    }
+
+    #[test]
+    fn test_reproducible_remaps_source_paths() {
+        let compiler = Compiler::new(Config::default(), 1);
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let args = Args::parse_from(["lol", temp_dir.path().to_str().unwrap(), "--reproducible"]);
+        let root = temp_dir.path().canonicalize().unwrap();
+
+        let (options, _) = compiler.get_compile_options(&Language::C, &args).unwrap();
+        let command = Language::C.get_compilation_command(Path::new("main.c"), &options).unwrap();
+        let command_args: Vec<_> = command.get_args().map(|arg| arg.to_string_lossy().into_owned()).collect();
+        assert_eq!(command_args[1], format!("-ffile-prefix-map={}=.", root.display()));
+
+        let (options, _) = compiler.get_compile_options(&Language::Rust, &args).unwrap();
+        let command = Language::Rust.get_compilation_command(Path::new("main.rs"), &options).unwrap();
+        let command_args: Vec<_> = command.get_args().map(|arg| arg.to_string_lossy().into_owned()).collect();
+        assert_eq!(command_args, [format!("--remap-path-prefix={}=.", root.display()), "main.rs".to_string()]);
+    }
+
+    #[test]
+    fn test_wasm32_target() {
+        let compiler = Compiler::new(Config::default(), 1);
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let args = Args::parse_from(["lol", temp_dir.path().to_str().unwrap(), "--target", "wasm32"]);
+        let wasm_file = temp_dir.path().join("build").join("wasm").join("lib.wasm");
+
+        let (options, _) = compiler.get_compile_options(&Language::Rust, &args).unwrap();
+        assert!(wasm_file.parent().unwrap().is_dir());
+        let command = Language::Rust
+            .get_compilation_command(Path::new("lib.rs"), &options)
+            .unwrap();
+        let command_args: Vec<_> = command.get_args().collect();
+        assert_eq!(
+            command_args,
+            [
+                "--target".as_ref(),
+                "wasm32-unknown-unknown".as_ref(),
+                "--crate-type".as_ref(),
+                "cdylib".as_ref(),
+                "-o".as_ref(),
+                wasm_file.as_os_str(),
+                "lib.rs".as_ref(),
+            ]
+        );
+
+        let (options, _) = compiler.get_compile_options(&Language::C, &args).unwrap();
+        let command = Language::C.get_compilation_command(Path::new("lib.c"), &options).unwrap();
+        assert_eq!(command.get_program(), "clang");
+
+        let mut config = Config::default();
+        config.set_language_config("c", crate::config::LanguageConfig { compiler_path: Some("/usr/bin/gcc".to_string()), ..Default::default() });
+        let error = Compiler::new(config, 1).get_compile_options(&Language::C, &args).unwrap_err();
+        assert!(error.to_string().contains("configured C compiler is /usr/bin/gcc"), "{}", error);
+        assert!(command.get_args().any(|arg| arg == wasm_file.as_os_str()));
+
+        let (options, _) = compiler.get_compile_options(&Language::WebAssembly, &args).unwrap();
+        let command = Language::WebAssembly
+            .get_compilation_command(Path::new("lib.wasm"), &options)
+            .unwrap();
+        assert_eq!(command.get_program(), "wasm-validate");
+    }
+
+    #[test]
+    fn test_artifact_collisions_are_found_up_front() {
+        let options = CompileOptions {
+            output_dir: Some(PathBuf::from("project/build")),
+            project_root: Some(PathBuf::from("project")),
+            target: Some(Target::Wasm32),
+            ..CompileOptions::default()
+        };
+        let utils = vec![PathBuf::from("project/a/util.c"), PathBuf::from("project/b/util.c")];
+        assert_eq!(
+            Language::C.artifact_file(&utils[1], &options),
+            Some(PathBuf::from("project/build/wasm/b/util.wasm"))
+        );
+        let groups = vec![(Language::C, utils, Ok((options, None)))];
+        assert!(check_artifact_collisions(&groups).is_ok());
+
+        let options = CompileOptions::default();
+        let groups = vec![
+            (Language::C, vec![PathBuf::from("src/util.c")], Ok((options.clone(), None))),
+            (Language::Cpp, vec![PathBuf::from("src/util.cpp")], Ok((options, None))),
+        ];
+        let error = check_artifact_collisions(&groups).unwrap_err().to_string();
+        assert!(error.contains("src/util.c and src/util.cpp would both write src/util.o"), "{}", error);
+    }
+
+    #[test]
+    fn test_out_of_tree_builds_keep_outputs_in_build_dir() {
+        let mut options = CompileOptions {
+            output_dir: Some(PathBuf::from("/out")),
+            project_root: Some(PathBuf::from("/project")),
+            ..CompileOptions::default()
+        };
+        let main = Path::new("/project/cmd/tool/main.go");
+        let command = Language::Go.get_compilation_command(main, &options).unwrap();
+        assert_eq!(command.get_args().collect::<Vec<_>>(), ["build", "/project/cmd/tool/main.go"]);
+        assert_eq!(Language::C.artifact_file(Path::new("/project/src/util.c"), &options), Some(PathBuf::from("/project/src/util.o")));
+
+        options.out_of_tree = true;
+        let binary = PathBuf::from("/out/bin/cmd/tool").join(Language::executable_name(main));
+        let command = Language::Go.get_compilation_command(main, &options).unwrap();
+        assert_eq!(
+            command.get_args().collect::<Vec<_>>(),
+            ["build".as_ref(), "-o".as_ref(), binary.as_os_str(), main.as_os_str()]
+        );
+        assert_eq!(Language::C.artifact_file(Path::new("/project/src/util.c"), &options), Some(PathBuf::from("/out/obj/src/util.o")));
+    }
+
+    #[test]
+    fn test_pascal_units_and_lazarus_projects() {
+        let compiler = Compiler::new(Config::default(), 1);
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let args = Args::parse_from(["lol", temp_dir.path().to_str().unwrap()]);
+        let files = vec![temp_dir.path().join("unit1.pas")];
+
+        let (options, _) = compiler.get_compile_options(&Language::Pascal, &args).unwrap();
+        let unit_dir = temp_dir.path().join("build").join("pascal");
+        assert!(unit_dir.is_dir());
+        assert!(Language::Pascal.get_batch_compilation_command(&files, &options).is_none());
+        let command = Language::Pascal.get_compilation_command(&files[0], &options).unwrap();
+        assert!(command.get_args().any(|arg| arg == format!("-FU{}", unit_dir.display()).as_str()));
+
+        std::fs::write(temp_dir.path().join("editor.lpi"), "<CONFIG/>").unwrap();
+        let (options, toolchain) = compiler.get_compile_options(&Language::Pascal, &args).unwrap();
+        let command = Language::Pascal
+            .get_batch_compilation_command(&files, &options)
+            .unwrap();
+        assert_eq!(toolchain.as_deref(), Some("lazbuild (project editor.lpi)"));
+        assert_eq!(command.get_program(), "lazbuild");
+    }
+
+    #[test]
+    fn test_custom_language_templates() {
+        let mut config = Config::default();
+        config.custom_languages.push(crate::config::CustomLanguageConfig {
+            name: "gleam".to_string(),
+            extensions: vec!["gleam".to_string()],
+            check: Some("gleam format --check {file}".to_string()),
+            compile: Some("gleamc {flags} -o {output} {file}".to_string()),
+            probe: Some("sh -c true".to_string()),
+            comment: None,
+        });
+        config.set_language_config("gleam", crate::config::LanguageConfig {
+            compiler_flags: vec!["--warnings-as-errors".to_string()],
+            ..Default::default()
+        });
+        let compiler = Compiler::new(config, 1);
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let args = Args::parse_from(["lol", temp_dir.path().to_str().unwrap()]);
+        let language = Language::Custom("gleam".to_string());
+
+        let (options, _) = compiler.get_compile_options(&language, &args).unwrap();
+        let pipeline = language
+            .get_compilation_pipeline(Path::new("app.gleam"), &options)
+            .unwrap();
+        assert_eq!(pipeline.len(), 2);
+        assert_eq!(pipeline[0].get_program(), "gleam");
+
+        let output = temp_dir.path().join("build").join("gleam").join("app");
+        let compile_args: Vec<_> = pipeline[1].get_args().collect();
+        assert_eq!(pipeline[1].get_program(), "gleamc");
+        assert_eq!(
+            compile_args,
+            ["--warnings-as-errors".as_ref(), "-o".as_ref(), output.as_os_str(), "app.gleam".as_ref()]
+        );
+
+        let mut config = Config::default();
+        config.custom_languages.push(crate::config::CustomLanguageConfig {
+            name: "gleam".to_string(),
+            probe: Some("lol-missing-probe --version".to_string()),
+            ..Default::default()
+        });
+        let compiler = Compiler::new(config, 1);
+        assert!(compiler.get_compile_options(&language, &args).is_err());
+    }
+
+    #[test]
+    fn test_package_jar_requires_configuration() {
+        let compiler = Compiler::new(Config::default(), 1);
+        let results = vec![CompilationResult {
+            language: Language::Java,
+            files: vec![PathBuf::from("Main.java")],
+            status: CompilationStatus::Success { output: String::new() },
+            diagnostics: Vec::new(),
+            toolchain: None,
+            retries: 0,
+            duration_ms: 0,
+        }];
+
+        assert!(compiler.package_jar(&results, Path::new(".")).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_unusual_file_names_reach_the_compiler_unchanged() {
+        let compiler = Compiler::new(Config::default(), 1);
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let args = Args::parse_from(["lol", temp_dir.path().to_str().unwrap()]);
+
+        let long_dir = temp_dir.path().join("nested ".repeat(40).trim_end());
+        let mut files = vec![
+            temp_dir.path().join("with spaces/main program.c"),
+            temp_dir.path().join("ünïcødé/日本語.c"),
+            long_dir.join("x".repeat(200) + ".c"),
+        ];
+        #[cfg(unix)]
+        {
+            use std::os::unix::ffi::OsStrExt;
+            // Latin-1 bytes, as left behind by older tools, are not UTF-8
+            files.push(temp_dir.path().join(std::ffi::OsStr::from_bytes(b"latin1-\xe9t\xe9.c")));
+        }
+
+        let (options, _) = compiler.get_compile_options(&Language::C, &args).unwrap();
+        for file in &files {
+            let command = Language::C.get_compilation_command(file, &options).unwrap();
+            let command_args: Vec<_> = command.get_args().collect();
+            assert_eq!(command_args[command_args.len() - 2..], [file.with_extension("o").as_os_str(), file.as_os_str()]);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_build_summary_tells_missing_compilers_from_errors() {
+        let config = Config {
+            language_settings: HashMap::from([(
+                "c".to_string(),
+                crate::config::LanguageConfig { compiler_path: Some("lol-test-no-such-cc".to_string()), ..Default::default() },
+            )]),
+            ..Default::default()
+        };
+        let compiler = Compiler::new(config, 1);
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let file = temp_dir.path().join("main.c");
+        std::fs::write(&file, "int main(void) { return 0; }\n").unwrap();
+        let args = Args::parse_from(["lol", temp_dir.path().to_str().unwrap()]);
+
+        let (options, _) = compiler.get_compile_options(&Language::C, &args).unwrap();
+        let files = std::slice::from_ref(&file);
+        assert_eq!(compiler.missing_toolchain(&Language::C, files, &options).as_deref(), Some("lol-test-no-such-cc"));
+        assert_eq!(compiler.missing_toolchain(&Language::Python, files, &options), None);
+        let command = Language::C.get_compilation_command(&file, &options).unwrap();
+        let error = compiler.execute(command, &options, "c:main.c").await.unwrap_err();
+        assert_eq!(error.to_string(), "Compiler 'lol-test-no-such-cc' not found");
+
+        let mut summary = BuildSummary::default();
+        assert_eq!(summary.failure(), None);
+        summary.results.push(CompilationResult {
+            language: Language::C,
+            files: Vec::new(),
+            status: CompilationStatus::Failure { error: error.to_string() },
+            diagnostics: Vec::new(),
+            toolchain: None,
+            retries: 0,
+            duration_ms: 0,
+        });
+        assert_eq!(summary.failure(), Some(BuildFailure::CompileErrors));
+        summary.note_failure(&Language::C, files, &error);
+        assert_eq!(summary.failed_files[&Language::C], [file]);
+        assert_eq!(summary.missing_toolchains, ["lol-test-no-such-cc"]);
+        assert_eq!(summary.failure(), Some(BuildFailure::ToolchainMissing));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_transient_failures_are_retried() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let marker = temp_dir.path().join("tried");
+        let flaky = format!(
+            "if [ -e '{0}' ]; then echo ok; else touch '{0}'; echo 'java.lang.OutOfMemoryError: Java heap space' >&2; exit 1; fi",
+            marker.display()
+        );
+        let compiler = Compiler::new(Config::default(), 1).with_retries(2, Duration::ZERO);
+        let mut command = Command::new("sh");
+        command.args(["-c", &flaky]);
+        assert_eq!(compiler.execute(command, &CompileOptions::default(), "java").await.unwrap(), "ok\n");
+        assert_eq!(compiler.retried.load(Ordering::Relaxed), 1);
+
+        // Real compile errors fail straight away
+        let mut command = Command::new("sh");
+        command.args(["-c", "echo \"main.c:1:1: error: expected ';'\" >&2; exit 1"]);
+        assert!(compiler.execute(command, &CompileOptions::default(), "c").await.is_err());
+        assert_eq!(compiler.retried.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_compiler_availability_check() {
+        // Interpreted languages need no compiler, so some are always available
+        assert!(Language::all().iter().any(Language::check_compiler_available));
     }
 
     #[test]
     fn test_compiler_info() {
         let config = Config::default();
         let compiler = Compiler::new(config, 1);
-        let info = compiler.get_compiler_info();
+        let info = compiler.get_compiler_info_for(Language::all());
         
         // Should have info for all supported languages
         assert!(!info.is_empty());
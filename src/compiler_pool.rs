@@ -0,0 +1,465 @@
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, ChildStdout, Command, ExitStatus, Output, Stdio};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+use anyhow::{Context, Result};
+
+/// Runs javac in one JVM for as long as it is fed requests: the arguments
+/// of a compile one per line, then an empty line. javac's output follows,
+/// then a line of NUL and the exit status.
+const JAVAC_SERVER: &str = r#"import java.io.*;
+import java.nio.charset.StandardCharsets;
+import java.util.*;
+import javax.tools.*;
+
+public class LolJavacServer {
+    public static void main(String[] args) throws IOException {
+        JavaCompiler javac = ToolProvider.getSystemJavaCompiler();
+        BufferedReader in = new BufferedReader(new InputStreamReader(System.in, StandardCharsets.UTF_8));
+        PrintStream out = new PrintStream(new FileOutputStream(FileDescriptor.out), true, "UTF-8");
+        List<String> request = new ArrayList<>();
+        String line;
+        while ((line = in.readLine()) != null) {
+            if (!line.isEmpty()) {
+                request.add(line);
+                continue;
+            }
+            ByteArrayOutputStream diagnostics = new ByteArrayOutputStream();
+            int status = javac.run(null, diagnostics, diagnostics, request.toArray(new String[0]));
+            request.clear();
+            String text = diagnostics.toString("UTF-8");
+            out.print(text);
+            if (!text.isEmpty() && !text.endsWith("\n")) {
+                out.println();
+            }
+            out.println("\u0000" + status);
+        }
+    }
+}
+"#;
+
+/// The same protocol for kotlinc: `K2JVMCompiler` from the
+/// `kotlin-compiler.jar` on the classpath, run in process for each request.
+const KOTLINC_SERVER: &str = r#"import java.io.*;
+import java.lang.reflect.Method;
+import java.nio.charset.StandardCharsets;
+import java.util.*;
+
+public class LolKotlincServer {
+    public static void main(String[] args) throws Exception {
+        Class<?> compiler = Class.forName("org.jetbrains.kotlin.cli.jvm.K2JVMCompiler");
+        Method exec = compiler.getMethod("exec", PrintStream.class, String[].class);
+        BufferedReader in = new BufferedReader(new InputStreamReader(System.in, StandardCharsets.UTF_8));
+        PrintStream out = new PrintStream(new FileOutputStream(FileDescriptor.out), true, "UTF-8");
+        List<String> request = new ArrayList<>();
+        String line;
+        while ((line = in.readLine()) != null) {
+            if (!line.isEmpty()) {
+                request.add(line);
+                continue;
+            }
+            ByteArrayOutputStream diagnostics = new ByteArrayOutputStream();
+            PrintStream errors = new PrintStream(diagnostics, true, "UTF-8");
+            Object exitCode = exec.invoke(compiler.getDeclaredConstructor().newInstance(), errors, request.toArray(new String[0]));
+            int status = (Integer) exitCode.getClass().getMethod("getCode").invoke(exitCode);
+            request.clear();
+            String text = diagnostics.toString("UTF-8");
+            out.print(text);
+            if (!text.isEmpty() && !text.endsWith("\n")) {
+                out.println();
+            }
+            out.println("\u0000" + status);
+        }
+    }
+}
+"#;
+
+/// How long `tsc --watch` may sit idle, with no compile under way, before
+/// its last report is taken as the answer.
+const TSC_IDLE: Duration = Duration::from_secs(5);
+
+/// Compiler processes kept running between compiles and between the builds
+/// of `lol watch`, `lol lsp` and `lol daemon`, so the JVM and Node start
+/// once rather than for every invocation. Languages opt in with `daemon`.
+/// Everything still running is stopped when the pool is dropped.
+#[derive(Default)]
+pub struct CompilerPool {
+    servers: Mutex<HashMap<ServerKey, Arc<Mutex<Server>>>>,
+}
+
+/// Which warm process serves a command: its compiler and the environment
+/// it was given, since a running process cannot be handed another.
+type ServerKey = (PathBuf, Vec<(OsString, Option<OsString>)>);
+
+enum Server {
+    Jvm(JvmServer),
+    TscWatch(TscWatch),
+}
+
+impl Server {
+    fn start(program: &Path, command: &Command) -> Result<Self> {
+        match program.file_stem().and_then(|stem| stem.to_str()) {
+            Some("javac") => JvmServer::javac(program, command).map(Server::Jvm),
+            Some("kotlinc") => JvmServer::kotlinc(program, command).map(Server::Jvm),
+            _ => TscWatch::start(command).map(Server::TscWatch),
+        }
+    }
+
+    fn run(&mut self, command: &Command) -> Result<Output> {
+        match self {
+            Server::Jvm(server) => server.run(command),
+            Server::TscWatch(watch) => watch.run(command),
+        }
+    }
+
+    fn stop(&mut self) {
+        let child = match self {
+            Server::Jvm(server) => &mut server.child,
+            Server::TscWatch(watch) => &mut watch.child,
+        };
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+}
+
+impl CompilerPool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Runs `command` on a warm process when it invokes a compiler the pool
+    /// knows, `javac`, `kotlinc` or `tsc`; `None` means run it the usual way. A warm
+    /// process that fails is dropped, and the command is left to run
+    /// normally.
+    pub fn run(&self, command: &Command) -> Option<Output> {
+        let program = PathBuf::from(command.get_program());
+        let known = matches!(program.file_stem().and_then(|stem| stem.to_str()), Some("javac" | "kotlinc" | "tsc"));
+        // Servers run where lol does, so commands must not need a directory of their own
+        if !known || command.get_current_dir().is_some() {
+            return None;
+        }
+        let environment: Vec<_> = command.get_envs().map(|(key, value)| (key.to_owned(), value.map(|value| value.to_owned()))).collect();
+        let key = (program.clone(), environment);
+
+        let server = {
+            let mut servers = self.servers.lock().unwrap();
+            match servers.get(&key) {
+                Some(server) => Arc::clone(server),
+                None => match Server::start(&program, command) {
+                    Ok(server) => Arc::clone(servers.entry(key.clone()).or_insert(Arc::new(Mutex::new(server)))),
+                    Err(error) => {
+                        tracing::warn!("Could not keep {} warm: {:#}", program.display(), error);
+                        return None;
+                    }
+                },
+            }
+        };
+        let mut server = server.lock().unwrap();
+        match server.run(command) {
+            Ok(output) => Some(output),
+            Err(error) => {
+                tracing::warn!("Warm {} failed: {:#}; running it afresh", program.display(), error);
+                server.stop();
+                self.servers.lock().unwrap().remove(&key);
+                None
+            }
+        }
+    }
+
+    /// Stops every warm process.
+    pub fn shutdown(&self) {
+        for (_, server) in self.servers.lock().unwrap().drain() {
+            server.lock().unwrap().stop();
+        }
+    }
+}
+
+impl Drop for CompilerPool {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+/// Compiler calls served by `LolJavacServer` or `LolKotlincServer` in a
+/// JVM that stays up, warmed by the JIT after the first compile.
+struct JvmServer {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    /// Sent ahead of every command's own arguments
+    leading_args: Vec<String>,
+}
+
+impl JvmServer {
+    fn javac(javac: &Path, command: &Command) -> Result<Self> {
+        // The launcher beside javac belongs to the same JDK
+        let java = match javac.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            Some(dir) => dir.join(format!("java{}", std::env::consts::EXE_SUFFIX)),
+            None => PathBuf::from("java"),
+        };
+        Self::start(&java, "LolJavacServer", JAVAC_SERVER, None, Vec::new(), command)
+    }
+
+    /// kotlinc is a launcher script in `<home>/bin`, with the compiler in
+    /// `<home>/lib`; packaged installs link the script from elsewhere.
+    fn kotlinc(kotlinc: &Path, command: &Command) -> Result<Self> {
+        let home = kotlin_home(kotlinc, command)?;
+        let jar = home.join("lib").join("kotlin-compiler.jar");
+        let leading_args = vec!["-kotlin-home".to_string(), home.to_str().context("Kotlin home is not UTF-8")?.to_string()];
+        let java = std::env::var_os("JAVA_HOME").map_or_else(|| PathBuf::from("java"), |home| Path::new(&home).join("bin").join("java"));
+        Self::start(&java, "LolKotlincServer", KOTLINC_SERVER, Some(&jar), leading_args, command)
+    }
+
+    fn start(java: &Path, class: &str, code: &str, classpath: Option<&Path>, leading_args: Vec<String>, command: &Command) -> Result<Self> {
+        let source = dirs::cache_dir().context("Could not determine cache directory")?.join("lol").join(format!("{}.java", class));
+        if fs::read_to_string(&source).ok().as_deref() != Some(code) {
+            fs::create_dir_all(source.parent().unwrap_or(Path::new(".")))?;
+            fs::write(&source, code).with_context(|| format!("Failed to write {}", source.display()))?;
+        }
+        let mut server = Command::new(java);
+        for (key, value) in command.get_envs() {
+            match value {
+                Some(value) => server.env(key, value),
+                None => server.env_remove(key),
+            };
+        }
+        if let Some(classpath) = classpath {
+            server.arg("-cp").arg(classpath);
+        }
+        let mut child = server
+            .arg(&source)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .with_context(|| format!("Failed to start {}", java.display()))?;
+        let stdin = child.stdin.take().context("compile server has no stdin")?;
+        let stdout = BufReader::new(child.stdout.take().context("compile server has no stdout")?);
+        Ok(Self { child, stdin, stdout, leading_args })
+    }
+
+    fn run(&mut self, command: &Command) -> Result<Output> {
+        let mut request = String::new();
+        for arg in self.leading_args.iter().map(String::as_str).map(Ok).chain(command.get_args().map(|arg| arg.to_str().context("compiler argument is not UTF-8"))) {
+            let arg = arg?;
+            anyhow::ensure!(!arg.is_empty() && !arg.contains('\n'), "argument {:?} cannot be sent to the compile server", arg);
+            request.push_str(arg);
+            request.push('\n');
+        }
+        request.push('\n');
+        self.stdin.write_all(request.as_bytes())?;
+        self.stdin.flush()?;
+
+        let mut output = Vec::new();
+        loop {
+            let mut line = String::new();
+            anyhow::ensure!(self.stdout.read_line(&mut line)? > 0, "compile server exited");
+            if let Some(status) = line.strip_prefix('\0') {
+                let status: i32 = status.trim().parse().context("compile server sent a malformed status")?;
+                return Ok(Output { status: exit_status(status), stdout: output, stderr: Vec::new() });
+            }
+            output.extend_from_slice(line.as_bytes());
+        }
+    }
+}
+
+/// The Kotlin installation `kotlinc` runs from, with the compiler jar in it.
+fn kotlin_home(kotlinc: &Path, command: &Command) -> Result<PathBuf> {
+    let script = find_program(kotlinc, command).context("kotlinc is not on PATH")?;
+    let script = script.canonicalize().unwrap_or(script);
+    let home = script.parent().and_then(Path::parent).context("kotlinc is not inside a Kotlin installation")?;
+    let jar = home.join("lib").join("kotlin-compiler.jar");
+    anyhow::ensure!(jar.is_file(), "{} not found", jar.display());
+    Ok(home.to_path_buf())
+}
+
+/// Where `program` is found, on the `PATH` `command` runs with.
+fn find_program(program: &Path, command: &Command) -> Option<PathBuf> {
+    if program.components().count() > 1 {
+        return Some(program.to_path_buf());
+    }
+    let path = command
+        .get_envs()
+        .find(|(key, _)| *key == "PATH")
+        .map_or_else(|| std::env::var_os("PATH"), |(_, value)| value.map(|value| value.to_owned()))?;
+    std::env::split_paths(&path)
+        .map(|dir| dir.join(program))
+        .find(|candidate| candidate.is_file())
+}
+
+/// One compile `tsc --watch` reported.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Report {
+    /// When tsc started it, after noticing the change
+    started: SystemTime,
+    errors: usize,
+    output: String,
+}
+
+/// Follows `tsc --watch` output, which frames each compile between a
+/// "Starting compilation" or "File change detected" line and a
+/// "Found N errors" line.
+#[derive(Debug, Default)]
+struct WatchLog {
+    /// The compile under way, if any
+    current: Option<Report>,
+    last: Option<Report>,
+    /// When tsc last printed anything
+    active: Option<Instant>,
+    exited: bool,
+}
+
+impl WatchLog {
+    fn line(&mut self, line: &str, now: SystemTime) {
+        self.active = Some(Instant::now());
+        if line.contains("Starting compilation in watch mode") || line.contains("Starting incremental compilation") {
+            self.current = Some(Report { started: now, errors: 0, output: String::new() });
+        } else if let Some(errors) = found_errors(line) {
+            if let Some(mut report) = self.current.take() {
+                report.errors = errors;
+                self.last = Some(report);
+            }
+        } else if let Some(report) = &mut self.current {
+            report.output.push_str(line);
+            report.output.push('\n');
+        }
+    }
+
+    /// The report of a compile started after `since`, or of the last one
+    /// once tsc has been idle for `TSC_IDLE`: a change made no difference
+    /// to it, or happened before it started watching.
+    fn answer(&self, since: SystemTime) -> Option<&Report> {
+        let last = self.last.as_ref()?;
+        let idle = self.current.is_none() && self.active.is_some_and(|active| active.elapsed() >= TSC_IDLE);
+        (last.started >= since || idle).then_some(last)
+    }
+}
+
+/// N from tsc's "Found N errors. Watching for file changes.".
+fn found_errors(line: &str) -> Option<usize> {
+    let found = &line[line.find("Found ")? + "Found ".len()..];
+    if !found.contains("Watching for file changes") {
+        return None;
+    }
+    found.split_whitespace().next()?.parse().ok()
+}
+
+/// `tsc` kept running with `--watch`, answering for the files it was
+/// started on; different files start it again.
+struct TscWatch {
+    child: Child,
+    args: Vec<OsString>,
+    log: Arc<(Mutex<WatchLog>, Condvar)>,
+}
+
+impl TscWatch {
+    fn start(command: &Command) -> Result<Self> {
+        let mut watch = Command::new(command.get_program());
+        for (key, value) in command.get_envs() {
+            match value {
+                Some(value) => watch.env(key, value),
+                None => watch.env_remove(key),
+            };
+        }
+        let mut child = watch
+            .args(["--watch", "--preserveWatchOutput", "--pretty", "false"])
+            .args(command.get_args())
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .with_context(|| format!("Failed to start {} --watch", command.get_program().to_string_lossy()))?;
+        let stdout = child.stdout.take().context("tsc has no stdout")?;
+        let log = Arc::new((Mutex::new(WatchLog::default()), Condvar::new()));
+        let reader_log = Arc::clone(&log);
+        std::thread::spawn(move || {
+            let (log, changed) = &*reader_log;
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else { break };
+                log.lock().unwrap().line(&line, SystemTime::now());
+                changed.notify_all();
+            }
+            log.lock().unwrap().exited = true;
+            changed.notify_all();
+        });
+        Ok(Self { child, args: command.get_args().map(|arg| arg.to_owned()).collect(), log })
+    }
+
+    fn run(&mut self, command: &Command) -> Result<Output> {
+        let args: Vec<OsString> = command.get_args().map(|arg| arg.to_owned()).collect();
+        if args != self.args {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+            *self = Self::start(command)?;
+        }
+        // The newest of the files, which tsc must have seen
+        let since = args
+            .iter()
+            .filter_map(|arg| fs::metadata(arg).and_then(|metadata| metadata.modified()).ok())
+            .max()
+            .unwrap_or(SystemTime::UNIX_EPOCH);
+
+        let (log, changed) = &*self.log;
+        let mut log = log.lock().unwrap();
+        loop {
+            if let Some(report) = log.answer(since) {
+                let status = exit_status(if report.errors == 0 { 0 } else { 2 });
+                return Ok(Output { status, stdout: report.output.clone().into_bytes(), stderr: Vec::new() });
+            }
+            anyhow::ensure!(!log.exited, "tsc --watch exited");
+            log = changed.wait_timeout(log, Duration::from_millis(200)).unwrap().0;
+        }
+    }
+}
+
+#[cfg(unix)]
+fn exit_status(code: i32) -> ExitStatus {
+    std::os::unix::process::ExitStatusExt::from_raw(code << 8)
+}
+
+#[cfg(windows)]
+fn exit_status(code: i32) -> ExitStatus {
+    std::os::windows::process::ExitStatusExt::from_raw(code as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_watch_log_answers_for_compiles_after_a_change() {
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(100);
+        let mut log = WatchLog::default();
+        log.line("10:00:00 - Starting compilation in watch mode...", start);
+        log.line("src/app.ts(3,7): error TS2322: Type 'string' is not assignable to type 'number'.", start);
+        log.line("10:00:01 - Found 1 error. Watching for file changes.", start);
+        assert_eq!(log.answer(start).unwrap().errors, 1);
+
+        let edited = start + Duration::from_secs(10);
+        assert!(log.answer(edited).is_none());
+        log.line("10:00:10 - File change detected. Starting incremental compilation...", edited);
+        assert!(log.answer(edited).is_none());
+        log.line("10:00:11 - Found 0 errors. Watching for file changes.", edited);
+        let report = log.answer(edited).unwrap();
+        assert_eq!((report.errors, report.output.as_str()), (0, ""));
+        assert!(exit_status(2).code() == Some(2) && exit_status(0).success());
+    }
+
+    #[test]
+    fn test_kotlin_home_follows_the_kotlinc_on_the_command_path() {
+        let install = tempfile::tempdir().unwrap();
+        fs::create_dir_all(install.path().join("bin")).unwrap();
+        fs::create_dir_all(install.path().join("lib")).unwrap();
+        fs::write(install.path().join("bin").join("kotlinc"), "").unwrap();
+        let mut command = Command::new("kotlinc");
+        assert!(kotlin_home(Path::new("kotlinc"), command.env("PATH", install.path().join("bin"))).is_err());
+
+        fs::write(install.path().join("lib").join("kotlin-compiler.jar"), "").unwrap();
+        let home = kotlin_home(Path::new("kotlinc"), &command).unwrap();
+        assert_eq!(home, install.path().canonicalize().unwrap());
+    }
+}
@@ -0,0 +1,83 @@
+use std::io::Write;
+use std::path::Path;
+use anyhow::Result;
+use clap::{CommandFactory, ValueEnum};
+use clap_complete::env::{Bash, CompleteEnv, Elvish, EnvCompleter, Fish, Powershell, Zsh};
+use clap_complete::engine::CompletionCandidate;
+use crate::args::Args;
+use crate::config::Config;
+use crate::language_support::Language;
+
+/// Environment variable the shell sets when it asks `lol` for completions.
+const COMPLETE_VAR: &str = "LOL_COMPLETE";
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    Powershell,
+    Elvish,
+}
+
+impl Shell {
+    fn completer(self) -> &'static dyn EnvCompleter {
+        match self {
+            Shell::Bash => &Bash,
+            Shell::Zsh => &Zsh,
+            Shell::Fish => &Fish,
+            Shell::Powershell => &Powershell,
+            Shell::Elvish => &Elvish,
+        }
+    }
+}
+
+/// Answers a completion request from the shell and exits, when this run is
+/// one. Must come before argument parsing.
+pub fn handle_request() {
+    CompleteEnv::with_factory(Args::command).var(COMPLETE_VAR).complete();
+}
+
+/// Writes the script that hooks `lol` into `shell`'s completion. The
+/// script calls back into `lol` for every completion, so candidates such
+/// as the project's custom languages are always current.
+pub fn write_script(shell: Shell, out: &mut dyn Write) -> Result<()> {
+    shell.completer().write_registration(COMPLETE_VAR, "lol", "lol", "lol", out)?;
+    Ok(())
+}
+
+/// Every language `--lang` accepts, by the key it has in `language_settings`:
+/// the built-in ones and the current project's custom languages.
+pub fn languages() -> Vec<CompletionCandidate> {
+    let builtin = Language::all()
+        .iter()
+        .map(|language| CompletionCandidate::new(language.config_key()).help(Some(language.name().into())));
+    // A completion must not create the global config as a side effect
+    let custom = Config::get_config_path()
+        .ok()
+        .filter(|path| path.exists())
+        .and_then(|_| Config::load_for_project(Path::new(".")).ok())
+        .map(|config| config.custom_languages)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|language| CompletionCandidate::new(language.name).help(Some("custom language".into())));
+    builtin.chain(custom).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scripts_and_languages() {
+        let mut script = Vec::new();
+        write_script(Shell::Bash, &mut script).unwrap();
+        let script = String::from_utf8(script).unwrap();
+        assert!(script.contains("LOL_COMPLETE=\"bash\""));
+        assert!(script.contains("complete -o nospace -o bashdefault -F _clap_complete_lol lol"));
+
+        let languages = languages();
+        let kotlin = languages.iter().find(|candidate| candidate.get_value() == "kotlin").unwrap();
+        assert_eq!(kotlin.get_help().unwrap().to_string(), "Kotlin");
+    }
+}
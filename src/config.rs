@@ -3,7 +3,92 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
 use std::path::{Path, PathBuf};
-use dirs;
+use clap::ValueEnum;
+use crate::args::Profile;
+use crate::language_support::Language;
+use crate::secrets::SecretScan;
+
+/// Per-project configuration file, read from the project root.
+pub const PROJECT_CONFIG_FILE: &str = "lol.json";
+
+/// Names the project configuration may have, in any of the formats.
+pub const PROJECT_CONFIG_FILES: &[&str] = &["lol.toml", "lol.yaml", "lol.yml", PROJECT_CONFIG_FILE];
+
+/// Names the global configuration may have in lol's config directory.
+const GLOBAL_CONFIG_FILES: &[&str] = &["config.toml", "config.yaml", "config.yml", "config.json"];
+
+/// The formats config files can be written in, told apart by extension.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Json,
+    Toml,
+    Yaml,
+}
+
+impl ConfigFormat {
+    pub fn of(path: &Path) -> Result<Self> {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => Ok(ConfigFormat::Json),
+            Some("toml") => Ok(ConfigFormat::Toml),
+            Some("yaml" | "yml") => Ok(ConfigFormat::Yaml),
+            _ => anyhow::bail!("{} is not a .json, .toml or .yaml config file", path.display()),
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ConfigFormat::Json => "json",
+            ConfigFormat::Toml => "toml",
+            ConfigFormat::Yaml => "yaml",
+        }
+    }
+
+    /// Parses `contents` into the JSON value the config is merged as.
+    pub fn parse(self, contents: &str) -> Result<serde_json::Value> {
+        Ok(match self {
+            ConfigFormat::Json => serde_json::from_str(contents)?,
+            ConfigFormat::Toml => toml::from_str(contents)?,
+            ConfigFormat::Yaml => serde_norway::from_str(contents)?,
+        })
+    }
+
+    pub fn render(self, value: &serde_json::Value) -> Result<String> {
+        Ok(match self {
+            ConfigFormat::Json => serde_json::to_string_pretty(value)? + "\n",
+            // TOML has no null; a missing key means the same to serde
+            ConfigFormat::Toml => toml::to_string_pretty(&without_nulls(value.clone()))?,
+            ConfigFormat::Yaml => serde_norway::to_string(value)?,
+        })
+    }
+}
+
+/// Reads a config file in whichever format its extension names.
+pub fn read_config_file(path: &Path) -> Result<serde_json::Value> {
+    let contents = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    ConfigFormat::of(path)?.parse(&contents).with_context(|| format!("Failed to parse {}", path.display()))
+}
+
+/// Writes `value` to a config file in the format its extension names.
+pub fn write_config_file(path: &Path, value: &serde_json::Value) -> Result<()> {
+    let contents = ConfigFormat::of(path)?.render(value).with_context(|| format!("Failed to serialize {}", path.display()))?;
+    fs::write(path, contents).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// The one of `names` present in `dir`. Two present at once is an error
+/// rather than a guess at which the user meant.
+fn find_config_file(dir: &Path, names: &[&str]) -> Result<Option<PathBuf>> {
+    let mut present = names.iter().map(|name| dir.join(name)).filter(|path| path.is_file());
+    let found = present.next();
+    if let (Some(first), Some(second)) = (&found, present.next()) {
+        anyhow::bail!("Both {} and {} exist; remove one of them", first.display(), second.display());
+    }
+    Ok(found)
+}
+
+/// The project's config file, or where a new `lol.json` would go.
+pub fn project_config_path(project_path: &Path) -> Result<PathBuf> {
+    Ok(find_config_file(project_path, PROJECT_CONFIG_FILES)?.unwrap_or_else(|| project_path.join(PROJECT_CONFIG_FILE)))
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
@@ -33,6 +118,89 @@ pub struct Config {
     
     #[serde(default = "default_language_settings")]
     pub language_settings: HashMap<String, LanguageConfig>,
+    
+    #[serde(default = "default_ts_backend")]
+    pub ts_backend: Option<String>,
+    
+    #[serde(default = "default_jar")]
+    pub jar: Option<JarConfig>,
+    
+    #[serde(default = "default_extension_overrides")]
+    pub extension_overrides: HashMap<String, String>,
+    
+    #[serde(default = "default_custom_languages")]
+    pub custom_languages: Vec<CustomLanguageConfig>,
+    
+    #[serde(default = "default_hooks")]
+    pub hooks: HooksConfig,
+    
+    #[serde(default = "default_codegen")]
+    pub codegen: CodegenConfig,
+    
+    #[serde(default = "default_stages")]
+    pub stages: Vec<StageConfig>,
+    
+    #[serde(default = "default_workspace")]
+    pub workspace: Option<WorkspaceConfig>,
+    
+    #[serde(default = "default_depends_on")]
+    pub depends_on: Vec<String>,
+    
+    #[serde(default = "default_pkg_config")]
+    pub pkg_config: Vec<String>,
+    
+    #[serde(default = "default_license")]
+    pub license: LicenseConfig,
+    
+    #[serde(default = "default_secret_scan")]
+    pub secret_scan: SecretScan,
+
+    /// Fingerprints of the keys `lol verify` accepts signatures from; with
+    /// none, any key in the user's GPG keyring
+    #[serde(default = "default_trusted_keys")]
+    pub trusted_keys: Vec<String>,
+    
+    #[serde(default = "default_package")]
+    pub package: PackageConfig,
+    
+    #[serde(default = "default_notifications")]
+    pub notifications: NotificationsConfig,
+    
+    #[serde(default = "default_publish")]
+    pub publish: PublishConfig,
+    
+    /// Build profile used when `--profile` is not given
+    #[serde(default = "default_profile")]
+    pub profile: Option<Profile>,
+    
+    #[serde(default = "default_env")]
+    pub env: HashMap<String, String>,
+    
+    #[serde(default = "default_toolchain_paths")]
+    pub toolchain_paths: Vec<String>,
+    
+    #[serde(default = "default_isolate_path")]
+    pub isolate_path: bool,
+    
+    #[serde(default = "default_suggestion_rules")]
+    pub suggestion_rules: Option<String>,
+    
+    /// Failures `--retries` compiles again, as regexes over the compiler's output
+    #[serde(default = "default_retry_patterns")]
+    pub retry_patterns: Vec<String>,
+    
+    /// Record each build in .lol-history.jsonl for `lol history`
+    #[serde(default = "default_history")]
+    pub history: bool,
+    
+    /// Where the organization's config overlay is published; read from the
+    /// user config only
+    #[serde(default = "default_organization")]
+    pub organization: Option<OrganizationConfig>,
+    
+    /// Niceness and CPUs of builds, on a developer's machine and in CI
+    #[serde(default = "default_priority")]
+    pub priority: PriorityConfig,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -48,6 +216,329 @@ pub struct LanguageConfig {
     
     #[serde(default = "default_output_format")]
     pub output_format: Option<String>,
+    
+    #[serde(default = "default_type_checker")]
+    pub type_checker: Option<String>,
+    
+    #[serde(default = "default_python_path")]
+    pub python_path: Option<String>,
+    
+    #[serde(default = "default_optimization_level")]
+    pub optimization_level: Option<u8>,
+    
+    #[serde(default = "default_pycache_prefix")]
+    pub pycache_prefix: Option<String>,
+    
+    #[serde(default = "default_compile_all")]
+    pub compile_all: bool,
+    
+    #[serde(default = "default_daemon")]
+    pub daemon: bool,
+    
+    /// Compile every file of the language in one invocation; unset means
+    /// on for javac, tsc, kotlinc and scalac
+    #[serde(default = "default_batch")]
+    pub batch: Option<bool>,
+    
+    #[serde(default = "default_check_mode")]
+    pub check_mode: Option<String>,
+    
+    #[serde(default = "default_package_compiler")]
+    pub package_compiler: bool,
+    
+    #[serde(default = "default_frameworks")]
+    pub frameworks: Vec<String>,
+    
+    #[serde(default = "default_arch")]
+    pub arch: Option<String>,
+    
+    /// How AppImages get an interpreter: `pyinstaller`, `python-appimage`
+    /// or `system` for Python, `sea`, `pkg` or `system` for JavaScript
+    #[serde(default = "default_bundler")]
+    pub bundler: Option<String>,
+    
+    #[serde(default = "default_min_version")]
+    pub min_version: Option<String>,
+    
+    #[serde(default = "default_compilers")]
+    pub compilers: Vec<String>,
+    
+    #[serde(default = "default_env")]
+    pub env: HashMap<String, String>,
+    
+    #[serde(default = "default_toolchain_paths")]
+    pub toolchain_paths: Vec<String>,
+}
+
+/// Languages to generate from schema files before compiling, e.g.
+/// `["cpp", "python"]`. Empty lists disable that generator.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CodegenConfig {
+    #[serde(default = "default_codegen_languages")]
+    pub protobuf: Vec<String>,
+
+    #[serde(default = "default_codegen_languages")]
+    pub flatbuffers: Vec<String>,
+}
+
+/// A named step of the build pipeline. The built-in `generate` and
+/// `compile` stages run schema code generation and compilation when they
+/// have no `commands`; any other stage runs its commands through the shell,
+/// up to `jobs` at a time.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StageConfig {
+    pub name: String,
+
+    #[serde(default = "default_stage_depends_on")]
+    pub depends_on: Vec<String>,
+
+    #[serde(default = "default_stage_commands")]
+    pub commands: Vec<String>,
+
+    #[serde(default = "default_stage_jobs")]
+    pub jobs: Option<usize>,
+}
+
+/// Marks the project as a workspace. `members` are directories relative to
+/// the workspace root; when empty, every subdirectory with its own
+/// `lol.json` is a member.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WorkspaceConfig {
+    #[serde(default = "default_workspace_members")]
+    pub members: Vec<String>,
+}
+
+/// License header policy for `lol license check`. `template` is the header
+/// every source file must start with, without comment markers; `{spdx}` is
+/// replaced with `spdx` and `{year}` matches any year. `templates` overrides
+/// it per language key, and files under `exclude` are not checked.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LicenseConfig {
+    #[serde(default = "default_license_spdx")]
+    pub spdx: Option<String>,
+
+    #[serde(default = "default_license_template")]
+    pub template: Option<String>,
+
+    #[serde(default = "default_license_templates")]
+    pub templates: HashMap<String, String>,
+
+    #[serde(default = "default_license_exclude")]
+    pub exclude: Vec<String>,
+}
+
+/// Metadata for `lol package`. The name defaults to the project directory
+/// and the version to `git describe --tags`. The rest fills in deb, rpm
+/// and Flatpak package metadata.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PackageConfig {
+    #[serde(default = "default_package_name")]
+    pub name: Option<String>,
+
+    #[serde(default = "default_package_version")]
+    pub version: Option<String>,
+
+    /// `Name <email>`, required for deb and rpm packages
+    #[serde(default = "default_package_maintainer")]
+    pub maintainer: Option<String>,
+
+    #[serde(default = "default_package_description")]
+    pub description: Option<String>,
+
+    /// Runtime dependencies in Debian syntax, e.g. `libssl3 (>= 3.0)`
+    #[serde(default = "default_package_depends")]
+    pub depends: Vec<String>,
+
+    /// Reverse-DNS application ID for Flatpak, e.g. `org.example.MyApp`
+    #[serde(default = "default_package_app_id")]
+    pub app_id: Option<String>,
+
+    /// Sandbox permissions for Flatpak; unset means a desktop app's usual set
+    #[serde(default = "default_package_finish_args")]
+    pub finish_args: Option<Vec<String>>,
+
+    /// Sign, and optionally notarize, the macOS binaries and app bundles in
+    /// tar.gz and zip packages
+    #[serde(default = "default_package_codesign")]
+    pub codesign: Option<CodesignConfig>,
+}
+
+/// `package.codesign`: how `codesign` and `notarytool` are run.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CodesignConfig {
+    /// Signing identity, e.g. `Developer ID Application: Example (TEAMID)`; `-` signs ad hoc
+    pub identity: String,
+
+    /// Entitlements plist, relative to the project
+    #[serde(default = "default_codesign_entitlements")]
+    pub entitlements: Option<String>,
+
+    /// Keychain profile saved with `xcrun notarytool store-credentials`;
+    /// the package is notarized when set
+    #[serde(default = "default_codesign_notarize_profile")]
+    pub notarize_profile: Option<String>,
+}
+
+/// Destinations for `lol publish`. Every configured one receives the
+/// release files; `{tag}` and `{version}` in `url` and `s3` are replaced.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PublishConfig {
+    /// `owner/repo` whose release for the tag gets the files as assets
+    #[serde(default = "default_publish_github")]
+    pub github: Option<String>,
+
+    /// `s3://bucket/prefix`, uploaded to with the AWS CLI
+    #[serde(default = "default_publish_s3")]
+    pub s3: Option<String>,
+
+    /// Base URL each file is PUT under
+    #[serde(default = "default_publish_url")]
+    pub url: Option<String>,
+
+    /// Attempts per upload before giving up
+    #[serde(default = "default_publish_retries")]
+    pub retries: u32,
+}
+
+impl Default for PublishConfig {
+    fn default() -> Self {
+        Self {
+            github: default_publish_github(),
+            s3: default_publish_s3(),
+            url: default_publish_url(),
+            retries: default_publish_retries(),
+        }
+    }
+}
+
+/// Where to report a finished build. Desktop notifications show on this
+/// machine; each webhook gets a POST with the results summary. Builds
+/// shorter than `min_duration_secs` are not reported.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotificationsConfig {
+    #[serde(default = "default_notifications_desktop")]
+    pub desktop: bool,
+
+    #[serde(default = "default_notifications_webhooks")]
+    pub webhooks: Vec<WebhookConfig>,
+
+    /// `always`, `success` or `failure`
+    #[serde(default = "default_notifications_on")]
+    pub on: String,
+
+    #[serde(default = "default_notifications_min_duration_secs")]
+    pub min_duration_secs: u64,
+}
+
+impl Default for NotificationsConfig {
+    fn default() -> Self {
+        Self {
+            desktop: default_notifications_desktop(),
+            webhooks: default_notifications_webhooks(),
+            on: default_notifications_on(),
+            min_duration_secs: default_notifications_min_duration_secs(),
+        }
+    }
+}
+
+/// How hard builds may load the machine. `ci` applies when the `CI`
+/// environment variable is set, `interactive` otherwise; `--nice` and
+/// `--cpus` override both.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PriorityConfig {
+    #[serde(default = "default_priority_profile")]
+    pub interactive: PriorityProfile,
+
+    #[serde(default = "default_priority_profile")]
+    pub ci: PriorityProfile,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PriorityProfile {
+    /// Nice level, from -20 to 19; watch, LSP and daemon builds default to
+    /// 10 on a developer's machine
+    #[serde(default = "default_priority_nice")]
+    pub nice: Option<i32>,
+
+    /// CPUs to pin builds to, such as `0-3,6`; Linux only
+    #[serde(default = "default_priority_cpus")]
+    pub cpus: Option<String>,
+}
+
+/// An organization-wide config overlay, fetched over HTTPS with a detached
+/// armored signature at `<url>.asc`, and layered beneath the user's config.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrganizationConfig {
+    pub url: String,
+
+    /// Armored GPG public key the overlay must be signed with
+    pub public_key: PathBuf,
+
+    /// How long a fetched overlay is used before fetching it again
+    #[serde(default = "default_organization_max_age_secs")]
+    pub max_age_secs: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookConfig {
+    pub url: String,
+
+    /// `slack`, `discord` or `json`
+    #[serde(default = "default_webhook_format")]
+    pub format: String,
+}
+
+/// Shell commands run around a build, in the project directory.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HooksConfig {
+    #[serde(default = "default_hook_commands")]
+    pub pre_build: Vec<String>,
+
+    #[serde(default = "default_hook_commands")]
+    pub post_build: Vec<String>,
+
+    #[serde(default = "default_hook_commands")]
+    pub on_success: Vec<String>,
+
+    #[serde(default = "default_hook_commands")]
+    pub on_failure: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct JarConfig {
+    #[serde(default = "default_main_class")]
+    pub main_class: Option<String>,
+    
+    #[serde(default = "default_jar_name")]
+    pub name: Option<String>,
+}
+
+/// A language declared in configuration rather than built into lol.
+///
+/// `check` and `compile` are command templates; `{file}` is replaced with
+/// the source file, `{output}` with `<output_directory>/<name>/<file stem>`
+/// and `{flags}` with the language's `compiler_flags` from
+/// `language_settings`. `probe` is run once to see whether the toolchain is
+/// installed. `comment` is the language's line comment marker, used for
+/// license headers.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CustomLanguageConfig {
+    pub name: String,
+
+    #[serde(default = "default_custom_extensions")]
+    pub extensions: Vec<String>,
+
+    #[serde(default = "default_check_command")]
+    pub check: Option<String>,
+
+    #[serde(default = "default_compile_command")]
+    pub compile: Option<String>,
+
+    #[serde(default = "default_probe_command")]
+    pub probe: Option<String>,
+
+    #[serde(default = "default_comment_prefix")]
+    pub comment: Option<String>,
 }
 
 impl Config {
@@ -55,114 +546,150 @@ impl Config {
         let config_path = Self::get_config_path()?;
         
         if config_path.exists() {
-            let config_content = fs::read_to_string(&config_path)
-                .context("Failed to read configuration file")?;
-            
-            let config: Config = serde_json::from_str(&config_content)
-                .context("Failed to parse configuration file")?;
-            
-            Ok(config)
+            Self::load_from(&config_path)
         } else {
-            // Create default configuration
-            let config = Config::default();
-            config.save()?;
-            Ok(config)
+            // Nothing is written until asked; `lol init` sets up a project's lol.json
+            Ok(Config::default())
         }
     }
 
-    pub fn save(&self) -> Result<()> {
+    fn load_from(config_path: &Path) -> Result<Self> {
+        serde_json::from_value(read_config_file(config_path)?)
+            .map_err(anyhow::Error::from)
+            .and_then(Config::validated)
+            .with_context(|| format!("Invalid settings in {}", config_path.display()))
+    }
+
+    /// Loads the global configuration with the project's `lol.json` (or
+    /// `lol.toml`, `lol.yaml`), if any, layered on top. Only the keys
+    /// present in the project file are overridden.
+    pub fn load_for_project(project_path: &Path) -> Result<Self> {
+        let mut config = Self::load()?;
+        if let Some(organization) = &config.organization {
+            if let Some(overlay) = crate::organization::load(organization)? {
+                config = Self::beneath_user(overlay, Self::load_user_overlay()?)?;
+            }
+        }
+        let project_config_path = project_config_path(project_path)?;
+        if !project_config_path.is_file() {
+            return Ok(config);
+        }
+
+        let overlay = read_config_file(&project_config_path)?;
+
+        let mut merged = serde_json::to_value(&config).context("Failed to serialize configuration")?;
+        merge_json(&mut merged, overlay);
+        serde_json::from_value(merged)
+            .map_err(anyhow::Error::from)
+            .and_then(Config::validated)
+            .with_context(|| format!("Invalid settings in {}", project_config_path.display()))
+    }
+
+    /// The user's config file as written, without defaults filled in.
+    fn load_user_overlay() -> Result<Option<serde_json::Value>> {
         let config_path = Self::get_config_path()?;
-        
+        if !config_path.exists() {
+            return Ok(None);
+        }
+        read_config_file(&config_path).map(Some)
+    }
+
+    /// The organization's `overlay` with the user's config on top: its keys
+    /// apply unless the user sets them too.
+    fn beneath_user(mut overlay: serde_json::Value, user: Option<serde_json::Value>) -> Result<Self> {
+        // The organization cannot point lol somewhere else
+        if let Some(overlay) = overlay.as_object_mut() {
+            overlay.remove("organization");
+        }
+        let mut merged = serde_json::to_value(Config::default()).context("Failed to serialize configuration")?;
+        merge_json(&mut merged, overlay);
+        if let Some(user) = user {
+            merge_json(&mut merged, user);
+        }
+        serde_json::from_value(merged)
+            .map_err(anyhow::Error::from)
+            .and_then(Config::validated)
+            .context("Invalid settings in the organization config")
+    }
+
+    /// Rejects settings that would otherwise be dropped without a word.
+    fn validated(self) -> Result<Self> {
+        // Their `language_settings` would be shared with the built-in language
+        if let Some(custom) = self
+            .custom_languages
+            .iter()
+            .find(|custom| Language::all().iter().any(|language| language.config_key() == custom.name))
+        {
+            anyhow::bail!("custom_languages declares \"{}\", the key of a built-in language; give it another name", custom.name);
+        }
+        let mut overrides: Vec<_> = self.extension_overrides.iter().collect();
+        overrides.sort();
+        for (extension, key) in overrides {
+            let known = self.get_custom_language(key).is_some()
+                || Language::all().iter().any(|language| language.config_key() == key);
+            if !known {
+                anyhow::bail!("extension_overrides.{} names \"{}\", which is neither a built-in nor a custom language", extension, key);
+            }
+        }
+        Ok(self)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        self.save_to(&Self::get_config_path()?)
+    }
+
+    fn save_to(&self, config_path: &Path) -> Result<()> {
         // Ensure config directory exists
         if let Some(parent) = config_path.parent() {
             fs::create_dir_all(parent)
                 .context("Failed to create configuration directory")?;
         }
         
-        let config_content = serde_json::to_string_pretty(self)
+        let config_value = serde_json::to_value(self)
             .context("Failed to serialize configuration")?;
         
-        fs::write(&config_path, config_content)
-            .context("Failed to write configuration file")?;
-        
-        Ok(())
+        write_config_file(config_path, &config_value)
     }
 
+    /// The global config file in whichever format it was written, or where
+    /// a new `config.json` would go.
     pub fn get_config_path() -> Result<PathBuf> {
         let config_dir = dirs::config_dir()
-            .ok_or_else(|| anyhow::anyhow!("Could not determine config directory"))?;
+            .ok_or_else(|| anyhow::anyhow!("Could not determine config directory"))?
+            .join("lol");
         
-        Ok(config_dir.join("lol").join("config.json"))
+        Ok(find_config_file(&config_dir, GLOBAL_CONFIG_FILES)?.unwrap_or_else(|| config_dir.join("config.json")))
     }
 
     pub fn get_compiler_flags(&self, language: &str) -> Option<&String> {
         self.compiler_flags.get(language)
     }
 
-    pub fn set_compiler_flags(&mut self, language: &str, flags: String) {
-        self.compiler_flags.insert(language.to_string(), flags);
-    }
-
-    pub fn add_ignore_pattern(&mut self, pattern: String) {
-        if !self.ignore_patterns.contains(&pattern) {
-            self.ignore_patterns.push(pattern);
-        }
-    }
-
-    pub fn remove_ignore_pattern(&mut self, pattern: &str) {
-        self.ignore_patterns.retain(|p| p != pattern);
+    /// Where code generated from `.proto`/`.fbs` schemas is written:
+    /// `<output_directory>/generated`, or `generated/` in the project when no
+    /// output directory is set.
+    pub fn generated_dir(&self, project_path: &Path) -> PathBuf {
+        self.output_dir(project_path)
+            .unwrap_or_else(|| project_path.to_path_buf())
+            .join("generated")
     }
 
-    pub fn add_include_pattern(&mut self, pattern: String) {
-        if !self.include_patterns.contains(&pattern) {
-            self.include_patterns.push(pattern);
-        }
+    /// Resolves `output_directory` against the project root.
+    pub fn output_dir(&self, project_path: &Path) -> Option<PathBuf> {
+        self.output_directory
+            .as_ref()
+            .map(|dir| project_path.join(dir))
     }
 
-    pub fn remove_include_pattern(&mut self, pattern: &str) {
-        self.include_patterns.retain(|p| p != pattern);
+    /// The custom language claiming `extension`, if any.
+    pub fn custom_language_for_extension(&self, extension: &str) -> Option<&CustomLanguageConfig> {
+        self.custom_languages
+            .iter()
+            .find(|language| language.extensions.iter().any(|ext| ext.eq_ignore_ascii_case(extension)))
     }
 
-    pub fn should_ignore_file(&self, file_path: &Path) -> bool {
-        let file_path_str = file_path.to_string_lossy();
-        
-        // Check ignore patterns
-        for pattern in &self.ignore_patterns {
-            if Self::matches_pattern(&file_path_str, pattern) {
-                return true;
-            }
-        }
-        
-        // Check include patterns (if any are specified, file must match at least one)
-        if !self.include_patterns.is_empty() {
-            let mut matches_include = false;
-            for pattern in &self.include_patterns {
-                if Self::matches_pattern(&file_path_str, pattern) {
-                    matches_include = true;
-                    break;
-                }
-            }
-            if !matches_include {
-                return true;
-            }
-        }
-        
-        false
-    }
-
-    fn matches_pattern(file_path: &str, pattern: &str) -> bool {
-        // Simple glob-like pattern matching
-        if pattern.contains('*') {
-            let regex_pattern = pattern
-                .replace(".", "\\.")
-                .replace("*", ".*");
-            
-            if let Ok(regex) = regex::Regex::new(&format!("^{}$", regex_pattern)) {
-                return regex.is_match(file_path);
-            }
-        }
-        
-        file_path.contains(pattern)
+    pub fn get_custom_language(&self, name: &str) -> Option<&CustomLanguageConfig> {
+        self.custom_languages.iter().find(|language| language.name == name)
     }
 
     pub fn get_language_config(&self, language: &str) -> Option<&LanguageConfig> {
@@ -193,6 +720,31 @@ impl Default for Config {
             auto_clean: default_auto_clean(),
             watch_mode: default_watch_mode(),
             language_settings: default_language_settings(),
+            ts_backend: default_ts_backend(),
+            jar: default_jar(),
+            extension_overrides: default_extension_overrides(),
+            custom_languages: default_custom_languages(),
+            hooks: default_hooks(),
+            codegen: default_codegen(),
+            stages: default_stages(),
+            workspace: default_workspace(),
+            depends_on: default_depends_on(),
+            pkg_config: default_pkg_config(),
+            license: default_license(),
+            secret_scan: default_secret_scan(),
+            trusted_keys: default_trusted_keys(),
+            package: default_package(),
+            notifications: default_notifications(),
+            publish: default_publish(),
+            profile: default_profile(),
+            env: default_env(),
+            toolchain_paths: default_toolchain_paths(),
+            isolate_path: default_isolate_path(),
+            suggestion_rules: default_suggestion_rules(),
+            retry_patterns: default_retry_patterns(),
+            history: default_history(),
+            organization: default_organization(),
+            priority: default_priority(),
         }
     }
 }
@@ -204,10 +756,100 @@ impl Default for LanguageConfig {
             compiler_path: default_compiler_path(),
             compiler_flags: default_compiler_flags_vec(),
             output_format: default_output_format(),
+            type_checker: default_type_checker(),
+            python_path: default_python_path(),
+            optimization_level: default_optimization_level(),
+            pycache_prefix: default_pycache_prefix(),
+            compile_all: default_compile_all(),
+            daemon: default_daemon(),
+            batch: default_batch(),
+            check_mode: default_check_mode(),
+            package_compiler: default_package_compiler(),
+            frameworks: default_frameworks(),
+            arch: default_arch(),
+            bundler: default_bundler(),
+            min_version: default_min_version(),
+            compilers: default_compilers(),
+            env: default_env(),
+            toolchain_paths: default_toolchain_paths(),
+        }
+    }
+}
+
+/// Recursively merges `overlay` into `base`; objects are merged key by key,
+/// anything else is replaced.
+fn merge_json(base: &mut serde_json::Value, overlay: serde_json::Value) {
+    match (base, overlay) {
+        (serde_json::Value::Object(base), serde_json::Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge_json(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
         }
+        (base, overlay) => *base = overlay,
     }
 }
 
+/// Drops null values from objects, for formats without null.
+fn without_nulls(value: serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Object(map) => {
+            map.into_iter().filter(|(_, value)| !value.is_null()).map(|(key, value)| (key, without_nulls(value))).collect()
+        }
+        serde_json::Value::Array(items) => items.into_iter().map(without_nulls).collect(),
+        value => value,
+    }
+}
+
+/// What `lol config migrate` did to one config file.
+pub struct Migration {
+    /// Where the config is now, which differs from where it was when it was
+    /// converted to another format
+    pub path: PathBuf,
+    /// Each pattern changed with its replacement
+    pub patterns: Vec<(String, String)>,
+}
+
+/// Rewrites the `ignore_patterns` and `include_patterns` of the config file
+/// at `path` as globs and, with `to`, converts it to that format, replacing
+/// the old file. Every other key is left as it was, though comments do not
+/// survive a rewrite.
+pub fn migrate_config_file(path: &Path, to: Option<ConfigFormat>) -> Result<Migration> {
+    let mut value = read_config_file(path)?;
+    let mut patterns = Vec::new();
+    for key in ["ignore_patterns", "include_patterns"] {
+        let Some(entries) = value.get_mut(key).and_then(serde_json::Value::as_array_mut) else {
+            continue;
+        };
+        for pattern in entries {
+            let Some(old) = pattern.as_str() else {
+                continue;
+            };
+            let new = crate::patterns::migrate(old);
+            if new != old {
+                patterns.push((old.to_string(), new.clone()));
+                *pattern = serde_json::Value::String(new);
+            }
+        }
+    }
+
+    let target = match to {
+        Some(format) if format != ConfigFormat::of(path)? => path.with_extension(format.extension()),
+        _ => path.to_path_buf(),
+    };
+    if target != path {
+        write_config_file(&target, &value)?;
+        fs::remove_file(path).with_context(|| format!("Failed to remove {}", path.display()))?;
+    } else if !patterns.is_empty() {
+        write_config_file(path, &value)?;
+    }
+    Ok(Migration { path: target, patterns })
+}
+
 fn default_parallel_jobs() -> usize {
     num_cpus::get()
 }
@@ -277,6 +919,330 @@ fn default_output_format() -> Option<String> {
     None
 }
 
+fn default_type_checker() -> Option<String> {
+    None
+}
+
+fn default_python_path() -> Option<String> {
+    None
+}
+
+fn default_optimization_level() -> Option<u8> {
+    None
+}
+
+fn default_pycache_prefix() -> Option<String> {
+    None
+}
+
+fn default_compile_all() -> bool {
+    false
+}
+
+fn default_ts_backend() -> Option<String> {
+    None
+}
+
+fn default_daemon() -> bool {
+    false
+}
+
+fn default_batch() -> Option<bool> {
+    None
+}
+
+fn default_main_class() -> Option<String> {
+    None
+}
+
+fn default_jar_name() -> Option<String> {
+    None
+}
+
+fn default_jar() -> Option<JarConfig> {
+    None
+}
+
+fn default_check_mode() -> Option<String> {
+    None
+}
+
+fn default_package_compiler() -> bool {
+    false
+}
+
+fn default_frameworks() -> Vec<String> {
+    Vec::new()
+}
+
+fn default_arch() -> Option<String> {
+    None
+}
+
+fn default_extension_overrides() -> HashMap<String, String> {
+    HashMap::new()
+}
+
+fn default_custom_languages() -> Vec<CustomLanguageConfig> {
+    Vec::new()
+}
+
+fn default_custom_extensions() -> Vec<String> {
+    Vec::new()
+}
+
+fn default_check_command() -> Option<String> {
+    None
+}
+
+fn default_compile_command() -> Option<String> {
+    None
+}
+
+fn default_probe_command() -> Option<String> {
+    None
+}
+
+fn default_comment_prefix() -> Option<String> {
+    None
+}
+
+fn default_hooks() -> HooksConfig {
+    HooksConfig::default()
+}
+
+fn default_codegen_languages() -> Vec<String> {
+    Vec::new()
+}
+
+fn default_hook_commands() -> Vec<String> {
+    Vec::new()
+}
+
+fn default_stage_depends_on() -> Vec<String> {
+    Vec::new()
+}
+
+fn default_stage_commands() -> Vec<String> {
+    Vec::new()
+}
+
+fn default_stage_jobs() -> Option<usize> {
+    None
+}
+
+fn default_workspace_members() -> Vec<String> {
+    Vec::new()
+}
+
+fn default_license_spdx() -> Option<String> {
+    None
+}
+
+fn default_license_template() -> Option<String> {
+    None
+}
+
+fn default_license_templates() -> HashMap<String, String> {
+    HashMap::new()
+}
+
+fn default_license_exclude() -> Vec<String> {
+    Vec::new()
+}
+
+fn default_package_name() -> Option<String> {
+    None
+}
+
+fn default_package_version() -> Option<String> {
+    None
+}
+
+fn default_package_maintainer() -> Option<String> {
+    None
+}
+
+fn default_package_description() -> Option<String> {
+    None
+}
+
+fn default_package_depends() -> Vec<String> {
+    Vec::new()
+}
+
+fn default_package_app_id() -> Option<String> {
+    None
+}
+
+fn default_package_finish_args() -> Option<Vec<String>> {
+    None
+}
+
+fn default_package_codesign() -> Option<CodesignConfig> {
+    None
+}
+
+fn default_codesign_entitlements() -> Option<String> {
+    None
+}
+
+fn default_codesign_notarize_profile() -> Option<String> {
+    None
+}
+
+fn default_codegen() -> CodegenConfig {
+    CodegenConfig::default()
+}
+
+fn default_stages() -> Vec<StageConfig> {
+    Vec::new()
+}
+
+fn default_workspace() -> Option<WorkspaceConfig> {
+    None
+}
+
+fn default_depends_on() -> Vec<String> {
+    Vec::new()
+}
+
+fn default_pkg_config() -> Vec<String> {
+    Vec::new()
+}
+
+fn default_publish_github() -> Option<String> {
+    None
+}
+
+fn default_publish_s3() -> Option<String> {
+    None
+}
+
+fn default_publish_url() -> Option<String> {
+    None
+}
+
+fn default_publish_retries() -> u32 {
+    3
+}
+
+fn default_notifications_desktop() -> bool {
+    false
+}
+
+fn default_notifications_webhooks() -> Vec<WebhookConfig> {
+    Vec::new()
+}
+
+fn default_notifications_on() -> String {
+    "always".to_string()
+}
+
+fn default_notifications_min_duration_secs() -> u64 {
+    0
+}
+
+fn default_webhook_format() -> String {
+    "json".to_string()
+}
+
+fn default_license() -> LicenseConfig {
+    LicenseConfig::default()
+}
+
+fn default_secret_scan() -> SecretScan {
+    SecretScan::Deny
+}
+
+fn default_trusted_keys() -> Vec<String> {
+    Vec::new()
+}
+
+fn default_package() -> PackageConfig {
+    PackageConfig::default()
+}
+
+fn default_bundler() -> Option<String> {
+    None
+}
+
+fn default_notifications() -> NotificationsConfig {
+    NotificationsConfig::default()
+}
+
+fn default_publish() -> PublishConfig {
+    PublishConfig::default()
+}
+
+fn default_profile() -> Option<Profile> {
+    None
+}
+
+fn default_min_version() -> Option<String> {
+    None
+}
+
+fn default_compilers() -> Vec<String> {
+    Vec::new()
+}
+
+fn default_env() -> HashMap<String, String> {
+    HashMap::new()
+}
+
+fn default_toolchain_paths() -> Vec<String> {
+    Vec::new()
+}
+
+fn default_isolate_path() -> bool {
+    false
+}
+
+fn default_suggestion_rules() -> Option<String> {
+    None
+}
+
+/// Output of failures that tend to go away on a second try: the JVM or the
+/// machine running out of memory, and build steps that touch the network.
+const TRANSIENT_FAILURES: &[&str] = &[
+    r"java\.lang\.OutOfMemoryError|GC overhead limit exceeded|Could not reserve enough space",
+    r"Cannot allocate memory|out of memory allocating|Killed signal terminated program",
+    r"Connection (?:reset|refused|timed out)|Could not resolve host|Temporary failure in name resolution",
+];
+
+fn default_retry_patterns() -> Vec<String> {
+    TRANSIENT_FAILURES.iter().map(|pattern| pattern.to_string()).collect()
+}
+
+fn default_history() -> bool {
+    false
+}
+
+fn default_organization() -> Option<OrganizationConfig> {
+    None
+}
+
+fn default_organization_max_age_secs() -> u64 {
+    3600
+}
+
+fn default_priority() -> PriorityConfig {
+    PriorityConfig::default()
+}
+
+fn default_priority_profile() -> PriorityProfile {
+    PriorityProfile::default()
+}
+
+fn default_priority_nice() -> Option<i32> {
+    None
+}
+
+fn default_priority_cpus() -> Option<String> {
+    None
+}
+
 fn default_language_settings() -> HashMap<String, LanguageConfig> {
     let mut settings = HashMap::new();
     
@@ -286,6 +1252,7 @@ fn default_language_settings() -> HashMap<String, LanguageConfig> {
         compiler_path: None,
         compiler_flags: vec!["-Wall".to_string(), "-Wextra".to_string(), "-std=c99".to_string()],
         output_format: Some("o".to_string()),
+        ..LanguageConfig::default()
     });
     
     // C++ language settings
@@ -294,6 +1261,7 @@ fn default_language_settings() -> HashMap<String, LanguageConfig> {
         compiler_path: None,
         compiler_flags: vec!["-Wall".to_string(), "-Wextra".to_string(), "-std=c++17".to_string()],
         output_format: Some("o".to_string()),
+        ..LanguageConfig::default()
     });
     
     // Rust language settings
@@ -302,6 +1270,7 @@ fn default_language_settings() -> HashMap<String, LanguageConfig> {
         compiler_path: None,
         compiler_flags: vec!["--release".to_string()],
         output_format: None,
+        ..LanguageConfig::default()
     });
     
     // Go language settings
@@ -310,6 +1279,7 @@ fn default_language_settings() -> HashMap<String, LanguageConfig> {
         compiler_path: None,
         compiler_flags: vec!["-ldflags=-s".to_string(), "-ldflags=-w".to_string()],
         output_format: None,
+        ..LanguageConfig::default()
     });
     
     settings
@@ -318,6 +1288,7 @@ fn default_language_settings() -> HashMap<String, LanguageConfig> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::patterns::FileFilter;
     use tempfile::TempDir;
 
     #[test]
@@ -330,45 +1301,121 @@ mod tests {
 
     #[test]
     fn test_ignore_patterns() {
-        let mut config = Config::default();
+        let config = Config::default();
         
         // Test ignore patterns
-        assert!(config.should_ignore_file(Path::new("file.o")));
-        assert!(config.should_ignore_file(Path::new("build/file.cpp")));
-        assert!(!config.should_ignore_file(Path::new("main.c")));
+        assert!(FileFilter::new(&config).unwrap().is_ignored(Path::new("file.o")));
+        assert!(FileFilter::new(&config).unwrap().is_ignored(Path::new("build/file.cpp")));
+        assert!(!FileFilter::new(&config).unwrap().is_ignored(Path::new("main.c")));
     }
 
     #[test]
     fn test_include_patterns() {
-        let mut config = Config::default();
-        config.include_patterns = vec!["*.c".to_string(), "*.cpp".to_string()];
+        let config = Config {
+            include_patterns: vec!["*.c".to_string(), "*.cpp".to_string()],
+            ..Config::default()
+        };
         
-        assert!(config.should_ignore_file(Path::new("file.py")));
-        assert!(!config.should_ignore_file(Path::new("main.c")));
-        assert!(!config.should_ignore_file(Path::new("helper.cpp")));
+        assert!(FileFilter::new(&config).unwrap().is_ignored(Path::new("file.py")));
+        assert!(!FileFilter::new(&config).unwrap().is_ignored(Path::new("main.c")));
+        assert!(!FileFilter::new(&config).unwrap().is_ignored(Path::new("helper.cpp")));
     }
 
     #[test]
     fn test_pattern_matching() {
-        assert!(Config::matches_pattern("file.o", "*.o"));
-        assert!(Config::matches_pattern("src/main.c", "*.c"));
-        assert!(Config::matches_pattern("build/", "build/"));
-        assert!(!Config::matches_pattern("main.c", "*.o"));
+        let config = Config {
+            ignore_patterns: vec!["build/".to_string(), "*.o".to_string()],
+            ..Config::default()
+        };
+        assert!(FileFilter::new(&config).unwrap().is_ignored(Path::new("file.o")));
+        assert!(FileFilter::new(&config).unwrap().is_ignored(Path::new("src/file.o")));
+        assert!(FileFilter::new(&config).unwrap().is_ignored(Path::new("build/main.c")));
+        assert!(!FileFilter::new(&config).unwrap().is_ignored(Path::new("rebuild/main.c")));
+        assert!(!FileFilter::new(&config).unwrap().is_ignored(Path::new("main.c")));
+    }
+
+    #[test]
+    fn test_merge_project_overlay() {
+        let mut base = serde_json::to_value(Config::default()).unwrap();
+        let overlay = serde_json::json!({
+            "extension_overrides": { "v": "verilog" },
+            "language_settings": { "python": { "type_checker": "mypy" } }
+        });
+        merge_json(&mut base, overlay);
+        let config: Config = serde_json::from_value(base).unwrap();
+
+        assert_eq!(config.extension_overrides.get("v").map(String::as_str), Some("verilog"));
+        assert_eq!(config.get_language_config("python").unwrap().type_checker.as_deref(), Some("mypy"));
+        assert!(config.get_language_config("c").unwrap().enabled);
+        let mut config = config.validated().unwrap();
+
+        config.extension_overrides.insert("h".to_string(), "objective-c".to_string());
+        let error = config.validated().unwrap_err().to_string();
+        assert!(error.contains("extension_overrides.h names \"objective-c\""), "{}", error);
+
+        let mut config = Config::default();
+        config.custom_languages.push(CustomLanguageConfig { name: "c".to_string(), ..Default::default() });
+        let error = config.validated().unwrap_err().to_string();
+        assert!(error.contains("custom_languages declares \"c\""), "{}", error);
+    }
+
+    #[test]
+    fn test_organization_beneath_user() {
+        let organization = serde_json::json!({
+            "parallel_jobs": 4,
+            "history": true,
+            "organization": { "url": "https://evil.example/lol.json", "public_key": "evil.asc" }
+        });
+        let user = serde_json::json!({ "parallel_jobs": 8 });
+        let config = Config::beneath_user(organization, Some(user)).unwrap();
+
+        assert_eq!(config.parallel_jobs, 8);
+        assert!(config.history);
+        assert!(config.organization.is_none());
+    }
+
+    #[test]
+    fn test_config_formats() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let mut defaults = serde_json::to_value(Config::default()).unwrap();
+        defaults["ignore_patterns"] = serde_json::json!(["src/*.c", "*.o"]);
+        for format in [ConfigFormat::Json, ConfigFormat::Toml, ConfigFormat::Yaml] {
+            let path = root.join(format!("lol.{}", format.extension()));
+            write_config_file(&path, &defaults).unwrap();
+            assert_eq!(project_config_path(root).unwrap(), path);
+            let config: Config = serde_json::from_value(read_config_file(&path).unwrap()).unwrap();
+            assert_eq!(config.parallel_jobs, num_cpus::get());
+            assert_eq!(config.output_directory.as_deref(), Some("build"));
+            fs::remove_file(path).unwrap();
+        }
+
+        let json = root.join(PROJECT_CONFIG_FILE);
+        write_config_file(&json, &defaults).unwrap();
+        let migration = migrate_config_file(&json, Some(ConfigFormat::Toml)).unwrap();
+        assert_eq!(migration.path, root.join("lol.toml"));
+        assert_eq!(migration.patterns, [("src/*.c".to_string(), "src/**/*.c".to_string())]);
+        assert!(!json.exists());
+        assert_eq!(read_config_file(&migration.path).unwrap()["ignore_patterns"], serde_json::json!(["src/**/*.c", "*.o"]));
+
+        fs::write(&json, "{}").unwrap();
+        assert!(project_config_path(root).is_err());
     }
 
     #[test]
     fn test_config_save_load() {
         let temp_dir = TempDir::new().unwrap();
+        let config_path = temp_dir.path().join("lol").join("config.json");
         let original_config = Config::default();
         
         // Modify config
         let mut config = original_config.clone();
         config.parallel_jobs = 8;
-        config.add_ignore_pattern("*.tmp".to_string());
+        config.ignore_patterns.push("*.tmp".to_string());
         
         // Save and reload
-        config.save().unwrap();
-        let loaded_config = Config::load().unwrap();
+        config.save_to(&config_path).unwrap();
+        let loaded_config = Config::load_from(&config_path).unwrap();
         
         assert_eq!(loaded_config.parallel_jobs, 8);
         assert!(loaded_config.ignore_patterns.contains(&"*.tmp".to_string()));
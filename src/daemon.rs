@@ -0,0 +1,700 @@
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use anyhow::Result;
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{Html, IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use clap::{Parser, ValueEnum};
+use futures_util::stream::{self, Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::net::TcpListener;
+use tokio::sync::{broadcast, Notify};
+use crate::args::{Args, Profile};
+use crate::artifacts::ArtifactManifest;
+use crate::diagnostics::Diagnostic;
+use crate::events::BuildEvent;
+use crate::metrics::Metrics;
+
+/// Port `lol daemon` listens on unless told otherwise.
+pub const DEFAULT_PORT: u16 = 7171;
+
+/// Finished builds kept for the API; older ones are forgotten.
+const HISTORY_LIMIT: usize = 100;
+
+/// The page `lol daemon --dashboard` serves at `/`.
+const DASHBOARD: &str = include_str!("dashboard.html");
+
+/// How soon a queued build runs: higher priorities first, and in the order
+/// submitted within one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+/// The body of `POST /api/builds`.
+#[derive(Debug, Deserialize)]
+pub struct BuildRequest {
+    pub project_path: PathBuf,
+    /// Languages to build, as their CLI switches (`c`, `cpp`, `python`…);
+    /// every detected language when empty
+    #[serde(default)]
+    pub languages: Vec<String>,
+    /// `debug` or `release`
+    pub profile: Option<String>,
+    pub jobs: Option<usize>,
+    #[serde(default)]
+    pub version_stamp: bool,
+    #[serde(default)]
+    pub priority: Priority,
+}
+
+impl BuildRequest {
+    /// The arguments `lol` would be run with for this build.
+    pub fn args(&self) -> Result<Args, String> {
+        if !self.project_path.is_dir() {
+            return Err(format!("{} is not a directory", self.project_path.display()));
+        }
+        let mut args = Args::parse_from(["lol".as_ref(), self.project_path.as_os_str()]);
+        for language in &self.languages {
+            match language.to_lowercase().as_str() {
+                "c" => args.c = true,
+                "cpp" | "c++" => args.cpp = true,
+                "python" => args.python = true,
+                "java" => args.java = true,
+                "rust" => args.rust = true,
+                "go" => args.go = true,
+                "js" | "javascript" => args.js = true,
+                "ts" | "typescript" => args.ts = true,
+                "all" => args.all = true,
+                other => return Err(format!("Unknown language '{}'", other)),
+            }
+        }
+        if let Some(profile) = &self.profile {
+            args.profile = Profile::from_str(profile, true).map_err(|_| format!("Unknown profile '{}'", profile))?;
+        }
+        if let Some(jobs) = self.jobs {
+            args.jobs = jobs.max(1);
+        }
+        args.version_stamp = self.version_stamp;
+        Ok(args)
+    }
+
+    /// Identifies requests for the same build, whatever order the languages
+    /// come in and whatever their priority.
+    fn coalesce_key(&self) -> String {
+        let mut languages: Vec<String> = self.languages.iter().map(|language| language.to_lowercase()).collect();
+        languages.sort();
+        languages.dedup();
+        let project_path = self.project_path.canonicalize().unwrap_or_else(|_| self.project_path.clone());
+        json!([project_path, languages, self.profile, self.jobs, self.version_stamp]).to_string()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BuildStatus {
+    Queued,
+    Running,
+    Passed,
+    Failed,
+    Cancelled,
+}
+
+/// A build submitted to the daemon, with everything the API serves about it.
+#[derive(Debug, Clone, Serialize)]
+pub struct BuildRecord {
+    pub id: u64,
+    pub project_path: PathBuf,
+    pub status: BuildStatus,
+    pub priority: Priority,
+    /// Unix seconds
+    pub submitted_at: u64,
+    pub duration_ms: Option<u64>,
+    /// Why the build could not run, as opposed to failing to compile
+    pub error: Option<String>,
+    #[serde(skip)]
+    pub events: Vec<BuildEvent>,
+    #[serde(skip)]
+    pub diagnostics: Vec<Diagnostic>,
+    #[serde(skip)]
+    pub artifacts: Option<ArtifactManifest>,
+}
+
+impl BuildRecord {
+    fn is_finished(&self) -> bool {
+        matches!(self.status, BuildStatus::Passed | BuildStatus::Failed | BuildStatus::Cancelled)
+    }
+
+    /// How long each language took to compile, from its events.
+    pub fn language_durations(&self) -> BTreeMap<String, u64> {
+        self.events
+            .iter()
+            .filter_map(|event| match event {
+                BuildEvent::LanguageFinished { language, duration_ms, .. } => Some((language.clone(), *duration_ms)),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// A finished build's timings, for `GET /api/history`.
+#[derive(Debug, Serialize)]
+pub struct HistoryEntry {
+    pub id: u64,
+    pub project_path: PathBuf,
+    pub status: BuildStatus,
+    pub submitted_at: u64,
+    pub duration_ms: Option<u64>,
+    pub languages: BTreeMap<String, u64>,
+}
+
+/// How a build ended, reported by whoever ran it.
+#[derive(Debug, Default)]
+pub struct BuildResult {
+    pub success: bool,
+    pub duration_ms: u64,
+    pub diagnostics: Vec<Diagnostic>,
+    pub artifacts: Option<ArtifactManifest>,
+    pub error: Option<String>,
+}
+
+/// A build for the daemon's loop to run.
+pub struct Job {
+    pub id: u64,
+    pub args: Args,
+    priority: Priority,
+    coalesce_key: String,
+}
+
+/// A build waiting or running, for `GET /api/queue`.
+#[derive(Debug, Serialize)]
+pub struct QueueEntry {
+    pub id: u64,
+    pub project_path: PathBuf,
+    pub status: BuildStatus,
+    pub priority: Priority,
+    pub submitted_at: u64,
+}
+
+/// An event of one build, numbered within it so streams can skip the ones
+/// they already replayed. WebSocket clients get it as the event with a
+/// `build` field added.
+#[derive(Debug, Clone, Serialize)]
+struct Update {
+    #[serde(rename = "build")]
+    id: u64,
+    #[serde(skip)]
+    sequence: usize,
+    #[serde(flatten)]
+    event: BuildEvent,
+}
+
+#[derive(Default)]
+struct Builds {
+    next_id: u64,
+    records: BTreeMap<u64, BuildRecord>,
+    /// Builds waiting to run, in the order they will
+    pending: Vec<Job>,
+}
+
+impl Builds {
+    fn sort_pending(&mut self) {
+        self.pending.sort_by_key(|job| (Reverse(job.priority), job.id));
+    }
+}
+
+/// The end of the queue the daemon's loop takes builds from.
+pub struct JobQueue {
+    builds: Arc<Mutex<Builds>>,
+    queued: Arc<Notify>,
+}
+
+impl JobQueue {
+    /// The next build to run, waiting for one to be submitted if need be.
+    pub async fn recv(&mut self) -> Job {
+        loop {
+            {
+                let mut builds = self.builds.lock().unwrap();
+                if !builds.pending.is_empty() {
+                    return builds.pending.remove(0);
+                }
+            }
+            self.queued.notified().await;
+        }
+    }
+}
+
+/// Shared state behind the API. The HTTP side queues builds and reads
+/// records; the loop running the builds reports their events and results.
+#[derive(Clone)]
+pub struct Daemon {
+    builds: Arc<Mutex<Builds>>,
+    updates: broadcast::Sender<Update>,
+    queued: Arc<Notify>,
+    metrics: Metrics,
+    dashboard: bool,
+}
+
+impl Daemon {
+    pub fn new() -> (Self, JobQueue) {
+        let builds: Arc<Mutex<Builds>> = Arc::default();
+        let queued = Arc::new(Notify::new());
+        let (updates, _) = broadcast::channel(1024);
+        let jobs = JobQueue { builds: Arc::clone(&builds), queued: Arc::clone(&queued) };
+        (Self { builds, updates, queued, metrics: Metrics::new(), dashboard: false }, jobs)
+    }
+
+    /// Also serves the web dashboard at `/`.
+    pub fn with_dashboard(mut self, dashboard: bool) -> Self {
+        self.dashboard = dashboard;
+        self
+    }
+
+    /// Queues the build `request` asks for, run with `args`. A request for
+    /// a build that is already waiting joins it instead, raising its
+    /// priority if need be; the second value tells which happened.
+    fn submit(&self, request: &BuildRequest, args: Args) -> (u64, bool) {
+        let coalesce_key = request.coalesce_key();
+        let id = {
+            let mut builds = self.builds.lock().unwrap();
+            let builds = &mut *builds;
+            if let Some(job) = builds.pending.iter_mut().find(|job| job.coalesce_key == coalesce_key) {
+                job.priority = job.priority.max(request.priority);
+                let (id, priority) = (job.id, job.priority);
+                if let Some(record) = builds.records.get_mut(&id) {
+                    record.priority = priority;
+                }
+                builds.sort_pending();
+                return (id, true);
+            }
+            builds.next_id += 1;
+            let id = builds.next_id;
+            builds.records.insert(
+                id,
+                BuildRecord {
+                    id,
+                    project_path: request.project_path.clone(),
+                    status: BuildStatus::Queued,
+                    priority: request.priority,
+                    submitted_at: SystemTime::now().duration_since(UNIX_EPOCH).map(|now| now.as_secs()).unwrap_or(0),
+                    duration_ms: None,
+                    error: None,
+                    events: Vec::new(),
+                    diagnostics: Vec::new(),
+                    artifacts: None,
+                },
+            );
+            let finished: Vec<u64> = builds
+                .records
+                .values()
+                .filter(|record| record.is_finished())
+                .map(|record| record.id)
+                .collect();
+            for old in finished.iter().take(finished.len().saturating_sub(HISTORY_LIMIT)) {
+                builds.records.remove(old);
+            }
+            builds.pending.push(Job { id, args, priority: request.priority, coalesce_key });
+            builds.sort_pending();
+            id
+        };
+        self.record(id, BuildEvent::Queued);
+        self.queued.notify_one();
+        (id, false)
+    }
+
+    /// Takes a build that has not started out of the queue.
+    fn cancel(&self, id: u64) -> Result<(), ApiError> {
+        {
+            let mut builds = self.builds.lock().unwrap();
+            let status = builds.records.get(&id).map(|record| record.status).ok_or_else(|| not_found(id))?;
+            let conflict = match status {
+                BuildStatus::Queued => None,
+                BuildStatus::Running => Some("is already running"),
+                BuildStatus::Cancelled => Some("was already cancelled"),
+                _ => Some("has finished"),
+            };
+            if let Some(conflict) = conflict {
+                return Err((StatusCode::CONFLICT, Json(json!({ "error": format!("Build {} {}", id, conflict) }))));
+            }
+            builds.pending.retain(|job| job.id != id);
+            if let Some(record) = builds.records.get_mut(&id) {
+                record.status = BuildStatus::Cancelled;
+            }
+        }
+        self.record(id, BuildEvent::Cancelled);
+        Ok(())
+    }
+
+    /// The running build, then the waiting ones in the order they will run.
+    fn queue(&self) -> Vec<QueueEntry> {
+        let builds = self.builds.lock().unwrap();
+        let running = builds.records.values().filter(|record| record.status == BuildStatus::Running);
+        let pending = builds.pending.iter().filter_map(|job| builds.records.get(&job.id));
+        running
+            .chain(pending)
+            .map(|record| QueueEntry {
+                id: record.id,
+                project_path: record.project_path.clone(),
+                status: record.status,
+                priority: record.priority,
+                submitted_at: record.submitted_at,
+            })
+            .collect()
+    }
+
+    /// Adds `event` to the build's history and metrics, and streams it to
+    /// listeners.
+    pub fn record(&self, id: u64, event: BuildEvent) {
+        let mut builds = self.builds.lock().unwrap();
+        let Some(record) = builds.records.get_mut(&id) else {
+            return;
+        };
+        self.metrics.observe(&event);
+        if event == BuildEvent::Started {
+            record.status = BuildStatus::Running;
+        }
+        record.events.push(event.clone());
+        let _ = self.updates.send(Update { id, sequence: record.events.len() - 1, event });
+    }
+
+    pub fn finish(&self, id: u64, result: BuildResult) {
+        {
+            let mut builds = self.builds.lock().unwrap();
+            let Some(record) = builds.records.get_mut(&id) else {
+                return;
+            };
+            record.status = if result.success { BuildStatus::Passed } else { BuildStatus::Failed };
+            record.duration_ms = Some(result.duration_ms);
+            record.diagnostics = result.diagnostics;
+            record.artifacts = result.artifacts;
+            record.error = result.error;
+        }
+        self.record(id, BuildEvent::Finished { success: result.success, duration_ms: result.duration_ms });
+    }
+
+    fn get(&self, id: u64) -> Result<BuildRecord, ApiError> {
+        self.builds.lock().unwrap().records.get(&id).cloned().ok_or_else(|| not_found(id))
+    }
+
+    /// Finished builds, oldest first.
+    pub fn history(&self) -> Vec<HistoryEntry> {
+        let builds = self.builds.lock().unwrap();
+        builds
+            .records
+            .values()
+            .filter(|record| record.is_finished())
+            .map(|record| HistoryEntry {
+                id: record.id,
+                project_path: record.project_path.clone(),
+                status: record.status,
+                submitted_at: record.submitted_at,
+                duration_ms: record.duration_ms,
+                languages: record.language_durations(),
+            })
+            .collect()
+    }
+
+    pub fn router(self) -> Router {
+        let router = if self.dashboard { Router::new().route("/", get(dashboard)) } else { Router::new() };
+        router
+            .route("/metrics", get(metrics))
+            .route("/api/health", get(health))
+            .route("/api/history", get(history))
+            .route("/api/queue", get(queue))
+            .route("/api/ws", get(updates_socket))
+            .route("/api/builds", get(list_builds).post(submit_build))
+            .route("/api/builds/{id}", get(get_build).delete(cancel_build))
+            .route("/api/builds/{id}/events", get(build_events))
+            .route("/api/builds/{id}/diagnostics", get(build_diagnostics))
+            .route("/api/builds/{id}/artifacts", get(build_artifacts))
+            .with_state(self)
+    }
+}
+
+/// Serves the API until the listener fails.
+pub async fn serve(listener: TcpListener, daemon: Daemon) -> Result<()> {
+    axum::serve(listener, daemon.router()).await?;
+    Ok(())
+}
+
+/// A daemon API error as the client sees it: the `error` the daemon sent,
+/// or why it could not be reached.
+fn client_error(host: &str, port: u16, error: ureq::Error) -> anyhow::Error {
+    match error {
+        ureq::Error::Status(_, response) => {
+            let body: Value = response.into_json().unwrap_or_default();
+            anyhow::anyhow!("{}", body["error"].as_str().unwrap_or("The daemon rejected the request"))
+        }
+        error => anyhow::anyhow!("No lol daemon at {}:{} ({})", host, port, error),
+    }
+}
+
+/// `lol queue status`: what the daemon at `host:port` is running and has
+/// waiting.
+pub fn print_queue(host: &str, port: u16) -> Result<()> {
+    let entries: Vec<Value> = ureq::get(&format!("http://{}:{}/api/queue", host, port))
+        .call()
+        .map_err(|error| client_error(host, port, error))?
+        .into_json()?;
+    if entries.is_empty() {
+        say!("🛰️  No builds are queued or running");
+        return Ok(());
+    }
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|now| now.as_secs()).unwrap_or(0);
+    say!("{:>5}  {:<9}  {:<8}  {:>8}  Project", "ID", "Status", "Priority", "Age");
+    for entry in entries {
+        say!(
+            "{:>5}  {:<9}  {:<8}  {:>7}s  {}",
+            entry["id"].as_u64().unwrap_or_default(),
+            entry["status"].as_str().unwrap_or_default(),
+            entry["priority"].as_str().unwrap_or_default(),
+            now.saturating_sub(entry["submitted_at"].as_u64().unwrap_or(now)),
+            entry["project_path"].as_str().unwrap_or_default()
+        );
+    }
+    Ok(())
+}
+
+/// `lol queue cancel`: takes build `id` out of the daemon's queue.
+pub fn cancel_queued(host: &str, port: u16, id: u64) -> Result<()> {
+    ureq::delete(&format!("http://{}:{}/api/builds/{}", host, port, id))
+        .call()
+        .map_err(|error| client_error(host, port, error))?;
+    Ok(())
+}
+
+type ApiError = (StatusCode, Json<Value>);
+
+fn not_found(id: u64) -> ApiError {
+    (StatusCode::NOT_FOUND, Json(json!({ "error": format!("No build {}", id) })))
+}
+
+async fn health() -> Json<Value> {
+    Json(json!({ "name": "lol", "version": env!("CARGO_PKG_VERSION") }))
+}
+
+async fn dashboard() -> Html<&'static str> {
+    Html(DASHBOARD)
+}
+
+async fn metrics(State(daemon): State<Daemon>) -> Response {
+    let queued = daemon.builds.lock().unwrap().records.values().filter(|record| record.status == BuildStatus::Queued).count();
+    daemon.metrics.set_queue_depth(queued);
+    crate::metrics::handler(State(daemon.metrics)).await.into_response()
+}
+
+async fn history(State(daemon): State<Daemon>) -> Json<Vec<HistoryEntry>> {
+    Json(daemon.history())
+}
+
+async fn queue(State(daemon): State<Daemon>) -> Json<Vec<QueueEntry>> {
+    Json(daemon.queue())
+}
+
+async fn list_builds(State(daemon): State<Daemon>) -> Json<Vec<BuildRecord>> {
+    Json(daemon.builds.lock().unwrap().records.values().rev().cloned().collect())
+}
+
+async fn submit_build(State(daemon): State<Daemon>, Json(request): Json<BuildRequest>) -> Result<(StatusCode, Json<Value>), ApiError> {
+    let args = request.args().map_err(|error| (StatusCode::BAD_REQUEST, Json(json!({ "error": error }))))?;
+    let (id, coalesced) = daemon.submit(&request, args);
+    Ok((StatusCode::ACCEPTED, Json(json!({ "id": id, "status": BuildStatus::Queued, "coalesced": coalesced }))))
+}
+
+async fn cancel_build(State(daemon): State<Daemon>, Path(id): Path<u64>) -> Result<StatusCode, ApiError> {
+    daemon.cancel(id).map(|_| StatusCode::NO_CONTENT)
+}
+
+async fn get_build(State(daemon): State<Daemon>, Path(id): Path<u64>) -> Result<Json<BuildRecord>, ApiError> {
+    daemon.get(id).map(Json)
+}
+
+async fn build_diagnostics(State(daemon): State<Daemon>, Path(id): Path<u64>) -> Result<Json<Vec<Diagnostic>>, ApiError> {
+    daemon.get(id).map(|record| Json(record.diagnostics))
+}
+
+async fn build_artifacts(State(daemon): State<Daemon>, Path(id): Path<u64>) -> Result<Json<ArtifactManifest>, ApiError> {
+    let record = daemon.get(id)?;
+    record
+        .artifacts
+        .map(Json)
+        .ok_or_else(|| (StatusCode::NOT_FOUND, Json(json!({ "error": format!("Build {} has no artifacts", id) }))))
+}
+
+/// Server-sent events for one build: the events so far, then live ones
+/// until it finishes.
+async fn build_events(
+    State(daemon): State<Daemon>,
+    Path(id): Path<u64>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ApiError> {
+    // Subscribe before copying the history, so nothing falls in between
+    let updates = daemon.updates.subscribe();
+    let record = daemon.get(id)?;
+    let replayed = record.events.len();
+    let finished = record.is_finished();
+
+    let live = stream::unfold((updates, finished), move |(mut updates, finished)| async move {
+        if finished {
+            return None;
+        }
+        loop {
+            match updates.recv().await {
+                Ok(update) if update.id == id && update.sequence >= replayed => {
+                    let finished = matches!(update.event, BuildEvent::Finished { .. } | BuildEvent::Cancelled);
+                    return Some((update.event, (updates, finished)));
+                }
+                Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    });
+    let events = stream::iter(record.events).chain(live).map(|event| Ok(Event::default().json_data(&event).unwrap_or_default()));
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+/// Every build's events as they happen, one JSON text message each, for
+/// clients following the whole daemon rather than one build.
+async fn updates_socket(State(daemon): State<Daemon>, upgrade: WebSocketUpgrade) -> Response {
+    let updates = daemon.updates.subscribe();
+    upgrade.on_upgrade(move |socket| forward_updates(socket, updates))
+}
+
+async fn forward_updates(mut socket: WebSocket, mut updates: broadcast::Receiver<Update>) {
+    loop {
+        let update = tokio::select! {
+            update = updates.recv() => update,
+            // Anything the client sends is ignored; only its going away matters
+            message = socket.recv() => match message {
+                Some(Ok(_)) => continue,
+                _ => return,
+            },
+        };
+        let update = match update {
+            Ok(update) => update,
+            Err(broadcast::error::RecvError::Lagged(_)) => continue,
+            Err(broadcast::error::RecvError::Closed) => return,
+        };
+        let Ok(text) = serde_json::to_string(&update) else {
+            continue;
+        };
+        if socket.send(Message::text(text)).await.is_err() {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::FutureExt;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_requests_and_build_records() {
+        let temp_dir = TempDir::new().unwrap();
+        let request = BuildRequest {
+            project_path: temp_dir.path().to_path_buf(),
+            languages: vec!["C++".to_string(), "python".to_string()],
+            profile: Some("release".to_string()),
+            jobs: Some(2),
+            version_stamp: false,
+            priority: Priority::Normal,
+        };
+        let args = request.args().unwrap();
+        assert!(args.cpp && args.python && !args.c);
+        assert_eq!((args.profile, args.jobs), (Profile::Release, 2));
+        let bad = BuildRequest { languages: vec!["cobol".to_string()], ..request };
+        assert_eq!(bad.args().unwrap_err(), "Unknown language 'cobol'");
+
+        let (daemon, mut jobs) = Daemon::new();
+        let request = |languages: &[&str], priority| BuildRequest {
+            project_path: temp_dir.path().to_path_buf(),
+            languages: languages.iter().map(|language| language.to_string()).collect(),
+            profile: None,
+            jobs: None,
+            version_stamp: false,
+            priority,
+        };
+        let submit = |request: BuildRequest| daemon.submit(&request, request.args().unwrap());
+        let (id, _) = submit(request(&["c"], Priority::Normal));
+        let (low, _) = submit(request(&["python"], Priority::Low));
+        assert_eq!(submit(request(&["C", "c"], Priority::High)), (id, true));
+        let (high, _) = submit(request(&["go"], Priority::High));
+        let order: Vec<(u64, Priority)> = daemon.queue().iter().map(|entry| (entry.id, entry.priority)).collect();
+        assert_eq!(order, [(id, Priority::High), (high, Priority::High), (low, Priority::Low)]);
+
+        daemon.cancel(low).unwrap();
+        assert_eq!(daemon.get(low).unwrap().status, BuildStatus::Cancelled);
+        assert_eq!(daemon.cancel(low).unwrap_err().0, StatusCode::CONFLICT);
+        assert_eq!(jobs.recv().now_or_never().unwrap().id, id);
+        assert_eq!(jobs.recv().now_or_never().unwrap().id, high);
+        assert!(jobs.recv().now_or_never().is_none());
+        daemon.record(id, BuildEvent::Started);
+        assert_eq!(daemon.get(id).unwrap().status, BuildStatus::Running);
+        daemon.finish(id, BuildResult { success: true, duration_ms: 5, ..BuildResult::default() });
+
+        let record = daemon.get(id).unwrap();
+        assert_eq!(record.status, BuildStatus::Passed);
+        assert_eq!(record.events, [BuildEvent::Queued, BuildEvent::Started, BuildEvent::Finished { success: true, duration_ms: 5 }]);
+        assert_eq!(daemon.history()[0].duration_ms, Some(5));
+        let update = Update { id, sequence: 2, event: BuildEvent::Stage { name: "compile".to_string() } };
+        assert_eq!(serde_json::to_value(update).unwrap(), json!({ "build": id, "event": "stage", "name": "compile" }));
+        assert!(daemon.get(high + 1).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_dashboard_streams_build_events_over_websocket() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (daemon, _jobs) = Daemon::new();
+        tokio::spawn(serve(listener, daemon.clone().with_dashboard(true)));
+
+        let page = tokio::task::spawn_blocking(move || ureq::get(&format!("http://127.0.0.1:{}/", port)).call().unwrap().into_string().unwrap())
+            .await
+            .unwrap();
+        assert!(page.contains("/api/ws"));
+
+        let (mut socket, _) = tokio_tungstenite::connect_async(format!("ws://127.0.0.1:{}/api/ws", port)).await.unwrap();
+        let temp_dir = TempDir::new().unwrap();
+        let request = BuildRequest {
+            project_path: temp_dir.path().to_path_buf(),
+            languages: vec!["c".to_string()],
+            profile: None,
+            jobs: None,
+            version_stamp: false,
+            priority: Priority::Normal,
+        };
+        let (id, _) = daemon.submit(&request, request.args().unwrap());
+        daemon.record(id, BuildEvent::Started);
+        daemon.finish(id, BuildResult { success: false, duration_ms: 7, ..BuildResult::default() });
+
+        let mut streamed = Vec::new();
+        while streamed.len() < 3 {
+            let message = tokio::time::timeout(std::time::Duration::from_secs(5), socket.next()).await.unwrap().unwrap().unwrap();
+            if let tokio_tungstenite::tungstenite::Message::Text(text) = message {
+                streamed.push(serde_json::from_str::<Value>(&text).unwrap());
+            }
+        }
+        assert_eq!(
+            streamed,
+            [
+                json!({ "build": id, "event": "queued" }),
+                json!({ "build": id, "event": "started" }),
+                json!({ "build": id, "event": "finished", "success": false, "duration_ms": 7 }),
+            ]
+        );
+    }
+}
@@ -0,0 +1,240 @@
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use crate::doctor::on_path;
+
+/// Where compile commands run, chosen with `--env`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum DevEnvironment {
+    /// The tools installed on this machine, even in a devcontainer project
+    Host,
+    /// The project's `nix develop` shell, from `flake.nix` or `shell.nix`
+    Nix,
+    /// The project's devcontainer, through the `devcontainer` CLI
+    Devcontainer,
+}
+
+/// A declared environment compile commands are run through.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DevShell {
+    /// `nix develop <installable> --command …`
+    Nix { installable: Vec<OsString>, file: &'static str },
+    /// `devcontainer exec --workspace-folder <project> …`, with paths under
+    /// `project_dir` mapped onto `workspace_folder` inside the container.
+    Devcontainer { project_dir: PathBuf, workspace_folder: PathBuf },
+}
+
+/// The project's devcontainer configuration, if it has one.
+fn devcontainer_config(project_dir: &Path) -> Option<PathBuf> {
+    [".devcontainer/devcontainer.json", ".devcontainer.json"]
+        .iter()
+        .map(|file| project_dir.join(file))
+        .find(|path| path.is_file())
+}
+
+/// Whether lol itself runs in a container already, as inside VS Code's
+/// Dev Containers or a Codespace, where the host tools are the right ones.
+fn in_container() -> bool {
+    ["REMOTE_CONTAINERS", "CODESPACES", "DEVCONTAINER"].iter().any(|name| std::env::var_os(name).is_some())
+        || Path::new("/.dockerenv").exists()
+        || Path::new("/run/.containerenv").exists()
+}
+
+/// The environment to build `project_path` in: the one `requested` with
+/// `--env`, or else its devcontainer when it has one and the CLI is
+/// installed. `None` means the host.
+pub fn select(requested: Option<DevEnvironment>, project_path: &Path) -> Result<Option<DevShell>> {
+    let project_dir = project_path
+        .canonicalize()
+        .with_context(|| format!("Failed to resolve {}", project_path.display()))?;
+    match requested {
+        Some(DevEnvironment::Host) => Ok(None),
+        Some(DevEnvironment::Nix) => {
+            if !on_path("nix") {
+                anyhow::bail!("--env nix needs Nix; see https://nixos.org/download");
+            }
+            nix_shell(&project_dir).map(Some)
+        }
+        Some(DevEnvironment::Devcontainer) => {
+            let config = devcontainer_config(&project_dir)
+                .with_context(|| format!("--env devcontainer needs a .devcontainer/devcontainer.json in {}", project_dir.display()))?;
+            if !on_path("devcontainer") {
+                anyhow::bail!("--env devcontainer needs the devcontainer CLI: npm install -g @devcontainers/cli");
+            }
+            Ok(Some(devcontainer_shell(project_dir, &config)))
+        }
+        None => {
+            let Some(config) = devcontainer_config(&project_dir).filter(|_| !in_container()) else {
+                return Ok(None);
+            };
+            if !on_path("devcontainer") {
+                tracing::warn!(
+                    "{} defines a devcontainer, but the devcontainer CLI is not installed; building with the host toolchains",
+                    project_dir.display()
+                );
+                return Ok(None);
+            }
+            Ok(Some(devcontainer_shell(project_dir, &config)))
+        }
+    }
+}
+
+fn nix_shell(project_dir: &Path) -> Result<DevShell> {
+    let shell = if project_dir.join("flake.nix").is_file() {
+        DevShell::Nix { installable: vec![project_dir.into()], file: "flake.nix" }
+    } else if project_dir.join("shell.nix").is_file() {
+        DevShell::Nix { installable: vec!["--file".into(), project_dir.join("shell.nix").into()], file: "shell.nix" }
+    } else {
+        anyhow::bail!("--env nix needs a flake.nix or shell.nix in {}", project_dir.display());
+    };
+    Ok(shell)
+}
+
+/// The container's workspace folder: `workspaceFolder` from the
+/// configuration, else the CLI's default `/workspaces/<project name>`.
+fn devcontainer_shell(project_dir: PathBuf, config: &Path) -> DevShell {
+    // devcontainer.json allows comments; whole-line ones are the usual kind
+    let contents: String = fs::read_to_string(config)
+        .unwrap_or_default()
+        .lines()
+        .filter(|line| !line.trim_start().starts_with("//"))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let workspace_folder = serde_json::from_str::<serde_json::Value>(&contents)
+        .ok()
+        .and_then(|config| config.get("workspaceFolder")?.as_str().map(PathBuf::from))
+        .unwrap_or_else(|| Path::new("/workspaces").join(project_dir.file_name().unwrap_or_default()));
+    DevShell::Devcontainer { project_dir, workspace_folder }
+}
+
+impl DevShell {
+    pub fn describe(&self) -> String {
+        match self {
+            DevShell::Nix { file, .. } => format!("nix develop ({})", file),
+            DevShell::Devcontainer { workspace_folder, .. } => format!("devcontainer ({})", workspace_folder.display()),
+        }
+    }
+
+    /// Builds the Nix shell or starts the container once before the build,
+    /// so a broken environment fails with its own error message.
+    pub fn prepare(&self) -> Result<()> {
+        let command = match self {
+            DevShell::Nix { .. } => self.wrap(&Command::new("true")),
+            DevShell::Devcontainer { project_dir, .. } => {
+                let mut command = Command::new("devcontainer");
+                command.arg("up").arg("--workspace-folder").arg(project_dir);
+                command
+            }
+        };
+        let output = run(command).with_context(|| format!("Failed to start {}", self.describe()))?;
+        if !output.status.success() {
+            anyhow::bail!("{} failed:\n{}", self.describe(), String::from_utf8_lossy(&output.stderr).trim());
+        }
+        Ok(())
+    }
+
+    /// `command` rewritten to run inside this environment. Its environment
+    /// variables come along, except a host `PATH` inside a container.
+    pub fn wrap(&self, command: &Command) -> Command {
+        let envs = command.get_envs().filter_map(|(name, value)| Some((name.to_os_string(), value?.to_os_string())));
+        match self {
+            DevShell::Nix { installable, .. } => {
+                let mut wrapped = Command::new("nix");
+                wrapped
+                    .args(["--extra-experimental-features", "nix-command flakes", "develop"])
+                    .args(installable)
+                    .arg("--command")
+                    .arg(command.get_program())
+                    .args(command.get_args())
+                    .envs(envs);
+                if let Some(dir) = command.get_current_dir() {
+                    wrapped.current_dir(dir);
+                }
+                wrapped
+            }
+            DevShell::Devcontainer { project_dir, .. } => {
+                let current_dir = command.get_current_dir().map(Path::to_path_buf).or_else(|| std::env::current_dir().ok());
+                let mut wrapped = Command::new("devcontainer");
+                wrapped.arg("exec").arg("--workspace-folder").arg(project_dir);
+                for (name, value) in envs.filter(|(name, _)| name != "PATH") {
+                    let mut assignment = name;
+                    assignment.push("=");
+                    assignment.push(self.map_path(&value));
+                    wrapped.arg("--remote-env").arg(assignment);
+                }
+                // Relative paths in the command are relative to the directory it runs in
+                wrapped
+                    .args(["sh", "-c", "cd \"$0\" && exec \"$@\""])
+                    .arg(current_dir.map(|dir| self.map_path(dir.as_os_str())).unwrap_or_else(|| ".".into()))
+                    .arg(self.map_path(command.get_program()))
+                    .args(command.get_args().map(|arg| self.map_path(arg)));
+                wrapped
+            }
+        }
+    }
+
+    /// `value` with the project directory replaced by the container's
+    /// workspace folder wherever it appears, as in `-I/project/include`.
+    fn map_path(&self, value: &OsStr) -> OsString {
+        let DevShell::Devcontainer { project_dir, workspace_folder } = self else {
+            return value.to_os_string();
+        };
+        let (Some(text), Some(host), Some(container)) = (value.to_str(), project_dir.to_str(), workspace_folder.to_str()) else {
+            return value.to_os_string();
+        };
+        text.replace(host, container).into()
+    }
+
+    /// Runs `command` inside this environment. A program the environment
+    /// does not provide is reported as not found, as it would be on the host.
+    pub fn output(&self, command: &Command) -> io::Result<Output> {
+        let output = run(self.wrap(command))?;
+        if output.status.code() == Some(127) {
+            return Err(io::Error::new(io::ErrorKind::NotFound, format!("{:?} is not in {}", command.get_program(), self.describe())));
+        }
+        Ok(output)
+    }
+}
+
+fn run(mut command: Command) -> io::Result<Output> {
+    tracing::debug!(?command, "Running in dev environment");
+    command.output()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_wrap_commands() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_dir = temp_dir.path().canonicalize().unwrap();
+        assert!(select(Some(DevEnvironment::Host), &project_dir).unwrap().is_none());
+        assert!(nix_shell(&project_dir).is_err());
+
+        fs::write(project_dir.join("flake.nix"), "{}").unwrap();
+        let nix = nix_shell(&project_dir).unwrap();
+        let mut command = Command::new("gcc");
+        command.arg(project_dir.join("main.c")).env("CFLAGS", "-O2");
+        let wrapped = nix.wrap(&command);
+        assert_eq!(wrapped.get_program(), "nix");
+        let args: Vec<_> = wrapped.get_args().map(|arg| arg.to_string_lossy().into_owned()).collect();
+        assert_eq!(args[3..], [project_dir.display().to_string(), "--command".to_string(), "gcc".to_string(), project_dir.join("main.c").display().to_string()]);
+        assert_eq!(wrapped.get_envs().count(), 1);
+
+        fs::create_dir(project_dir.join(".devcontainer")).unwrap();
+        let config = project_dir.join(".devcontainer/devcontainer.json");
+        fs::write(&config, "{\n  // built from the Dockerfile\n  \"workspaceFolder\": \"/src\"\n}\n").unwrap();
+        assert_eq!(devcontainer_config(&project_dir), Some(config.clone()));
+        let container = devcontainer_shell(project_dir.clone(), &config);
+        command.current_dir(project_dir.join("lib")).env("PATH", "/host/bin");
+        let args: Vec<_> = container.wrap(&command).get_args().map(|arg| arg.to_string_lossy().into_owned()).collect();
+        assert_eq!(args[3..5], ["--remote-env", "CFLAGS=-O2"]);
+        assert_eq!(args[8..], ["/src/lib", "gcc", "/src/main.c"]);
+    }
+}
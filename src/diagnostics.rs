@@ -0,0 +1,228 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
+
+impl Severity {
+    fn parse(value: &str) -> Self {
+        match value {
+            "error" | "fatal error" => Severity::Error,
+            "warning" => Severity::Warning,
+            _ => Severity::Note,
+        }
+    }
+}
+
+/// A single compiler or checker message tied to a source location.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Diagnostic {
+    pub file: PathBuf,
+    pub line: Option<u32>,
+    pub column: Option<u32>,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.file.display())?;
+        if let Some(line) = self.line {
+            write!(f, ":{}", line)?;
+        }
+        if let Some(column) = self.column {
+            write!(f, ":{}", column)?;
+        }
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Note => "note",
+        };
+        write!(f, ": {}: {}", severity, self.message)
+    }
+}
+
+/// Extracts diagnostics from raw tool output.
+///
+/// Understands the GCC-style `file:line[:col]: severity: message` format
+/// (gcc, clang, mypy, rustc short output), pyright's
+/// `file:line:col - severity: message` format, Perl's
+/// `message at file line N.`, R's `file:line:col: message` parse errors,
+/// Verilator's `%Severity: file:line:col: message` and the
+/// `file(line,col): error CODE: message` of tsc, fsharpc and MSVC.
+pub fn parse(output: &str) -> Vec<Diagnostic> {
+    output.lines().filter_map(parse_line).collect()
+}
+
+fn parse_line(line: &str) -> Option<Diagnostic> {
+    static PATTERNS: OnceLock<[Regex; 6]> = OnceLock::new();
+    let [gcc_style, pyright_style, verilator_style, msbuild_style, perl_style, r_style] = PATTERNS.get_or_init(|| {
+        [
+            r"^(?P<file>[^:\s][^:]*):(?P<line>\d+):(?:(?P<col>\d+):)?\s*(?P<sev>fatal error|error|warning|note):\s*(?P<msg>.*)$",
+            r"^\s*(?P<file>[^:\s][^:]*):(?P<line>\d+):(?P<col>\d+) - (?P<sev>error|warning|information):\s*(?P<msg>.*)$",
+            r"^%(?P<sev>Error|Warning)(?:-[\w-]+)?: (?P<file>[^:\s]+):(?P<line>\d+):(?:(?P<col>\d+):)?\s*(?P<msg>.*)$",
+            r"^(?P<file>[^(\s][^(]*)\((?P<line>\d+)(?:,(?P<col>\d+))?\)\s*: (?P<sev>fatal error|error|warning) \w+: (?P<msg>.*)$",
+            r"^(?P<msg>.+?) at (?P<file>\S+) line (?P<line>\d+)[,.]",
+            r"(?P<file>[^:\s]+\.[Rr]):(?P<line>\d+):(?P<col>\d+): (?P<msg>.*)$",
+        ]
+        .map(|pattern| Regex::new(pattern).unwrap())
+    });
+
+    let caps = gcc_style
+        .captures(line)
+        .or_else(|| pyright_style.captures(line))
+        .or_else(|| verilator_style.captures(line))
+        .or_else(|| msbuild_style.captures(line))
+        .or_else(|| perl_style.captures(line))
+        .or_else(|| r_style.captures(line))?;
+    Some(Diagnostic {
+        file: PathBuf::from(caps["file"].trim()),
+        line: caps.name("line").and_then(|m| m.as_str().parse().ok()),
+        column: caps.name("col").and_then(|m| m.as_str().parse().ok()),
+        severity: caps
+            .name("sev")
+            .map_or(Severity::Error, |m| Severity::parse(&m.as_str().to_lowercase())),
+        message: caps["msg"].trim().to_string(),
+    })
+}
+
+/// `output` cut just before its error number `max_errors + 1`, or `None`
+/// when it reports no more errors than that. What follows each kept error,
+/// such as the source excerpt, stays with it.
+pub fn truncate_errors(output: &str, max_errors: usize) -> Option<&str> {
+    let mut errors = 0;
+    let mut offset = 0;
+    for line in output.split_inclusive('\n') {
+        if parse_line(line.trim_end()).is_some_and(|diagnostic| diagnostic.severity == Severity::Error) {
+            errors += 1;
+            if errors > max_errors {
+                return Some(&output[..offset]);
+            }
+        }
+        offset += line.len();
+    }
+    None
+}
+
+/// Diagnostics sharing a severity and message, e.g. the same error in a
+/// header reported by every translation unit that includes it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Group<'a> {
+    /// The first one reported
+    pub first: &'a Diagnostic,
+    pub count: usize,
+}
+
+/// Groups identical messages, in the order they first appear.
+pub fn group<'a>(diagnostics: impl IntoIterator<Item = &'a Diagnostic>) -> Vec<Group<'a>> {
+    let mut groups: Vec<Group> = Vec::new();
+    let mut positions: HashMap<(Severity, &str), usize> = HashMap::new();
+    for diagnostic in diagnostics {
+        let position = *positions.entry((diagnostic.severity, &diagnostic.message)).or_insert(groups.len());
+        match groups.get_mut(position) {
+            Some(group) => group.count += 1,
+            None => groups.push(Group { first: diagnostic, count: 1 }),
+        }
+    }
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_gcc_and_mypy_output() {
+        let output = "main.c:3:5: error: expected ';' before 'return'\n\
+                      In file included from main.c:1:\n\
+                      app.py:12: error: Incompatible return value type  [return-value]\n\
+                      Found 1 error in 1 file (checked 1 source file)";
+        let diagnostics = parse(output);
+
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].file, PathBuf::from("main.c"));
+        assert_eq!(diagnostics[0].line, Some(3));
+        assert_eq!(diagnostics[0].column, Some(5));
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+        assert_eq!(diagnostics[1].file, PathBuf::from("app.py"));
+        assert_eq!(diagnostics[1].column, None);
+    }
+
+    #[test]
+    fn test_parse_pyright_output() {
+        let output = "/src/app.py\n  /src/app.py:4:9 - warning: Import \"foo\" could not be resolved";
+        let diagnostics = parse(output);
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Severity::Warning);
+        assert_eq!(diagnostics[0].column, Some(9));
+    }
+
+    #[test]
+    fn test_parse_perl_and_r_output() {
+        let output = "syntax error at lib/Tool.pm line 12, near \"}\"\n\
+                      lib/Tool.pm had compilation errors.\n\
+                      Error in parse(file = commandArgs(TRUE)[1]) : analysis.R:7:3: unexpected symbol";
+        let diagnostics = parse(output);
+
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].file, PathBuf::from("lib/Tool.pm"));
+        assert_eq!(diagnostics[0].line, Some(12));
+        assert_eq!(diagnostics[0].message, "syntax error");
+        assert_eq!(diagnostics[1].file, PathBuf::from("analysis.R"));
+        assert_eq!(diagnostics[1].column, Some(3));
+        assert_eq!(diagnostics[1].severity, Severity::Error);
+    }
+
+    #[test]
+    fn test_parse_verilator_and_ghdl_output() {
+        let output = "%Warning-WIDTH: rtl/alu.sv:14:20: Operator ASSIGN expects 8 bits\n\
+                      %Error: rtl/top.sv:3:1: syntax error, unexpected endmodule\n\
+                      cpu.vhd:22:5:error: no declaration for \"clk\"";
+        let diagnostics = parse(output);
+
+        assert_eq!(diagnostics.len(), 3);
+        assert_eq!(diagnostics[0].severity, Severity::Warning);
+        assert_eq!(diagnostics[0].file, PathBuf::from("rtl/alu.sv"));
+        assert_eq!(diagnostics[1].severity, Severity::Error);
+        assert_eq!(diagnostics[2].file, PathBuf::from("cpu.vhd"));
+        assert_eq!(diagnostics[2].line, Some(22));
+    }
+
+    #[test]
+    fn test_group_identical_messages() {
+        let output = "util.h:4:1: error: unknown type name 'size'\n\
+                      a.c:9:3: warning: unused variable 'x'\n\
+                      util.h:4:1: error: unknown type name 'size'\n\
+                      b.c:2:1: warning: unused variable 'x'\n\
+                      util.h:4:1: error: unknown type name 'size'";
+        let diagnostics = parse(output);
+        let groups = group(&diagnostics);
+
+        assert_eq!(groups.len(), 2);
+        assert_eq!((groups[0].first, groups[0].count), (&diagnostics[0], 3));
+        assert_eq!(groups[1].first.file, PathBuf::from("a.c"));
+        assert_eq!(groups[1].count, 2);
+    }
+
+    #[test]
+    fn test_truncate_after_max_errors() {
+        let output = "Main.java:3: error: cannot find symbol\n    Foo foo;\n    ^\n\
+                      Main.java:4: warning: [deprecation] bar() is deprecated\n\
+                      Main.java:9: error: ';' expected\n\
+                      2 errors\n";
+
+        assert_eq!(truncate_errors(output, 2), None);
+        let truncated = truncate_errors(output, 1).unwrap();
+        assert!(truncated.ends_with("deprecated\n"));
+        assert!(!truncated.contains("';' expected"));
+    }
+}
@@ -0,0 +1,161 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use crate::patterns::PatternSet;
+
+/// Overrides for the directory holding it and everything below.
+pub const DIR_CONFIG_FILE: &str = ".lol.toml";
+
+/// What a `.lol.toml` changes for its subtree.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DirConfig {
+    /// Stop at this file instead of also applying those in parent directories
+    pub root: bool,
+    /// Flags added for every language in the subtree
+    pub flags: Vec<String>,
+    /// Patterns, relative to this directory, of files left out of the build
+    pub ignore_patterns: Vec<String>,
+    /// Flags added for one language, by config key
+    pub language: BTreeMap<String, DirLanguageConfig>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DirLanguageConfig {
+    pub flags: Vec<String>,
+}
+
+impl DirConfig {
+    pub fn parse(contents: &str) -> Result<Self> {
+        Ok(toml::from_str(contents)?)
+    }
+}
+
+/// A `.lol.toml` with its ignore patterns compiled.
+struct Loaded {
+    config: DirConfig,
+    ignore: PatternSet,
+}
+
+/// The `.lol.toml` files of a project, read as directories are first asked
+/// about. Like `.editorconfig`, the files from the project root down to a
+/// source's directory all apply, the nearest last so its flags win.
+pub struct DirConfigs {
+    root: PathBuf,
+    loaded: HashMap<PathBuf, Option<Arc<Loaded>>>,
+}
+
+impl DirConfigs {
+    /// `root` must be spelled as the paths later passed in are, canonical
+    /// or not.
+    pub fn new(root: &Path) -> Self {
+        Self { root: root.to_path_buf(), loaded: HashMap::new() }
+    }
+
+    fn load(&mut self, dir: &Path) -> Result<Option<Arc<Loaded>>> {
+        if let Some(loaded) = self.loaded.get(dir) {
+            return Ok(loaded.clone());
+        }
+        let path = dir.join(DIR_CONFIG_FILE);
+        let loaded = match fs::read_to_string(&path) {
+            Ok(contents) => {
+                let config = DirConfig::parse(&contents).with_context(|| format!("Failed to parse {}", path.display()))?;
+                let ignore = PatternSet::new(&config.ignore_patterns).with_context(|| format!("Invalid ignore_patterns in {}", path.display()))?;
+                Some(Arc::new(Loaded { config, ignore }))
+            }
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => None,
+            Err(error) => return Err(error).with_context(|| format!("Failed to read {}", path.display())),
+        };
+        self.loaded.insert(dir.to_path_buf(), loaded.clone());
+        Ok(loaded)
+    }
+
+    /// The files applying to `file` with their directories, outermost first.
+    fn applying_to(&mut self, file: &Path) -> Result<Vec<(PathBuf, Arc<Loaded>)>> {
+        let dirs: Vec<PathBuf> = file
+            .ancestors()
+            .skip(1)
+            .take_while(|dir| dir.starts_with(&self.root))
+            .map(Path::to_path_buf)
+            .collect();
+        let mut applying = Vec::new();
+        for dir in dirs {
+            if let Some(loaded) = self.load(&dir)? {
+                let root = loaded.config.root;
+                applying.push((dir, loaded));
+                if root {
+                    break;
+                }
+            }
+        }
+        applying.reverse();
+        Ok(applying)
+    }
+
+    /// Whether a `.lol.toml` above `file` leaves it out of the build.
+    pub fn is_ignored(&mut self, file: &Path) -> Result<bool> {
+        Ok(self
+            .applying_to(file)?
+            .iter()
+            .any(|(dir, loaded)| loaded.ignore.is_match(file.strip_prefix(dir).unwrap_or(file))))
+    }
+
+    /// Flags the `.lol.toml` files above `file` add for the language with
+    /// config key `language`.
+    pub fn flags(&mut self, file: &Path, language: &str) -> Result<Vec<String>> {
+        let mut flags = Vec::new();
+        for (_, loaded) in self.applying_to(file)? {
+            let config = &loaded.config;
+            flags.extend(config.flags.iter().cloned());
+            flags.extend(config.language.get(language).into_iter().flat_map(|settings| settings.flags.iter().cloned()));
+        }
+        Ok(flags)
+    }
+
+    /// `files` grouped by the flags they get, in order of first appearance.
+    pub fn partition(&mut self, files: Vec<PathBuf>, language: &str) -> Result<Vec<(Vec<String>, Vec<PathBuf>)>> {
+        let mut parts: Vec<(Vec<String>, Vec<PathBuf>)> = Vec::new();
+        for file in files {
+            let flags = self.flags(&file, language)?;
+            match parts.iter_mut().find(|(part_flags, _)| *part_flags == flags) {
+                Some((_, part_files)) => part_files.push(file),
+                None => parts.push((flags, vec![file])),
+            }
+        }
+        Ok(parts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nested_overrides_merge_down_the_tree() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("third_party/zlib")).unwrap();
+        fs::create_dir_all(root.join("src/strict")).unwrap();
+        fs::write(root.join(DIR_CONFIG_FILE), "flags = [\"-g\"]\n").unwrap();
+        fs::write(root.join("third_party").join(DIR_CONFIG_FILE), "flags = [\"-w\"]\nignore_patterns = [\"test_*.c\"]\n").unwrap();
+        fs::write(root.join("src/strict").join(DIR_CONFIG_FILE), "[language.cpp]\nflags = [\"-Werror\"]\n").unwrap();
+
+        let mut configs = DirConfigs::new(root);
+        assert_eq!(configs.flags(&root.join("third_party/zlib/inflate.c"), "c").unwrap(), ["-g", "-w"]);
+        assert_eq!(configs.flags(&root.join("src/strict/parser.cpp"), "cpp").unwrap(), ["-g", "-Werror"]);
+        assert_eq!(configs.flags(&root.join("src/strict/parser.c"), "c").unwrap(), ["-g"]);
+        assert!(configs.is_ignored(&root.join("third_party/zlib/test_inflate.c")).unwrap());
+        assert!(!configs.is_ignored(&root.join("src/test_main.c")).unwrap());
+
+        fs::write(root.join("src/strict").join(DIR_CONFIG_FILE), "root = true\nflags = [\"-Werror\"]\n").unwrap();
+        let mut configs = DirConfigs::new(root);
+        assert_eq!(configs.flags(&root.join("src/strict/parser.c"), "c").unwrap(), ["-Werror"]);
+
+        fs::write(root.join(DIR_CONFIG_FILE), "flag = [\"-g\"]\n").unwrap();
+        assert!(DirConfigs::new(root).flags(&root.join("main.c"), "c").is_err());
+    }
+}
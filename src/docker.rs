@@ -0,0 +1,196 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use anyhow::{Context, Result};
+use crate::language_support::Language;
+
+/// Runtime image when the package needs no interpreter.
+const BASE_IMAGE: &str = "debian:bookworm-slim";
+
+/// A stage that rebuilds one language's outputs inside its toolchain
+/// image, with the lol binary copied into the build context.
+#[derive(Debug)]
+pub struct Builder {
+    pub language: String,
+    pub image: &'static str,
+    /// The lol switch that limits the build to this language
+    pub flag: &'static str,
+    /// Built files, relative to the project, and where they go under `/app`
+    pub artifacts: Vec<(PathBuf, PathBuf)>,
+}
+
+/// The toolchain image and lol switch for a compiled language, by name.
+/// These are Debian bookworm images, like the runtime stage.
+pub fn builder_image(language: &str) -> Option<(&'static str, &'static str)> {
+    match language {
+        "C" => Some(("gcc:14-bookworm", "--c")),
+        "C++" => Some(("gcc:14-bookworm", "--cpp")),
+        "Rust" => Some(("rust:1-bookworm", "--rust")),
+        "Go" => Some(("golang:1-bookworm", "--go")),
+        "Java" => Some(("eclipse-temurin:21-jdk", "--java")),
+        _ => None,
+    }
+}
+
+/// The runtime stage's base image: one that has the interpreter the
+/// launchers need, or a slim Debian when only native code ships. Only one
+/// interpreter can come from the base image; others are warned about.
+pub fn runtime_image(languages: &[Language]) -> &'static str {
+    let mut images = languages.iter().filter_map(|language| {
+        let image = match language {
+            Language::Python => "python:3-slim-bookworm",
+            Language::JavaScript => "node:20-bookworm-slim",
+            Language::Ruby => "ruby:3-slim-bookworm",
+            Language::Perl => "perl:5-slim-bookworm",
+            Language::Java => "eclipse-temurin:21-jre",
+            Language::R => "r-base:latest",
+            Language::Julia => "julia:1-bookworm",
+            _ => return None,
+        };
+        Some((language, image))
+    });
+    let Some((_, image)) = images.next() else {
+        return BASE_IMAGE;
+    };
+    for (language, _) in images {
+        tracing::warn!("The {} image has no {} interpreter; install it in the Dockerfile", image, language.name());
+    }
+    image
+}
+
+/// `<name>:<version>`, with the characters image tags disallow replaced.
+pub fn image_tag(name: &str, version: &str) -> String {
+    let clean = |text: &str, allowed: &str| -> String {
+        text.chars().map(|c| if c.is_ascii_alphanumeric() || allowed.contains(c) { c } else { '-' }).collect()
+    };
+    format!("{}:{}", clean(&name.to_lowercase(), "._-"), clean(version, "._-"))
+}
+
+/// A multi-stage Dockerfile: one builder stage per compiled language,
+/// then a runtime stage with the staged package in `/app` and each
+/// builder's outputs copied over the host-built ones.
+pub fn dockerfile(
+    builders: &[Builder],
+    runtime_image: &str,
+    staging_dir: &Path,
+    command: &str,
+    name: &str,
+    version: &str,
+) -> String {
+    let mut dockerfile = String::from("# Generated by `lol package --format docker`\n");
+    for builder in builders {
+        dockerfile.push_str(&format!(
+            "\nFROM {} AS build-{}\n\
+            WORKDIR /src\n\
+            COPY . .\n\
+            COPY {} /usr/local/bin/lol\n\
+            RUN lol . {}\n",
+            builder.image,
+            stage_name(&builder.language),
+            lol_binary().display(),
+            builder.flag
+        ));
+    }
+
+    dockerfile.push_str(&format!(
+        "\nFROM {}\n\
+        LABEL org.opencontainers.image.title=\"{}\" org.opencontainers.image.version=\"{}\"\n\
+        WORKDIR /app\n\
+        COPY {} /app\n",
+        runtime_image,
+        name,
+        version,
+        staging_dir.display()
+    ));
+    for builder in builders {
+        for (source, destination) in &builder.artifacts {
+            dockerfile.push_str(&format!(
+                "COPY --from=build-{} /src/{} /app/{}\n",
+                stage_name(&builder.language),
+                source.display(),
+                destination.display()
+            ));
+        }
+    }
+    dockerfile.push_str(&format!("ENV PATH=\"/app/bin:$PATH\"\nENTRYPOINT [\"/app/bin/{}\"]\n", command));
+    dockerfile
+}
+
+fn stage_name(language: &str) -> String {
+    language.to_lowercase().replace('+', "p")
+}
+
+/// Where the lol executable is copied in the build context.
+pub fn lol_binary() -> PathBuf {
+    Path::new(crate::package::DIST_DIR).join("docker").join("lol")
+}
+
+/// Keeps host build outputs and everything in `dist/` except the staged
+/// package and the lol binary out of the build context.
+pub fn dockerignore(staging_dir: &Path, output_dir: Option<&Path>) -> String {
+    let mut ignore = String::from(".git\n");
+    if let Some(output_dir) = output_dir {
+        ignore.push_str(&format!("{}\n", output_dir.display()));
+    }
+    ignore.push_str(&format!(
+        "{}\n!{}\n!{}\n",
+        crate::package::DIST_DIR,
+        staging_dir.display(),
+        lol_binary().display()
+    ));
+    ignore
+}
+
+/// Builds the image from the project directory with docker, or buildah
+/// when docker is missing. Returns whether either was available.
+pub fn build(project_path: &Path, dockerfile_path: &Path, tag: &str) -> Result<bool> {
+    for (tool, subcommand) in [("docker", "build"), ("buildah", "bud")] {
+        if Command::new(tool).arg("--version").output().is_err() {
+            continue;
+        }
+        let status = Command::new(tool)
+            .arg(subcommand)
+            .arg("--file")
+            .arg(dockerfile_path)
+            .args(["--tag", tag])
+            .arg(".")
+            .current_dir(project_path)
+            // The classic builder ignores `Dockerfile.dockerignore`
+            .env("DOCKER_BUILDKIT", "1")
+            .status()
+            .with_context(|| format!("Failed to run {}", tool))?;
+        if !status.success() {
+            anyhow::bail!("{} {} failed for {}", tool, subcommand, tag);
+        }
+        return Ok(true);
+    }
+    tracing::warn!(
+        "Neither docker nor buildah is installed; build {} yourself",
+        dockerfile_path.display()
+    );
+    Ok(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dockerfile_has_builder_and_runtime_stages() {
+        let (image, flag) = builder_image("Rust").unwrap();
+        let builders = vec![Builder {
+            language: "Rust".to_string(),
+            image,
+            flag,
+            artifacts: vec![(PathBuf::from("build/rust/tool"), PathBuf::from("bin/tool"))],
+        }];
+        let runtime = runtime_image(&[Language::Python]);
+        let dockerfile = dockerfile(&builders, runtime, Path::new("dist/demo-1.0.0"), "demo", "demo", "1.0.0");
+
+        assert!(dockerfile.contains("FROM rust:1-bookworm AS build-rust\nWORKDIR /src\nCOPY . .\nCOPY dist/docker/lol /usr/local/bin/lol\nRUN lol . --rust\n"));
+        assert!(dockerfile.contains("FROM python:3-slim-bookworm\n"));
+        assert!(dockerfile.contains("COPY dist/demo-1.0.0 /app\nCOPY --from=build-rust /src/build/rust/tool /app/bin/tool\n"));
+        assert!(dockerfile.ends_with("ENTRYPOINT [\"/app/bin/demo\"]\n"));
+        assert_eq!(runtime_image(&[Language::Rust]), BASE_IMAGE);
+        assert_eq!(image_tag("My App", "1.0.0+git"), "my-app:1.0.0-git");
+    }
+}
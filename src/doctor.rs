@@ -0,0 +1,367 @@
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use anyhow::{Context, Result};
+use colored::*;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use crate::config::Config;
+use crate::init;
+use crate::language_support::Language;
+
+/// Versions `lol doctor --fix` installs from official release tarballs.
+const ZIG_VERSION: &str = "0.14.1";
+const NIM_VERSION: &str = "2.2.4";
+
+const ZIG_INDEX_URL: &str = "https://ziglang.org/download/index.json";
+const NIM_DOWNLOAD_URL: &str = "https://nim-lang.org/download";
+const RUSTUP_DIST_URL: &str = "https://static.rust-lang.org/rustup/dist";
+
+/// How `--fix` can install a toolchain without root.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Installer {
+    Rustup,
+    Zig,
+    Nim,
+}
+
+fn installer(language: &Language) -> Option<Installer> {
+    match language {
+        Language::Rust => Some(Installer::Rustup),
+        Language::Zig => Some(Installer::Zig),
+        Language::Nim => Some(Installer::Nim),
+        _ => None,
+    }
+}
+
+/// Package managers whose install command `lol doctor` suggests, in the
+/// order they are looked for.
+const PACKAGE_MANAGERS: &[(&str, &str)] = &[
+    ("apt-get", "sudo apt-get install"),
+    ("dnf", "sudo dnf install"),
+    ("pacman", "sudo pacman -S"),
+    ("brew", "brew install"),
+    ("winget", "winget install"),
+];
+
+/// The package providing `language`'s toolchain under `manager`.
+fn package(language: &Language, manager: &str) -> Option<&'static str> {
+    let package = match (language, manager) {
+        (Language::C | Language::ObjectiveC, "brew") => "gcc",
+        (Language::C, "apt-get") => "build-essential",
+        (Language::C, _) => "gcc",
+        (Language::Cpp | Language::ObjectiveCpp, "apt-get" | "dnf") => "g++",
+        (Language::Cpp | Language::ObjectiveCpp, "pacman" | "brew") => "gcc",
+        (Language::Fortran, "pacman") => "gcc-fortran",
+        (Language::Fortran, "dnf") => "gcc-gfortran",
+        (Language::Fortran, "brew") => "gcc",
+        (Language::Fortran, _) => "gfortran",
+        (Language::Ada, "apt-get") => "gnat",
+        (Language::Ada, "dnf") => "gcc-gnat",
+        (Language::Ada, "pacman") => "gcc-ada",
+        (Language::Go, "apt-get" | "dnf") => "golang",
+        (Language::Go, "winget") => "GoLang.Go",
+        (Language::Go, _) => "go",
+        (Language::Java, "apt-get") => "default-jdk",
+        (Language::Java, "dnf") => "java-latest-openjdk-devel",
+        (Language::Java, "pacman") => "jdk-openjdk",
+        (Language::Java, "brew") => "openjdk",
+        (Language::CSharp | Language::FSharp, "apt-get" | "dnf") => "dotnet-sdk-8.0",
+        (Language::CSharp | Language::FSharp, "pacman") => "dotnet-sdk",
+        (Language::CSharp | Language::FSharp, "winget") => "Microsoft.DotNet.SDK.8",
+        (Language::Kotlin, "brew" | "pacman") => "kotlin",
+        (Language::Scala, "brew" | "pacman") => "scala",
+        (Language::Haskell, "apt-get" | "dnf" | "pacman" | "brew") => "ghc",
+        (Language::OCaml, "apt-get" | "dnf" | "pacman" | "brew") => "ocaml",
+        (Language::Erlang, "apt-get" | "dnf" | "pacman" | "brew") => "erlang",
+        (Language::Elixir, "apt-get" | "dnf" | "pacman" | "brew") => "elixir",
+        (Language::Crystal, "pacman" | "brew") => "crystal",
+        (Language::Cython, "apt-get") => "cython3",
+        (Language::Cython, "dnf" | "pacman") => "cython",
+        (Language::Pascal, "apt-get" | "dnf" | "brew") => "fpc",
+        (Language::Verilog, "apt-get" | "dnf" | "pacman" | "brew") => "iverilog",
+        (Language::Vhdl, "apt-get" | "dnf" | "pacman" | "brew") => "ghdl",
+        (Language::WebAssembly, "apt-get" | "dnf" | "pacman" | "brew") => "wabt",
+        (Language::Swift, "brew") => "swift",
+        _ => return None,
+    };
+    Some(package)
+}
+
+/// The install command for `language` with the first package manager
+/// found on this machine.
+fn package_hint(language: &Language) -> Option<String> {
+    PACKAGE_MANAGERS
+        .iter()
+        .find(|(manager, _)| on_path(manager))
+        .and_then(|(manager, install)| package(language, manager).map(|package| format!("{} {}", install, package)))
+}
+
+/// How to get the toolchain for `language`: `lol doctor --fix` where lol
+/// installs it, else a package manager command, else a plain instruction.
+pub fn install_hint(language: &Language) -> String {
+    if installer(language).is_some() {
+        return "run `lol doctor --fix` to install it for your user".to_string();
+    }
+    package_hint(language).unwrap_or_else(|| format!("install {} and make sure it is on PATH", language.get_compiler_command().0))
+}
+
+/// Whether `program` can be found on `PATH`.
+pub(crate) fn on_path(program: &str) -> bool {
+    std::env::var_os("PATH").is_some_and(|path| {
+        std::env::split_paths(&path).any(|dir| {
+            dir.join(program).is_file() || dir.join(format!("{}{}", program, std::env::consts::EXE_SUFFIX)).is_file()
+        })
+    })
+}
+
+/// `lol doctor`: checks the toolchain of every language in the project.
+/// With `fix`, installs the missing ones lol knows how to install for the
+/// current user and suggests package manager commands for the rest.
+pub fn run(project_path: &Path, config: &Config, fix: bool) -> Result<()> {
+    let detected = init::detect(project_path, config)?;
+    if detected.is_empty() {
+        say!("{} No source files found in {}", "ℹ️".blue(), project_path.display());
+        return Ok(());
+    }
+
+    let mut missing = Vec::new();
+    for found in &detected {
+        if found.toolchain == "Not available" {
+            say!("❌ {:<14} {}", found.language.name().bold(), "toolchain not found".red());
+            missing.push(found.language.clone());
+        } else {
+            say!("✅ {:<14} {}", found.language.name().bold(), found.toolchain.dimmed());
+        }
+    }
+    if missing.is_empty() {
+        return Ok(());
+    }
+
+    say!();
+    let mut unresolved = 0;
+    for language in &missing {
+        match (installer(language), fix) {
+            (Some(installer), true) => {
+                if let Err(error) = install(installer) {
+                    say_error!("❌ Failed to install {}: {:#}", language.name(), error);
+                    unresolved += 1;
+                }
+            }
+            (_, false) | (None, true) => {
+                say!("💡 {}: {}", language.name(), install_hint(language));
+                unresolved += 1;
+            }
+        }
+    }
+    if unresolved > 0 {
+        anyhow::bail!("{} of {} toolchains are missing", unresolved, detected.len());
+    }
+    Ok(())
+}
+
+/// Where `--fix` unpacks toolchains: `~/.local/share/lol/toolchains` on Linux.
+fn toolchains_dir() -> Result<PathBuf> {
+    Ok(dirs::data_local_dir().context("Could not determine the user data directory")?.join("lol").join("toolchains"))
+}
+
+/// Where `--fix` links installed programs, which is usually on `PATH`.
+fn bin_dir() -> Result<PathBuf> {
+    dirs::executable_dir()
+        .or_else(|| dirs::home_dir().map(|home| home.join(".local").join("bin")))
+        .context("Could not determine the user's bin directory")
+}
+
+fn install(installer: Installer) -> Result<()> {
+    match installer {
+        Installer::Rustup => install_rustup(),
+        Installer::Zig => {
+            let platform = format!("{}-{}", std::env::consts::ARCH, std::env::consts::OS);
+            let index: Value = ureq::get(ZIG_INDEX_URL).call().map_err(Box::new)?.into_json()?;
+            let (url, sha256) = zig_release(&index, ZIG_VERSION, &platform)
+                .with_context(|| format!("Zig {} has no release for {}", ZIG_VERSION, platform))?;
+            install_tarball("zig", &url, &sha256, Path::new("zig"))
+        }
+        Installer::Nim => {
+            let archive = nim_archive(NIM_VERSION).context("Nim publishes no binaries for this platform; try `brew install nim`")?;
+            let url = format!("{}/{}", NIM_DOWNLOAD_URL, archive);
+            let checksum = ureq::get(&format!("{}.sha256", url)).call().map_err(Box::new)?.into_string()?;
+            let sha256 = checksum.split_whitespace().next().context("Empty Nim checksum file")?.to_string();
+            install_tarball("nim", &url, &sha256, &Path::new("bin").join("nim"))
+        }
+    }
+}
+
+/// The tarball URL and checksum for `platform` (e.g. `x86_64-linux`) from
+/// ziglang.org's release index.
+fn zig_release(index: &Value, version: &str, platform: &str) -> Option<(String, String)> {
+    let release = &index[version][platform];
+    Some((release["tarball"].as_str()?.to_string(), release["shasum"].as_str()?.to_string()))
+}
+
+fn nim_archive(version: &str) -> Option<String> {
+    match (std::env::consts::OS, std::env::consts::ARCH) {
+        ("linux", "x86_64") => Some(format!("nim-{}-linux_x64.tar.xz", version)),
+        ("linux", "aarch64") => Some(format!("nim-{}-linux_arm64.tar.xz", version)),
+        ("windows", "x86_64") => Some(format!("nim-{}_x64.zip", version)),
+        _ => None,
+    }
+}
+
+/// Downloads `url`, checks it against `sha256`, unpacks it into the
+/// toolchains directory and links `program` (relative to the archive's top
+/// directory) into the user's bin directory.
+fn install_tarball(name: &str, url: &str, sha256: &str, program: &Path) -> Result<()> {
+    let toolchains = toolchains_dir()?;
+    fs::create_dir_all(&toolchains)?;
+    let file_name = url.rsplit('/').next().unwrap_or(name);
+    let archive = toolchains.join(file_name);
+
+    say!("⬇️  Downloading {}", url);
+    let actual = download(url, &archive)?;
+    if !actual.eq_ignore_ascii_case(sha256) {
+        let _ = fs::remove_file(&archive);
+        anyhow::bail!("Checksum mismatch for {}: expected {}, got {}", file_name, sha256, actual);
+    }
+
+    let output = Command::new("tar")
+        .arg("-xf")
+        .arg(&archive)
+        .arg("-C")
+        .arg(&toolchains)
+        .output()
+        .context("Unpacking toolchains needs tar")?;
+    fs::remove_file(&archive)?;
+    if !output.status.success() {
+        anyhow::bail!("tar failed: {}", String::from_utf8_lossy(&output.stderr));
+    }
+
+    let top_dir = [".tar.xz", ".tar.gz", ".zip"]
+        .iter()
+        .find_map(|extension| file_name.strip_suffix(extension))
+        .unwrap_or(file_name);
+    // Nim's archives unpack to nim-<version> whatever their platform suffix
+    let top_dir = if name == "nim" { format!("nim-{}", NIM_VERSION) } else { top_dir.to_string() };
+    let installed = toolchains.join(top_dir).join(program).with_extension(std::env::consts::EXE_EXTENSION);
+    link(&installed, name)
+}
+
+fn install_rustup() -> Result<()> {
+    let triple = match (std::env::consts::ARCH, std::env::consts::OS) {
+        (arch @ ("x86_64" | "aarch64"), "linux") => format!("{}-unknown-linux-gnu", arch),
+        (arch @ ("x86_64" | "aarch64"), "macos") => format!("{}-apple-darwin", arch),
+        (arch @ ("x86_64" | "aarch64"), "windows") => format!("{}-pc-windows-msvc", arch),
+        (arch, os) => anyhow::bail!("rustup has no installer for {}-{}; see https://rustup.rs", arch, os),
+    };
+    let url = format!("{}/{}/rustup-init{}", RUSTUP_DIST_URL, triple, std::env::consts::EXE_SUFFIX);
+    let checksum = ureq::get(&format!("{}.sha256", url)).call().map_err(Box::new)?.into_string()?;
+    let sha256 = checksum.split_whitespace().next().context("Empty rustup checksum file")?;
+
+    let toolchains = toolchains_dir()?;
+    fs::create_dir_all(&toolchains)?;
+    let rustup_init = toolchains.join(format!("rustup-init{}", std::env::consts::EXE_SUFFIX));
+    say!("⬇️  Downloading {}", url);
+    let actual = download(&url, &rustup_init)?;
+    if !actual.eq_ignore_ascii_case(sha256) {
+        let _ = fs::remove_file(&rustup_init);
+        anyhow::bail!("Checksum mismatch for rustup-init: expected {}, got {}", sha256, actual);
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&rustup_init, fs::Permissions::from_mode(0o755))?;
+    }
+
+    let status = Command::new(&rustup_init).args(["-y", "--no-modify-path", "--profile", "minimal"]).status();
+    fs::remove_file(&rustup_init)?;
+    let status = status.context("Failed to run rustup-init")?;
+    if !status.success() {
+        anyhow::bail!("rustup-init failed with {}", status);
+    }
+    let cargo_bin = dirs::home_dir().map(|home| home.join(".cargo").join("bin")).unwrap_or_default();
+    say!("✅ Installed Rust into {}", cargo_bin.display());
+    warn_if_not_on_path(&cargo_bin);
+    Ok(())
+}
+
+/// Streams `url` into `path`, returning the SHA-256 of what was written.
+fn download(url: &str, path: &Path) -> Result<String> {
+    let response = ureq::get(url).call().map_err(Box::new).with_context(|| format!("Failed to download {}", url))?;
+    let mut reader = response.into_reader();
+    let mut file = File::create(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = [0; 64 * 1024];
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+        file.write_all(&buffer[..read])?;
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Makes `program` callable from the user's bin directory.
+#[cfg(unix)]
+fn link(program: &Path, name: &str) -> Result<()> {
+    let file_name = program.file_name().filter(|_| program.is_file());
+    let file_name = file_name.with_context(|| format!("{} is missing from the unpacked archive", program.display()))?;
+    let bin_dir = bin_dir()?;
+    fs::create_dir_all(&bin_dir)?;
+    let target = bin_dir.join(file_name);
+    if target.symlink_metadata().is_ok() {
+        fs::remove_file(&target)?;
+    }
+    std::os::unix::fs::symlink(program, &target)?;
+    say!("✅ Installed {} as {}", name, target.display());
+    warn_if_not_on_path(&bin_dir);
+    Ok(())
+}
+
+/// Without symlinks the toolchain stays where it was unpacked, since it
+/// finds its standard library next to the real binary.
+#[cfg(not(unix))]
+fn link(program: &Path, name: &str) -> Result<()> {
+    let dir = program.parent().filter(|_| program.is_file());
+    let dir = dir.with_context(|| format!("{} is missing from the unpacked archive", program.display()))?;
+    say!("✅ Installed {} into {}", name, dir.display());
+    warn_if_not_on_path(dir);
+    Ok(())
+}
+
+fn warn_if_not_on_path(dir: &Path) {
+    let on_path = std::env::var_os("PATH").is_some_and(|path| std::env::split_paths(&path).any(|entry| entry == dir));
+    if !on_path {
+        tracing::warn!("{} is not on PATH; add it to use the new toolchain", dir.display());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_installers_and_hints() {
+        assert_eq!(installer(&Language::Zig), Some(Installer::Zig));
+        assert_eq!(installer(&Language::C), None);
+        assert_eq!(package(&Language::Go, "apt-get"), Some("golang"));
+        assert_eq!(package(&Language::Fortran, "pacman"), Some("gcc-fortran"));
+        assert_eq!(package(&Language::Jai, "apt-get"), None);
+
+        let index = json!({
+            "0.14.1": {
+                "x86_64-linux": {
+                    "tarball": "https://ziglang.org/download/0.14.1/zig-x86_64-linux-0.14.1.tar.xz",
+                    "shasum": "abc123"
+                }
+            }
+        });
+        let (url, sha256) = zig_release(&index, "0.14.1", "x86_64-linux").unwrap();
+        assert!(url.ends_with("zig-x86_64-linux-0.14.1.tar.xz"));
+        assert_eq!(sha256, "abc123");
+        assert!(zig_release(&index, "0.14.1", "riscv64-linux").is_none());
+    }
+}
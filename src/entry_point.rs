@@ -0,0 +1,193 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use anyhow::{Context, Result};
+use regex::Regex;
+use crate::artifacts::Artifact;
+use crate::language_support::Language;
+
+/// The source file a packaged program starts from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntryPoint {
+    /// Relative to the project root
+    pub file: PathBuf,
+    pub language: Language,
+}
+
+/// The entry point `--entry` names, which must be one of the detected
+/// sources so its language is known.
+pub fn from_override(project_path: &Path, entry: &Path, source_files: &HashMap<Language, Vec<PathBuf>>) -> Result<EntryPoint> {
+    let root = project_path.canonicalize()?;
+    let wanted = project_path
+        .join(entry)
+        .canonicalize()
+        .or_else(|_| entry.canonicalize())
+        .with_context(|| format!("Entry point {} does not exist", entry.display()))?;
+    for (language, files) in source_files {
+        if files.iter().any(|file| file.canonicalize().is_ok_and(|file| file == wanted)) {
+            return Ok(EntryPoint {
+                file: wanted.strip_prefix(&root).unwrap_or(&wanted).to_path_buf(),
+                language: language.clone(),
+            });
+        }
+    }
+    anyhow::bail!("Entry point {} is not one of the project's source files", entry.display())
+}
+
+/// Guesses the entry point from project manifests and source contents:
+/// Cargo's binary target, `package.json`'s `main`, then a Python
+/// `__main__.py` or `__name__ == "__main__"` guard, then a `main`
+/// function in C, C++, Go or Java, then a file named `main`.
+pub fn detect(project_path: &Path, source_files: &HashMap<Language, Vec<PathBuf>>) -> Option<EntryPoint> {
+    let root = project_path.canonicalize().ok()?;
+    let mut candidates: Vec<(Language, PathBuf)> = source_files
+        .iter()
+        .flat_map(|(language, files)| {
+            files.iter().map(|file| {
+                let file = file.canonicalize().unwrap_or_else(|_| file.clone());
+                (language.clone(), file.strip_prefix(&root).unwrap_or(&file).to_path_buf())
+            })
+        })
+        .collect();
+    // Shallow files first, so a top-level main wins over one in a subdirectory
+    candidates.sort_by(|(_, a), (_, b)| a.components().count().cmp(&b.components().count()).then(a.cmp(b)));
+    let found = |language: &Language, file: &Path| {
+        candidates
+            .iter()
+            .find(|(candidate_language, candidate)| candidate_language == language && candidate == file)
+            .map(|(language, file)| EntryPoint { file: file.clone(), language: language.clone() })
+    };
+
+    if let Some(entry) = cargo_binary(&root).and_then(|file| found(&Language::Rust, &file)) {
+        return Some(entry);
+    }
+    if let Some(main) = package_json_main(&root) {
+        for language in [Language::JavaScript, Language::TypeScript] {
+            if let Some(entry) = found(&language, &main) {
+                return Some(entry);
+            }
+        }
+    }
+
+    let python_guard = Regex::new(r#"(?m)^if\s+__name__\s*==\s*['"]__main__['"]\s*:"#).expect("valid pattern");
+    let patterns = [
+        (Language::C, r"(?m)^\s*(?:int|void)\s+main\s*\("),
+        (Language::Cpp, r"(?m)^\s*(?:int|auto)\s+main\s*\("),
+        (Language::Go, r"(?m)^func\s+main\s*\(\s*\)"),
+        (Language::Java, r"public\s+static\s+void\s+main\s*\("),
+    ];
+    let contains = |root: &Path, file: &Path, pattern: &Regex| {
+        fs::read_to_string(root.join(file)).is_ok_and(|content| pattern.is_match(&content))
+    };
+
+    let python = candidates.iter().filter(|(language, _)| *language == Language::Python);
+    if let Some((_, file)) = python.clone().find(|(_, file)| file.file_name().is_some_and(|name| name == "__main__.py")) {
+        return Some(EntryPoint { file: file.clone(), language: Language::Python });
+    }
+    if let Some((_, file)) = python.clone().find(|(_, file)| contains(&root, file, &python_guard)) {
+        return Some(EntryPoint { file: file.clone(), language: Language::Python });
+    }
+    for (language, pattern) in patterns {
+        let pattern = Regex::new(pattern).expect("valid pattern");
+        if let Some((_, file)) = candidates.iter().find(|(candidate, file)| *candidate == language && contains(&root, file, &pattern)) {
+            return Some(EntryPoint { file: file.clone(), language });
+        }
+    }
+
+    candidates
+        .iter()
+        .find(|(_, file)| file.file_stem().is_some_and(|stem| stem == "main"))
+        .map(|(language, file)| EntryPoint { file: file.clone(), language: language.clone() })
+}
+
+/// The C and C++ object files among `artifacts`.
+pub fn objects(artifacts: &[Artifact]) -> Vec<&Artifact> {
+    artifacts
+        .iter()
+        .filter(|artifact| artifact.path.extension().is_some_and(|ext| ext == "o"))
+        .filter(|artifact| matches!(artifact.language.as_deref(), Some("C" | "C++")))
+        .collect()
+}
+
+/// Links the C and C++ object files among `artifacts`, then the static
+/// `libraries`, into `executable`, with the C++ driver if any object came
+/// from C++.
+pub fn link_objects(artifacts: &[Artifact], project_path: &Path, libraries: &[PathBuf], executable: &Path) -> Result<()> {
+    let objects = objects(artifacts);
+    if objects.is_empty() {
+        anyhow::bail!("No C or C++ object files to link into {}", executable.display());
+    }
+    let driver = if objects.iter().any(|artifact| artifact.language.as_deref() == Some("C++")) { "c++" } else { "cc" };
+    if let Some(parent) = executable.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let output = Command::new(driver)
+        .args(objects.iter().map(|artifact| project_path.join(&artifact.path)))
+        .args(libraries)
+        .arg("-o")
+        .arg(executable)
+        .output()
+        .with_context(|| format!("Failed to run {}", driver))?;
+    if !output.status.success() {
+        anyhow::bail!("Linking {} failed: {}", executable.display(), String::from_utf8_lossy(&output.stderr));
+    }
+    Ok(())
+}
+
+/// The source of Cargo's first `[[bin]]`, or `src/main.rs`.
+fn cargo_binary(root: &Path) -> Option<PathBuf> {
+    let manifest = fs::read_to_string(root.join("Cargo.toml")).ok()?;
+    let mut in_bin = false;
+    for line in manifest.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_bin = line == "[[bin]]";
+        } else if in_bin {
+            if let Some(path) = line.strip_prefix("path").and_then(|rest| rest.trim_start().strip_prefix('=')) {
+                return Some(PathBuf::from(path.trim().trim_matches('"')));
+            }
+        }
+    }
+    Some(PathBuf::from("src").join("main.rs")).filter(|path| root.join(path).is_file())
+}
+
+fn package_json_main(root: &Path) -> Option<PathBuf> {
+    let content = fs::read_to_string(root.join("package.json")).ok()?;
+    let manifest: serde_json::Value = serde_json::from_str(&content).ok()?;
+    let main = manifest.get("main")?.as_str()?;
+    Some(Path::new(main).components().filter(|component| !matches!(component, std::path::Component::CurDir)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_detect_entry_point() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::write(root.join("util.c"), "int add(int a, int b) { return a + b; }\n").unwrap();
+        fs::write(root.join("tool.c"), "#include <stdio.h>\nint main(void) { return 0; }\n").unwrap();
+        fs::write(root.join("helpers.py"), "def run(): pass\n").unwrap();
+        let mut sources = HashMap::new();
+        sources.insert(Language::C, vec![root.join("util.c"), root.join("tool.c")]);
+        sources.insert(Language::Python, vec![root.join("helpers.py")]);
+
+        let entry = detect(root, &sources).unwrap();
+        assert_eq!(entry, EntryPoint { file: PathBuf::from("tool.c"), language: Language::C });
+
+        fs::write(root.join("cli.py"), "import helpers\n\nif __name__ == '__main__':\n    helpers.run()\n").unwrap();
+        sources.get_mut(&Language::Python).unwrap().push(root.join("cli.py"));
+        assert_eq!(detect(root, &sources).unwrap().file, PathBuf::from("cli.py"));
+
+        fs::write(root.join("package.json"), r#"{"main": "./server.js"}"#).unwrap();
+        fs::write(root.join("server.js"), "").unwrap();
+        sources.insert(Language::JavaScript, vec![root.join("server.js")]);
+        assert_eq!(detect(root, &sources).unwrap().language, Language::JavaScript);
+
+        let chosen = from_override(root, Path::new("helpers.py"), &sources).unwrap();
+        assert_eq!(chosen.language, Language::Python);
+        assert!(from_override(root, Path::new("package.json"), &sources).is_err());
+    }
+}
@@ -0,0 +1,40 @@
+use std::path::PathBuf;
+use serde::Serialize;
+use tokio::sync::mpsc;
+
+/// Progress of a build, for consumers other than the terminal: the daemon
+/// API streams these to its clients.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum BuildEvent {
+    Queued,
+    Started,
+    Stage { name: String },
+    LanguageStarted { language: String, files: usize },
+    FileCompiled { language: String, file: PathBuf, success: bool },
+    LanguageFinished {
+        language: String,
+        success: bool,
+        files: usize,
+        diagnostics: usize,
+        duration_ms: u64,
+        /// The compiler or interpreter used, when lol resolved one
+        #[serde(skip_serializing_if = "Option::is_none")]
+        toolchain: Option<String>,
+        /// Compiles repeated after transient failures
+        retries: u32,
+    },
+    Finished { success: bool, duration_ms: u64 },
+    /// Taken out of the daemon's queue before it ran
+    Cancelled,
+}
+
+pub type EventSender = mpsc::UnboundedSender<BuildEvent>;
+
+/// Sends `event` when someone is listening. A listener that went away does
+/// not affect the build.
+pub fn emit(events: Option<&EventSender>, event: BuildEvent) {
+    if let Some(events) = events {
+        let _ = events.send(event);
+    }
+}
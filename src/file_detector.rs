@@ -4,6 +4,8 @@ use anyhow::Result;
 use walkdir::WalkDir;
 use crate::language_support::{Language, LanguageSupport};
 use crate::args::Args;
+use crate::dir_config::DirConfigs;
+use crate::patterns::FileFilter;
 
 pub struct FileDetector {
     language_support: LanguageSupport,
@@ -20,35 +22,48 @@ impl FileDetector {
         &self,
         project_path: &Path,
         args: &Args,
-        _config: &crate::config::Config,
+        config: &crate::config::Config,
     ) -> Result<HashMap<Language, Vec<PathBuf>>> {
         let mut language_files: HashMap<Language, Vec<PathBuf>> = HashMap::new();
+        let output_dir = config.output_dir(project_path);
+        let generated_dir = config.generated_dir(project_path);
+        let mut dir_configs = DirConfigs::new(project_path);
+        let filter = FileFilter::new(config)?;
 
-        // Walk through the project directory recursively
+        // Walk through the project directory recursively, skipping our own build
+        // output apart from code generated from schemas
         for entry in WalkDir::new(project_path)
             .follow_links(true)
             .into_iter()
+            .filter_entry(|e| {
+                output_dir.as_ref().is_none_or(|dir| !e.path().starts_with(dir))
+                    || e.path().starts_with(&generated_dir)
+                    || generated_dir.starts_with(e.path())
+            })
             .filter_map(|e| e.ok())
         {
             let path = entry.path();
             
-            // Skip directories and hidden files
+            // Skip directories, hidden files and what the patterns leave out
             if path.is_dir() || self.is_hidden_file(path) {
                 continue;
             }
+            if filter.is_ignored(path.strip_prefix(project_path).unwrap_or(path)) || dir_configs.is_ignored(path)? {
+                continue;
+            }
 
             // Get file extension
             if let Some(extension) = path.extension() {
                 let ext_str = extension.to_string_lossy().to_lowercase();
                 
                 // Get language for this extension
-                if let Some(language) = self.language_support.get_language_by_extension(&ext_str) {
-                    // Check if this language should be compiled based on args
-                    if self.should_compile_language(language, args) {
+                if let Some(language) = self.resolve_language(path, &ext_str, config) {
+                    // Check if this language should be compiled based on args and config
+                    if config.is_language_enabled(language.config_key()) && self.should_compile_language(&language, args) {
                         // Add file to the appropriate language group
                         language_files
-                            .entry(language.clone())
-                            .or_insert_with(Vec::new)
+                            .entry(language)
+                            .or_default()
                             .push(path.canonicalize().unwrap_or_else(|_| path.to_path_buf()));
                     }
                 }
@@ -63,6 +78,48 @@ impl FileDetector {
         Ok(language_files)
     }
 
+    /// Picks the language for `path`: custom languages from the config come
+    /// first, then `extension_overrides`, then the built-in languages, telling
+    /// apart those that share an extension (`.v` is both V and Verilog) by
+    /// looking at the file contents.
+    fn resolve_language(&self, path: &Path, extension: &str, config: &crate::config::Config) -> Option<Language> {
+        if let Some(custom) = config.custom_language_for_extension(extension) {
+            return Some(Language::Custom(custom.name.clone()));
+        }
+
+        if let Some(key) = config.extension_overrides.get(extension) {
+            if config.get_custom_language(key).is_some() {
+                return Some(Language::Custom(key.clone()));
+            }
+            return Language::all().iter().find(|language| language.config_key() == key).cloned();
+        }
+
+        let default = self.language_support.get_language_by_extension(extension)?;
+        let alternatives = self.language_support.get_alternative_languages(extension);
+        if alternatives.is_empty() {
+            return Some(default.clone());
+        }
+
+        let content = std::fs::read_to_string(path).unwrap_or_default();
+        alternatives
+            .into_iter()
+            .find(|language| Self::looks_like(language, &content))
+            .or(Some(default))
+            .cloned()
+    }
+
+    fn looks_like(language: &Language, content: &str) -> bool {
+        match language {
+            Language::Verilog => {
+                let words: Vec<&str> = content
+                    .split(|c: char| !c.is_alphanumeric() && c != '_')
+                    .collect();
+                words.contains(&"module") && words.contains(&"endmodule")
+            }
+            _ => false,
+        }
+    }
+
     fn is_hidden_file(&self, path: &Path) -> bool {
         path.file_name()
             .and_then(|name| name.to_str())
@@ -77,13 +134,25 @@ impl FileDetector {
         }
 
         // Check if any specific language flags are set
-        let has_specific_flags = args.c || args.cpp || args.python || args.java || args.rust || args.go || args.js || args.ts;
+        let has_specific_flags = args.c
+            || args.cpp
+            || args.python
+            || args.java
+            || args.rust
+            || args.go
+            || args.js
+            || args.ts
+            || !args.languages.is_empty();
 
         // If no specific flags are set, compile all languages by default
         if !has_specific_flags {
             return true;
         }
 
+        if args.languages.iter().any(|name| name.eq_ignore_ascii_case(language.config_key())) {
+            return true;
+        }
+
         // Check specific language flags
         match language {
             Language::C => args.c,
@@ -111,6 +180,7 @@ mod tests {
     use super::*;
     use tempfile::TempDir;
     use std::fs;
+    use clap::Parser;
 
     #[test]
     fn test_detect_files() {
@@ -124,23 +194,7 @@ mod tests {
         fs::write(project_path.join(".hidden"), "hidden content").unwrap();
 
         let detector = FileDetector::new();
-        let args = Args {
-            project_path: project_path.to_path_buf(),
-            c: false,
-            cpp: false,
-            python: false,
-            java: false,
-            rust: false,
-            go: false,
-            js: false,
-            ts: false,
-            all: true,
-            verbose: false,
-            jobs: 1,
-            cflags: None,
-            cxxflags: None,
-            name: None,
-        };
+        let args = Args::parse_from(["lol", project_path.to_str().unwrap(), "--all"]);
 
         let files = detector.detect_files(project_path, &args, &crate::config::Config::default()).unwrap();
 
@@ -155,6 +209,67 @@ mod tests {
         }));
     }
 
+    #[test]
+    fn test_detect_files_skips_output_directory() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_path = temp_dir.path();
+
+        fs::write(project_path.join("fast.pyx"), "def f(): pass").unwrap();
+        fs::create_dir_all(project_path.join("build").join("cython")).unwrap();
+        fs::write(project_path.join("build").join("cython").join("fast.c"), "").unwrap();
+
+        let detector = FileDetector::new();
+        let args = Args::parse_from(["lol", project_path.to_str().unwrap()]);
+        let files = detector.detect_files(project_path, &args, &crate::config::Config::default()).unwrap();
+
+        assert!(files.contains_key(&Language::Cython));
+        assert!(!files.contains_key(&Language::Python));
+        assert!(!files.contains_key(&Language::C));
+    }
+
+    #[test]
+    fn test_v_files_are_disambiguated() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_path = temp_dir.path();
+
+        fs::write(project_path.join("counter.v"), "module counter(input clk);\nendmodule\n").unwrap();
+        fs::write(project_path.join("main.v"), "module main\n\nfn main() {\n\tprintln('hi')\n}\n").unwrap();
+
+        let detector = FileDetector::new();
+        let args = Args::parse_from(["lol", project_path.to_str().unwrap()]);
+        let mut config = crate::config::Config::default();
+        let files = detector.detect_files(project_path, &args, &config).unwrap();
+
+        assert_eq!(files[&Language::Verilog], vec![project_path.join("counter.v").canonicalize().unwrap()]);
+        assert_eq!(files[&Language::V], vec![project_path.join("main.v").canonicalize().unwrap()]);
+
+        config.extension_overrides.insert("v".to_string(), "verilog".to_string());
+        let files = detector.detect_files(project_path, &args, &config).unwrap();
+        assert_eq!(files[&Language::Verilog].len(), 2);
+        assert!(!files.contains_key(&Language::V));
+    }
+
+    #[test]
+    fn test_detect_custom_language() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_path = temp_dir.path();
+        fs::write(project_path.join("schema.dhall"), "{ name = \"lol\" }").unwrap();
+
+        let mut config = crate::config::Config::default();
+        config.custom_languages.push(crate::config::CustomLanguageConfig {
+            name: "dhall".to_string(),
+            extensions: vec!["dhall".to_string()],
+            check: Some("dhall type --file {file}".to_string()),
+            ..Default::default()
+        });
+
+        let detector = FileDetector::new();
+        let args = Args::parse_from(["lol", project_path.to_str().unwrap()]);
+        let files = detector.detect_files(project_path, &args, &config).unwrap();
+
+        assert_eq!(files[&Language::Custom("dhall".to_string())].len(), 1);
+    }
+
     #[test]
     fn test_is_hidden_file() {
         let detector = FileDetector::new();
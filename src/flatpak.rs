@@ -0,0 +1,211 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use anyhow::{Context, Result};
+use serde_json::json;
+use crate::config::Config;
+use crate::language_support::Language;
+use crate::reproducible;
+
+/// The freedesktop runtime branch manifests target.
+const RUNTIME_VERSION: &str = "24.08";
+
+/// Permissions a windowed desktop app usually needs.
+const DEFAULT_FINISH_ARGS: &[&str] = &["--share=ipc", "--socket=fallback-x11", "--socket=wayland", "--device=dri"];
+
+/// The runtime and SDK an app is built against, plus SDK extensions whose
+/// interpreters get copied into the app because the runtime lacks them.
+#[derive(Debug, PartialEq)]
+pub struct Runtime {
+    pub runtime: &'static str,
+    pub sdk: &'static str,
+    pub sdk_extensions: Vec<&'static str>,
+    /// Commands that copy an extension's interpreter into `/app`
+    pub install_commands: Vec<&'static str>,
+    /// Directories those interpreters land in, added to `PATH`
+    pub paths: Vec<&'static str>,
+}
+
+/// Picks the runtime for the languages the launchers need. The freedesktop
+/// runtime ships Python and Perl; Node.js and Java come from SDK
+/// extensions. Other interpreters are not available and only warned about.
+pub fn runtime_for(languages: &[Language]) -> Runtime {
+    let mut runtime = Runtime {
+        runtime: "org.freedesktop.Platform",
+        sdk: "org.freedesktop.Sdk",
+        sdk_extensions: Vec::new(),
+        install_commands: Vec::new(),
+        paths: Vec::new(),
+    };
+    for language in languages {
+        match language {
+            Language::JavaScript | Language::TypeScript if !runtime.sdk_extensions.contains(&"org.freedesktop.Sdk.Extension.node20") => {
+                runtime.sdk_extensions.push("org.freedesktop.Sdk.Extension.node20");
+                runtime.install_commands.push("/usr/lib/sdk/node20/install.sh");
+                runtime.paths.push("/app/node/bin");
+            }
+            Language::Java | Language::Kotlin | Language::Scala if !runtime.sdk_extensions.contains(&"org.freedesktop.Sdk.Extension.openjdk21") => {
+                runtime.sdk_extensions.push("org.freedesktop.Sdk.Extension.openjdk21");
+                runtime.install_commands.push("/usr/lib/sdk/openjdk21/install.sh");
+                runtime.paths.push("/app/jre/bin");
+            }
+            Language::Ruby | Language::Lua | Language::R | Language::Julia => {
+                tracing::warn!(
+                    "The Flatpak runtime has no {} interpreter; bundle one in the manifest before building",
+                    language.name()
+                );
+            }
+            _ => {}
+        }
+    }
+    runtime
+}
+
+/// `package.app_id`, or when it is not set `io.github.<owner>.<name>` for
+/// a project whose `origin` remote is on GitHub, and `io.lol.<name>` for
+/// any other. Flatpak and AppStream share this reverse-DNS form.
+pub fn app_id(config: &Config, project_path: &Path, package_name: &str) -> String {
+    if let Some(app_id) = &config.package.app_id {
+        return app_id.clone();
+    }
+    let origin = Command::new("git")
+        .args(["remote", "get-url", "origin"])
+        .current_dir(project_path)
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string());
+    match origin.as_deref().and_then(github_owner) {
+        Some(owner) => format!("io.github.{}.{}", id_component(owner), id_component(package_name)),
+        None => format!("io.lol.{}", id_component(package_name)),
+    }
+}
+
+/// The owner in a GitHub remote URL, over HTTPS or SSH.
+fn github_owner(url: &str) -> Option<&str> {
+    let path = ["https://github.com/", "ssh://git@github.com/", "git@github.com:"]
+        .iter()
+        .find_map(|prefix| url.strip_prefix(prefix))?;
+    path.split('/').next().filter(|owner| !owner.is_empty())
+}
+
+/// `text` as one part of a reverse-DNS ID: letters, digits and
+/// underscores, not starting with a digit.
+pub fn id_component(text: &str) -> String {
+    let component: String = text.chars().map(|c| if c.is_ascii_alphanumeric() || c == '_' { c } else { '_' }).collect();
+    if component.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("_{}", component)
+    } else {
+        component
+    }
+}
+
+/// A flatpak-builder manifest with one module that copies the staged
+/// tree at `staging_dir` (relative to the manifest) into `/app`.
+pub fn manifest(
+    app_id: &str,
+    command: &str,
+    runtime: &Runtime,
+    staging_dir: &Path,
+    config: &Config,
+) -> serde_json::Value {
+    let mut finish_args: Vec<String> = match &config.package.finish_args {
+        Some(args) => args.clone(),
+        None => DEFAULT_FINISH_ARGS.iter().map(|arg| arg.to_string()).collect(),
+    };
+    if !runtime.paths.is_empty() {
+        finish_args.push(format!("--env=PATH={}:/app/bin:/usr/bin", runtime.paths.join(":")));
+    }
+
+    let mut build_commands: Vec<String> = runtime.install_commands.iter().map(|command| command.to_string()).collect();
+    build_commands.push("cp -a . /app/".to_string());
+
+    let mut manifest = json!({
+        "app-id": app_id,
+        "runtime": runtime.runtime,
+        "runtime-version": RUNTIME_VERSION,
+        "sdk": runtime.sdk,
+        "command": command,
+        "finish-args": finish_args,
+        "modules": [{
+            "name": app_id.rsplit('.').next().unwrap_or(app_id),
+            "buildsystem": "simple",
+            "build-commands": build_commands,
+            "sources": [{ "type": "dir", "path": staging_dir }],
+        }],
+    });
+    if !runtime.sdk_extensions.is_empty() {
+        manifest["sdk-extensions"] = json!(runtime.sdk_extensions);
+    }
+    manifest
+}
+
+/// Builds the manifest with `flatpak-builder` into a local repository and
+/// exports it as a single-file bundle. Returns `None` when flatpak-builder
+/// is not installed, leaving the manifest for the user to build.
+pub fn build(manifest_path: &Path, dist_dir: &Path, app_id: &str, bundle_path: &Path) -> Result<Option<PathBuf>> {
+    if Command::new("flatpak-builder").arg("--version").output().is_err() {
+        tracing::warn!(
+            "flatpak-builder is not installed; build {} with it to get a bundle",
+            manifest_path.display()
+        );
+        return Ok(None);
+    }
+
+    let build_dir = dist_dir.join("flatpak-build");
+    let repo_dir = dist_dir.join("flatpak-repo");
+    let status = reproducible::apply(&mut Command::new("flatpak-builder"))
+        .args(["--force-clean", "--user", "--install-deps-from=flathub"])
+        .arg(format!("--state-dir={}", dist_dir.join(".flatpak-builder").display()))
+        .arg(format!("--repo={}", repo_dir.display()))
+        .arg(&build_dir)
+        .arg(manifest_path)
+        .status()
+        .context("Failed to run flatpak-builder")?;
+    if !status.success() {
+        anyhow::bail!("flatpak-builder failed for {}", manifest_path.display());
+    }
+
+    let status = reproducible::apply(&mut Command::new("flatpak"))
+        .arg("build-bundle")
+        .arg(&repo_dir)
+        .arg(bundle_path)
+        .arg(app_id)
+        .status()
+        .context("Exporting the bundle needs flatpak on the PATH")?;
+    if !status.success() {
+        anyhow::bail!("flatpak build-bundle failed for {}", app_id);
+    }
+    let _ = fs::remove_dir_all(&build_dir);
+    Ok(Some(bundle_path.to_path_buf()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest_picks_runtime_per_language() {
+        let config = Config::default();
+        let runtime = runtime_for(&[Language::Python, Language::JavaScript, Language::TypeScript]);
+        assert_eq!(runtime.sdk_extensions, ["org.freedesktop.Sdk.Extension.node20"]);
+        assert!(runtime_for(&[Language::Python, Language::Rust]).sdk_extensions.is_empty());
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let manifest = manifest(&app_id(&config, temp_dir.path(), "my-app"), "my-app", &runtime, Path::new("my-app-1.0.0"), &config);
+        assert_eq!(manifest["app-id"], "io.lol.my_app");
+        assert_eq!(manifest["runtime"], "org.freedesktop.Platform");
+        assert_eq!(manifest["sdk-extensions"][0], "org.freedesktop.Sdk.Extension.node20");
+        assert_eq!(manifest["finish-args"][4], "--env=PATH=/app/node/bin:/app/bin:/usr/bin");
+        let module = &manifest["modules"][0];
+        assert_eq!(module["build-commands"], json!(["/usr/lib/sdk/node20/install.sh", "cp -a . /app/"]));
+        assert_eq!(module["sources"][0]["path"], "my-app-1.0.0");
+
+        for url in ["https://github.com/some-user/my-app.git", "git@github.com:some-user/my-app.git"] {
+            assert_eq!(github_owner(url), Some("some-user"));
+        }
+        assert_eq!(github_owner("https://gitlab.com/some-user/my-app.git"), None);
+        assert_eq!(id_component("some-user"), "some_user");
+        assert_eq!(id_component("2048"), "_2048");
+    }
+}
@@ -0,0 +1,296 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use anyhow::{Context, Result};
+use colored::*;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use crate::build_info;
+use crate::compiler::{BuildSummary, CompilationStatus};
+use crate::config::{project_config_path, read_config_file, write_config_file};
+use crate::diagnostics::Severity;
+use crate::sbom::format_rfc3339;
+
+/// Past builds of a project with `history` on, one JSON object per line.
+pub const HISTORY_FILE: &str = ".lol-history.jsonl";
+
+/// One recorded build.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+    pub id: u64,
+    /// Seconds since the Unix epoch when the build finished
+    pub timestamp: u64,
+    pub duration_ms: u64,
+    pub success: bool,
+    /// `HEAD` at the time, when the project is a git checkout
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+    pub languages: Vec<LanguageRecord>,
+}
+
+/// How one language went in a recorded build.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LanguageRecord {
+    pub language: String,
+    pub compiled: usize,
+    pub failed: usize,
+    /// The files that failed, relative to the project
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub failed_files: Vec<String>,
+    pub errors: usize,
+    pub warnings: usize,
+    pub duration_ms: u64,
+    #[serde(default)]
+    pub retries: u32,
+    /// The compiler that was not installed, when the language was skipped
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub missing_toolchain: Option<String>,
+}
+
+impl LanguageRecord {
+    fn new(result: &crate::compiler::CompilationResult, summary: &BuildSummary, project_path: &Path) -> Self {
+        let count = |severity| result.diagnostics.iter().filter(|d| d.severity == severity).count();
+        let failed_files = summary.failed_files.get(&result.language).map(Vec::as_slice).unwrap_or_default();
+        let root = project_path.canonicalize().unwrap_or_else(|_| project_path.to_path_buf());
+        Self {
+            language: result.language.name().to_string(),
+            compiled: result.files.len(),
+            failed: failed_files.len(),
+            failed_files: failed_files
+                .iter()
+                .map(|file| file.strip_prefix(&root).or_else(|_| file.strip_prefix(project_path)).unwrap_or(file).to_string_lossy().into_owned())
+                .collect(),
+            errors: count(Severity::Error),
+            warnings: count(Severity::Warning),
+            duration_ms: result.duration_ms,
+            retries: result.retries,
+            missing_toolchain: match &result.status {
+                CompilationStatus::ToolchainMissing { program, .. } => Some(program.clone()),
+                _ => None,
+            },
+        }
+    }
+}
+
+/// The recorded builds, oldest first. Lines that do not parse, say from a
+/// build killed mid-write, are skipped.
+pub fn load(project_path: &Path) -> Result<Vec<Session>> {
+    let path = project_path.join(HISTORY_FILE);
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(error) => return Err(error).with_context(|| format!("Failed to read {}", path.display())),
+    };
+    Ok(contents.lines().filter_map(|line| serde_json::from_str(line).ok()).collect())
+}
+
+/// Appends the build `summary` describes to the project's history.
+pub fn record(project_path: &Path, summary: &BuildSummary, success: bool, duration: Duration) -> Result<Session> {
+    let id = load(project_path)?.last().map_or(1, |session| session.id + 1);
+    let session = Session {
+        id,
+        timestamp: SystemTime::now().duration_since(UNIX_EPOCH).map(|now| now.as_secs()).unwrap_or(0),
+        duration_ms: duration.as_millis() as u64,
+        success,
+        commit: build_info::git_commit(project_path, "HEAD"),
+        languages: summary.results.iter().map(|result| LanguageRecord::new(result, summary, project_path)).collect(),
+    };
+    let path = project_path.join(HISTORY_FILE);
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    writeln!(file, "{}", serde_json::to_string(&session)?).with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(session)
+}
+
+/// How `lol analytics export` writes the history.
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// One row per language of each build
+    #[default]
+    Csv,
+    /// The recorded builds as a JSON array
+    Json,
+}
+
+/// Columns of the CSV export, in order.
+const CSV_COLUMNS: &[&str] = &[
+    "id", "timestamp", "commit", "success", "duration_ms", "language", "compiled", "failed", "errors", "warnings",
+    "language_duration_ms", "retries", "missing_toolchain",
+];
+
+/// A CSV field, quoted when it has to be.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// `sessions` in `format`. A build without languages still gets a CSV row,
+/// with the language columns empty.
+pub fn export(sessions: &[Session], format: ExportFormat) -> Result<String> {
+    if format == ExportFormat::Json {
+        return Ok(serde_json::to_string_pretty(sessions)? + "\n");
+    }
+    let mut csv = CSV_COLUMNS.join(",") + "\n";
+    for session in sessions {
+        let build = [
+            session.id.to_string(),
+            format_rfc3339(session.timestamp),
+            session.commit.clone().unwrap_or_default(),
+            session.success.to_string(),
+            session.duration_ms.to_string(),
+        ];
+        let languages: Vec<[String; 8]> = session
+            .languages
+            .iter()
+            .map(|record| {
+                [
+                    record.language.clone(),
+                    record.compiled.to_string(),
+                    record.failed.to_string(),
+                    record.errors.to_string(),
+                    record.warnings.to_string(),
+                    record.duration_ms.to_string(),
+                    record.retries.to_string(),
+                    record.missing_toolchain.clone().unwrap_or_default(),
+                ]
+            })
+            .collect();
+        for language in languages.iter().map(|fields| fields.as_slice()).chain(languages.is_empty().then_some(&[][..])) {
+            let mut row: Vec<String> = build.iter().chain(language).map(|value| csv_field(value)).collect();
+            row.resize(CSV_COLUMNS.len(), String::new());
+            csv.push_str(&row.join(","));
+            csv.push('\n');
+        }
+    }
+    Ok(csv)
+}
+
+/// Turns recording on or off for the project, in its `lol.json` (or
+/// `lol.toml`, `lol.yaml`), leaving the rest of the settings as they are.
+pub fn set_recording(project_path: &Path, enabled: bool) -> Result<()> {
+    let path = project_config_path(project_path)?;
+    let mut config: Value = if path.is_file() { read_config_file(&path)? } else { json!({}) };
+    config
+        .as_object_mut()
+        .with_context(|| format!("{} is not a table of settings", path.display()))?
+        .insert("history".to_string(), Value::Bool(enabled));
+    write_config_file(&path, &config)
+}
+
+/// `lol history`: the last `limit` builds, newest first.
+pub fn list(project_path: &Path, limit: usize) -> Result<()> {
+    let sessions = load(project_path)?;
+    if sessions.is_empty() {
+        say!("{} No builds recorded; set \"history\": true in lol.json to record them", "ℹ️".blue());
+        return Ok(());
+    }
+    say!("{:>5}  {:<20}  {:>9}  {:<6}  Languages", "ID", "Finished", "Duration", "Result");
+    for session in sessions.iter().rev().take(limit) {
+        let languages: Vec<String> = session
+            .languages
+            .iter()
+            .map(|record| format!("{} {}", record.language, record.compiled + record.failed))
+            .collect();
+        let result = if session.success { "ok".green() } else { "FAIL".red() };
+        say!(
+            "{:>5}  {:<20}  {:>8.1}s  {:<6}  {}",
+            session.id,
+            format_rfc3339(session.timestamp),
+            session.duration_ms as f64 / 1000.0,
+            result,
+            languages.join(", ")
+        );
+    }
+    Ok(())
+}
+
+/// `lol history show <id>`: one build, language by language.
+pub fn show(project_path: &Path, id: u64) -> Result<()> {
+    let sessions = load(project_path)?;
+    let session = sessions
+        .iter()
+        .find(|session| session.id == id)
+        .with_context(|| format!("No build {} in {}", id, project_path.join(HISTORY_FILE).display()))?;
+    let result = if session.success { "succeeded".green() } else { "failed".red() };
+    say!("🧾 Build {} {} at {} in {:.1}s", session.id, result, format_rfc3339(session.timestamp), session.duration_ms as f64 / 1000.0);
+    for record in &session.languages {
+        if let Some(program) = &record.missing_toolchain {
+            say!("  ⏭️  {}: skipped, `{}` is not installed", record.language.bold(), program);
+            continue;
+        }
+        say!(
+            "  {} {}: {} compiled, {} failed, {} errors, {} warnings in {:.1}s",
+            if record.failed == 0 { "✅" } else { "❌" },
+            record.language.bold(),
+            record.compiled,
+            record.failed,
+            record.errors,
+            record.warnings,
+            record.duration_ms as f64 / 1000.0
+        );
+        if record.retries > 0 {
+            say!("     🔁 Retried {} times", record.retries);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use tempfile::TempDir;
+    use crate::compiler::CompilationResult;
+    use crate::language_support::Language;
+
+    #[test]
+    fn test_record_and_load_sessions() {
+        let temp_dir = TempDir::new().unwrap();
+        assert!(load(temp_dir.path()).unwrap().is_empty());
+
+        let mut summary = BuildSummary::default();
+        summary.results.push(CompilationResult {
+            language: Language::C,
+            files: vec![PathBuf::from("main.c")],
+            status: CompilationStatus::Failure { error: "boom".to_string() },
+            diagnostics: Vec::new(),
+            toolchain: None,
+            retries: 1,
+            duration_ms: 250,
+        });
+        summary.failed_files.insert(Language::C, vec![temp_dir.path().join("src/util.c")]);
+        let first = record(temp_dir.path(), &summary, false, Duration::from_millis(300)).unwrap();
+        // A torn line from an interrupted build does not hide the rest
+        let path = temp_dir.path().join(HISTORY_FILE);
+        fs::write(&path, fs::read_to_string(&path).unwrap() + "{\"id\": 2, \"timest\n").unwrap();
+        let second = record(temp_dir.path(), &BuildSummary::default(), true, Duration::ZERO).unwrap();
+
+        let sessions = load(temp_dir.path()).unwrap();
+        assert_eq!(sessions, [first, second]);
+        assert_eq!(sessions[1].id, 2);
+        let csv = export(&sessions, ExportFormat::Csv).unwrap();
+        let rows: Vec<&str> = csv.lines().collect();
+        assert_eq!(rows.len(), 3);
+        assert!(rows[1].ends_with(",false,300,C,1,1,0,0,250,1,"));
+        assert_eq!(rows[2].split(',').count(), CSV_COLUMNS.len());
+        assert_eq!(csv_field("C, \"fast\""), "\"C, \"\"fast\"\"\"");
+
+        let c = &sessions[0].languages[0];
+        assert_eq!((c.compiled, c.failed, c.retries, c.duration_ms), (1, 1, 1, 250));
+        assert_eq!(c.failed_files, [Path::new("src").join("util.c").to_string_lossy()]);
+
+        let config_path = temp_dir.path().join(crate::config::PROJECT_CONFIG_FILE);
+        fs::write(&config_path, r#"{"parallel_jobs": 2}"#).unwrap();
+        set_recording(temp_dir.path(), true).unwrap();
+        let written: Value = serde_json::from_str(&fs::read_to_string(&config_path).unwrap()).unwrap();
+        assert_eq!(written, json!({"parallel_jobs": 2, "history": true}));
+    }
+}
@@ -0,0 +1,147 @@
+use std::path::Path;
+use std::process::Command;
+use anyhow::{Context, Result};
+use colored::*;
+use crate::compiler::{CompilationResult, CompilationStatus};
+use crate::config::{Config, HooksConfig};
+use crate::reproducible;
+
+/// Which point of the build a hook runs at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookStage {
+    PreBuild,
+    PostBuild,
+    OnSuccess,
+    OnFailure,
+}
+
+impl HookStage {
+    pub fn name(&self) -> &'static str {
+        match self {
+            HookStage::PreBuild => "pre_build",
+            HookStage::PostBuild => "post_build",
+            HookStage::OnSuccess => "on_success",
+            HookStage::OnFailure => "on_failure",
+        }
+    }
+
+    fn commands<'a>(&self, hooks: &'a HooksConfig) -> &'a [String] {
+        match self {
+            HookStage::PreBuild => &hooks.pre_build,
+            HookStage::PostBuild => &hooks.post_build,
+            HookStage::OnSuccess => &hooks.on_success,
+            HookStage::OnFailure => &hooks.on_failure,
+        }
+    }
+}
+
+/// Runs the configured commands for `stage` through the shell, in
+/// `project_path`, stopping at the first one that fails.
+///
+/// Every hook sees `LOL_HOOK`, `LOL_PROJECT_DIR` and `LOL_OUTPUT_DIR`. Once
+/// compilation has finished, `LOL_STATUS` (`success`/`failure`),
+/// `LOL_FILES_COMPILED`, `LOL_LANGUAGES` and `LOL_FAILED_LANGUAGES` describe
+/// the results.
+pub fn run(stage: HookStage, config: &Config, project_path: &Path, results: Option<&[CompilationResult]>) -> Result<()> {
+    let commands = stage.commands(&config.hooks);
+    if commands.is_empty() {
+        return Ok(());
+    }
+
+    let env = environment(stage, config, project_path, results);
+    for command in commands {
+        say!("🪝 {} hook: {}", stage.name(), command.dimmed());
+        let status = shell(command)
+            .current_dir(project_path)
+            .envs(env.iter().cloned())
+            .status()
+            .with_context(|| format!("Failed to run {} hook '{}'", stage.name(), command))?;
+
+        if !status.success() {
+            anyhow::bail!("{} hook '{}' failed with {}", stage.name(), command, status);
+        }
+    }
+    Ok(())
+}
+
+fn environment(
+    stage: HookStage,
+    config: &Config,
+    project_path: &Path,
+    results: Option<&[CompilationResult]>,
+) -> Vec<(String, String)> {
+    let mut env = vec![
+        ("LOL_HOOK".to_string(), stage.name().to_string()),
+        ("LOL_PROJECT_DIR".to_string(), project_path.display().to_string()),
+    ];
+    if let Some(output_dir) = config.output_dir(project_path) {
+        env.push(("LOL_OUTPUT_DIR".to_string(), output_dir.display().to_string()));
+    }
+
+    if let Some(results) = results {
+        let failed: Vec<&str> = results
+            .iter()
+            .filter(|result| matches!(result.status, CompilationStatus::Failure { .. }))
+            .map(|result| result.language.name())
+            .collect();
+        let languages: Vec<&str> = results.iter().map(|result| result.language.name()).collect();
+        let compiled: usize = results.iter().map(|result| result.files.len()).sum();
+
+        env.push(("LOL_STATUS".to_string(), if failed.is_empty() { "success" } else { "failure" }.to_string()));
+        env.push(("LOL_FILES_COMPILED".to_string(), compiled.to_string()));
+        env.push(("LOL_LANGUAGES".to_string(), languages.join(",")));
+        env.push(("LOL_FAILED_LANGUAGES".to_string(), failed.join(",")));
+    }
+    env
+}
+
+pub fn shell(command: &str) -> Command {
+    let mut cmd = if cfg!(windows) {
+        let mut cmd = Command::new("cmd");
+        cmd.args(["/C", command]);
+        cmd
+    } else {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", command]);
+        cmd
+    };
+    reproducible::apply(&mut cmd);
+    cmd
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::language_support::Language;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_hooks_see_build_results() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = Config::default();
+        config.hooks.on_failure = vec!["echo \"$LOL_STATUS $LOL_FAILED_LANGUAGES\" > hook.txt".to_string()];
+        let results = vec![CompilationResult {
+            language: Language::C,
+            files: Vec::new(),
+            status: CompilationStatus::Failure { error: "boom".to_string() },
+            diagnostics: Vec::new(),
+            toolchain: None,
+            retries: 0,
+            duration_ms: 0,
+        }];
+
+        run(HookStage::OnFailure, &config, temp_dir.path(), Some(&results)).unwrap();
+        let written = std::fs::read_to_string(temp_dir.path().join("hook.txt")).unwrap();
+        assert_eq!(written.trim(), "failure C");
+    }
+
+    #[test]
+    fn test_failing_hook_stops_the_build() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = Config::default();
+        config.hooks.pre_build = vec!["exit 3".to_string(), "touch never".to_string()];
+
+        assert!(run(HookStage::PreBuild, &config, temp_dir.path(), None).is_err());
+        assert!(!temp_dir.path().join("never").exists());
+    }
+}
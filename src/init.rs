@@ -0,0 +1,193 @@
+use std::collections::BTreeMap;
+use std::io::{BufRead, Write};
+use std::path::Path;
+use anyhow::Result;
+use clap::Parser;
+use colored::*;
+use serde_json::{json, Map, Value};
+use crate::args::{Args, Profile};
+use crate::compiler::Compiler;
+use crate::config::{project_config_path, write_config_file, Config};
+use crate::file_detector::FileDetector;
+use crate::language_support::Language;
+
+/// Directories that usually hold dependencies, environments or tool
+/// caches rather than the project's own sources.
+const IGNORE_CANDIDATES: &[&str] = &[
+    ".venv", "venv", "env", "__pycache__", ".tox", ".mypy_cache", "vendor", "third_party", "out", "obj", "bin",
+    ".gradle", ".idea", ".vscode", "zig-cache", ".zig-cache", "zig-out", "_build", "deps", ".stack-work", "nimcache",
+];
+
+/// A language found in the project, with the toolchain that would build it.
+#[derive(Debug)]
+pub struct Detected {
+    pub language: Language,
+    pub files: usize,
+    pub toolchain: String,
+}
+
+/// What `lol init` writes into `lol.json`.
+#[derive(Debug, PartialEq)]
+pub struct Answers {
+    pub disabled: Vec<Language>,
+    pub extra_ignores: Vec<String>,
+    pub profile: Profile,
+}
+
+/// Languages with at least one source file in the project, most files first.
+pub fn detect(project_path: &Path, config: &Config) -> Result<Vec<Detected>> {
+    let args = Args::parse_from(["lol".as_ref(), project_path.as_os_str()]);
+    let sources = FileDetector::new().detect_files(project_path, &args, config)?;
+    let compiler = Compiler::new(config.clone(), 1);
+    let mut toolchains = compiler.get_compiler_info_for(sources.keys());
+    let mut detected: Vec<Detected> = sources
+        .into_iter()
+        .map(|(language, files)| {
+            let toolchain = toolchains.remove(&language).unwrap_or_default();
+            Detected { language, files: files.len(), toolchain }
+        })
+        .collect();
+    detected.sort_by(|a, b| b.files.cmp(&a.files).then_with(|| a.language.name().cmp(b.language.name())));
+    Ok(detected)
+}
+
+/// Ignore patterns for the dependency and cache directories the project
+/// has, leaving out ones the configuration already ignores.
+pub fn proposed_ignores(project_path: &Path, config: &Config) -> Vec<String> {
+    IGNORE_CANDIDATES
+        .iter()
+        .filter(|dir| project_path.join(dir).is_dir())
+        .map(|dir| format!("{}/", dir))
+        .filter(|pattern| !config.ignore_patterns.contains(pattern))
+        .collect()
+}
+
+/// The project configuration for `answers`: only the settings that differ
+/// from the global configuration, which `lol.json` is layered on.
+pub fn project_config(config: &Config, answers: &Answers) -> Value {
+    let mut settings = Map::new();
+    if !answers.extra_ignores.is_empty() {
+        let patterns: Vec<&String> = config.ignore_patterns.iter().chain(&answers.extra_ignores).collect();
+        settings.insert("ignore_patterns".to_string(), json!(patterns));
+    }
+    if !answers.disabled.is_empty() {
+        let languages: BTreeMap<&str, Value> =
+            answers.disabled.iter().map(|language| (language.config_key(), json!({ "enabled": false }))).collect();
+        settings.insert("language_settings".to_string(), json!(languages));
+    }
+    settings.insert("profile".to_string(), json!(answers.profile));
+    Value::Object(settings)
+}
+
+/// `lol init`: shows what was detected, asks what to build and writes
+/// `lol.json`. With `yes`, every detected language and proposed pattern is
+/// accepted without asking.
+pub fn run(project_path: &Path, config: &Config, yes: bool, force: bool, input: &mut impl BufRead) -> Result<()> {
+    let config_path = project_config_path(project_path)?;
+    if config_path.exists() && !force {
+        anyhow::bail!("{} already exists; pass --force to replace it", config_path.display());
+    }
+
+    let detected = detect(project_path, config)?;
+    if detected.is_empty() {
+        say!("{} No source files found in {}", "ℹ️".blue(), project_path.display());
+    } else {
+        say!("🔍 Detected languages:");
+        for found in &detected {
+            say!("  {:<14} {:>5} files   {}", found.language.name().bold(), found.files, found.toolchain.dimmed());
+        }
+        say!();
+    }
+
+    let mut disabled = Vec::new();
+    for found in &detected {
+        if !yes && !confirm(input, &format!("Build {}?", found.language.name()), true)? {
+            disabled.push(found.language.clone());
+        }
+    }
+
+    let mut extra_ignores = Vec::new();
+    for pattern in proposed_ignores(project_path, config) {
+        if yes || confirm(input, &format!("Ignore {}?", pattern), true)? {
+            extra_ignores.push(pattern);
+        }
+    }
+
+    let profile = if yes { Profile::Debug } else { ask_profile(input)? };
+    let answers = Answers { disabled, extra_ignores, profile };
+    write_config_file(&config_path, &project_config(config, &answers))?;
+    say!("📝 Wrote {}", config_path.display());
+    Ok(())
+}
+
+/// `lol init --global`: writes the default global configuration for
+/// editing, which lol otherwise never creates by itself.
+pub fn write_global(force: bool) -> Result<()> {
+    let path = Config::get_config_path()?;
+    if path.exists() && !force {
+        anyhow::bail!("{} already exists; pass --force to replace it", path.display());
+    }
+    Config::default().save()?;
+    say!("📝 Wrote {}", path.display());
+    Ok(())
+}
+
+fn prompt(input: &mut impl BufRead, question: &str) -> Result<String> {
+    print!("{} ", question);
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    input.read_line(&mut answer)?;
+    Ok(answer.trim().to_lowercase())
+}
+
+fn confirm(input: &mut impl BufRead, question: &str, default: bool) -> Result<bool> {
+    let hint = if default { "[Y/n]" } else { "[y/N]" };
+    loop {
+        match prompt(input, &format!("{} {}", question, hint))?.as_str() {
+            "" => return Ok(default),
+            "y" | "yes" => return Ok(true),
+            "n" | "no" => return Ok(false),
+            _ => say!("Please answer y or n"),
+        }
+    }
+}
+
+fn ask_profile(input: &mut impl BufRead) -> Result<Profile> {
+    loop {
+        match prompt(input, "Default profile, debug or release? [debug]")?.as_str() {
+            "" | "debug" => return Ok(Profile::Debug),
+            "release" => return Ok(Profile::Release),
+            _ => say!("Please answer debug or release"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_init_writes_answers() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::write(root.join("main.c"), "int main(void) { return 0; }\n").unwrap();
+        fs::write(root.join("tool.py"), "print('hi')\n").unwrap();
+        fs::create_dir(root.join(".venv")).unwrap();
+        fs::create_dir(root.join("node_modules")).unwrap();
+
+        let config = Config::default();
+        assert_eq!(proposed_ignores(root, &config), [".venv/"]);
+
+        // Both languages are detected, in name order as they have one file each
+        let mut input = "y\nn\n\nrelease\n".as_bytes();
+        run(root, &config, false, false, &mut input).unwrap();
+        let written: Value = serde_json::from_str(&fs::read_to_string(root.join(crate::config::PROJECT_CONFIG_FILE)).unwrap()).unwrap();
+        assert_eq!(written["language_settings"], json!({ "python": { "enabled": false } }));
+        assert_eq!(written["profile"], "release");
+        assert!(written["ignore_patterns"].as_array().unwrap().contains(&json!(".venv/")));
+
+        assert!(run(root, &config, true, false, &mut "".as_bytes()).is_err());
+    }
+}
@@ -1,13 +1,214 @@
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::process::Command;
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
+use crate::args::{Profile, Target};
+use crate::config::{CustomLanguageConfig, LanguageConfig};
+use crate::python_env::PYTHON;
+
+/// A program plus any leading arguments used to invoke a language tool,
+/// e.g. `python3` or `conda run -n science python`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Toolchain {
+    pub program: String,
+    pub args: Vec<String>,
+}
+
+impl Toolchain {
+    pub fn new(program: impl Into<String>) -> Self {
+        Self {
+            program: program.into(),
+            args: Vec::new(),
+        }
+    }
+
+    pub fn with_args<I, S>(program: impl Into<String>, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            program: program.into(),
+            args: args.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    pub fn command(&self) -> Command {
+        let mut cmd = Command::new(&self.program);
+        cmd.args(&self.args);
+        cmd
+    }
+}
+
+impl fmt::Display for Toolchain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.program)?;
+        for arg in &self.args {
+            write!(f, " {}", arg)?;
+        }
+        Ok(())
+    }
+}
+
+/// The default C and C++ compilers: Apple's clang on macOS, where `gcc` is
+/// only an alias for it, and GCC elsewhere.
+const C_COMPILER: &str = if cfg!(target_os = "macos") { "clang" } else { "gcc" };
+const CXX_COMPILER: &str = if cfg!(target_os = "macos") { "clang++" } else { "g++" };
+
+/// Parses a Julia file without running it and reports every syntax error.
+const JULIA_SYNTAX_CHECK: &str = "ex = Meta.parseall(read(ARGS[1], String); filename = ARGS[1]); \
+    errors = filter(e -> Meta.isexpr(e, (:error, :incomplete)), ex.args); \
+    foreach(e -> println(stderr, e.args[1]), errors); \
+    exit(isempty(errors) ? 0 : 1)";
+
+/// Settings resolved by the compiler for one language group before
+/// commands are built.
+#[derive(Debug, Clone, Default)]
+pub struct CompileOptions {
+    pub custom_flags: Option<String>,
+    /// Replaces the language's default program when set
+    pub toolchain: Option<Toolchain>,
+    /// Directories searched before `PATH` for auxiliary tools such as type checkers
+    pub tool_dirs: Vec<PathBuf>,
+    /// Where intermediate and output artifacts should be written
+    pub output_dir: Option<PathBuf>,
+    /// Project root whose directory layout per-file artifacts mirror under `output_dir`
+    pub project_root: Option<PathBuf>,
+    /// Keep every output under `output_dir`, including those that normally
+    /// land beside sources or in the working directory (`--build-dir`)
+    pub out_of_tree: bool,
+    /// Options for the C compiler used by languages that generate C (Cython)
+    pub c_options: Option<Box<CompileOptions>>,
+    /// Alternative backend for the language, e.g. `esbuild` for TypeScript
+    pub backend: Option<String>,
+    /// Project file driving a whole-project build, e.g. a GNAT `.gpr`
+    pub project_file: Option<PathBuf>,
+    /// Build profile selected with `--profile`
+    pub profile: Profile,
+    /// Cross-compilation target selected with `--target`
+    pub target: Option<Target>,
+    /// Environment variables set on every spawned tool
+    pub env: Vec<(String, String)>,
+    /// Project root rewritten to `.` in debug info and panic messages (`--reproducible`)
+    pub remap_prefix: Option<PathBuf>,
+    /// `NAME=VALUE` preprocessor definitions for C-family compilers (`--version-stamp`)
+    pub defines: Vec<String>,
+    /// The language's entry from `language_settings`
+    pub settings: LanguageConfig,
+    /// Definition of a language declared under `custom_languages`
+    pub custom: Option<CustomLanguageConfig>,
+}
+
+/// `flag` immediately followed by `path`, as in `-FUbuild/units`, without
+/// going through `str` so paths that are not UTF-8 survive.
+fn with_path(flag: &str, path: &Path) -> OsString {
+    let mut arg = OsString::from(flag);
+    arg.push(path);
+    arg
+}
+
+impl CompileOptions {
+    /// `-O`/`-OO` style flags for interpreters that support optimization levels.
+    fn optimization_flags(&self) -> Vec<OsString> {
+        match self.settings.optimization_level {
+            Some(level) if level > 0 => vec![format!("-{}", "O".repeat(level.min(2) as usize)).into()],
+            _ => Vec::new(),
+        }
+    }
+
+    /// `flag<root>=.` so embedded source paths do not depend on where the
+    /// project was checked out.
+    fn prefix_map_flags(&self, flag: &str) -> Vec<OsString> {
+        match &self.remap_prefix {
+            Some(root) => {
+                let mut arg = with_path(flag, root);
+                arg.push("=.");
+                vec![arg]
+            }
+            None => Vec::new(),
+        }
+    }
+
+    /// These options with `flags` added after the custom flags, for files
+    /// that `.lol.toml` files give flags of their own.
+    pub fn with_extra_flags(&self, flags: &[String]) -> CompileOptions {
+        let mut options = self.clone();
+        if !flags.is_empty() {
+            let custom_flags = options.custom_flags.get_or_insert_with(String::new);
+            for flag in flags {
+                if !custom_flags.is_empty() {
+                    custom_flags.push(' ');
+                }
+                custom_flags.push_str(flag);
+            }
+        }
+        options
+    }
+
+    /// `<output_dir>/<subdir>/<dirs>/<name>`, where `<dirs>` is where `file`
+    /// sits in the project, so `a/util.c` and `b/util.c` get their own artifacts.
+    fn mirrored_artifact(&self, output_dir: &Path, subdir: &str, file: &Path, name: &OsStr) -> PathBuf {
+        let source_dir = self
+            .project_root
+            .as_deref()
+            .and_then(|root| file.parent()?.strip_prefix(root).ok())
+            .unwrap_or(Path::new(""));
+        output_dir.join(subdir).join(source_dir).join(name)
+    }
+
+    /// Whether C and C++ go through MSVC's `cl.exe`, which takes `/` options.
+    fn uses_msvc(&self) -> bool {
+        self.toolchain.as_ref().is_some_and(|toolchain| crate::msvc::is_cl(&toolchain.program))
+    }
+
+    fn define_flags(&self) -> Vec<OsString> {
+        self.defines.iter().map(|define| format!("-D{}", define).into()).collect()
+    }
+
+    fn base_command(&self, default_program: &str) -> Command {
+        match &self.toolchain {
+            Some(toolchain) => toolchain.command(),
+            None => Command::new(self.resolve_tool(default_program)),
+        }
+    }
+
+    /// First `tool_dirs` entry containing `name`, otherwise `name` for a `PATH` lookup.
+    pub fn resolve_tool(&self, name: &str) -> OsString {
+        self.tool_dirs
+            .iter()
+            .map(|dir| dir.join(name))
+            .find(|candidate| candidate.is_file())
+            .map(PathBuf::into_os_string)
+            .unwrap_or_else(|| OsString::from(name))
+    }
+}
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Language {
     C,
     Cpp,
+    ObjectiveC,
+    ObjectiveCpp,
+    Cuda,
+    WebAssembly,
+    Pascal,
+    Verilog,
+    Vhdl,
+    Fortran,
+    Ada,
+    Julia,
+    Lua,
+    Ruby,
+    Perl,
+    R,
+    Elixir,
+    Erlang,
+    Crystal,
     Python,
+    Cython,
     Java,
     Rust,
     Go,
@@ -25,14 +226,71 @@ pub enum Language {
     V,
     Odin,
     Jai,
+    /// A language declared under `custom_languages` in the configuration
+    Custom(String),
+}
+
+/// Which pool of job slots a language's files wait for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lane {
+    /// Syntax checks, which start and finish in moments
+    Check,
+    /// Compiles that keep a core busy for a while
+    Compile,
 }
 
 impl Language {
-    pub fn name(&self) -> &'static str {
+    pub fn all() -> &'static [Language] {
+        &[
+            Language::C, Language::Cpp, Language::Python, Language::Cython, Language::Java,
+            Language::Rust, Language::Go, Language::CSharp, Language::Swift,
+            Language::Kotlin, Language::Scala, Language::Haskell, Language::FSharp,
+            Language::OCaml, Language::Nim, Language::Zig, Language::V,
+            Language::Odin, Language::Jai, Language::JavaScript, Language::TypeScript,
+            Language::Fortran,
+            Language::Ada,
+            Language::Julia,
+            Language::Lua,
+            Language::Ruby,
+            Language::Elixir,
+            Language::Erlang,
+            Language::Crystal,
+            Language::ObjectiveC,
+            Language::ObjectiveCpp,
+            Language::Cuda,
+            Language::WebAssembly,
+            Language::Pascal,
+            Language::Perl,
+            Language::R,
+            Language::Verilog,
+            Language::Vhdl,
+        ]
+    }
+
+    pub fn name(&self) -> &str {
         match self {
+            Language::Custom(name) => name,
             Language::C => "C",
+            Language::Elixir => "Elixir",
+            Language::Crystal => "Crystal",
+            Language::Erlang => "Erlang",
+            Language::R => "R",
+            Language::Perl => "Perl",
+            Language::Ruby => "Ruby",
+            Language::Lua => "Lua",
+            Language::Julia => "Julia",
+            Language::Ada => "Ada",
+            Language::Fortran => "Fortran",
+            Language::Vhdl => "VHDL",
+            Language::Verilog => "Verilog",
+            Language::Pascal => "Pascal",
+            Language::WebAssembly => "WebAssembly",
+            Language::Cuda => "CUDA",
+            Language::ObjectiveCpp => "Objective-C++",
+            Language::ObjectiveC => "Objective-C",
             Language::Cpp => "C++",
             Language::Python => "Python",
+            Language::Cython => "Cython",
             Language::Java => "Java",
             Language::Rust => "Rust",
             Language::Go => "Go",
@@ -53,11 +311,74 @@ impl Language {
         }
     }
 
+    /// Key used for this language in `compiler_flags` and `language_settings`.
+    pub fn config_key(&self) -> &str {
+        match self {
+            Language::Custom(name) => name,
+            Language::C => "c",
+            Language::Cpp => "cpp",
+            Language::ObjectiveC => "objc",
+            Language::ObjectiveCpp => "objcpp",
+            Language::Cuda => "cuda",
+            Language::WebAssembly => "wasm",
+            Language::Pascal => "pascal",
+            Language::Verilog => "verilog",
+            Language::Vhdl => "vhdl",
+            Language::Fortran => "fortran",
+            Language::Ada => "ada",
+            Language::Julia => "julia",
+            Language::Lua => "lua",
+            Language::Ruby => "ruby",
+            Language::Perl => "perl",
+            Language::R => "r",
+            Language::Elixir => "elixir",
+            Language::Erlang => "erlang",
+            Language::Crystal => "crystal",
+            Language::Python => "python",
+            Language::Cython => "cython",
+            Language::Java => "java",
+            Language::Rust => "rust",
+            Language::Go => "go",
+            Language::JavaScript => "javascript",
+            Language::TypeScript => "typescript",
+            Language::CSharp => "csharp",
+            Language::Swift => "swift",
+            Language::Kotlin => "kotlin",
+            Language::Scala => "scala",
+            Language::Haskell => "haskell",
+            Language::FSharp => "fsharp",
+            Language::OCaml => "ocaml",
+            Language::Nim => "nim",
+            Language::Zig => "zig",
+            Language::V => "v",
+            Language::Odin => "odin",
+            Language::Jai => "jai",
+        }
+    }
+
     pub fn extensions(&self) -> Vec<&'static str> {
         match self {
             Language::C => vec!["c", "h"],
             Language::Cpp => vec!["cpp", "cc", "cxx", "c++", "hpp", "hxx", "h++"],
-            Language::Python => vec!["py", "pyw", "pyx", "pxd"],
+            Language::ObjectiveC => vec!["m"],
+            Language::ObjectiveCpp => vec!["mm"],
+            Language::Cuda => vec!["cu", "cuh"],
+            Language::WebAssembly => vec!["wat", "wasm"],
+            Language::Pascal => vec!["pas", "pp", "dpr"],
+            Language::Verilog => vec!["sv", "svh", "v"],
+            Language::Vhdl => vec!["vhd", "vhdl"],
+            Language::Fortran => vec!["f", "f90", "f95", "f03"],
+            Language::Ada => vec!["adb", "ads"],
+            Language::Julia => vec!["jl"],
+            Language::Lua => vec!["lua"],
+            Language::Ruby => vec!["rb", "rake"],
+            Language::Perl => vec!["pl", "pm"],
+            Language::R => vec!["r"],
+            Language::Elixir => vec!["ex", "exs"],
+            Language::Erlang => vec!["erl", "hrl"],
+            Language::Crystal => vec!["cr"],
+            Language::Python => vec!["py", "pyw"],
+            Language::Cython => vec!["pyx", "pxd"],
             Language::Java => vec!["java"],
             Language::Rust => vec!["rs"],
             Language::Go => vec!["go"],
@@ -75,6 +396,8 @@ impl Language {
             Language::V => vec!["v"],
             Language::Odin => vec!["odin"],
             Language::Jai => vec!["jai"],
+            // Declared in the configuration, see `Config::custom_language_for_extension`
+            Language::Custom(_) => vec![],
         }
     }
 
@@ -83,8 +406,27 @@ impl Language {
             Language::C | Language::Cpp | Language::Java | Language::Rust | 
             Language::Go | Language::CSharp | Language::Swift | Language::Kotlin | 
             Language::Scala | Language::Haskell | Language::FSharp | Language::OCaml |
-            Language::Nim | Language::Zig | Language::V | Language::Odin | Language::Jai => true,
-            Language::Python | Language::JavaScript | Language::TypeScript => false,
+            Language::Nim | Language::Zig | Language::V | Language::Odin | Language::Jai |
+            Language::Cython |
+            Language::Fortran |
+            Language::Ada |
+            Language::Elixir |
+            Language::Erlang |
+            Language::Crystal |
+            Language::ObjectiveC |
+            Language::ObjectiveCpp |
+            Language::Cuda |
+            Language::WebAssembly |
+            Language::Pascal |
+            Language::Verilog |
+            Language::Vhdl |
+            Language::Custom(_) => true,
+            Language::Python | Language::JavaScript | Language::TypeScript |
+            Language::Julia |
+            Language::Lua |
+            Language::Ruby |
+            Language::Perl |
+            Language::R => false,
         }
     }
 
@@ -93,8 +435,27 @@ impl Language {
             Language::C | Language::Cpp | Language::Java | Language::Rust | 
             Language::Go | Language::CSharp | Language::Swift | Language::Kotlin | 
             Language::Scala | Language::Haskell | Language::FSharp | Language::OCaml |
-            Language::Nim | Language::Zig | Language::V | Language::Odin | Language::Jai => true,
-            Language::Python | Language::JavaScript | Language::TypeScript => false,
+            Language::Nim | Language::Zig | Language::V | Language::Odin | Language::Jai |
+            Language::Cython |
+            Language::Fortran |
+            Language::Ada |
+            Language::Elixir |
+            Language::Erlang |
+            Language::Crystal |
+            Language::ObjectiveC |
+            Language::ObjectiveCpp |
+            Language::Cuda |
+            Language::WebAssembly |
+            Language::Pascal |
+            Language::Verilog |
+            Language::Vhdl |
+            Language::Julia |
+            Language::Lua |
+            Language::Ruby |
+            Language::Perl |
+            Language::R => true,
+            Language::Python | Language::JavaScript | Language::TypeScript |
+            Language::Custom(_) => false,
         }
     }
 
@@ -112,8 +473,9 @@ impl Language {
 
     pub fn get_compiler_command(&self) -> (&'static str, Vec<&'static str>) {
         match self {
-            Language::C => ("gcc", vec!["--version"]),
-            Language::Cpp => ("g++", vec!["--version"]),
+            Language::C => (C_COMPILER, vec!["--version"]),
+            Language::Cpp => (CXX_COMPILER, vec!["--version"]),
+            Language::Cython => ("cython", vec!["--version"]),
             Language::Java => ("javac", vec!["-version"]),
             Language::Rust => ("rustc", vec!["--version"]),
             Language::Go => ("go", vec!["version"]),
@@ -129,123 +491,439 @@ impl Language {
             Language::V => ("v", vec!["version"]),
             Language::Odin => ("odin", vec!["version"]),
             Language::Jai => ("jai", vec!["--version"]),
+            Language::Fortran => ("gfortran", vec!["--version"]),
+            Language::Ada => ("gnatmake", vec!["--version"]),
+            Language::Elixir => ("elixir", vec!["--version"]),
+            Language::Erlang => ("erl", vec!["-version"]),
+            Language::Crystal => ("crystal", vec!["--version"]),
+            Language::ObjectiveC => ("clang", vec!["--version"]),
+            Language::ObjectiveCpp => ("clang", vec!["--version"]),
+            Language::Cuda => ("nvcc", vec!["--version"]),
+            Language::WebAssembly => ("wat2wasm", vec!["--version"]),
+            Language::Pascal => ("fpc", vec!["-iV"]),
+            Language::Verilog => ("verilator", vec!["--version"]),
+            Language::Vhdl => ("ghdl", vec!["--version"]),
+            Language::Julia => ("julia", vec!["--version"]),
+            Language::Lua => ("luac", vec!["-v"]),
+            Language::Ruby => ("ruby", vec!["--version"]),
+            Language::Perl => ("perl", vec!["--version"]),
+            Language::R => ("Rscript", vec!["--version"]),
             Language::Python | Language::JavaScript | Language::TypeScript => ("", vec![]),
+            // Probed with the definition's own `probe` command
+            Language::Custom(_) => ("", vec![]),
         }
     }
 
-    pub fn get_compilation_command(&self, file: &PathBuf, custom_flags: Option<&str>) -> Result<Command> {
+    pub fn get_compilation_command(&self, file: &Path, options: &CompileOptions) -> Result<Command> {
         let mut cmd;
-        let mut args: Vec<String> = Vec::new();
+        let mut args: Vec<OsString> = Vec::new();
 
         match self {
+            Language::C if options.target == Some(Target::Wasm32) => {
+                cmd = options.base_command("clang");
+                args.extend(["--target=wasm32", "-nostdlib", "-Wl,--no-entry", "-Wl,--export-all"].map(OsString::from));
+                args.extend(options.prefix_map_flags("-ffile-prefix-map="));
+                args.extend(options.define_flags());
+                if let Some(flags) = &options.custom_flags {
+                    args.extend(flags.split_whitespace().map(OsString::from));
+                }
+                args.push("-o".into());
+                args.push(Self::wasm_output_file(file, options).into());
+                args.push(file.into());
+            }
+            Language::C | Language::Cpp if options.uses_msvc() => {
+                cmd = options.base_command("cl.exe");
+                args.extend(["/nologo", "/c"].map(OsString::from));
+                if self == &Language::Cpp {
+                    args.push("/EHsc".into());
+                }
+                args.extend(options.prefix_map_flags("/pathmap:"));
+                args.extend(options.defines.iter().map(|define| format!("/D{}", define).into()));
+                if let Some(flags) = &options.custom_flags {
+                    args.extend(flags.split_whitespace().map(OsString::from));
+                }
+                args.push(with_path("/Fo", &Self::object_file(file, options, "obj")));
+                args.push(file.into());
+            }
             Language::C => {
-                cmd = Command::new("gcc");
-                args.push("-c".to_string());
-                if let Some(flags) = custom_flags {
-                    args.extend(flags.split_whitespace().map(|s| s.to_string()));
+                cmd = options.base_command(C_COMPILER);
+                args.push("-c".into());
+                args.extend(options.prefix_map_flags("-ffile-prefix-map="));
+                args.extend(options.define_flags());
+                if let Some(flags) = &options.custom_flags {
+                    args.extend(flags.split_whitespace().map(OsString::from));
                 }
-                args.push("-o".to_string());
-                let output_file = file.with_extension("o");
-                let output_file_str = output_file.to_str().unwrap().to_string();
-                args.push(output_file_str);
-                args.push(file.to_str().unwrap().to_string());
+                args.push("-o".into());
+                let output_file = Self::object_file(file, options, "o");
+                args.push(output_file.into());
+                args.push(file.into());
             }
             Language::Cpp => {
-                cmd = Command::new("g++");
-                args.push("-c".to_string());
-                if let Some(flags) = custom_flags {
-                    args.extend(flags.split_whitespace().map(|s| s.to_string()));
+                cmd = options.base_command(CXX_COMPILER);
+                args.push("-c".into());
+                args.extend(options.prefix_map_flags("-ffile-prefix-map="));
+                args.extend(options.define_flags());
+                if let Some(flags) = &options.custom_flags {
+                    args.extend(flags.split_whitespace().map(OsString::from));
+                }
+                args.push("-o".into());
+                let output_file = Self::object_file(file, options, "o");
+                args.push(output_file.into());
+                args.push(file.into());
+            }
+            Language::Fortran => {
+                cmd = options.base_command("gfortran");
+                args.push("-c".into());
+                // `.f` is fixed-form (FORTRAN 77 layout); everything newer is free-form.
+                let fixed_form = file.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("f"));
+                args.push(if fixed_form { "-ffixed-form" } else { "-ffree-form" }.into());
+                if let Some(module_dir) = Self::fortran_module_dir(options) {
+                    args.push("-J".into());
+                    args.push(module_dir.into());
+                }
+                if let Some(flags) = &options.custom_flags {
+                    args.extend(flags.split_whitespace().map(OsString::from));
+                }
+                args.push("-o".into());
+                let output_file = Self::fortran_object_file(file, options);
+                args.push(output_file.into());
+                args.push(file.into());
+            }
+            Language::Ada => {
+                cmd = options.base_command("gnatmake");
+                args.push("-c".into());
+                if let Some(object_dir) = Self::ada_object_dir(options) {
+                    args.push("-D".into());
+                    args.push(object_dir.into());
+                }
+                if let Some(flags) = &options.custom_flags {
+                    args.extend(flags.split_whitespace().map(OsString::from));
+                }
+                args.push(file.into());
+            }
+            Language::Julia => {
+                cmd = options.base_command("julia");
+                args.push("--startup-file=no".into());
+                match options.settings.check_mode.as_deref() {
+                    // Loading the file compiles it, at the cost of running its top level
+                    Some("precompile") => {
+                        args.push("--compile=min".into());
+                        args.push("-e".into());
+                        args.push("include(ARGS[1])".into());
+                    }
+                    Some("syntax") | None => {
+                        args.push("-e".into());
+                        args.push(JULIA_SYNTAX_CHECK.into());
+                    }
+                    Some(mode) => bail!("Unknown Julia check_mode '{}' (expected 'syntax' or 'precompile')", mode),
+                }
+                args.push(file.into());
+            }
+            Language::Lua => {
+                let luajit = options.backend.as_deref() == Some("luajit");
+                cmd = options.base_command(if luajit { "luajit" } else { "luac" });
+                let bytecode = Self::lua_bytecode_file(file, options);
+                match (luajit, &bytecode) {
+                    (true, Some(bytecode)) => {
+                        args.push("-b".into());
+                        args.push(file.into());
+                        args.push(bytecode.into());
+                    }
+                    // luajit has no parse-only mode, so compile and throw the result away
+                    (true, None) => {
+                        args.push("-b".into());
+                        args.push(file.into());
+                        args.push(if cfg!(windows) { "NUL" } else { "/dev/null" }.into());
+                    }
+                    (false, Some(bytecode)) => {
+                        args.push("-o".into());
+                        args.push(bytecode.into());
+                        args.push(file.into());
+                    }
+                    (false, None) => {
+                        args.push("-p".into());
+                        args.push(file.into());
+                    }
+                }
+            }
+            Language::Ruby => {
+                cmd = options.base_command("ruby");
+                args.push("-c".into());
+                args.push(file.into());
+            }
+            Language::Elixir => {
+                cmd = options.base_command("elixirc");
+                if let Some(beam_dir) = Self::beam_dir(options) {
+                    args.push("-o".into());
+                    args.push(beam_dir.into());
+                }
+                args.push(file.into());
+            }
+            Language::Erlang => {
+                cmd = options.base_command("erlc");
+                if let Some(beam_dir) = Self::beam_dir(options) {
+                    args.push("-o".into());
+                    args.push(beam_dir.into());
+                }
+                if let Some(flags) = &options.custom_flags {
+                    args.extend(flags.split_whitespace().map(OsString::from));
+                }
+                args.push(file.into());
+            }
+            Language::Crystal => {
+                cmd = options.base_command("crystal");
+                args.push("build".into());
+                if options.profile == Profile::Release {
+                    args.push("--release".into());
+                }
+                if let Some(output_dir) = &options.output_dir {
+                    let binary = options.mirrored_artifact(output_dir, "crystal", file, &Self::executable_name(file));
+                    args.push("-o".into());
+                    args.push(binary.into());
+                }
+                args.push(file.into());
+            }
+            Language::ObjectiveC | Language::ObjectiveCpp => {
+                cmd = self.objc_compiler_command(options);
+                args.push("-c".into());
+                args.push("-o".into());
+                let output_file = Self::object_file(file, options, "o");
+                args.push(output_file.into());
+                args.push(file.into());
+            }
+            Language::Cuda => {
+                cmd = options.base_command("nvcc");
+                args.push("-c".into());
+                if let Some(arch) = &options.settings.arch {
+                    args.push(format!("-arch={}", arch).into());
+                }
+                // Host-side flags have to be forwarded to the host compiler
+                if let Some(flags) = &options.custom_flags {
+                    let host_flags: Vec<_> = flags.split_whitespace().collect();
+                    if !host_flags.is_empty() {
+                        args.push("-Xcompiler".into());
+                        args.push(host_flags.join(",").into());
+                    }
+                }
+                args.push("-o".into());
+                let output_file = Self::object_file(file, options, "o");
+                args.push(output_file.into());
+                args.push(file.into());
+            }
+            Language::Pascal => {
+                cmd = options.base_command("fpc");
+                if let Some(unit_dir) = Self::pascal_unit_dir(options) {
+                    // Write compiled units and programs to the output directory
+                    // and let later units find the ones already built there
+                    args.push(with_path("-FU", &unit_dir));
+                    args.push(with_path("-FE", &unit_dir));
+                    args.push(with_path("-Fu", &unit_dir));
+                }
+                if let Some(flags) = &options.custom_flags {
+                    args.extend(flags.split_whitespace().map(OsString::from));
+                }
+                args.push(file.into());
+            }
+            Language::Perl => {
+                cmd = options.base_command("perl");
+                args.push("-c".into());
+                args.push(file.into());
+            }
+            Language::R => {
+                cmd = options.base_command("Rscript");
+                args.push("-e".into());
+                args.push("invisible(parse(file = commandArgs(TRUE)[1]))".into());
+                args.push(file.into());
+            }
+            Language::Verilog if options.backend.as_deref() == Some("iverilog") => {
+                cmd = options.base_command("iverilog");
+                args.extend(["-g2012", "-t", "null"].map(OsString::from));
+                args.push(file.into());
+            }
+            Language::Verilog => {
+                cmd = options.base_command("verilator");
+                args.push("--lint-only".into());
+                args.push(file.into());
+            }
+            Language::Vhdl => {
+                cmd = options.base_command("ghdl");
+                args.push("-a".into());
+                if let Some(work_dir) = Self::vhdl_work_dir(options) {
+                    args.push(with_path("--workdir=", &work_dir));
                 }
-                args.push("-o".to_string());
-                let output_file = file.with_extension("o");
-                let output_file_str = output_file.to_str().unwrap().to_string();
-                args.push(output_file_str);
-                args.push(file.to_str().unwrap().to_string());
+                args.push(file.into());
             }
             Language::Python => {
-                cmd = Command::new("python3");
-                args.push("-m".to_string());
-                args.push("py_compile".to_string());
-                args.push(file.to_str().unwrap().to_string());
+                cmd = options.base_command(PYTHON);
+                args.extend(options.optimization_flags());
+                args.push("-m".into());
+                args.push("py_compile".into());
+                args.push(file.into());
+            }
+            Language::Cython => {
+                cmd = Command::new("cython");
+                args.push("-3".into());
+                args.push(file.into());
+                args.push("-o".into());
+                args.push(Self::cython_c_file(file, options).into());
             }
             Language::Java => {
-                cmd = Command::new("javac");
-                args.push(file.to_str().unwrap().to_string());
+                cmd = options.base_command("javac");
+                if let Some(classes_dir) = Self::classes_dir(options) {
+                    args.push("-d".into());
+                    args.push(classes_dir.into());
+                }
+                args.push(file.into());
+            }
+            Language::Rust if options.target == Some(Target::Wasm32) => {
+                cmd = options.base_command("rustc");
+                args.extend(options.prefix_map_flags("--remap-path-prefix="));
+                args.extend(["--target", "wasm32-unknown-unknown", "--crate-type", "cdylib", "-o"].map(OsString::from));
+                args.push(Self::wasm_output_file(file, options).into());
+                args.push(file.into());
             }
             Language::Rust => {
-                cmd = Command::new("rustc");
-                args.push(file.to_str().unwrap().to_string());
+                cmd = options.base_command("rustc");
+                args.extend(options.prefix_map_flags("--remap-path-prefix="));
+                if let Some(binary) = Self::out_of_tree_executable(file, options) {
+                    args.push("-o".into());
+                    args.push(binary.into());
+                }
+                args.push(file.into());
             }
             Language::Go => {
-                cmd = Command::new("go");
-                args.push("build".to_string());
-                args.push(file.to_str().unwrap().to_string());
+                cmd = options.base_command("go");
+                args.push("build".into());
+                if options.remap_prefix.is_some() {
+                    args.push("-trimpath".into());
+                }
+                if let Some(binary) = Self::out_of_tree_executable(file, options) {
+                    args.push("-o".into());
+                    args.push(binary.into());
+                }
+                args.push(file.into());
             }
             Language::CSharp => {
-                cmd = Command::new("dotnet");
-                args.push("build".to_string());
-                args.push(file.to_str().unwrap().to_string());
+                cmd = options.base_command("dotnet");
+                args.push("build".into());
+                args.push(file.into());
             }
             Language::Swift => {
-                cmd = Command::new("swiftc");
-                args.push(file.to_str().unwrap().to_string());
-            }
-            Language::Kotlin => {
-                cmd = Command::new("kotlinc");
-                args.push(file.to_str().unwrap().to_string());
+                cmd = options.base_command("swiftc");
+                if let Some(binary) = Self::out_of_tree_executable(file, options) {
+                    args.push("-o".into());
+                    args.push(binary.into());
+                }
+                args.push(file.into());
             }
-            Language::Scala => {
-                cmd = Command::new("scalac");
-                args.push(file.to_str().unwrap().to_string());
+            Language::Kotlin | Language::Scala => {
+                cmd = self.jvm_compiler_command(options);
+                args.push(file.into());
             }
             Language::Haskell => {
-                cmd = Command::new("ghc");
-                args.push("-c".to_string());
-                args.push(file.to_str().unwrap().to_string());
+                cmd = options.base_command("ghc");
+                args.push("-c".into());
+                if let Some(output_dir) = options.output_dir.as_ref().filter(|_| options.out_of_tree) {
+                    args.push("-outputdir".into());
+                    args.push(output_dir.join("haskell").into());
+                }
+                args.push(file.into());
             }
             Language::FSharp => {
-                cmd = Command::new("fsharpc");
-                args.push(file.to_str().unwrap().to_string());
+                cmd = options.base_command("fsharpc");
+                args.push(file.into());
             }
             Language::OCaml => {
-                cmd = Command::new("ocamlc");
-                args.push("-c".to_string());
-                args.push(file.to_str().unwrap().to_string());
+                cmd = options.base_command("ocamlc");
+                args.push("-c".into());
+                args.push(file.into());
             }
             Language::Nim => {
-                cmd = Command::new("nim");
-                args.push("compile".to_string());
-                args.push("--run".to_string());
-                args.push(file.to_str().unwrap().to_string());
+                cmd = options.base_command("nim");
+                args.push("compile".into());
+                args.push("--run".into());
+                if let Some(binary_dir) = Self::out_of_tree_executable(file, options).as_deref().and_then(Path::parent) {
+                    args.push(with_path("--outdir:", binary_dir));
+                }
+                args.push(file.into());
             }
             Language::Zig => {
-                cmd = Command::new("zig");
-                args.push("build-exe".to_string());
-                args.push(file.to_str().unwrap().to_string());
+                cmd = options.base_command("zig");
+                args.push("build-exe".into());
+                args.push(file.into());
+                if options.target == Some(Target::Wasm32) {
+                    args.extend(["-target", "wasm32-freestanding", "-fno-entry", "-rdynamic"].map(OsString::from));
+                    args.push(with_path("-femit-bin=", &Self::wasm_output_file(file, options)));
+                } else if let Some(binary) = Self::out_of_tree_executable(file, options) {
+                    args.push(with_path("-femit-bin=", &binary));
+                }
+            }
+            Language::WebAssembly => {
+                if file.extension().is_some_and(|ext| ext == "wasm") {
+                    cmd = options.base_command("wasm-validate");
+                    args.push(file.into());
+                } else {
+                    cmd = options.base_command("wat2wasm");
+                    args.push(file.into());
+                    args.push("-o".into());
+                    args.push(Self::wasm_output_file(file, options).into());
+                }
             }
             Language::V => {
-                cmd = Command::new("v");
-                args.push(file.to_str().unwrap().to_string());
+                cmd = options.base_command("v");
+                args.push(file.into());
             }
             Language::Odin => {
-                cmd = Command::new("odin");
-                args.push("build".to_string());
-                args.push(file.to_str().unwrap().to_string());
+                cmd = options.base_command("odin");
+                args.push("build".into());
+                args.push(file.into());
             }
             Language::Jai => {
-                cmd = Command::new("jai");
-                args.push(file.to_str().unwrap().to_string());
+                cmd = options.base_command("jai");
+                args.push(file.into());
+            }
+            Language::Custom(name) => {
+                let definition = options
+                    .custom
+                    .as_ref()
+                    .with_context(|| format!("No definition for custom language '{}'", name))?;
+                let template = definition
+                    .compile
+                    .as_deref()
+                    .or(definition.check.as_deref())
+                    .with_context(|| format!("Custom language '{}' has neither a check nor a compile command", name))?;
+                return Self::render_command_template(template, file, options);
+            }
+            Language::JavaScript | Language::TypeScript if options.backend.is_some() => {
+                // Transpile and bundle into the output directory
+                let output_file = Self::bundle_output_file(file, options);
+                match options.backend.as_deref() {
+                    Some("esbuild") => {
+                        cmd = Command::new(options.resolve_tool("esbuild"));
+                        args.push(file.into());
+                        args.push("--bundle".into());
+                        args.push(with_path("--outfile=", &output_file));
+                    }
+                    Some("swc") => {
+                        cmd = Command::new(options.resolve_tool("swc"));
+                        args.push(file.into());
+                        args.push("-o".into());
+                        args.push(output_file.into());
+                    }
+                    Some(backend) => bail!("Unsupported {} backend '{}'", self.name(), backend),
+                    None => unreachable!(),
+                }
             }
             Language::JavaScript | Language::TypeScript => {
                 // For JS/TS, we'll just do syntax checking
                 if self == &Language::TypeScript {
-                    cmd = Command::new("tsc");
-                    args.push("--noEmit".to_string());
-                    args.push(file.to_str().unwrap().to_string());
+                    cmd = options.base_command("tsc");
+                    args.push("--noEmit".into());
+                    args.push(file.into());
                 } else {
-                    cmd = Command::new("node");
-                    args.push("--check".to_string());
-                    args.push(file.to_str().unwrap().to_string());
+                    cmd = options.base_command("node");
+                    args.push("--check".into());
+                    args.push(file.into());
                 }
             }
         }
@@ -253,6 +931,526 @@ impl Language {
         cmd.args(args);
         Ok(cmd)
     }
+
+    /// Returns every command needed to build `file`, run in order.
+    ///
+    /// Most languages need a single invocation; Cython translates `.pyx` to C
+    /// and then compiles that C with the configured C toolchain. Cython `.pxd`
+    /// declaration files are only consumed by the `.pyx` that cimport them.
+    pub fn get_compilation_pipeline(&self, file: &Path, options: &CompileOptions) -> Result<Vec<Command>> {
+        match self {
+            Language::Cython => {
+                if file.extension().is_some_and(|ext| ext == "pxd") {
+                    return Ok(Vec::new());
+                }
+
+                let c_file = Self::cython_c_file(file, options);
+                if let Some(parent) = c_file.parent() {
+                    fs::create_dir_all(parent)
+                        .with_context(|| format!("Failed to create {}", parent.display()))?;
+                }
+
+                let c_options = options.c_options.as_deref().cloned().unwrap_or_default();
+                Ok(vec![
+                    self.get_compilation_command(file, options)?,
+                    Language::C.get_compilation_command(&c_file, &c_options)?,
+                ])
+            }
+            // gnatmake compiles a spec's body along with it, so only
+            // body-less specs need their own invocation
+            Language::Ada if file.extension().is_some_and(|ext| ext == "ads")
+                && file.with_extension("adb").exists() =>
+            {
+                Ok(Vec::new())
+            }
+            Language::Erlang if file.extension().is_some_and(|ext| ext == "hrl") => Ok(Vec::new()),
+            Language::Cuda if file.extension().is_some_and(|ext| ext == "cuh") => Ok(Vec::new()),
+            Language::Verilog if file.extension().is_some_and(|ext| ext == "svh") => Ok(Vec::new()),
+            Language::Elixir if file.extension().is_some_and(|ext| ext == "exs") => Ok(Vec::new()),
+            // A custom language may declare both a check and a compile step
+            Language::Custom(_) => {
+                let Some(definition) = &options.custom else {
+                    return Ok(vec![self.get_compilation_command(file, options)?]);
+                };
+                let mut pipeline = Vec::new();
+                if let (Some(check), Some(_)) = (&definition.check, &definition.compile) {
+                    pipeline.push(Self::render_command_template(check, file, options)?);
+                }
+                pipeline.push(self.get_compilation_command(file, options)?);
+                Ok(pipeline)
+            }
+            _ => Ok(vec![self.get_compilation_command(file, options)?]),
+        }
+    }
+
+    /// The file compiling `file` on its own writes, for languages whose
+    /// per-file output could be claimed by another source.
+    pub fn artifact_file(&self, file: &Path, options: &CompileOptions) -> Option<PathBuf> {
+        let has_extension = |extension: &str| file.extension().is_some_and(|ext| ext == extension);
+        match self {
+            Language::C | Language::Rust | Language::Zig if options.target == Some(Target::Wasm32) => {
+                Some(Self::wasm_output_file(file, options))
+            }
+            Language::WebAssembly if !has_extension("wasm") => Some(Self::wasm_output_file(file, options)),
+            Language::Rust | Language::Go | Language::Swift | Language::Nim | Language::Zig => {
+                Self::out_of_tree_executable(file, options)
+            }
+            Language::C | Language::Cpp if options.uses_msvc() => Some(Self::object_file(file, options, "obj")),
+            Language::Fortran => Some(Self::fortran_object_file(file, options)),
+            Language::C | Language::Cpp | Language::ObjectiveC | Language::ObjectiveCpp => {
+                Some(Self::object_file(file, options, "o"))
+            }
+            Language::Cuda if !has_extension("cuh") => Some(Self::object_file(file, options, "o")),
+            Language::Cython if !has_extension("pxd") => Some(Self::cython_c_file(file, options)),
+            Language::Lua => Self::lua_bytecode_file(file, options),
+            Language::Crystal if options.project_file.is_none() => {
+                let output_dir = options.output_dir.as_ref()?;
+                Some(options.mirrored_artifact(output_dir, "crystal", file, &Self::executable_name(file)))
+            }
+            Language::JavaScript | Language::TypeScript if options.backend.is_some() => {
+                Some(Self::bundle_output_file(file, options))
+            }
+            Language::Custom(_) => {
+                let definition = options.custom.as_ref()?;
+                let template = definition.compile.as_deref().or(definition.check.as_deref())?;
+                let output_dir = options.output_dir.as_ref()?;
+                template
+                    .contains("{output}")
+                    .then(|| options.mirrored_artifact(output_dir, &definition.name, file, file.file_stem().unwrap_or_default()))
+            }
+            _ => None,
+        }
+    }
+
+    /// The executable compiling `file` on its own links: where `--build-dir`
+    /// puts it, or else where the compiler does by default, beside the
+    /// source for Nim and in the working directory for the others.
+    pub fn executable_file(&self, file: &Path, options: &CompileOptions) -> Option<PathBuf> {
+        if options.target == Some(Target::Wasm32) {
+            return None;
+        }
+        let in_tree = match self {
+            Language::Rust | Language::Go | Language::Swift | Language::Zig => PathBuf::from(Self::executable_name(file)),
+            Language::Nim => file.with_file_name(Self::executable_name(file)),
+            Language::Crystal => return self.artifact_file(file, options),
+            _ => return None,
+        };
+        Some(Self::out_of_tree_executable(file, options).unwrap_or(in_tree))
+    }
+
+    /// Builds a single invocation that compiles every file of the group, for
+    /// languages configured to work that way.
+    pub fn get_batch_compilation_command(&self, files: &[PathBuf], options: &CompileOptions) -> Option<Command> {
+        match self {
+            // Each directory holding sources, without recursing: the ones below
+            // that hold sources are listed too, and ignored ones stay out
+            Language::Python if options.settings.compile_all => {
+                let mut cmd = options.base_command(PYTHON);
+                cmd.args(options.optimization_flags());
+                cmd.args(["-m", "compileall", "-q", "-l"]);
+                let mut dirs: Vec<&Path> = files.iter().filter_map(|file| file.parent()).collect();
+                dirs.sort();
+                dirs.dedup();
+                cmd.args(dirs);
+                Some(cmd)
+            }
+            // javac, kotlinc and scalac pay a JVM cold start per invocation
+            Language::Kotlin | Language::Scala if self.batches(options) => {
+                let mut cmd = self.jvm_compiler_command(options);
+                cmd.args(files);
+                Some(cmd)
+            }
+            Language::Java if self.batches(options) => {
+                let mut cmd = options.base_command("javac");
+                if let Some(classes_dir) = Self::classes_dir(options) {
+                    cmd.arg("-d").arg(classes_dir);
+                }
+                cmd.args(files);
+                Some(cmd)
+            }
+            Language::TypeScript if options.backend.is_none() && self.batches(options) => {
+                let mut cmd = options.base_command("tsc");
+                cmd.arg("--noEmit");
+                cmd.args(files);
+                Some(cmd)
+            }
+            // One assembly from every file, in order, the last holding the entry point
+            Language::FSharp if self.batches(options) => {
+                let mut cmd = options.base_command("fsharpc");
+                if let Some(output_dir) = &options.output_dir {
+                    cmd.arg(with_path("--out:", &output_dir.join(self.config_key()).join("app.exe")));
+                }
+                cmd.args(files);
+                Some(cmd)
+            }
+            Language::Ada if options.project_file.is_some() => {
+                let mut cmd = options.base_command("gprbuild");
+                cmd.arg("-p").arg("-P").arg(options.project_file.as_ref()?);
+                Some(cmd)
+            }
+            // mix builds the whole project, including every app of an umbrella
+            Language::Elixir | Language::Erlang if options.project_file.is_some() => {
+                let project_file = options.project_file.as_ref()?;
+                let mut cmd = Command::new(options.resolve_tool("mix"));
+                cmd.arg("compile");
+                if let Some(project_dir) = project_file.parent() {
+                    cmd.current_dir(project_dir);
+                }
+                Some(cmd)
+            }
+            // Modules depend on each other at compile time, so elixirc needs
+            // to see them together. Scripts (.exs) are run, not compiled.
+            Language::Elixir => {
+                let modules: Vec<_> = files
+                    .iter()
+                    .filter(|file| file.extension().is_some_and(|ext| ext == "ex"))
+                    .collect();
+                if modules.is_empty() {
+                    return None;
+                }
+
+                let mut cmd = options.base_command("elixirc");
+                if let Some(beam_dir) = Self::beam_dir(options) {
+                    cmd.arg("-o").arg(beam_dir);
+                }
+                cmd.args(modules);
+                Some(cmd)
+            }
+            Language::Crystal if options.project_file.is_some() => {
+                let project_file = options.project_file.as_ref()?;
+                let mut cmd = Command::new(options.resolve_tool("shards"));
+                cmd.arg("build");
+                if options.profile == Profile::Release {
+                    cmd.arg("--release");
+                }
+                if let Some(project_dir) = project_file.parent() {
+                    cmd.current_dir(project_dir);
+                }
+                Some(cmd)
+            }
+            // Frameworks only matter once something is linked, so link the group
+            Language::C | Language::Cpp | Language::ObjectiveC | Language::ObjectiveCpp
+                if !options.settings.frameworks.is_empty() && !options.uses_msvc() =>
+            {
+                let mut cmd = match self {
+                    Language::C | Language::Cpp => {
+                        let mut cmd = options.base_command(self.get_compiler_command().0);
+                        cmd.args(options.prefix_map_flags("-ffile-prefix-map="));
+                        cmd.args(options.define_flags());
+                        if let Some(flags) = &options.custom_flags {
+                            cmd.args(flags.split_whitespace());
+                        }
+                        cmd
+                    }
+                    _ => self.objc_compiler_command(options),
+                };
+                cmd.args(files);
+                for framework in &options.settings.frameworks {
+                    cmd.arg("-framework").arg(framework);
+                }
+                if let Some(output_dir) = &options.output_dir {
+                    cmd.arg("-o").arg(output_dir.join(self.config_key()).join(format!("app{}", std::env::consts::EXE_SUFFIX)));
+                }
+                Some(cmd)
+            }
+            Language::Pascal if options.project_file.is_some() => {
+                let mut cmd = Command::new(options.resolve_tool("lazbuild"));
+                cmd.arg(options.project_file.as_ref()?);
+                Some(cmd)
+            }
+            _ => None,
+        }
+    }
+
+    /// Checks of interpreted languages go in the check lane, unless a
+    /// backend or mode makes them compile; everything else is a compile.
+    /// A custom language is a check when it only has a `check` command.
+    pub fn lane(&self, options: &CompileOptions) -> Lane {
+        let check = match self {
+            Language::Python | Language::Lua | Language::Ruby | Language::Perl | Language::R => true,
+            Language::JavaScript => options.backend.is_none(),
+            Language::Julia => options.settings.check_mode.as_deref() != Some("precompile"),
+            Language::Custom(_) => options.custom.as_ref().is_some_and(|definition| definition.compile.is_none()),
+            _ => false,
+        };
+        if check { Lane::Check } else { Lane::Compile }
+    }
+
+    /// Whether the language's files are compiled in one invocation: the
+    /// `batch` setting, else on for the compilers with a slow start.
+    pub fn batches(&self, options: &CompileOptions) -> bool {
+        options.settings.batch.unwrap_or(matches!(self, Language::Java | Language::TypeScript | Language::Kotlin | Language::Scala))
+    }
+
+    /// The name of the program built from `file`: its stem, plus `.exe` on
+    /// Windows.
+    pub fn executable_name(file: &Path) -> OsString {
+        let mut name = file.file_stem().unwrap_or_default().to_os_string();
+        name.push(std::env::consts::EXE_SUFFIX);
+        name
+    }
+
+    /// Where `file`'s object file goes: beside it, or mirrored under
+    /// `<output_dir>/obj` in out-of-tree builds.
+    fn object_file(file: &Path, options: &CompileOptions, extension: &str) -> PathBuf {
+        let object_file = file.with_extension(extension);
+        match (&options.output_dir, object_file.file_name()) {
+            (Some(output_dir), Some(name)) if options.out_of_tree && !file.starts_with(output_dir) => {
+                options.mirrored_artifact(output_dir, "obj", file, name)
+            }
+            _ => object_file,
+        }
+    }
+
+    /// Where an out-of-tree build puts the program built from `file`, which
+    /// would otherwise land in the working directory or beside the source.
+    fn out_of_tree_executable(file: &Path, options: &CompileOptions) -> Option<PathBuf> {
+        let output_dir = options.output_dir.as_ref().filter(|_| options.out_of_tree)?;
+        Some(options.mirrored_artifact(output_dir, "bin", file, &Self::executable_name(file)))
+    }
+
+    /// clang invocation with the language forced by `-x` and ARC enabled,
+    /// since `.m` is ambiguous and `.mm` is not recognised by every driver.
+    fn objc_compiler_command(&self, options: &CompileOptions) -> Command {
+        let mut cmd = options.base_command("clang");
+        let language = match self {
+            Language::ObjectiveCpp => "objective-c++",
+            _ => "objective-c",
+        };
+        cmd.args(["-x", language, "-fobjc-arc"]);
+        cmd.args(options.prefix_map_flags("-ffile-prefix-map="));
+        cmd.args(options.define_flags());
+        if let Some(flags) = &options.custom_flags {
+            cmd.args(flags.split_whitespace());
+        }
+        cmd
+    }
+
+    /// Looks for a project file in `project_root` that describes how to build
+    /// the whole project, for languages whose build tools work that way.
+    pub fn find_project_file(&self, project_root: &Path) -> Option<PathBuf> {
+        let is_project_file: fn(&Path) -> bool = match self {
+            Language::Ada => |path| path.extension().is_some_and(|ext| ext == "gpr"),
+            Language::Elixir | Language::Erlang => |path| path.file_name().is_some_and(|name| name == "mix.exs"),
+            Language::Crystal => |path| path.file_name().is_some_and(|name| name == "shard.yml"),
+            Language::Pascal => |path| path.extension().is_some_and(|ext| ext == "lpi"),
+            _ => return None,
+        };
+
+        let mut candidates: Vec<PathBuf> = fs::read_dir(project_root)
+            .ok()?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.is_file() && is_project_file(path))
+            .collect();
+        candidates.sort();
+        candidates.into_iter().next()
+    }
+
+    /// Compiler invocation for Kotlin/Scala, writing classes to `<output>/classes`.
+    ///
+    /// With `daemon` enabled Scala goes through `fsc`, whose compile server
+    /// stays warm between runs. The Kotlin CLI compiler has no daemon mode.
+    fn jvm_compiler_command(&self, options: &CompileOptions) -> Command {
+        let mut cmd = match self {
+            Language::Scala if options.settings.daemon && options.toolchain.is_none() => {
+                Command::new(options.resolve_tool("fsc"))
+            }
+            Language::Scala => options.base_command("scalac"),
+            _ => options.base_command("kotlinc"),
+        };
+
+        if let Some(classes_dir) = Self::classes_dir(options) {
+            cmd.arg("-d").arg(classes_dir);
+        }
+        cmd
+    }
+
+    /// Expands a custom language's command template for `file`.
+    ///
+    /// The template is split on whitespace before substitution, so paths
+    /// containing spaces stay single arguments. A bare `{flags}` expands to
+    /// one argument per flag.
+    pub fn render_command_template(template: &str, file: &Path, options: &CompileOptions) -> Result<Command> {
+        let file_str = file.to_string_lossy();
+        let output = match (&options.output_dir, &options.custom, file.file_stem()) {
+            (Some(output_dir), Some(definition), Some(stem)) => options.mirrored_artifact(output_dir, &definition.name, file, stem),
+            _ => file.with_extension(""),
+        };
+        let output_str = output.to_string_lossy();
+        let flags = &options.settings.compiler_flags;
+
+        let mut words: Vec<OsString> = Vec::new();
+        for word in template.split_whitespace() {
+            match word {
+                "{flags}" => words.extend(flags.iter().map(OsString::from)),
+                // Whole-word paths are passed as they are, even when not UTF-8
+                "{file}" => words.push(file.into()),
+                "{output}" => words.push(output.clone().into()),
+                _ => words.push(
+                    word.replace("{file}", &file_str)
+                        .replace("{output}", &output_str)
+                        .replace("{flags}", &flags.join(" "))
+                        .into(),
+                ),
+            }
+        }
+
+        let Some((program, args)) = words.split_first() else {
+            bail!("Empty command template");
+        };
+        let mut cmd = Command::new(options.resolve_tool(&program.to_string_lossy()));
+        cmd.args(args);
+        Ok(cmd)
+    }
+
+    /// Where JVM languages put compiled classes.
+    pub fn classes_dir(options: &CompileOptions) -> Option<PathBuf> {
+        options.output_dir.as_ref().map(|dir| dir.join("classes"))
+    }
+
+    /// Where gfortran writes `.mod` files, and searches for them.
+    pub fn fortran_module_dir(options: &CompileOptions) -> Option<PathBuf> {
+        options.output_dir.as_ref().map(|dir| dir.join("fortran"))
+    }
+
+    /// Where gfortran writes the object for `file`: beside its `.mod` files,
+    /// mirroring the source tree, unless an out-of-tree build wants it in `obj`.
+    fn fortran_object_file(file: &Path, options: &CompileOptions) -> PathBuf {
+        let object_file = file.with_extension("o");
+        match (&options.output_dir, object_file.file_name()) {
+            (Some(output_dir), Some(name)) if !options.out_of_tree && !file.starts_with(output_dir) => {
+                options.mirrored_artifact(output_dir, "fortran", file, name)
+            }
+            _ => Self::object_file(file, options, "o"),
+        }
+    }
+
+    /// The order to compile Fortran `files` in, as indices into it: a file
+    /// that `use`s a module comes after the file defining it, since gfortran
+    /// needs the `.mod` to exist. Files keep their order otherwise, and a
+    /// cycle leaves the files in it where they were.
+    pub fn fortran_build_order(files: &[&Path]) -> Vec<usize> {
+        let module = regex::Regex::new(r"(?i)^\s*module\s+([a-z_][a-z0-9_]*)\s*$").unwrap();
+        let submodule = regex::Regex::new(r"(?i)^\s*submodule\s*\(\s*([a-z_][a-z0-9_]*)").unwrap();
+        let uses = regex::Regex::new(r"(?i)^\s*use(?:\s*,\s*non_intrinsic)?(?:\s*::\s*|\s+)([a-z_][a-z0-9_]*)").unwrap();
+
+        let mut defined_in = HashMap::new();
+        let mut needs: Vec<Vec<String>> = Vec::new();
+        for (index, file) in files.iter().enumerate() {
+            let fixed_form = file.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("f"));
+            let mut used = Vec::new();
+            for line in fs::read_to_string(file).unwrap_or_default().lines() {
+                if fixed_form && line.starts_with(['c', 'C', '*']) {
+                    continue;
+                }
+                let line = line.split('!').next().unwrap_or_default();
+                if let Some(name) = module.captures(line) {
+                    defined_in.entry(name[1].to_lowercase()).or_insert(index);
+                } else if let Some(name) = submodule.captures(line).or_else(|| uses.captures(line)) {
+                    used.push(name[1].to_lowercase());
+                }
+            }
+            needs.push(used);
+        }
+
+        let mut order = Vec::with_capacity(files.len());
+        let mut placed = vec![false; files.len()];
+        while order.len() < files.len() {
+            let ready = (0..files.len()).find(|&index| {
+                !placed[index]
+                    && needs[index]
+                        .iter()
+                        .filter_map(|name| defined_in.get(name))
+                        .all(|&dependency| dependency == index || placed[dependency])
+            });
+            // In a cycle nothing is ready; take the first file left
+            let next = ready.unwrap_or_else(|| placed.iter().position(|placed| !placed).unwrap_or_default());
+            placed[next] = true;
+            order.push(next);
+        }
+        order
+    }
+
+    /// Where gnatmake writes `.o`/`.ali` files when building without a project file.
+    pub fn ada_object_dir(options: &CompileOptions) -> Option<PathBuf> {
+        options.output_dir.as_ref().map(|dir| dir.join("ada"))
+    }
+
+    /// Where Lua bytecode goes when `output_format` is `bytecode`.
+    pub fn lua_bytecode_file(file: &Path, options: &CompileOptions) -> Option<PathBuf> {
+        if options.settings.output_format.as_deref() != Some("bytecode") {
+            return None;
+        }
+        let output_dir = options.output_dir.as_ref()?;
+        let name = file.with_extension("luac");
+        Some(options.mirrored_artifact(output_dir, "lua", file, name.file_name()?))
+    }
+
+    /// Where elixirc/erlc write `.beam` files outside of a mix project.
+    pub fn beam_dir(options: &CompileOptions) -> Option<PathBuf> {
+        options.output_dir.as_ref().map(|dir| dir.join("beam"))
+    }
+
+    /// Where WebAssembly modules are collected.
+    pub fn wasm_output_file(file: &Path, options: &CompileOptions) -> PathBuf {
+        let wasm_file = file.with_extension("wasm");
+        match (&options.output_dir, wasm_file.file_name()) {
+            (Some(output_dir), Some(name)) => options.mirrored_artifact(output_dir, "wasm", file, name),
+            _ => wasm_file,
+        }
+    }
+
+    /// Where fpc writes compiled units (`.ppu`/`.o`) and programs.
+    pub fn pascal_unit_dir(options: &CompileOptions) -> Option<PathBuf> {
+        options.output_dir.as_ref().map(|dir| dir.join("pascal"))
+    }
+
+    /// ghdl's library directory, shared by every analysed unit.
+    pub fn vhdl_work_dir(options: &CompileOptions) -> Option<PathBuf> {
+        options.output_dir.as_ref().map(|dir| dir.join("vhdl"))
+    }
+
+    fn bundle_output_file(file: &Path, options: &CompileOptions) -> PathBuf {
+        let output_dir = options.output_dir.clone().unwrap_or_else(|| match &options.project_root {
+            Some(root) => root.join("dist"),
+            None => PathBuf::from("dist"),
+        });
+        let name = file.with_extension("js");
+        options.mirrored_artifact(&output_dir, "js", file, name.file_name().unwrap_or_default())
+    }
+
+    fn cython_c_file(file: &Path, options: &CompileOptions) -> PathBuf {
+        let c_file = file.with_extension("c");
+        match (&options.output_dir, c_file.file_name()) {
+            (Some(output_dir), Some(name)) => options.mirrored_artifact(output_dir, "cython", file, name),
+            _ => c_file,
+        }
+    }
+
+    pub fn get_type_check_command(&self, checker: &str, files: &[&Path], options: &CompileOptions) -> Result<Command> {
+        let mut cmd = match (self, checker) {
+            (Language::Python, "mypy") => {
+                let mut cmd = Command::new(options.resolve_tool("mypy"));
+                cmd.args(["--show-column-numbers", "--no-color-output", "--no-error-summary"]);
+                cmd
+            }
+            (Language::Python, "pyright") => Command::new(options.resolve_tool("pyright")),
+            (Language::JavaScript | Language::TypeScript, "eslint") => {
+                let mut cmd = Command::new(options.resolve_tool("eslint"));
+                cmd.args(["--format", "unix"]);
+                cmd
+            }
+            (Language::TypeScript, "tsc") => {
+                let mut cmd = Command::new(options.resolve_tool("tsc"));
+                cmd.arg("--noEmit");
+                cmd
+            }
+            _ => bail!("Unsupported type checker '{}' for {}", checker, self.name()),
+        };
+
+        cmd.args(files);
+        Ok(cmd)
+    }
 }
 
 pub struct LanguageSupport {
@@ -263,15 +1461,11 @@ impl LanguageSupport {
     pub fn new() -> Self {
         let mut languages = HashMap::new();
         
-        for lang in [
-            Language::C, Language::Cpp, Language::Python, Language::Java,
-            Language::Rust, Language::Go, Language::CSharp, Language::Swift,
-            Language::Kotlin, Language::Scala, Language::Haskell, Language::FSharp,
-            Language::OCaml, Language::Nim, Language::Zig, Language::V,
-            Language::Odin, Language::Jai, Language::JavaScript, Language::TypeScript,
-        ] {
+        // The first language claiming an extension is its default; files
+        // with shared extensions are disambiguated by the file detector
+        for lang in Language::all() {
             for ext in lang.extensions() {
-                languages.insert(ext.to_string(), lang.clone());
+                languages.entry(ext.to_string()).or_insert_with(|| lang.clone());
             }
         }
         
@@ -282,12 +1476,13 @@ impl LanguageSupport {
         self.languages.get(extension)
     }
 
-    pub fn get_available_languages(&self) -> Vec<&Language> {
-        self.languages.values().collect::<Vec<_>>()
-    }
-
-    pub fn get_supported_extensions(&self) -> Vec<&String> {
-        self.languages.keys().collect::<Vec<_>>()
+    /// Languages registered for `extension` after the default one.
+    pub fn get_alternative_languages(&self, extension: &str) -> Vec<&'static Language> {
+        Language::all()
+            .iter()
+            .filter(|language| language.extensions().contains(&extension))
+            .skip(1)
+            .collect()
     }
 }
 
@@ -0,0 +1,127 @@
+use colored::*;
+use serde::Serialize;
+use crate::compiler::Compiler;
+use crate::config::Config;
+use crate::language_support::Language;
+
+/// One row of `lol languages`.
+#[derive(Debug, Serialize)]
+pub struct LanguageStatus {
+    pub language: String,
+    pub key: String,
+    pub extensions: Vec<&'static str>,
+    pub enabled: bool,
+    /// The program whose version is probed, or `None` when lol runs the
+    /// sources through an interpreter it does not check up front.
+    pub tool: Option<String>,
+    pub available: bool,
+    pub version: Option<String>,
+    pub flags: Vec<String>,
+}
+
+/// The status of every built-in language, probing each toolchain once.
+pub fn statuses(config: &Config) -> Vec<LanguageStatus> {
+    let compiler = Compiler::new(config.clone(), 1);
+    let mut versions = compiler.get_compiler_info_for(Language::all());
+    Language::all()
+        .iter()
+        .map(|language| {
+            let key = language.config_key();
+            let version = versions.remove(language).unwrap_or_default();
+            let available = version != "Not available";
+            let tool = language.needs_compiler_check().then(|| compiler.compiler_program(language));
+            LanguageStatus {
+                language: language.name().to_string(),
+                key: key.to_string(),
+                extensions: language.extensions(),
+                enabled: config.is_language_enabled(key),
+                version: (tool.is_some() && available).then_some(version),
+                tool,
+                available,
+                flags: flags(config, key),
+            }
+        })
+        .collect()
+}
+
+/// The flags configured for `key`: the language's `language_settings`
+/// flags, or its `compiler_flags` entry when those are empty.
+fn flags(config: &Config, key: &str) -> Vec<String> {
+    match config.get_language_config(key) {
+        Some(settings) if !settings.compiler_flags.is_empty() => settings.compiler_flags.clone(),
+        _ => config.get_compiler_flags(key).map(|flags| flags.split_whitespace().map(String::from).collect()).unwrap_or_default(),
+    }
+}
+
+pub fn print_table(statuses: &[LanguageStatus]) {
+    say!(
+        "  {:<13} {:<30} {:<10} {:<40} {}",
+        "Language".bold(),
+        "Extensions".bold(),
+        "Toolchain".bold(),
+        "Version".bold(),
+        "Flags".bold()
+    );
+    for status in statuses {
+        let mark = if status.available { "✅" } else { "❌" };
+        let tool = status.tool.as_deref().unwrap_or("built-in");
+        let version = match (&status.version, status.available) {
+            (Some(version), _) => version.as_str(),
+            (None, true) => "",
+            (None, false) => "not found",
+        };
+        let name = if status.enabled { status.language.normal() } else { status.language.dimmed() };
+        let extensions = status.extensions.iter().map(|ext| format!(".{}", ext)).collect::<Vec<_>>().join(" ");
+        say!(
+            "{} {:<13} {:<30} {:<10} {:<40} {}",
+            mark,
+            name,
+            extensions,
+            tool,
+            truncate(version, 40),
+            status.flags.join(" ").dimmed()
+        );
+    }
+}
+
+fn truncate(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        text.to_string()
+    } else {
+        text.chars().take(width - 1).chain(['…']).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::LanguageConfig;
+
+    #[test]
+    fn test_statuses_report_flags_and_builtins() {
+        let mut config = Config::default();
+        config.set_language_config("c", LanguageConfig {
+            compiler_flags: vec!["-O2".to_string()],
+            ..Default::default()
+        });
+        config.set_language_config("ruby", LanguageConfig { enabled: false, ..Default::default() });
+
+        let statuses = statuses(&config);
+        assert_eq!(statuses.len(), Language::all().len());
+
+        let c = statuses.iter().find(|status| status.key == "c").unwrap();
+        assert_eq!(c.flags, ["-O2"]);
+        let go = statuses.iter().find(|status| status.key == "go").unwrap();
+        assert_eq!(go.flags, ["-ldflags=-s", "-ldflags=-w"]);
+        assert_eq!(c.tool.as_deref(), Some(Language::C.get_compiler_command().0));
+
+        let ruby = statuses.iter().find(|status| status.key == "ruby").unwrap();
+        assert!(!ruby.enabled && ruby.tool.as_deref() == Some("ruby"));
+        let python = statuses.iter().find(|status| status.key == "python").unwrap();
+        assert!(python.available && python.tool.is_none() && python.version.is_none());
+
+        let json = serde_json::to_value(&statuses).unwrap();
+        assert_eq!(json[0]["extensions"], serde_json::json!(["c", "h"]));
+        assert_eq!(truncate("abcdef", 4), "abc…");
+    }
+}
@@ -0,0 +1,222 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use anyhow::{Context, Result};
+use regex::Regex;
+use crate::config::{CustomLanguageConfig, LicenseConfig};
+use crate::diagnostics::{Diagnostic, Severity};
+use crate::language_support::Language;
+
+/// How far into a file the header is looked for.
+const HEADER_SEARCH_LINES: usize = 30;
+
+/// How a language writes comments: a line prefix, plus a suffix for
+/// languages that only have block comments. Custom languages use the
+/// `comment` they declare, and have no style without one.
+fn comment_style(language: &Language, custom_languages: &[CustomLanguageConfig]) -> Option<(String, &'static str)> {
+    let (prefix, suffix) = match language {
+        Language::Custom(name) => {
+            let custom = custom_languages.iter().find(|custom| &custom.name == name)?;
+            return Some((format!("{} ", custom.comment.as_deref()?.trim_end()), ""));
+        }
+        Language::Python | Language::Cython | Language::Ruby | Language::Perl | Language::R
+        | Language::Elixir | Language::Julia | Language::Crystal | Language::Nim => ("# ", ""),
+        Language::Lua | Language::Ada | Language::Vhdl | Language::Haskell => ("-- ", ""),
+        Language::Erlang => ("% ", ""),
+        Language::Fortran => ("! ", ""),
+        Language::WebAssembly => (";; ", ""),
+        Language::OCaml | Language::FSharp => ("(* ", " *)"),
+        _ => ("// ", ""),
+    };
+    Some((prefix.to_string(), suffix))
+}
+
+/// The header template for `language`, with `{spdx}` filled in.
+fn template_for(config: &LicenseConfig, language: &Language) -> Option<String> {
+    let template = config.templates.get(language.config_key()).or(config.template.as_ref())?;
+    Some(template.replace("{spdx}", config.spdx.as_deref().unwrap_or("")))
+}
+
+/// Matches one template line, allowing any year (or year range) for `{year}`.
+fn line_pattern(line: &str) -> Regex {
+    let escaped = regex::escape(line.trim()).replace(r"\{year\}", r"\d{4}(?:\s*-\s*\d{4})?");
+    Regex::new(&format!("^{}$", escaped)).expect("escaped template is a valid regex")
+}
+
+/// A source line with comment markers, and `extra_prefix` if given, removed.
+fn strip_comment<'a>(line: &'a str, extra_prefix: Option<&str>) -> &'a str {
+    let mut line = line.trim();
+    for prefix in extra_prefix.into_iter().chain(["///", "//!", "//", "/*", "(*", "*", "#", "--", "%", ";;", "!"]) {
+        if let Some(rest) = line.strip_prefix(prefix) {
+            line = rest;
+            break;
+        }
+    }
+    for suffix in ["*/", "*)"] {
+        line = line.strip_suffix(suffix).unwrap_or(line);
+    }
+    line.trim()
+}
+
+/// Whether the first lines of `content` include every non-empty line of
+/// `template`, in any comment style or with `extra_prefix`.
+pub fn has_header(content: &str, template: &str, extra_prefix: Option<&str>) -> bool {
+    let head: Vec<&str> = content.lines().take(HEADER_SEARCH_LINES).map(|line| strip_comment(line, extra_prefix)).collect();
+    template
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .all(|line| {
+            let pattern = line_pattern(line);
+            head.iter().any(|candidate| pattern.is_match(candidate))
+        })
+}
+
+/// `content` with the header inserted in the comment style `(prefix,
+/// suffix)`, after any shebang or Python encoding declaration.
+pub fn insert_header(content: &str, template: &str, (prefix, suffix): (&str, &str), year: &str) -> String {
+    let mut header: String = template
+        .replace("{year}", year)
+        .lines()
+        .map(|line| format!("{}{}{}", prefix, line, suffix).trim_end().to_string() + "\n")
+        .collect();
+    header.push('\n');
+
+    // A PEP 263 encoding declaration only counts on the first two lines
+    static CODING: OnceLock<Regex> = OnceLock::new();
+    let coding = CODING.get_or_init(|| Regex::new(r"^[ \t\f]*#.*?coding[:=][ \t]*([-_.a-zA-Z0-9]+)").expect("valid regex"));
+    let lines: Vec<&str> = content.split_inclusive('\n').take(2).collect();
+    let preamble = match lines.iter().rposition(|line| coding.is_match(line)) {
+        Some(index) => index + 1,
+        None => usize::from(lines.first().is_some_and(|line| line.starts_with("#!"))),
+    };
+    let keep: usize = lines[..preamble].iter().map(|line| line.len()).sum();
+    format!("{}{}{}", &content[..keep], header, &content[keep..])
+}
+
+/// Checks every source file for its language's header, reporting a
+/// diagnostic for each file without one. With `fix`, missing headers are
+/// inserted and the files are reported as notes instead.
+pub fn check(
+    config: &LicenseConfig,
+    custom_languages: &[CustomLanguageConfig],
+    source_files: &HashMap<Language, Vec<PathBuf>>,
+    project_path: &Path,
+    fix: bool,
+    year: &str,
+) -> Result<Vec<Diagnostic>> {
+    let mut diagnostics = Vec::new();
+    let mut languages: Vec<&Language> = source_files.keys().collect();
+    languages.sort_by_key(|language| language.name().to_string());
+
+    for language in languages {
+        let Some(template) = template_for(config, language) else {
+            continue;
+        };
+        let style = comment_style(language, custom_languages);
+        let extra_prefix = match language {
+            Language::Custom(_) => style.as_ref().map(|(prefix, _)| prefix.trim()),
+            _ => None,
+        };
+        for file in &source_files[language] {
+            let relative = file.strip_prefix(project_path).unwrap_or(file);
+            if config.exclude.iter().any(|excluded| relative.starts_with(excluded)) {
+                continue;
+            }
+            // Binary inputs such as .wasm have no header to check
+            let Ok(content) = fs::read_to_string(file) else {
+                continue;
+            };
+            if has_header(&content, &template, extra_prefix) {
+                continue;
+            }
+
+            let (severity, message) = match &style {
+                Some((prefix, suffix)) if fix => {
+                    fs::write(file, insert_header(&content, &template, (prefix, suffix), year))
+                        .with_context(|| format!("Failed to write {}", file.display()))?;
+                    (Severity::Note, "license header added".to_string())
+                }
+                None if fix => (
+                    Severity::Error,
+                    format!("missing license header; set `comment` for custom language {} so lol can add it", language.config_key()),
+                ),
+                _ => (Severity::Error, format!("missing license header ({})", template.lines().next().unwrap_or_default().trim())),
+            };
+            diagnostics.push(Diagnostic {
+                file: relative.to_path_buf(),
+                line: Some(1),
+                column: None,
+                severity,
+                message,
+            });
+        }
+    }
+
+    Ok(diagnostics)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const TEMPLATE: &str = "SPDX-License-Identifier: {spdx}\nCopyright (c) {year} Acme Corp";
+
+    #[test]
+    fn test_has_header_accepts_any_comment_style_and_year() {
+        let template = TEMPLATE.replace("{spdx}", "MIT");
+        assert!(has_header("// SPDX-License-Identifier: MIT\n// Copyright (c) 2019-2024 Acme Corp\nint x;\n", &template, None));
+        assert!(has_header("#!/usr/bin/env python3\n# SPDX-License-Identifier: MIT\n# Copyright (c) 2024 Acme Corp\n", &template, None));
+        assert!(has_header("/*\n * SPDX-License-Identifier: MIT\n * Copyright (c) 2024 Acme Corp\n */\n", &template, None));
+        assert!(!has_header("// SPDX-License-Identifier: Apache-2.0\n// Copyright (c) 2024 Acme Corp\n", &template, None));
+        assert!(!has_header("// SPDX-License-Identifier: MIT\n", &template, None));
+    }
+
+    #[test]
+    fn test_fix_inserts_header_after_shebang() {
+        let temp_dir = TempDir::new().unwrap();
+        let script = temp_dir.path().join("tool.py");
+        let header = temp_dir.path().join("ok.c");
+        fs::write(&script, "#!/usr/bin/env python3\nprint('hi')\n").unwrap();
+        fs::write(&header, "// SPDX-License-Identifier: MIT\n// Copyright (c) 2020 Acme Corp\n").unwrap();
+
+        let config = LicenseConfig { spdx: Some("MIT".to_string()), template: Some(TEMPLATE.to_string()), ..LicenseConfig::default() };
+        let mut files = HashMap::new();
+        files.insert(Language::Python, vec![script.clone()]);
+        files.insert(Language::C, vec![header]);
+
+        let violations = check(&config, &[], &files, temp_dir.path(), false, "2024").unwrap();
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].to_string(), "tool.py:1: error: missing license header (SPDX-License-Identifier: MIT)");
+
+        let fixed = check(&config, &[], &files, temp_dir.path(), true, "2024").unwrap();
+        assert_eq!(fixed[0].severity, Severity::Note);
+        assert_eq!(
+            fs::read_to_string(&script).unwrap(),
+            "#!/usr/bin/env python3\n# SPDX-License-Identifier: MIT\n# Copyright (c) 2024 Acme Corp\n\nprint('hi')\n"
+        );
+        assert!(check(&config, &[], &files, temp_dir.path(), false, "2024").unwrap().is_empty());
+
+        // Only a PEP 263 declaration on the first two lines stays above the header
+        let style = ("# ", "");
+        assert_eq!(
+            insert_header("# Tool\n# -*- coding: latin-1 -*-\nx = 1\n", "H", style, "2024"),
+            "# Tool\n# -*- coding: latin-1 -*-\n# H\n\nx = 1\n"
+        );
+        assert_eq!(insert_header("print('coding: x')\n", "H", style, "2024"), "# H\n\nprint('coding: x')\n");
+        assert_eq!(insert_header("x = 1\ny = 2\n# coding=utf-8\n", "H", style, "2024"), "# H\n\nx = 1\ny = 2\n# coding=utf-8\n");
+
+        // Custom languages are commented with the prefix they declare
+        let query = temp_dir.path().join("report.sql");
+        fs::write(&query, "select 1;\n").unwrap();
+        let sql = CustomLanguageConfig { name: "sql".to_string(), comment: Some("--".to_string()), ..Default::default() };
+        let files = HashMap::from([(Language::Custom("sql".to_string()), vec![query.clone()])]);
+        assert!(check(&config, &[], &files, temp_dir.path(), true, "2024").unwrap()[0].severity == Severity::Error);
+        check(&config, std::slice::from_ref(&sql), &files, temp_dir.path(), true, "2024").unwrap();
+        assert!(fs::read_to_string(&query).unwrap().starts_with("-- SPDX-License-Identifier: MIT\n"));
+        let rem = CustomLanguageConfig { comment: Some("REM".to_string()), ..sql };
+        fs::write(&query, "REM SPDX-License-Identifier: MIT\nREM Copyright (c) 2024 Acme Corp\n").unwrap();
+        assert!(check(&config, &[rem], &files, temp_dir.path(), false, "2024").unwrap().is_empty());
+    }
+}
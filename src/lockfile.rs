@@ -0,0 +1,171 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use crate::compiler::Compiler;
+use crate::language_support::Language;
+
+/// Records the toolchains of the last successful build, next to `lol.json`.
+pub const LOCK_FILE: &str = "lol.lock";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockedToolchain {
+    pub tool: String,
+    pub version: String,
+}
+
+/// Toolchain versions by language key, as in `language_settings`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Lockfile {
+    pub toolchains: BTreeMap<String, LockedToolchain>,
+}
+
+/// A language whose toolchain is not the one the lockfile records.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Mismatch {
+    pub language: String,
+    pub locked: Option<String>,
+    pub current: String,
+}
+
+impl std::fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.locked {
+            Some(locked) => write!(f, "{}: locked to \"{}\", found \"{}\"", self.language, locked, self.current),
+            None => write!(f, "{}: not in {}, found \"{}\"", self.language, LOCK_FILE, self.current),
+        }
+    }
+}
+
+impl Lockfile {
+    fn path(project_path: &Path) -> PathBuf {
+        project_path.join(LOCK_FILE)
+    }
+
+    /// The toolchains installed now for `languages`. Interpreted languages
+    /// lol does not probe, and toolchains that are missing, are left out.
+    pub fn current<'a>(compiler: &Compiler, languages: impl IntoIterator<Item = &'a Language>) -> Self {
+        let toolchains = compiler
+            .get_compiler_info_for(languages.into_iter().filter(|language| language.needs_compiler_check()))
+            .into_iter()
+            .filter(|(_, version)| version != "Not available")
+            .map(|(language, version)| {
+                let tool = compiler.compiler_program(&language);
+                (language.config_key().to_string(), LockedToolchain { tool, version })
+            })
+            .collect();
+        Lockfile { toolchains }
+    }
+
+    pub fn load(project_path: &Path) -> Result<Option<Self>> {
+        let path = Self::path(project_path);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let contents = fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let lockfile = serde_json::from_str(&contents).with_context(|| format!("Failed to parse {}", path.display()))?;
+        Ok(Some(lockfile))
+    }
+
+    pub fn save(&self, project_path: &Path) -> Result<PathBuf> {
+        let path = Self::path(project_path);
+        fs::write(&path, serde_json::to_string_pretty(self)? + "\n")
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(path)
+    }
+
+    /// Toolchains in `current` that differ from, or are missing in, this
+    /// lockfile. Locked languages the build no longer uses are not reported.
+    pub fn mismatches(&self, current: &Lockfile) -> Vec<Mismatch> {
+        current
+            .toolchains
+            .iter()
+            .filter_map(|(language, toolchain)| {
+                let locked = self.toolchains.get(language);
+                (locked != Some(toolchain)).then(|| Mismatch {
+                    language: language.clone(),
+                    locked: locked.map(|locked| locked.version.clone()),
+                    current: toolchain.version.clone(),
+                })
+            })
+            .collect()
+    }
+
+    /// `current` with the entries of languages this build did not use kept,
+    /// so building part of a project does not drop the rest of the lock.
+    pub fn merged(&self, current: Lockfile) -> Lockfile {
+        let mut toolchains = self.toolchains.clone();
+        toolchains.extend(current.toolchains);
+        Lockfile { toolchains }
+    }
+}
+
+/// Compares `current` with the project's lockfile: a mismatch is an error
+/// with `locked` (`--locked`) and a warning otherwise.
+pub fn verify(project_path: &Path, current: &Lockfile, locked: bool) -> Result<()> {
+    let Some(lockfile) = Lockfile::load(project_path)? else {
+        if locked {
+            anyhow::bail!("--locked needs {}; run a build without --locked to create it", LOCK_FILE);
+        }
+        return Ok(());
+    };
+    let mismatches = lockfile.mismatches(current);
+    if mismatches.is_empty() {
+        return Ok(());
+    }
+    if locked {
+        let list: Vec<String> = mismatches.iter().map(|mismatch| format!("  {}", mismatch)).collect();
+        anyhow::bail!("Toolchains differ from {}:\n{}", LOCK_FILE, list.join("\n"));
+    }
+    for mismatch in &mismatches {
+        tracing::warn!("Toolchain differs from {}: {}", LOCK_FILE, mismatch);
+    }
+    Ok(())
+}
+
+/// Records `current` in the project's lockfile after a successful build,
+/// returning its path when anything changed.
+pub fn update(project_path: &Path, current: Lockfile) -> Result<Option<PathBuf>> {
+    let existing = Lockfile::load(project_path)?;
+    let updated = existing.clone().unwrap_or_default().merged(current);
+    if existing.as_ref() == Some(&updated) {
+        return Ok(None);
+    }
+    updated.save(project_path).map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn toolchain(version: &str) -> LockedToolchain {
+        LockedToolchain { tool: "gcc".to_string(), version: version.to_string() }
+    }
+
+    #[test]
+    fn test_mismatches_and_round_trip() {
+        let temp_dir = TempDir::new().unwrap();
+        let locked = Lockfile {
+            toolchains: BTreeMap::from([("c".to_string(), toolchain("gcc 12.2.0")), ("go".to_string(), toolchain("go1.22"))]),
+        };
+        locked.save(temp_dir.path()).unwrap();
+        let loaded = Lockfile::load(temp_dir.path()).unwrap().unwrap();
+        assert_eq!(loaded, locked);
+
+        let current = Lockfile {
+            toolchains: BTreeMap::from([("c".to_string(), toolchain("gcc 13.1.0")), ("cpp".to_string(), toolchain("g++ 13.1.0"))]),
+        };
+        let mismatches = loaded.mismatches(&current);
+        assert_eq!(mismatches.len(), 2);
+        assert_eq!(mismatches[0].to_string(), "c: locked to \"gcc 12.2.0\", found \"gcc 13.1.0\"");
+        assert_eq!(mismatches[1].locked, None);
+        assert!(loaded.mismatches(&loaded).is_empty());
+
+        let merged = loaded.merged(current);
+        assert_eq!(merged.toolchains["c"].version, "gcc 13.1.0");
+        assert!(merged.toolchains.contains_key("go"));
+        assert!(Lockfile::load(&temp_dir.path().join("missing")).unwrap().is_none());
+    }
+}
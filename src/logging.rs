@@ -0,0 +1,142 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use indicatif::MultiProgress;
+use tracing::level_filters::LevelFilter;
+use tracing::Subscriber;
+use tracing_subscriber::fmt::MakeWriter;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl From<LogLevel> for LevelFilter {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Error => LevelFilter::ERROR,
+            LogLevel::Warn => LevelFilter::WARN,
+            LogLevel::Info => LevelFilter::INFO,
+            LogLevel::Debug => LevelFilter::DEBUG,
+            LogLevel::Trace => LevelFilter::TRACE,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    /// One human-readable line per event
+    Pretty,
+    /// One JSON object per line, for log collectors
+    Json,
+}
+
+/// The progress bars of every build in this process. Log lines written to
+/// the terminal hide them while printing, so the two never interleave.
+pub fn progress() -> &'static MultiProgress {
+    static PROGRESS: OnceLock<MultiProgress> = OnceLock::new();
+    PROGRESS.get_or_init(MultiProgress::new)
+}
+
+/// Sends log events to stderr, or to `file` when given, from now on.
+pub fn init(level: LogLevel, format: LogFormat, file: Option<&Path>) -> Result<()> {
+    let subscriber = match file {
+        Some(path) => {
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .with_context(|| format!("Failed to open log file {}", path.display()))?;
+            subscriber(level, format, Mutex::new(file), false)
+        }
+        None => {
+            let ansi = colored::control::SHOULD_COLORIZE.should_colorize();
+            subscriber(level, format, TerminalWriter::default, ansi)
+        }
+    };
+    tracing::subscriber::set_global_default(subscriber).context("Failed to set up logging")
+}
+
+fn subscriber<W>(level: LogLevel, format: LogFormat, writer: W, ansi: bool) -> Box<dyn Subscriber + Send + Sync>
+where
+    W: for<'a> MakeWriter<'a> + Send + Sync + 'static,
+{
+    let builder = tracing_subscriber::fmt().with_max_level(LevelFilter::from(level)).with_writer(writer);
+    match format {
+        LogFormat::Json => Box::new(builder.json().with_current_span(false).finish()),
+        // A terminal shows events as they happen; logs kept for later need timestamps
+        LogFormat::Pretty if ansi => Box::new(builder.with_ansi(true).with_target(false).without_time().finish()),
+        LogFormat::Pretty => Box::new(builder.with_ansi(false).with_target(false).finish()),
+    }
+}
+
+/// Collects one log line and prints it to stderr with the progress bars
+/// suspended.
+#[derive(Default)]
+struct TerminalWriter {
+    line: Vec<u8>,
+}
+
+impl Write for TerminalWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.line.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for TerminalWriter {
+    fn drop(&mut self) {
+        if !self.line.is_empty() {
+            progress().suspend(|| std::io::stderr().write_all(&self.line).ok());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_json_logs_respect_level() {
+        let captured = Captured::default();
+        let writer = captured.clone();
+        let subscriber = subscriber(LogLevel::Info, LogFormat::Json, move || writer.clone(), false);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::debug!("hidden");
+            tracing::info!(language = "C", files = 2, "Compiling");
+        });
+
+        let output = String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<serde_json::Value> = output.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0]["level"], "INFO");
+        assert_eq!(lines[0]["fields"]["message"], "Compiling");
+        assert_eq!(lines[0]["fields"]["language"], "C");
+    }
+}
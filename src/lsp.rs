@@ -0,0 +1,252 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use anyhow::{Context, Result};
+use serde_json::{json, Value};
+use tokio::sync::mpsc;
+use crate::diagnostics::{Diagnostic, Severity};
+
+/// JSON-RPC error code for requests the server does not implement.
+const METHOD_NOT_FOUND: i64 = -32601;
+
+/// A message from the editor.
+#[derive(Debug, PartialEq)]
+pub enum Message {
+    Request { id: Value, method: String, params: Value },
+    Notification { method: String, params: Value },
+}
+
+/// The editor connection over stdin and stdout. Anything else printed to
+/// stdout, such as build output, goes to stderr instead, which editors
+/// show in their language server log.
+pub struct Connection {
+    writer: File,
+    pub incoming: mpsc::UnboundedReceiver<Result<Message>>,
+}
+
+impl Connection {
+    pub fn stdio() -> Result<Self> {
+        let writer = protocol_stdout()?;
+        let (sender, incoming) = mpsc::unbounded_channel();
+        std::thread::spawn(move || {
+            let mut reader = BufReader::new(std::io::stdin().lock());
+            loop {
+                match read_message(&mut reader) {
+                    Ok(Some(message)) => {
+                        if sender.send(Ok(message)).is_err() {
+                            break;
+                        }
+                    }
+                    Ok(None) => break,
+                    Err(error) => {
+                        let _ = sender.send(Err(error));
+                        break;
+                    }
+                }
+            }
+        });
+        Ok(Self { writer, incoming })
+    }
+
+    pub fn respond(&mut self, id: Value, result: Value) -> Result<()> {
+        self.send(&json!({ "jsonrpc": "2.0", "id": id, "result": result }))
+    }
+
+    pub fn respond_unknown(&mut self, id: Value, method: &str) -> Result<()> {
+        self.send(&json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": METHOD_NOT_FOUND, "message": format!("{} is not supported", method) },
+        }))
+    }
+
+    pub fn notify(&mut self, method: &str, params: Value) -> Result<()> {
+        self.send(&json!({ "jsonrpc": "2.0", "method": method, "params": params }))
+    }
+
+    /// Shows `message` in the editor's output for the server.
+    pub fn log(&mut self, message: &str) -> Result<()> {
+        self.notify("window/logMessage", json!({ "type": 3, "message": message }))
+    }
+
+    fn send(&mut self, message: &Value) -> Result<()> {
+        write_message(&mut self.writer, message).context("Lost the connection to the editor")
+    }
+}
+
+/// A handle on the original stdout for the protocol, with stdout itself
+/// pointed at stderr from then on.
+#[cfg(unix)]
+fn protocol_stdout() -> Result<File> {
+    use std::os::fd::FromRawFd;
+
+    std::io::stdout().flush()?;
+    // SAFETY: duplicates stdout before pointing it at stderr; the copy is owned by the returned File
+    unsafe {
+        let protocol = libc::dup(libc::STDOUT_FILENO);
+        if protocol < 0 || libc::dup2(libc::STDERR_FILENO, libc::STDOUT_FILENO) < 0 {
+            return Err(std::io::Error::last_os_error()).context("Failed to set up the LSP connection");
+        }
+        Ok(File::from_raw_fd(protocol))
+    }
+}
+
+#[cfg(not(unix))]
+fn protocol_stdout() -> Result<File> {
+    anyhow::bail!("lol lsp is only available on Unix")
+}
+
+/// Reads one `Content-Length` framed message, or `None` at end of input.
+pub fn read_message(reader: &mut impl BufRead) -> Result<Option<Message>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some(value) = header.strip_prefix("Content-Length:") {
+            length = Some(value.trim().parse::<usize>().context("Invalid Content-Length")?);
+        }
+    }
+    let length = length.context("Message without Content-Length")?;
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+
+    let value: Value = serde_json::from_slice(&body).context("Invalid JSON-RPC message")?;
+    let method = value["method"].as_str().unwrap_or_default().to_string();
+    let params = value.get("params").cloned().unwrap_or(Value::Null);
+    Ok(Some(match value.get("id") {
+        Some(id) => Message::Request { id: id.clone(), method, params },
+        None => Message::Notification { method, params },
+    }))
+}
+
+pub fn write_message(writer: &mut impl Write, message: &Value) -> std::io::Result<()> {
+    let body = message.to_string();
+    write!(writer, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    writer.flush()
+}
+
+/// What the server offers: diagnostics only, refreshed when files are saved.
+pub fn initialize_result() -> Value {
+    json!({
+        "capabilities": {
+            "textDocumentSync": { "openClose": true, "change": 0, "save": true },
+        },
+        "serverInfo": { "name": "lol", "version": env!("CARGO_PKG_VERSION") },
+    })
+}
+
+/// Turns each build's diagnostics into `textDocument/publishDiagnostics`
+/// parameters, clearing files that had diagnostics last time but not now.
+#[derive(Debug, Default)]
+pub struct Publisher {
+    published: HashSet<String>,
+}
+
+impl Publisher {
+    pub fn publish(&mut self, project_path: &Path, diagnostics: &[Diagnostic]) -> Vec<Value> {
+        let root = project_path.canonicalize().unwrap_or_else(|_| project_path.to_path_buf());
+        let mut by_file: BTreeMap<String, Vec<Value>> = BTreeMap::new();
+        for diagnostic in diagnostics {
+            let path = if diagnostic.file.is_absolute() { diagnostic.file.clone() } else { root.join(&diagnostic.file) };
+            by_file.entry(file_uri(&path)).or_default().push(lsp_diagnostic(diagnostic));
+        }
+
+        let stale: Vec<String> = self.published.iter().filter(|uri| !by_file.contains_key(*uri)).cloned().collect();
+        let mut params: Vec<Value> = stale.into_iter().map(|uri| json!({ "uri": uri, "diagnostics": [] })).collect();
+        params.extend(by_file.iter().map(|(uri, diagnostics)| json!({ "uri": uri, "diagnostics": diagnostics })));
+        self.published = by_file.into_keys().collect();
+        params
+    }
+}
+
+fn lsp_diagnostic(diagnostic: &Diagnostic) -> Value {
+    // LSP positions are zero-based; a diagnostic without a line marks the file's start
+    let line = diagnostic.line.unwrap_or(1).saturating_sub(1);
+    let character = diagnostic.column.unwrap_or(1).saturating_sub(1);
+    let severity = match diagnostic.severity {
+        Severity::Error => 1,
+        Severity::Warning => 2,
+        Severity::Note => 3,
+    };
+    json!({
+        "range": {
+            "start": { "line": line, "character": character },
+            "end": { "line": line, "character": character },
+        },
+        "severity": severity,
+        "source": "lol",
+        "message": diagnostic.message,
+    })
+}
+
+/// A `file://` URI, percent-encoding everything but unreserved characters
+/// and separators.
+pub fn file_uri(path: &Path) -> String {
+    let mut uri = String::from("file://");
+    for byte in path.to_string_lossy().bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => uri.push(byte as char),
+            _ => uri.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    uri
+}
+
+/// The project directory an editor opened, from `rootUri`.
+pub fn root_path(params: &Value) -> Option<PathBuf> {
+    let uri = params["rootUri"].as_str()?.strip_prefix("file://")?;
+    let mut bytes = Vec::new();
+    let mut rest = uri.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        match (byte, tail) {
+            (b'%', [high, low, tail @ ..]) => {
+                let hex = std::str::from_utf8(&[*high, *low]).ok().and_then(|hex| u8::from_str_radix(hex, 16).ok())?;
+                bytes.push(hex);
+                rest = tail;
+            }
+            _ => {
+                bytes.push(byte);
+                rest = tail;
+            }
+        }
+    }
+    Some(PathBuf::from(String::from_utf8(bytes).ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_messages_and_publishing() {
+        let input = "Content-Length: 44\r\n\r\n{\"jsonrpc\":\"2.0\",\"id\":1,\"method\":\"shutdown\"}  ";
+        let message = read_message(&mut input.as_bytes()).unwrap().unwrap();
+        assert_eq!(message, Message::Request { id: json!(1), method: "shutdown".to_string(), params: Value::Null });
+        let mut written = Vec::new();
+        write_message(&mut written, &json!({ "id": 1 })).unwrap();
+        assert_eq!(written, b"Content-Length: 8\r\n\r\n{\"id\":1}");
+
+        let error = Diagnostic {
+            file: PathBuf::from("/work/my app/main.c"),
+            line: Some(3),
+            column: Some(5),
+            severity: Severity::Error,
+            message: "boom".to_string(),
+        };
+        let mut publisher = Publisher::default();
+        let params = publisher.publish(Path::new("/work/my app"), &[error]);
+        assert_eq!(params[0]["uri"], "file:///work/my%20app/main.c");
+        assert_eq!(params[0]["diagnostics"][0]["range"]["start"], json!({ "line": 2, "character": 4 }));
+        assert_eq!(params[0]["diagnostics"][0]["severity"], 1);
+        // Fixed on the next build: the file is cleared
+        assert_eq!(publisher.publish(Path::new("/work/my app"), &[]), [json!({ "uri": "file:///work/my%20app/main.c", "diagnostics": [] })]);
+        assert_eq!(root_path(&json!({ "rootUri": "file:///work/my%20app" })), Some(PathBuf::from("/work/my app")));
+    }
+}
@@ -1,27 +1,222 @@
 use anyhow::{Context, Result};
-use clap::Parser;
+use clap::{CommandFactory, FromArgMatches};
 use colored::*;
-use indicatif::{MultiProgress, ProgressStyle};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Instant;
+use indicatif::ProgressStyle;
 
+#[macro_use]
+mod output;
 mod compiler;
+mod compiler_pool;
+mod completions;
 mod config;
+mod dir_config;
+mod organization;
+mod dev_env;
+mod diagnostics;
+mod doctor;
 mod file_detector;
+mod init;
+mod scaffold;
 mod language_support;
+mod languages;
 mod args;
 mod appimage;
+mod node_env;
+mod python_env;
+mod plugins;
+mod hooks;
+mod codegen;
+mod codesign;
+mod pipeline;
+mod workspace;
+mod matrix;
+mod msvc;
+mod artifacts;
+mod sbom;
+mod license;
+mod lockfile;
+mod secrets;
+mod reproducible;
+mod signing;
+mod patterns;
+mod package;
+mod system_package;
+mod flatpak;
+mod docker;
+mod snap;
+mod entry_point;
+mod build_info;
+mod daemon;
+mod events;
+mod notifications;
+mod publish;
+mod logging;
+mod stream;
+mod suggestions;
+mod history;
+mod compare;
+mod lsp;
+mod metrics;
+mod tui;
+mod version_managers;
+mod watch;
+mod priority;
 
-use compiler::Compiler;
+use compiler::{Compiler, JobPools};
+use compiler_pool::CompilerPool;
 use config::Config;
 use file_detector::FileDetector;
-use args::Args;
+use args::{AnalyticsCommand, Args, Command, ConfigCommand, HistoryCommand, LicenseCommand, QueueCommand};
 use appimage::AppImageBuilder;
+use language_support::Language;
+use plugins::{HookEvent, PluginHost};
+use hooks::HookStage;
+use pipeline::StageKind;
+
+/// Exit status when code failed to compile, or a pipeline stage failed.
+const EXIT_COMPILE_ERRORS: u8 = 1;
+/// Exit status when a compiler the project needs is not installed. clap
+/// uses 2 for command-line mistakes.
+const EXIT_TOOLCHAIN_MISSING: u8 = 3;
+/// Exit status when lol could not do its job: a bad configuration, an I/O
+/// error or a bug.
+const EXIT_INTERNAL_ERROR: u8 = 4;
 
 #[tokio::main]
-async fn main() -> Result<()> {
-    let args = Args::parse();
-    
-    // Load configuration
-    let config = Config::load().context("Failed to load configuration")?;
+async fn main() -> std::process::ExitCode {
+    let Err(error) = run().await else {
+        return std::process::ExitCode::SUCCESS;
+    };
+    eprintln!("Error: {:?}", error);
+    let code = match error.downcast_ref::<BuildFailed>() {
+        Some(failed) if failed.failure == compiler::BuildFailure::ToolchainMissing => EXIT_TOOLCHAIN_MISSING,
+        Some(_) => EXIT_COMPILE_ERRORS,
+        None => EXIT_INTERNAL_ERROR,
+    };
+    std::process::ExitCode::from(code)
+}
+
+async fn run() -> Result<()> {
+    completions::handle_request();
+    let matches = Args::command().get_matches();
+    let mut args = Args::from_arg_matches(&matches).unwrap_or_else(|error| error.exit());
+    let profile_given = matches.value_source("profile") == Some(clap::parser::ValueSource::CommandLine);
+    output::configure(args.quiet, args.no_emoji);
+    // Quiet means errors only, on the terminal; a log file still gets what was asked for
+    let log_level = if args.quiet && args.log_file.is_none() { logging::LogLevel::Error } else { args.log_level };
+    logging::init(log_level, args.log_format, args.log_file.as_deref())?;
+
+    // `lol package` runs the normal build first, for its own project path
+    let mut package_format = None;
+    if let Some(Command::Package { project_path, format }) = &args.command {
+        package_format = Some(*format);
+        args.project_path = project_path.clone();
+    }
+    // So does `lol watch`, again after every change
+    let mut watching = None;
+    if let Some(Command::Watch { project_path, serve, tui, metrics_port }) = &args.command {
+        if *tui {
+            args.project_path = project_path.clone();
+            return watch_tui(&args).await;
+        }
+        watching = Some((serve.clone(), *metrics_port));
+        args.project_path = project_path.clone();
+    }
+
+    match &args.command {
+        Some(Command::Clean { project_path }) => return clean(project_path),
+        Some(Command::Sbom { project_path, format, output }) => {
+            return write_sbom(&args, project_path, *format, output.as_deref());
+        }
+        Some(Command::Verify { path, trusted_keys }) => {
+            let config = Config::load().context("Failed to load configuration")?;
+            let trusted: Vec<String> = config.trusted_keys.iter().chain(trusted_keys).cloned().collect();
+            return signing::verify(path, &trusted);
+        }
+        Some(Command::Init { global: true, force, .. }) => return init::write_global(*force),
+        Some(Command::Init { project_path, yes, force, .. }) => {
+            let config = Config::load().context("Failed to load configuration")?;
+            return init::run(project_path, &config, *yes, *force, &mut std::io::stdin().lock());
+        }
+        Some(Command::Languages { project_path, json }) => {
+            let config = Config::load_for_project(project_path).context("Failed to load configuration")?;
+            let statuses = languages::statuses(&config);
+            if *json {
+                println!("{}", serde_json::to_string_pretty(&statuses)?);
+            } else {
+                languages::print_table(&statuses);
+            }
+            return Ok(());
+        }
+        Some(Command::Doctor { project_path, fix }) => {
+            let config = Config::load_for_project(project_path).context("Failed to load configuration")?;
+            return doctor::run(project_path, &config, *fix);
+        }
+        Some(Command::New { language, name }) => {
+            let project_dir = scaffold::create(Path::new("."), scaffold::language(language)?, name)?;
+            say!("✨ Created {}; build it with `lol {}`", project_dir.display(), name);
+            return Ok(());
+        }
+        Some(Command::Completions { shell }) => return completions::write_script(*shell, &mut std::io::stdout()),
+        Some(Command::Lsp { project_path }) => return lsp_server(&args, project_path).await,
+        Some(Command::Daemon { host, port, dashboard }) => {
+            let config = Config::load().context("Failed to load configuration")?;
+            apply_priority(&args, &config, true)?;
+            return run_daemon(host, *port, *dashboard).await;
+        }
+        Some(Command::Publish { project_path, tag, dry_run }) => {
+            let config = Config::load_for_project(project_path).context("Failed to load configuration")?;
+            return publish::run(project_path, &config, tag.as_deref(), *dry_run);
+        }
+        Some(Command::History { action: Some(HistoryCommand::Show { id, project_path }), .. }) => {
+            return history::show(project_path, *id);
+        }
+        Some(Command::History { action: None, project_path, limit }) => return history::list(project_path, *limit),
+        Some(Command::Analytics { action: AnalyticsCommand::Export { project_path, format, output } }) => {
+            let exported = history::export(&history::load(project_path)?, *format)?;
+            match output {
+                Some(path) => std::fs::write(path, exported).with_context(|| format!("Failed to write {}", path.display()))?,
+                None => print!("{}", exported),
+            }
+            return Ok(());
+        }
+        Some(Command::Analytics { action: AnalyticsCommand::Enable { project_path } }) => {
+            history::set_recording(project_path, true)?;
+            say!("📊 Builds of {} will be recorded in {}", project_path.display(), history::HISTORY_FILE);
+            return Ok(());
+        }
+        Some(Command::Analytics { action: AnalyticsCommand::Disable { project_path } }) => {
+            history::set_recording(project_path, false)?;
+            say!("📊 Builds of {} will no longer be recorded", project_path.display());
+            return Ok(());
+        }
+        Some(Command::Queue { action: QueueCommand::Status { host, port } }) => {
+            return daemon::print_queue(host, *port);
+        }
+        Some(Command::Queue { action: QueueCommand::Cancel { id, host, port } }) => {
+            daemon::cancel_queued(host, *port, *id)?;
+            say!("🗑️  Build {} cancelled", id);
+            return Ok(());
+        }
+        Some(Command::Compare { project_path, baseline, fail_on_regression }) => {
+            let regressions = compare::run(project_path, baseline.as_deref(), *fail_on_regression)?;
+            if !regressions.is_empty() {
+                return Err(BuildFailed::new(format!("{} regressions against the baseline", regressions.len()), false).into());
+            }
+            return Ok(());
+        }
+        Some(Command::Config { action: ConfigCommand::Migrate { project_path, to } }) => {
+            return migrate_configs(project_path, *to);
+        }
+        Some(Command::License { action: LicenseCommand::Check { project_path, fix } }) => {
+            return check_licenses(&args, project_path, *fix);
+        }
+        Some(Command::Package { .. }) | Some(Command::Watch { .. }) | None => {}
+    }
     
     // Validate project path
     if !args.project_path.exists() {
@@ -32,146 +227,1404 @@ async fn main() -> Result<()> {
         anyhow::bail!("Project path is not a directory: {:?}", args.project_path);
     }
 
-    println!("🚀 {} - Multi-language Code Compiler", "lol".bold().blue());
-    println!("📁 Project: {:?}", args.project_path);
+    // Like PROJECT_PATH, relative to where lol runs rather than to the project
+    if let Some(build_dir) = &args.build_dir {
+        let build_dir = std::path::absolute(build_dir).with_context(|| format!("Invalid build directory {:?}", build_dir))?;
+        args.build_dir = Some(build_dir);
+    }
+
+    // Spawned compilers, hooks and packagers are passed the epoch from here
+    if args.reproducible {
+        let epoch = reproducible::source_date_epoch(&args.project_path);
+        reproducible::set_epoch(epoch);
+        say!("🔁 Reproducible build (SOURCE_DATE_EPOCH={})", epoch);
+    }
+
+    // Load configuration, with the project's lol.json on top
+    let mut config = Config::load_for_project(&args.project_path).context("Failed to load configuration")?;
+    apply_build_dir(&mut config, &args);
+    if let Some(profile) = config.profile.filter(|_| !profile_given) {
+        args.profile = profile;
+    }
+
+    // Load plugins; the languages they define behave like custom_languages
+    let plugins = PluginHost::load();
+    for (path, reason) in &plugins.skipped {
+        tracing::warn!("Skipping plugin {}: {}", path.display(), reason);
+    }
+    config.custom_languages.extend(plugins.languages());
+
+    say!("🚀 {} - Multi-language Code Compiler", "lol".bold().blue());
+    say!("📁 Project: {:?}", args.project_path);
+    for manifest in plugins.manifests() {
+        say!("🔌 Plugin: {} {}", manifest.name, manifest.version);
+    }
     
+    let plugins = Arc::new(plugins);
+
+    if config.watch_mode && args.command.is_none() && args.name.is_none() {
+        watching = Some((None, None));
+    }
+    apply_priority(&args, &config, watching.is_some())?;
+    if let Some((serve, metrics_port)) = watching {
+        return watch(&args, serve.as_deref(), metrics_port, plugins).await;
+    }
+
+    if let Some(format) = package_format {
+        return package(&args, &config, format, plugins).await;
+    }
+
     // Check if we're creating an AppImage
     if let Some(app_name) = &args.name {
-        println!("🎯 Creating AppImage: {}", app_name.bold().green());
-        return create_appimage(&args, &config, app_name).await;
+        if cfg!(windows) {
+            // AppImages only run on Linux, so Windows gets the same files in a zip
+            say!("🎯 Creating zip package: {} (AppImages are Linux-only)", app_name.bold().green());
+            let mut config = config.clone();
+            config.package.name = Some(app_name.clone());
+            return package(&args, &config, package::PackageFormat::Zip, plugins).await;
+        }
+        say!("🎯 Creating AppImage: {}", app_name.bold().green());
+        return create_appimage(&args, &config, app_name, plugins).await;
     }
     
-    println!("🔧 Parallel jobs: {}", args.jobs);
-    println!();
+    say!("🔧 Parallel jobs: {} ({} for syntax checks)", args.jobs, args.check_jobs);
+    say!();
 
-    // Detect source files
-    let file_detector = FileDetector::new();
-    let source_files = file_detector.detect_files(&args.project_path, &args, &config)?;
+    if let Some(members) = workspace::members(&config, &args.project_path)? {
+        return build_workspace(&args, members, plugins).await;
+    }
+    if !args.matrix.is_empty() {
+        return build_matrix(&args, &config, plugins).await;
+    }
 
-    if source_files.is_empty() {
-        println!("{} No source files found to compile.", "⚠️".yellow());
+    let started = std::time::Instant::now();
+    let outcome = build_project(&args, &config, plugins, None, None, None).await?;
+    if config.history && !args.no_telemetry {
+        let success = outcome.failed_stage.is_none() && outcome.summary.failure().is_none();
+        if let Err(error) = history::record(&args.project_path, &outcome.summary, success, started.elapsed()) {
+            tracing::warn!("Could not record the build: {:#}", error);
+        }
+    }
+
+    // Display results
+    if !outcome.summary.results.is_empty() {
+        display_results(&outcome.summary, &args, &config);
+    }
+    outcome.check()?;
+
+    // Package JVM classes when configured
+    if let Some(jar_path) = outcome.compiler.package_jar(&outcome.summary.results, &args.project_path)? {
+        say!("☕ Packaged JAR: {}", jar_path.display());
+    }
+    record_artifacts(&outcome, &args.project_path, &config, false)?;
+    if let Some(toolchains) = outcome.toolchains.filter(|_| !args.locked) {
+        if let Some(path) = lockfile::update(&args.project_path, toolchains)? {
+            say!("🔒 Toolchain lock: {}", path.display());
+        }
+    }
+
+    Ok(())
+}
+
+/// Builds each workspace member with its own configuration, after the
+/// members it depends on, all drawing from one pool of `--jobs` compile
+/// slots, then prints a member/language matrix.
+async fn build_workspace(args: &Args, members: Vec<workspace::Member>, plugins: Arc<PluginHost>) -> Result<()> {
+    say!("🗂️  Workspace with {} members", members.len());
+    let mut configured = Vec::new();
+    for member in members {
+        let mut config = Config::load_for_project(&member.path)
+            .with_context(|| format!("Failed to load configuration for {}", member.name))?;
+        if let Some(build_dir) = &args.build_dir {
+            config.output_directory = Some(build_dir.join(&member.name).to_string_lossy().into_owned());
+        }
+        config.custom_languages.extend(plugins.languages());
+        configured.push((member, config));
+    }
+    let configured = workspace::build_order(configured)?;
+
+    // Members build side by side, sharing one pool of `--jobs` compile slots,
+    // each starting once the members it depends on have built
+    let job_pools = JobPools::new(args.jobs, args.check_jobs);
+    let mut schedule = workspace::Schedule::new(&configured);
+    let mut results: Vec<Option<Vec<compiler::CompilationResult>>> = configured.iter().map(|_| None).collect();
+    let mut running = tokio::task::JoinSet::new();
+    let mut missing_toolchain = false;
+    // Static libraries of the members others depend on, by member name
+    let mut libraries: HashMap<String, std::path::PathBuf> = HashMap::new();
+
+    loop {
+        while let Some(next) = schedule.next() {
+            let index = match next {
+                workspace::Next::Build(index) => index,
+                workspace::Next::Skip { index, dependency } => {
+                    say!("⏭️  Skipping {} ({} failed)", configured[index].0.name, dependency);
+                    results[index] = Some(Vec::new());
+                    continue;
+                }
+            };
+            let (member, config) = &configured[index];
+            say!("📦 Member: {}", member.name.bold());
+
+            // Let C-family sources include headers from the members they depend on
+            let dependencies = configured.iter().map(|(other, _)| other).filter(|other| config.depends_on.contains(&other.name));
+            let includes = workspace::include_flags(dependencies);
+            let with_includes = |flags: &Option<String>| match flags {
+                _ if includes.is_empty() => flags.clone(),
+                Some(flags) => Some(format!("{} {}", flags, includes)),
+                None => Some(includes.clone()),
+            };
+            let member_args = Args {
+                project_path: member.path.clone(),
+                cflags: with_includes(&args.cflags),
+                cxxflags: with_includes(&args.cxxflags),
+                ..args.clone()
+            };
+            let config = config.clone();
+            let plugins = Arc::clone(&plugins);
+            let job_pools = job_pools.clone();
+            running.spawn(async move {
+                let outcome = build_project(&member_args, &config, plugins, Some(job_pools), None, None).await;
+                (index, outcome)
+            });
+        }
+
+        let Some(joined) = running.join_next().await else {
+            break;
+        };
+        let (index, outcome) = joined.context("Workspace member build panicked")?;
+        let (member, config) = &configured[index];
+        let outcome = outcome.with_context(|| format!("Workspace member {} failed", member.name))?;
+        missing_toolchain |= outcome.summary.failure() == Some(compiler::BuildFailure::ToolchainMissing);
+        let mut success = outcome.summary.failure().is_none() && outcome.failed_stage.is_none();
+        if success {
+            if let Some(jar_path) = outcome.compiler.package_jar(&outcome.summary.results, &member.path)? {
+                say!("☕ Packaged JAR: {}", jar_path.display());
+            }
+        }
+        let manifest = record_artifacts(&outcome, &member.path, config, false)?;
+        if let (true, Some(manifest), Some(output_dir)) = (success, &manifest, config.output_dir(&member.path)) {
+            if let Err(error) = link_member(member, config, &configured, &mut libraries, manifest, &outcome.summary.results, &output_dir) {
+                say_error!("{} {}: {:#}", "❌".red(), member.name, error);
+                success = false;
+            }
+        }
+        schedule.finish(index, success);
+        results[index] = Some(outcome.summary.results);
+    }
+
+    let builds: Vec<(workspace::Member, Vec<compiler::CompilationResult>)> = configured
+        .into_iter()
+        .zip(results)
+        .map(|((member, _), results)| (member, results.unwrap_or_default()))
+        .collect();
+    let failed = schedule.failed();
+    say!("\n📊 Workspace Results:");
+    say!("{}", workspace::results_matrix(&builds).trim_end());
+    if !failed.is_empty() {
+        return Err(BuildFailed::new(format!("Workspace members failed: {}", failed.join(", ")), missing_toolchain).into());
+    }
+    say!("\n🎉 {} All workspace members built successfully!", "SUCCESS".bold().green());
+    Ok(())
+}
+
+/// Archives a workspace member others depend on into a static library, and
+/// links a C or C++ member with a `main` against the libraries of the
+/// members it depends on.
+fn link_member(
+    member: &workspace::Member,
+    config: &Config,
+    members: &[(workspace::Member, Config)],
+    libraries: &mut HashMap<String, std::path::PathBuf>,
+    manifest: &artifacts::ArtifactManifest,
+    results: &[compiler::CompilationResult],
+    output_dir: &Path,
+) -> Result<()> {
+    if members.iter().any(|(_, other)| other.depends_on.contains(&member.name)) {
+        if let Some(library) = workspace::archive(member, manifest, &member.path, output_dir)? {
+            say!("📚 Library: {}", library.display());
+            libraries.insert(member.name.clone(), library);
+        }
+    }
+
+    let dependency_libraries = workspace::dependency_libraries(config, members, libraries);
+    if dependency_libraries.is_empty() {
         return Ok(());
     }
+    let source_files: HashMap<Language, Vec<std::path::PathBuf>> =
+        results.iter().map(|result| (result.language.clone(), result.files.clone())).collect();
+    let Some(entry) = entry_point::detect(&member.path, &source_files).filter(|entry| matches!(entry.language, Language::C | Language::Cpp)) else {
+        return Ok(());
+    };
+    let executable = output_dir.join(entry.file.file_stem().unwrap_or_default());
+    entry_point::link_objects(&manifest.artifacts, &member.path, &dependency_libraries, &executable)?;
+    say!("🔗 Linked {}", executable.display());
+    Ok(())
+}
 
-    // Display detected files
-    println!("📋 Detected source files:");
-    for (lang, files) in &source_files {
-        println!("  {}: {} files", lang.name().bold(), files.len());
+/// Builds every `--matrix` cell concurrently, each into its own
+/// subdirectory of the output directory and sharing one pool of `--jobs`
+/// compile slots, then summarizes pass/fail per cell.
+async fn build_matrix(args: &Args, config: &Config, plugins: Arc<PluginHost>) -> Result<()> {
+    let cells = matrix::cells(&args.matrix, args.profile, args.target)?;
+    say!("🧮 Matrix: {}", cells.iter().map(matrix::Cell::name).collect::<Vec<_>>().join(", "));
+
+    let job_pools = JobPools::new(args.jobs, args.check_jobs);
+    let base_output = config.output_directory.clone().unwrap_or_else(|| "build".to_string());
+    let mut builds = tokio::task::JoinSet::new();
+    for (index, cell) in cells.iter().copied().enumerate() {
+        let cell_args = Args { profile: cell.profile, target: cell.target, matrix: Vec::new(), ..args.clone() };
+        let mut cell_config = config.clone();
+        cell_config.output_directory = Some(format!("{}/{}", base_output, cell.name()));
+        let plugins = Arc::clone(&plugins);
+        let job_pools = job_pools.clone();
+
+        builds.spawn(async move {
+            let outcome = build_project(&cell_args, &cell_config, plugins, Some(job_pools), None, None).await;
+            (index, outcome, cell_config)
+        });
+    }
+
+    let mut outcomes = vec![None; cells.len()];
+    let mut missing_toolchain = false;
+    while let Some(joined) = builds.join_next().await {
+        let (index, outcome, cell_config) = joined.context("Matrix build panicked")?;
+        let outcome = outcome.with_context(|| format!("Matrix cell {} failed", cells[index].name()))?;
+        // Cells build side by side, so each only claims files in its own output directory
+        record_artifacts(&outcome, &args.project_path, &cell_config, true)?;
+        // A failed language keeps only its compiled files in `files`
+        let succeeded = outcome.summary.results.iter().map(|result| result.files.len()).sum();
+        let mut failed = outcome.summary.failed_files.values().map(Vec::len).sum();
+        if outcome.failed_stage.is_some() && failed == 0 {
+            failed = 1;
+        }
+        missing_toolchain |= outcome.summary.failure() == Some(compiler::BuildFailure::ToolchainMissing);
+        outcomes[index] = Some((cells[index], succeeded, failed));
+    }
+
+    let outcomes: Vec<_> = outcomes.into_iter().flatten().collect();
+    say!("\n📊 Matrix Results:");
+    say!("{}", matrix::summary(&outcomes).trim_end());
+    let failed: Vec<String> = outcomes.iter().filter(|(_, _, failed)| *failed > 0).map(|(cell, _, _)| cell.name()).collect();
+    if !failed.is_empty() {
+        return Err(BuildFailed::new(format!("Matrix cells failed: {}", failed.join(", ")), missing_toolchain).into());
+    }
+    say!("\n🎉 {} All matrix cells passed!", "SUCCESS".bold().green());
+    Ok(())
+}
+
+/// What building one project produced.
+struct BuildOutcome {
+    compiler: Compiler,
+    summary: compiler::BuildSummary,
+    failed_stage: Option<String>,
+    build_info: Option<build_info::BuildInfo>,
+    /// Toolchains of the compiled languages, for `lol.lock`
+    toolchains: Option<lockfile::Lockfile>,
+}
+
+impl BuildOutcome {
+    /// The files the build produced, for the manifest and packaging.
+    fn collect_artifacts(&self, project_path: &std::path::Path, config: &Config) -> Result<artifacts::ArtifactManifest> {
+        let output_dir = config.output_dir(project_path);
+        artifacts::collect(project_path, output_dir.as_deref(), &self.summary.results, &self.summary.outputs)
+    }
+
+    /// Fails when a stage did, with the exit status the failure calls for.
+    fn check(&self) -> Result<()> {
+        match &self.failed_stage {
+            Some(stage) => {
+                let missing_toolchain = self.summary.failure() == Some(compiler::BuildFailure::ToolchainMissing);
+                Err(BuildFailed::new(format!("Stage '{}' failed", stage), missing_toolchain).into())
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+/// A build that ran and failed, as opposed to lol itself failing.
+#[derive(Debug)]
+struct BuildFailed {
+    message: String,
+    failure: compiler::BuildFailure,
+}
+
+impl BuildFailed {
+    fn new(message: String, missing_toolchain: bool) -> Self {
+        let failure = if missing_toolchain { compiler::BuildFailure::ToolchainMissing } else { compiler::BuildFailure::CompileErrors };
+        Self { message, failure }
+    }
+}
+
+impl std::fmt::Display for BuildFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for BuildFailed {}
+
+/// Writes `artifacts.json` listing the files the build produced. With
+/// `output_only`, files outside the output directory are left out.
+fn record_artifacts(
+    outcome: &BuildOutcome,
+    project_path: &std::path::Path,
+    config: &Config,
+    output_only: bool,
+) -> Result<Option<artifacts::ArtifactManifest>> {
+    let Some(output_dir) = config.output_dir(project_path) else {
+        return Ok(None);
+    };
+
+    let mut manifest = outcome.collect_artifacts(project_path, config)?;
+    manifest.build_info = outcome.build_info.clone();
+    if output_only {
+        let relative_output = output_dir.strip_prefix(project_path).unwrap_or(&output_dir);
+        manifest.artifacts.retain(|artifact| artifact.path.starts_with(relative_output));
+    }
+    let path = manifest.save(&output_dir)?;
+    say!("🧾 Artifact manifest: {} ({} files)", path.display(), manifest.artifacts.len());
+    Ok(Some(manifest))
+}
+
+/// Sets lol's niceness and CPUs from `--nice`, `--cpus` and `priority`, for
+/// the compilers it starts to inherit. `background` is for the long-running
+/// watch, LSP and daemon builds. Only a bad CPU list is an error; the OS
+/// refusing, such as a negative nice level without root, is a warning.
+fn apply_priority(args: &Args, config: &Config, background: bool) -> Result<()> {
+    let priority = priority::Priority::resolve(args, &config.priority, background)?;
+    if let Err(error) = priority.apply() {
+        tracing::warn!("{:#}", error);
+        return Ok(());
+    }
+    if let Some(nice) = priority.nice {
+        say!("🐢 Nice level {}", nice);
+    }
+    if let Some(cpus) = &priority.cpus {
+        say!("📌 Pinned to CPUs {:?}", cpus);
+    }
+    Ok(())
+}
+
+/// `--build-dir` takes the place of the configured `output_directory`. One
+/// inside the project is kept relative, so source detection still skips it.
+fn apply_build_dir(config: &mut Config, args: &Args) {
+    if let Some(build_dir) = &args.build_dir {
+        let root = args.project_path.canonicalize().unwrap_or_else(|_| args.project_path.clone());
+        let build_dir = build_dir.strip_prefix(&root).unwrap_or(build_dir);
+        config.output_directory = Some(build_dir.to_string_lossy().into_owned());
+    }
+}
+
+/// Files the build created or changed in the project outside its output
+/// and generated-code directories.
+fn written_into_sources(snapshot: &artifacts::Snapshot, project_path: &std::path::Path, config: &Config) -> Vec<std::path::PathBuf> {
+    let output_dirs: Vec<_> = config
+        .output_dir(project_path)
+        .into_iter()
+        .chain(Some(config.generated_dir(project_path)))
+        .map(|dir| dir.canonicalize().unwrap_or(dir))
+        .collect();
+    snapshot.written_outside(project_path, &output_dirs)
+}
+
+/// Runs the project's pipeline stages and build hooks. Compilation shares
+/// `job_pools` when given, so several projects can build under one `--jobs`
+/// and `--check-jobs` limit, and reports progress to `events` when given.
+async fn build_project(
+    args: &Args,
+    config: &Config,
+    plugins: Arc<PluginHost>,
+    job_pools: Option<JobPools>,
+    events: Option<events::EventSender>,
+    compiler_pool: Option<Arc<CompilerPool>>,
+) -> Result<BuildOutcome> {
+    let started = Instant::now();
+    let stages = pipeline::order(&pipeline::stages(config))?;
+    let custom_pipeline = !config.stages.is_empty();
+    let compile_jobs = stages
+        .iter()
+        .find(|stage| pipeline::kind(stage) == StageKind::Compile)
+        .and_then(|stage| stage.jobs)
+        .unwrap_or(args.jobs);
+    let snapshot = args.build_dir.is_some().then(|| artifacts::Snapshot::take(&args.project_path));
+    let mut config = config.clone();
+    for resolution in version_managers::resolve(&args.project_path) {
         if args.verbose {
-            for file in files {
-                println!("    {}", file.display());
+            say!("🧰 Toolchain: {}", resolution.description);
+        }
+        resolution.apply(&mut config);
+    }
+    let config = &config;
+    // Without a pool that outlives the build, warm processes serve this build only
+    let compiler_pool = compiler_pool.unwrap_or_default();
+    let mut compiler = Compiler::new(config.clone(), compile_jobs)
+        .with_check_jobs(args.check_jobs)
+        .with_plugins(plugins)
+        .with_compiler_pool(compiler_pool);
+    if let Some(dev_shell) = dev_env::select(args.env, &args.project_path)? {
+        say!("🐚 Environment: {}", dev_shell.describe());
+        dev_shell.prepare()?;
+        compiler = compiler.with_dev_shell(dev_shell);
+    }
+    if let Some(job_pools) = job_pools {
+        compiler = compiler.with_job_pools(job_pools);
+    }
+    if let Some(events) = &events {
+        compiler = compiler.with_events(events.clone());
+    }
+    if args.stream_output {
+        compiler = compiler.with_stream_output();
+    }
+    if args.retries > 0 {
+        compiler = compiler.with_retries(args.retries, std::time::Duration::from_secs(args.retry_backoff.unwrap_or_default()));
+    }
+    let build_info = args.version_stamp.then(|| build_info::BuildInfo::collect(&args.project_path, config));
+    if let Some(build_info) = &build_info {
+        let output_dir = config.output_dir(&args.project_path).unwrap_or_else(|| args.project_path.clone());
+        let path = build_info.save(&output_dir)?;
+        say!("🏷️  Version stamp: {} ({})", build_info.version, path.display());
+        compiler = compiler.with_build_info(build_info.clone());
+    }
+
+    hooks::run(HookStage::PreBuild, config, &args.project_path, None)?;
+
+    let mut summary = compiler::BuildSummary::default();
+    let mut failed_stage: Option<String> = None;
+    let mut toolchains = None;
+    for stage in &stages {
+        if let Some(failed) = &failed_stage {
+            say!("⏭️  Skipping stage {} ({} failed)", stage.name, failed);
+            continue;
+        }
+        if custom_pipeline {
+            say!("🧱 Stage: {}", stage.name.bold());
+        }
+        tracing::info!(stage = %stage.name, "Running stage");
+        events::emit(events.as_ref(), events::BuildEvent::Stage { name: stage.name.clone() });
+
+        match pipeline::kind(stage) {
+            StageKind::Generate => codegen::run(config, &args.project_path)?,
+            StageKind::Compile => {
+                // Detect source files
+                let file_detector = FileDetector::new();
+                let source_files = file_detector.detect_files(&args.project_path, args, config)?;
+
+                // Later stages and the hooks still run, since they may not need sources
+                if source_files.is_empty() {
+                    tracing::warn!(stage = %stage.name, "No source files found to compile.");
+                    continue;
+                }
+
+                // Display detected files
+                say!("📋 Detected source files:");
+                for (lang, files) in &source_files {
+                    say!("  {}: {} files", lang.name().bold(), files.len());
+                    if args.verbose {
+                        for file in files {
+                            say!("    {}", file.display());
+                        }
+                    }
+                }
+                say!();
+
+                let current = lockfile::Lockfile::current(&compiler, source_files.keys());
+                lockfile::verify(&args.project_path, &current, args.locked)?;
+                toolchains = Some(current);
+
+                // Initialize progress bars
+                let multi_progress = logging::progress();
+                let progress_style = ProgressStyle::default_bar()
+                    .template("[{elapsed_precise}] {bar:40.cyan/blue} {pos:>7}/{len:7} {msg}")
+                    .unwrap()
+                    .progress_chars("#>-");
+
+                // Compile files
+                compiler.plugins().run_hook(&HookEvent { hook: "pre_build", project: &args.project_path, success: None })?;
+                summary = compiler
+                    .compile_all(source_files, multi_progress, &progress_style, args)
+                    .await?;
+                if summary.failure().is_some() {
+                    failed_stage = Some(stage.name.clone());
+                }
+            }
+            StageKind::Commands => {
+                if let Err(error) = pipeline::run_commands(stage, config, &args.project_path, args.jobs) {
+                    say_error!("{} {:#}", "❌".red(), error);
+                    failed_stage = Some(stage.name.clone());
+                }
+            }
+        }
+    }
+
+    if let Some(snapshot) = &snapshot {
+        let written = written_into_sources(snapshot, &args.project_path, config);
+        if !written.is_empty() {
+            say_error!("{} {} file(s) written into the source tree despite --build-dir:", "❌".red(), written.len());
+            for path in &written {
+                say_error!("   {}", path.display());
             }
+            failed_stage.get_or_insert_with(|| "out-of-tree check".to_string());
         }
     }
-    println!();
 
-    // Initialize progress bars
-    let multi_progress = MultiProgress::new();
-    let progress_style = ProgressStyle::default_bar()
-        .template("[{elapsed_precise}] {bar:40.cyan/blue} {pos:>7}/{len:7} {msg}")
-        .unwrap()
-        .progress_chars("#>-");
+    let success = failed_stage.is_none();
+    compiler.plugins().run_hook(&HookEvent { hook: "post_build", project: &args.project_path, success: Some(success) })?;
+    hooks::run(HookStage::PostBuild, config, &args.project_path, Some(&summary.results))?;
+    let outcome = if success { HookStage::OnSuccess } else { HookStage::OnFailure };
+    hooks::run(outcome, config, &args.project_path, Some(&summary.results))?;
+    let notification = notifications::Summary::new(&args.project_path, &summary.results, failed_stage.as_deref(), started.elapsed());
+    notifications::send(&config.notifications, &notification);
 
-    // Compile files
-    let compiler = Compiler::new(config, args.jobs);
-    let results = compiler
-        .compile_all(source_files, &multi_progress, &progress_style, &args)
-        .await?;
+    Ok(BuildOutcome { compiler, summary, failed_stage, build_info, toolchains })
+}
 
-    // Display results
-    display_results(&results, args.verbose);
+fn clean(project_path: &std::path::Path) -> Result<()> {
+    let config = Config::load_for_project(project_path).context("Failed to load configuration")?;
+    // A `generated/` lol did not write belongs to the project
+    let generated_dir = codegen::owns_generated_dir(&config, project_path).then(|| config.generated_dir(project_path));
+    let mut removed = false;
 
+    // Artifacts written next to their sources are only known from the manifest
+    let manifest = match config.output_dir(project_path) {
+        Some(output_dir) => artifacts::ArtifactManifest::load(&output_dir)?,
+        None => None,
+    };
+    for artifact in manifest.iter().flat_map(|manifest| &manifest.artifacts) {
+        let path = project_path.join(&artifact.path);
+        if !path.is_file() {
+            continue;
+        }
+        if artifacts::sha256(&path)? != artifact.sha256 {
+            tracing::warn!("Keeping {}: changed since it was built", path.display());
+            continue;
+        }
+        std::fs::remove_file(&path).with_context(|| format!("Failed to remove {}", path.display()))?;
+        removed = true;
+    }
+
+    for dir in config.output_dir(project_path).into_iter().chain(generated_dir) {
+        if dir.is_dir() {
+            std::fs::remove_dir_all(&dir).with_context(|| format!("Failed to remove {}", dir.display()))?;
+            say!("🧹 Removed {}", dir.display());
+            removed = true;
+        }
+    }
+
+    if !removed {
+        say!("✨ Nothing to clean.");
+    }
     Ok(())
 }
 
-async fn create_appimage(args: &Args, config: &Config, app_name: &str) -> Result<()> {
-    println!("🔍 Scanning for source files...");
+fn migrate_configs(project_path: &std::path::Path, to: Option<config::ConfigFormat>) -> Result<()> {
+    for path in [Config::get_config_path()?, config::project_config_path(project_path)?] {
+        if !path.is_file() {
+            continue;
+        }
+        let migration = config::migrate_config_file(&path, to)?;
+        if migration.path != path {
+            say!("🔁 Converted {} to {}", path.display(), migration.path.display());
+        }
+        if migration.patterns.is_empty() {
+            say!("🔁 {} already uses glob patterns", migration.path.display());
+            continue;
+        }
+        say!("🔁 Migrated {} patterns in {}", migration.patterns.len(), migration.path.display());
+        for (old, new) in &migration.patterns {
+            say!("   {} → {}", old, new);
+        }
+    }
+    Ok(())
+}
+
+fn check_licenses(args: &Args, project_path: &std::path::Path, fix: bool) -> Result<()> {
+    let config = Config::load_for_project(project_path).context("Failed to load configuration")?;
+    if config.license.template.is_none() && config.license.templates.is_empty() {
+        anyhow::bail!("No license policy configured; set license.template in lol.json");
+    }
+
+    let project_args = Args { project_path: project_path.to_path_buf(), ..args.clone() };
+    let source_files = FileDetector::new().detect_files(project_path, &project_args, &config)?;
+    let root = project_path.canonicalize().unwrap_or_else(|_| project_path.to_path_buf());
+    let year = &sbom::timestamp()[..4];
+    let diagnostics = license::check(&config.license, &config.custom_languages, &source_files, &root, fix, year)?;
+
+    for diagnostic in &diagnostics {
+        say!("{}", diagnostic);
+    }
+    let checked: usize = source_files.values().map(Vec::len).sum();
+    if fix {
+        say!("🪪 Added license headers to {} of {} files", diagnostics.len(), checked);
+    } else if diagnostics.is_empty() {
+        say!("🪪 All {} files have license headers", checked);
+    } else {
+        anyhow::bail!("{} of {} files are missing license headers", diagnostics.len(), checked);
+    }
+    Ok(())
+}
+
+fn write_sbom(
+    args: &Args,
+    project_path: &std::path::Path,
+    format: sbom::SbomFormat,
+    output: Option<&std::path::Path>,
+) -> Result<()> {
+    let config = Config::load_for_project(project_path).context("Failed to load configuration")?;
+    let project_args = Args { project_path: project_path.to_path_buf(), ..args.clone() };
+    let source_files = FileDetector::new().detect_files(project_path, &project_args, &config)?;
+    let root = project_path.canonicalize().unwrap_or_else(|_| project_path.to_path_buf());
+
+    let mut inventory = sbom::Inventory {
+        name: root.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_else(|| "project".to_string()),
+        ..Default::default()
+    };
+    for (language, files) in &source_files {
+        for file in files {
+            inventory.files.push(sbom::SourceFile {
+                path: file.strip_prefix(&root).unwrap_or(file).to_path_buf(),
+                language: language.name().to_string(),
+                sha256: artifacts::sha256(file)?,
+            });
+        }
+    }
+    inventory.files.sort_by(|a, b| a.path.cmp(&b.path));
+
+    let compiler = Compiler::new(config.clone(), 1);
+    let mut toolchains: Vec<_> = compiler.get_compiler_info_for(source_files.keys()).into_iter().collect();
+    toolchains.sort_by(|a, b| a.0.name().cmp(b.0.name()));
+    for (language, version) in toolchains {
+        if version == "Not available" {
+            continue;
+        }
+        inventory.toolchains.push(sbom::Toolchain {
+            language: language.name().to_string(),
+            tool: language.get_compiler_command().0.to_string(),
+            version,
+        });
+    }
+    inventory.dependencies = sbom::declared_dependencies(project_path, &config)?;
+
+    let path = match output {
+        Some(path) => path.to_path_buf(),
+        None => config
+            .output_dir(project_path)
+            .unwrap_or_else(|| project_path.to_path_buf())
+            .join(format.file_name()),
+    };
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    let document = sbom::render(format, &inventory, &sbom::timestamp());
+    std::fs::write(&path, serde_json::to_string_pretty(&document)?)
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+
+    say!(
+        "📜 SBOM: {} ({} files, {} toolchains, {} dependencies)",
+        path.display(),
+        inventory.files.len(),
+        inventory.toolchains.len(),
+        inventory.dependencies.len()
+    );
+    Ok(())
+}
+
+async fn create_appimage(args: &Args, config: &Config, app_name: &str, plugins: Arc<PluginHost>) -> Result<()> {
+    say!("🔍 Scanning for source files...");
     
     // Detect source files
     let file_detector = FileDetector::new();
     let source_files = file_detector.detect_files(&args.project_path, args, config)?;
 
     if source_files.is_empty() {
-        println!("{} No source files found to include in AppImage.", "⚠️".yellow());
+        tracing::warn!("No source files found to include in AppImage.");
         return Ok(());
     }
 
     // Display what will be included
-    println!("📋 Files to include in AppImage:");
+    say!("📋 Files to include in AppImage:");
     for (lang, files) in &source_files {
-        println!("  {}: {} files", lang.name().bold(), files.len());
+        say!("  {}: {} files", lang.name().bold(), files.len());
         if args.verbose {
             for file in files {
-                println!("    {}", file.display());
+                say!("    {}", file.display());
             }
         }
     }
-    println!();
+    say!();
+
+    // Build first so compiled executables ship instead of sources alone
+    say!("🔨 Building the project before packaging...");
+    let outcome = build_project(args, config, plugins, None, None, None).await?;
+    if !outcome.summary.results.is_empty() {
+        display_results(&outcome.summary, args, config);
+    }
+    outcome.check()?;
+    let entry = match &args.entry {
+        Some(file) => Some(entry_point::from_override(&args.project_path, file, &source_files)?),
+        None => entry_point::detect(&args.project_path, &source_files),
+    };
+    if let Some(entry) = &entry {
+        say!("🎯 Entry point: {} ({})", entry.file.display(), entry.language.name());
+    }
+
+    let output_dir = config.output_dir(&args.project_path).unwrap_or_else(|| args.project_path.clone());
+    // Only executables the compilers reported ship, never whatever else
+    // happens to be executable in the project
+    let canonical = |path: &std::path::Path| path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let mut built: Vec<&compiler::BuiltFile> = outcome.summary.outputs.iter().filter(|output| appimage::is_executable(&output.path)).collect();
+    // The executable built from the entry point goes first, so it is run
+    let entry_file = entry.as_ref().map(|entry| canonical(&entry.file));
+    built.sort_by_key(|output| Some(canonical(&output.source)) != entry_file);
+    let mut binaries: Vec<std::path::PathBuf> = built.iter().map(|output| output.path.clone()).collect();
+    // C and C++ builds stop at object files; link them when one has main()
+    if let Some(entry) = entry.as_ref().filter(|entry| matches!(entry.language, Language::C | Language::Cpp)) {
+        if built.first().is_none_or(|output| Some(canonical(&output.source)) != entry_file) {
+            let collected = outcome.collect_artifacts(&args.project_path, config)?.artifacts;
+            let executable = output_dir.join(entry.file.file_stem().unwrap_or_default());
+            entry_point::link_objects(&collected, &args.project_path, &[], &executable)?;
+            binaries.insert(0, executable);
+        }
+    }
 
     // Create AppImage
-    println!("🏗️  Building AppImage...");
-    let appimage_builder = AppImageBuilder::new(app_name.to_string(), source_files);
+    say!("🏗️  Building AppImage...");
+    let use_package_compiler = source_files.contains_key(&Language::Julia)
+        && config
+            .get_language_config(Language::Julia.config_key())
+            .is_some_and(|settings| settings.package_compiler);
+    let has_ruby = source_files.contains_key(&Language::Ruby);
+    let python_dominant = appimage::is_dominant(&source_files, &[Language::Python]);
+    let node_dominant = appimage::is_dominant(&source_files, &[Language::JavaScript, Language::TypeScript]);
+    let mut appimage_builder = AppImageBuilder::new(app_name.to_string(), source_files)
+        .with_secret_scan(config.secret_scan, args.project_path.clone())
+        .with_app_id(flatpak::app_id(config, &args.project_path, app_name));
+    if use_package_compiler {
+        say!("🔬 Compiling Julia app with PackageCompiler...");
+        appimage_builder = appimage_builder.with_julia_app(args.project_path.clone());
+    }
+    if python_dominant {
+        let python_settings = config.get_language_config(Language::Python.config_key());
+        let python_path = python_settings.and_then(|settings| settings.python_path.as_deref());
+        let python = python_env::PythonEnvironment::detect(&args.project_path, python_path).interpreter;
+        let setting = python_settings.and_then(|settings| settings.bundler.as_deref());
+        match appimage::PythonBundler::select(setting, &python)? {
+            Some(bundler) => {
+                say!("🐍 Bundling a Python interpreter with {:?}...", bundler);
+                appimage_builder = appimage_builder.with_python_app(args.project_path.clone(), python, bundler);
+            }
+            None => tracing::warn!("The AppImage will need a system python3; install PyInstaller or python-appimage to bundle one"),
+        }
+    }
+    if node_dominant {
+        let environment = node_env::NodeEnvironment::detect(&args.project_path);
+        let setting = config
+            .get_language_config(Language::JavaScript.config_key())
+            .and_then(|settings| settings.bundler.as_deref());
+        match appimage::NodeBundler::select(setting, &environment)? {
+            Some(bundler) => {
+                say!("📦 Building a single Node.js executable with {:?}...", bundler);
+                appimage_builder = appimage_builder.with_node_app(args.project_path.clone(), environment, bundler);
+            }
+            None => tracing::warn!("The AppImage will need a system node; install esbuild with postject or pkg to bundle one"),
+        }
+    }
+    if has_ruby {
+        appimage_builder = appimage_builder.with_ruby_app(args.project_path.clone());
+    }
+    if let Some(entry) = entry {
+        appimage_builder = appimage_builder.with_entry(args.project_path.clone(), entry);
+    }
+    if let Some(icon) = &args.icon {
+        appimage_builder = appimage_builder.with_icon(icon.clone());
+    }
+    if !args.desktop_categories.is_empty() {
+        appimage_builder = appimage_builder.with_categories(args.desktop_categories.clone());
+    }
+    if let Some(version) = &args.app_version {
+        appimage_builder = appimage_builder.with_version(version.clone());
+    }
+    if let Some(update_info) = &args.update_info {
+        appimage_builder = appimage_builder.with_update_info(update_info.clone())?;
+    }
+    if let Some(license) = &config.license.spdx {
+        appimage_builder = appimage_builder.with_license(license.clone());
+    }
+    if let Some(build_info) = &outcome.build_info {
+        appimage_builder = appimage_builder.with_build_info(build_info.clone());
+    }
+    if !binaries.is_empty() {
+        say!("📦 Bundling {} executables and their shared libraries", binaries.len());
+        appimage_builder = appimage_builder.with_binaries(binaries);
+    }
+    if let Some(key) = &args.sign {
+        appimage_builder = appimage_builder.with_signing(Some(key.clone()).filter(|key| !key.is_empty()));
+    }
+    if args.reproducible {
+        appimage_builder = appimage_builder.with_source_date_epoch(reproducible::source_date_epoch(&args.project_path));
+    }
     
     // Show source summary
     if args.verbose {
-        println!("{}", appimage_builder.get_source_summary());
+        say!("{}", appimage_builder.get_source_summary());
     }
     
     let appimage_path = appimage_builder.build()?;
+    if let Some(package_dir) = appimage_path.parent() {
+        let manifest = artifacts::package_manifest(package_dir)?;
+        let path = manifest.save(package_dir)?;
+        say!("🧾 Artifact manifest: {} ({} files)", path.display(), manifest.artifacts.len());
+        // `lol verify` only trusts the checksums of a signed manifest
+        if let Some(key) = &args.sign {
+            signing::sign_detached(&path, Some(key.as_str()).filter(|key| !key.is_empty()))?;
+        }
+    }
     
-    println!("✅ AppImage created successfully!");
-    println!("📦 Output: {}", appimage_path.display());
-    println!("\n🚀 You can now run your AppImage:");
-    println!("   ./{}", appimage_path.file_name().unwrap().to_string_lossy());
+    say!("✅ AppImage created successfully!");
+    say!("📦 Output: {}", appimage_path.display());
+    if appimage_path.extension().is_some_and(|ext| ext == "AppImage") {
+        say!("\n🚀 You can now run your AppImage:");
+        say!("   ./{}", appimage_path.file_name().unwrap().to_string_lossy());
+    }
     
     Ok(())
 }
 
-fn display_results(results: &[compiler::CompilationResult], verbose: bool) {
-    println!("\n📊 Compilation Results:");
-    println!("{}", "=".repeat(50));
+/// Builds the project, then bundles what it produced into a distribution
+/// archive.
+async fn package(args: &Args, config: &Config, format: package::PackageFormat, plugins: Arc<PluginHost>) -> Result<()> {
+    let outcome = build_project(args, config, plugins, None, None, None).await?;
+    if !outcome.summary.results.is_empty() {
+        display_results(&outcome.summary, args, config);
+    }
+    outcome.check()?;
+    if let Some(jar_path) = outcome.compiler.package_jar(&outcome.summary.results, &args.project_path)? {
+        say!("☕ Packaged JAR: {}", jar_path.display());
+    }
+    record_artifacts(&outcome, &args.project_path, config, false)?;
+
+    let sources = FileDetector::new().detect_files(&args.project_path, args, config)?;
+    let archive = package::create(format, &args.project_path, config, &sources)?;
+    say!("📦 Package: {}", archive.display());
+    Ok(())
+}
+
+/// Rebuilds the project whenever one of its sources or its `lol.json`
+/// changes, reloading the configuration each time. With `serve`, the
+/// script is run and restarted after every rebuild that passes; a failing
+/// rebuild leaves the previous instance running.
+async fn watch(args: &Args, serve: Option<&std::path::Path>, metrics_port: Option<u16>, plugins: Arc<PluginHost>) -> Result<()> {
+    let mut server = match serve {
+        Some(entry) => {
+            let config = Config::load_for_project(&args.project_path).context("Failed to load configuration")?;
+            let sources = FileDetector::new().detect_files(&args.project_path, args, &config)?;
+            let entry = entry_point::from_override(&args.project_path, entry, &sources)?;
+            let interpreter = package::interpreter(&entry.language)
+                .with_context(|| format!("--serve needs a script; {} is not an interpreted language", entry.language.name()))?;
+            Some(watch::Server::new(interpreter, &args.project_path, entry.file))
+        }
+        None => None,
+    };
+    let metrics = metrics::Metrics::new();
+    if let Some(port) = metrics_port {
+        let listener = tokio::net::TcpListener::bind(("127.0.0.1", port))
+            .await
+            .with_context(|| format!("Failed to listen on port {}", port))?;
+        say!("📈 Metrics at http://{}/metrics", listener.local_addr()?);
+        tokio::spawn(metrics::serve(listener, metrics.clone()));
+    }
+
+    // Warm compilers stay up between rebuilds, until watching stops
+    let compiler_pool = Arc::new(CompilerPool::new());
+    let mut fingerprint = watch::Fingerprint::default();
+    loop {
+        let mut config = Config::load_for_project(&args.project_path).context("Failed to load configuration")?;
+        apply_build_dir(&mut config, args);
+        config.custom_languages.extend(plugins.languages());
+        let sources = FileDetector::new().detect_files(&args.project_path, args, &config)?;
+        fingerprint = watch::Fingerprint::take(&args.project_path, sources.values().flatten(), &fingerprint);
+
+        let started = Instant::now();
+        metrics.observe(&events::BuildEvent::Started);
+        let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+        let outcome = build_project(args, &config, Arc::clone(&plugins), None, Some(sender), Some(Arc::clone(&compiler_pool))).await?;
+        record_artifacts(&outcome, &args.project_path, &config, false)?;
+        let mut passed = outcome.failed_stage.is_none();
+        for result in &outcome.summary.results {
+            if let compiler::CompilationStatus::Failure { error } = &result.status {
+                say_error!("❌ {}: {}", result.language.name().bold().red(), error);
+                passed = false;
+            }
+        }
+        while let Ok(event) = receiver.try_recv() {
+            metrics.observe(&event);
+        }
+        metrics.observe(&events::BuildEvent::Finished { success: passed, duration_ms: started.elapsed().as_millis() as u64 });
+        match &mut server {
+            Some(server) if passed => server.restart()?,
+            Some(server) if server.is_running() => tracing::warn!("Build failed; keeping the running version"),
+            _ if passed => say!("✅ {}", "Build passed".green()),
+            _ => say_error!("❌ {}", "Build failed".red()),
+        }
+        say!("👀 Watching {} for changes (Ctrl-C to stop)", args.project_path.display());
+
+        loop {
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => {
+                    if let Some(server) = &mut server {
+                        server.stop();
+                    }
+                    return Ok(());
+                }
+                _ = tokio::time::sleep(watch::POLL_INTERVAL) => {}
+            }
+            if let Some(server) = &mut server {
+                server.check_exit();
+            }
+            let sources = FileDetector::new().detect_files(&args.project_path, args, &config)?;
+            let current = watch::Fingerprint::take(&args.project_path, sources.values().flatten(), &fingerprint);
+            let changes = current.changes(&fingerprint);
+            // Kept even without changes, so touched files are not hashed again every poll
+            fingerprint = current;
+            if !changes.is_empty() {
+                let root = args.project_path.canonicalize().unwrap_or_else(|_| args.project_path.clone());
+                for change in &changes {
+                    say!("\n🔄 Changed: {}", change.strip_prefix(&root).unwrap_or(change).display());
+                }
+                break;
+            }
+        }
+    }
+}
+
+/// `lol watch --tui`: the watch loop behind an interactive UI. Builds run
+/// in the background while the UI keeps drawing; their output goes to the
+/// output pane.
+async fn watch_tui(args: &Args) -> Result<()> {
+    if !args.project_path.is_dir() {
+        anyhow::bail!("Project path is not a directory: {:?}", args.project_path);
+    }
+    let config = Config::load_for_project(&args.project_path).context("Failed to load configuration")?;
+    apply_priority(args, &config, true)?;
+    let plugins = Arc::new(PluginHost::load());
+    let compiler_pool = Arc::new(CompilerPool::new());
+    let mut session = tui::Session::start()?;
+    let mut app = tui::App::new(&args.project_path);
+    let mut fingerprint = watch::Fingerprint::default();
+    let mut next_poll = Instant::now();
+    let mut rebuild = true;
+    let mut build: Option<(tokio::task::JoinHandle<Result<BuildOutcome>>, Config, Instant)> = None;
+
+    loop {
+        session.draw(&mut app)?;
+        if let Some(key) = session.poll_key(std::time::Duration::from_millis(100))? {
+            match app.handle_key(key) {
+                tui::Action::Quit => return Ok(()),
+                tui::Action::Rebuild | tui::Action::Toggle(_) => rebuild = true,
+                tui::Action::Edit(diagnostic) => session.edit(&diagnostic)?,
+                tui::Action::Nothing => {}
+            }
+        }
+
+        if build.as_ref().is_some_and(|(handle, _, _)| handle.is_finished()) {
+            let (handle, config, started) = build.take().expect("checked above");
+            match handle.await.context("Watch build panicked")? {
+                Ok(outcome) => {
+                    if let Err(error) = record_artifacts(&outcome, &args.project_path, &config, false) {
+                        tracing::warn!("{:#}", error);
+                    }
+                    app.build_finished(&outcome.summary.results, outcome.failed_stage.as_deref(), started.elapsed());
+                }
+                Err(error) => {
+                    say_error!("❌ {:#}", error);
+                    app.build_finished(&[], Some("setup"), started.elapsed());
+                }
+            }
+        }
+        if build.is_some() || Instant::now() < next_poll {
+            continue;
+        }
+        next_poll = Instant::now() + watch::POLL_INTERVAL;
+
+        // Reloaded every poll, so edits to lol.json apply to the next build
+        let mut config = match Config::load_for_project(&args.project_path) {
+            Ok(config) => config,
+            Err(error) => {
+                say_error!("❌ {:#}", error);
+                continue;
+            }
+        };
+        apply_build_dir(&mut config, args);
+        config.custom_languages.extend(plugins.languages());
+        for language in app.disabled() {
+            config.language_settings.entry(language.config_key().to_string()).or_default().enabled = false;
+        }
+        let sources = FileDetector::new().detect_files(&args.project_path, args, &config)?;
+        app.set_sources(&sources);
+        let current = watch::Fingerprint::take(&args.project_path, sources.values().flatten(), &fingerprint);
+        if !current.changes(&fingerprint).is_empty() {
+            rebuild = true;
+        }
+        fingerprint = current;
+
+        if rebuild {
+            rebuild = false;
+            app.build_started();
+            let (build_args, build_config, plugins) = (args.clone(), config.clone(), Arc::clone(&plugins));
+            let compiler_pool = Arc::clone(&compiler_pool);
+            let handle = tokio::spawn(async move { build_project(&build_args, &build_config, plugins, None, None, Some(compiler_pool)).await });
+            build = Some((handle, config, Instant::now()));
+        }
+    }
+}
+
+/// `lol lsp`: a language server whose only feature is diagnostics. The
+/// project is rebuilt when the editor saves a file or any source changes,
+/// and every build's diagnostics are published to the editor.
+async fn lsp_server(args: &Args, project_path: &std::path::Path) -> Result<()> {
+    let mut connection = lsp::Connection::stdio()?;
+    let config = Config::load_for_project(project_path).context("Failed to load configuration")?;
+    apply_priority(args, &config, true)?;
+    let plugins = Arc::new(PluginHost::load());
+    let compiler_pool = Arc::new(CompilerPool::new());
+    let mut args = Args { project_path: project_path.to_path_buf(), ..args.clone() };
+    let mut publisher = lsp::Publisher::default();
+    let mut fingerprint = watch::Fingerprint::default();
+    let mut initialized = false;
+    let mut rebuild = false;
+    let mut build: Option<tokio::task::JoinHandle<Result<BuildOutcome>>> = None;
+
+    loop {
+        tokio::select! {
+            message = connection.incoming.recv() => match message.transpose()? {
+                // The editor went away without `exit`
+                None => return Ok(()),
+                Some(lsp::Message::Request { id, method, params }) => match method.as_str() {
+                    "initialize" => {
+                        if let Some(root) = lsp::root_path(&params) {
+                            args.project_path = root;
+                        }
+                        connection.respond(id, lsp::initialize_result())?;
+                    }
+                    "shutdown" => connection.respond(id, serde_json::Value::Null)?,
+                    _ => connection.respond_unknown(id, &method)?,
+                },
+                Some(lsp::Message::Notification { method, .. }) => match method.as_str() {
+                    "initialized" => {
+                        initialized = true;
+                        rebuild = true;
+                    }
+                    "textDocument/didSave" => rebuild = true,
+                    "exit" => return Ok(()),
+                    _ => {}
+                },
+            },
+            _ = tokio::time::sleep(watch::POLL_INTERVAL) => {}
+        }
+        if !initialized {
+            continue;
+        }
+
+        if build.as_ref().is_some_and(|handle| handle.is_finished()) {
+            let handle = build.take().expect("checked above");
+            match handle.await.context("Build panicked")? {
+                Ok(outcome) => {
+                    let diagnostics: Vec<diagnostics::Diagnostic> =
+                        outcome.summary.results.iter().flat_map(|result| result.diagnostics.iter().cloned()).collect();
+                    for params in publisher.publish(&args.project_path, &diagnostics) {
+                        connection.notify("textDocument/publishDiagnostics", params)?;
+                    }
+                    let failed: Vec<&str> = outcome
+                        .summary
+                        .results
+                        .iter()
+                        .filter(|result| matches!(result.status, compiler::CompilationStatus::Failure { .. }))
+                        .map(|result| result.language.name())
+                        .collect();
+                    match (&outcome.failed_stage, failed.is_empty()) {
+                        (None, _) => connection.log("lol: build passed")?,
+                        (Some(_), false) => connection.log(&format!("lol: build failed ({})", failed.join(", ")))?,
+                        (Some(stage), true) => connection.log(&format!("lol: stage {} failed", stage))?,
+                    }
+                }
+                Err(error) => connection.log(&format!("lol: {:#}", error))?,
+            }
+        }
+        if build.is_some() {
+            continue;
+        }
+
+        let mut config = Config::load_for_project(&args.project_path).context("Failed to load configuration")?;
+        apply_build_dir(&mut config, &args);
+        config.custom_languages.extend(plugins.languages());
+        let sources = FileDetector::new().detect_files(&args.project_path, &args, &config)?;
+        let current = watch::Fingerprint::take(&args.project_path, sources.values().flatten(), &fingerprint);
+        if !current.changes(&fingerprint).is_empty() {
+            rebuild = true;
+        }
+        fingerprint = current;
+        if rebuild {
+            rebuild = false;
+            let (build_args, plugins, compiler_pool) = (args.clone(), Arc::clone(&plugins), Arc::clone(&compiler_pool));
+            build = Some(tokio::spawn(async move { build_project(&build_args, &config, plugins, None, None, Some(compiler_pool)).await }));
+        }
+    }
+}
+
+/// `lol daemon`: serves the build API and runs the builds it queues, one
+/// at a time, until interrupted.
+async fn run_daemon(host: &str, port: u16, dashboard: bool) -> Result<()> {
+    let plugins = Arc::new(PluginHost::load());
+    let compiler_pool = Arc::new(CompilerPool::new());
+    let (daemon, mut jobs) = daemon::Daemon::new();
+    let daemon = daemon.with_dashboard(dashboard);
+    let listener = tokio::net::TcpListener::bind((host, port))
+        .await
+        .with_context(|| format!("Failed to listen on {}:{}", host, port))?;
+    say!("🛰️  lol daemon listening on http://{}", listener.local_addr()?);
+    if dashboard {
+        say!("📈 Dashboard at http://{}/", listener.local_addr()?);
+    }
+    let server = tokio::spawn(daemon::serve(listener, daemon.clone()));
+
+    loop {
+        let job = tokio::select! {
+            job = jobs.recv() => Some(job),
+            _ = tokio::signal::ctrl_c() => None,
+        };
+        let Some(job) = job else {
+            break;
+        };
+        say!("\n🛰️  Build {}: {}", job.id, job.args.project_path.display());
+        let result = run_daemon_build(&daemon, &job, Arc::clone(&plugins), Arc::clone(&compiler_pool)).await;
+        daemon.finish(job.id, result);
+    }
+    server.abort();
+    Ok(())
+}
+
+async fn run_daemon_build(
+    daemon: &daemon::Daemon,
+    job: &daemon::Job,
+    plugins: Arc<PluginHost>,
+    compiler_pool: Arc<CompilerPool>,
+) -> daemon::BuildResult {
+    let started = Instant::now();
+    daemon.record(job.id, events::BuildEvent::Started);
+    let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+    let forwarder = {
+        let (daemon, id) = (daemon.clone(), job.id);
+        tokio::spawn(async move {
+            while let Some(event) = receiver.recv().await {
+                daemon.record(id, event);
+            }
+        })
+    };
+
+    let built = async {
+        let mut config = Config::load_for_project(&job.args.project_path).context("Failed to load configuration")?;
+        config.custom_languages.extend(plugins.languages());
+        let outcome = build_project(&job.args, &config, plugins, None, Some(sender), Some(compiler_pool)).await?;
+        let artifacts = record_artifacts(&outcome, &job.args.project_path, &config, false)?;
+        anyhow::Ok((outcome, artifacts))
+    }
+    .await;
+    let mut result = match built {
+        Ok((outcome, artifacts)) => daemon::BuildResult {
+            success: outcome.failed_stage.is_none(),
+            diagnostics: outcome.summary.results.iter().flat_map(|result| result.diagnostics.iter().cloned()).collect(),
+            artifacts,
+            ..daemon::BuildResult::default()
+        },
+        Err(error) => daemon::BuildResult { error: Some(format!("{:#}", error)), ..daemon::BuildResult::default() },
+    };
+    // The compiler holding the sender is gone now, so this sees every event
+    let _ = forwarder.await;
+    result.duration_ms = started.elapsed().as_millis() as u64;
+    result
+}
+
+/// Writes the complete error output of every failed language to
+/// `lol-errors.log` in the output directory, for when the summary cut it short.
+fn write_error_log(results: &[compiler::CompilationResult], project_path: &Path, config: &Config) -> Result<std::path::PathBuf> {
+    let dir = config.output_dir(project_path).unwrap_or_else(|| project_path.to_path_buf());
+    std::fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let mut log = String::new();
+    for result in results {
+        if let compiler::CompilationStatus::Failure { error } = &result.status {
+            log.push_str(&format!("==> {} <==\n{}\n", result.language.name(), error.trim_end()));
+        }
+    }
+    let path = dir.join("lol-errors.log");
+    std::fs::write(&path, log).with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(path)
+}
+
+/// Prints what each language produced and a summary. How the run ends is
+/// left to the caller.
+fn display_results(summary: &compiler::BuildSummary, args: &Args, config: &Config) {
+    say!("\n📊 Compilation Results:");
+    say!("{}", "=".repeat(50));
 
     let mut total_files = 0;
     let mut successful_compilations = 0;
     let mut failed_compilations = 0;
+    // Errors still to list under --max-errors, and whether any were left out
+    let mut error_budget = args.max_errors;
+    let mut truncated = false;
+    let suggestions = suggestions::Suggestions::load(config.suggestion_rules.as_deref(), &args.project_path).unwrap_or_else(|error| {
+        tracing::warn!("{:#}; using the built-in suggestions only", error);
+        suggestions::Suggestions::load(None, &args.project_path).expect("built-in rules")
+    });
+
+    for result in &summary.results {
+        // A failed language keeps only its compiled files in `files`
+        let failed_files = summary.failed_files.get(&result.language).map_or(0, Vec::len);
+        total_files += result.files.len() + failed_files;
+        successful_compilations += result.files.len();
+        let mut hints = Vec::new();
 
-    for result in results {
-        total_files += result.files.len();
-        
         match &result.status {
             compiler::CompilationStatus::Success { output } => {
-                successful_compilations += result.files.len();
-                println!("✅ {}: {} files compiled successfully", 
+                say!("✅ {}: {} files compiled successfully", 
                     result.language.name().bold().green(), 
                     result.files.len()
                 );
-                if verbose && !output.is_empty() {
-                    println!("   Output: {}", output);
+                if args.verbose && !output.is_empty() {
+                    say!("   Output: {}", output);
                 }
             }
             compiler::CompilationStatus::Failure { error } => {
-                failed_compilations += result.files.len();
-                println!("❌ {}: {} files failed to compile", 
+                failed_compilations += failed_files;
+                say_error!("❌ {}: {} files failed to compile", 
                     result.language.name().bold().red(), 
-                    result.files.len()
+                    failed_files
                 );
-                if verbose {
-                    println!("   Error: {}", error);
+                // With --quiet this is the only place the compiler's errors appear
+                if args.verbose || output::is_quiet() {
+                    match args.max_errors.and_then(|max_errors| diagnostics::truncate_errors(error, max_errors)) {
+                        Some(shown) => {
+                            say_error!("   Error: {}", shown.trim_end());
+                            say_error!("   … truncated after {} errors", args.max_errors.unwrap_or_default());
+                            truncated = true;
+                        }
+                        None => say_error!("   Error: {}", error),
+                    }
                 }
+                hints = suggestions.suggest(&result.language, error);
+            }
+            compiler::CompilationStatus::ToolchainMissing { program, hint } => {
+                say_error!("⏭️  {}: skipped, `{}` is not installed", result.language.name().bold().yellow(), program);
+                hints.push(hint.clone());
             }
         }
+
+        if let Some(toolchain) = &result.toolchain {
+            say!("   🔧 Toolchain: {}", toolchain);
+        }
+        if result.retries > 0 {
+            say!("   🔁 Retried {} times after transient failures", result.retries);
+        }
+
+        if !result.diagnostics.is_empty() {
+            let errors = result
+                .diagnostics
+                .iter()
+                .filter(|d| d.severity == diagnostics::Severity::Error)
+                .count();
+            let warnings = result
+                .diagnostics
+                .iter()
+                .filter(|d| d.severity == diagnostics::Severity::Warning)
+                .count();
+            say!("   🔎 Diagnostics: {} errors, {} warnings", errors, warnings);
+            // Notes only explain the message before them
+            let reported = result.diagnostics.iter().filter(|d| d.severity != diagnostics::Severity::Note);
+            let mut duplicates = 0;
+            let entries: Vec<(diagnostics::Severity, String)> = if args.show_all_duplicates {
+                reported.map(|diagnostic| (diagnostic.severity, diagnostic.to_string())).collect()
+            } else {
+                let groups = diagnostics::group(reported);
+                duplicates = groups.iter().map(|group| group.count - 1).sum();
+                groups
+                    .iter()
+                    .map(|group| match group.count {
+                        1 => (group.first.severity, group.first.to_string()),
+                        count => (group.first.severity, format!("{} ({} times)", group.first, count)),
+                    })
+                    .collect()
+            };
+            let mut omitted = 0;
+            for (severity, entry) in entries {
+                if let (diagnostics::Severity::Error, Some(budget)) = (severity, error_budget.as_mut()) {
+                    if *budget == 0 {
+                        omitted += 1;
+                        continue;
+                    }
+                    *budget -= 1;
+                }
+                say!("      {}", entry);
+            }
+            if omitted > 0 {
+                say!("      {} more errors not shown (--max-errors)", omitted);
+                truncated = true;
+            }
+            if duplicates > 0 {
+                say!("      {} duplicates hidden; --show-all-duplicates lists them", duplicates);
+            }
+        }
+        for hint in hints {
+            say!("   💡 {}", hint);
+        }
+    }
+
+    if truncated {
+        match write_error_log(&summary.results, &args.project_path, config) {
+            Ok(path) => say_error!("📄 Full compiler output: {}", path.display()),
+            Err(error) => tracing::warn!("{:#}", error),
+        }
     }
 
-    println!("{}", "=".repeat(50));
-    println!("📈 Summary:");
-    println!("  Total files: {}", total_files);
-    println!("  Successful: {} {}", successful_compilations, "✅".green());
-    println!("  Failed: {} {}", failed_compilations, "❌".red());
+    say!("{}", "=".repeat(50));
+    say!("📈 Summary:");
+    say!("  Total files: {}", total_files);
+    say!("  Successful: {} {}", successful_compilations, "✅".green());
+    say!("  Failed: {} {}", failed_compilations, "❌".red());
+    let skipped = summary.results.iter().filter(|result| matches!(result.status, compiler::CompilationStatus::ToolchainMissing { .. })).count();
+    if skipped > 0 {
+        say!("  Skipped: {} languages without a compiler (--strict-toolchains fails instead) {}", skipped, "⏭️".yellow());
+    }
     
-    if failed_compilations == 0 {
-        println!("\n🎉 {} All files compiled successfully!", "SUCCESS".bold().green());
-    } else {
-        println!("\n⚠️  {} files failed to compile. Check the output above for details.", failed_compilations);
-        std::process::exit(1);
+    match summary.failure() {
+        None => say!("\n🎉 {} All files compiled successfully!", "SUCCESS".bold().green()),
+        Some(compiler::BuildFailure::ToolchainMissing) => {
+            say_error!("\n⚠️  Compilers not installed: {}. `lol doctor` shows how to install them.", summary.missing_toolchains.join(", "));
+        }
+        Some(compiler::BuildFailure::CompileErrors) => {
+            say_error!("\n⚠️  {} files failed to compile. Check the output above for details.", failed_compilations);
+        }
     }
-} 
\ No newline at end of file
+} 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[tokio::test]
+    async fn test_stages_after_an_empty_compile_still_run() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(
+            temp_dir.path().join("lol.toml"),
+            "[[stages]]\nname = \"compile\"\n\n[[stages]]\nname = \"stamp\"\ndepends_on = [\"compile\"]\ncommands = [\"echo done > stamped.txt\"]\n",
+        )
+        .unwrap();
+        let args = Args::parse_from(["lol", temp_dir.path().to_str().unwrap()]);
+        let config = Config::load_for_project(temp_dir.path()).unwrap();
+
+        let outcome = build_project(&args, &config, Arc::new(PluginHost::default()), None, None, None).await.unwrap();
+        assert!(outcome.failed_stage.is_none());
+        assert!(outcome.summary.results.is_empty());
+        assert!(temp_dir.path().join("stamped.txt").is_file());
+    }
+}
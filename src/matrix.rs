@@ -0,0 +1,101 @@
+use anyhow::Result;
+use clap::ValueEnum;
+use colored::*;
+use crate::args::{Profile, Target};
+
+/// One configuration of a `--matrix` build.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cell {
+    pub profile: Profile,
+    pub target: Option<Target>,
+}
+
+impl Cell {
+    /// A short name such as `release-wasm32`, also used as the cell's
+    /// subdirectory of the output directory.
+    pub fn name(&self) -> String {
+        format!("{}-{}", value_name(&self.profile), self.target.as_ref().map_or("host".to_string(), value_name))
+    }
+}
+
+fn value_name<T: ValueEnum>(value: &T) -> String {
+    value.to_possible_value().map(|value| value.get_name().to_string()).unwrap_or_default()
+}
+
+/// Expands `--matrix` axes such as `profile=debug,release` and
+/// `target=host,wasm32` into every combination. Axes that are not given keep
+/// the value from `--profile`/`--target`.
+pub fn cells(axes: &[String], profile: Profile, target: Option<Target>) -> Result<Vec<Cell>> {
+    let mut profiles = vec![profile];
+    let mut targets = vec![target];
+
+    for axis in axes {
+        let Some((key, values)) = axis.split_once('=') else {
+            anyhow::bail!("Invalid matrix axis '{}', expected KEY=VALUE[,VALUE...]", axis);
+        };
+        let values: Vec<&str> = values.split(',').map(str::trim).filter(|value| !value.is_empty()).collect();
+        if values.is_empty() {
+            anyhow::bail!("Matrix axis '{}' has no values", key);
+        }
+
+        match key.trim() {
+            "profile" => {
+                profiles = values
+                    .iter()
+                    .map(|value| Profile::from_str(value, true).map_err(|_| anyhow::anyhow!("Unknown profile '{}'", value)))
+                    .collect::<Result<_>>()?;
+            }
+            "target" => {
+                targets = values
+                    .iter()
+                    .map(|value| match *value {
+                        "host" => Ok(None),
+                        _ => Target::from_str(value, true)
+                            .map(Some)
+                            .map_err(|_| anyhow::anyhow!("Unknown target '{}'", value)),
+                    })
+                    .collect::<Result<_>>()?;
+            }
+            other => anyhow::bail!("Unknown matrix axis '{}' (expected profile or target)", other),
+        }
+    }
+
+    Ok(profiles
+        .iter()
+        .flat_map(|&profile| targets.iter().map(move |&target| Cell { profile, target }))
+        .collect())
+}
+
+/// One line per cell with its pass/fail state and file counts.
+pub fn summary(outcomes: &[(Cell, usize, usize)]) -> String {
+    let width = outcomes.iter().map(|(cell, _, _)| cell.name().len()).max().unwrap_or(0);
+    outcomes
+        .iter()
+        .map(|(cell, succeeded, failed)| {
+            let state = if *failed == 0 { "pass".green() } else { "FAIL".red() };
+            format!("  {:<width$}  {}  {} ok, {} failed\n", cell.name(), state, succeeded, failed)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cells_are_the_product_of_axes() {
+        let axes = vec!["profile=debug,release".to_string(), "target=host,wasm32".to_string()];
+        let names: Vec<String> = cells(&axes, Profile::Debug, None).unwrap().iter().map(Cell::name).collect();
+        assert_eq!(names, ["debug-host", "debug-wasm32", "release-host", "release-wasm32"]);
+
+        let only_targets = cells(&["target=wasm32".to_string()], Profile::Release, None).unwrap();
+        assert_eq!(only_targets, vec![Cell { profile: Profile::Release, target: Some(Target::Wasm32) }]);
+    }
+
+    #[test]
+    fn test_invalid_axes_are_rejected() {
+        assert!(cells(&["profile".to_string()], Profile::Debug, None).is_err());
+        assert!(cells(&["profile=fast".to_string()], Profile::Debug, None).is_err());
+        assert!(cells(&["arch=arm64".to_string()], Profile::Debug, None).is_err());
+    }
+}
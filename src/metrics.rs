@@ -0,0 +1,128 @@
+use anyhow::Result;
+use axum::extract::State;
+use axum::http::header;
+use axum::response::IntoResponse;
+use axum::routing::get;
+use axum::Router;
+use prometheus::{Encoder, Histogram, HistogramOpts, HistogramVec, IntCounter, IntGauge, Registry, TextEncoder};
+use tokio::net::TcpListener;
+use crate::events::BuildEvent;
+
+/// Bucket bounds in seconds, from a handful of small files to a large
+/// project.
+const DURATION_BUCKETS: &[f64] = &[0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0, 300.0];
+
+/// Build metrics in the Prometheus text format, fed from build events.
+/// Clones share the same values.
+#[derive(Clone)]
+pub struct Metrics {
+    registry: Registry,
+    builds_started: IntCounter,
+    builds_failed: IntCounter,
+    build_duration: Histogram,
+    compile_duration: HistogramVec,
+    queue_depth: IntGauge,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let registry = Registry::new();
+        let builds_started = IntCounter::new("lol_builds_started_total", "Builds started").unwrap();
+        let builds_failed = IntCounter::new("lol_builds_failed_total", "Builds that failed").unwrap();
+        let build_duration = Histogram::with_opts(
+            HistogramOpts::new("lol_build_duration_seconds", "Time taken by whole builds").buckets(DURATION_BUCKETS.to_vec()),
+        )
+        .unwrap();
+        let compile_duration = HistogramVec::new(
+            HistogramOpts::new("lol_compile_duration_seconds", "Time taken to compile each language")
+                .buckets(DURATION_BUCKETS.to_vec()),
+            &["language"],
+        )
+        .unwrap();
+        let queue_depth = IntGauge::new("lol_queue_depth", "Builds waiting to run").unwrap();
+        for collector in [
+            Box::new(builds_started.clone()) as Box<dyn prometheus::core::Collector>,
+            Box::new(builds_failed.clone()),
+            Box::new(build_duration.clone()),
+            Box::new(compile_duration.clone()),
+            Box::new(queue_depth.clone()),
+        ] {
+            // Names are fixed and distinct, so registering cannot fail
+            registry.register(collector).unwrap();
+        }
+        Self { registry, builds_started, builds_failed, build_duration, compile_duration, queue_depth }
+    }
+
+    pub fn observe(&self, event: &BuildEvent) {
+        match event {
+            BuildEvent::Started => self.builds_started.inc(),
+            BuildEvent::LanguageFinished { language, duration_ms, .. } => {
+                self.compile_duration.with_label_values(&[language]).observe(*duration_ms as f64 / 1000.0);
+            }
+            BuildEvent::Finished { success, duration_ms } => {
+                if !success {
+                    self.builds_failed.inc();
+                }
+                self.build_duration.observe(*duration_ms as f64 / 1000.0);
+            }
+            _ => {}
+        }
+    }
+
+    pub fn set_queue_depth(&self, depth: usize) {
+        self.queue_depth.set(depth as i64);
+    }
+
+    pub fn render(&self) -> String {
+        let mut buffer = Vec::new();
+        // Encoding into memory only fails on invalid metric families, which these are not
+        let _ = TextEncoder::new().encode(&self.registry.gather(), &mut buffer);
+        String::from_utf8(buffer).unwrap_or_default()
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub async fn handler(State(metrics): State<Metrics>) -> impl IntoResponse {
+    ([(header::CONTENT_TYPE, prometheus::TEXT_FORMAT)], metrics.render())
+}
+
+/// Serves `/metrics` alone, for `lol watch --metrics-port`.
+pub async fn serve(listener: TcpListener, metrics: Metrics) -> Result<()> {
+    let router = Router::new().route("/metrics", get(handler)).with_state(metrics);
+    axum::serve(listener, router).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metrics_from_events() {
+        let metrics = Metrics::new();
+        metrics.observe(&BuildEvent::Started);
+        metrics.observe(&BuildEvent::LanguageFinished {
+            language: "C".to_string(),
+            success: true,
+            files: 2,
+            diagnostics: 0,
+            duration_ms: 300,
+            toolchain: None,
+            retries: 0,
+        });
+        metrics.observe(&BuildEvent::Finished { success: false, duration_ms: 400 });
+        metrics.set_queue_depth(3);
+
+        let text = metrics.render();
+        assert!(text.contains("lol_builds_started_total 1\n"));
+        assert!(text.contains("lol_builds_failed_total 1\n"));
+        assert!(text.contains("lol_compile_duration_seconds_bucket{language=\"C\",le=\"0.5\"} 1\n"));
+        assert!(text.contains("lol_compile_duration_seconds_count{language=\"C\"} 1\n"));
+        assert!(text.contains("lol_queue_depth 3\n"));
+    }
+}
@@ -0,0 +1,90 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::OnceLock;
+
+/// Whether `program` is MSVC's `cl.exe`, by its file name. Either
+/// separator is accepted, since configurations are shared across machines.
+pub fn is_cl(program: &str) -> bool {
+    let name = program.rsplit(['/', '\\']).next().unwrap_or(program);
+    name.eq_ignore_ascii_case("cl") || name.eq_ignore_ascii_case("cl.exe")
+}
+
+/// `vswhere.exe`, which the Visual Studio Installer puts in the same place
+/// for every edition since 2017.
+fn vswhere() -> Option<PathBuf> {
+    let program_files = std::env::var_os("ProgramFiles(x86)")?;
+    Some(Path::new(&program_files).join("Microsoft Visual Studio").join("Installer").join("vswhere.exe")).filter(|path| path.is_file())
+}
+
+/// The newest Visual Studio or Build Tools installation with the C++ tools.
+fn installation() -> Option<PathBuf> {
+    let output = Command::new(vswhere()?)
+        .args(["-latest", "-products", "*", "-requires", "Microsoft.VisualStudio.Component.VC.Tools.x86.x64"])
+        .args(["-property", "installationPath"])
+        .output()
+        .ok()?;
+    let path = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (output.status.success() && !path.is_empty()).then(|| PathBuf::from(path))
+}
+
+/// The variables `vcvars64.bat` sets, which `cl.exe` and `link.exe` need:
+/// `PATH`, `INCLUDE`, `LIB` and the rest. Found once per run; `None` when
+/// Visual Studio's C++ tools are not installed.
+pub fn environment() -> Option<&'static [(String, String)]> {
+    static ENVIRONMENT: OnceLock<Option<Vec<(String, String)>>> = OnceLock::new();
+    ENVIRONMENT
+        .get_or_init(|| {
+            let vcvars = installation()?.join("VC").join("Auxiliary").join("Build").join("vcvars64.bat");
+            let output = vcvars_output(&vcvars)?;
+            Some(changed_variables(&output, |name| std::env::var(name).ok()))
+        })
+        .as_deref()
+}
+
+/// `set` run after `vcvars64.bat`, in one `cmd` so the variables survive.
+#[cfg(windows)]
+fn vcvars_output(vcvars: &Path) -> Option<String> {
+    use std::os::windows::process::CommandExt;
+    let output = Command::new("cmd")
+        .args(["/d", "/s", "/c"])
+        .raw_arg(format!("\"call \"{}\" >nul && set\"", vcvars.display()))
+        .output()
+        .ok()?;
+    output.status.success().then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(not(windows))]
+fn vcvars_output(_vcvars: &Path) -> Option<String> {
+    None
+}
+
+/// The `NAME=value` lines of `set` output whose value differs from `current`.
+fn changed_variables(set_output: &str, current: impl Fn(&str) -> Option<String>) -> Vec<(String, String)> {
+    set_output
+        .lines()
+        .filter_map(|line| line.split_once('='))
+        .filter(|(name, value)| !name.is_empty() && current(name).as_deref() != Some(*value))
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cl_and_vcvars_variables() {
+        assert!(is_cl("cl") && is_cl("CL.EXE") && is_cl(r"C:\VS\VC\Tools\MSVC\14.38\bin\Hostx64\x64\cl.exe"));
+        assert!(!is_cl("clang") && !is_cl("gcc"));
+
+        let set_output = "Path=C:\\VS\\bin;C:\\Windows\nINCLUDE=C:\\VS\\include\nUSERNAME=dev\n";
+        let current = |name: &str| match name {
+            "Path" => Some("C:\\Windows".to_string()),
+            "USERNAME" => Some("dev".to_string()),
+            _ => None,
+        };
+        let changed = changed_variables(set_output, current);
+        assert_eq!(changed.len(), 2);
+        assert_eq!(changed[1], ("INCLUDE".to_string(), "C:\\VS\\include".to_string()));
+    }
+}
@@ -0,0 +1,194 @@
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use serde_json::Value;
+
+/// Node.js project environment: local tool directory, package manager and
+/// the Node version the project asks for.
+#[derive(Debug, Clone, Default)]
+pub struct NodeEnvironment {
+    pub package_root: Option<PathBuf>,
+    pub package_manager: Option<String>,
+    pub required_version: Option<String>,
+    dependencies: Vec<String>,
+}
+
+impl NodeEnvironment {
+    /// Looks for the nearest `package.json` at or above `project_path`.
+    pub fn detect(project_path: &Path) -> Self {
+        let Some(package_root) = project_path
+            .ancestors()
+            .find(|dir| dir.join("package.json").is_file())
+        else {
+            return Self {
+                required_version: Self::nvmrc_version(project_path),
+                ..Self::default()
+            };
+        };
+
+        let manifest: Value = fs::read_to_string(package_root.join("package.json"))
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or(Value::Null);
+
+        let dependencies = ["dependencies", "devDependencies"]
+            .iter()
+            .filter_map(|key| manifest.get(key).and_then(Value::as_object))
+            .flat_map(|deps| deps.keys().cloned())
+            .collect();
+
+        let required_version = Self::nvmrc_version(package_root).or_else(|| {
+            manifest
+                .pointer("/engines/node")
+                .and_then(Value::as_str)
+                .map(str::to_string)
+        });
+
+        Self {
+            package_root: Some(package_root.to_path_buf()),
+            package_manager: Some(Self::package_manager(package_root, &manifest)),
+            required_version,
+            dependencies,
+        }
+    }
+
+    /// `node_modules/.bin` of the project, if it has been installed.
+    pub fn bin_dir(&self) -> Option<PathBuf> {
+        self.package_root
+            .as_ref()
+            .map(|root| root.join("node_modules").join(".bin"))
+            .filter(|dir| dir.is_dir())
+    }
+
+    /// Fails when `tool` is declared in `package.json` but has not been
+    /// installed into `node_modules/.bin`, rather than silently running
+    /// whatever happens to be on `PATH`.
+    pub fn require_local_tool(&self, tool: &str) -> anyhow::Result<()> {
+        let Some(package) = Self::package_for_tool(tool) else {
+            return Ok(());
+        };
+
+        if !self.dependencies.iter().any(|dep| dep == package) {
+            return Ok(());
+        }
+
+        let installed = self
+            .bin_dir()
+            .is_some_and(|dir| dir.join(tool).is_file() || dir.join(format!("{}.cmd", tool)).is_file());
+
+        if installed {
+            Ok(())
+        } else {
+            anyhow::bail!(
+                "'{}' is declared in package.json but node_modules/.bin/{} is missing; run `{} install`",
+                package,
+                tool,
+                self.package_manager.as_deref().unwrap_or("npm")
+            )
+        }
+    }
+
+    /// Summary for the build results; `search_path` is the `PATH` the
+    /// compiler runs with, so the reported node is the one in use.
+    pub fn describe(&self, search_path: Option<&OsStr>) -> String {
+        let mut node = Command::new("node");
+        if let Some(path) = search_path {
+            node.env("PATH", path);
+        }
+        let node_version = node
+            .arg("--version")
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string());
+
+        let mut description = format!("node {}", node_version.as_deref().unwrap_or("(not found)"));
+        if let Some(required) = &self.required_version {
+            description.push_str(&format!(", project requires {}", required));
+        }
+        if let Some(package_manager) = &self.package_manager {
+            description.push_str(&format!(", {}", package_manager));
+        }
+        if self.bin_dir().is_some() {
+            description.push_str(", local node_modules/.bin");
+        }
+        description
+    }
+
+    fn package_for_tool(tool: &str) -> Option<&'static str> {
+        match tool {
+            "tsc" => Some("typescript"),
+            "esbuild" => Some("esbuild"),
+            "swc" => Some("@swc/cli"),
+            "eslint" => Some("eslint"),
+            _ => None,
+        }
+    }
+
+    fn package_manager(package_root: &Path, manifest: &Value) -> String {
+        if let Some(declared) = manifest.get("packageManager").and_then(Value::as_str) {
+            return declared.split('@').next().unwrap_or(declared).to_string();
+        }
+
+        [
+            ("pnpm-lock.yaml", "pnpm"),
+            ("yarn.lock", "yarn"),
+            ("bun.lockb", "bun"),
+            ("bun.lock", "bun"),
+        ]
+        .iter()
+        .find(|(lockfile, _)| package_root.join(lockfile).exists())
+        .map(|(_, manager)| manager.to_string())
+        .unwrap_or_else(|| "npm".to_string())
+    }
+
+    fn nvmrc_version(dir: &Path) -> Option<String> {
+        fs::read_to_string(dir.join(".nvmrc"))
+            .ok()
+            .map(|content| content.trim().to_string())
+            .filter(|version| !version.is_empty())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_detect_package_manager_and_version() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(
+            temp_dir.path().join("package.json"),
+            r#"{"engines": {"node": ">=18"}, "devDependencies": {"typescript": "^5.0.0"}}"#,
+        )
+        .unwrap();
+        fs::write(temp_dir.path().join("pnpm-lock.yaml"), "").unwrap();
+
+        let env = NodeEnvironment::detect(&temp_dir.path().join("src"));
+        assert_eq!(env.package_root.as_deref(), Some(temp_dir.path()));
+        assert_eq!(env.package_manager.as_deref(), Some("pnpm"));
+        assert_eq!(env.required_version.as_deref(), Some(">=18"));
+    }
+
+    #[test]
+    fn test_missing_local_toolchain_is_reported() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(
+            temp_dir.path().join("package.json"),
+            r#"{"devDependencies": {"typescript": "^5.0.0"}}"#,
+        )
+        .unwrap();
+
+        let env = NodeEnvironment::detect(temp_dir.path());
+        let error = env.require_local_tool("tsc").unwrap_err().to_string();
+        assert!(error.contains("npm install"));
+        assert!(env.require_local_tool("eslint").is_ok());
+
+        let bin_dir = temp_dir.path().join("node_modules").join(".bin");
+        fs::create_dir_all(&bin_dir).unwrap();
+        fs::write(bin_dir.join("tsc"), "").unwrap();
+        assert!(env.require_local_tool("tsc").is_ok());
+    }
+}
@@ -0,0 +1,162 @@
+use std::path::Path;
+use std::time::Duration;
+use anyhow::{Context, Result};
+use colored::*;
+use serde::Serialize;
+use serde_json::json;
+use crate::compiler::{CompilationResult, CompilationStatus};
+use crate::config::{NotificationsConfig, WebhookConfig};
+
+/// How long a webhook gets to answer before the build moves on.
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// What a notification reports about a finished build.
+#[derive(Debug, Serialize)]
+pub struct Summary {
+    pub project: String,
+    pub success: bool,
+    pub files_compiled: usize,
+    pub files_failed: usize,
+    pub failed_languages: Vec<String>,
+    /// The pipeline stage that failed, when it was not compilation
+    pub failed_stage: Option<String>,
+    pub duration_secs: u64,
+}
+
+impl Summary {
+    pub fn new(project_path: &Path, results: &[CompilationResult], failed_stage: Option<&str>, duration: Duration) -> Self {
+        let project = project_path
+            .canonicalize()
+            .ok()
+            .and_then(|path| path.file_name().map(|name| name.to_string_lossy().into_owned()))
+            .unwrap_or_else(|| project_path.display().to_string());
+        let (mut files_compiled, mut files_failed) = (0, 0);
+        let mut failed_languages = Vec::new();
+        for result in results {
+            match result.status {
+                CompilationStatus::Success { .. } => files_compiled += result.files.len(),
+                CompilationStatus::Failure { .. } => {
+                    files_failed += result.files.len();
+                    failed_languages.push(result.language.name().to_string());
+                }
+                CompilationStatus::ToolchainMissing { .. } => {}
+            }
+        }
+        Self {
+            project,
+            success: failed_stage.is_none(),
+            files_compiled,
+            files_failed,
+            failed_languages,
+            failed_stage: failed_stage.map(str::to_string),
+            duration_secs: duration.as_secs(),
+        }
+    }
+
+    /// One line for chat messages and the notification body.
+    pub fn message(&self) -> String {
+        let mut message = if self.success {
+            format!("✅ {} built: {} files in {}s", self.project, self.files_compiled, self.duration_secs)
+        } else {
+            format!("❌ {} failed after {}s", self.project, self.duration_secs)
+        };
+        if !self.failed_languages.is_empty() {
+            message.push_str(&format!(": {} files failed ({})", self.files_failed, self.failed_languages.join(", ")));
+        } else if let Some(stage) = self.failed_stage.as_ref().filter(|_| !self.success) {
+            message.push_str(&format!(": stage {} failed", stage));
+        }
+        message
+    }
+}
+
+/// Sends the configured notifications for a finished build. Failing to
+/// deliver one is only a warning; the build result stands.
+pub fn send(config: &NotificationsConfig, summary: &Summary) {
+    let wanted = match config.on.as_str() {
+        "success" => summary.success,
+        "failure" => !summary.success,
+        _ => true,
+    };
+    if !wanted || summary.duration_secs < config.min_duration_secs {
+        return;
+    }
+
+    if config.desktop {
+        if let Err(error) = desktop(summary) {
+            tracing::warn!("Desktop notification failed: {:#}", error);
+        }
+    }
+    for webhook in &config.webhooks {
+        match post(webhook, summary) {
+            Ok(()) => say!("📣 Notified {}", webhook.url.dimmed()),
+            Err(error) => tracing::warn!("Webhook {} failed: {:#}", webhook.url, error),
+        }
+    }
+}
+
+fn desktop(summary: &Summary) -> Result<()> {
+    let title = if summary.success { "lol: build succeeded" } else { "lol: build failed" };
+    notify_rust::Notification::new()
+        .summary(title)
+        .body(&summary.message())
+        .appname("lol")
+        .show()
+        .context("No notification service is available")?;
+    Ok(())
+}
+
+/// The request body for a webhook: a chat message for Slack and Discord,
+/// the summary itself otherwise.
+fn payload(format: &str, summary: &Summary) -> serde_json::Value {
+    match format {
+        "slack" => json!({ "text": summary.message() }),
+        "discord" => json!({ "content": summary.message() }),
+        _ => json!(summary),
+    }
+}
+
+fn post(webhook: &WebhookConfig, summary: &Summary) -> Result<()> {
+    ureq::post(&webhook.url)
+        .timeout(WEBHOOK_TIMEOUT)
+        .send_json(payload(&webhook.format, summary))
+        .map_err(|error| anyhow::anyhow!("{}", error))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use crate::language_support::Language;
+
+    #[test]
+    fn test_payload_formats() {
+        let results = vec![
+            CompilationResult {
+                language: Language::C,
+                files: vec![PathBuf::from("a.c"), PathBuf::from("b.c")],
+                status: CompilationStatus::Success { output: String::new() },
+                diagnostics: Vec::new(),
+                toolchain: None,
+                retries: 0,
+                duration_ms: 0,
+            },
+            CompilationResult {
+                language: Language::Rust,
+                files: vec![PathBuf::from("main.rs")],
+                status: CompilationStatus::Failure { error: "boom".to_string() },
+                diagnostics: Vec::new(),
+                toolchain: None,
+                retries: 0,
+                duration_ms: 0,
+            },
+        ];
+        let summary = Summary::new(Path::new("demo"), &results, Some("compile"), Duration::from_secs(75));
+        assert_eq!(summary.message(), "❌ demo failed after 75s: 1 files failed (Rust)");
+        assert_eq!(payload("slack", &summary), json!({ "text": summary.message() }));
+        assert_eq!(payload("discord", &summary)["content"], summary.message());
+        let generic = payload("json", &summary);
+        assert_eq!(generic["files_compiled"], 2);
+        assert_eq!(generic["failed_languages"], json!(["Rust"]));
+    }
+}
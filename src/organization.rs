@@ -0,0 +1,87 @@
+use std::fs;
+use std::io::Read;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use crate::config::OrganizationConfig;
+use crate::signing;
+
+const FETCH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Where the last verified overlay from `url` and its signature are kept.
+fn cache_paths(url: &str) -> Result<(PathBuf, PathBuf)> {
+    let cache_dir = dirs::cache_dir().context("Could not determine cache directory")?.join("lol").join("organization");
+    let key: String = Sha256::digest(url.as_bytes()).iter().take(8).map(|byte| format!("{:02x}", byte)).collect();
+    Ok((cache_dir.join(format!("{}.json", key)), cache_dir.join(format!("{}.json.asc", key))))
+}
+
+fn fetch(url: &str) -> Result<Vec<u8>> {
+    let response = ureq::get(url).timeout(FETCH_TIMEOUT).call().map_err(|error| anyhow::anyhow!("{}", error))?;
+    let mut body = Vec::new();
+    response.into_reader().read_to_end(&mut body).with_context(|| format!("Failed to read {}", url))?;
+    Ok(body)
+}
+
+/// Checks `overlay` against its `signature`. A bad signature is an error,
+/// never a reason to fall back to anything.
+fn verify(organization: &OrganizationConfig, overlay: &[u8], signature: &[u8]) -> Result<()> {
+    let public_key = fs::read(&organization.public_key)
+        .with_context(|| format!("Failed to read the organization key {}", organization.public_key.display()))?;
+    let signer = signing::verify_with_key(overlay, signature, &public_key)
+        .with_context(|| format!("The organization config at {} is not signed by {}", organization.url, organization.public_key.display()))?;
+    tracing::debug!("Organization config from {} signed by {}", organization.url, signer);
+    Ok(())
+}
+
+fn parse(overlay: &[u8], source: &str) -> Result<serde_json::Value> {
+    let overlay: serde_json::Value = serde_json::from_slice(overlay).with_context(|| format!("Failed to parse the organization config from {}", source))?;
+    anyhow::ensure!(overlay.is_object(), "The organization config from {} is not a JSON object", source);
+    Ok(overlay)
+}
+
+/// The organization's overlay: the cached copy while it is younger than
+/// `max_age_secs`, else a freshly fetched and verified one. When the server
+/// cannot be reached, the cached copy is used however old it is, and
+/// without one the build goes ahead with the user's config alone.
+pub fn load(organization: &OrganizationConfig) -> Result<Option<serde_json::Value>> {
+    anyhow::ensure!(
+        organization.url.starts_with("https://"),
+        "The organization config must be fetched over HTTPS, not from {}",
+        organization.url
+    );
+    let (cache_path, signature_path) = cache_paths(&organization.url)?;
+    let age = fs::metadata(&cache_path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok());
+    if age.is_some_and(|age| age < Duration::from_secs(organization.max_age_secs)) {
+        let cached = fs::read(&cache_path).with_context(|| format!("Failed to read {}", cache_path.display()))?;
+        return parse(&cached, &cache_path.display().to_string()).map(Some);
+    }
+
+    let fetched = fetch(&organization.url).and_then(|overlay| Ok((overlay, fetch(&format!("{}.asc", organization.url))?)));
+    let (overlay, signature) = match fetched {
+        Ok(fetched) => fetched,
+        Err(error) => {
+            return match fs::read(&cache_path) {
+                Ok(cached) => {
+                    tracing::warn!("Could not refresh the organization config ({:#}); using the copy cached in {}", error, cache_path.display());
+                    parse(&cached, &cache_path.display().to_string()).map(Some)
+                }
+                Err(_) => {
+                    tracing::warn!("Could not fetch the organization config ({:#}); building without it", error);
+                    Ok(None)
+                }
+            };
+        }
+    };
+    verify(organization, &overlay, &signature)?;
+    let parsed = parse(&overlay, &organization.url)?;
+    if let Some(parent) = cache_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&signature_path, &signature)?;
+    fs::write(&cache_path, &overlay)?;
+    Ok(Some(parsed))
+}
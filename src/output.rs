@@ -0,0 +1,97 @@
+use std::io::IsTerminal;
+use std::sync::atomic::{AtomicBool, Ordering};
+use indicatif::ProgressDrawTarget;
+use crate::logging;
+
+static QUIET: AtomicBool = AtomicBool::new(false);
+static EMOJI: AtomicBool = AtomicBool::new(true);
+
+/// Prints a line of regular output, which `--quiet` hides.
+macro_rules! say {
+    () => {
+        $crate::output::print(String::new(), false)
+    };
+    ($($arg:tt)*) => {
+        $crate::output::print(format!($($arg)*), false)
+    };
+}
+
+/// Prints a line reporting a failure, shown even with `--quiet`.
+macro_rules! say_error {
+    ($($arg:tt)*) => {
+        $crate::output::print(format!($($arg)*), true)
+    };
+}
+
+/// Decides how output looks for the rest of the run. Pipes, CI logs and
+/// `NO_COLOR` get plain text without colors or emoji; `--no-emoji` keeps
+/// colors but drops emoji.
+pub fn configure(quiet: bool, no_emoji: bool) {
+    let plain = !std::io::stdout().is_terminal() || std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+    if plain {
+        colored::control::set_override(false);
+    }
+    EMOJI.store(!(plain || no_emoji), Ordering::Relaxed);
+    QUIET.store(quiet, Ordering::Relaxed);
+    if quiet {
+        logging::progress().set_draw_target(ProgressDrawTarget::hidden());
+    }
+}
+
+pub fn is_quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+pub fn print(text: String, error: bool) {
+    if is_quiet() && !error {
+        return;
+    }
+    if EMOJI.load(Ordering::Relaxed) {
+        println!("{}", text);
+    } else {
+        println!("{}", strip_emoji(&text));
+    }
+}
+
+fn is_emoji(c: char) -> bool {
+    matches!(
+        c as u32,
+        0x2139 | 0x2300..=0x23FF | 0x25A0..=0x25FF | 0x2600..=0x27BF | 0x2B00..=0x2BFF
+            | 0x1F000..=0x1FAFF | 0xFE0F | 0x200D
+    )
+}
+
+/// Removes emoji along with the spacing that followed them, e.g.
+/// `"   🔧 Toolchain"` becomes `"   Toolchain"`.
+pub fn strip_emoji(text: &str) -> String {
+    let lines = text.split('\n').map(|line| {
+        let mut stripped = String::with_capacity(line.len());
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            if !is_emoji(c) {
+                stripped.push(c);
+                continue;
+            }
+            if stripped.is_empty() || stripped.ends_with(' ') {
+                while chars.next_if(|next| *next == ' ' || is_emoji(*next)).is_some() {}
+            }
+        }
+        stripped.trim_end().to_string()
+    });
+    lines.collect::<Vec<_>>().join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_emoji() {
+        assert_eq!(strip_emoji("\n📊 Compilation Results:"), "\nCompilation Results:");
+        assert_eq!(strip_emoji("   🔧 Toolchain: gcc 13"), "   Toolchain: gcc 13");
+        assert_eq!(strip_emoji("⚠️  appimagetool not found"), "appimagetool not found");
+        assert_eq!(strip_emoji("  Successful: 3 ✅"), "  Successful: 3");
+        assert_eq!(strip_emoji("🛰️  Build 1: /tmp/app → build"), "Build 1: /tmp/app → build");
+        assert_eq!(strip_emoji("Total: 3 files"), "Total: 3 files");
+    }
+}
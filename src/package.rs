@@ -0,0 +1,658 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use walkdir::WalkDir;
+use crate::appimage;
+use crate::artifacts::{self, Artifact, ArtifactManifest, MANIFEST_FILE};
+use crate::codesign;
+use crate::config::Config;
+use crate::docker;
+use crate::flatpak;
+use crate::language_support::Language;
+use crate::python_env;
+use crate::reproducible;
+use crate::secrets;
+use crate::snap;
+use crate::system_package;
+
+/// Where `lol package` writes its output, relative to the project.
+pub const DIST_DIR: &str = "dist";
+
+/// Build by-products that are not worth shipping.
+const INTERMEDIATE_EXTENSIONS: &[&str] = &[
+    "o", "obj", "pyc", "pyo", "class", "hi", "dyn_hi", "dyn_o", "cmi", "cmo", "cmx", "cmt", "ali", "ppu", "mod", "d",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum PackageFormat {
+    #[value(name = "tar.gz")]
+    TarGz,
+    Zip,
+    Deb,
+    Rpm,
+    Flatpak,
+    Docker,
+    Snap,
+}
+
+impl PackageFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            PackageFormat::TarGz => "tar.gz",
+            PackageFormat::Zip => "zip",
+            PackageFormat::Deb => "deb",
+            PackageFormat::Rpm => "rpm",
+            PackageFormat::Flatpak => "flatpak",
+            PackageFormat::Docker => "docker",
+            PackageFormat::Snap => "snap",
+        }
+    }
+}
+
+/// Where non-executable outputs and interpreted sources go, relative to
+/// the directory holding `bin/`. Launchers find them from there.
+pub struct Layout {
+    pub lib: PathBuf,
+    pub share: PathBuf,
+}
+
+impl Layout {
+    /// `lib/` and `share/` next to `bin/`, for self-contained archives.
+    pub fn archive() -> Self {
+        Self { lib: PathBuf::from("lib"), share: PathBuf::from("share") }
+    }
+
+    /// `lib/<name>/` and `share/<name>/` under `/usr`, as the FHS expects.
+    pub fn fhs(package_name: &str) -> Self {
+        Self { lib: Path::new("lib").join(package_name), share: Path::new("share").join(package_name) }
+    }
+}
+
+/// The interpreter a launcher script runs a language's sources with.
+pub fn interpreter(language: &Language) -> Option<&'static str> {
+    match language {
+        Language::Python => Some(python_env::PYTHON),
+        Language::Ruby => Some("ruby"),
+        Language::JavaScript => Some("node"),
+        Language::Perl => Some("perl"),
+        Language::Lua => Some("lua"),
+        Language::R => Some("Rscript"),
+        Language::Julia => Some("julia"),
+        _ => None,
+    }
+}
+
+/// `package.name`, or the project directory's name.
+pub fn package_name(config: &Config, project_path: &Path) -> String {
+    config.package.name.clone().unwrap_or_else(|| {
+        project_path
+            .canonicalize()
+            .ok()
+            .and_then(|path| path.file_name().map(|name| name.to_string_lossy().into_owned()))
+            .unwrap_or_else(|| "app".to_string())
+    })
+}
+
+/// `package.version`, or the nearest git tag without its `v`, or `0.0.0`.
+pub fn package_version(config: &Config, project_path: &Path) -> String {
+    if let Some(version) = &config.package.version {
+        return version.clone();
+    }
+    Command::new("git")
+        .args(["describe", "--tags", "--always", "--dirty"])
+        .current_dir(project_path)
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().trim_start_matches('v').to_string())
+        .filter(|version| version.chars().next().is_some_and(|c| c.is_ascii_digit()))
+        .unwrap_or_else(|| "0.0.0".to_string())
+}
+
+/// The source a launcher should run: a `main`, `app` or `index` file, or
+/// the only file there is.
+pub fn entry_point(files: &[PathBuf]) -> Option<&PathBuf> {
+    ["main", "app", "index", "__main__"]
+        .iter()
+        .find_map(|name| files.iter().find(|file| file.file_stem().is_some_and(|stem| stem == *name)))
+        .or(if files.len() == 1 { files.first() } else { None })
+}
+
+/// Writes a script running `command`, in which `$HERE` is `bin/`: a shell
+/// script, or on Windows a `.cmd` batch file.
+fn write_launcher(bin_dir: &Path, name: &str, command: &str) -> Result<()> {
+    let (path, script) = if cfg!(windows) {
+        let command = command.replace("$HERE/", "%~dp0").replace('/', "\\");
+        (bin_dir.join(format!("{}.cmd", name)), format!("@echo off\r\n{} %*\r\n", command))
+    } else {
+        let script = format!(
+            "#!/bin/sh\n\
+            HERE=\"$(cd \"$(dirname \"$0\")\" && pwd)\"\n\
+            exec {} \"$@\"\n",
+            command
+        );
+        (bin_dir.join(name), script)
+    };
+    fs::write(&path, script).with_context(|| format!("Failed to write {}", path.display()))?;
+    appimage::set_executable(&path)?;
+    Ok(())
+}
+
+/// A launcher name that does not clash with what is already in `bin/`.
+fn launcher_name(bin_dir: &Path, stem: &str, package_name: &str, language: &Language) -> String {
+    let name = if ["main", "app", "index", "__main__"].contains(&stem) { package_name } else { stem };
+    if bin_dir.join(name).exists() {
+        format!("{}-{}", name, language.config_key())
+    } else {
+        name.to_string()
+    }
+}
+
+fn copy_into(source: &Path, destination: &Path) -> Result<()> {
+    if let Some(parent) = destination.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::copy(source, destination).with_context(|| format!("Failed to copy {}", source.display()))?;
+    Ok(())
+}
+
+/// Where a build output goes in the staged package: `bin/` for
+/// executables, the layout's `lib` directory otherwise. Intermediate files
+/// and earlier packages are left out.
+fn destination(layout: &Layout, relative_output: &Path, artifact_path: &Path, path: &Path) -> Option<PathBuf> {
+    let extension = path.extension().unwrap_or_default().to_string_lossy();
+    if INTERMEDIATE_EXTENSIONS.contains(&extension.as_ref()) || artifact_path.starts_with(DIST_DIR) {
+        return None;
+    }
+    if appimage::is_executable(path) {
+        return Some(Path::new("bin").join(artifact_path.file_name().unwrap_or_default()));
+    }
+    Some(layout.lib.join(artifact_path.strip_prefix(relative_output).unwrap_or(artifact_path)))
+}
+
+/// Lays out the package under `staging_dir`: executables and launchers
+/// in `bin/`, other build outputs in the layout's `lib` directory and
+/// interpreted sources in `<share>/<language>/`.
+pub fn stage(
+    staging_dir: &Path,
+    layout: &Layout,
+    project_path: &Path,
+    output_dir: &Path,
+    package_name: &str,
+    built: &ArtifactManifest,
+    sources: &HashMap<Language, Vec<PathBuf>>,
+) -> Result<()> {
+    let bin_dir = staging_dir.join("bin");
+    fs::create_dir_all(&bin_dir)?;
+    let relative_output = output_dir.strip_prefix(project_path).unwrap_or(output_dir);
+
+    for artifact in &built.artifacts {
+        let path = project_path.join(&artifact.path);
+        let Some(destination) = destination(layout, relative_output, &artifact.path, &path) else {
+            continue;
+        };
+        copy_into(&path, &staging_dir.join(&destination))?;
+        if path.extension().is_some_and(|ext| ext == "jar") {
+            let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+            let name = launcher_name(&bin_dir, &stem, package_name, &Language::Java);
+            write_launcher(&bin_dir, &name, &format!("java -jar \"$HERE/../{}\"", destination.display()))?;
+        }
+    }
+
+    let root = project_path.canonicalize().unwrap_or_else(|_| project_path.to_path_buf());
+    let mut languages: Vec<&Language> = sources.keys().filter(|language| interpreter(language).is_some()).collect();
+    languages.sort_by_key(|language| language.name());
+    for language in languages {
+        let files = &sources[language];
+        let share_dir = staging_dir.join(&layout.share).join(language.config_key());
+        let mut staged = Vec::new();
+        for file in files {
+            let relative = file
+                .strip_prefix(&root)
+                .or_else(|_| file.strip_prefix(project_path))
+                .map(Path::to_path_buf)
+                .unwrap_or_else(|_| PathBuf::from(file.file_name().unwrap_or_default()));
+            copy_into(file, &share_dir.join(&relative))?;
+            staged.push(relative);
+        }
+        if let Some(entry) = entry_point(&staged) {
+            let stem = entry.file_stem().unwrap_or_default().to_string_lossy();
+            let name = launcher_name(&bin_dir, &stem, package_name, language);
+            let command = format!(
+                "{} \"$HERE/../{}/{}/{}\"",
+                interpreter(language).unwrap_or_default(),
+                layout.share.display(),
+                language.config_key(),
+                entry.display()
+            );
+            write_launcher(&bin_dir, &name, &command)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether anything under `dir` is an ELF file, which ties the package to
+/// the build machine's architecture.
+pub fn contains_native_code(dir: &Path) -> bool {
+    WalkDir::new(dir)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .any(|entry| {
+            let mut magic = [0u8; 4];
+            fs::File::open(entry.path())
+                .and_then(|mut file| std::io::Read::read_exact(&mut file, &mut magic))
+                .is_ok_and(|_| magic == *b"\x7fELF")
+        })
+}
+
+/// Every file under `staging_dir` with its checksum.
+fn staged_manifest(staging_dir: &Path) -> Result<ArtifactManifest> {
+    let mut manifest = ArtifactManifest::default();
+    for entry in WalkDir::new(staging_dir).sort_by_file_name() {
+        let entry = entry?;
+        if !entry.file_type().is_file() || entry.path() == staging_dir.join(MANIFEST_FILE) {
+            continue;
+        }
+        manifest.artifacts.push(Artifact {
+            path: entry.path().strip_prefix(staging_dir)?.to_path_buf(),
+            language: None,
+            inputs: Vec::new(),
+            size: entry.metadata()?.len(),
+            sha256: artifacts::sha256(entry.path())?,
+        });
+    }
+    Ok(manifest)
+}
+
+fn archive(format: PackageFormat, staging_dir: &Path, archive_path: &Path) -> Result<()> {
+    let epoch = reproducible::epoch();
+    if let Some(epoch) = epoch {
+        reproducible::normalize_mtimes(staging_dir, epoch)?;
+    }
+    let parent = staging_dir.parent().unwrap_or(Path::new("."));
+    let top = staging_dir.file_name().unwrap_or_default();
+
+    match format {
+        PackageFormat::TarGz => match epoch {
+            Some(epoch) => reproducible::tar_gz(staging_dir, archive_path, epoch)?,
+            None => {
+                let output = Command::new("tar")
+                    .arg("-czf")
+                    .arg(archive_path)
+                    .arg("-C")
+                    .arg(parent)
+                    .arg(top)
+                    .output()
+                    .context("Failed to create tar archive")?;
+                if !output.status.success() {
+                    anyhow::bail!("tar failed: {}", String::from_utf8_lossy(&output.stderr));
+                }
+            }
+        },
+        // Windows 10 and later ship bsdtar, which writes zips; zip itself is rare there
+        PackageFormat::Zip if cfg!(windows) => {
+            let output = Command::new("tar")
+                .arg("-a")
+                .arg("-cf")
+                .arg(std::path::absolute(archive_path)?)
+                .arg("-C")
+                .arg(parent)
+                .arg(top)
+                .output()
+                .context("Failed to create zip archive")?;
+            if !output.status.success() {
+                anyhow::bail!("tar failed: {}", String::from_utf8_lossy(&output.stderr));
+            }
+        }
+        PackageFormat::Zip => {
+            // Names are fed in sorted order so the archive layout is stable
+            let mut names = Vec::new();
+            for entry in WalkDir::new(staging_dir).sort_by_file_name() {
+                let entry = entry?;
+                names.push(entry.path().strip_prefix(parent)?.to_string_lossy().into_owned());
+            }
+            let _ = fs::remove_file(archive_path);
+            // zip runs from the staging directory's parent
+            let archive_path = std::path::absolute(archive_path)?;
+            let mut zip = Command::new("zip")
+                .args(["-q", "-X", "-y"])
+                .arg(&archive_path)
+                .arg("-@")
+                .current_dir(parent)
+                .stdin(Stdio::piped())
+                .stderr(Stdio::piped())
+                .spawn()
+                .context("Creating zip packages needs zip on the PATH")?;
+            zip.stdin.take().expect("zip stdin is piped").write_all((names.join("\n") + "\n").as_bytes())?;
+            let output = zip.wait_with_output()?;
+            if !output.status.success() {
+                anyhow::bail!("zip failed: {}", String::from_utf8_lossy(&output.stderr));
+            }
+        }
+        PackageFormat::Deb | PackageFormat::Rpm | PackageFormat::Flatpak | PackageFormat::Docker | PackageFormat::Snap => {
+            unreachable!("system packages are not archives")
+        }
+    }
+    Ok(())
+}
+
+/// Stages the files the build listed in `<output_directory>/artifacts.json`
+/// and the sources, refusing to go on if anything staged contains secrets,
+/// and packs them into `dist/`: an archive named
+/// `<name>-<version>.<ext>`, a deb, rpm or snap package, a Flatpak
+/// manifest and bundle, or a Dockerfile and image. `dist/artifacts.json`
+/// is updated to list the result.
+pub fn create(
+    format: PackageFormat,
+    project_path: &Path,
+    config: &Config,
+    sources: &HashMap<Language, Vec<PathBuf>>,
+) -> Result<PathBuf> {
+    let Some(output_dir) = config.output_dir(project_path) else {
+        anyhow::bail!("lol package ships the files listed in the build's artifacts.json, so it needs an output_directory");
+    };
+    let built = &ArtifactManifest::load(&output_dir)?.unwrap_or_default();
+    let name = package_name(config, project_path);
+    let version = package_version(config, project_path);
+    let dist_dir = project_path.join(DIST_DIR);
+    let staging_dir = dist_dir.join(format!("{}-{}", name, version));
+    if staging_dir.exists() {
+        fs::remove_dir_all(&staging_dir)?;
+    }
+
+    let (stage_root, layout) = match format {
+        PackageFormat::Deb | PackageFormat::Rpm => (staging_dir.join("usr"), Layout::fhs(&name)),
+        PackageFormat::Flatpak => (staging_dir.clone(), Layout::fhs(&name)),
+        PackageFormat::TarGz | PackageFormat::Zip | PackageFormat::Docker | PackageFormat::Snap => {
+            (staging_dir.clone(), Layout::archive())
+        }
+    };
+    stage(&stage_root, &layout, project_path, &output_dir, &name, built, sources)?;
+    if let Err(error) = secrets::guard_packaging(config.secret_scan, &staging_dir, project_path) {
+        let _ = fs::remove_dir_all(&staging_dir);
+        return Err(error);
+    }
+
+    let built_package = match format {
+        PackageFormat::TarGz | PackageFormat::Zip => {
+            // Signing changes the files, so it comes before their checksums
+            if let Some(codesign_config) = &config.package.codesign {
+                let signed = codesign::sign_package(&staging_dir, codesign_config, project_path)?;
+                if signed > 0 {
+                    say!("🔏 Signed {} macOS binaries and app bundles", signed);
+                }
+            }
+            staged_manifest(&staging_dir)?.save(&staging_dir)?;
+            let archive_path = dist_dir.join(format!("{}-{}.{}", name, version, format.extension()));
+            archive(format, &staging_dir, &archive_path).map(|_| archive_path)
+        }
+        PackageFormat::Deb | PackageFormat::Rpm => {
+            let metadata = system_package::Metadata::new(&name, &version, config, contains_native_code(&stage_root))?;
+            if format == PackageFormat::Deb {
+                system_package::build_deb(&staging_dir, &dist_dir, &metadata)
+            } else {
+                system_package::build_rpm(&staging_dir, &dist_dir, &metadata)
+            }
+        }
+        PackageFormat::Flatpak => {
+            let languages = launcher_languages(built, sources);
+            flatpak_package(project_path, &staging_dir, &dist_dir, &name, &version, config, &languages)
+        }
+        PackageFormat::Docker => docker_package(project_path, &staging_dir, &output_dir, &name, &version, built, sources),
+        PackageFormat::Snap => snap_package(&staging_dir, &dist_dir, &name, &version, config, built, sources),
+    };
+    // The staging tree goes away whether or not packaging worked, except
+    // for the formats whose build files point at it
+    if !matches!(format, PackageFormat::Flatpak | PackageFormat::Docker | PackageFormat::Snap) {
+        let _ = fs::remove_dir_all(&staging_dir);
+    }
+    let package_path = built_package?;
+
+    artifacts::package_manifest(&dist_dir)?.save(&dist_dir)?;
+    Ok(package_path)
+}
+
+/// What the package runs: the launcher or executable named after it, or
+/// else the first one in `bin/`.
+fn main_command(staging_dir: &Path, name: &str) -> Result<String> {
+    let bin_dir = staging_dir.join("bin");
+    if bin_dir.join(name).is_file() {
+        return Ok(name.to_string());
+    }
+    let mut commands: Vec<String> = fs::read_dir(&bin_dir)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .collect();
+    commands.sort();
+    commands.into_iter().next().context("Nothing to run: the build produced no executables or launchers")
+}
+
+/// The languages whose interpreters the launchers call, in a stable order.
+fn launcher_languages(built: &ArtifactManifest, sources: &HashMap<Language, Vec<PathBuf>>) -> Vec<Language> {
+    let mut languages: Vec<Language> = sources.keys().filter(|language| interpreter(language).is_some()).cloned().collect();
+    if built.artifacts.iter().any(|artifact| artifact.path.extension().is_some_and(|ext| ext == "jar")) {
+        languages.push(Language::Java);
+    }
+    languages.sort_by_key(|language| language.name().to_string());
+    languages
+}
+
+/// Writes `dist/<app-id>.json` for the tree staged under `/app`, and
+/// builds a `.flatpak` bundle from it when flatpak-builder is installed.
+/// Returns the bundle, or the manifest when nothing was built.
+fn flatpak_package(
+    project_path: &Path,
+    staging_dir: &Path,
+    dist_dir: &Path,
+    name: &str,
+    version: &str,
+    config: &Config,
+    languages: &[Language],
+) -> Result<PathBuf> {
+    let command = main_command(staging_dir, name)?;
+    let app_id = flatpak::app_id(config, project_path, name);
+    let runtime = flatpak::runtime_for(languages);
+    let manifest = flatpak::manifest(&app_id, &command, &runtime, Path::new(staging_dir.file_name().unwrap_or_default()), config);
+    let manifest_path = dist_dir.join(format!("{}.json", app_id));
+    fs::write(&manifest_path, serde_json::to_string_pretty(&manifest)? + "\n")
+        .with_context(|| format!("Failed to write {}", manifest_path.display()))?;
+    say!("📝 Flatpak manifest: {}", manifest_path.display());
+
+    let bundle_path = dist_dir.join(format!("{}-{}.flatpak", name, version));
+    Ok(flatpak::build(&manifest_path, dist_dir, &app_id, &bundle_path)?.unwrap_or(manifest_path))
+}
+
+/// Writes `dist/docker/Dockerfile` for the package staged under `/app`,
+/// with a builder stage for each compiled language lol can build in a
+/// container, and builds the image when docker or buildah is installed.
+/// Returns the Dockerfile.
+fn docker_package(
+    project_path: &Path,
+    staging_dir: &Path,
+    output_dir: &Path,
+    name: &str,
+    version: &str,
+    built: &ArtifactManifest,
+    sources: &HashMap<Language, Vec<PathBuf>>,
+) -> Result<PathBuf> {
+    let relative_output = output_dir.strip_prefix(project_path).unwrap_or(output_dir);
+    let mut builders: Vec<docker::Builder> = Vec::new();
+    for artifact in &built.artifacts {
+        let Some(language) = &artifact.language else {
+            continue;
+        };
+        let Some((image, flag)) = docker::builder_image(language) else {
+            continue;
+        };
+        let path = project_path.join(&artifact.path);
+        let Some(destination) = destination(&Layout::archive(), relative_output, &artifact.path, &path) else {
+            continue;
+        };
+        match builders.iter_mut().find(|builder| builder.language == *language) {
+            Some(builder) => builder.artifacts.push((artifact.path.clone(), destination)),
+            None => builders.push(docker::Builder {
+                language: language.clone(),
+                image,
+                flag,
+                artifacts: vec![(artifact.path.clone(), destination)],
+            }),
+        }
+    }
+
+    let docker_dir = project_path.join(DIST_DIR).join("docker");
+    fs::create_dir_all(&docker_dir)?;
+    if !builders.is_empty() {
+        let lol = std::env::current_exe().context("Failed to find the lol executable")?;
+        fs::copy(&lol, project_path.join(docker::lol_binary()))
+            .with_context(|| format!("Failed to copy {} into the build context", lol.display()))?;
+    }
+
+    let relative_staging = staging_dir.strip_prefix(project_path).unwrap_or(staging_dir);
+    let runtime_image = docker::runtime_image(&launcher_languages(built, sources));
+    let command = main_command(staging_dir, name)?;
+    let dockerfile_path = docker_dir.join("Dockerfile");
+    let dockerfile = docker::dockerfile(&builders, runtime_image, relative_staging, &command, name, version);
+    fs::write(&dockerfile_path, dockerfile).with_context(|| format!("Failed to write {}", dockerfile_path.display()))?;
+    // BuildKit reads `<Dockerfile>.dockerignore` next to the Dockerfile
+    let relative_output = (!relative_output.as_os_str().is_empty()).then_some(relative_output);
+    fs::write(docker_dir.join("Dockerfile.dockerignore"), docker::dockerignore(relative_staging, relative_output))?;
+    say!("🐳 Dockerfile: {}", dockerfile_path.display());
+
+    let tag = docker::image_tag(name, version);
+    let relative_dockerfile = dockerfile_path.strip_prefix(project_path).unwrap_or(&dockerfile_path);
+    if docker::build(project_path, relative_dockerfile, &tag)? {
+        say!("🐳 Image: {}", tag);
+    }
+    Ok(dockerfile_path)
+}
+
+/// Writes `dist/snap/snap/snapcraft.yaml` with an app for every launcher
+/// and executable, and packs the snap when snapcraft is installed.
+/// Returns the snap, or the snapcraft.yaml when nothing was built.
+fn snap_package(
+    staging_dir: &Path,
+    dist_dir: &Path,
+    name: &str,
+    version: &str,
+    config: &Config,
+    built: &ArtifactManifest,
+    sources: &HashMap<Language, Vec<PathBuf>>,
+) -> Result<PathBuf> {
+    let mut commands: Vec<String> = fs::read_dir(staging_dir.join("bin"))?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .collect();
+    if commands.is_empty() {
+        anyhow::bail!("Nothing to run: the build produced no executables or launchers");
+    }
+    commands.sort();
+
+    let project_dir = dist_dir.join("snap");
+    let yaml_path = project_dir.join("snap").join("snapcraft.yaml");
+    fs::create_dir_all(yaml_path.parent().unwrap_or(&project_dir))?;
+    let source = Path::new("..").join(staging_dir.file_name().unwrap_or_default());
+    let yaml = snap::snapcraft_yaml(name, version, config, &source, &commands, &launcher_languages(built, sources));
+    fs::write(&yaml_path, yaml).with_context(|| format!("Failed to write {}", yaml_path.display()))?;
+    say!("📝 snapcraft.yaml: {}", yaml_path.display());
+
+    let snap_path = dist_dir.join(format!(
+        "{}_{}_{}.snap",
+        snap::snap_name(name),
+        version,
+        system_package::deb_architecture()
+    ));
+    let output = std::path::absolute(&snap_path)?;
+    Ok(snap::pack(&project_dir, &output)?.map(|_| snap_path).unwrap_or(yaml_path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_stage_lays_out_binaries_libraries_and_launchers() {
+        let temp_dir = TempDir::new().unwrap();
+        let project = temp_dir.path();
+        let output_dir = project.join("build");
+        fs::create_dir_all(output_dir.join("wasm")).unwrap();
+        fs::write(output_dir.join("wasm").join("lib.wasm"), "\0asm").unwrap();
+        fs::write(project.join("util.o"), "obj").unwrap();
+        fs::write(project.join("main.py"), "print('hi')\n").unwrap();
+        fs::write(project.join("helpers.py"), "").unwrap();
+
+        let built = ArtifactManifest {
+            artifacts: ["build/wasm/lib.wasm", "util.o"]
+                .iter()
+                .map(|path| Artifact { path: PathBuf::from(path), language: None, inputs: Vec::new(), size: 0, sha256: String::new() })
+                .collect(),
+            ..ArtifactManifest::default()
+        };
+        let mut sources = HashMap::new();
+        sources.insert(Language::Python, vec![project.join("helpers.py"), project.join("main.py")]);
+
+        let staging_dir = project.join("dist").join("demo-1.0.0");
+        stage(&staging_dir, &Layout::archive(), project, &output_dir, "demo", &built, &sources).unwrap();
+        staged_manifest(&staging_dir).unwrap().save(&staging_dir).unwrap();
+
+        assert!(staging_dir.join("lib/wasm/lib.wasm").is_file());
+        assert!(!staging_dir.join("lib/util.o").exists());
+        assert!(staging_dir.join("share/python/helpers.py").is_file());
+        let launcher = fs::read_to_string(staging_dir.join("bin/demo")).unwrap();
+        assert!(launcher.contains("exec python3 \"$HERE/../share/python/main.py\" \"$@\""));
+
+        let manifest = ArtifactManifest::load(&staging_dir).unwrap().unwrap();
+        let paths: Vec<&Path> = manifest.artifacts.iter().map(|artifact| artifact.path.as_path()).collect();
+        assert_eq!(
+            paths,
+            [Path::new("bin/demo"), Path::new("lib/wasm/lib.wasm"), Path::new("share/python/helpers.py"), Path::new("share/python/main.py")]
+        );
+        assert!(!contains_native_code(&staging_dir));
+
+        let usr = project.join("dist").join("fhs").join("usr");
+        stage(&usr, &Layout::fhs("demo"), project, &output_dir, "demo", &built, &sources).unwrap();
+        assert!(usr.join("share/demo/python/main.py").is_file());
+        let launcher = fs::read_to_string(usr.join("bin/demo")).unwrap();
+        assert!(launcher.contains("exec python3 \"$HERE/../share/demo/python/main.py\" \"$@\""));
+    }
+
+    #[test]
+    fn test_create_ships_only_what_artifacts_json_lists() {
+        let temp_dir = TempDir::new().unwrap();
+        let project = temp_dir.path();
+        let output_dir = project.join("build");
+        fs::create_dir_all(output_dir.join("wasm")).unwrap();
+        fs::write(output_dir.join("wasm").join("lib.wasm"), "\0asm").unwrap();
+        fs::write(output_dir.join("wasm").join("stale.wasm"), "\0asm").unwrap();
+        let built = ArtifactManifest {
+            artifacts: vec![Artifact {
+                path: PathBuf::from("build/wasm/lib.wasm"),
+                language: None,
+                inputs: Vec::new(),
+                size: 4,
+                sha256: String::new(),
+            }],
+            ..ArtifactManifest::default()
+        };
+        built.save(&output_dir).unwrap();
+
+        let mut config = Config::default();
+        config.package.name = Some("demo".to_string());
+        config.package.version = Some("1.0.0".to_string());
+        let archive_path = create(PackageFormat::TarGz, project, &config, &HashMap::new()).unwrap();
+        assert!(archive_path.ends_with("dist/demo-1.0.0.tar.gz"));
+        let listing = Command::new("tar").arg("-tzf").arg(&archive_path).output().unwrap();
+        let listing = String::from_utf8_lossy(&listing.stdout);
+        assert!(listing.contains("demo-1.0.0/lib/wasm/lib.wasm"));
+        assert!(!listing.contains("stale.wasm"));
+
+        config.output_directory = None;
+        assert!(create(PackageFormat::TarGz, project, &config, &HashMap::new()).is_err());
+    }
+}
@@ -0,0 +1,159 @@
+use std::path::Path;
+use anyhow::{Context, Result};
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use crate::config::Config;
+
+/// Patterns matched against relative paths the way `.gitignore` lines are:
+///
+/// - `*`, `?` and `[a-z]` stay within one path component, `**` spans any number
+/// - a pattern without `/` matches a file or directory of that name at any depth
+/// - a pattern containing `/` is anchored at the root; a leading `/` only anchors
+/// - a trailing `/` matches directories only
+/// - `!` brings back what an earlier pattern matched; the last pattern matching wins
+pub struct PatternSet {
+    globs: GlobSet,
+    /// The pattern each glob came from, and whether it is negated
+    owners: Vec<(usize, bool)>,
+}
+
+impl PatternSet {
+    pub fn new(patterns: &[String]) -> Result<Self> {
+        let mut builder = GlobSetBuilder::new();
+        let mut owners = Vec::new();
+        for (index, pattern) in patterns.iter().enumerate() {
+            let (negated, body) = match pattern.strip_prefix('!') {
+                Some(body) => (true, body),
+                None => (false, pattern.as_str()),
+            };
+            for glob in globs_for(body) {
+                let glob = GlobBuilder::new(&glob)
+                    .literal_separator(true)
+                    .build()
+                    .with_context(|| format!("Invalid pattern `{}`", pattern))?;
+                builder.add(glob);
+                owners.push((index, negated));
+            }
+        }
+        Ok(Self { globs: builder.build()?, owners })
+    }
+
+    pub fn is_match(&self, path: &Path) -> bool {
+        // Patterns are written with `/`, whatever the platform separates paths with
+        let path = path.to_string_lossy();
+        let path = if cfg!(windows) { path.replace('\\', "/") } else { path.into_owned() };
+        self.globs
+            .matches(&path)
+            .into_iter()
+            .map(|glob| self.owners[glob])
+            .max_by_key(|(index, _)| *index)
+            .is_some_and(|(_, negated)| !negated)
+    }
+}
+
+/// The globs matching what `pattern`, without its `!`, stands for: the
+/// path itself, and everything below it when it names a directory.
+fn globs_for(pattern: &str) -> Vec<String> {
+    let (pattern, directory_only) = match pattern.strip_suffix('/') {
+        Some(directory) => (directory, true),
+        None => (pattern, false),
+    };
+    let anchored = pattern.contains('/');
+    let pattern = pattern.trim_start_matches('/');
+    if pattern.is_empty() {
+        return Vec::new();
+    }
+    let path = if anchored { pattern.to_string() } else { format!("**/{}", pattern) };
+    let mut globs = vec![format!("{}/**", path)];
+    if !directory_only {
+        globs.push(path);
+    }
+    globs
+}
+
+/// A project's `ignore_patterns` and `include_patterns`, compiled once.
+pub struct FileFilter {
+    ignore: PatternSet,
+    /// When set, files must match it to be built
+    include: Option<PatternSet>,
+}
+
+impl FileFilter {
+    pub fn new(config: &Config) -> Result<Self> {
+        let ignore = PatternSet::new(&config.ignore_patterns).context("Invalid ignore_patterns")?;
+        let include = if config.include_patterns.is_empty() {
+            None
+        } else {
+            Some(PatternSet::new(&config.include_patterns).context("Invalid include_patterns")?)
+        };
+        Ok(Self { ignore, include })
+    }
+
+    /// Whether `path`, relative to the project root, is left out of the build.
+    pub fn is_ignored(&self, path: &Path) -> bool {
+        self.ignore.is_match(path) || self.include.as_ref().is_some_and(|include| !include.is_match(path))
+    }
+}
+
+/// The glob for a pattern written for the matcher before globs, where `*`
+/// also crossed `/` and a pattern without `*` matched anywhere in the path.
+/// Patterns that already mean the same under both are returned unchanged.
+pub fn migrate(pattern: &str) -> String {
+    if !pattern.contains('*') {
+        return match pattern {
+            "" => String::new(),
+            pattern if pattern.ends_with('/') => pattern.to_string(),
+            pattern if pattern.contains('/') => format!("**/*{}*", pattern),
+            pattern => format!("*{}*", pattern),
+        };
+    }
+    if !pattern.contains('/') {
+        return pattern.to_string();
+    }
+    let mut segments: Vec<String> = Vec::new();
+    for segment in pattern.split('/') {
+        let converted = match segment {
+            "*" | "**" => "**".to_string(),
+            segment if segment.contains('*') => format!("**/{}", segment),
+            segment => segment.to_string(),
+        };
+        for part in converted.split('/') {
+            if part != "**" || segments.last().is_none_or(|last| last != "**") {
+                segments.push(part.to_string());
+            }
+        }
+    }
+    segments.join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(patterns: &[&str]) -> PatternSet {
+        PatternSet::new(&patterns.iter().map(|pattern| pattern.to_string()).collect::<Vec<_>>()).unwrap()
+    }
+
+    #[test]
+    fn test_gitignore_style_matching() {
+        let patterns = set(&["build/", "*.o", "src/gen/**/*.c", "test_[0-9].py", "vendor/", "!vendor/keep/**"]);
+        assert!(patterns.is_match(Path::new("build/main.c")));
+        assert!(patterns.is_match(Path::new("lib/build/main.c")));
+        assert!(!patterns.is_match(Path::new("rebuild/main.c")));
+        assert!(!patterns.is_match(Path::new("build")));
+        assert!(patterns.is_match(Path::new("deep/dir/util.o")));
+        assert!(patterns.is_match(Path::new("src/gen/a/b/parser.c")));
+        assert!(!patterns.is_match(Path::new("lib/src/gen/parser.c")));
+        assert!(patterns.is_match(Path::new("tests/test_1.py")));
+        assert!(!patterns.is_match(Path::new("tests/test_a.py")));
+        assert!(patterns.is_match(Path::new("vendor/lib.c")));
+        assert!(!patterns.is_match(Path::new("vendor/keep/lib.c")));
+        assert!(PatternSet::new(&["src/[a-".to_string()]).is_err());
+
+        assert_eq!(migrate("*.o"), "*.o");
+        assert_eq!(migrate("build/"), "build/");
+        assert_eq!(migrate("generated"), "*generated*");
+        assert_eq!(migrate("src/*.c"), "src/**/*.c");
+        assert_eq!(migrate("*/tests/*"), "**/tests/**");
+        assert_eq!(migrate(&migrate("src/*.c")), "src/**/*.c");
+    }
+}
@@ -0,0 +1,197 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use anyhow::{Context, Result};
+use colored::*;
+use rayon::prelude::*;
+use crate::config::{Config, StageConfig};
+use crate::hooks;
+
+pub const GENERATE_STAGE: &str = "generate";
+pub const COMPILE_STAGE: &str = "compile";
+
+/// What running a stage does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StageKind {
+    /// Schema code generation (see `codegen`)
+    Generate,
+    /// Source detection and compilation
+    Compile,
+    /// The stage's own shell commands
+    Commands,
+}
+
+pub fn kind(stage: &StageConfig) -> StageKind {
+    if !stage.commands.is_empty() {
+        return StageKind::Commands;
+    }
+    match stage.name.as_str() {
+        GENERATE_STAGE => StageKind::Generate,
+        COMPILE_STAGE => StageKind::Compile,
+        _ => StageKind::Commands,
+    }
+}
+
+/// The stages from `config`, or `generate → compile` when none are declared.
+pub fn stages(config: &Config) -> Vec<StageConfig> {
+    if !config.stages.is_empty() {
+        return config.stages.clone();
+    }
+    vec![
+        StageConfig { name: GENERATE_STAGE.to_string(), ..StageConfig::default() },
+        StageConfig {
+            name: COMPILE_STAGE.to_string(),
+            depends_on: vec![GENERATE_STAGE.to_string()],
+            ..StageConfig::default()
+        },
+    ]
+}
+
+/// Orders stages so each runs after everything in its `depends_on`, keeping
+/// the declared order where dependencies allow.
+pub fn order(stages: &[StageConfig]) -> Result<Vec<StageConfig>> {
+    let ordered = topological_order(stages, "stage", |stage| &stage.name, |stage| &stage.depends_on)?;
+    Ok(ordered.into_iter().cloned().collect())
+}
+
+/// Orders `items` so each comes after the items named in its dependencies,
+/// keeping the given order where dependencies allow. `what` names the kind
+/// of item in errors about duplicates, unknown dependencies and cycles.
+pub fn topological_order<'a, T>(
+    items: &'a [T],
+    what: &str,
+    name: impl Fn(&T) -> &str,
+    depends_on: impl Fn(&T) -> &[String],
+) -> Result<Vec<&'a T>> {
+    let mut label = what.to_string();
+    label[..1].make_ascii_uppercase();
+
+    let mut names = HashSet::new();
+    for item in items {
+        if !names.insert(name(item)) {
+            anyhow::bail!("{} '{}' is declared more than once", label, name(item));
+        }
+    }
+    for item in items {
+        if let Some(missing) = depends_on(item).iter().find(|dep| !names.contains(dep.as_str())) {
+            anyhow::bail!("{} '{}' depends on unknown {} '{}'", label, name(item), what, missing);
+        }
+    }
+
+    let mut ordered: Vec<&T> = Vec::with_capacity(items.len());
+    let mut done: HashSet<&str> = HashSet::new();
+    while ordered.len() < items.len() {
+        let next = items.iter().find(|item| {
+            !done.contains(name(item)) && depends_on(item).iter().all(|dep| done.contains(dep.as_str()))
+        });
+        match next {
+            Some(item) => {
+                done.insert(name(item));
+                ordered.push(item);
+            }
+            None => {
+                let blocked: Vec<&str> = items
+                    .iter()
+                    .map(&name)
+                    .filter(|item| !done.contains(item))
+                    .collect();
+                anyhow::bail!("{} dependencies form a cycle between: {}", label, blocked.join(", "));
+            }
+        }
+    }
+    Ok(ordered)
+}
+
+/// Runs a command stage's commands through the shell in `project_path`, up
+/// to `jobs` (or `default_jobs`) at once. Every command runs even if another
+/// fails; the stage fails if any did.
+pub fn run_commands(stage: &StageConfig, config: &Config, project_path: &Path, default_jobs: usize) -> Result<()> {
+    let mut env: HashMap<&str, String> = HashMap::new();
+    env.insert("LOL_STAGE", stage.name.clone());
+    env.insert("LOL_PROJECT_DIR", project_path.display().to_string());
+    if let Some(output_dir) = config.output_dir(project_path) {
+        env.insert("LOL_OUTPUT_DIR", output_dir.display().to_string());
+    }
+
+    let jobs = stage.jobs.unwrap_or(default_jobs).max(1);
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(jobs)
+        .build()
+        .context("Failed to start stage workers")?;
+
+    let failures: Vec<String> = pool.install(|| {
+        stage
+            .commands
+            .par_iter()
+            .filter_map(|command| {
+                say!("   ▶ {}", command.dimmed());
+                let status = hooks::shell(command)
+                    .current_dir(project_path)
+                    .envs(&env)
+                    .status();
+                match status {
+                    Ok(status) if status.success() => None,
+                    Ok(status) => Some(format!("'{}' failed with {}", command, status)),
+                    Err(error) => Some(format!("'{}' could not be run: {}", command, error)),
+                }
+            })
+            .collect()
+    });
+
+    if !failures.is_empty() {
+        anyhow::bail!("Stage '{}': {}", stage.name, failures.join("; "));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn stage(name: &str, depends_on: &[&str]) -> StageConfig {
+        StageConfig {
+            name: name.to_string(),
+            depends_on: depends_on.iter().map(|dep| dep.to_string()).collect(),
+            ..StageConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_order_respects_dependencies() {
+        let stages = vec![
+            stage("package", &["test", "link"]),
+            stage("test", &["link"]),
+            stage("generate", &[]),
+            stage("compile", &["generate"]),
+            stage("link", &["compile"]),
+        ];
+        let names: Vec<String> = order(&stages).unwrap().into_iter().map(|stage| stage.name).collect();
+        assert_eq!(names, ["generate", "compile", "link", "test", "package"]);
+
+        assert_eq!(kind(&stages[3]), StageKind::Compile);
+        assert_eq!(kind(&stages[2]), StageKind::Generate);
+        assert_eq!(kind(&stages[0]), StageKind::Commands);
+    }
+
+    #[test]
+    fn test_order_rejects_cycles_and_unknown_stages() {
+        let cycle = vec![stage("a", &["b"]), stage("b", &["a"]), stage("c", &[])];
+        let error = order(&cycle).unwrap_err().to_string();
+        assert!(error.contains("cycle between: a, b"), "{}", error);
+
+        assert!(order(&[stage("link", &["compile"])]).is_err());
+    }
+
+    #[test]
+    fn test_run_commands_reports_failures() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut link = stage("link", &[]);
+        link.commands = vec!["echo $LOL_STAGE > stage.txt".to_string(), "exit 2".to_string()];
+        link.jobs = Some(2);
+
+        let error = run_commands(&link, &Config::default(), temp_dir.path(), 1).unwrap_err();
+        assert!(error.to_string().contains("'exit 2' failed"));
+        let written = std::fs::read_to_string(temp_dir.path().join("stage.txt")).unwrap();
+        assert_eq!(written.trim(), "link");
+    }
+}
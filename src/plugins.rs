@@ -0,0 +1,316 @@
+//! Sandboxed WebAssembly plugins loaded from `~/.config/lol/plugins`.
+//!
+//! A plugin is a core WebAssembly module with no imports. It exports its
+//! linear `memory` and:
+//!
+//! - `lol_abi_version() -> i32`, which must return [`PLUGIN_ABI_VERSION`]
+//! - `lol_alloc(len: i32) -> i32`, returning a buffer the host writes input to
+//! - `lol_manifest() -> i64`, a [`PluginManifest`] as JSON
+//! - optionally `lol_parse_diagnostics(ptr: i32, len: i32) -> i64`, taking raw
+//!   tool output and returning a JSON array of diagnostics
+//! - optionally `lol_hook(ptr: i32, len: i32) -> i32`, taking a [`HookEvent`]
+//!   as JSON and returning non-zero to fail the build
+//!
+//! Strings are returned packed as `(ptr << 32) | len`. Each call runs with a
+//! fuel budget so a misbehaving plugin cannot hang the build.
+
+use std::path::{Path, PathBuf};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use crate::config::CustomLanguageConfig;
+use crate::diagnostics::Diagnostic;
+
+#[cfg_attr(not(feature = "plugins"), allow(dead_code))]
+pub const PLUGIN_ABI_VERSION: i32 = 1;
+
+/// What a plugin provides, as reported by its `lol_manifest` export.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PluginManifest {
+    pub name: String,
+    pub version: String,
+    #[serde(default)]
+    pub languages: Vec<CustomLanguageConfig>,
+    #[serde(default)]
+    pub hooks: Vec<String>,
+}
+
+/// Passed to a plugin's `lol_hook` export.
+#[derive(Debug, Serialize)]
+pub struct HookEvent<'a> {
+    pub hook: &'a str,
+    pub project: &'a Path,
+    pub success: Option<bool>,
+}
+
+/// The plugins found at startup, at most one version of each.
+#[derive(Default)]
+pub struct PluginHost {
+    plugins: Vec<runtime::Plugin>,
+    /// `.wasm` files that could not be loaded, with the reason
+    pub skipped: Vec<(PathBuf, String)>,
+}
+
+impl PluginHost {
+    pub fn plugin_dir() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("lol").join("plugins"))
+    }
+
+    /// Loads every `*.wasm` in the plugin directory. When several files
+    /// declare the same plugin name only the highest version is kept.
+    pub fn load() -> Self {
+        match Self::plugin_dir() {
+            Some(dir) => Self::load_from(&dir),
+            None => Self::default(),
+        }
+    }
+
+    pub fn load_from(dir: &Path) -> Self {
+        let mut host = Self::default();
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return host;
+        };
+
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "wasm"))
+            .collect();
+        paths.sort();
+
+        for path in paths {
+            match runtime::Plugin::load(&path) {
+                Ok(plugin) => host.add(plugin),
+                Err(error) => host.skipped.push((path, format!("{:#}", error))),
+            }
+        }
+        host
+    }
+
+    fn add(&mut self, plugin: runtime::Plugin) {
+        let existing = self
+            .plugins
+            .iter()
+            .position(|loaded| loaded.manifest.name == plugin.manifest.name);
+        match existing {
+            Some(index) if version_key(&plugin.manifest.version) > version_key(&self.plugins[index].manifest.version) => {
+                self.plugins[index] = plugin;
+            }
+            Some(_) => {}
+            None => self.plugins.push(plugin),
+        }
+    }
+
+    pub fn manifests(&self) -> impl Iterator<Item = &PluginManifest> {
+        self.plugins.iter().map(|plugin| &plugin.manifest)
+    }
+
+    /// Language definitions contributed by plugins.
+    pub fn languages(&self) -> Vec<CustomLanguageConfig> {
+        self.manifests()
+            .flat_map(|manifest| manifest.languages.iter().cloned())
+            .collect()
+    }
+
+    /// Diagnostics plugins recognise in `output`, on top of the built-in parsers.
+    pub fn parse_diagnostics(&self, output: &str) -> Vec<Diagnostic> {
+        self.plugins
+            .iter()
+            .flat_map(|plugin| plugin.parse_diagnostics(output).unwrap_or_default())
+            .collect()
+    }
+
+    /// Runs `hook` in every plugin that registered it.
+    pub fn run_hook(&self, event: &HookEvent) -> Result<()> {
+        for plugin in self.plugins.iter().filter(|plugin| plugin.manifest.hooks.iter().any(|hook| hook == event.hook)) {
+            plugin.run_hook(event)?;
+        }
+        Ok(())
+    }
+}
+
+/// Orders dotted versions numerically, so `1.10.0` sorts after `1.9.2`.
+pub(crate) fn version_key(version: &str) -> Vec<u64> {
+    version
+        .trim_start_matches('v')
+        .split('.')
+        .map(|part| part.chars().take_while(char::is_ascii_digit).collect::<String>().parse().unwrap_or(0))
+        .collect()
+}
+
+#[cfg(feature = "plugins")]
+mod runtime {
+    use std::path::Path;
+    use std::sync::Mutex;
+    use anyhow::{bail, Context, Result};
+    use wasmtime::{Config, Engine, Instance, Memory, Module, Store};
+    use super::{HookEvent, PluginManifest, PLUGIN_ABI_VERSION};
+    use crate::diagnostics::Diagnostic;
+
+    const FUEL_PER_CALL: u64 = 500_000_000;
+
+    pub struct Plugin {
+        pub manifest: PluginManifest,
+        store: Mutex<Store<()>>,
+        instance: Instance,
+        memory: Memory,
+    }
+
+    impl Plugin {
+        pub fn load(path: &Path) -> Result<Self> {
+            let mut config = Config::new();
+            config.consume_fuel(true);
+            let engine = Engine::new(&config)?;
+            let module = Module::from_file(&engine, path)
+                .with_context(|| format!("Failed to compile {}", path.display()))?;
+            if module.imports().len() > 0 {
+                bail!("plugins may not import host functions");
+            }
+
+            let mut store = Store::new(&engine, ());
+            store.set_fuel(FUEL_PER_CALL)?;
+            let instance = Instance::new(&mut store, &module, &[])?;
+            let memory = instance
+                .get_memory(&mut store, "memory")
+                .context("plugin does not export its memory")?;
+
+            let abi_version = instance
+                .get_typed_func::<(), i32>(&mut store, "lol_abi_version")?
+                .call(&mut store, ())?;
+            if abi_version != PLUGIN_ABI_VERSION {
+                bail!("plugin ABI version {} is not supported (expected {})", abi_version, PLUGIN_ABI_VERSION);
+            }
+
+            let packed = instance
+                .get_typed_func::<(), i64>(&mut store, "lol_manifest")?
+                .call(&mut store, ())?;
+            let manifest: PluginManifest = serde_json::from_slice(&read_packed(&memory, &store, packed)?)
+                .context("plugin returned an invalid manifest")?;
+
+            Ok(Self {
+                manifest,
+                store: Mutex::new(store),
+                instance,
+                memory,
+            })
+        }
+
+        pub fn parse_diagnostics(&self, output: &str) -> Result<Vec<Diagnostic>> {
+            let mut store = self.store.lock().unwrap();
+            let Ok(parse) = self
+                .instance
+                .get_typed_func::<(i32, i32), i64>(&mut *store, "lol_parse_diagnostics")
+            else {
+                return Ok(Vec::new());
+            };
+
+            let (ptr, len) = self.write_input(&mut store, output.as_bytes())?;
+            let packed = parse.call(&mut *store, (ptr, len))?;
+            Ok(serde_json::from_slice(&read_packed(&self.memory, &store, packed)?)?)
+        }
+
+        pub fn run_hook(&self, event: &HookEvent) -> Result<()> {
+            let mut store = self.store.lock().unwrap();
+            let hook = self
+                .instance
+                .get_typed_func::<(i32, i32), i32>(&mut *store, "lol_hook")
+                .with_context(|| format!("plugin {} registers hooks but has no lol_hook export", self.manifest.name))?;
+
+            let (ptr, len) = self.write_input(&mut store, &serde_json::to_vec(event)?)?;
+            let status = hook.call(&mut *store, (ptr, len))?;
+            if status != 0 {
+                bail!("{} hook from plugin {} failed with status {}", event.hook, self.manifest.name, status);
+            }
+            Ok(())
+        }
+
+        fn write_input(&self, store: &mut Store<()>, bytes: &[u8]) -> Result<(i32, i32)> {
+            store.set_fuel(FUEL_PER_CALL)?;
+            let len = i32::try_from(bytes.len()).context("input too large for plugin")?;
+            let ptr = self
+                .instance
+                .get_typed_func::<i32, i32>(&mut *store, "lol_alloc")?
+                .call(&mut *store, len)?;
+            self.memory.write(&mut *store, ptr as usize, bytes)?;
+            Ok((ptr, len))
+        }
+    }
+
+    fn read_packed(memory: &Memory, store: &Store<()>, packed: i64) -> Result<Vec<u8>> {
+        let ptr = (packed as u64 >> 32) as usize;
+        let len = (packed as u64 & 0xffff_ffff) as usize;
+        let data = memory
+            .data(store)
+            .get(ptr..ptr + len)
+            .context("plugin returned an out-of-bounds string")?;
+        Ok(data.to_vec())
+    }
+}
+
+#[cfg(not(feature = "plugins"))]
+mod runtime {
+    use std::path::Path;
+    use anyhow::{bail, Result};
+    use super::{HookEvent, PluginManifest};
+    use crate::diagnostics::Diagnostic;
+
+    pub struct Plugin {
+        pub manifest: PluginManifest,
+    }
+
+    impl Plugin {
+        pub fn load(_path: &Path) -> Result<Self> {
+            bail!("lol was built without plugin support (enable the `plugins` feature)")
+        }
+
+        pub fn parse_diagnostics(&self, _output: &str) -> Result<Vec<Diagnostic>> {
+            Ok(Vec::new())
+        }
+
+        pub fn run_hook(&self, _event: &HookEvent) -> Result<()> {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_version_ordering() {
+        assert!(version_key("1.10.0") > version_key("1.9.2"));
+        assert!(version_key("v2.0") > version_key("1.99.99"));
+        assert_eq!(version_key("1.2.3-beta"), vec![1, 2, 3]);
+    }
+
+    #[cfg(feature = "plugins")]
+    #[test]
+    fn test_load_plugin_and_run_hook() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let manifest = r#"{"name":"demo","version":"0.2.0","hooks":["pre_build"]}"#;
+        let module = format!(
+            r#"(module
+                (memory (export "memory") 1)
+                (data (i32.const 0) "{manifest}")
+                (global $next (mut i32) (i32.const 1024))
+                (func (export "lol_abi_version") (result i32) (i32.const 1))
+                (func (export "lol_alloc") (param $len i32) (result i32)
+                    (local $ptr i32)
+                    (local.set $ptr (global.get $next))
+                    (global.set $next (i32.add (global.get $next) (local.get $len)))
+                    (local.get $ptr))
+                (func (export "lol_manifest") (result i64) (i64.const {len}))
+                (func (export "lol_hook") (param i32 i32) (result i32) (i32.const 0)))"#,
+            manifest = manifest.replace('"', "\\\""),
+            len = manifest.len(),
+        );
+        // wasmtime accepts the text format wherever it accepts binaries
+        std::fs::write(temp_dir.path().join("demo.wasm"), module).unwrap();
+
+        let host = PluginHost::load_from(temp_dir.path());
+        assert!(host.skipped.is_empty(), "{:?}", host.skipped);
+        assert_eq!(host.manifests().next().unwrap().version, "0.2.0");
+
+        let event = HookEvent { hook: "pre_build", project: temp_dir.path(), success: None };
+        host.run_hook(&event).unwrap();
+    }
+}
@@ -0,0 +1,121 @@
+use anyhow::{Context, Result};
+use crate::args::Args;
+use crate::config::{PriorityConfig, PriorityProfile};
+
+/// Niceness of `lol watch`, `lol lsp` and `lol daemon` builds on a
+/// developer's machine when nothing else is configured.
+pub const BACKGROUND_NICE: i32 = 10;
+
+/// Whether lol runs in CI, which every major CI service signals by setting
+/// `CI`.
+pub fn is_ci() -> bool {
+    std::env::var("CI").is_ok_and(|value| !value.is_empty() && value != "false" && value != "0")
+}
+
+/// The `priority` settings for where lol runs.
+pub fn profile(config: &PriorityConfig) -> &PriorityProfile {
+    if is_ci() {
+        &config.ci
+    } else {
+        &config.interactive
+    }
+}
+
+/// Parses a CPU list such as `0-3,6` into CPU numbers.
+pub fn parse_cpus(list: &str) -> Result<Vec<usize>> {
+    let mut cpus = Vec::new();
+    for part in list.split(',').map(str::trim).filter(|part| !part.is_empty()) {
+        let number = |text: &str| text.trim().parse::<usize>().with_context(|| format!("Invalid CPU {:?} in {:?}", text, list));
+        match part.split_once('-') {
+            Some((first, last)) => {
+                let (first, last) = (number(first)?, number(last)?);
+                anyhow::ensure!(first <= last, "Invalid CPU range {:?} in {:?}", part, list);
+                cpus.extend(first..=last);
+            }
+            None => cpus.push(number(part)?),
+        }
+    }
+    anyhow::ensure!(!cpus.is_empty(), "No CPUs in {:?}", list);
+    cpus.sort_unstable();
+    cpus.dedup();
+    Ok(cpus)
+}
+
+/// What lol's process runs with: the niceness and CPUs from the command
+/// line, else from the profile, else `BACKGROUND_NICE` for `background`
+/// builds outside CI.
+pub struct Priority {
+    pub nice: Option<i32>,
+    pub cpus: Option<Vec<usize>>,
+}
+
+impl Priority {
+    pub fn resolve(args: &Args, config: &PriorityConfig, background: bool) -> Result<Self> {
+        let profile = profile(config);
+        let nice = args.nice.or(profile.nice).or_else(|| (background && !is_ci()).then_some(BACKGROUND_NICE));
+        let cpus = args.cpus.as_deref().or(profile.cpus.as_deref()).map(parse_cpus).transpose()?;
+        Ok(Self { nice, cpus })
+    }
+
+    /// Applies both to lol itself; the compilers, hooks and warm compiler
+    /// processes it starts inherit them.
+    pub fn apply(&self) -> Result<()> {
+        if let Some(nice) = self.nice {
+            set_nice(nice).with_context(|| format!("Could not set the nice level to {}", nice))?;
+        }
+        if let Some(cpus) = &self.cpus {
+            set_affinity(cpus).with_context(|| format!("Could not pin the build to CPUs {:?}", cpus))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+fn set_nice(nice: i32) -> Result<()> {
+    // SAFETY: setpriority only reads its arguments; `who` 0 is this process
+    if unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, nice) } != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn set_nice(_nice: i32) -> Result<()> {
+    anyhow::bail!("nice levels are only supported on Unix")
+}
+
+#[cfg(target_os = "linux")]
+fn set_affinity(cpus: &[usize]) -> Result<()> {
+    // SAFETY: the set is zeroed before use and only written through CPU_SET
+    // within its size; pid 0 is this process
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        for &cpu in cpus {
+            anyhow::ensure!(cpu < libc::CPU_SETSIZE as usize, "CPU {} is out of range", cpu);
+            libc::CPU_SET(cpu, &mut set);
+        }
+        if libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_affinity(_cpus: &[usize]) -> Result<()> {
+    anyhow::bail!("pinning to CPUs is only supported on Linux")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_cpu_lists() {
+        assert_eq!(parse_cpus("0-3,6").unwrap(), [0, 1, 2, 3, 6]);
+        assert_eq!(parse_cpus("2, 1,2").unwrap(), [1, 2]);
+        assert!(parse_cpus("3-1").is_err());
+        assert!(parse_cpus("a").is_err());
+        assert!(parse_cpus("").is_err());
+    }
+}
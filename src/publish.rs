@@ -0,0 +1,318 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::thread;
+use std::time::Duration;
+use anyhow::{Context, Result};
+use colored::*;
+use serde::Deserialize;
+use crate::artifacts;
+use crate::config::{Config, PublishConfig};
+use crate::package::{self, DIST_DIR};
+
+/// Written next to the packages and published with them.
+pub const CHECKSUMS_FILE: &str = "SHA256SUMS";
+
+/// What `lol package` and AppImage builds produce, plus their zsync files
+/// and detached signatures.
+const RELEASE_EXTENSIONS: &[&str] = &["gz", "zip", "deb", "rpm", "flatpak", "snap", "AppImage", "zsync", "asc", "sig"];
+
+/// A place release files are uploaded to.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Destination {
+    GitHub { repo: String },
+    S3 { uri: String },
+    Http { url: String },
+}
+
+impl Destination {
+    fn describe(&self, tag: &str) -> String {
+        match self {
+            Destination::GitHub { repo } => format!("GitHub release {} of {}", tag, repo),
+            Destination::S3 { uri } => uri.clone(),
+            Destination::Http { url } => url.clone(),
+        }
+    }
+}
+
+/// The configured destinations, with `{tag}` and `{version}` filled in.
+pub fn destinations(config: &PublishConfig, tag: &str, version: &str) -> Vec<Destination> {
+    let expand = |template: &str| template.replace("{tag}", tag).replace("{version}", version).trim_end_matches('/').to_string();
+    let mut destinations = Vec::new();
+    if let Some(repo) = &config.github {
+        destinations.push(Destination::GitHub { repo: repo.clone() });
+    }
+    if let Some(uri) = &config.s3 {
+        destinations.push(Destination::S3 { uri: expand(uri) });
+    }
+    if let Some(url) = &config.url {
+        destinations.push(Destination::Http { url: expand(url) });
+    }
+    destinations
+}
+
+/// Packages in `dist/` and AppImages in `<name>_appimage/` directories,
+/// sorted by name.
+pub fn release_files(project_path: &Path) -> Result<Vec<PathBuf>> {
+    let mut directories = vec![project_path.join(DIST_DIR)];
+    for entry in fs::read_dir(project_path).with_context(|| format!("Failed to read {}", project_path.display()))? {
+        let path = entry?.path();
+        if path.is_dir() && path.file_name().is_some_and(|name| name.to_string_lossy().ends_with("_appimage")) {
+            directories.push(path);
+        }
+    }
+
+    let mut files = Vec::new();
+    for directory in directories.iter().filter(|directory| directory.is_dir()) {
+        for entry in fs::read_dir(directory)? {
+            let path = entry?.path();
+            let release = path.extension().is_some_and(|ext| RELEASE_EXTENSIONS.contains(&ext.to_string_lossy().as_ref()));
+            if path.is_file() && release {
+                files.push(path);
+            }
+        }
+    }
+    files.sort_by_key(|path| path.file_name().map(|name| name.to_os_string()));
+    Ok(files)
+}
+
+/// `sha256sum`-style lines for `files`, so `sha256sum -c` can check a download.
+pub fn checksums(files: &[PathBuf]) -> Result<String> {
+    let mut sums = String::new();
+    for file in files {
+        sums.push_str(&format!("{}  {}\n", artifacts::sha256(file)?, file_name(file)));
+    }
+    Ok(sums)
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default()
+}
+
+/// Uploads the release files and their checksums to every configured
+/// destination. The tag defaults to `v<version>`. A dry run lists the
+/// uploads without writing or sending anything.
+pub fn run(project_path: &Path, config: &Config, tag: Option<&str>, dry_run: bool) -> Result<()> {
+    let version = package::package_version(config, project_path);
+    let tag = tag.map(str::to_string).unwrap_or_else(|| format!("v{}", version));
+    let destinations = destinations(&config.publish, &tag, &version);
+    if destinations.is_empty() {
+        anyhow::bail!("No publish destination configured; set publish.github, publish.s3 or publish.url");
+    }
+
+    let mut files = release_files(project_path)?;
+    files.retain(|file| file_name(file) != CHECKSUMS_FILE);
+    if files.is_empty() {
+        anyhow::bail!("Nothing to publish; run `lol package` or build an AppImage first");
+    }
+    let checksums_path = project_path.join(DIST_DIR).join(CHECKSUMS_FILE);
+    if !dry_run {
+        fs::create_dir_all(project_path.join(DIST_DIR))?;
+        fs::write(&checksums_path, checksums(&files)?)
+            .with_context(|| format!("Failed to write {}", checksums_path.display()))?;
+    }
+    files.push(checksums_path);
+
+    for destination in &destinations {
+        say!("🚚 Publishing {} files to {}", files.len(), destination.describe(&tag).bold());
+        if dry_run {
+            for file in &files {
+                say!("  would upload {}", file.display());
+            }
+            continue;
+        }
+
+        let attempts = config.publish.retries.max(1);
+        let release = match destination {
+            Destination::GitHub { repo } => Some(retry(attempts, "Finding the release", || GitHubRelease::find_or_create(repo, &tag))?),
+            _ => None,
+        };
+        for file in &files {
+            say!("📤 {}", file_name(file));
+            retry(attempts, &format!("Uploading {}", file_name(file)), || match (destination, &release) {
+                (Destination::GitHub { .. }, Some(release)) => release.upload(file),
+                (Destination::S3 { uri }, _) => upload_s3(uri, file),
+                (Destination::Http { url }, _) => put(url, file),
+                (Destination::GitHub { .. }, None) => unreachable!("the release is looked up first"),
+            })?;
+        }
+    }
+    if dry_run {
+        say!("{} Dry run; nothing was uploaded", "ℹ️".blue());
+    } else {
+        say!("🎉 {} Published {}", "SUCCESS".bold().green(), tag);
+    }
+    Ok(())
+}
+
+/// Runs `action` up to `attempts` times, waiting 2, 4, 8... seconds
+/// between tries.
+fn retry<T>(attempts: u32, what: &str, mut action: impl FnMut() -> Result<T>) -> Result<T> {
+    let mut attempt = 1;
+    loop {
+        match action() {
+            Ok(value) => return Ok(value),
+            Err(error) if attempt < attempts => {
+                let delay = Duration::from_secs(1 << attempt);
+                tracing::warn!("{} failed ({:#}); retrying in {}s", what, error, delay.as_secs());
+                thread::sleep(delay);
+                attempt += 1;
+            }
+            Err(error) => return Err(error.context(format!("{} failed after {} attempts", what, attempts))),
+        }
+    }
+}
+
+/// A release on GitHub, or GitHub Enterprise when `GITHUB_API_URL` is set.
+struct GitHubRelease {
+    api: String,
+    repo: String,
+    token: String,
+    upload_url: String,
+    /// Existing asset IDs by name, replaced when uploaded again
+    assets: HashMap<String, u64>,
+}
+
+#[derive(Deserialize)]
+struct ReleaseResponse {
+    upload_url: String,
+    assets: Vec<AssetResponse>,
+}
+
+#[derive(Deserialize)]
+struct AssetResponse {
+    id: u64,
+    name: String,
+}
+
+impl GitHubRelease {
+    fn find_or_create(repo: &str, tag: &str) -> Result<Self> {
+        let token = std::env::var("GITHUB_TOKEN")
+            .or_else(|_| std::env::var("GH_TOKEN"))
+            .context("Publishing to GitHub needs GITHUB_TOKEN or GH_TOKEN")?;
+        let api = std::env::var("GITHUB_API_URL").unwrap_or_else(|_| "https://api.github.com".to_string());
+        let authorization = format!("Bearer {}", token);
+
+        let found = ureq::get(&format!("{}/repos/{}/releases/tags/{}", api, repo, tag))
+            .set("Authorization", &authorization)
+            .set("Accept", "application/vnd.github+json")
+            .call();
+        let response = match found {
+            Ok(response) => response,
+            Err(ureq::Error::Status(404, _)) => {
+                say!("🏷️  Creating release {}", tag);
+                ureq::post(&format!("{}/repos/{}/releases", api, repo))
+                    .set("Authorization", &authorization)
+                    .set("Accept", "application/vnd.github+json")
+                    .send_json(serde_json::json!({ "tag_name": tag, "name": tag }))
+                    .map_err(http_error)?
+            }
+            Err(error) => return Err(http_error(error)),
+        };
+        let release: ReleaseResponse = response.into_json().context("Unexpected release response from GitHub")?;
+        Ok(Self {
+            api,
+            repo: repo.to_string(),
+            token,
+            // The API returns a URI template, `.../assets{?name,label}`
+            upload_url: release.upload_url.split('{').next().unwrap_or_default().to_string(),
+            assets: release.assets.into_iter().map(|asset| (asset.name, asset.id)).collect(),
+        })
+    }
+
+    fn upload(&self, file: &Path) -> Result<()> {
+        let name = file_name(file);
+        let authorization = format!("Bearer {}", self.token);
+        if let Some(id) = self.assets.get(&name) {
+            match ureq::delete(&format!("{}/repos/{}/releases/assets/{}", self.api, self.repo, id))
+                .set("Authorization", &authorization)
+                .call()
+            {
+                // Already removed by an earlier attempt
+                Ok(_) | Err(ureq::Error::Status(404, _)) => {}
+                Err(error) => return Err(http_error(error)),
+            }
+        }
+        let body = fs::read(file).with_context(|| format!("Failed to read {}", file.display()))?;
+        ureq::post(&self.upload_url)
+            .query("name", &name)
+            .set("Authorization", &authorization)
+            .set("Content-Type", "application/octet-stream")
+            .send_bytes(&body)
+            .map_err(http_error)?;
+        Ok(())
+    }
+}
+
+fn upload_s3(uri: &str, file: &Path) -> Result<()> {
+    let destination = format!("{}/{}", uri, file_name(file));
+    let status = Command::new("aws")
+        .args(["s3", "cp", "--only-show-errors"])
+        .arg(file)
+        .arg(&destination)
+        .status()
+        .context("Publishing to S3 needs the AWS CLI (`aws`) on the PATH")?;
+    if !status.success() {
+        anyhow::bail!("aws s3 cp to {} failed with {}", destination, status);
+    }
+    Ok(())
+}
+
+/// PUTs `file` under `url`, with `LOL_PUBLISH_TOKEN` as a bearer token
+/// when it is set.
+fn put(url: &str, file: &Path) -> Result<()> {
+    let body = fs::read(file).with_context(|| format!("Failed to read {}", file.display()))?;
+    let mut request = ureq::put(&format!("{}/{}", url, file_name(file))).set("Content-Type", "application/octet-stream");
+    if let Ok(token) = std::env::var("LOL_PUBLISH_TOKEN") {
+        request = request.set("Authorization", &format!("Bearer {}", token));
+    }
+    request.send_bytes(&body).map_err(http_error)?;
+    Ok(())
+}
+
+fn http_error(error: ureq::Error) -> anyhow::Error {
+    match error {
+        ureq::Error::Status(code, response) => {
+            let url = response.get_url().to_string();
+            let body = response.into_string().unwrap_or_default();
+            anyhow::anyhow!("{} returned {}: {}", url, code, body.trim())
+        }
+        error => anyhow::anyhow!("{}", error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_release_files_and_destinations() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("dist/demo-1.0.0")).unwrap();
+        fs::create_dir_all(root.join("Demo_appimage")).unwrap();
+        fs::write(root.join("dist/demo-1.0.0.tar.gz"), "archive").unwrap();
+        fs::write(root.join("dist/io.lol.demo.json"), "{}").unwrap();
+        fs::write(root.join("Demo_appimage/Demo.AppImage"), "appimage").unwrap();
+
+        let files = release_files(root).unwrap();
+        let names: Vec<String> = files.iter().map(|file| file_name(file)).collect();
+        assert_eq!(names, ["Demo.AppImage", "demo-1.0.0.tar.gz"]);
+        assert!(checksums(&files).unwrap().ends_with("  demo-1.0.0.tar.gz\n"));
+
+        let config = PublishConfig {
+            github: Some("me/demo".to_string()),
+            url: Some("https://files.example.com/demo/{version}/".to_string()),
+            ..PublishConfig::default()
+        };
+        assert_eq!(
+            destinations(&config, "v1.0.0", "1.0.0"),
+            [
+                Destination::GitHub { repo: "me/demo".to_string() },
+                Destination::Http { url: "https://files.example.com/demo/1.0.0".to_string() },
+            ]
+        );
+    }
+}
@@ -0,0 +1,186 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use crate::language_support::Toolchain;
+
+/// The system interpreter: `python` on Windows, whose `python3` may only
+/// be the stub that opens the Microsoft Store.
+pub const PYTHON: &str = if cfg!(windows) { "python" } else { "python3" };
+
+/// Where the Python interpreter used for a build came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PythonEnvironmentKind {
+    Override,
+    Virtualenv(PathBuf),
+    Poetry,
+    Conda(String),
+    System,
+}
+
+#[derive(Debug, Clone)]
+pub struct PythonEnvironment {
+    pub kind: PythonEnvironmentKind,
+    pub interpreter: Toolchain,
+}
+
+impl PythonEnvironment {
+    /// Resolves the interpreter for `project_path`, preferring an explicit
+    /// `python_path`, then a project virtualenv, Poetry, conda, and finally
+    /// the system interpreter.
+    pub fn detect(project_path: &Path, python_path: Option<&str>) -> Self {
+        if let Some(path) = python_path {
+            return Self {
+                kind: PythonEnvironmentKind::Override,
+                interpreter: Toolchain::new(path),
+            };
+        }
+
+        for venv in [".venv", "venv", "env"] {
+            let venv_dir = project_path.join(venv);
+            if let Some(python) = Self::venv_interpreter(&venv_dir) {
+                return Self {
+                    kind: PythonEnvironmentKind::Virtualenv(venv_dir),
+                    interpreter: Toolchain::new(python.to_string_lossy()),
+                };
+            }
+        }
+
+        if project_path.join("poetry.lock").exists() {
+            if let Some(python) = Self::poetry_interpreter(project_path) {
+                return Self {
+                    kind: PythonEnvironmentKind::Poetry,
+                    interpreter: Toolchain::new(python),
+                };
+            }
+        }
+
+        for file in ["environment.yml", "environment.yaml"] {
+            if let Some(name) = Self::conda_environment_name(&project_path.join(file)) {
+                return Self {
+                    kind: PythonEnvironmentKind::Conda(name.clone()),
+                    interpreter: Toolchain::with_args("conda", ["run", "-n", name.as_str(), "python"]),
+                };
+            }
+        }
+
+        Self {
+            kind: PythonEnvironmentKind::System,
+            interpreter: Toolchain::new(PYTHON),
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match &self.kind {
+            PythonEnvironmentKind::Override => format!("{} (python_path)", self.interpreter),
+            PythonEnvironmentKind::Virtualenv(dir) => {
+                format!("{} (virtualenv {})", self.interpreter, dir.display())
+            }
+            PythonEnvironmentKind::Poetry => format!("{} (poetry)", self.interpreter),
+            PythonEnvironmentKind::Conda(name) => format!("{} (conda env '{}')", self.interpreter, name),
+            PythonEnvironmentKind::System => format!("{} (system)", self.interpreter),
+        }
+    }
+
+    /// Directory containing `Python.h` for this interpreter, used when
+    /// building C extension modules.
+    pub fn include_dir(&self) -> Option<PathBuf> {
+        let output = self
+            .interpreter
+            .command()
+            .args(["-c", "import sysconfig; print(sysconfig.get_paths()['include'])"])
+            .output()
+            .ok()?;
+
+        if !output.status.success() {
+            return None;
+        }
+
+        let include_dir = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        (!include_dir.is_empty()).then(|| PathBuf::from(include_dir))
+    }
+
+    fn venv_interpreter(venv_dir: &Path) -> Option<PathBuf> {
+        [
+            venv_dir.join("bin").join("python"),
+            venv_dir.join("Scripts").join("python.exe"),
+        ]
+        .into_iter()
+        .find(|candidate| candidate.is_file())
+    }
+
+    fn poetry_interpreter(project_path: &Path) -> Option<String> {
+        let output = Command::new("poetry")
+            .args(["env", "info", "--executable"])
+            .current_dir(project_path)
+            .output()
+            .ok()?;
+
+        if !output.status.success() {
+            return None;
+        }
+
+        let executable = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        (!executable.is_empty()).then_some(executable)
+    }
+
+    fn conda_environment_name(environment_file: &Path) -> Option<String> {
+        let content = fs::read_to_string(environment_file).ok()?;
+        content.lines().find_map(|line| {
+            line.strip_prefix("name:")
+                .map(|name| name.trim().trim_matches(|c| c == '"' || c == '\'').to_string())
+                .filter(|name| !name.is_empty())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_override_takes_precedence() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("environment.yml"), "name: science\n").unwrap();
+
+        let env = PythonEnvironment::detect(temp_dir.path(), Some("/opt/python/bin/python3.12"));
+        assert_eq!(env.kind, PythonEnvironmentKind::Override);
+        assert_eq!(env.interpreter.program, "/opt/python/bin/python3.12");
+    }
+
+    #[test]
+    fn test_detect_virtualenv() {
+        let temp_dir = TempDir::new().unwrap();
+        let bin_dir = temp_dir.path().join(".venv").join("bin");
+        fs::create_dir_all(&bin_dir).unwrap();
+        fs::write(bin_dir.join("python"), "").unwrap();
+
+        let env = PythonEnvironment::detect(temp_dir.path(), None);
+        assert!(matches!(env.kind, PythonEnvironmentKind::Virtualenv(_)));
+        assert!(env.interpreter.program.ends_with("python"));
+    }
+
+    #[test]
+    fn test_detect_conda_environment() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(
+            temp_dir.path().join("environment.yml"),
+            "name: \"science\"\ndependencies:\n  - numpy\n",
+        )
+        .unwrap();
+
+        let env = PythonEnvironment::detect(temp_dir.path(), None);
+        assert_eq!(env.kind, PythonEnvironmentKind::Conda("science".to_string()));
+        assert_eq!(env.interpreter.program, "conda");
+        assert_eq!(env.interpreter.args, vec!["run", "-n", "science", "python"]);
+    }
+
+    #[test]
+    fn test_falls_back_to_system_python() {
+        let temp_dir = TempDir::new().unwrap();
+
+        let env = PythonEnvironment::detect(temp_dir.path(), None);
+        assert_eq!(env.kind, PythonEnvironmentKind::System);
+        assert_eq!(env.interpreter.program, PYTHON);
+    }
+}
@@ -0,0 +1,135 @@
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+use std::time::UNIX_EPOCH;
+use anyhow::{Context, Result};
+use filetime::FileTime;
+use walkdir::WalkDir;
+
+/// The epoch `--reproducible` chose, set once before anything is built.
+static EPOCH: OnceLock<u64> = OnceLock::new();
+
+/// Makes `epoch` the build's `SOURCE_DATE_EPOCH`, for lol itself and for
+/// the programs it starts.
+pub fn set_epoch(epoch: u64) {
+    let _ = EPOCH.set(epoch);
+}
+
+/// The epoch `--reproducible` chose, or else `SOURCE_DATE_EPOCH` from the
+/// environment.
+pub fn epoch() -> Option<u64> {
+    EPOCH.get().copied().or_else(|| std::env::var("SOURCE_DATE_EPOCH").ok()?.trim().parse().ok())
+}
+
+/// Passes the chosen epoch to `command` as `SOURCE_DATE_EPOCH`. One that
+/// was already in the environment is inherited anyway.
+pub fn apply(command: &mut Command) -> &mut Command {
+    if let Some(epoch) = EPOCH.get() {
+        command.env("SOURCE_DATE_EPOCH", epoch.to_string());
+    }
+    command
+}
+
+/// The timestamp a reproducible build embeds: `SOURCE_DATE_EPOCH` when it
+/// is already set, then the last commit time, then the newest file in the
+/// project.
+pub fn source_date_epoch(project_path: &Path) -> u64 {
+    if let Some(epoch) = std::env::var("SOURCE_DATE_EPOCH").ok().and_then(|value| value.trim().parse().ok()) {
+        return epoch;
+    }
+
+    let committed = Command::new("git")
+        .args(["log", "-1", "--format=%ct"])
+        .current_dir(project_path)
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8_lossy(&output.stdout).trim().parse().ok());
+    if let Some(epoch) = committed {
+        return epoch;
+    }
+
+    WalkDir::new(project_path)
+        .into_iter()
+        .filter_entry(|e| e.depth() == 0 || !e.file_name().to_string_lossy().starts_with('.'))
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .filter_map(|e| e.metadata().ok()?.modified().ok())
+        .filter_map(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map(|age| age.as_secs())
+        .max()
+        .unwrap_or(0)
+}
+
+/// Sets every file and directory under `dir` to `epoch`, so archive tools
+/// record the same times on every build.
+pub fn normalize_mtimes(dir: &Path, epoch: u64) -> Result<()> {
+    let time = FileTime::from_unix_time(epoch as i64, 0);
+    // Children first, so setting a directory's time is not undone by
+    // touching its entries afterwards
+    for entry in WalkDir::new(dir).contents_first(true) {
+        let entry = entry?;
+        if entry.path_is_symlink() {
+            continue;
+        }
+        // Unlike `File::set_modified`, this works on directories on Windows too
+        filetime::set_file_mtime(entry.path(), time)
+            .with_context(|| format!("Failed to set the time of {}", entry.path().display()))?;
+    }
+    Ok(())
+}
+
+/// Packs `dir` into a `.tar.gz` at `archive_path` with a fixed entry order,
+/// times and owners, piped through `gzip -n` so the gzip header carries no
+/// name or time either.
+pub fn tar_gz(dir: &Path, archive_path: &Path, epoch: u64) -> Result<()> {
+    let archive = fs::File::create(archive_path)
+        .with_context(|| format!("Failed to create {}", archive_path.display()))?;
+    let mut gzip = Command::new("gzip")
+        .arg("-n")
+        .stdin(Stdio::piped())
+        .stdout(archive)
+        .spawn()
+        .context("Failed to run gzip")?;
+
+    let tar_status = Command::new("tar")
+        .args(["--sort=name", "--owner=0", "--group=0", "--numeric-owner"])
+        .arg(format!("--mtime=@{}", epoch))
+        .arg("-cf")
+        .arg("-")
+        .arg("-C")
+        .arg(dir.parent().unwrap_or(Path::new(".")))
+        .arg(dir.file_name().unwrap_or_default())
+        .stdout(gzip.stdin.take().expect("gzip stdin is piped"))
+        .status()
+        .context("Failed to create tar archive")?;
+    let gzip_status = gzip.wait().context("Failed to run gzip")?;
+
+    if !tar_status.success() || !gzip_status.success() {
+        anyhow::bail!("tar failed to create {}", archive_path.display());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_normalize_mtimes() {
+        let temp_dir = TempDir::new().unwrap();
+        let nested = temp_dir.path().join("usr").join("bin");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(nested.join("app"), "#!/bin/sh\n").unwrap();
+
+        normalize_mtimes(temp_dir.path(), 1_700_000_000).unwrap();
+
+        let expected = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        for path in [temp_dir.path().to_path_buf(), nested.clone(), nested.join("app")] {
+            assert_eq!(fs::metadata(&path).unwrap().modified().unwrap(), expected);
+        }
+    }
+}
@@ -0,0 +1,398 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use serde_json::{json, Value};
+use crate::config::Config;
+use crate::reproducible;
+
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SbomFormat {
+    /// CycloneDX 1.5 JSON
+    #[default]
+    Cyclonedx,
+    /// SPDX 2.3 JSON
+    Spdx,
+}
+
+impl SbomFormat {
+    pub fn file_name(&self) -> &'static str {
+        match self {
+            SbomFormat::Cyclonedx => "sbom.cdx.json",
+            SbomFormat::Spdx => "sbom.spdx.json",
+        }
+    }
+}
+
+/// A source file covered by the SBOM.
+#[derive(Debug, Clone)]
+pub struct SourceFile {
+    /// Relative to the project root
+    pub path: PathBuf,
+    pub language: String,
+    pub sha256: String,
+}
+
+/// A toolchain used to build the project.
+#[derive(Debug, Clone)]
+pub struct Toolchain {
+    pub language: String,
+    pub tool: String,
+    pub version: String,
+}
+
+/// A dependency declared by the project.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dependency {
+    /// The package URL type: `cargo`, `npm` or `generic` for pkg-config
+    pub ecosystem: &'static str,
+    pub name: String,
+    pub version: Option<String>,
+}
+
+impl Dependency {
+    pub fn purl(&self) -> String {
+        match &self.version {
+            Some(version) => format!("pkg:{}/{}@{}", self.ecosystem, self.name, version),
+            None => format!("pkg:{}/{}", self.ecosystem, self.name),
+        }
+    }
+}
+
+/// Everything an SBOM document describes.
+#[derive(Debug, Default)]
+pub struct Inventory {
+    pub name: String,
+    pub files: Vec<SourceFile>,
+    pub toolchains: Vec<Toolchain>,
+    pub dependencies: Vec<Dependency>,
+}
+
+/// Dependencies from `Cargo.lock`, `package-lock.json` and the `pkg_config`
+/// packages listed in the config, whose versions come from `pkg-config`.
+pub fn declared_dependencies(project_path: &Path, config: &Config) -> Result<Vec<Dependency>> {
+    let mut dependencies = Vec::new();
+
+    let cargo_lock = project_path.join("Cargo.lock");
+    if cargo_lock.is_file() {
+        let content = fs::read_to_string(&cargo_lock).with_context(|| format!("Failed to read {}", cargo_lock.display()))?;
+        dependencies.extend(parse_cargo_lock(&content));
+    }
+
+    let package_lock = project_path.join("package-lock.json");
+    if package_lock.is_file() {
+        let content = fs::read_to_string(&package_lock).with_context(|| format!("Failed to read {}", package_lock.display()))?;
+        dependencies.extend(parse_package_lock(&content).with_context(|| format!("Failed to parse {}", package_lock.display()))?);
+    }
+
+    for package in &config.pkg_config {
+        let version = Command::new("pkg-config")
+            .args(["--modversion", package])
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string());
+        dependencies.push(Dependency { ecosystem: "generic", name: package.clone(), version });
+    }
+
+    Ok(dependencies)
+}
+
+/// The `[[package]]` entries of a `Cargo.lock`.
+pub fn parse_cargo_lock(content: &str) -> Vec<Dependency> {
+    let mut dependencies = Vec::new();
+    let mut current: Option<(Option<String>, Option<String>)> = None;
+
+    let mut finish = |entry: Option<(Option<String>, Option<String>)>| {
+        if let Some((Some(name), version)) = entry {
+            dependencies.push(Dependency { ecosystem: "cargo", name, version });
+        }
+    };
+
+    for line in content.lines().map(str::trim) {
+        if line.starts_with('[') {
+            finish(current.take());
+            if line == "[[package]]" {
+                current = Some((None, None));
+            }
+            continue;
+        }
+        let Some(entry) = current.as_mut() else {
+            continue;
+        };
+        if let Some((key, value)) = line.split_once('=') {
+            let value = value.trim().trim_matches('"').to_string();
+            match key.trim() {
+                "name" => entry.0 = Some(value),
+                "version" => entry.1 = Some(value),
+                _ => {}
+            }
+        }
+    }
+    finish(current);
+    dependencies
+}
+
+/// The installed packages of a `package-lock.json`, from `packages`
+/// (lockfile v2/v3) or `dependencies` (v1).
+pub fn parse_package_lock(content: &str) -> Result<Vec<Dependency>> {
+    let lock: Value = serde_json::from_str(content)?;
+    let mut dependencies = Vec::new();
+
+    if let Some(packages) = lock.get("packages").and_then(Value::as_object) {
+        for (path, package) in packages {
+            let Some(name) = path.rsplit("node_modules/").next().filter(|_| path.contains("node_modules/")) else {
+                continue;
+            };
+            let version = package.get("version").and_then(Value::as_str).map(String::from);
+            dependencies.push(Dependency { ecosystem: "npm", name: name.to_string(), version });
+        }
+    } else if let Some(packages) = lock.get("dependencies").and_then(Value::as_object) {
+        for (name, package) in packages {
+            let version = package.get("version").and_then(Value::as_str).map(String::from);
+            dependencies.push(Dependency { ecosystem: "npm", name: name.clone(), version });
+        }
+    }
+
+    Ok(dependencies)
+}
+
+/// Renders `inventory` as a CycloneDX or SPDX JSON document.
+pub fn render(format: SbomFormat, inventory: &Inventory, timestamp: &str) -> Value {
+    match format {
+        SbomFormat::Cyclonedx => cyclonedx(inventory, timestamp),
+        SbomFormat::Spdx => spdx(inventory, timestamp),
+    }
+}
+
+fn cyclonedx(inventory: &Inventory, timestamp: &str) -> Value {
+    let mut components: Vec<Value> = inventory
+        .files
+        .iter()
+        .map(|file| {
+            json!({
+                "type": "file",
+                "name": file.path.to_string_lossy(),
+                "hashes": [{ "alg": "SHA-256", "content": file.sha256 }],
+                "properties": [{ "name": "lol:language", "value": file.language }],
+            })
+        })
+        .collect();
+    components.extend(inventory.toolchains.iter().map(|toolchain| {
+        json!({
+            "type": "application",
+            "name": toolchain.tool,
+            "version": toolchain.version,
+            "scope": "excluded",
+            "properties": [{ "name": "lol:language", "value": toolchain.language }],
+        })
+    }));
+    components.extend(inventory.dependencies.iter().map(|dependency| {
+        let mut component = json!({
+            "type": "library",
+            "name": dependency.name,
+            "purl": dependency.purl(),
+        });
+        if let Some(version) = &dependency.version {
+            component["version"] = json!(version);
+        }
+        component
+    }));
+
+    json!({
+        "bomFormat": "CycloneDX",
+        "specVersion": "1.5",
+        "version": 1,
+        "metadata": {
+            "timestamp": timestamp,
+            "tools": [{ "name": "lol", "version": env!("CARGO_PKG_VERSION") }],
+            "component": { "type": "application", "name": inventory.name },
+        },
+        "components": components,
+    })
+}
+
+fn spdx(inventory: &Inventory, timestamp: &str) -> Value {
+    let spdx_id = |prefix: &str, index: usize| format!("SPDXRef-{}-{}", prefix, index);
+
+    let mut packages = vec![json!({
+        "SPDXID": "SPDXRef-Project",
+        "name": inventory.name,
+        "downloadLocation": "NOASSERTION",
+        "filesAnalyzed": false,
+    })];
+    let mut relationships = vec![json!({
+        "spdxElementId": "SPDXRef-DOCUMENT",
+        "relationshipType": "DESCRIBES",
+        "relatedSpdxElement": "SPDXRef-Project",
+    })];
+
+    let files: Vec<Value> = inventory
+        .files
+        .iter()
+        .enumerate()
+        .map(|(index, file)| {
+            relationships.push(json!({
+                "spdxElementId": "SPDXRef-Project",
+                "relationshipType": "CONTAINS",
+                "relatedSpdxElement": spdx_id("File", index),
+            }));
+            json!({
+                "SPDXID": spdx_id("File", index),
+                "fileName": format!("./{}", file.path.to_string_lossy()),
+                "checksums": [{ "algorithm": "SHA256", "checksumValue": file.sha256 }],
+                "comment": format!("Language: {}", file.language),
+            })
+        })
+        .collect();
+
+    for (index, toolchain) in inventory.toolchains.iter().enumerate() {
+        packages.push(json!({
+            "SPDXID": spdx_id("Toolchain", index),
+            "name": toolchain.tool,
+            "versionInfo": toolchain.version,
+            "downloadLocation": "NOASSERTION",
+            "filesAnalyzed": false,
+        }));
+        relationships.push(json!({
+            "spdxElementId": spdx_id("Toolchain", index),
+            "relationshipType": "BUILD_TOOL_OF",
+            "relatedSpdxElement": "SPDXRef-Project",
+        }));
+    }
+
+    for (index, dependency) in inventory.dependencies.iter().enumerate() {
+        let mut package = json!({
+            "SPDXID": spdx_id("Dependency", index),
+            "name": dependency.name,
+            "downloadLocation": "NOASSERTION",
+            "filesAnalyzed": false,
+            "externalRefs": [{
+                "referenceCategory": "PACKAGE-MANAGER",
+                "referenceType": "purl",
+                "referenceLocator": dependency.purl(),
+            }],
+        });
+        if let Some(version) = &dependency.version {
+            package["versionInfo"] = json!(version);
+        }
+        packages.push(package);
+        relationships.push(json!({
+            "spdxElementId": "SPDXRef-Project",
+            "relationshipType": "DEPENDS_ON",
+            "relatedSpdxElement": spdx_id("Dependency", index),
+        }));
+    }
+
+    json!({
+        "spdxVersion": "SPDX-2.3",
+        "dataLicense": "CC0-1.0",
+        "SPDXID": "SPDXRef-DOCUMENT",
+        "name": inventory.name,
+        "documentNamespace": format!("https://spdx.org/spdxdocs/{}-{}", inventory.name, content_hash(&inventory.files)),
+        "creationInfo": {
+            "created": timestamp,
+            "creators": [format!("Tool: lol-{}", env!("CARGO_PKG_VERSION"))],
+        },
+        "packages": packages,
+        "files": files,
+        "relationships": relationships,
+    })
+}
+
+/// A digest of the sorted file checksums, so the SPDX document namespace is
+/// unique per source tree yet stable across runs.
+fn content_hash(files: &[SourceFile]) -> String {
+    use sha2::{Digest, Sha256};
+    let mut checksums: Vec<&str> = files.iter().map(|file| file.sha256.as_str()).collect();
+    checksums.sort_unstable();
+    Sha256::digest(checksums.concat().as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// The current time as RFC 3339 UTC, or `SOURCE_DATE_EPOCH` when set.
+pub fn timestamp() -> String {
+    let seconds = reproducible::epoch()
+        .unwrap_or_else(|| SystemTime::now().duration_since(UNIX_EPOCH).map(|elapsed| elapsed.as_secs()).unwrap_or(0));
+    format_rfc3339(seconds)
+}
+
+pub fn format_rfc3339(seconds: u64) -> String {
+    let days = (seconds / 86_400) as i64;
+    let time = seconds % 86_400;
+
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_lockfiles() {
+        let cargo_lock = "version = 3\n\n[[package]]\nname = \"anyhow\"\nversion = \"1.0.86\"\nsource = \"registry+https://github.com/rust-lang/crates.io-index\"\n\n[[package]]\nname = \"app\"\nversion = \"0.1.0\"\ndependencies = [\n \"anyhow\",\n]\n\n[metadata]\nfoo = \"bar\"\n";
+        let purls: Vec<String> = parse_cargo_lock(cargo_lock).iter().map(Dependency::purl).collect();
+        assert_eq!(purls, ["pkg:cargo/anyhow@1.0.86", "pkg:cargo/app@0.1.0"]);
+
+        let package_lock = r#"{"lockfileVersion": 3, "packages": {
+            "": {"name": "app"},
+            "node_modules/left-pad": {"version": "1.3.0"},
+            "node_modules/@babel/core": {"version": "7.24.0"},
+            "node_modules/a/node_modules/b": {"version": "2.0.0"}
+        }}"#;
+        let purls: Vec<String> = parse_package_lock(package_lock).unwrap().iter().map(Dependency::purl).collect();
+        assert_eq!(purls, ["pkg:npm/@babel/core@7.24.0", "pkg:npm/b@2.0.0", "pkg:npm/left-pad@1.3.0"]);
+    }
+
+    #[test]
+    fn test_render_documents() {
+        let inventory = Inventory {
+            name: "demo".to_string(),
+            files: vec![SourceFile { path: PathBuf::from("src/main.c"), language: "C".to_string(), sha256: "ab".repeat(32) }],
+            toolchains: vec![Toolchain { language: "C".to_string(), tool: "gcc".to_string(), version: "gcc 13.2.0".to_string() }],
+            dependencies: vec![Dependency { ecosystem: "generic", name: "zlib".to_string(), version: Some("1.3".to_string()) }],
+        };
+
+        let bom = render(SbomFormat::Cyclonedx, &inventory, "2024-01-01T00:00:00Z");
+        assert_eq!(bom["bomFormat"], "CycloneDX");
+        assert_eq!(bom["components"].as_array().unwrap().len(), 3);
+        assert_eq!(bom["components"][0]["hashes"][0]["content"], "ab".repeat(32));
+        assert_eq!(bom["components"][2]["purl"], "pkg:generic/zlib@1.3");
+
+        let spdx = render(SbomFormat::Spdx, &inventory, "2024-01-01T00:00:00Z");
+        assert_eq!(spdx["spdxVersion"], "SPDX-2.3");
+        assert_eq!(spdx["files"][0]["fileName"], "./src/main.c");
+        assert_eq!(spdx["packages"].as_array().unwrap().len(), 3);
+        assert_eq!(spdx["relationships"].as_array().unwrap().len(), 4);
+    }
+
+    #[test]
+    fn test_format_rfc3339() {
+        assert_eq!(format_rfc3339(0), "1970-01-01T00:00:00Z");
+        assert_eq!(format_rfc3339(951_782_400), "2000-02-29T00:00:00Z");
+        assert_eq!(format_rfc3339(1_700_000_000), "2023-11-14T22:13:20Z");
+    }
+}
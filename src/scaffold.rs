@@ -0,0 +1,160 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use anyhow::{Context, Result};
+use serde_json::json;
+use crate::config::PROJECT_CONFIG_FILE;
+use crate::language_support::Language;
+
+/// The hello-world source file a new project for `language` starts with.
+fn hello_world(language: &Language) -> Option<(&'static str, &'static str)> {
+    let source = match language {
+        Language::C => ("main.c", "#include <stdio.h>\n\nint main(void) {\n    printf(\"Hello from C!\\n\");\n    return 0;\n}\n"),
+        Language::Cpp => (
+            "main.cpp",
+            "#include <iostream>\n\nint main() {\n    std::cout << \"Hello from C++!\" << std::endl;\n    return 0;\n}\n",
+        ),
+        Language::ObjectiveC => ("main.m", "#include <stdio.h>\n\nint main(void) {\n    printf(\"Hello from Objective-C!\\n\");\n    return 0;\n}\n"),
+        Language::ObjectiveCpp => (
+            "main.mm",
+            "#include <iostream>\n\nint main() {\n    std::cout << \"Hello from Objective-C++!\" << std::endl;\n    return 0;\n}\n",
+        ),
+        Language::Cuda => (
+            "main.cu",
+            "#include <cstdio>\n\n__global__ void hello() {\n    printf(\"Hello from CUDA thread %d!\\n\", threadIdx.x);\n}\n\nint main() {\n    hello<<<1, 4>>>();\n    cudaDeviceSynchronize();\n    return 0;\n}\n",
+        ),
+        Language::WebAssembly => (
+            "main.wat",
+            "(module\n  (func $answer (export \"answer\") (result i32)\n    i32.const 42))\n",
+        ),
+        Language::Pascal => ("hello.pas", "program Hello;\nbegin\n  writeln('Hello from Pascal!');\nend.\n"),
+        Language::Verilog => ("hello.sv", "module hello;\n  initial $display(\"Hello from Verilog!\");\nendmodule\n"),
+        Language::Vhdl => (
+            "hello.vhd",
+            "entity hello is\nend hello;\n\narchitecture sim of hello is\nbegin\n  process\n  begin\n    report \"Hello from VHDL!\";\n    wait;\n  end process;\nend sim;\n",
+        ),
+        Language::Fortran => ("hello.f90", "program hello\n  print *, 'Hello from Fortran!'\nend program hello\n"),
+        Language::Ada => ("hello.adb", "with Ada.Text_IO;\n\nprocedure Hello is\nbegin\n   Ada.Text_IO.Put_Line (\"Hello from Ada!\");\nend Hello;\n"),
+        Language::Julia => ("main.jl", "println(\"Hello from Julia!\")\n"),
+        Language::Lua => ("main.lua", "print(\"Hello from Lua!\")\n"),
+        Language::Ruby => ("main.rb", "puts \"Hello from Ruby!\"\n"),
+        Language::Perl => ("main.pl", "use strict;\nuse warnings;\n\nprint \"Hello from Perl!\\n\";\n"),
+        Language::R => ("main.R", "cat(\"Hello from R!\\n\")\n"),
+        Language::Elixir => ("main.exs", "IO.puts(\"Hello from Elixir!\")\n"),
+        Language::Erlang => ("hello.erl", "-module(hello).\n-export([main/0]).\n\nmain() ->\n    io:format(\"Hello from Erlang!~n\").\n"),
+        Language::Crystal => ("main.cr", "puts \"Hello from Crystal!\"\n"),
+        Language::Python => (
+            "main.py",
+            "#!/usr/bin/env python3\n\n\ndef main():\n    print(\"Hello from Python!\")\n\n\nif __name__ == \"__main__\":\n    main()\n",
+        ),
+        Language::Cython => ("hello.pyx", "def main():\n    print(\"Hello from Cython!\")\n"),
+        Language::Java => (
+            "Main.java",
+            "public class Main {\n    public static void main(String[] args) {\n        System.out.println(\"Hello from Java!\");\n    }\n}\n",
+        ),
+        Language::Rust => ("main.rs", "fn main() {\n    println!(\"Hello from Rust!\");\n}\n"),
+        Language::Go => ("main.go", "package main\n\nimport \"fmt\"\n\nfunc main() {\n\tfmt.Println(\"Hello from Go!\")\n}\n"),
+        Language::JavaScript => ("main.js", "console.log(\"Hello from JavaScript!\");\n"),
+        Language::TypeScript => ("main.ts", "const greeting: string = \"Hello from TypeScript!\";\nconsole.log(greeting);\n"),
+        Language::CSharp => (
+            "Program.cs",
+            "using System;\n\nclass Program\n{\n    static void Main()\n    {\n        Console.WriteLine(\"Hello from C#!\");\n    }\n}\n",
+        ),
+        Language::Swift => ("main.swift", "print(\"Hello from Swift!\")\n"),
+        Language::Kotlin => ("main.kt", "fun main() {\n    println(\"Hello from Kotlin!\")\n}\n"),
+        Language::Scala => (
+            "Main.scala",
+            "object Main {\n  def main(args: Array[String]): Unit =\n    println(\"Hello from Scala!\")\n}\n",
+        ),
+        Language::Haskell => ("Main.hs", "main :: IO ()\nmain = putStrLn \"Hello from Haskell!\"\n"),
+        Language::FSharp => ("Program.fs", "printfn \"Hello from F#!\"\n"),
+        Language::OCaml => ("main.ml", "let () = print_endline \"Hello from OCaml!\"\n"),
+        Language::Nim => ("main.nim", "echo \"Hello from Nim!\"\n"),
+        Language::Zig => (
+            "main.zig",
+            "const std = @import(\"std\");\n\npub fn main() void {\n    std.debug.print(\"Hello from Zig!\\n\", .{});\n}\n",
+        ),
+        Language::V => ("main.v", "fn main() {\n\tprintln('Hello from V!')\n}\n"),
+        Language::Odin => ("main.odin", "package main\n\nimport \"core:fmt\"\n\nmain :: proc() {\n\tfmt.println(\"Hello from Odin!\")\n}\n"),
+        Language::Jai => ("main.jai", "#import \"Basic\";\n\nmain :: () {\n    print(\"Hello from Jai!\\n\");\n}\n"),
+        Language::Custom(_) => return None,
+    };
+    Some(source)
+}
+
+/// Build output and tool droppings a new project for `language` should
+/// keep out of version control.
+fn ignored(language: &Language) -> &'static [&'static str] {
+    match language {
+        Language::C | Language::Cpp | Language::ObjectiveC | Language::ObjectiveCpp | Language::Cuda | Language::Fortran => &["*.o"],
+        Language::Python | Language::Cython => &["__pycache__/", ".venv/"],
+        Language::JavaScript | Language::TypeScript => &["node_modules/"],
+        Language::Rust => &["target/"],
+        Language::Java | Language::Kotlin | Language::Scala => &["*.class"],
+        Language::CSharp | Language::FSharp => &["bin/", "obj/"],
+        Language::Haskell => &["*.hi", "*.o"],
+        Language::OCaml => &["_build/", "*.cm[iox]"],
+        Language::Erlang | Language::Elixir => &["_build/", "*.beam"],
+        Language::Nim => &["nimcache/"],
+        Language::Zig => &[".zig-cache/", "zig-cache/", "zig-out/"],
+        Language::Ada => &["*.ali", "*.o"],
+        _ => &[],
+    }
+}
+
+/// The built-in language `key` names, by `language_settings` key or by name.
+pub fn language(key: &str) -> Result<&'static Language> {
+    Language::all()
+        .iter()
+        .find(|language| language.config_key().eq_ignore_ascii_case(key) || language.name().eq_ignore_ascii_case(key))
+        .with_context(|| format!("Unknown language '{}'; `lol new` supports the built-in languages", key))
+}
+
+/// `lol new`: creates `<parent>/<name>` with a hello-world program, a
+/// `lol.json` and a `.gitignore`, and returns the new directory.
+pub fn create(parent: &Path, language: &Language, name: &str) -> Result<PathBuf> {
+    let (file_name, source) = hello_world(language)
+        .with_context(|| format!("There is no starter project for {}", language.name()))?;
+    if name.is_empty() || name.contains(['/', '\\']) || name == "." || name == ".." {
+        anyhow::bail!("'{}' is not a valid project name", name);
+    }
+    let project_dir = parent.join(name);
+    if project_dir.exists() {
+        anyhow::bail!("{} already exists", project_dir.display());
+    }
+
+    fs::create_dir_all(&project_dir).with_context(|| format!("Failed to create {}", project_dir.display()))?;
+    fs::write(project_dir.join(file_name), source)?;
+    let config = json!({ "output_directory": "build", "profile": "debug" });
+    fs::write(project_dir.join(PROJECT_CONFIG_FILE), serde_json::to_string_pretty(&config)? + "\n")?;
+    let gitignore: String = ["build/"].iter().chain(ignored(language)).map(|pattern| format!("{}\n", pattern)).collect();
+    fs::write(project_dir.join(".gitignore"), gitignore)?;
+    Ok(project_dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::args::Args;
+    use crate::config::Config;
+    use crate::file_detector::FileDetector;
+    use clap::Parser;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_every_language_has_a_detectable_starter() {
+        let temp_dir = TempDir::new().unwrap();
+        let args = Args::parse_from(["lol"]);
+        for language in Language::all() {
+            let project_dir = create(temp_dir.path(), language, language.config_key()).unwrap();
+            let sources = FileDetector::new().detect_files(&project_dir, &args, &Config::default()).unwrap();
+            assert_eq!(sources.keys().collect::<Vec<_>>(), [language], "{}", language.name());
+        }
+
+        let rust = temp_dir.path().join("rust");
+        assert_eq!(fs::read_to_string(rust.join(".gitignore")).unwrap(), "build/\ntarget/\n");
+        assert!(create(temp_dir.path(), &Language::Rust, "rust").is_err());
+        assert!(create(temp_dir.path(), &Language::Custom("bf".to_string()), "bf").is_err());
+        assert_eq!(language("C#").unwrap(), &Language::CSharp);
+        assert!(language("brainfuck").is_err());
+    }
+}
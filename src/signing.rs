@@ -0,0 +1,323 @@
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use anyhow::{Context, Result};
+use colored::*;
+use sha2::{Digest, Sha256};
+use tempfile::TempDir;
+use crate::appimage::find_section;
+use crate::artifacts::{self, ArtifactManifest, MANIFEST_FILE};
+
+/// Runtime sections holding the embedded signature and the public key
+/// that verifies it, as written by appimagetool.
+const SIGNATURE_SECTION: &str = ".sha256_sig";
+const KEY_SECTION: &str = ".sig_key";
+
+/// SHA-256 of the AppImage with its signature sections zeroed, in hex.
+/// This is the text that gets signed, so signing does not change it.
+pub fn embedded_digest(image: &[u8]) -> String {
+    let mut unsigned = image.to_vec();
+    for section in [SIGNATURE_SECTION, KEY_SECTION] {
+        if let Some((offset, size)) = find_section(image, section) {
+            unsigned[offset..offset + size].fill(0);
+        }
+    }
+    Sha256::digest(&unsigned).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Runs gpg with `input` on stdin and returns its stdout.
+fn gpg(args: &[&str], input: Option<&[u8]>, home: Option<&Path>) -> Result<Vec<u8>> {
+    let mut command = Command::new("gpg");
+    command
+        .args(["--batch", "--yes"])
+        .args(args)
+        .stdin(if input.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    if let Some(home) = home {
+        command.env("GNUPGHOME", home);
+    }
+    let mut child = command.spawn().context("Signing needs gpg on the PATH")?;
+    if let Some(input) = input {
+        child.stdin.take().expect("gpg stdin is piped").write_all(input)?;
+    }
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        anyhow::bail!("gpg {} failed: {}", args.join(" "), String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(output.stdout)
+}
+
+/// `key`, or the fingerprint of the first secret key when none is given.
+fn resolve_key(key: Option<&str>) -> Result<String> {
+    if let Some(key) = key {
+        return Ok(key.to_string());
+    }
+    let listing = gpg(&["--list-secret-keys", "--with-colons"], None, None)?;
+    String::from_utf8_lossy(&listing)
+        .lines()
+        .find(|line| line.starts_with("fpr:"))
+        .and_then(|line| line.split(':').nth(9))
+        .map(str::to_string)
+        .context("No GPG secret key found; create one or pass --sign <KEYID>")
+}
+
+/// Signs an AppImage in place the way appimagetool does: the digest is
+/// signed with an armored detached signature, which goes into the
+/// runtime's `.sha256_sig` section with the public key in `.sig_key`.
+pub fn embed_signature(image: &mut [u8], key: Option<&str>) -> Result<()> {
+    let key = resolve_key(key)?;
+    let digest = embedded_digest(image);
+    let signature = gpg(&["--local-user", &key, "--detach-sign", "--armor"], Some(digest.as_bytes()), None)?;
+    let public_key = gpg(&["--export", "--armor", &key], None, None)?;
+    crate::appimage::embed_in_section(image, SIGNATURE_SECTION, &signature)?;
+    crate::appimage::embed_in_section(image, KEY_SECTION, &public_key)?;
+    Ok(())
+}
+
+/// Writes `<path>.sig`, a binary detached signature of the whole file.
+pub fn sign_detached(path: &Path, key: Option<&str>) -> Result<PathBuf> {
+    let key = resolve_key(key)?;
+    let signature_path = with_suffix(path, ".sig");
+    let signature_arg = signature_path.to_string_lossy().into_owned();
+    let path_arg = path.to_string_lossy().into_owned();
+    gpg(&["--local-user", &key, "--detach-sign", "--output", &signature_arg, &path_arg], None, None)?;
+    Ok(signature_path)
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// The NUL-padded contents of an ELF section, if it holds anything.
+fn section_text(image: &[u8], name: &str) -> Option<Vec<u8>> {
+    let (offset, size) = find_section(image, name)?;
+    let content = &image[offset..offset + size];
+    let length = content.iter().position(|byte| *byte == 0).unwrap_or(size);
+    (length > 0).then(|| content[..length].to_vec())
+}
+
+/// A keyring only lol uses, removed when dropped. gpg insists on a home
+/// only its owner can read.
+fn throwaway_home() -> Result<TempDir> {
+    let home = tempfile::Builder::new().prefix("lol-verify-").tempdir().context("Failed to create a GPG home")?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(home.path(), fs::Permissions::from_mode(0o700))?;
+    }
+    Ok(home)
+}
+
+/// Runs `gpg --verify` on a detached `signature` of `data` and returns the
+/// signer's user ID with the fingerprints of the signing key and its
+/// primary key, from gpg's status lines.
+fn verify_signature(data: &[u8], signature: &[u8], home: Option<&Path>) -> Result<(String, Vec<String>)> {
+    let scratch = throwaway_home()?;
+    let signature_path = scratch.path().join("data.sig");
+    fs::write(&signature_path, signature)?;
+    let signature_arg = signature_path.to_string_lossy().into_owned();
+    let status = gpg(&["--status-fd", "1", "--verify", &signature_arg, "-"], Some(data), home)?;
+
+    let status = String::from_utf8_lossy(&status);
+    let mut signer = None;
+    let mut fingerprints = Vec::new();
+    for line in status.lines() {
+        let fields: Vec<&str> = line.split(' ').collect();
+        match fields.get(1..) {
+            Some(["GOODSIG", _, uid @ ..]) => signer = Some(uid.join(" ")),
+            Some(["VALIDSIG", fingerprint, rest @ ..]) => {
+                fingerprints.push(fingerprint.to_string());
+                fingerprints.extend(rest.get(8).map(|primary| primary.to_string()));
+            }
+            _ => {}
+        }
+    }
+    if fingerprints.is_empty() {
+        anyhow::bail!("gpg did not report a valid signature");
+    }
+    Ok((signer.unwrap_or_else(|| "unknown key".to_string()), fingerprints))
+}
+
+/// `fingerprint` without spaces or a `0x` prefix, in upper case.
+fn normalize_fingerprint(fingerprint: &str) -> String {
+    let fingerprint: String = fingerprint.chars().filter(|c| !c.is_whitespace()).collect();
+    fingerprint.trim_start_matches("0x").to_ascii_uppercase()
+}
+
+/// Checks a detached `signature` of `data` and returns who made it. With
+/// `trusted` fingerprints, the signer must be one of them, and a key
+/// shipped next to the signature (`embedded_key`) may supply it. Without,
+/// only keys already in the user's keyring are accepted.
+fn verify_trusted(data: &[u8], signature: &[u8], embedded_key: Option<&[u8]>, trusted: &[String]) -> Result<String> {
+    if trusted.is_empty() {
+        let (signer, fingerprints) = verify_signature(data, signature, None)?;
+        return Ok(format!("{} ({})", signer, fingerprints[0]));
+    }
+
+    let home = match embedded_key {
+        Some(key) => {
+            let home = throwaway_home()?;
+            gpg(&["--import"], Some(key), Some(home.path()))?;
+            Some(home)
+        }
+        None => None,
+    };
+    let (signer, fingerprints) = verify_signature(data, signature, home.as_ref().map(TempDir::path))?;
+    let trusted: Vec<String> = trusted.iter().map(|fingerprint| normalize_fingerprint(fingerprint)).collect();
+    if !fingerprints.iter().any(|fingerprint| trusted.contains(&normalize_fingerprint(fingerprint))) {
+        anyhow::bail!("signed by {} ({}), which is not a trusted key", signer, fingerprints[0]);
+    }
+    Ok(format!("{} ({})", signer, fingerprints[0]))
+}
+
+/// Checks a detached `signature` of `data` against the armored
+/// `public_key`, in a throwaway keyring, and returns the signer's user ID.
+pub fn verify_with_key(data: &[u8], signature: &[u8], public_key: &[u8]) -> Result<String> {
+    let home = throwaway_home()?;
+    gpg(&["--import"], Some(public_key), Some(home.path()))?;
+    verify_signature(data, signature, Some(home.path())).map(|(signer, _)| signer)
+}
+
+/// Finds `path` in an `artifacts.json` in its directory or any parent.
+/// Entries are relative to the manifest's directory or one of its parents
+/// (build manifests list paths from the project root).
+fn find_in_manifests(path: &Path) -> Result<Option<(PathBuf, artifacts::Artifact)>> {
+    let path = path.canonicalize()?;
+    for directory in path.ancestors().skip(1) {
+        let Some(manifest) = ArtifactManifest::load(directory)? else {
+            continue;
+        };
+        for artifact in manifest.artifacts {
+            let matches = directory
+                .ancestors()
+                .any(|root| root.join(&artifact.path).canonicalize().is_ok_and(|candidate| candidate == path));
+            if matches {
+                return Ok(Some((directory.join(MANIFEST_FILE), artifact)));
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Checks every signature lol can find for `path`: the embedded AppImage
+/// signature, a detached `<path>.sig`, and the entry in the nearest
+/// artifact manifest when the manifest is signed itself. Signers must be
+/// `trusted`, or with no fingerprints given, in the user's keyring. Fails
+/// if any check fails or no trusted signature covers the file.
+pub fn verify(path: &Path, trusted: &[String]) -> Result<()> {
+    let image = fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let mut checks = 0;
+    let mut failures = 0;
+    let mut report = |what: &str, result: Result<String>| {
+        checks += 1;
+        match result {
+            Ok(detail) => say!("{} {}: {}", "✅".green(), what, detail),
+            Err(error) => {
+                failures += 1;
+                say_error!("{} {}: {:#}", "❌".red(), what, error);
+            }
+        }
+    };
+
+    if let Some(signature) = section_text(&image, SIGNATURE_SECTION) {
+        // The key in the image only helps find the signer; it is trusted
+        // only when its fingerprint is
+        let embedded_key = section_text(&image, KEY_SECTION);
+        let digest = embedded_digest(&image);
+        report(
+            "Embedded signature",
+            verify_trusted(digest.as_bytes(), &signature, embedded_key.as_deref(), trusted).map(|signer| format!("good signature from {}", signer)),
+        );
+    }
+
+    let detached = with_suffix(path, ".sig");
+    if detached.is_file() {
+        let signature = fs::read(&detached).with_context(|| format!("Failed to read {}", detached.display()))?;
+        report(
+            "Detached signature",
+            verify_trusted(&image, &signature, None, trusted).map(|signer| format!("good signature from {}", signer)),
+        );
+    }
+
+    // Whoever can replace the file can replace a manifest next to it, so
+    // only a signed manifest vouches for the checksum
+    if let Some((manifest, artifact)) = find_in_manifests(path)? {
+        let manifest_signature = with_suffix(&manifest, ".sig");
+        if manifest_signature.is_file() {
+            let checksum = (|| {
+                let signature = fs::read(&manifest_signature)?;
+                let signer = verify_trusted(&fs::read(&manifest)?, &signature, None, trusted)
+                    .with_context(|| format!("{} is not validly signed", manifest.display()))?;
+                let actual = artifacts::sha256(path)?;
+                if actual != artifact.sha256 {
+                    anyhow::bail!("sha256 {} does not match {} in {}", actual, artifact.sha256, manifest.display());
+                }
+                Ok(format!("sha256 matches {}, signed by {}", manifest.display(), signer))
+            })();
+            report("Checksum", checksum);
+        } else {
+            say!("ℹ️  {} is not signed, so its checksum is not trusted", manifest.display());
+        }
+    }
+
+    if checks == 0 {
+        anyhow::bail!("{} has no signature and is not listed in any signed {}", path.display(), MANIFEST_FILE);
+    }
+    if failures > 0 {
+        anyhow::bail!("{} of {} checks failed for {}", failures, checks, path.display());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_verify_needs_a_signed_manifest() {
+        let temp_dir = TempDir::new().unwrap();
+        let package_dir = temp_dir.path().join("Demo_appimage");
+        fs::create_dir_all(&package_dir).unwrap();
+        let package = package_dir.join("Demo.AppImage");
+        fs::write(&package, "image").unwrap();
+
+        assert!(verify(&package, &[]).is_err());
+
+        // Anyone who can swap the image can swap an unsigned manifest too
+        artifacts::package_manifest(&package_dir).unwrap().save(&package_dir).unwrap();
+        assert!(verify(&package, &[]).is_err());
+    }
+
+    #[test]
+    fn test_only_trusted_fingerprints_are_accepted() {
+        let gnupg_home = throwaway_home().unwrap();
+        let home = Some(gnupg_home.path());
+        let batch = ["--passphrase", "", "--pinentry-mode", "loopback"];
+        gpg(&[&batch[..], &["--quick-generate-key", "Lol Test <test@example.com>", "ed25519", "sign", "never"]].concat(), None, home).unwrap();
+        let listing = gpg(&["--list-keys", "--with-colons"], None, home).unwrap();
+        let fingerprint = String::from_utf8_lossy(&listing)
+            .lines()
+            .find(|line| line.starts_with("fpr:"))
+            .and_then(|line| line.split(':').nth(9))
+            .unwrap()
+            .to_string();
+        let public_key = gpg(&["--export", "--armor", &fingerprint], None, home).unwrap();
+        let signature = gpg(&[&batch[..], &["--local-user", &fingerprint, "--detach-sign", "--armor"]].concat(), Some(b"digest"), home).unwrap();
+
+        let trusted = [fingerprint.to_lowercase()];
+        let signer = verify_trusted(b"digest", &signature, Some(&public_key), &trusted).unwrap();
+        assert!(signer.starts_with("Lol Test <test@example.com>"), "{}", signer);
+        assert!(verify_trusted(b"tampered", &signature, Some(&public_key), &trusted).is_err());
+        let stranger = ["0".repeat(40)];
+        let error = verify_trusted(b"digest", &signature, Some(&public_key), &stranger).unwrap_err().to_string();
+        assert!(error.contains("not a trusted key"), "{}", error);
+        assert_eq!(verify_with_key(b"digest", &signature, &public_key).unwrap(), "Lol Test <test@example.com>");
+
+        let _ = Command::new("gpgconf").arg("--homedir").arg(gnupg_home.path()).args(["--kill", "gpg-agent"]).status();
+    }
+}
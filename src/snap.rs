@@ -0,0 +1,139 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use anyhow::{Context, Result};
+use crate::config::Config;
+use crate::language_support::Language;
+use crate::reproducible;
+
+/// The Ubuntu base snaps are built on.
+const BASE: &str = "core24";
+
+/// Snap names are lowercase letters, digits and single hyphens, at most
+/// 40 characters, not starting or ending with a hyphen.
+pub fn snap_name(name: &str) -> String {
+    let mut snap = String::new();
+    for c in name.to_lowercase().chars() {
+        let c = if c.is_ascii_alphanumeric() { c } else { '-' };
+        if !(c == '-' && (snap.is_empty() || snap.ends_with('-'))) {
+            snap.push(c);
+        }
+    }
+    snap.truncate(40);
+    snap.trim_end_matches('-').to_string()
+}
+
+/// Ubuntu packages providing the interpreters the launchers call.
+fn stage_package(language: &Language) -> Option<&'static str> {
+    match language {
+        Language::Python => Some("python3"),
+        Language::JavaScript => Some("nodejs"),
+        Language::Ruby => Some("ruby"),
+        Language::Perl => Some("perl"),
+        Language::Lua => Some("lua5.4"),
+        Language::R => Some("r-base-core"),
+        Language::Java => Some("openjdk-21-jre-headless"),
+        _ => None,
+    }
+}
+
+/// A snapcraft.yaml that dumps the staged tree at `source` (relative to
+/// the snapcraft project) into the snap and exposes each of `commands`
+/// from `bin/` as an app.
+pub fn snapcraft_yaml(
+    name: &str,
+    version: &str,
+    config: &Config,
+    source: &Path,
+    commands: &[String],
+    languages: &[Language],
+) -> String {
+    let description = config.package.description.clone().unwrap_or_else(|| "Built with lol".to_string());
+    let summary: String = description.lines().next().unwrap_or_default().chars().take(78).collect();
+    // Versions from `git describe` are between releases
+    let grade = if version.contains('-') { "devel" } else { "stable" };
+
+    let mut yaml = format!(
+        "name: {}\nbase: {}\nversion: '{}'\nsummary: {}\ndescription: |\n",
+        snap_name(name),
+        BASE,
+        version,
+        yaml_string(&summary)
+    );
+    for line in description.lines() {
+        yaml.push_str(&format!("  {}\n", line));
+    }
+    if let Some(license) = &config.license.spdx {
+        yaml.push_str(&format!("license: {}\n", license));
+    }
+    yaml.push_str(&format!("grade: {}\nconfinement: strict\n\napps:\n", grade));
+    for command in commands {
+        yaml.push_str(&format!(
+            "  {}:\n    command: bin/{}\n    plugs: [home, network]\n",
+            snap_name(command),
+            command
+        ));
+    }
+
+    yaml.push_str(&format!("\nparts:\n  {}:\n    plugin: dump\n    source: {}\n", snap_name(name), source.display()));
+    let mut packages: Vec<&str> = languages.iter().filter_map(stage_package).collect();
+    packages.dedup();
+    if !packages.is_empty() {
+        yaml.push_str("    stage-packages:\n");
+        for package in packages {
+            yaml.push_str(&format!("      - {}\n", package));
+        }
+    }
+    yaml
+}
+
+/// Quotes a value when YAML would otherwise read it as something else.
+fn yaml_string(text: &str) -> String {
+    if text.contains(": ") || text.contains(" #") || text.starts_with(|c: char| "!&*?|>'\"%@`{[-#,".contains(c)) {
+        format!("'{}'", text.replace('\'', "''"))
+    } else {
+        text.to_string()
+    }
+}
+
+/// Runs `snapcraft pack` in `project_dir`, the directory holding
+/// `snap/snapcraft.yaml`, writing the snap to `output`. Returns `None`
+/// when snapcraft is not installed.
+pub fn pack(project_dir: &Path, output: &Path) -> Result<Option<PathBuf>> {
+    if Command::new("snapcraft").arg("--version").output().is_err() {
+        tracing::warn!(
+            "snapcraft is not installed; run `snapcraft pack` in {} to build the snap",
+            project_dir.display()
+        );
+        return Ok(None);
+    }
+    let status = reproducible::apply(&mut Command::new("snapcraft"))
+        .arg("pack")
+        .arg("--output")
+        .arg(output)
+        .current_dir(project_dir)
+        .status()
+        .context("Failed to run snapcraft")?;
+    if !status.success() {
+        anyhow::bail!("snapcraft failed in {}", project_dir.display());
+    }
+    Ok(Some(output.to_path_buf()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapcraft_yaml() {
+        let mut config = Config::default();
+        config.package.description = Some("Demo: a tool\nIt does things.".to_string());
+        let commands = vec!["My_App".to_string(), "helper".to_string()];
+        let yaml = snapcraft_yaml("My_App", "1.2.0-3-gabc", &config, Path::new("../my-app-1.2.0"), &commands, &[Language::Python]);
+
+        assert!(yaml.starts_with("name: my-app\nbase: core24\nversion: '1.2.0-3-gabc'\nsummary: 'Demo: a tool'\n"));
+        assert!(yaml.contains("description: |\n  Demo: a tool\n  It does things.\ngrade: devel\nconfinement: strict\n"));
+        assert!(yaml.contains("  my-app:\n    command: bin/My_App\n"));
+        assert!(yaml.ends_with("plugin: dump\n    source: ../my-app-1.2.0\n    stage-packages:\n      - python3\n"));
+        assert_eq!(snap_name("--Big__Tool--"), "big-tool");
+    }
+}
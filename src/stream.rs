@@ -0,0 +1,45 @@
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::process::{Command, Output, Stdio};
+use colored::Colorize;
+use crate::logging::progress;
+
+/// Runs `command` to completion like `Command::output`, and echoes each line
+/// of its stdout and stderr to ours as it arrives, prefixed with `[label]`.
+pub fn output(command: &mut Command, label: &str) -> io::Result<Output> {
+    let mut child = command.stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()?;
+    let prefix = format!("[{}]", label).cyan().to_string();
+    let (stdout, stderr) = (child.stdout.take().unwrap(), child.stderr.take().unwrap());
+    let (stdout, stderr) = std::thread::scope(|scope| {
+        let stderr = scope.spawn(|| relay(stderr, &prefix, io::stderr()));
+        let stdout = relay(stdout, &prefix, io::stdout());
+        (stdout, stderr.join().unwrap_or_default())
+    });
+    Ok(Output { status: child.wait()?, stdout, stderr })
+}
+
+/// Copies `from` line by line to `to` with `prefix`, above the progress
+/// bars, and returns everything read.
+fn relay(from: impl Read, prefix: &str, mut to: impl Write) -> Vec<u8> {
+    let mut captured = Vec::new();
+    let mut reader = BufReader::new(from);
+    let mut line = Vec::new();
+    while reader.read_until(b'\n', &mut line).is_ok_and(|read| read > 0) {
+        let text = String::from_utf8_lossy(&line);
+        progress().suspend(|| writeln!(to, "{} {}", prefix, text.trim_end_matches(['\r', '\n'])).ok());
+        captured.append(&mut line);
+    }
+    captured
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_relay_prefixes_and_captures_lines() {
+        let mut relayed = Vec::new();
+        let captured = relay(&b"main.c:1: warning\r\nlast line"[..], "[c:main.c]", &mut relayed);
+        assert_eq!(captured, b"main.c:1: warning\r\nlast line");
+        assert_eq!(String::from_utf8(relayed).unwrap(), "[c:main.c] main.c:1: warning\n[c:main.c] last line\n");
+    }
+}
@@ -0,0 +1,137 @@
+use std::fs;
+use std::path::Path;
+use anyhow::{Context, Result};
+use regex::Regex;
+use serde::Deserialize;
+use crate::language_support::Language;
+
+/// A pattern to look for in a failed compile's output, and the hint to show
+/// when it matches. `$name` or `${name}` in the hint is replaced by the
+/// pattern's capture group of that name.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Rule {
+    pub pattern: String,
+    pub hint: String,
+    /// `language_settings` keys the rule applies to; empty means all
+    #[serde(default)]
+    pub languages: Vec<String>,
+}
+
+/// Failures common enough to explain out of the box.
+const BUILTIN_RULES: &[(&[&str], &str, &str)] = &[
+    (
+        &[],
+        r"Compiler '(?P<program>[^']+)' not found",
+        "Install `$program` or add its directory to `toolchain_paths`; `lol doctor` shows how to install it",
+    ),
+    (
+        &["c", "cpp", "objc", "objcpp", "cuda"],
+        r"fatal error: (?P<header>[^:\s]+): No such file or directory|fatal error: '(?P<quoted>[^']+)' file not found",
+        "Header `$header$quoted` was not found: install the library's development package, or add its directory with `-I` to `compiler_flags`",
+    ),
+    (
+        &["c", "cpp", "objc", "objcpp", "cuda", "fortran"],
+        r#"undefined reference to [`'](?P<symbol>[^'`]+)'|Undefined symbols for architecture \w+:\s*"(?P<macos_symbol>[^"]+)""#,
+        "A symbol `$symbol$macos_symbol` is used but never defined: link its library with `-l<name>` in `compiler_flags`, or compile the file that defines it",
+    ),
+    (
+        &["cpp", "cuda"],
+        r"(?:only available with|requires) ['‘]?-std=(?P<std>[\w+]+)",
+        "This code needs `-std=$std`: add it to `language_settings.cpp.compiler_flags` or pass `--cxxflags=-std=$std`",
+    ),
+    (
+        &["c", "cpp"],
+        r"unrecognized command[- ]line option ['‘]-std=(?P<std>[\w+]+)|invalid value '(?P<value>[\w+]+)' in '-std=",
+        "This compiler does not know `-std=$std$value`: choose an older standard or set `compiler_path` to a newer compiler",
+    ),
+    (
+        &["java"],
+        r"class (?P<class>\w+) is public, should be declared in a file named",
+        "Rename the file to `$class.java`: Java wants a public class in a file of the same name",
+    ),
+    (
+        &["rust"],
+        r"error\[E0463\]: can't find crate for `(?P<krate>\w+)`",
+        "`$krate` is a dependency; single files are compiled with plain rustc, so use a Cargo project for crates",
+    ),
+    (
+        &["python"],
+        r#"Cannot find implementation or library stub for module named "(?P<module>[\w.]+)""#,
+        "Install `$module`, or its `types-` stub package, for the interpreter lol uses; `language_settings.python.python_path` pins one",
+    ),
+];
+
+/// Compiled rules, the project's own ahead of the built-in ones.
+pub struct Suggestions {
+    rules: Vec<(Vec<String>, Regex, String)>,
+}
+
+impl Suggestions {
+    /// The built-in rules, with those of the `suggestion_rules` file, a JSON
+    /// list of rules relative to the project, taking precedence.
+    pub fn load(rules_file: Option<&str>, project_path: &Path) -> Result<Self> {
+        let mut rules = Vec::new();
+        if let Some(file) = rules_file {
+            let path = project_path.join(file);
+            let contents = fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+            let custom: Vec<Rule> = serde_json::from_str(&contents).with_context(|| format!("Failed to parse {}", path.display()))?;
+            for rule in custom {
+                let pattern = Regex::new(&rule.pattern).with_context(|| format!("Invalid pattern in {}: {}", path.display(), rule.pattern))?;
+                rules.push((rule.languages, pattern, rule.hint));
+            }
+        }
+        for (languages, pattern, hint) in BUILTIN_RULES {
+            let languages = languages.iter().map(|language| language.to_string()).collect();
+            rules.push((languages, Regex::new(pattern).expect("built-in rule"), hint.to_string()));
+        }
+        Ok(Self { rules })
+    }
+
+    /// A hint for each rule matching `output`, in rule order, once each.
+    pub fn suggest(&self, language: &Language, output: &str) -> Vec<String> {
+        let mut hints: Vec<String> = Vec::new();
+        for (languages, pattern, hint) in &self.rules {
+            if !languages.is_empty() && !languages.iter().any(|key| key == language.config_key()) {
+                continue;
+            }
+            if let Some(captures) = pattern.captures(output) {
+                let mut expanded = String::new();
+                captures.expand(hint, &mut expanded);
+                if !hints.contains(&expanded) {
+                    hints.push(expanded);
+                }
+            }
+        }
+        hints
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_builtin_and_project_rules() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(
+            temp_dir.path().join("hints.json"),
+            r#"[{"pattern": "(?P<lib>libfoo)\\.so: cannot open", "hint": "Run scripts/fetch-$lib.sh first", "languages": ["c"]}]"#,
+        )
+        .unwrap();
+        let suggestions = Suggestions::load(Some("hints.json"), temp_dir.path()).unwrap();
+
+        let output = "main.c:1:10: fatal error: png.h: No such file or directory\n\
+                      /usr/bin/ld: main.o: undefined reference to `png_create_read_struct'\n\
+                      error while loading libfoo.so: cannot open shared object file";
+        let hints = suggestions.suggest(&Language::C, output);
+        assert_eq!(hints.len(), 3);
+        assert_eq!(hints[0], "Run scripts/fetch-libfoo.sh first");
+        assert!(hints[1].starts_with("Header `png.h` was not found"));
+        assert!(hints[2].starts_with("A symbol `png_create_read_struct` is used"));
+
+        assert!(suggestions.suggest(&Language::Python, output).is_empty());
+        let std = suggestions.suggest(&Language::Cpp, "error: 'std::optional' is only available with '-std=c++17' or '-std=gnu++17'");
+        assert_eq!(std, ["This code needs `-std=c++17`: add it to `language_settings.cpp.compiler_flags` or pass `--cxxflags=-std=c++17`"]);
+    }
+}
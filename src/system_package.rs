@@ -0,0 +1,219 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use anyhow::{Context, Result};
+use walkdir::WalkDir;
+use crate::config::Config;
+use crate::reproducible;
+
+/// What deb control files and rpm specs are generated from.
+#[derive(Debug)]
+pub struct Metadata {
+    pub name: String,
+    pub version: String,
+    pub maintainer: String,
+    pub description: String,
+    pub license: Option<String>,
+    pub depends: Vec<String>,
+    /// Whether the package holds compiled code, or is architecture-independent
+    pub native: bool,
+}
+
+impl Metadata {
+    pub fn new(name: &str, version: &str, config: &Config, native: bool) -> Result<Self> {
+        let maintainer = config
+            .package
+            .maintainer
+            .clone()
+            .context("Set package.maintainer (\"Name <email>\") to build deb and rpm packages")?;
+        // Both formats restrict package names to lowercase letters, digits and `+-.`
+        let name: String = name
+            .to_lowercase()
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || "+-.".contains(c) { c } else { '-' })
+            .collect();
+        Ok(Self {
+            name,
+            version: version.to_string(),
+            maintainer,
+            description: config.package.description.clone().unwrap_or_else(|| "Built with lol".to_string()),
+            license: config.license.spdx.clone(),
+            depends: config.package.depends.clone(),
+            native,
+        })
+    }
+}
+
+/// Debian's name for the build machine's architecture.
+pub fn deb_architecture() -> String {
+    Command::new("dpkg")
+        .arg("--print-architecture")
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .unwrap_or_else(|| match std::env::consts::ARCH {
+            "x86_64" => "amd64".to_string(),
+            "aarch64" => "arm64".to_string(),
+            "x86" => "i386".to_string(),
+            "arm" => "armhf".to_string(),
+            other => other.to_string(),
+        })
+}
+
+pub fn control_file(metadata: &Metadata, architecture: &str) -> String {
+    let mut control = format!(
+        "Package: {}\nVersion: {}\nArchitecture: {}\nMaintainer: {}\n",
+        metadata.name, metadata.version, architecture, metadata.maintainer
+    );
+    if !metadata.depends.is_empty() {
+        control.push_str(&format!("Depends: {}\n", metadata.depends.join(", ")));
+    }
+    // Continuation lines of the long description start with a space; blank ones are " ."
+    let mut lines = metadata.description.lines();
+    control.push_str(&format!("Description: {}\n", lines.next().unwrap_or_default()));
+    for line in lines {
+        let line = if line.trim().is_empty() { "." } else { line };
+        control.push_str(&format!(" {}\n", line));
+    }
+    control
+}
+
+/// Lays `DEBIAN/control` next to the staged `usr/` tree and runs
+/// `dpkg-deb`, writing `<dist>/<name>_<version>_<arch>.deb`.
+pub fn build_deb(staging_dir: &Path, dist_dir: &Path, metadata: &Metadata) -> Result<PathBuf> {
+    let architecture = if metadata.native { deb_architecture() } else { "all".to_string() };
+    let debian_dir = staging_dir.join("DEBIAN");
+    fs::create_dir_all(&debian_dir)?;
+    fs::write(debian_dir.join("control"), control_file(metadata, &architecture))?;
+
+    let package_path = dist_dir.join(format!("{}_{}_{}.deb", metadata.name, metadata.version, architecture));
+    let output = reproducible::apply(&mut Command::new("dpkg-deb"))
+        .args(["--root-owner-group", "--build"])
+        .arg(staging_dir)
+        .arg(&package_path)
+        .output()
+        .context("Building deb packages needs dpkg-deb on the PATH")?;
+    if !output.status.success() {
+        anyhow::bail!("dpkg-deb failed: {}", String::from_utf8_lossy(&output.stderr));
+    }
+    Ok(package_path)
+}
+
+/// The spec rpmbuild needs to package an already staged tree as-is.
+pub fn spec_file(metadata: &Metadata, staging_dir: &Path, files: &[PathBuf]) -> String {
+    let summary = metadata.description.lines().next().unwrap_or_default();
+    let mut spec = String::from(
+        "# Files are installed prebuilt; skip debuginfo extraction and stripping\n\
+        %global debug_package %{nil}\n\
+        %global __os_install_post %{nil}\n\n",
+    );
+    spec.push_str(&format!("Name: {}\n", metadata.name));
+    // `-` separates version from release in rpm
+    spec.push_str(&format!("Version: {}\n", metadata.version.replace('-', ".")));
+    spec.push_str("Release: 1\n");
+    spec.push_str(&format!("Summary: {}\n", summary));
+    spec.push_str(&format!("License: {}\n", metadata.license.as_deref().unwrap_or("LicenseRef-Proprietary")));
+    spec.push_str(&format!("Packager: {}\n", metadata.maintainer));
+    if !metadata.native {
+        spec.push_str("BuildArch: noarch\n");
+    }
+    // `libssl3 (>= 3.0)` becomes `libssl3 >= 3.0`
+    for dependency in &metadata.depends {
+        spec.push_str(&format!("Requires: {}\n", dependency.replace(['(', ')'], "")));
+    }
+    spec.push_str(&format!("\n%description\n{}\n", metadata.description));
+    spec.push_str(&format!(
+        "\n%install\nmkdir -p %{{buildroot}}\ncp -a \"{}/.\" %{{buildroot}}/\n",
+        staging_dir.display()
+    ));
+    spec.push_str("\n%files\n");
+    for file in files {
+        spec.push_str(&format!("\"/{}\"\n", file.display()));
+    }
+    spec
+}
+
+/// Writes a spec for the staged tree and runs `rpmbuild -bb` in a private
+/// top directory, moving the result to `<dist>/`.
+pub fn build_rpm(staging_dir: &Path, dist_dir: &Path, metadata: &Metadata) -> Result<PathBuf> {
+    let staging_dir = staging_dir.canonicalize()?;
+    let mut files = Vec::new();
+    for entry in WalkDir::new(&staging_dir).sort_by_file_name() {
+        let entry = entry?;
+        if !entry.file_type().is_dir() {
+            files.push(entry.path().strip_prefix(&staging_dir)?.to_path_buf());
+        }
+    }
+
+    let top_dir = dist_dir.canonicalize()?.join("rpmbuild");
+    let spec_path = top_dir.join("SPECS").join(format!("{}.spec", metadata.name));
+    fs::create_dir_all(spec_path.parent().unwrap_or(&top_dir))?;
+    fs::write(&spec_path, spec_file(metadata, &staging_dir, &files))?;
+
+    let result = run_rpmbuild(&top_dir, &spec_path, dist_dir);
+    let _ = fs::remove_dir_all(&top_dir);
+    result
+}
+
+fn run_rpmbuild(top_dir: &Path, spec_path: &Path, dist_dir: &Path) -> Result<PathBuf> {
+    let output = reproducible::apply(&mut Command::new("rpmbuild"))
+        .arg("-bb")
+        .arg("--define")
+        .arg(format!("_topdir {}", top_dir.display()))
+        .arg(spec_path)
+        .output()
+        .context("Building rpm packages needs rpmbuild on the PATH")?;
+    if !output.status.success() {
+        anyhow::bail!("rpmbuild failed: {}", String::from_utf8_lossy(&output.stderr));
+    }
+
+    let built = WalkDir::new(top_dir.join("RPMS"))
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.into_path())
+        .find(|path| path.extension().is_some_and(|ext| ext == "rpm"))
+        .context("rpmbuild did not produce a package")?;
+    let package_path = dist_dir.join(built.file_name().unwrap_or_default());
+    fs::rename(&built, &package_path)?;
+    Ok(package_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::PackageConfig;
+
+    fn metadata() -> Metadata {
+        let config = Config {
+            package: PackageConfig {
+                maintainer: Some("Ada Lovelace <ada@example.com>".to_string()),
+                description: Some("Demo tool\nDoes demo things.\n\nReally.".to_string()),
+                depends: vec!["python3".to_string(), "libssl3 (>= 3.0)".to_string()],
+                ..PackageConfig::default()
+            },
+            ..Config::default()
+        };
+        Metadata::new("My_App", "1.2.0-3-gabc", &config, false).unwrap()
+    }
+
+    #[test]
+    fn test_control_file() {
+        assert_eq!(
+            control_file(&metadata(), "all"),
+            "Package: my-app\nVersion: 1.2.0-3-gabc\nArchitecture: all\nMaintainer: Ada Lovelace <ada@example.com>\n\
+            Depends: python3, libssl3 (>= 3.0)\nDescription: Demo tool\n Does demo things.\n .\n Really.\n"
+        );
+        assert!(Metadata::new("demo", "1.0", &Config::default(), false).is_err());
+    }
+
+    #[test]
+    fn test_spec_file() {
+        let files = vec![PathBuf::from("usr/bin/my-app"), PathBuf::from("usr/share/my-app/python/main.py")];
+        let spec = spec_file(&metadata(), Path::new("/tmp/stage"), &files);
+        assert!(spec.contains("Name: my-app\nVersion: 1.2.0.3.gabc\nRelease: 1\nSummary: Demo tool\n"));
+        assert!(spec.contains("BuildArch: noarch\nRequires: python3\nRequires: libssl3 >= 3.0\n"));
+        assert!(spec.contains("cp -a \"/tmp/stage/.\" %{buildroot}/\n"));
+        assert!(spec.ends_with("%files\n\"/usr/bin/my-app\"\n\"/usr/share/my-app/python/main.py\"\n"));
+    }
+}
@@ -0,0 +1,467 @@
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use anyhow::{Context, Result};
+use ratatui::backend::CrosstermBackend;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::crossterm::execute;
+use ratatui::crossterm::terminal::{self, EnterAlternateScreen, LeaveAlternateScreen};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph};
+use ratatui::{Frame, Terminal};
+use crate::compiler::{CompilationResult, CompilationStatus};
+use crate::diagnostics::{Diagnostic, Severity};
+use crate::language_support::Language;
+
+/// Lines of build output kept for the output pane.
+const LOG_LIMIT: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LanguageStatus {
+    Waiting,
+    Building,
+    Passed,
+    Failed,
+    /// Skipped because its compiler is not installed
+    Missing,
+    Disabled,
+}
+
+/// One language's widget: its sources and how its last build went.
+#[derive(Debug, Clone)]
+pub struct LanguageState {
+    pub language: Language,
+    pub enabled: bool,
+    pub status: LanguageStatus,
+    pub files: usize,
+    pub errors: usize,
+    pub warnings: usize,
+}
+
+/// What a key press asks the watch loop to do.
+#[derive(Debug, PartialEq)]
+pub enum Action {
+    Quit,
+    Rebuild,
+    /// The language's enabled state changed; rebuild without it, or with it again
+    Toggle(Language),
+    Edit(Diagnostic),
+    Nothing,
+}
+
+/// Everything the watch TUI shows.
+#[derive(Debug, Default)]
+pub struct App {
+    project: String,
+    pub languages: Vec<LanguageState>,
+    pub diagnostics: Vec<Diagnostic>,
+    selected: usize,
+    log: Vec<String>,
+    status: String,
+}
+
+impl App {
+    pub fn new(project_path: &Path) -> Self {
+        let root = project_path.canonicalize().unwrap_or_else(|_| project_path.to_path_buf());
+        Self {
+            project: root.display().to_string(),
+            status: "Starting".to_string(),
+            ..Self::default()
+        }
+    }
+
+    /// Languages toggled off, left out of the next build.
+    pub fn disabled(&self) -> impl Iterator<Item = &Language> {
+        self.languages.iter().filter(|state| !state.enabled).map(|state| &state.language)
+    }
+
+    /// Updates file counts from a fresh detection. Languages stay listed
+    /// once seen, so a disabled one can be switched back on.
+    pub fn set_sources(&mut self, sources: &HashMap<Language, Vec<PathBuf>>) {
+        for state in &mut self.languages {
+            state.files = sources.get(&state.language).map_or(0, Vec::len);
+        }
+        let mut new: Vec<&Language> = sources.keys().filter(|language| !self.languages.iter().any(|state| &state.language == *language)).collect();
+        new.sort_by_key(|language| language.name().to_string());
+        for language in new {
+            self.languages.push(LanguageState {
+                language: language.clone(),
+                enabled: true,
+                status: LanguageStatus::Waiting,
+                files: sources[language].len(),
+                errors: 0,
+                warnings: 0,
+            });
+        }
+    }
+
+    pub fn build_started(&mut self) {
+        for state in self.languages.iter_mut().filter(|state| state.enabled) {
+            state.status = LanguageStatus::Building;
+        }
+        self.status = "Building…".to_string();
+    }
+
+    pub fn build_finished(&mut self, results: &[CompilationResult], failed_stage: Option<&str>, duration: Duration) {
+        self.diagnostics = results.iter().flat_map(|result| result.diagnostics.iter().cloned()).collect();
+        // Errors first, so the one to fix is at the top
+        self.diagnostics.sort_by_key(|diagnostic| diagnostic.severity != Severity::Error);
+        self.selected = self.selected.min(self.diagnostics.len().saturating_sub(1));
+
+        for state in &mut self.languages {
+            let result = results.iter().find(|result| result.language == state.language);
+            let count = |severity| result.map_or(0, |result| result.diagnostics.iter().filter(|d| d.severity == severity).count());
+            state.errors = count(Severity::Error);
+            state.warnings = count(Severity::Warning);
+            state.status = match result.map(|result| &result.status) {
+                _ if !state.enabled => LanguageStatus::Disabled,
+                Some(CompilationStatus::Success { .. }) => LanguageStatus::Passed,
+                Some(CompilationStatus::Failure { .. }) => LanguageStatus::Failed,
+                Some(CompilationStatus::ToolchainMissing { .. }) => LanguageStatus::Missing,
+                None => LanguageStatus::Waiting,
+            };
+        }
+        self.status = match failed_stage {
+            None => format!("Build passed in {:.1}s", duration.as_secs_f64()),
+            Some(stage) => format!("Stage {} failed after {:.1}s", stage, duration.as_secs_f64()),
+        };
+    }
+
+    pub fn push_log(&mut self, lines: Vec<String>) {
+        self.log.extend(lines);
+        if self.log.len() > LOG_LIMIT {
+            self.log.drain(..self.log.len() - LOG_LIMIT);
+        }
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> Action {
+        if key.kind != KeyEventKind::Press {
+            return Action::Nothing;
+        }
+        let last = self.diagnostics.len().saturating_sub(1);
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Action::Quit,
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return Action::Quit,
+            KeyCode::Char('r') => return Action::Rebuild,
+            KeyCode::Char('e') | KeyCode::Enter => {
+                if let Some(diagnostic) = self.diagnostics.get(self.selected) {
+                    return Action::Edit(diagnostic.clone());
+                }
+            }
+            KeyCode::Char(digit @ '1'..='9') => {
+                let index = digit as usize - '1' as usize;
+                if let Some(state) = self.languages.get_mut(index) {
+                    state.enabled = !state.enabled;
+                    state.status = if state.enabled { LanguageStatus::Waiting } else { LanguageStatus::Disabled };
+                    return Action::Toggle(state.language.clone());
+                }
+            }
+            KeyCode::Up | KeyCode::Char('k') => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => self.selected = (self.selected + 1).min(last),
+            KeyCode::PageUp => self.selected = self.selected.saturating_sub(10),
+            KeyCode::PageDown => self.selected = (self.selected + 10).min(last),
+            KeyCode::Home => self.selected = 0,
+            KeyCode::End => self.selected = last,
+            _ => {}
+        }
+        Action::Nothing
+    }
+
+    pub fn draw(&self, frame: &mut Frame) {
+        let [header, widgets, diagnostics, output, help] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Length(4),
+            Constraint::Min(5),
+            Constraint::Length(10),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        frame.render_widget(
+            Paragraph::new(Line::from(vec![
+                Span::styled(" lol watch ", Style::new().fg(Color::Black).bg(Color::Blue).add_modifier(Modifier::BOLD)),
+                Span::raw(format!(" {}  ", self.project)),
+                Span::styled(self.status.clone(), Style::new().add_modifier(Modifier::BOLD)),
+            ])),
+            header,
+        );
+        self.draw_languages(frame, widgets);
+        self.draw_diagnostics(frame, diagnostics);
+
+        let visible = output.height.saturating_sub(2) as usize;
+        let lines: Vec<Line> = self.log.iter().skip(self.log.len().saturating_sub(visible)).map(|line| Line::raw(line.as_str())).collect();
+        frame.render_widget(Paragraph::new(lines).block(Block::bordered().title(" Output ")), output);
+
+        frame.render_widget(
+            Paragraph::new(" q quit · r rebuild · ↑↓ select · e open in $EDITOR · 1-9 toggle language")
+                .style(Style::new().fg(Color::DarkGray)),
+            help,
+        );
+    }
+
+    fn draw_languages(&self, frame: &mut Frame, area: Rect) {
+        if self.languages.is_empty() {
+            frame.render_widget(Paragraph::new("No source files found").block(Block::bordered()), area);
+            return;
+        }
+        let areas = Layout::horizontal(vec![Constraint::Ratio(1, self.languages.len() as u32); self.languages.len()]).split(area);
+        for (index, (state, area)) in self.languages.iter().zip(areas.iter()).enumerate() {
+            let (label, color) = match state.status {
+                LanguageStatus::Waiting => ("waiting", Color::DarkGray),
+                LanguageStatus::Building => ("building", Color::Yellow),
+                LanguageStatus::Passed => ("passed", Color::Green),
+                LanguageStatus::Failed => ("failed", Color::Red),
+                LanguageStatus::Missing => ("no compiler", Color::Magenta),
+                LanguageStatus::Disabled => ("off", Color::DarkGray),
+            };
+            let text = vec![
+                Line::styled(label, Style::new().fg(color).add_modifier(Modifier::BOLD)),
+                Line::raw(format!("{} files · {}E {}W", state.files, state.errors, state.warnings)),
+            ];
+            let title = if index < 9 { format!(" {} {} ", index + 1, state.language.name()) } else { format!(" {} ", state.language.name()) };
+            frame.render_widget(Paragraph::new(text).block(Block::bordered().title(title).border_style(Style::new().fg(color))), *area);
+        }
+    }
+
+    fn draw_diagnostics(&self, frame: &mut Frame, area: Rect) {
+        let root = Path::new(&self.project);
+        let items: Vec<ListItem> = self
+            .diagnostics
+            .iter()
+            .map(|diagnostic| {
+                let color = match diagnostic.severity {
+                    Severity::Error => Color::Red,
+                    Severity::Warning => Color::Yellow,
+                    Severity::Note => Color::Cyan,
+                };
+                let file = diagnostic.file.strip_prefix(root).unwrap_or(&diagnostic.file);
+                let location = match diagnostic.line {
+                    Some(line) => format!("{}:{}", file.display(), line),
+                    None => file.display().to_string(),
+                };
+                ListItem::new(Line::from(vec![
+                    Span::styled(format!("{:<8}", format!("{:?}", diagnostic.severity).to_lowercase()), Style::new().fg(color)),
+                    Span::styled(location, Style::new().add_modifier(Modifier::BOLD)),
+                    Span::raw(format!("  {}", diagnostic.message)),
+                ]))
+            })
+            .collect();
+        let list = List::new(items)
+            .block(Block::bordered().title(format!(" Diagnostics ({}) ", self.diagnostics.len())))
+            .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        let mut state = ListState::default().with_selected((!self.diagnostics.is_empty()).then_some(self.selected));
+        frame.render_stateful_widget(list, area, &mut state);
+    }
+}
+
+/// The terminal while the TUI runs: raw mode on `/dev/tty`, and the
+/// build's own printing captured for the output pane instead of drawn over
+/// the UI.
+pub struct Session {
+    terminal: Terminal<CrosstermBackend<File>>,
+    capture: OutputCapture,
+}
+
+impl Session {
+    pub fn start() -> Result<Self> {
+        let tty = OpenOptions::new().read(true).write(true).open("/dev/tty").context("The watch TUI needs a terminal")?;
+        let capture = OutputCapture::start()?;
+        colored::control::set_override(false);
+        terminal::enable_raw_mode()?;
+        let mut backend = CrosstermBackend::new(tty);
+        execute!(backend, EnterAlternateScreen)?;
+        Ok(Self { terminal: Terminal::new(backend)?, capture })
+    }
+
+    pub fn draw(&mut self, app: &mut App) -> Result<()> {
+        app.push_log(self.capture.drain());
+        self.terminal.draw(|frame| app.draw(frame))?;
+        Ok(())
+    }
+
+    pub fn poll_key(&self, timeout: Duration) -> Result<Option<KeyEvent>> {
+        if event::poll(timeout)? {
+            if let Event::Key(key) = event::read()? {
+                return Ok(Some(key));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Opens the diagnostic's file at its line in `$VISUAL` or `$EDITOR`
+    /// (`vi` when neither is set), handing the terminal over until it exits.
+    pub fn edit(&mut self, diagnostic: &Diagnostic) -> Result<()> {
+        let editor = std::env::var("VISUAL").or_else(|_| std::env::var("EDITOR")).unwrap_or_else(|_| "vi".to_string());
+        let mut words = editor.split_whitespace();
+        let program = words.next().unwrap_or("vi");
+        let mut command = Command::new(program);
+        command.args(words).args(editor_args(program, &diagnostic.file, diagnostic.line));
+
+        // The editor gets the real terminal, not the captured output
+        let tty = || OpenOptions::new().read(true).write(true).open("/dev/tty");
+        command.stdin(Stdio::from(tty()?)).stdout(Stdio::from(tty()?)).stderr(Stdio::from(tty()?));
+
+        self.suspend()?;
+        let status = command.status().with_context(|| format!("Failed to run {}", program));
+        self.resume()?;
+        status?;
+        Ok(())
+    }
+
+    fn suspend(&mut self) -> Result<()> {
+        execute!(self.terminal.backend_mut(), LeaveAlternateScreen)?;
+        terminal::disable_raw_mode()?;
+        Ok(())
+    }
+
+    fn resume(&mut self) -> Result<()> {
+        terminal::enable_raw_mode()?;
+        execute!(self.terminal.backend_mut(), EnterAlternateScreen)?;
+        self.terminal.clear()?;
+        Ok(())
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        let _ = self.suspend();
+        let _ = self.terminal.show_cursor();
+        colored::control::unset_override();
+        self.capture.stop();
+    }
+}
+
+/// `+line file` for terminal editors, `--goto file:line` for VS Code.
+fn editor_args(program: &str, file: &Path, line: Option<u32>) -> Vec<String> {
+    let name = Path::new(program).file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+    match (name.as_str(), line) {
+        ("code" | "codium", Some(line)) => vec!["--goto".to_string(), format!("{}:{}", file.display(), line)],
+        (_, Some(line)) => vec![format!("+{}", line), file.display().to_string()],
+        (_, None) => vec![file.display().to_string()],
+    }
+}
+
+/// Points stdout and stderr at a pipe whose lines are collected, so
+/// everything the build prints ends up in the output pane.
+#[cfg(unix)]
+struct OutputCapture {
+    saved: Option<[libc::c_int; 2]>,
+    lines: Arc<Mutex<Vec<String>>>,
+}
+
+#[cfg(unix)]
+impl OutputCapture {
+    fn start() -> Result<Self> {
+        use std::io::{BufRead, BufReader, Write};
+        use std::os::fd::FromRawFd;
+
+        let _ = std::io::stdout().flush();
+        let mut pipe = [0; 2];
+        // SAFETY: plain descriptor juggling; the originals are kept to be restored in `stop`
+        let saved = unsafe {
+            if libc::pipe(pipe.as_mut_ptr()) != 0 {
+                return Err(std::io::Error::last_os_error()).context("Failed to capture build output");
+            }
+            let saved = [libc::dup(libc::STDOUT_FILENO), libc::dup(libc::STDERR_FILENO)];
+            libc::dup2(pipe[1], libc::STDOUT_FILENO);
+            libc::dup2(pipe[1], libc::STDERR_FILENO);
+            libc::close(pipe[1]);
+            saved
+        };
+
+        let lines = Arc::new(Mutex::new(Vec::new()));
+        let collected = Arc::clone(&lines);
+        // SAFETY: the read end is owned by this thread alone
+        let reader = BufReader::new(unsafe { File::from_raw_fd(pipe[0]) });
+        std::thread::spawn(move || {
+            let escapes = regex::Regex::new(r"\x1b\[[0-9;?]*[A-Za-z]").expect("valid pattern");
+            for line in reader.lines().map_while(Result::ok) {
+                // Progress output redraws with carriage returns; keep the final state
+                let line = line.rsplit('\r').next().unwrap_or_default();
+                collected.lock().unwrap().push(escapes.replace_all(line, "").into_owned());
+            }
+        });
+        Ok(Self { saved: Some(saved), lines })
+    }
+
+    fn drain(&self) -> Vec<String> {
+        std::mem::take(&mut *self.lines.lock().unwrap())
+    }
+
+    fn stop(&mut self) {
+        use std::io::Write;
+        let _ = std::io::stdout().flush();
+        if let Some([stdout, stderr]) = self.saved.take() {
+            // SAFETY: restores the descriptors saved in `start`
+            unsafe {
+                libc::dup2(stdout, libc::STDOUT_FILENO);
+                libc::dup2(stderr, libc::STDERR_FILENO);
+                libc::close(stdout);
+                libc::close(stderr);
+            }
+        }
+    }
+}
+
+#[cfg(not(unix))]
+struct OutputCapture;
+
+#[cfg(not(unix))]
+impl OutputCapture {
+    fn start() -> Result<Self> {
+        anyhow::bail!("The watch TUI is only available on Unix terminals; use `lol watch` without --tui")
+    }
+
+    fn drain(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    fn stop(&mut self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    #[test]
+    fn test_app_tracks_builds_and_keys() {
+        let mut app = App::new(Path::new("."));
+        let mut sources = HashMap::new();
+        sources.insert(Language::Python, vec![PathBuf::from("app.py")]);
+        sources.insert(Language::C, vec![PathBuf::from("a.c"), PathBuf::from("b.c")]);
+        app.set_sources(&sources);
+        assert_eq!(app.languages.iter().map(|state| state.language.name()).collect::<Vec<_>>(), ["C", "Python"]);
+
+        let warning = Diagnostic { file: PathBuf::from("a.c"), line: Some(3), column: None, severity: Severity::Warning, message: "unused".to_string() };
+        let error = Diagnostic { severity: Severity::Error, line: Some(9), message: "boom".to_string(), ..warning.clone() };
+        let results = vec![CompilationResult {
+            language: Language::C,
+            files: sources[&Language::C].clone(),
+            status: CompilationStatus::Failure { error: "boom".to_string() },
+            diagnostics: vec![warning, error.clone()],
+            toolchain: None,
+            retries: 0,
+            duration_ms: 0,
+        }];
+        app.build_started();
+        app.build_finished(&results, Some("compile"), Duration::from_secs(2));
+        assert_eq!(app.languages[0].status, LanguageStatus::Failed);
+        assert_eq!((app.languages[0].errors, app.languages[0].warnings), (1, 1));
+        assert_eq!(app.languages[1].status, LanguageStatus::Waiting);
+
+        assert_eq!(app.handle_key(key(KeyCode::Enter)), Action::Edit(error));
+        assert_eq!(app.handle_key(key(KeyCode::Char('2'))), Action::Toggle(Language::Python));
+        assert_eq!(app.disabled().collect::<Vec<_>>(), [&Language::Python]);
+        assert_eq!(app.handle_key(key(KeyCode::Char('q'))), Action::Quit);
+        assert_eq!(editor_args("/usr/bin/code", Path::new("a.c"), Some(9)), ["--goto", "a.c:9"]);
+    }
+}
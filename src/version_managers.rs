@@ -0,0 +1,241 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use crate::config::Config;
+use crate::doctor::on_path;
+use crate::plugins::version_key;
+
+/// A toolchain a version manager file asks for, and how to reach it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Resolution {
+    /// `language_settings` keys the toolchain compiles
+    pub languages: Vec<&'static str>,
+    /// e.g. `rust 1.75.0 via rustup (rust-toolchain.toml)`
+    pub description: String,
+    /// Searched after the language's configured `toolchain_paths`
+    pub bin_dir: Option<PathBuf>,
+    pub env: Vec<(String, String)>,
+}
+
+impl Resolution {
+    /// Makes the language settings in `config` use this toolchain.
+    pub fn apply(&self, config: &mut Config) {
+        for key in &self.languages {
+            // Settings written in the configuration take precedence
+            let mut settings = config.get_language_config(key).cloned().unwrap_or_default();
+            if let Some(bin_dir) = &self.bin_dir {
+                settings.toolchain_paths.push(bin_dir.to_string_lossy().into_owned());
+            }
+            for (name, value) in &self.env {
+                settings.env.entry(name.clone()).or_insert_with(|| value.clone());
+            }
+            config.set_language_config(key, settings);
+        }
+    }
+}
+
+/// `.tool-versions` tool names, with the languages they compile and the
+/// program to ask the manager for.
+const TOOLS: &[(&str, &[&str], &str)] = &[
+    ("nodejs", &["javascript", "typescript"], "node"),
+    ("node", &["javascript", "typescript"], "node"),
+    ("python", &["python", "cython"], "python3"),
+    ("golang", &["go"], "go"),
+    ("go", &["go"], "go"),
+    ("rust", &["rust"], "rustc"),
+    ("ruby", &["ruby"], "ruby"),
+    ("java", &["java"], "javac"),
+    ("kotlin", &["kotlin"], "kotlinc"),
+    ("scala", &["scala"], "scalac"),
+    ("elixir", &["elixir"], "elixir"),
+    ("erlang", &["erlang"], "erlc"),
+    ("zig", &["zig"], "zig"),
+    ("nim", &["nim"], "nim"),
+    ("crystal", &["crystal"], "crystal"),
+    ("julia", &["julia"], "julia"),
+    ("lua", &["lua"], "lua"),
+    ("perl", &["perl"], "perl"),
+    ("ghc", &["haskell"], "ghc"),
+    ("haskell", &["haskell"], "ghc"),
+    ("ocaml", &["ocaml"], "ocaml"),
+    ("dotnet", &["csharp", "fsharp"], "dotnet"),
+    ("dotnet-core", &["csharp", "fsharp"], "dotnet"),
+];
+
+/// Toolchains pinned by the project's `rust-toolchain.toml`/`rust-toolchain`,
+/// `.tool-versions` (asdf or mise), `.nvmrc` (nvm) and `.python-version`
+/// (pyenv). When several files pin the same language, the earlier one in
+/// that list wins.
+pub fn resolve(project_path: &Path) -> Vec<Resolution> {
+    let mut resolutions = Vec::new();
+    let mut resolve_unless_pinned = |resolution: Option<Resolution>| {
+        if let Some(resolution) = resolution {
+            let pinned: HashSet<&str> = resolutions.iter().flat_map(|r: &Resolution| r.languages.clone()).collect();
+            if !resolution.languages.iter().any(|language| pinned.contains(language)) {
+                resolutions.push(resolution);
+            }
+        }
+    };
+
+    resolve_unless_pinned(rust_toolchain(project_path));
+    for resolution in tool_versions(project_path) {
+        resolve_unless_pinned(Some(resolution));
+    }
+    let nvm_dir = std::env::var_os("NVM_DIR").map(PathBuf::from).or_else(|| dirs::home_dir().map(|home| home.join(".nvm")));
+    resolve_unless_pinned(nvm_dir.and_then(|nvm_dir| nvmrc(project_path, &nvm_dir)));
+    resolve_unless_pinned(python_version(project_path));
+    resolutions
+}
+
+/// rustup reads the toolchain file itself, but only from the directory it
+/// runs in; `RUSTUP_TOOLCHAIN` makes the choice independent of that.
+fn rust_toolchain(project_path: &Path) -> Option<Resolution> {
+    let (file, contents) = ["rust-toolchain.toml", "rust-toolchain"]
+        .iter()
+        .find_map(|file| Some((*file, fs::read_to_string(project_path.join(file)).ok()?)))?;
+    let channel = toolchain_channel(&contents)?;
+    Some(Resolution {
+        languages: vec!["rust"],
+        description: format!("rust {} via rustup ({})", channel, file),
+        bin_dir: None,
+        env: vec![("RUSTUP_TOOLCHAIN".to_string(), channel)],
+    })
+}
+
+/// The channel of a toolchain file: `channel = "…"` under `[toolchain]`,
+/// or the whole file in the legacy one-line format.
+fn toolchain_channel(contents: &str) -> Option<String> {
+    let channel = contents
+        .lines()
+        .map(str::trim)
+        .find_map(|line| line.strip_prefix("channel")?.trim_start().strip_prefix('='))
+        .map(|value| value.trim().trim_matches(|c| c == '"' || c == '\'').to_string());
+    let legacy = || Some(contents.trim()).filter(|line| !line.is_empty() && !line.contains(['[', '=', '\n'])).map(str::to_string);
+    channel.or_else(legacy).filter(|channel| !channel.is_empty())
+}
+
+/// `(tool, version)` pairs of a `.tool-versions` file.
+fn parse_tool_versions(contents: &str) -> Vec<(&str, &str)> {
+    contents
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or_default())
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            Some((fields.next()?, fields.next()?))
+        })
+        .collect()
+}
+
+/// Asks mise, or else asdf, where each tool in `.tool-versions` lives.
+fn tool_versions(project_path: &Path) -> Vec<Resolution> {
+    let Ok(contents) = fs::read_to_string(project_path.join(".tool-versions")) else {
+        return Vec::new();
+    };
+    let Some(manager) = ["mise", "asdf"].into_iter().find(|manager| on_path(manager)) else {
+        tracing::warn!(".tool-versions found, but neither mise nor asdf is installed");
+        return Vec::new();
+    };
+
+    parse_tool_versions(&contents)
+        .into_iter()
+        .filter_map(|(tool, version)| {
+            let (_, languages, program) = TOOLS.iter().find(|(name, _, _)| *name == tool)?;
+            let Some(program_path) = which(manager, program, project_path) else {
+                tracing::warn!("{} {} from .tool-versions is not installed; run `{} install`", tool, version, manager);
+                return None;
+            };
+            Some(Resolution {
+                languages: languages.to_vec(),
+                description: format!("{} {} via {} (.tool-versions)", tool, version, manager),
+                bin_dir: program_path.parent().map(Path::to_path_buf),
+                env: Vec::new(),
+            })
+        })
+        .collect()
+}
+
+/// `<manager> which <program>`, run in the project so its pins apply.
+fn which(manager: &str, program: &str, project_path: &Path) -> Option<PathBuf> {
+    let output = Command::new(manager).args(["which", program]).current_dir(project_path).output().ok()?;
+    let path = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (output.status.success() && !path.is_empty()).then(|| PathBuf::from(path))
+}
+
+/// nvm is a shell function, so the version is looked up in its install
+/// tree: the newest installed `v<version>*` matching `.nvmrc`.
+fn nvmrc(project_path: &Path, nvm_dir: &Path) -> Option<Resolution> {
+    let requested = fs::read_to_string(project_path.join(".nvmrc")).ok()?.trim().to_string();
+    let prefix = format!("v{}", requested.trim_start_matches('v'));
+    let installed = fs::read_dir(nvm_dir.join("versions").join("node")).ok()?;
+    let matching = installed
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .filter(|version| version == &prefix || version.starts_with(&format!("{}.", prefix)))
+        .max_by_key(|version| version_key(version));
+    let Some(version) = matching else {
+        tracing::warn!("Node.js {} from .nvmrc is not installed; run `nvm install`", requested);
+        return None;
+    };
+    Some(Resolution {
+        languages: vec!["javascript", "typescript"],
+        description: format!("node {} via nvm (.nvmrc)", version),
+        bin_dir: Some(nvm_dir.join("versions").join("node").join(&version).join("bin")),
+        env: Vec::new(),
+    })
+}
+
+fn python_version(project_path: &Path) -> Option<Resolution> {
+    let requested = fs::read_to_string(project_path.join(".python-version")).ok()?;
+    let requested = requested.lines().next()?.trim().to_string();
+    if !on_path("pyenv") {
+        tracing::warn!(".python-version found, but pyenv is not installed");
+        return None;
+    }
+    let output = Command::new("pyenv").arg("prefix").current_dir(project_path).output().ok()?;
+    if !output.status.success() {
+        tracing::warn!("Python {} from .python-version is not installed; run `pyenv install`", requested);
+        return None;
+    }
+    let prefix = PathBuf::from(String::from_utf8_lossy(&output.stdout).trim());
+    Some(Resolution {
+        languages: vec!["python", "cython"],
+        description: format!("python {} via pyenv (.python-version)", requested),
+        bin_dir: Some(prefix.join("bin")),
+        env: Vec::new(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_version_files() {
+        assert_eq!(toolchain_channel("[toolchain]\nchannel = \"1.75.0\"\ncomponents = [\"clippy\"]\n").as_deref(), Some("1.75.0"));
+        assert_eq!(toolchain_channel("nightly-2024-01-01\n").as_deref(), Some("nightly-2024-01-01"));
+        assert_eq!(toolchain_channel("[toolchain]\ncomponents = []\n"), None);
+        assert_eq!(parse_tool_versions("nodejs 20.11.0\n# comment\ngolang 1.22.1 1.21.0\n"), [("nodejs", "20.11.0"), ("golang", "1.22.1")]);
+
+        let temp_dir = TempDir::new().unwrap();
+        let (project, nvm_dir) = (temp_dir.path().join("project"), temp_dir.path().join("nvm"));
+        fs::create_dir_all(&project).unwrap();
+        for version in ["v18.19.0", "v20.9.0", "v20.11.1", "v200.0.0"] {
+            fs::create_dir_all(nvm_dir.join("versions/node").join(version).join("bin")).unwrap();
+        }
+        fs::write(project.join(".nvmrc"), "20\n").unwrap();
+        fs::write(project.join("rust-toolchain.toml"), "[toolchain]\nchannel = \"stable\"\n").unwrap();
+
+        let node = nvmrc(&project, &nvm_dir).unwrap();
+        assert_eq!(node.bin_dir, Some(nvm_dir.join("versions/node/v20.11.1/bin")));
+
+        let mut config = Config::default();
+        rust_toolchain(&project).unwrap().apply(&mut config);
+        node.apply(&mut config);
+        let rust = config.get_language_config("rust").unwrap();
+        assert_eq!(rust.env["RUSTUP_TOOLCHAIN"], "stable");
+        assert_eq!(rust.compiler_flags, ["--release"]);
+        assert!(config.get_language_config("typescript").unwrap().enabled);
+    }
+}
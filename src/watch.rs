@@ -0,0 +1,151 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command};
+use std::time::{Duration, SystemTime};
+use anyhow::{Context, Result};
+use colored::*;
+use crate::config::PROJECT_CONFIG_FILES;
+
+/// How often watch mode looks for changed files.
+pub const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// What is known of a watched file: its modification time and size, and
+/// the hash of its contents when they were last read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Stamp {
+    modified: SystemTime,
+    len: u64,
+    sha256: String,
+}
+
+/// The watched files, compared between polls by content. Editors and tools
+/// that touch a file without changing it move its modification time, which
+/// alone would trigger a pointless rebuild.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Fingerprint {
+    files: HashMap<PathBuf, Stamp>,
+}
+
+impl Fingerprint {
+    /// Fingerprints `sources` and the project's config files, so
+    /// configuration edits also trigger a rebuild. Only files whose
+    /// modification time or size differ from `earlier` are read and hashed.
+    pub fn take<'a>(project_path: &Path, sources: impl IntoIterator<Item = &'a PathBuf>, earlier: &Fingerprint) -> Self {
+        let configs = PROJECT_CONFIG_FILES.iter().map(|name| project_path.join(name));
+        let files = sources
+            .into_iter()
+            .cloned()
+            .chain(configs)
+            .filter_map(|path| {
+                let metadata = fs::metadata(&path).ok()?;
+                let (modified, len) = (metadata.modified().ok()?, metadata.len());
+                let sha256 = match earlier.files.get(&path) {
+                    Some(stamp) if stamp.modified == modified && stamp.len == len => stamp.sha256.clone(),
+                    _ => crate::artifacts::sha256(&path).ok()?,
+                };
+                Some((path, Stamp { modified, len, sha256 }))
+            })
+            .collect();
+        Self { files }
+    }
+
+    /// Files added, removed or with different contents since `earlier`, sorted.
+    pub fn changes(&self, earlier: &Fingerprint) -> Vec<PathBuf> {
+        let mut changed: Vec<PathBuf> = self
+            .files
+            .iter()
+            .filter(|(path, stamp)| earlier.files.get(*path).is_none_or(|before| before.sha256 != stamp.sha256))
+            .map(|(path, _)| path.clone())
+            .chain(earlier.files.keys().filter(|path| !self.files.contains_key(*path)).cloned())
+            .collect();
+        changed.sort();
+        changed
+    }
+}
+
+/// The program `lol watch --serve` keeps running, restarted after each
+/// passing rebuild.
+pub struct Server {
+    interpreter: &'static str,
+    entry: PathBuf,
+    project_path: PathBuf,
+    child: Option<Child>,
+}
+
+impl Server {
+    /// `entry` is relative to `project_path`, where the program runs.
+    pub fn new(interpreter: &'static str, project_path: &Path, entry: PathBuf) -> Self {
+        Self { interpreter, entry, project_path: project_path.to_path_buf(), child: None }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.child.is_some()
+    }
+
+    /// Stops the running instance, if any, and starts a fresh one.
+    pub fn restart(&mut self) -> Result<()> {
+        let restarting = self.is_running();
+        self.stop();
+        let child = Command::new(self.interpreter)
+            .arg(&self.entry)
+            .current_dir(&self.project_path)
+            .spawn()
+            .with_context(|| format!("Failed to start {} {}", self.interpreter, self.entry.display()))?;
+        let verb = if restarting { "Restarted" } else { "Started" };
+        say!("▶️  {} {} (pid {})", verb, self.entry.display().to_string().bold(), child.id());
+        self.child = Some(child);
+        Ok(())
+    }
+
+    pub fn stop(&mut self) {
+        if let Some(mut child) = self.child.take() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+
+    /// Reports the program exiting on its own; it is started again after
+    /// the next passing rebuild.
+    pub fn check_exit(&mut self) {
+        let Some(child) = self.child.as_mut() else {
+            return;
+        };
+        if let Ok(Some(status)) = child.try_wait() {
+            say!("⏹️  {} exited with {}; waiting for changes", self.entry.display(), status);
+            self.child = None;
+        }
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_fingerprint_changes() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let (app, util) = (root.join("app.py"), root.join("util.py"));
+        fs::write(&app, "print('hi')\n").unwrap();
+        fs::write(&util, "X = 1\n").unwrap();
+        let before = Fingerprint::take(root, [&app, &util], &Fingerprint::default());
+        assert!(Fingerprint::take(root, [&app, &util], &before).changes(&before).is_empty());
+
+        // Touched, not edited
+        fs::File::options().write(true).open(&app).unwrap().set_modified(SystemTime::now() + Duration::from_secs(60)).unwrap();
+        assert!(Fingerprint::take(root, [&app, &util], &before).changes(&before).is_empty());
+
+        fs::write(&util, "X = 12\n").unwrap();
+        fs::write(root.join(crate::config::PROJECT_CONFIG_FILE), "{}").unwrap();
+        let after = Fingerprint::take(root, [&app], &before);
+        assert_eq!(after.changes(&before), [root.join(crate::config::PROJECT_CONFIG_FILE), util]);
+    }
+}
@@ -0,0 +1,395 @@
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use anyhow::{Context, Result};
+use colored::*;
+use walkdir::WalkDir;
+use crate::artifacts::ArtifactManifest;
+use crate::compiler::{CompilationResult, CompilationStatus};
+use crate::config::{Config, PROJECT_CONFIG_FILES};
+use crate::entry_point;
+use crate::pipeline;
+
+/// A sub-project of a workspace, built with its own configuration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Member {
+    /// The member's directory relative to the workspace root
+    pub name: String,
+    pub path: PathBuf,
+}
+
+/// The workspace members under `root`, or `None` when `config` does not
+/// declare a workspace.
+pub fn members(config: &Config, root: &Path) -> Result<Option<Vec<Member>>> {
+    let Some(workspace) = &config.workspace else {
+        return Ok(None);
+    };
+
+    if workspace.members.is_empty() {
+        return Ok(Some(discover(config, root)));
+    }
+
+    let mut members = Vec::new();
+    for name in &workspace.members {
+        let path = root.join(name);
+        if !path.is_dir() {
+            anyhow::bail!("Workspace member '{}' is not a directory", name);
+        }
+        members.push(Member { name: name.trim_end_matches('/').to_string(), path });
+    }
+    Ok(Some(members))
+}
+
+/// Finds subdirectories of `root` with their own project config, skipping hidden
+/// directories and the output directory. Members are not searched for
+/// nested members.
+pub fn discover(config: &Config, root: &Path) -> Vec<Member> {
+    let output_dir = config.output_dir(root);
+    let mut members = Vec::new();
+    let mut walker = WalkDir::new(root).min_depth(1).follow_links(true).into_iter();
+
+    while let Some(entry) = walker.next() {
+        let Ok(entry) = entry else {
+            continue;
+        };
+        let path = entry.path();
+        let skipped = entry.file_name().to_string_lossy().starts_with('.')
+            || output_dir.as_ref().is_some_and(|dir| path.starts_with(dir));
+        if !entry.file_type().is_dir() || skipped {
+            if entry.file_type().is_dir() {
+                walker.skip_current_dir();
+            }
+            continue;
+        }
+
+        if PROJECT_CONFIG_FILES.iter().any(|name| path.join(name).is_file()) {
+            let name = path.strip_prefix(root).unwrap_or(path).to_string_lossy().into_owned();
+            members.push(Member { name, path: path.to_path_buf() });
+            walker.skip_current_dir();
+        }
+    }
+
+    members.sort_by(|a, b| a.name.cmp(&b.name));
+    members
+}
+
+/// Orders members so each builds after the members in its config's
+/// `depends_on`, failing on unknown members and cycles.
+pub fn build_order(members: Vec<(Member, Config)>) -> Result<Vec<(Member, Config)>> {
+    let ordered: Vec<String> = pipeline::topological_order(
+        &members,
+        "workspace member",
+        |(member, _)| &member.name,
+        |(_, config)| &config.depends_on,
+    )?
+    .into_iter()
+    .map(|(member, _)| member.name.clone())
+    .collect();
+
+    let mut members = members;
+    let mut sorted = Vec::with_capacity(members.len());
+    for name in ordered {
+        let index = members
+            .iter()
+            .position(|(member, _)| member.name == name)
+            .expect("topological_order returns the members it was given");
+        sorted.push(members.remove(index));
+    }
+    Ok(sorted)
+}
+
+/// What a workspace build should do next with one of its members.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Next {
+    /// Every dependency built; the member at this index can start
+    Build(usize),
+    /// The member at `index` is not built, because `dependency` failed
+    Skip { index: usize, dependency: String },
+}
+
+/// Tracks which members may build, so that members independent of each
+/// other build at the same time and each one waits only for its own
+/// `depends_on`.
+pub struct Schedule {
+    names: Vec<String>,
+    /// Members not started yet, by index, in build order
+    waiting: Vec<(usize, Vec<String>)>,
+    finished: HashSet<String>,
+    failed: Vec<String>,
+}
+
+impl Schedule {
+    /// A schedule for `members`, ordered by [`build_order`].
+    pub fn new(members: &[(Member, Config)]) -> Self {
+        Self {
+            names: members.iter().map(|(member, _)| member.name.clone()).collect(),
+            waiting: members.iter().enumerate().map(|(index, (_, config))| (index, config.depends_on.clone())).collect(),
+            finished: HashSet::new(),
+            failed: Vec::new(),
+        }
+    }
+
+    /// The next member whose dependencies have all finished, if any.
+    pub fn next(&mut self) -> Option<Next> {
+        let position = self
+            .waiting
+            .iter()
+            .position(|(_, depends_on)| depends_on.iter().all(|dependency| self.finished.contains(dependency)))?;
+        let (index, depends_on) = self.waiting.remove(position);
+        match depends_on.into_iter().find(|dependency| self.failed.contains(dependency)) {
+            Some(dependency) => {
+                self.finish(index, false);
+                Some(Next::Skip { index, dependency })
+            }
+            None => Some(Next::Build(index)),
+        }
+    }
+
+    /// Records that the member at `index` is done, so its dependents can start.
+    pub fn finish(&mut self, index: usize, success: bool) {
+        let name = self.names[index].clone();
+        if !success {
+            self.failed.push(name.clone());
+        }
+        self.finished.insert(name);
+    }
+
+    /// The members that failed or were skipped, in the order they finished.
+    pub fn failed(&self) -> &[String] {
+        &self.failed
+    }
+}
+
+/// C-family flags that let a member include headers from the members it
+/// depends on.
+pub fn include_flags<'a>(dependencies: impl IntoIterator<Item = &'a Member>) -> String {
+    dependencies
+        .into_iter()
+        .map(|member| format!("-I{}", member.path.display()))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Archives the C and C++ objects `manifest` lists into
+/// `<output_dir>/lib<name>.a`, for the members depending on `member` to link
+/// against. Members without such objects have no library.
+pub fn archive(member: &Member, manifest: &ArtifactManifest, project_path: &Path, output_dir: &Path) -> Result<Option<PathBuf>> {
+    let objects = entry_point::objects(&manifest.artifacts);
+    if objects.is_empty() {
+        return Ok(None);
+    }
+    let name = Path::new(&member.name).file_name().unwrap_or_default().to_string_lossy();
+    let library = output_dir.join(format!("lib{}.a", name.trim_start_matches("lib")));
+    // `ar r` adds to an existing archive, which may hold objects since removed
+    if library.exists() {
+        std::fs::remove_file(&library).with_context(|| format!("Failed to remove {}", library.display()))?;
+    }
+    let output = Command::new("ar")
+        .arg("rcs")
+        .arg(&library)
+        .args(objects.iter().map(|artifact| project_path.join(&artifact.path)))
+        .output()
+        .context("Failed to run ar")?;
+    if !output.status.success() {
+        anyhow::bail!("Archiving {} failed: {}", library.display(), String::from_utf8_lossy(&output.stderr));
+    }
+    Ok(Some(library))
+}
+
+/// The libraries of everything `config` depends on, directly or through
+/// other members, each before the libraries it needs itself, as static
+/// linking wants.
+pub fn dependency_libraries(config: &Config, members: &[(Member, Config)], libraries: &HashMap<String, PathBuf>) -> Vec<PathBuf> {
+    let mut queue: VecDeque<&String> = config.depends_on.iter().collect();
+    let mut seen = HashSet::new();
+    let mut ordered = Vec::new();
+    while let Some(name) = queue.pop_front() {
+        if !seen.insert(name) {
+            continue;
+        }
+        ordered.extend(libraries.get(name).cloned());
+        if let Some((_, dependency)) = members.iter().find(|(member, _)| &member.name == name) {
+            queue.extend(&dependency.depends_on);
+        }
+    }
+    ordered
+}
+
+/// A member-by-language table of compilation outcomes.
+pub fn results_matrix(builds: &[(Member, Vec<CompilationResult>)]) -> String {
+    let languages: BTreeSet<&str> = builds
+        .iter()
+        .flat_map(|(_, results)| results.iter().map(|result| result.language.name()))
+        .collect();
+
+    let name_width = builds
+        .iter()
+        .map(|(member, _)| member.name.len())
+        .chain(Some("Member".len()))
+        .max()
+        .unwrap_or(0);
+    let widths: Vec<usize> = languages.iter().map(|language| language.len().max(4)).collect();
+
+    let mut table = format!("{:<name_width$}", "Member");
+    for (language, width) in languages.iter().zip(&widths) {
+        table.push_str(&format!("  {:<width$}", language));
+    }
+    table.push('\n');
+
+    for (member, results) in builds {
+        table.push_str(&format!("{:<name_width$}", member.name));
+        for (language, width) in languages.iter().zip(&widths) {
+            let cell = match results.iter().find(|result| result.language.name() == *language) {
+                Some(result) if matches!(result.status, CompilationStatus::Success { .. }) => {
+                    format!("{:<width$}", "ok").green()
+                }
+                Some(CompilationResult { status: CompilationStatus::ToolchainMissing { .. }, .. }) => {
+                    format!("{:<width$}", "missing").yellow()
+                }
+                Some(_) => format!("{:<width$}", "FAIL").red(),
+                None => format!("{:<width$}", "-").dimmed(),
+            };
+            table.push_str(&format!("  {}", cell));
+        }
+        table.push('\n');
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::WorkspaceConfig;
+    use crate::language_support::Language;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_members_are_listed_or_discovered() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        for dir in ["libs/foo", "app", "app/vendor", "build/stale", ".cache"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+            fs::write(root.join(dir).join(crate::config::PROJECT_CONFIG_FILE), "{}").unwrap();
+        }
+
+        let mut config = Config::default();
+        assert_eq!(members(&config, root).unwrap(), None);
+
+        config.workspace = Some(WorkspaceConfig::default());
+        let names: Vec<String> = members(&config, root).unwrap().unwrap().into_iter().map(|m| m.name).collect();
+        assert_eq!(names, ["app", "libs/foo"]);
+
+        config.workspace = Some(WorkspaceConfig { members: vec!["app/".to_string()] });
+        let listed = members(&config, root).unwrap().unwrap();
+        assert_eq!(listed, vec![Member { name: "app".to_string(), path: root.join("app/") }]);
+
+        config.workspace = Some(WorkspaceConfig { members: vec!["missing".to_string()] });
+        assert!(members(&config, root).is_err());
+    }
+
+    #[test]
+    fn test_build_order_follows_depends_on() {
+        let member = |name: &str, depends_on: &[&str]| {
+            let config = Config {
+                depends_on: depends_on.iter().map(|dep| dep.to_string()).collect(),
+                ..Config::default()
+            };
+            (Member { name: name.to_string(), path: PathBuf::from(name) }, config)
+        };
+
+        let ordered = build_order(vec![member("app", &["libfoo"]), member("libfoo", &[]), member("tool", &[])]).unwrap();
+        let names: Vec<&str> = ordered.iter().map(|(member, _)| member.name.as_str()).collect();
+        assert_eq!(names, ["libfoo", "app", "tool"]);
+
+        let error = build_order(vec![member("a", &["b"]), member("b", &["a"])]).unwrap_err();
+        assert_eq!(error.to_string(), "Workspace member dependencies form a cycle between: a, b");
+        let error = build_order(vec![member("app", &["libbar"])]).unwrap_err();
+        assert_eq!(error.to_string(), "Workspace member 'app' depends on unknown workspace member 'libbar'");
+    }
+
+    #[test]
+    fn test_schedule_starts_independent_members_together() {
+        let member = |name: &str, depends_on: &[&str]| {
+            let config = Config {
+                depends_on: depends_on.iter().map(|dep| dep.to_string()).collect(),
+                ..Config::default()
+            };
+            (Member { name: name.to_string(), path: PathBuf::from(name) }, config)
+        };
+        let members = build_order(vec![
+            member("app", &["libfoo", "libbar"]),
+            member("libfoo", &[]),
+            member("libbar", &[]),
+            member("cli", &["app"]),
+        ])
+        .unwrap();
+        let names: Vec<&str> = members.iter().map(|(member, _)| member.name.as_str()).collect();
+        assert_eq!(names, ["libfoo", "libbar", "app", "cli"]);
+
+        let mut schedule = Schedule::new(&members);
+        assert_eq!(schedule.next(), Some(Next::Build(0)));
+        assert_eq!(schedule.next(), Some(Next::Build(1)));
+        assert_eq!(schedule.next(), None);
+        schedule.finish(0, true);
+        assert_eq!(schedule.next(), None);
+        schedule.finish(1, false);
+        assert_eq!(schedule.next(), Some(Next::Skip { index: 2, dependency: "libbar".to_string() }));
+        assert_eq!(schedule.next(), Some(Next::Skip { index: 3, dependency: "app".to_string() }));
+        assert_eq!(schedule.next(), None);
+        assert_eq!(schedule.failed(), ["libbar", "app", "cli"]);
+    }
+
+    #[test]
+    fn test_dependency_libraries_come_before_their_own_dependencies() {
+        let member = |name: &str, depends_on: &[&str]| {
+            let config = Config {
+                depends_on: depends_on.iter().map(|dep| dep.to_string()).collect(),
+                ..Config::default()
+            };
+            (Member { name: name.to_string(), path: PathBuf::from(name) }, config)
+        };
+        let members = vec![member("core", &[]), member("libs/net", &["core"]), member("docs", &[]), member("app", &["libs/net", "docs"])];
+        let libraries = HashMap::from([
+            ("core".to_string(), PathBuf::from("core/build/libcore.a")),
+            ("libs/net".to_string(), PathBuf::from("libs/net/build/libnet.a")),
+        ]);
+        assert_eq!(
+            dependency_libraries(&members[3].1, &members, &libraries),
+            [PathBuf::from("libs/net/build/libnet.a"), PathBuf::from("core/build/libcore.a")]
+        );
+        assert!(dependency_libraries(&members[0].1, &members, &libraries).is_empty());
+    }
+
+    #[test]
+    fn test_results_matrix() {
+        let result = |language, status| CompilationResult {
+            language,
+            files: Vec::new(),
+            status,
+            diagnostics: Vec::new(),
+            toolchain: None,
+            retries: 0,
+            duration_ms: 0,
+        };
+        let builds = vec![
+            (
+                Member { name: "libfoo".to_string(), path: PathBuf::from("libfoo") },
+                vec![result(Language::C, CompilationStatus::Success { output: String::new() })],
+            ),
+            (
+                Member { name: "app".to_string(), path: PathBuf::from("app") },
+                vec![
+                    result(Language::C, CompilationStatus::Failure { error: String::new() }),
+                    result(Language::Python, CompilationStatus::Success { output: String::new() }),
+                ],
+            ),
+        ];
+
+        let ansi = regex::Regex::new("\x1b\\[[0-9;]*m").unwrap();
+        assert_eq!(
+            ansi.replace_all(&results_matrix(&builds), ""),
+            "Member  C     Python\nlibfoo  ok    -     \napp     FAIL  ok    \n"
+        );
+    }
+}
@@ -0,0 +1,69 @@
+mod common;
+
+use std::fs;
+use std::path::Path;
+use assert_cmd::assert::OutputAssertExt;
+use serde_json::Value;
+use tempfile::TempDir;
+use common::lol;
+
+fn recorded_builds(project: &Path) -> usize {
+    fs::read_to_string(project.join(".lol-history.jsonl")).map(|history| history.lines().count()).unwrap_or(0)
+}
+
+#[test]
+fn test_builds_are_recorded_only_when_opted_in() {
+    let home = TempDir::new().unwrap();
+    let project = TempDir::new().unwrap();
+    fs::write(project.path().join("main.c"), "int main(void) { return 0; }\n").unwrap();
+
+    lol(home.path()).arg(project.path()).assert().success();
+    assert_eq!(recorded_builds(project.path()), 0);
+
+    lol(home.path()).args(["analytics", "enable"]).arg(project.path()).assert().success();
+    lol(home.path()).arg(project.path()).assert().success();
+    assert_eq!(recorded_builds(project.path()), 1);
+
+    lol(home.path()).arg("--no-telemetry").arg(project.path()).assert().success();
+    assert_eq!(recorded_builds(project.path()), 1);
+
+    lol(home.path()).args(["analytics", "disable"]).arg(project.path()).assert().success();
+    lol(home.path()).arg(project.path()).assert().success();
+    assert_eq!(recorded_builds(project.path()), 1);
+    let config: Value = serde_json::from_str(&fs::read_to_string(project.path().join("lol.json")).unwrap()).unwrap();
+    assert_eq!(config["history"], false);
+}
+
+#[test]
+fn test_export_writes_the_documented_schema() {
+    let home = TempDir::new().unwrap();
+    let project = TempDir::new().unwrap();
+    fs::write(project.path().join("main.c"), "int main(void) { return 0; }\n").unwrap();
+    lol(home.path()).args(["analytics", "enable"]).arg(project.path()).assert().success();
+    lol(home.path()).arg(project.path()).assert().success();
+
+    let output = lol(home.path()).args(["analytics", "export"]).arg(project.path()).output().unwrap();
+    assert!(output.status.success());
+    let csv = String::from_utf8(output.stdout).unwrap();
+    let rows: Vec<&str> = csv.lines().collect();
+    assert_eq!(
+        rows[0],
+        "id,timestamp,commit,success,duration_ms,language,compiled,failed,errors,warnings,language_duration_ms,retries,missing_toolchain"
+    );
+    assert_eq!(rows.len(), 2);
+    let fields: Vec<&str> = rows[1].split(',').collect();
+    assert_eq!((fields[0], fields[3], fields[5], fields[6], fields[7]), ("1", "true", "C", "1", "0"));
+
+    let exported = project.path().join("history.json");
+    lol(home.path()).args(["analytics", "export", "--format", "json", "--output"]).arg(&exported).arg(project.path()).assert().success();
+    let sessions: Value = serde_json::from_str(&fs::read_to_string(&exported).unwrap()).unwrap();
+    let sessions = sessions.as_array().unwrap();
+    assert_eq!(sessions.len(), 1);
+    assert_eq!(sessions[0]["id"], 1);
+    assert_eq!(sessions[0]["success"], true);
+    assert_eq!(sessions[0]["languages"][0]["language"], "C");
+    assert_eq!(sessions[0]["languages"][0]["compiled"], 1);
+    for field in ["timestamp", "duration_ms"] {
+        assert!(sessions[0][field].is_u64(), "{} is not a number", field);
+    }
+}
@@ -0,0 +1,9 @@
+use std::path::Path;
+use std::process::Command;
+
+/// `lol` with its user configuration kept inside `home`.
+pub fn lol(home: &Path) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_lol"));
+    command.env("HOME", home).env("XDG_CONFIG_HOME", home.join(".config")).env("NO_COLOR", "1");
+    command
+}
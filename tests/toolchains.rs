@@ -0,0 +1,39 @@
+mod common;
+
+use std::fs;
+use std::path::Path;
+use assert_cmd::assert::OutputAssertExt;
+use predicates::str::contains;
+use tempfile::TempDir;
+use common::lol;
+
+/// A C and Perl project whose C compiler is not installed.
+fn project_missing_a_compiler(project: &Path) {
+    fs::write(project.join("main.c"), "int main(void) { return 0; }\n").unwrap();
+    fs::write(project.join("hello.pl"), "print \"hi\\n\";\n").unwrap();
+    fs::write(project.join("lol.json"), r#"{"language_settings": {"c": {"compiler_path": "lol-test-no-such-cc"}}}"#).unwrap();
+}
+
+#[test]
+fn test_missing_compiler_skips_the_language() {
+    let home = TempDir::new().unwrap();
+    let project = TempDir::new().unwrap();
+    project_missing_a_compiler(project.path());
+
+    lol(home.path())
+        .arg(project.path())
+        .assert()
+        .success()
+        .stdout(contains("C: skipped, `lol-test-no-such-cc` is not installed"))
+        .stdout(contains("Perl: 1 files compiled successfully"))
+        .stdout(contains("Skipped: 1 languages without a compiler"));
+}
+
+#[test]
+fn test_strict_toolchains_exits_with_status_3() {
+    let home = TempDir::new().unwrap();
+    let project = TempDir::new().unwrap();
+    project_missing_a_compiler(project.path());
+
+    lol(home.path()).arg("--strict-toolchains").arg(project.path()).assert().code(3);
+}